@@ -27,6 +27,30 @@ pub struct Chip {
     /// [`ChipFamily::flash_algorithms`]: crate::ChipFamily::flash_algorithms
     #[serde(default)]
     pub flash_algorithms: Vec<String>,
+    /// The numbers of the ARM access ports of the chip.
+    ///
+    /// If set, only these APs are used, instead of searching for APs. This is required for
+    /// chips which fault when a non-existent AP is accessed.
+    pub access_ports: Option<Vec<u8>>,
+    /// The probe pins which are connected to the boot mode straps of the chip, such as BOOT0 or
+    /// ISP.
+    ///
+    /// The wiring depends on the board, so this is usually set in the target description of a
+    /// board rather than the one of a chip family.
+    pub boot_mode_pins: Option<BootModePinMasks>,
+}
+
+/// Probe pins connected to the boot mode straps of a chip.
+///
+/// The pins are given as the bits of the CMSIS-DAP `DAP_SWJ_Pins` command, for example `0x20`
+/// for nTRST.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootModePinMasks {
+    /// The pins which are connected to boot mode straps.
+    pub select: u8,
+    /// The levels of the selected pins which select the bootloader of the chip. The inverted
+    /// levels select a normal boot.
+    pub bootloader: u8,
 }
 
 impl Chip {
@@ -44,6 +68,8 @@ impl Chip {
             }],
             memory_map: vec![],
             flash_algorithms: vec![],
+            access_ports: None,
+            boot_mode_pins: None,
         }
     }
 }
@@ -87,8 +113,16 @@ pub struct ArmCoreAccessOptions {
     /// Required in ARMv8-A
     #[serde(serialize_with = "hex_option")]
     pub cti_base: Option<u64>,
+    /// The base address of the performance monitors unit (PMU) for the core.
+    /// Required to use the PMU of Cortex-A cores
+    #[serde(serialize_with = "hex_option")]
+    pub pmu_base: Option<u64>,
 }
 
 /// The data required to access a Risc-V core
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RiscvCoreAccessOptions {}
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RiscvCoreAccessOptions {
+    /// The index of the hart in the debug module, for debug modules with multiple harts.
+    /// Hart 0 is used if this is not set.
+    pub hart_id: Option<u32>,
+}
@@ -0,0 +1,57 @@
+use crate::serialize::hex_u_int;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A way to tell the chips of a family apart when the target is detected automatically.
+///
+/// The chips are named by a part of their name, for example `nRF52840` for `nRF52840_xxAA`,
+/// which is matched ignoring case. The methods of a family narrow down its chips one after the
+/// other, until a single chip is left.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChipDetection {
+    /// The TARGETID register of an Arm debug port, which debug ports of version 2 or later have.
+    ///
+    /// The designer in TARGETID has to match the manufacturer of the family.
+    ArmTargetId {
+        /// The TPARTNO field of TARGETID.
+        #[serde(serialize_with = "hex_u_int")]
+        part: u16,
+        /// The chips with this part number, or all chips of the family if it is empty.
+        #[serde(default)]
+        chips: Vec<String>,
+    },
+    /// A register which identifies the chip, for example the DBGMCU_IDCODE register of STM32
+    /// parts, or the FICR of nRF parts.
+    ///
+    /// The register is only read on targets whose manufacturer matches the family.
+    IdRegister {
+        /// The address of the register, which is read as a 32 bit word.
+        #[serde(serialize_with = "hex_u_int")]
+        address: u64,
+        /// The bits of the register which identify the chip.
+        #[serde(serialize_with = "hex_u_int")]
+        mask: u32,
+        /// The chips, by the masked value of the register. A value which is not listed does
+        /// not belong to a chip of the family.
+        values: BTreeMap<u32, Vec<String>>,
+    },
+}
+
+impl ChipDetection {
+    /// The chip names listed by the method.
+    pub fn chips(&self) -> Vec<&str> {
+        match self {
+            ChipDetection::ArmTargetId { chips, .. } => chips.iter().map(String::as_str).collect(),
+            ChipDetection::IdRegister { values, .. } => {
+                values.values().flatten().map(String::as_str).collect()
+            }
+        }
+    }
+}
+
+/// Returns `true` if the chip `chip_name` is named by `name`, see [`ChipDetection`].
+pub fn chip_name_matches(chip_name: &str, name: &str) -> bool {
+    chip_name
+        .to_ascii_lowercase()
+        .contains(&name.to_ascii_lowercase())
+}
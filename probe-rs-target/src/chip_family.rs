@@ -1,7 +1,10 @@
-use crate::CoreAccessOptions;
+use crate::{CoreAccessOptions, MemoryRegion};
 
 use super::chip::Chip;
+use super::chip_detection::ChipDetection;
 use super::flash_algorithm::RawFlashAlgorithm;
+use super::image_checksum::ImageChecksum;
+use super::unique_id::UniqueIdPart;
 use jep106::JEP106Code;
 
 use serde::{Deserialize, Serialize};
@@ -133,6 +136,17 @@ pub struct ChipFamily {
     pub variants: Vec<Chip>,
     /// This vector holds all available algorithms.
     pub flash_algorithms: Vec<RawFlashAlgorithm>,
+    /// Checksums which have to be stored in images for the chips of this family to boot them.
+    #[serde(default)]
+    pub image_checksums: Vec<ImageChecksum>,
+    /// Where the unique ID of the chips of this family is stored. The parts are read in this
+    /// order, and concatenated.
+    #[serde(default)]
+    pub unique_id: Vec<UniqueIdPart>,
+    /// How the chips of this family are told apart when the target is detected automatically,
+    /// in addition to the [`Chip::part`] numbers in the ROM table.
+    #[serde(default)]
+    pub chip_detection: Vec<ChipDetection>,
     #[serde(skip, default = "default_source")]
     /// Source of the target description, used for diagnostics
     pub source: TargetDescriptionSource,
@@ -147,6 +161,30 @@ impl ChipFamily {
     ///
     /// This method should be called right after the [`ChipFamily`] is created!
     pub fn validate(&self) -> Result<(), String> {
+        if self.manufacturer.is_none()
+            && self
+                .chip_detection
+                .iter()
+                .any(|method| matches!(method, ChipDetection::ArmTargetId { .. }))
+        {
+            return Err("chip detection by TARGETID requires the manufacturer".to_string());
+        }
+
+        for checksum in &self.image_checksums {
+            if let ImageChecksum::Crc32 {
+                start,
+                end: Some(end),
+                ..
+            } = checksum
+            {
+                if end < start {
+                    return Err(format!(
+                        "the CRC range {start:#010x}..{end:#010x} ends before it starts"
+                    ));
+                }
+            }
+        }
+
         // We check each variant if it is valid.
         // If one is not valid, we abort with an appropriate error message.
         for variant in &self.variants {
@@ -225,6 +263,42 @@ impl ChipFamily {
                     }
                 }
             }
+
+            // The sector maps of NVM regions must cover the region exactly.
+            for region in variant.memory_map.iter() {
+                let MemoryRegion::Nvm(region) = region else {
+                    continue;
+                };
+                let name = region.name.as_deref().unwrap_or("unnamed");
+
+                if let Some(first) = region.sectors.first() {
+                    if first.address != 0 || region.sectors.iter().any(|s| s.size == 0) {
+                        return Err(format!(
+                            "the sector map of region `{}` of variant `{}` must start at 0, with sectors larger than 0",
+                            name, variant.name
+                        ));
+                    }
+
+                    if region
+                        .iter_sectors()
+                        .last()
+                        .map(|s| s.base_address + s.size)
+                        != Some(region.range.end)
+                    {
+                        return Err(format!(
+                            "the sector map of region `{}` of variant `{}` does not end at the end of the region",
+                            name, variant.name
+                        ));
+                    }
+                }
+
+                if region.bank_swap.is_some() && (region.range.end - region.range.start) % 2 != 0 {
+                    return Err(format!(
+                        "region `{}` of variant `{}` cannot be split into two banks",
+                        name, variant.name
+                    ));
+                }
+            }
         }
 
         Ok(())
@@ -0,0 +1,41 @@
+use crate::serialize::{hex_option, hex_u_int};
+use serde::{Deserialize, Serialize};
+
+/// A checksum which has to be stored in the image for the chip to boot it.
+///
+/// Some boot ROMs only start an image if it contains a valid checksum, for example the vector
+/// table checksum of NXP LPC parts, or the CRC in the application header of some Analog Devices
+/// parts. probe-rs can compute and patch these when flashing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageChecksum {
+    /// A checksum over the first entries of the vector table.
+    ///
+    /// The word after the covered entries is set to the two's complement of their sum,
+    /// so all of them including the checksum add up to zero.
+    VectorTable {
+        /// The address of the vector table.
+        #[serde(serialize_with = "hex_u_int")]
+        address: u64,
+        /// The number of entries covered by the checksum, 7 for LPC parts.
+        entries: u32,
+    },
+    /// A CRC-32 (IEEE 802.3) over a range of the image, stored in the image as a little endian word.
+    ///
+    /// The length field, if any, is written before the CRC is computed, and the CRC field is
+    /// computed as zero if it lies within the range.
+    Crc32 {
+        /// The start of the checked range.
+        #[serde(serialize_with = "hex_u_int")]
+        start: u64,
+        /// The end of the checked range, or `None` for the end of the image in the memory
+        /// region which contains `start`.
+        #[serde(default, serialize_with = "hex_option")]
+        end: Option<u64>,
+        /// The address at which the CRC is stored.
+        #[serde(serialize_with = "hex_u_int")]
+        crc_address: u64,
+        /// The address at which the length of the checked range is stored as a little endian word.
+        #[serde(default, serialize_with = "hex_option")]
+        length_address: Option<u64>,
+    },
+}
@@ -12,19 +12,27 @@
 //!
 
 mod chip;
+mod chip_detection;
 mod chip_family;
 mod flash_algorithm;
 mod flash_properties;
+mod image_checksum;
 mod memory;
 pub(crate) mod serialize;
+mod unique_id;
 
-pub use chip::{ArmCoreAccessOptions, Chip, Core, CoreAccessOptions, RiscvCoreAccessOptions};
+pub use chip::{
+    ArmCoreAccessOptions, BootModePinMasks, Chip, Core, CoreAccessOptions, RiscvCoreAccessOptions,
+};
+pub use chip_detection::{chip_name_matches, ChipDetection};
 pub use chip_family::{
     Architecture, ChipFamily, CoreType, InstructionSet, TargetDescriptionSource,
 };
 pub use flash_algorithm::RawFlashAlgorithm;
 pub use flash_properties::FlashProperties;
+pub use image_checksum::ImageChecksum;
 pub use memory::{
-    GenericRegion, MemoryRange, MemoryRegion, NvmRegion, PageInfo, RamRegion, SectorDescription,
-    SectorInfo,
+    BankSwap, GenericRegion, MemoryAccess, MemoryRange, MemoryRegion, NvmRegion, PageInfo,
+    RamRegion, SectorDescription, SectorInfo,
 };
+pub use unique_id::UniqueIdPart;
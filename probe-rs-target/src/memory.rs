@@ -2,6 +2,31 @@ use crate::serialize::{hex_range, hex_u_int};
 use core::ops::Range;
 use serde::{Deserialize, Serialize};
 
+/// Access restrictions of a memory region.
+///
+/// Accesses through a core which violate the restrictions are rejected, the default allows all
+/// accesses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryAccess {
+    /// The region must not be written, for example because it is one-time programmable.
+    pub read_only: bool,
+    /// The region must not be read, for example because reads have side effects.
+    pub write_only: bool,
+    /// The region must only be accessed while the core is halted, for example because its
+    /// clock is gated while the application runs.
+    pub no_access_while_running: bool,
+    /// The region can only be accessed if debugging Secure state is allowed.
+    pub requires_secure: bool,
+}
+
+impl MemoryAccess {
+    /// Returns true if the region has no access restrictions.
+    pub fn is_unrestricted(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 /// Represents a region in non-volatile memory (e.g. flash or EEPROM).
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NvmRegion {
@@ -15,9 +40,87 @@ pub struct NvmRegion {
     pub is_boot_memory: bool,
     /// List of cores that can access this region
     pub cores: Vec<String>,
+    /// Access restrictions of the region
+    #[serde(default)]
+    pub access: MemoryAccess,
+    /// The sectors of the region, with addresses relative to the start of the region.
+    ///
+    /// If set, these replace the sectors of the flash algorithm within the region, for flash
+    /// with sector sizes the flash algorithm does not describe.
+    #[serde(default)]
+    pub sectors: Vec<SectorDescription>,
+    /// How the chip swaps the two banks of the region, if it is a dual-bank flash.
+    #[serde(default)]
+    pub bank_swap: Option<BankSwap>,
+}
+
+/// The bank swap of a dual-bank flash region, like the `BFB2` option of STM32L4 parts or the
+/// `SWAP_BANK` option of STM32H7 parts.
+///
+/// The region consists of two banks of equal size. When the banks are swapped, the second bank
+/// is mapped at the start of the region. Reads and writes follow the mapping, but flash
+/// algorithms select the bank to erase from the address, so erase addresses are moved to the
+/// other bank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BankSwap {
+    /// The address of the register which contains the bank swap option.
+    #[serde(serialize_with = "hex_u_int")]
+    pub register: u64,
+    /// The banks are swapped if any of these bits are set in the register.
+    #[serde(serialize_with = "hex_u_int")]
+    pub mask: u32,
+}
+
+impl BankSwap {
+    /// Returns true if `value` of the register says that the banks are swapped.
+    pub fn is_swapped(&self, value: u32) -> bool {
+        value & self.mask != 0
+    }
 }
 
 impl NvmRegion {
+    /// Iterate over the sectors of the sector map of the region.
+    ///
+    /// The iterator is empty if the region has no sector map. The last sector ends at the end
+    /// of the region, or after it if the sector map does not fit the region.
+    pub fn iter_sectors(&self) -> impl Iterator<Item = SectorInfo> + '_ {
+        let mut address = self.range.start;
+        let mut index = 0;
+
+        std::iter::from_fn(move || {
+            if self.sectors.is_empty() || address >= self.range.end {
+                return None;
+            }
+
+            if let Some(next) = self.sectors.get(index + 1) {
+                if self.range.start + next.address <= address {
+                    index += 1;
+                }
+            }
+
+            let size = self.sectors[index].size;
+            let sector = SectorInfo {
+                base_address: address,
+                size,
+            };
+            address += size;
+
+            Some(sector)
+        })
+    }
+
+    /// Returns the address of the other bank of a dual-bank region, at the same offset as
+    /// `address` in its bank.
+    pub fn other_bank_address(&self, address: u64) -> u64 {
+        let bank_size = (self.range.end - self.range.start) / 2;
+
+        if address < self.range.start + bank_size {
+            address + bank_size
+        } else {
+            address - bank_size
+        }
+    }
+
     /// Returns the necessary information about the NVM.
     pub fn nvm_info(&self) -> NvmInfo {
         NvmInfo {
@@ -39,6 +142,9 @@ pub struct RamRegion {
     pub is_boot_memory: bool,
     /// List of cores that can access this region
     pub cores: Vec<String>,
+    /// Access restrictions of the region
+    #[serde(default)]
+    pub access: MemoryAccess,
 }
 
 /// Represents a generic region.
@@ -51,6 +157,9 @@ pub struct GenericRegion {
     pub range: Range<u64>,
     /// List of cores that can access this region
     pub cores: Vec<String>,
+    /// Access restrictions of the region
+    #[serde(default)]
+    pub access: MemoryAccess,
 }
 
 /// Holds information about a specific, individual flash
@@ -142,6 +251,44 @@ pub enum MemoryRegion {
     Nvm(NvmRegion),
 }
 
+impl MemoryRegion {
+    /// The name of the region.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            MemoryRegion::Ram(region) => region.name.as_deref(),
+            MemoryRegion::Generic(region) => region.name.as_deref(),
+            MemoryRegion::Nvm(region) => region.name.as_deref(),
+        }
+    }
+
+    /// The address range of the region.
+    pub fn address_range(&self) -> &Range<u64> {
+        match self {
+            MemoryRegion::Ram(region) => &region.range,
+            MemoryRegion::Generic(region) => &region.range,
+            MemoryRegion::Nvm(region) => &region.range,
+        }
+    }
+
+    /// The names of the cores which can access the region.
+    pub fn cores(&self) -> &[String] {
+        match self {
+            MemoryRegion::Ram(region) => &region.cores,
+            MemoryRegion::Generic(region) => &region.cores,
+            MemoryRegion::Nvm(region) => &region.cores,
+        }
+    }
+
+    /// The access restrictions of the region.
+    pub fn access(&self) -> &MemoryAccess {
+        match self {
+            MemoryRegion::Ram(region) => &region.access,
+            MemoryRegion::Generic(region) => &region.access,
+            MemoryRegion::Nvm(region) => &region.access,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -250,4 +397,40 @@ mod test {
         let range2 = 6..8;
         assert!(!range1.intersects_range(&range2));
     }
+
+    #[test]
+    fn stm32f4_sector_map() {
+        // The first bank of an STM32F42x: four 16 KiB, one 64 KiB and seven 128 KiB sectors.
+        let region = NvmRegion {
+            name: None,
+            range: 0x0800_0000..0x0810_0000,
+            is_boot_memory: true,
+            cores: vec![],
+            access: Default::default(),
+            sectors: vec![
+                SectorDescription {
+                    size: 0x4000,
+                    address: 0,
+                },
+                SectorDescription {
+                    size: 0x1_0000,
+                    address: 0x1_0000,
+                },
+                SectorDescription {
+                    size: 0x2_0000,
+                    address: 0x2_0000,
+                },
+            ],
+            bank_swap: None,
+        };
+
+        let sectors: Vec<_> = region.iter_sectors().map(|s| s.base_address).collect();
+        assert_eq!(sectors.len(), 12);
+        assert_eq!(sectors[4], 0x0801_0000);
+        assert_eq!(sectors[5], 0x0802_0000);
+        assert_eq!(sectors[11], 0x080E_0000);
+
+        assert_eq!(region.other_bank_address(0x0800_4000), 0x0808_4000);
+        assert_eq!(region.other_bank_address(0x080A_0000), 0x0802_0000);
+    }
 }
@@ -0,0 +1,16 @@
+use crate::serialize::hex_u_int;
+use serde::{Deserialize, Serialize};
+
+/// A part of the unique ID which the vendor programs into every chip.
+///
+/// Most chips store the ID in one block of memory, for example the 96 bit U_ID of STM32 parts.
+/// Others spread it over several registers, like the serial number of SAM D parts, which is
+/// described with one part per register.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UniqueIdPart {
+    /// The address of the part.
+    #[serde(serialize_with = "hex_u_int")]
+    pub address: u64,
+    /// The size of the part in bytes.
+    pub size: u32,
+}
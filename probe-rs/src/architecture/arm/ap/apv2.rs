@@ -0,0 +1,178 @@
+//! Access ports of ADIv6 debug ports.
+//!
+//! A DPv3 debug port does not number its APs. Instead, each AP is a 4 KiB component in the
+//! debug address space of the debug port, and the APs are listed in ROM tables starting at the
+//! address in BASEPTR0. The registers of an APv2 are at the same offsets as in ADIv5, plus
+//! [`APV2_REGISTER_OFFSET`].
+
+use crate::architecture::arm::ArmError;
+
+/// The offset of the ADIv5 compatible registers, CSW to IDR, in the 4 KiB block of an APv2.
+pub(crate) const APV2_REGISTER_OFFSET: u64 = 0xD00;
+
+/// How deep nested ROM tables are followed, to stop at tables which point to themselves.
+const MAX_ROM_TABLE_DEPTH: usize = 8;
+
+const CIDR1: u64 = 0xFF4;
+const DEVARCH: u64 = 0xFBC;
+const DEVID: u64 = 0xFC8;
+
+const CLASS_ROM_TABLE: u32 = 0x1;
+const CLASS_CORESIGHT: u32 = 0x9;
+
+/// The architecture ID of a Class 0x9 ROM table in DEVARCH.
+const ROM_TABLE_ARCH_ID: u32 = 0x0AF7;
+
+/// Find the base addresses of all APs in the ROM tables behind `base_address`, with `read`
+/// reading a word of the debug address space.
+///
+/// `base_address` is the value of BASEPTR0 and BASEPTR1. It can also point to an AP directly,
+/// if the debug port has only one.
+pub(crate) fn find_apv2_access_ports(
+    base_address: u64,
+    mut read: impl FnMut(u64) -> Result<u32, ArmError>,
+) -> Result<Vec<u64>, ArmError> {
+    let mut access_ports = Vec::new();
+    visit_component(base_address, 0, &mut read, &mut access_ports)?;
+    Ok(access_ports)
+}
+
+fn visit_component(
+    base_address: u64,
+    depth: usize,
+    read: &mut impl FnMut(u64) -> Result<u32, ArmError>,
+    access_ports: &mut Vec<u64>,
+) -> Result<(), ArmError> {
+    let class = (read(base_address + CIDR1)? >> 4) & 0xF;
+
+    let entries = match class {
+        CLASS_ROM_TABLE => read_class1_entries(base_address, read)?,
+        CLASS_CORESIGHT if read(base_address + DEVARCH)? & 0xFFFF == ROM_TABLE_ARCH_ID => {
+            read_class9_entries(base_address, read)?
+        }
+        CLASS_CORESIGHT => {
+            tracing::debug!("Found APv2 at {:#x}", base_address);
+            if !access_ports.contains(&base_address) {
+                access_ports.push(base_address);
+            }
+            return Ok(());
+        }
+        class => {
+            tracing::debug!(
+                "Ignoring component of class {:#x} at {:#x}",
+                class,
+                base_address
+            );
+            return Ok(());
+        }
+    };
+
+    if depth >= MAX_ROM_TABLE_DEPTH {
+        tracing::warn!(
+            "Not following the ROM table at {:#x}, it is nested too deeply",
+            base_address
+        );
+        return Ok(());
+    }
+
+    for offset in entries {
+        visit_component(
+            base_address.wrapping_add_signed(offset),
+            depth + 1,
+            read,
+            access_ports,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The offsets of the present entries of a Class 0x1 ROM table.
+fn read_class1_entries(
+    base_address: u64,
+    read: &mut impl FnMut(u64) -> Result<u32, ArmError>,
+) -> Result<Vec<i64>, ArmError> {
+    let mut offsets = Vec::new();
+
+    for entry_offset in (0..=0xEFC).step_by(4) {
+        let entry = read(base_address + entry_offset)?;
+        // The end of the table is marked by an all zero entry.
+        if entry == 0 {
+            break;
+        }
+        if entry & 1 == 1 {
+            offsets.push((entry & 0xFFFF_F000) as i32 as i64);
+        }
+    }
+
+    Ok(offsets)
+}
+
+/// The offsets of the present entries of a Class 0x9 ROM table, with 32 or 64 bit entries.
+fn read_class9_entries(
+    base_address: u64,
+    read: &mut impl FnMut(u64) -> Result<u32, ArmError>,
+) -> Result<Vec<i64>, ArmError> {
+    let wide = read(base_address + DEVID)? & 0xF == 1;
+    let entry_size = if wide { 8 } else { 4 };
+
+    let mut offsets = Vec::new();
+
+    for entry_offset in (0..=0x7FC).step_by(entry_size) {
+        let low = read(base_address + entry_offset)?;
+        // The PRESENT field is 0b00 at the end of the table, and 0b10 for entries which are not
+        // present, but are followed by more entries.
+        match low & 0b11 {
+            0b00 => break,
+            0b11 => {}
+            _ => continue,
+        }
+
+        let offset = if wide {
+            let high = read(base_address + entry_offset + 4)?;
+            ((u64::from(high) << 32) | u64::from(low & 0xFFFF_F000)) as i64
+        } else {
+            (low & 0xFFFF_F000) as i32 as i64
+        };
+        offsets.push(offset);
+    }
+
+    Ok(offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_apv2_access_ports;
+    use crate::architecture::arm::ArmError;
+    use std::collections::HashMap;
+
+    #[test]
+    fn nested_rom_tables() {
+        let mut words = HashMap::new();
+
+        // A Class 0x9 root table, with an AP, an entry which is not present, and a nested table.
+        words.insert(0x0FF4, 0x90);
+        words.insert(0x0FBC, (1 << 20) | 0x0AF7);
+        words.insert(0x0000, 0x2003);
+        words.insert(0x0004, 0x0002);
+        words.insert(0x0008, 0x8003);
+
+        // A Class 0x1 table, which lists an AP below it, and the first AP again.
+        words.insert(0x8FF4, 0x10);
+        words.insert(0x8000, 0xFFFF_F001);
+        words.insert(0x8004, 0xFFFF_A001);
+
+        // A MEM-AP and a JTAG-AP.
+        words.insert(0x2FF4, 0x90);
+        words.insert(0x2FBC, (1 << 20) | 0x0A17);
+        words.insert(0x7FF4, 0x90);
+        words.insert(0x7FBC, (1 << 20) | 0x0A27);
+
+        let access_ports = find_apv2_access_ports(0, |address| {
+            Ok::<_, ArmError>(words.get(&address).copied().unwrap_or(0))
+        })
+        .unwrap();
+
+        assert_eq!(access_ports, [0x2000, 0x7000]);
+    }
+}
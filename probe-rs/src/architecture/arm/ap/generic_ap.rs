@@ -1,7 +1,6 @@
 //! Generic access port
 
-use super::{AccessPort, ApRegister, Register};
-use crate::architecture::arm::{communication_interface::RegisterParseError, ApAddress};
+use crate::architecture::arm::communication_interface::RegisterParseError;
 use enum_primitive_derive::Primitive;
 use num_traits::cast::{FromPrimitive, ToPrimitive};
 
@@ -3,8 +3,8 @@
 #[doc(hidden)]
 pub(crate) mod mock;
 
-use super::{AccessPort, ApAccess, ApRegister, GenericAp, Register};
-use crate::architecture::arm::{communication_interface::RegisterParseError, ApAddress, ArmError};
+use super::{AccessPort, ApAccess, ApRegister, GenericAp};
+use crate::architecture::arm::{communication_interface::RegisterParseError, ArmError};
 use enum_primitive_derive::Primitive;
 use num_traits::{FromPrimitive, ToPrimitive};
 
@@ -2,8 +2,10 @@
 
 #[macro_use]
 pub mod register_generation;
+pub(crate) mod apv2;
 pub(crate) mod generic_ap;
 pub(crate) mod memory_ap;
+pub mod vendor;
 
 use crate::architecture::arm::dp::DebugPortError;
 use crate::DebugProbeError;
@@ -12,10 +14,12 @@ pub use generic_ap::{ApClass, ApType, GenericAp, IDR};
 pub use memory_ap::{
     AddressIncrement, BaseaddrFormat, DataSize, MemoryAp, BASE, BASE2, CFG, CSW, DRW, TAR, TAR2,
 };
+pub use vendor::VendorAp;
 
 use super::{
-    communication_interface::RegisterParseError, ApAddress, ArmError, DapAccess, DpAddress,
-    Register,
+    communication_interface::RegisterParseError,
+    dp::{Abort, Ctrl, DpAccess},
+    ApAddress, ArmError, DapAccess, DpAddress, Register,
 };
 
 /// Some error during AP handling occurred.
@@ -81,7 +85,8 @@ impl AccessPortError {
 
 /// A trait to be implemented by access port register types.
 ///
-/// Use the [`define_ap_register!`] macro to implement this.
+/// Use the [`define_ap_register!`] macro to implement this. Registers which are shared by
+/// several access ports, like [`IDR`], implement it once for every access port type.
 pub trait ApRegister<PORT: AccessPort>: Register + Sized {}
 
 /// A trait to be implemented on access port types.
@@ -135,7 +140,7 @@ pub trait ApAccess {
         R: ApRegister<PORT>;
 }
 
-impl<T: DapAccess> ApAccess for T {
+impl<T: DapAccess + ?Sized> ApAccess for T {
     #[tracing::instrument(skip(self, port), fields(ap = port.ap_address().ap, register = R::NAME, value))]
     fn read_ap_register<PORT, R>(&mut self, port: PORT) -> Result<R, ArmError>
     where
@@ -226,17 +231,109 @@ where
     }
 }
 
-/// Return a Vec of all valid access ports found that the target connected to the debug_probe.
-/// Can fail silently under the hood testing an ap that doesnt exist and would require cleanup.
+/// When the search for access ports by [`valid_access_ports`] stops before the
+/// [limit](ApScanOptions::limit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApScanStop {
+    /// Stop at the first AP which does not exist, as APs are usually numbered consecutively.
+    #[default]
+    FirstInvalid,
+    /// Stop after the given number of consecutive APs which do not exist, to find APs after a
+    /// gap in the numbering.
+    ConsecutiveInvalid(u8),
+    /// Check all APs up to the limit.
+    Never,
+}
+
+/// How the access ports of a debug port are found.
+///
+/// By default, the APs are numbered consecutively from 0, and checked until the first one
+/// which does not exist. Checking many APs is slow on some probes, and some targets fault or
+/// stop responding when a non-existent AP is accessed, so the APs can also be listed in the
+/// target description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApScanOptions {
+    /// The highest AP number which is checked.
+    pub limit: u8,
+    /// When the search stops before the limit.
+    pub stop: ApScanStop,
+    /// The numbers of the APs of the target. If set, these APs are used without checking
+    /// whether they exist, and no other APs are accessed.
+    pub access_ports: Option<Vec<u8>>,
+}
+
+impl Default for ApScanOptions {
+    fn default() -> Self {
+        Self {
+            limit: u8::MAX,
+            stop: ApScanStop::default(),
+            access_ports: None,
+        }
+    }
+}
+
+/// Return a Vec of all valid access ports found that the target connected to the debug_probe,
+/// searched as configured by `options`.
+///
+/// APs which fault when their IDR is read are treated as non-existent, and the sticky error
+/// flags of the debug port are cleared before the search continues.
 #[tracing::instrument(skip(debug_port))]
-pub(crate) fn valid_access_ports<AP>(debug_port: &mut AP, dp: DpAddress) -> Vec<GenericAp>
+pub(crate) fn valid_access_ports<AP>(
+    debug_port: &mut AP,
+    dp: DpAddress,
+    options: &ApScanOptions,
+) -> Vec<GenericAp>
 where
-    AP: ApAccess,
+    AP: ApAccess + DpAccess,
 {
-    (0..=255)
-        .map(|ap| GenericAp::new(ApAddress { dp, ap }))
-        .take_while(|port| access_port_is_valid(debug_port, *port))
-        .collect::<Vec<GenericAp>>()
+    let to_port = |ap| GenericAp::new(ApAddress { dp, ap });
+
+    if let Some(access_ports) = &options.access_ports {
+        return access_ports.iter().copied().map(to_port).collect();
+    }
+
+    let mut ports = Vec::new();
+    let mut consecutive_invalid = 0;
+
+    for port in (0..=options.limit).map(to_port) {
+        if access_port_is_valid(debug_port, port) {
+            ports.push(port);
+            consecutive_invalid = 0;
+            continue;
+        }
+
+        clear_sticky_errors(debug_port, dp);
+        consecutive_invalid += 1;
+
+        let stop = match options.stop {
+            ApScanStop::FirstInvalid => true,
+            ApScanStop::ConsecutiveInvalid(count) => consecutive_invalid >= count,
+            ApScanStop::Never => false,
+        };
+        if stop {
+            break;
+        }
+    }
+
+    ports
+}
+
+/// Clear the sticky error flags of `dp` after a faulted access, for example of a non-existent AP,
+/// so later accesses do not fail.
+pub(crate) fn clear_sticky_errors<AP: DpAccess + ?Sized>(debug_port: &mut AP, dp: DpAddress) {
+    let result = debug_port.read_dp_register(dp).and_then(|ctrl: Ctrl| {
+        if ctrl.sticky_err() {
+            tracing::trace!("Access faulted. Cleaning up");
+            let mut abort = Abort::default();
+            abort.set_stkerrclr(true);
+            debug_port.write_dp_register(dp, abort)?;
+        }
+        Ok(())
+    });
+
+    if let Err(e) = result {
+        tracing::debug!("Failed to clear sticky errors: {}", e);
+    }
 }
 
 /// Tries to find the first AP with the given idr value, returns `None` if there isn't any
@@ -255,3 +352,159 @@ where
             }
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        valid_access_ports, AccessPort, ApAccess, ApRegister, ApScanOptions, ApScanStop, ArmError,
+        DpAddress,
+    };
+    use crate::architecture::arm::dp::{DpAccess, DpRegister};
+
+    /// An IDR of an AHB-AP.
+    const IDR: u32 = 0x2477_0011;
+
+    /// A debug port with APs at the given numbers, which faults when other APs are accessed.
+    struct ScanMock {
+        access_ports: Vec<u8>,
+        sticky_err: bool,
+    }
+
+    impl ScanMock {
+        fn new(access_ports: &[u8]) -> Self {
+            Self {
+                access_ports: access_ports.to_vec(),
+                sticky_err: false,
+            }
+        }
+    }
+
+    impl ApAccess for ScanMock {
+        fn read_ap_register<PORT, R>(&mut self, port: PORT) -> Result<R, ArmError>
+        where
+            PORT: AccessPort,
+            R: ApRegister<PORT>,
+        {
+            let address = port.ap_address();
+
+            if self.sticky_err {
+                Err(ArmError::Timeout)
+            } else if self.access_ports.contains(&address.ap) {
+                Ok(R::try_from(IDR).unwrap())
+            } else {
+                self.sticky_err = true;
+                Err(ArmError::ApDoesNotExist(address))
+            }
+        }
+
+        fn read_ap_register_repeated<PORT, R>(
+            &mut self,
+            _port: impl Into<PORT> + Clone,
+            _register: R,
+            _values: &mut [u32],
+        ) -> Result<(), ArmError>
+        where
+            PORT: AccessPort,
+            R: ApRegister<PORT>,
+        {
+            unimplemented!()
+        }
+
+        fn write_ap_register<PORT, R>(
+            &mut self,
+            _port: impl Into<PORT>,
+            _register: R,
+        ) -> Result<(), ArmError>
+        where
+            PORT: AccessPort,
+            R: ApRegister<PORT>,
+        {
+            unimplemented!()
+        }
+
+        fn write_ap_register_repeated<PORT, R>(
+            &mut self,
+            _port: impl Into<PORT> + Clone,
+            _register: R,
+            _values: &[u32],
+        ) -> Result<(), ArmError>
+        where
+            PORT: AccessPort,
+            R: ApRegister<PORT>,
+        {
+            unimplemented!()
+        }
+    }
+
+    impl DpAccess for ScanMock {
+        fn read_dp_register<R: DpRegister>(&mut self, _dp: DpAddress) -> Result<R, ArmError> {
+            // CTRL/STAT.STICKYERR
+            let ctrl = if self.sticky_err { 1 << 5 } else { 0 };
+            Ok(R::try_from(ctrl).unwrap())
+        }
+
+        fn write_dp_register<R: DpRegister>(
+            &mut self,
+            _dp: DpAddress,
+            register: R,
+        ) -> Result<(), ArmError> {
+            // ABORT.STKERRCLR
+            if register.into() & (1 << 2) != 0 {
+                self.sticky_err = false;
+            }
+            Ok(())
+        }
+    }
+
+    fn scan(mock: &mut ScanMock, options: &ApScanOptions) -> Vec<u8> {
+        valid_access_ports(mock, DpAddress::Default, options)
+            .iter()
+            .map(|port| port.ap_address().ap)
+            .collect()
+    }
+
+    #[test]
+    fn scan_stops_at_first_invalid_ap() {
+        let mut mock = ScanMock::new(&[0, 1, 3]);
+
+        assert_eq!(scan(&mut mock, &ApScanOptions::default()), vec![0, 1]);
+    }
+
+    #[test]
+    fn scan_continues_after_faulting_aps() {
+        let mut mock = ScanMock::new(&[0, 1, 3, 8]);
+        let options = ApScanOptions {
+            stop: ApScanStop::ConsecutiveInvalid(4),
+            ..Default::default()
+        };
+
+        assert_eq!(scan(&mut mock, &options), vec![0, 1, 3]);
+
+        let options = ApScanOptions {
+            stop: ApScanStop::ConsecutiveInvalid(5),
+            ..Default::default()
+        };
+
+        assert_eq!(scan(&mut mock, &options), vec![0, 1, 3, 8]);
+
+        let options = ApScanOptions {
+            limit: 4,
+            stop: ApScanStop::Never,
+            ..Default::default()
+        };
+
+        assert_eq!(scan(&mut mock, &options), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn listed_aps_are_not_scanned() {
+        let mut mock = ScanMock::new(&[]);
+        let options = ApScanOptions {
+            access_ports: Some(vec![0, 2]),
+            ..Default::default()
+        };
+
+        assert_eq!(scan(&mut mock, &options), vec![0, 2]);
+        assert!(!mock.sticky_err);
+    }
+}
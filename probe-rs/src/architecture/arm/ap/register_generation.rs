@@ -1,5 +1,40 @@
 //! Helper macros to implement an access port
 //!
+//! The macros can also be used outside of probe-rs, to define access ports and registers
+//! which are not known to probe-rs. They only refer to items by their full path, so no
+//! imports are required.
+//!
+//! ```no_run
+//! use probe_rs::architecture::arm::ap::ApAccess;
+//! use probe_rs::{define_ap, define_ap_register};
+//! use probe_rs::architecture::arm::{ApAddress, DpAddress};
+//! # use probe_rs::{Permissions, Session};
+//!
+//! define_ap!(
+//!     /// A vendor specific access port.
+//!     CustomAp
+//! );
+//!
+//! define_ap_register!(
+//!     type: CustomAp,
+//!     /// The status register of the vendor specific access port.
+//!     name: STATUS,
+//!     address: 0x08,
+//!     fields: [
+//!         /// The device is locked.
+//!         LOCKED: bool,
+//!     ],
+//!     from: value => Ok(STATUS { LOCKED: value & 0x1 != 0 }),
+//!     to: value => u32::from(value.LOCKED)
+//! );
+//!
+//! # let mut session = Session::auto_attach("nrf52840_xxAA", Permissions::default())?;
+//! let interface = session.get_arm_interface()?;
+//! let ap = CustomAp::new(ApAddress { dp: DpAddress::Default, ap: 1 });
+//!
+//! let status: STATUS = interface.read_ap_register(ap)?;
+//! # Ok::<(), probe_rs::Error>(())
+//! ```
 
 /// Defines a new typed access port register for a specific access port.
 /// Takes
@@ -29,14 +64,14 @@ macro_rules! define_ap_register {
             $($(#[$inner])*pub $field: $type,)*
         }
 
-        impl Register for $name {
+        impl $crate::architecture::arm::Register for $name {
             // ADDRESS is always the lower 4 bits of the register address.
             const ADDRESS: u8 = $address;
             const NAME: &'static str = stringify!($name);
         }
 
         impl TryFrom<u32> for $name {
-            type Error = $crate::architecture::arm::communication_interface::RegisterParseError;
+            type Error = $crate::architecture::arm::RegisterParseError;
 
             fn try_from($from_param: u32) -> Result<$name, Self::Error> {
                 $from
@@ -49,7 +84,7 @@ macro_rules! define_ap_register {
             }
         }
 
-        impl ApRegister<$port_type> for $name {
+        impl $crate::architecture::arm::ap::ApRegister<$port_type> for $name {
         }
     }
 }
@@ -64,18 +99,18 @@ macro_rules! define_ap {
         $(#[$outer])*
         #[derive(Clone, Copy, Debug)]
         pub struct $name {
-            address: ApAddress,
+            address: $crate::architecture::arm::ApAddress,
         }
 
         impl $name {
             #[doc = concat!("Creates a new ", stringify!($name), " with `address` as base address.")]
-            pub const fn new(address: ApAddress) -> Self {
+            pub const fn new(address: $crate::architecture::arm::ApAddress) -> Self {
                 Self { address }
             }
         }
 
-        impl AccessPort for $name {
-            fn ap_address(&self) -> ApAddress {
+        impl $crate::architecture::arm::ap::AccessPort for $name {
+            fn ap_address(&self) -> $crate::architecture::arm::ApAddress {
                 self.address
             }
         }
@@ -0,0 +1,201 @@
+//! Measurement of the core clock frequency of Cortex-M cores.
+//!
+//! The SWO baud rate, timeouts and the conversion of cycle counts to time depend on the core
+//! clock, which is often only guessed. [`measure_core_clock`] measures it instead, by comparing
+//! the cycles counted by the target during an interval with the host time.
+//!
+//! The cycle counter of the DWT is used if the core has one, otherwise SysTick, if it is not
+//! used by the application. Both only count while the core is not sleeping, so the core should
+//! be busy during the measurement: an application waiting in `WFI` gives a too low frequency.
+
+use std::time::{Duration, Instant};
+
+use crate::architecture::arm::core::armv6m::Demcr;
+use crate::architecture::arm::ArmError;
+use crate::{Core, Error, MemoryInterface, MemoryMappedRegister};
+
+/// DWT control register.
+const DWT_CTRL: u64 = 0xE000_1000;
+/// DWT cycle counter.
+const DWT_CYCCNT: u64 = 0xE000_1004;
+/// DWT_CTRL.CYCCNTENA, enables the cycle counter.
+const DWT_CTRL_CYCCNTENA: u32 = 1 << 0;
+/// DWT_CTRL.NOCYCCNT, set if there is no cycle counter.
+const DWT_CTRL_NOCYCCNT: u32 = 1 << 25;
+
+/// SysTick control and status register.
+const SYST_CSR: u64 = 0xE000_E010;
+/// SysTick reload value register.
+const SYST_RVR: u64 = 0xE000_E014;
+/// SysTick current value register.
+const SYST_CVR: u64 = 0xE000_E018;
+/// SYST_CSR.ENABLE and SYST_CSR.CLKSOURCE, count the processor clock without interrupts.
+const SYST_CSR_ENABLE_PROCESSOR_CLOCK: u32 = 0b101;
+/// The largest reload value of SysTick, which is a 24 bit counter.
+const SYST_MAX_RELOAD: u32 = 0x00FF_FFFF;
+
+/// The time between two reads of the counter. SysTick wraps after 2^24 cycles, so it has to be
+/// read more often than that, which this allows for clocks of several hundred MHz.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The counter used to measure the core clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockCounter {
+    /// The cycle counter of the DWT.
+    Cyccnt,
+    /// The SysTick timer, counting the processor clock.
+    SysTick,
+}
+
+/// The result of [`measure_core_clock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockMeasurement {
+    /// The measured core clock frequency in Hz.
+    pub frequency: u32,
+    /// The counter used for the measurement.
+    pub counter: ClockCounter,
+    /// The number of cycles counted.
+    pub cycles: u64,
+    /// The host time in which the cycles were counted.
+    pub elapsed: Duration,
+}
+
+/// Measure the core clock of the running `core` for about `duration`.
+///
+/// The counters are enabled if necessary, and restored to their previous state afterwards.
+/// The accuracy is limited by the latency of the probe, so longer durations give more accurate
+/// results.
+pub fn measure_core_clock(core: &mut Core, duration: Duration) -> Result<ClockMeasurement, Error> {
+    if !core.core_type().is_cortex_m() {
+        return Err(ArmError::ArchitectureRequired(&["ARMv6-M", "ARMv7-M", "ARMv8-M"]).into());
+    }
+
+    if core.core_halted()? {
+        return Err(Error::GenericCoreError(
+            "The core has to be running to measure its clock".to_owned(),
+        ));
+    }
+
+    let demcr = Demcr(core.read_word_32(Demcr::get_mmio_address())?);
+    let mut enabled_demcr = demcr;
+    enabled_demcr.set_dwtena(true);
+    core.write_word_32(Demcr::get_mmio_address(), enabled_demcr.into())?;
+
+    let dwt_ctrl = core.read_word_32(DWT_CTRL)?;
+
+    let result = if dwt_ctrl & DWT_CTRL_NOCYCCNT == 0 {
+        core.write_word_32(DWT_CTRL, dwt_ctrl | DWT_CTRL_CYCCNTENA)?;
+
+        let result = measure(core, duration, ClockCounter::Cyccnt, |core, previous| {
+            let current = core.read_word_32(DWT_CYCCNT)?;
+            Ok((current, current.wrapping_sub(previous)))
+        });
+
+        core.write_word_32(DWT_CTRL, dwt_ctrl)?;
+        result
+    } else {
+        measure_with_systick(core, duration)
+    };
+
+    core.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
+
+    result
+}
+
+fn measure_with_systick(core: &mut Core, duration: Duration) -> Result<ClockMeasurement, Error> {
+    let csr = core.read_word_32(SYST_CSR)?;
+    if csr & 1 != 0 {
+        return Err(Error::GenericCoreError(
+            "The core has no cycle counter, and SysTick is used by the application".to_owned(),
+        ));
+    }
+
+    let rvr = core.read_word_32(SYST_RVR)?;
+    core.write_word_32(SYST_RVR, SYST_MAX_RELOAD)?;
+    // Any write clears the current value.
+    core.write_word_32(SYST_CVR, 0)?;
+    core.write_word_32(SYST_CSR, SYST_CSR_ENABLE_PROCESSOR_CLOCK)?;
+
+    let result = measure(core, duration, ClockCounter::SysTick, |core, previous| {
+        let current = core.read_word_32(SYST_CVR)?;
+        Ok((current, systick_cycles(previous, current, SYST_MAX_RELOAD)))
+    });
+
+    core.write_word_32(SYST_CSR, csr)?;
+    core.write_word_32(SYST_RVR, rvr)?;
+
+    result
+}
+
+/// Count cycles for `duration` by polling `read`, which returns the current counter value and
+/// the cycles since the `previous` value.
+fn measure(
+    core: &mut Core,
+    duration: Duration,
+    counter: ClockCounter,
+    read: impl Fn(&mut Core, u32) -> Result<(u32, u32), Error>,
+) -> Result<ClockMeasurement, Error> {
+    // The host time of a read is taken in the middle of the read, to compensate the latency.
+    let timed_read = |core: &mut Core, previous| {
+        let before = Instant::now();
+        let value = read(core, previous)?;
+        Ok::<_, Error>((value, before + before.elapsed() / 2))
+    };
+
+    let ((mut value, _), start) = timed_read(core, 0)?;
+    let mut cycles = 0u64;
+    let mut end = start;
+
+    while end.duration_since(start) < duration {
+        crate::cancellation::check()?;
+        std::thread::sleep(POLL_INTERVAL.min(duration.saturating_sub(start.elapsed())));
+
+        let ((current, delta), time) = timed_read(core, value)?;
+        value = current;
+        cycles += delta as u64;
+        end = time;
+    }
+
+    let elapsed = end.duration_since(start);
+
+    Ok(ClockMeasurement {
+        frequency: frequency(cycles, elapsed),
+        counter,
+        cycles,
+        elapsed,
+    })
+}
+
+/// The cycles between two values of the SysTick down counter with reload value `reload`.
+fn systick_cycles(previous: u32, current: u32, reload: u32) -> u32 {
+    if current <= previous {
+        previous - current
+    } else {
+        previous + (reload + 1) - current
+    }
+}
+
+/// The frequency in Hz of `cycles` counted in `elapsed`.
+fn frequency(cycles: u64, elapsed: Duration) -> u32 {
+    let nanos = elapsed.as_nanos().max(1);
+
+    (cycles as u128 * 1_000_000_000 / nanos) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frequency, systick_cycles};
+    use std::time::Duration;
+
+    #[test]
+    fn systick_wraps_at_reload_value() {
+        assert_eq!(systick_cycles(1000, 400, 0xFF_FFFF), 600);
+        assert_eq!(systick_cycles(100, 0xFF_FF00, 0xFF_FFFF), 100 + 0x100);
+    }
+
+    #[test]
+    fn frequency_from_cycles() {
+        assert_eq!(frequency(6_400_000, Duration::from_millis(100)), 64_000_000);
+        assert_eq!(frequency(0, Duration::ZERO), 0);
+    }
+}
@@ -1,19 +1,30 @@
 use super::{
     ap::{
-        valid_access_ports, AccessPort, ApAccess, ApClass, BaseaddrFormat, GenericAp, MemoryAp,
-        BASE, BASE2, CFG, CSW, IDR,
+        apv2::{find_apv2_access_ports, APV2_REGISTER_OFFSET},
+        valid_access_ports, AccessPort, ApAccess, ApClass, ApScanOptions, BaseaddrFormat,
+        GenericAp, MemoryAp, VendorAp, BASE, BASE2, CFG, CSW, IDR,
+    },
+    dp::{
+        targetsel_candidates, Abort, Ctrl, DebugPortError, DebugPortVersion, DpAccess,
+        MultidropTarget, Select, BASEPTR0, BASEPTR1, DPIDR, SELECT1, TARGETID,
     },
-    dp::{Abort, Ctrl, DebugPortVersion, DpAccess, Select, DPIDR},
     memory::{
         adi_v5_memory_interface::{ADIMemoryInterface, ArmProbe},
-        Component,
+        Component, CoresightComponent,
+    },
+    sequences::{
+        swj_dp_wake_from_dormant,
+        trace::{trace, SequenceOperation},
+        ArmDebugSequence, DefaultArmSequence,
     },
-    sequences::{ArmDebugSequence, DefaultArmSequence},
-    ApAddress, ArmError, DapAccess, DpAddress, PortType, RawDapAccess, SwoAccess, SwoConfig,
+    telemetry::TelemetryLog,
+    ApAddress, ArmError, ConnectionEventKind, ConnectionTelemetry, DapAccess, DapBatch,
+    DapOperation, DapStatistics, DpAddress, PortType, RawDapAccess, RawDapOperation, SwoAccess,
+    SwoChunk, SwoConfig,
 };
 use crate::{
     architecture::arm::ap::DataSize, CoreStatus, DebugProbe, DebugProbeError,
-    Error as ProbeRsError, Probe,
+    Error as ProbeRsError, Probe, WireProtocol,
 };
 use jep106::JEP106Code;
 
@@ -24,6 +35,10 @@ use std::{
     time::Duration,
 };
 
+/// Number of consecutive SWD protocol errors after which
+/// [`ArmCommunicationInterface::recover_swd`] is called automatically.
+const MAX_CONSECUTIVE_PROTOCOL_ERRORS: usize = 3;
+
 /// An error in the communication with an access port or
 /// debug port.
 #[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
@@ -55,6 +70,7 @@ pub trait Register:
     const NAME: &'static str;
 }
 
+/// The error returned when a raw value can not be converted into a [`Register`].
 #[derive(Debug, thiserror::Error)]
 #[error("Failed to parse register {name} from {value:#010x}")]
 pub struct RegisterParseError {
@@ -63,6 +79,7 @@ pub struct RegisterParseError {
 }
 
 impl RegisterParseError {
+    /// Create an error for the register `name`, which could not be parsed from `value`.
     pub fn new(name: &'static str, value: u32) -> Self {
         RegisterParseError { name, value }
     }
@@ -85,6 +102,77 @@ pub trait ArmProbeInterface: DapAccess + SwdSequence + SwoAccess + Send {
     /// if necessary. This will also  
     fn num_access_ports(&mut self, dp: DpAddress) -> Result<usize, ArmError>;
 
+    /// Returns the access ports the debug port has.
+    ///
+    /// The APs are usually numbered consecutively from 0, but the numbers can have gaps if
+    /// they are listed in [`ApScanOptions::access_ports`].
+    fn access_ports(&mut self, dp: DpAddress) -> Result<Vec<GenericAp>, ArmError> {
+        let num_access_ports = self.num_access_ports(dp)?;
+
+        Ok((0..num_access_ports)
+            .map(|ap| GenericAp::new(ApAddress { dp, ap: ap as u8 }))
+            .collect())
+    }
+
+    /// Reads the CoreSight components of all memory APs of the debug port.
+    ///
+    /// The ROM tables of the APs are walked recursively, so each returned component is the
+    /// root of the tree of components behind its AP, which can be iterated with
+    /// [`CoresightComponent::iter`]. APs which are not memory APs are skipped.
+    fn coresight_components(&mut self, dp: DpAddress) -> Result<Vec<CoresightComponent>, ArmError> {
+        let mut components = Vec::new();
+
+        for access_port in self.access_ports(dp)? {
+            let ap_information = self.ap_information(access_port)?.clone();
+
+            let component = match ap_information {
+                ApInformation::MemoryAp(MemoryApInformation {
+                    debug_base_address: 0,
+                    ..
+                }) => Err(ProbeRsError::Other(anyhow::anyhow!(
+                    "AP has a base address of 0"
+                ))),
+                ApInformation::MemoryAp(MemoryApInformation {
+                    address,
+                    debug_base_address,
+                    ..
+                }) => {
+                    let ap = MemoryAp::new(address);
+                    let mut memory = self.memory_interface(ap)?;
+                    let component = Component::try_parse(&mut *memory, debug_base_address)?;
+                    Ok(CoresightComponent::new(component, ap))
+                }
+                ApInformation::Vendor { ap, .. } => Err(ProbeRsError::Other(anyhow::anyhow!(
+                    "AP {:#x?} is a {}, unable to get ARM component.",
+                    ap.ap_address(),
+                    ap.name()
+                ))),
+                ApInformation::Other { address, .. } => {
+                    // Return an error, only possible to get Component from MemoryAP
+                    Err(ProbeRsError::Other(anyhow::anyhow!(
+                        "AP {:#x?} is not a MemoryAP, unable to get ARM component.",
+                        address
+                    )))
+                }
+            };
+
+            match component {
+                Ok(component) => {
+                    components.push(component);
+                }
+                Err(e) => {
+                    tracing::info!(
+                        "Not counting AP {} because of: {}",
+                        access_port.ap_address().ap,
+                        e
+                    );
+                }
+            }
+        }
+
+        Ok(components)
+    }
+
     /// Reads the chip info from the romtable of given debug port.
     fn read_chip_info_from_rom_table(
         &mut self,
@@ -93,6 +181,59 @@ pub trait ArmProbeInterface: DapAccess + SwdSequence + SwoAccess + Send {
 
     /// Closes the interface and returns back the generic probe it consumed.
     fn close(self: Box<Self>) -> Probe;
+
+    /// Resynchronize the SWD protocol after wedged transfers, and return the DPIDR
+    /// of the debug port.
+    ///
+    /// Probes which implement the protocol in their firmware do not support this.
+    fn recover_swd(&mut self) -> Result<DPIDR, ArmError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("SWD recovery").into())
+    }
+
+    /// Find the debug ports on a multidrop SWD bus.
+    ///
+    /// All 16 instances of each of the `targetids` are selected in turn, and the DPIDR and
+    /// TARGETID of each debug port which responds are read. The instance numbers of the
+    /// `targetids` are ignored. The TARGETIDs of the parts known to probe-rs are returned by
+    /// [`known_multidrop_targetids`](crate::architecture::arm::dp::known_multidrop_targetids).
+    ///
+    /// Probes which do not support multidrop SWD return an error.
+    fn scan_multidrop(&mut self, _targetids: &[u32]) -> Result<Vec<MultidropTarget>, ArmError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("multidrop SWD").into())
+    }
+
+    /// Write the cached AP and bank selection to the SELECT register of every debug port,
+    /// after it may have been changed by raw register accesses.
+    ///
+    /// Probes which select APs in their firmware do not need to do anything.
+    fn restore_cached_dap_state(&mut self) -> Result<(), ArmError> {
+        Ok(())
+    }
+
+    /// Returns the counters of the DAP accesses since the interface was initialized, or since
+    /// the last call of [`ArmProbeInterface::reset_statistics`].
+    ///
+    /// Probes which access the DAP in their firmware return `None`.
+    fn statistics(&self) -> Option<DapStatistics> {
+        None
+    }
+
+    /// Reset the counters returned by [`ArmProbeInterface::statistics`] to zero.
+    fn reset_statistics(&mut self) {}
+
+    /// Returns the connection problems since the interface was initialized.
+    ///
+    /// Probes which access the DAP in their firmware return `None`.
+    fn connection_telemetry(&self) -> Option<ConnectionTelemetry> {
+        None
+    }
+
+    /// Log a summary of the [`ArmProbeInterface::connection_telemetry`] every `interval`, or
+    /// stop logging with `None`.
+    ///
+    /// The summary is logged by the next transfer after the interval has passed, so nothing is
+    /// logged while the probe is idle.
+    fn set_telemetry_log_interval(&mut self, _interval: Option<Duration>) {}
 }
 
 // TODO: Rename trait!
@@ -110,6 +251,17 @@ pub trait SwdSequence {
 }
 
 pub trait UninitializedArmProbe: SwdSequence + Debug {
+    /// Configure how the access ports are found once the probe is initialized.
+    fn set_ap_scan_options(&mut self, _options: ApScanOptions) {}
+
+    /// Enable or disable the wire protocol detection of [`UninitializedArmProbe::initialize`].
+    ///
+    /// With the detection enabled, the debug port is also tried over SWD and over JTAG if it
+    /// does not respond over the protocol the probe uses, each time also waking it up from the
+    /// dormant state. [`Probe::try_into_arm_interface`] enables it if no protocol was selected
+    /// with [`Probe::select_protocol`].
+    fn set_protocol_detection(&mut self, _enabled: bool) {}
+
     fn initialize(
         self: Box<Self>,
         sequence: Arc<dyn ArmDebugSequence>,
@@ -131,6 +283,10 @@ pub trait ArmDebugState {}
 pub struct Uninitialized {
     /// Specify if overrun detect should be enabled when the probe is initialized.
     pub(crate) use_overrun_detect: bool,
+    /// How the access ports are found after the probe is initialized.
+    pub(crate) ap_scan: ApScanOptions,
+    /// Try other wire protocols if the debug port does not respond.
+    pub(crate) protocol_detection: bool,
 }
 
 pub struct Initialized {
@@ -139,7 +295,13 @@ pub struct Initialized {
     current_dp: Option<DpAddress>,
     dps: HashMap<DpAddress, DpState>,
     use_overrun_detect: bool,
+    ap_scan: ApScanOptions,
     sequence: Arc<dyn ArmDebugSequence>,
+    /// Number of SWD protocol errors since the last successful transfer.
+    protocol_errors: usize,
+    statistics: DapStatistics,
+    telemetry: ConnectionTelemetry,
+    telemetry_log: Option<TelemetryLog>,
 }
 
 impl Initialized {
@@ -148,7 +310,12 @@ impl Initialized {
             current_dp: None,
             dps: HashMap::new(),
             use_overrun_detect,
+            ap_scan: ApScanOptions::default(),
             sequence,
+            protocol_errors: 0,
+            statistics: DapStatistics::default(),
+            telemetry: ConnectionTelemetry::new(),
+            telemetry_log: None,
         }
     }
 }
@@ -159,27 +326,50 @@ impl ArmDebugState for Initialized {}
 
 #[derive(Debug)]
 pub(crate) struct DpState {
-    pub _debug_port_version: DebugPortVersion,
+    pub debug_port_version: DebugPortVersion,
 
     pub current_dpbanksel: u8,
 
     pub current_apsel: u8,
     pub current_apbanksel: u8,
 
-    /// Information about the APs of the target.
-    /// APs are identified by a number, starting from zero.
-    pub ap_information: Vec<ApInformation>,
+    /// The address selected in the debug address space of a DPv3 debug port, which replaces
+    /// the AP and bank selection.
+    pub current_address: u64,
+
+    /// The base addresses of the APs of a DPv3 debug port, in the order they were found. They
+    /// are numbered by their position in this list.
+    pub apv2_base_addresses: Vec<u64>,
+
+    /// Information about the APs of the target, in the order of their numbers, or `None` if the
+    /// APs were not scanned yet.
+    pub ap_information: Option<Vec<ApInformation>>,
 }
 
 impl DpState {
     pub fn new() -> Self {
         Self {
-            _debug_port_version: DebugPortVersion::Unsupported(0xFF),
+            debug_port_version: DebugPortVersion::Unsupported(0xFF),
             current_dpbanksel: 0,
             current_apsel: 0,
             current_apbanksel: 0,
-            ap_information: Vec::new(),
+            current_address: 0,
+            apv2_base_addresses: Vec::new(),
+            ap_information: None,
+        }
+    }
+
+    /// The value of the SELECT register for the cached selection.
+    fn select(&self) -> Select {
+        let mut select = Select(0);
+        if self.debug_port_version == DebugPortVersion::DPv3 {
+            select.set_addr((self.current_address as u32) >> 4);
+        } else {
+            select.set_ap_sel(self.current_apsel);
+            select.set_ap_bank_sel(self.current_apbanksel);
         }
+        select.set_dp_bank_sel(self.current_dpbanksel);
+        select
     }
 }
 
@@ -190,6 +380,13 @@ pub enum ApInformation {
     ///
     /// [ARM Debug Interface Architecture Specification]: https://developer.arm.com/documentation/ihi0031/d/
     MemoryAp(MemoryApInformation),
+    /// Information about a vendor specific AP, which was recognized by its [`IDR`].
+    Vendor {
+        /// The access port, which provides typed access to its registers.
+        ap: VendorAp,
+        /// Content of the [`IDR`] register describing this AP.
+        idr: IDR,
+    },
     /// Information about an AP with an unknown class.
     Other {
         /// Zero-based port number of the access port. This is used in the debug port to select an AP.
@@ -200,12 +397,22 @@ pub enum ApInformation {
 }
 
 impl ApInformation {
+    /// The address of the AP.
+    pub fn address(&self) -> ApAddress {
+        match self {
+            ApInformation::MemoryAp(information) => information.address,
+            ApInformation::Vendor { ap, .. } => ap.ap_address(),
+            ApInformation::Other { address, .. } => *address,
+        }
+    }
+
     /// Read information about an AP from its registers.
     ///
     /// This reads the IDR register of the AP, and parses
     /// further AP specific information based on its class.
     ///
-    /// Currently, AP specific information is read for Memory APs.
+    /// Currently, AP specific information is read for Memory APs. Known vendor
+    /// specific APs are recognized by their IDR.
     pub(crate) fn read_from_target<P>(
         probe: &mut P,
         access_port: GenericAp,
@@ -265,6 +472,8 @@ impl ApInformation {
                 has_large_data_extension,
                 device_enabled,
             }))
+        } else if let Some(ap) = VendorAp::from_idr(access_port.ap_address(), &idr) {
+            Ok(ApInformation::Vendor { ap, idr })
         } else {
             Ok(ApInformation::Other {
                 address: access_port.ap_address(),
@@ -354,13 +563,57 @@ impl ArmProbeInterface for ArmCommunicationInterface<Initialized> {
         ArmCommunicationInterface::num_access_ports(self, dp)
     }
 
+    fn access_ports(&mut self, dp: DpAddress) -> Result<Vec<GenericAp>, ArmError> {
+        ArmCommunicationInterface::access_ports(self, dp)
+    }
+
     fn close(self: Box<Self>) -> Probe {
         Probe::from_attached_probe(RawDapAccess::into_probe(self.probe))
     }
+
+    fn recover_swd(&mut self) -> Result<DPIDR, ArmError> {
+        ArmCommunicationInterface::recover_swd(self)
+    }
+
+    fn scan_multidrop(&mut self, targetids: &[u32]) -> Result<Vec<MultidropTarget>, ArmError> {
+        ArmCommunicationInterface::scan_multidrop(self, targetids)
+    }
+
+    fn restore_cached_dap_state(&mut self) -> Result<(), ArmError> {
+        let selects: Vec<_> = self
+            .state
+            .dps
+            .iter()
+            .map(|(dp, state)| (*dp, state.select()))
+            .collect();
+
+        for (dp, select) in selects {
+            self.write_dp_register(dp, select)?;
+        }
+
+        Ok(())
+    }
+
+    fn statistics(&self) -> Option<DapStatistics> {
+        Some(self.state.statistics)
+    }
+
+    fn reset_statistics(&mut self) {
+        self.state.statistics = DapStatistics::default();
+    }
+
+    fn connection_telemetry(&self) -> Option<ConnectionTelemetry> {
+        Some(self.state.telemetry.clone())
+    }
+
+    fn set_telemetry_log_interval(&mut self, interval: Option<Duration>) {
+        self.state.telemetry_log = interval.map(TelemetryLog::new);
+    }
 }
 
 impl<S: ArmDebugState> SwdSequence for ArmCommunicationInterface<S> {
     fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        trace(|| SequenceOperation::SwjSequence { bit_len, bits });
         self.probe.swj_sequence(bit_len, bits)?;
 
         Ok(())
@@ -372,17 +625,79 @@ impl<S: ArmDebugState> SwdSequence for ArmCommunicationInterface<S> {
         pin_select: u32,
         pin_wait: u32,
     ) -> Result<u32, DebugProbeError> {
+        trace(|| SequenceOperation::SwjPins {
+            pin_out,
+            pin_select,
+        });
         self.probe.swj_pins(pin_out, pin_select, pin_wait)
     }
 }
 
 impl ArmCommunicationInterface<Uninitialized> {
     pub(crate) fn new(probe: Box<dyn DapProbe>, use_overrun_detect: bool) -> Self {
-        let state = Uninitialized { use_overrun_detect };
+        let state = Uninitialized {
+            use_overrun_detect,
+            ap_scan: ApScanOptions::default(),
+            protocol_detection: false,
+        };
 
         Self { probe, state }
     }
 
+    /// Find a wire protocol over which the debug port responds, see
+    /// [`UninitializedArmProbe::set_protocol_detection`].
+    ///
+    /// SWD is tried before JTAG, and each protocol first with the SWJ-DP switch sequence of the
+    /// debug sequence, then by waking the SWJ-DP up from the dormant state.
+    fn detect_protocol(&mut self, sequence: &dyn ArmDebugSequence) -> Result<(), ProbeRsError> {
+        if self.debug_port_responds() {
+            return Ok(());
+        }
+
+        for protocol in [WireProtocol::Swd, WireProtocol::Jtag] {
+            if self.probe.active_protocol() != Some(protocol) {
+                if let Err(error) = self.switch_probe_protocol(protocol) {
+                    tracing::debug!("The probe cannot use {}: {}", protocol, error);
+                    continue;
+                }
+
+                sequence.debug_port_setup(&mut *self.probe)?;
+                if self.debug_port_responds() {
+                    tracing::info!("The debug port responds over {}", protocol);
+                    return Ok(());
+                }
+            }
+
+            swj_dp_wake_from_dormant(&mut *self.probe, protocol)?;
+            if self.debug_port_responds() {
+                tracing::info!(
+                    "The debug port responds over {}, after waking it up from the dormant state",
+                    protocol
+                );
+                return Ok(());
+            }
+        }
+
+        Err(ArmError::from(DebugPortError::NotResponding).into())
+    }
+
+    /// Re-attach the probe with `protocol`. If the probe does not support `protocol`, it is
+    /// re-attached with the protocol it used before.
+    fn switch_probe_protocol(&mut self, protocol: WireProtocol) -> Result<(), ProbeRsError> {
+        self.probe.detach()?;
+        let selected = self.probe.select_protocol(protocol);
+        self.probe.attach()?;
+
+        Ok(selected?)
+    }
+
+    /// Returns `true` if the DPIDR of the debug port can be read.
+    fn debug_port_responds(&mut self) -> bool {
+        self.probe
+            .raw_read_register(PortType::DebugPort, DPIDR::ADDRESS)
+            .is_ok()
+    }
+
     fn into_initialized(
         self,
         sequence: Arc<dyn ArmDebugSequence>,
@@ -398,6 +713,14 @@ impl ArmCommunicationInterface<Uninitialized> {
 }
 
 impl UninitializedArmProbe for ArmCommunicationInterface<Uninitialized> {
+    fn set_ap_scan_options(&mut self, options: ApScanOptions) {
+        self.state.ap_scan = options;
+    }
+
+    fn set_protocol_detection(&mut self, enabled: bool) {
+        self.state.protocol_detection = enabled;
+    }
+
     fn initialize(
         mut self: Box<Self>,
         sequence: Arc<dyn ArmDebugSequence>,
@@ -407,6 +730,12 @@ impl UninitializedArmProbe for ArmCommunicationInterface<Uninitialized> {
             return Err((self as Box<_>, e.into()));
         }
 
+        if self.state.protocol_detection {
+            if let Err(e) = self.detect_protocol(&*sequence) {
+                return Err((self as Box<_>, e));
+            }
+        }
+
         drop(setup_span);
 
         let interface = self
@@ -422,7 +751,7 @@ impl UninitializedArmProbe for ArmCommunicationInterface<Uninitialized> {
 }
 
 impl<S: ArmDebugState> ArmCommunicationInterface<S> {
-    fn _get_debug_port_version(&mut self) -> Result<DebugPortVersion, ArmError> {
+    fn get_debug_port_version(&mut self) -> Result<DebugPortVersion, ArmError> {
         let dpidr = DPIDR(self.probe.raw_read_register(PortType::DebugPort, 0)?);
 
         Ok(DebugPortVersion::from(dpidr.version()))
@@ -441,9 +770,12 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
             DebugProbeError,
         ),
     > {
+        let mut state = Initialized::new(sequence, use_overrun_detect);
+        state.ap_scan = interface.state.ap_scan;
+
         let initialized_interface = ArmCommunicationInterface {
             probe: interface.probe,
-            state: Initialized::new(sequence, use_overrun_detect),
+            state,
         };
 
         Ok(initialized_interface)
@@ -474,7 +806,9 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
 
                 Ok(Box::new(adi_v5_memory_interface))
             }
-            ApInformation::Other { .. } => Err(ArmError::WrongApType),
+            ApInformation::Vendor { .. } | ApInformation::Other { .. } => {
+                Err(ArmError::WrongApType)
+            }
         }
     }
 
@@ -493,46 +827,159 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         self.state.current_dp = Some(dp);
 
         if let hash_map::Entry::Vacant(entry) = self.state.dps.entry(dp) {
-            let sequence = self.state.sequence.clone();
-
             entry.insert(DpState::new());
 
-            let start_span = tracing::debug_span!("debug_port_start").entered();
-            sequence.debug_port_start(self, dp)?;
-            drop(start_span);
+            // The DP is started again by the next access if this fails, for example when an
+            // attach is retried.
+            if let Err(error) = self.start_dp(dp) {
+                self.state.dps.remove(&dp);
+                self.state.current_dp = None;
+                return Err(error);
+            }
+        }
 
-            // Make sure we enable the overrun detect mode when requested.
-            // For "bit-banging" probes, such as JLink or FTDI, we rely on it for good, stable communication.
-            // This is required as the default sequence (and most special implementations) does not do this.
-            tracing::debug!("Setting orun_detect: {}", self.state.use_overrun_detect);
-            let mut ctrl_reg: Ctrl = self.read_dp_register(dp)?;
-            ctrl_reg.set_orun_detect(self.state.use_overrun_detect);
-            self.write_dp_register(dp, ctrl_reg)?;
+        // note(unwrap): Entry gets inserted above
+        Ok(self.state.dps.get_mut(&dp).unwrap())
+    }
 
-            /* determine the number and type of available APs */
+    fn start_dp(&mut self, dp: DpAddress) -> Result<(), ArmError> {
+        let sequence = self.state.sequence.clone();
+
+        let start_span = tracing::debug_span!("debug_port_start").entered();
+        sequence.debug_port_start(self, dp)?;
+        drop(start_span);
+
+        // The AP selection depends on the version, DPv3 addresses APs by their base address.
+        match self.get_debug_port_version() {
+            Ok(version) => {
+                tracing::debug!("DP {:x?} is a {}", dp, version);
+                if let Some(state) = self.state.dps.get_mut(&dp) {
+                    state.debug_port_version = version;
+                }
+            }
+            Err(e) => tracing::debug!("Failed to read the version of DP {:x?}: {}", dp, e),
+        }
+
+        // Make sure we enable the overrun detect mode when requested.
+        // For "bit-banging" probes, such as JLink or FTDI, we rely on it for good, stable communication.
+        // This is required as the default sequence (and most special implementations) does not do this.
+        tracing::debug!("Setting orun_detect: {}", self.state.use_overrun_detect);
+        let mut ctrl_reg: Ctrl = self.read_dp_register(dp)?;
+        ctrl_reg.set_orun_detect(self.state.use_overrun_detect);
+        self.write_dp_register(dp, ctrl_reg)
+    }
+
+    /// Select `dp`, and determine the number and type of its APs if that was not done yet.
+    ///
+    /// The APs are scanned separately from starting the DP, so the DP registers, for example
+    /// the sticky errors, can still be read when the scan fails.
+    fn scanned_access_ports(&mut self, dp: DpAddress) -> Result<&[ApInformation], ArmError> {
+        if self.select_dp(dp)?.ap_information.is_none() {
             tracing::trace!("Searching valid APs");
 
             let ap_span = tracing::debug_span!("AP discovery").entered();
-            for ap in valid_access_ports(self, dp) {
+            let mut ap_information = Vec::new();
+            for ap in self.access_port_candidates(dp)? {
                 let ap_state = ApInformation::read_from_target(self, ap)?;
                 tracing::debug!("AP {:x?}: {:?}", ap, ap_state);
-
-                // note(unwrap): we have inserted the state above, it must exist.
-                let state = self.state.dps.get_mut(&dp).unwrap();
-                state.ap_information.push(ap_state);
+                ap_information.push(ap_state);
             }
             drop(ap_span);
+
+            self.select_dp(dp)?.ap_information = Some(ap_information);
         }
 
-        // note(unwrap): Entry gets inserted above
-        Ok(self.state.dps.get_mut(&dp).unwrap())
+        // note(unwrap): The APs were scanned above.
+        Ok(self.select_dp(dp)?.ap_information.as_deref().unwrap())
     }
 
-    fn select_dp_and_dp_bank(
+    /// The APs of `dp` which respond.
+    ///
+    /// The APs of a DPv3 debug port are found in the ROM tables at its base pointer, the AP
+    /// scan options only apply to older debug ports.
+    fn access_port_candidates(&mut self, dp: DpAddress) -> Result<Vec<GenericAp>, ArmError> {
+        if self.select_dp(dp)?.debug_port_version != DebugPortVersion::DPv3 {
+            let ap_scan = self.state.ap_scan.clone();
+            return Ok(valid_access_ports(self, dp, &ap_scan));
+        }
+
+        if self.select_dp(dp)?.apv2_base_addresses.is_empty() {
+            let baseptr0: BASEPTR0 = self.read_dp_register(dp)?;
+            if !baseptr0.valid() {
+                return Err(DebugPortError::Unsupported(
+                    "the DPv3 debug port has no base pointer to find its APs".to_string(),
+                )
+                .into());
+            }
+            let baseptr1: BASEPTR1 = self.read_dp_register(dp)?;
+            let base_address = (u64::from(baseptr1.0) << 32) | u64::from(baseptr0.ptr() << 12);
+
+            let base_addresses = find_apv2_access_ports(base_address, |address| {
+                self.read_debug_address(dp, address)
+            })?;
+            self.select_dp(dp)?.apv2_base_addresses = base_addresses;
+        }
+
+        let count = self.select_dp(dp)?.apv2_base_addresses.len();
+        Ok((0..count)
+            .map(|ap| GenericAp::new(ApAddress { dp, ap: ap as u8 }))
+            .collect())
+    }
+
+    /// Select `address` in the debug address space of a DPv3 debug port, see
+    /// [`Self::dp_bank_selection`].
+    fn debug_address_selection(
+        &mut self,
+        dp: DpAddress,
+        address: u64,
+    ) -> Result<Vec<(u8, u32)>, ArmError> {
+        let dp_state = self.select_dp(dp)?;
+        let address = address & !0xF;
+
+        if dp_state.current_address == address {
+            self.state.statistics.select_cache_hits += 1;
+            return Ok(Vec::new());
+        }
+
+        let upper_bits_changed = dp_state.current_address >> 32 != address >> 32;
+        dp_state.current_address = address;
+        tracing::debug!("Changing the selected address to {:#x}", address);
+
+        let writes = if upper_bits_changed {
+            // SELECT1 is in bank 5 of the DP registers, SELECT is written with the new address and
+            // the bank of SELECT1 first.
+            dp_state.current_dpbanksel = SELECT1::ADDRESS >> 4;
+            vec![
+                (Select::ADDRESS, dp_state.select().into()),
+                (SELECT1::ADDRESS & 0xF, (address >> 32) as u32),
+            ]
+        } else {
+            vec![(Select::ADDRESS, dp_state.select().into())]
+        };
+
+        self.state.statistics.select_cache_misses += 1;
+        Ok(writes)
+    }
+
+    /// Read a word from the debug address space of a DPv3 debug port.
+    fn read_debug_address(&mut self, dp: DpAddress, address: u64) -> Result<u32, ArmError> {
+        let writes = self.debug_address_selection(dp, address)?;
+        self.write_selection(writes)?;
+        self.state.statistics.record_ap_transaction(1);
+
+        let result = self
+            .probe
+            .raw_read_register(PortType::AccessPort, (address & 0xC) as u8);
+        self.track_protocol_errors(result)
+    }
+
+    /// Update the cached selection for an access of the DP register at `dp_register_address`,
+    /// and return the DP register writes which select it on the target.
+    fn dp_bank_selection(
         &mut self,
         dp: DpAddress,
         dp_register_address: u8,
-    ) -> Result<(), ArmError> {
+    ) -> Result<Vec<(u8, u32)>, ArmError> {
         let dp_state = self.select_dp(dp)?;
 
         // DP register addresses are 4 bank bits, 4 address bits. Lowest 2 address bits are
@@ -542,34 +989,49 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         let bank = dp_register_address >> 4;
         let addr = dp_register_address & 0xF;
 
-        if addr != 4 {
-            return Ok(());
+        // DPv3 also banks address 0x0, with DPIDR1 and the base pointers.
+        let banked =
+            addr == 4 || (addr == 0 && dp_state.debug_port_version == DebugPortVersion::DPv3);
+        if !banked {
+            return Ok(Vec::new());
         }
 
         if bank != dp_state.current_dpbanksel {
             dp_state.current_dpbanksel = bank;
 
-            let mut select = Select(0);
-
             tracing::debug!("Changing DP_BANK_SEL to {}", dp_state.current_dpbanksel);
 
-            select.set_ap_sel(dp_state.current_apsel);
-            select.set_ap_bank_sel(dp_state.current_apbanksel);
-            select.set_dp_bank_sel(dp_state.current_dpbanksel);
+            let select = dp_state.select();
 
-            self.write_dp_register(dp, select)?;
+            self.state.statistics.select_cache_misses += 1;
+            Ok(vec![(Select::ADDRESS, select.into())])
+        } else {
+            self.state.statistics.select_cache_hits += 1;
+            Ok(Vec::new())
         }
-
-        Ok(())
     }
 
-    fn select_ap_and_ap_bank(
+    /// Update the cached selection for an access of the AP register at `ap_register_address`,
+    /// see [`Self::dp_bank_selection`].
+    fn ap_bank_selection(
         &mut self,
         ap: ApAddress,
         ap_register_address: u8,
-    ) -> Result<(), ArmError> {
+    ) -> Result<Vec<(u8, u32)>, ArmError> {
         let dp_state = self.select_dp(ap.dp)?;
 
+        if dp_state.debug_port_version == DebugPortVersion::DPv3 {
+            let base_address = *dp_state
+                .apv2_base_addresses
+                .get(usize::from(ap.ap))
+                .ok_or(ArmError::ApDoesNotExist(ap))?;
+
+            return self.debug_address_selection(
+                ap.dp,
+                base_address + APV2_REGISTER_OFFSET + u64::from(ap_register_address & 0xF0),
+            );
+        }
+
         let port = ap.ap;
         let ap_bank = ap_register_address >> 4;
 
@@ -586,24 +1048,142 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         }
 
         if cache_changed {
-            let mut select = Select(0);
-
             tracing::debug!(
                 "Changing AP to {}, AP_BANK_SEL to {}",
                 dp_state.current_apsel,
                 dp_state.current_apbanksel
             );
 
-            select.set_ap_sel(dp_state.current_apsel);
-            select.set_ap_bank_sel(dp_state.current_apbanksel);
-            select.set_dp_bank_sel(dp_state.current_dpbanksel);
+            let select = dp_state.select();
 
-            self.write_dp_register(ap.dp, select)?;
+            self.state.statistics.select_cache_misses += 1;
+            Ok(vec![(Select::ADDRESS, select.into())])
+        } else {
+            self.state.statistics.select_cache_hits += 1;
+            Ok(Vec::new())
+        }
+    }
+
+    /// Write the DP registers returned by one of the selection functions.
+    fn write_selection(&mut self, writes: Vec<(u8, u32)>) -> Result<(), ArmError> {
+        for (address, value) in writes {
+            self.state.statistics.dp_transactions += 1;
+            let result = self
+                .probe
+                .raw_write_register(PortType::DebugPort, address, value);
+            self.track_protocol_errors(result)?;
         }
 
         Ok(())
     }
 
+    fn select_dp_and_dp_bank(
+        &mut self,
+        dp: DpAddress,
+        dp_register_address: u8,
+    ) -> Result<(), ArmError> {
+        let writes = self.dp_bank_selection(dp, dp_register_address)?;
+        self.write_selection(writes)
+    }
+
+    fn select_ap_and_ap_bank(
+        &mut self,
+        ap: ApAddress,
+        ap_register_address: u8,
+    ) -> Result<(), ArmError> {
+        let writes = self.ap_bank_selection(ap, ap_register_address)?;
+        self.write_selection(writes)
+    }
+
+    /// Execute `batch`, see [`DapAccess::execute_batch`].
+    ///
+    /// The writes to SELECT are added to the batch, so the probe can send it at once. The batch
+    /// is split where it switches to another debug port, because selecting a debug port on a
+    /// multidrop bus needs its own transfers.
+    fn execute_dap_batch(&mut self, batch: &DapBatch) -> Result<Vec<u32>, ArmError> {
+        let mut values = Vec::with_capacity(batch.reads());
+        let mut operations = Vec::new();
+        let mut current_dp = None;
+
+        for operation in batch.operations() {
+            let dp = operation.dp();
+            if current_dp != Some(dp) && !operations.is_empty() {
+                values.extend(self.execute_raw_batch(&operations)?);
+                operations.clear();
+            }
+            current_dp = Some(dp);
+
+            let (writes, operation) = match *operation {
+                DapOperation::ReadDp { dp, addr } => (
+                    self.dp_bank_selection(dp, addr)?,
+                    RawDapOperation::Read {
+                        port: PortType::DebugPort,
+                        addr,
+                    },
+                ),
+                DapOperation::WriteDp { dp, addr, value } => (
+                    self.dp_bank_selection(dp, addr)?,
+                    RawDapOperation::Write {
+                        port: PortType::DebugPort,
+                        addr,
+                        value,
+                    },
+                ),
+                DapOperation::ReadAp { ap, addr } => (
+                    self.ap_bank_selection(ap, addr)?,
+                    RawDapOperation::Read {
+                        port: PortType::AccessPort,
+                        addr,
+                    },
+                ),
+                DapOperation::WriteAp { ap, addr, value } => (
+                    self.ap_bank_selection(ap, addr)?,
+                    RawDapOperation::Write {
+                        port: PortType::AccessPort,
+                        addr,
+                        value,
+                    },
+                ),
+            };
+
+            operations.extend(
+                writes
+                    .into_iter()
+                    .map(|(addr, value)| RawDapOperation::Write {
+                        port: PortType::DebugPort,
+                        addr,
+                        value,
+                    }),
+            );
+            operations.push(operation);
+        }
+
+        if !operations.is_empty() {
+            values.extend(self.execute_raw_batch(&operations)?);
+        }
+
+        Ok(values)
+    }
+
+    fn execute_raw_batch(&mut self, operations: &[RawDapOperation]) -> Result<Vec<u32>, ArmError> {
+        for operation in operations {
+            match operation {
+                RawDapOperation::Read {
+                    port: PortType::DebugPort,
+                    ..
+                }
+                | RawDapOperation::Write {
+                    port: PortType::DebugPort,
+                    ..
+                } => self.state.statistics.dp_transactions += 1,
+                _ => self.state.statistics.record_ap_transaction(1),
+            }
+        }
+
+        let result = self.probe.raw_batch(operations);
+        self.track_protocol_errors(result)
+    }
+
     /// Determine the type and additional information about an AP.
     ///
     /// If the AP doesn't exist, None is returned.
@@ -613,15 +1193,220 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
     ) -> Result<Option<&ApInformation>, ArmError> {
         let addr = access_port.ap_address();
 
-        let state = self.select_dp(addr.dp)?;
-
-        Ok(state.ap_information.get(addr.ap as usize))
+        Ok(self
+            .scanned_access_ports(addr.dp)?
+            .iter()
+            .find(|information| information.address() == addr))
     }
 
     fn num_access_ports(&mut self, dp: DpAddress) -> Result<usize, ArmError> {
-        let state = self.select_dp(dp)?;
+        Ok(self.scanned_access_ports(dp)?.len())
+    }
+
+    fn access_ports(&mut self, dp: DpAddress) -> Result<Vec<GenericAp>, ArmError> {
+        Ok(self
+            .scanned_access_ports(dp)?
+            .iter()
+            .map(|information| GenericAp::new(information.address()))
+            .collect())
+    }
+
+    /// Resynchronize the SWD protocol with the target, and return the DPIDR of the debug port.
+    ///
+    /// This issues a line reset and the JTAG-to-SWD sequence. If the debug port still does not
+    /// respond, it is woken up from the dormant state. Multidrop debug ports are selected
+    /// again. Afterwards, sticky errors are cleared and the SELECT register is restored, so
+    /// the cached AP and bank selection stay valid.
+    ///
+    /// This is done automatically after three consecutive transfers failed with a protocol error.
+    pub fn recover_swd(&mut self) -> Result<DPIDR, ArmError> {
+        if let Some(protocol) = self.probe.active_protocol() {
+            if protocol != WireProtocol::Swd {
+                return Err(DebugProbeError::UnsupportedProtocol(protocol).into());
+            }
+        }
+
+        let dp = self.state.current_dp.unwrap_or(DpAddress::Default);
+        tracing::debug!("Recovering SWD connection to DP {:x?}", dp);
+
+        let dpidr = match dp {
+            DpAddress::Default => self.resynchronize_swd()?,
+            DpAddress::Multidrop(_) => {
+                // Selecting a multidrop DP includes the dormant wakeup and a DPIDR read.
+                self.probe.select_dp(dp)?;
+                self.probe
+                    .raw_read_register(PortType::DebugPort, DPIDR::ADDRESS)?
+            }
+        };
+
+        // Clear sticky errors left by the interrupted transfers.
+        let mut abort = Abort(0);
+        abort.set_orunerrclr(true);
+        abort.set_wderrclr(true);
+        abort.set_stkerrclr(true);
+        abort.set_stkcmpclr(true);
+        self.probe
+            .raw_write_register(PortType::DebugPort, Abort::ADDRESS, abort.into())?;
+
+        if let Some(state) = self.state.dps.get(&dp) {
+            let select = state.select();
+            self.probe
+                .raw_write_register(PortType::DebugPort, Select::ADDRESS, select.into())?;
+        }
+
+        self.state.protocol_errors = 0;
+        self.state.statistics.swd_recoveries += 1;
+        self.state
+            .telemetry
+            .record(ConnectionEventKind::Reinitialization);
+
+        Ok(DPIDR(dpidr))
+    }
+
+    /// Find the debug ports on a multidrop SWD bus, see [`ArmProbeInterface::scan_multidrop`].
+    pub fn scan_multidrop(&mut self, targetids: &[u32]) -> Result<Vec<MultidropTarget>, ArmError> {
+        if let Some(protocol) = self.probe.active_protocol() {
+            if protocol != WireProtocol::Swd {
+                return Err(DebugProbeError::UnsupportedProtocol(protocol).into());
+            }
+        }
+
+        let result = self.scan_targetsel_candidates(&targetsel_candidates(targetids));
 
-        Ok(state.ap_information.len())
+        // The scan selects other DPs behind the back of the cached state.
+        self.state.current_dp = None;
+
+        result
+    }
+
+    fn scan_targetsel_candidates(
+        &mut self,
+        candidates: &[u32],
+    ) -> Result<Vec<MultidropTarget>, ArmError> {
+        let mut targets = Vec::new();
+
+        for &targetsel in candidates {
+            let dp = DpAddress::Multidrop(targetsel);
+
+            // Selecting the DP includes a DPIDR read, which fails if no DP has this TARGETSEL.
+            match self.probe.select_dp(dp) {
+                Ok(()) => {}
+                Err(ArmError::Dap(e)) => {
+                    tracing::trace!("No DP responded to TARGETSEL {:#010x}: {}", targetsel, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+
+            let dpidr = self
+                .probe
+                .raw_read_register(PortType::DebugPort, DPIDR::ADDRESS)?;
+
+            // TARGETID is in bank 2 of the DP registers.
+            let mut select = Select(0);
+            select.set_dp_bank_sel(TARGETID::ADDRESS >> 4);
+            self.probe
+                .raw_write_register(PortType::DebugPort, Select::ADDRESS, select.into())?;
+            let targetid = self
+                .probe
+                .raw_read_register(PortType::DebugPort, TARGETID::ADDRESS & 0xf)?;
+
+            let select = self.state.dps.get(&dp).map_or(Select(0), DpState::select);
+            self.probe
+                .raw_write_register(PortType::DebugPort, Select::ADDRESS, select.into())?;
+
+            tracing::debug!(
+                "Found DP with TARGETSEL {:#010x}: DPIDR {:#010x}, TARGETID {:#010x}",
+                targetsel,
+                dpidr,
+                targetid
+            );
+            targets.push(MultidropTarget {
+                targetsel,
+                dpidr,
+                targetid,
+            });
+        }
+
+        Ok(targets)
+    }
+
+    /// Bring the default DP back into the SWD state, and read its DPIDR.
+    fn resynchronize_swd(&mut self) -> Result<u32, ArmError> {
+        let idle_cycles = self
+            .probe
+            .swd_timing()
+            .idle_cycles
+            .saturating_add(2)
+            .min(64);
+
+        // Line reset, JTAG-to-SWD switch sequence, line reset and idle cycles.
+        self.probe.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
+        self.probe.swj_sequence(16, 0xE79E)?;
+        self.probe.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
+        self.probe.swj_sequence(idle_cycles, 0x00)?;
+
+        match self
+            .probe
+            .raw_read_register(PortType::DebugPort, DPIDR::ADDRESS)
+        {
+            Ok(dpidr) => return Ok(dpidr),
+            Err(e) => tracing::debug!("No response after line reset, trying dormant wakeup: {}", e),
+        }
+
+        // At least 8 cycles high, the selection alert sequence, 4 idle cycles and the SWD
+        // activation code, followed by a line reset and idle cycles.
+        self.probe.swj_sequence(8, 0xFF)?;
+        self.probe.swj_sequence(64, 0x8685_2D95_6209_F392)?;
+        self.probe.swj_sequence(64, 0x19BC_0EA2_E3DD_AFE9)?;
+        self.probe.swj_sequence(12, 0x1A0)?;
+        self.probe.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
+        self.probe.swj_sequence(idle_cycles, 0x00)?;
+
+        self.probe
+            .raw_read_register(PortType::DebugPort, DPIDR::ADDRESS)
+    }
+
+    /// Keep track of consecutive SWD protocol errors, and recover the connection when there
+    /// are too many of them.
+    fn track_protocol_errors<T>(&mut self, result: Result<T, ArmError>) -> Result<T, ArmError> {
+        if result.is_err() {
+            self.state.statistics.failed_transactions += 1;
+        }
+
+        if let Err(ArmError::Dap(error)) = &result {
+            if let Some(kind) = ConnectionEventKind::from_dap_error(error) {
+                self.state.telemetry.record(kind);
+            }
+        }
+
+        if let Some(log) = &mut self.state.telemetry_log {
+            log.poll(&self.state.telemetry);
+        }
+
+        match &result {
+            Ok(_) => self.state.protocol_errors = 0,
+            Err(ArmError::Dap(
+                DapError::SwdProtocol | DapError::NoAcknowledge | DapError::IncorrectParity,
+            )) if self.probe.active_protocol() == Some(WireProtocol::Swd) => {
+                self.state.protocol_errors += 1;
+
+                if self.state.protocol_errors >= MAX_CONSECUTIVE_PROTOCOL_ERRORS {
+                    tracing::warn!(
+                        "{} consecutive SWD protocol errors, recovering the connection",
+                        self.state.protocol_errors
+                    );
+                    self.state.protocol_errors = 0;
+
+                    if let Err(e) = self.recover_swd() {
+                        tracing::warn!("Failed to recover the SWD connection: {}", e);
+                    }
+                }
+            }
+            Err(_) => {}
+        }
+
+        result
     }
 }
 
@@ -658,13 +1443,26 @@ impl SwoAccess for ArmCommunicationInterface<Initialized> {
             None => Err(ArmError::ArchitectureRequired(&["ARMv7", "ARMv8"])),
         }
     }
+
+    fn read_swo_chunks(&mut self, timeout: Duration) -> Result<Vec<SwoChunk>, ArmError> {
+        match self.probe.get_swo_interface_mut() {
+            Some(interface) => interface.read_swo_chunks(timeout),
+            None => Err(ArmError::ArchitectureRequired(&["ARMv7", "ARMv8"])),
+        }
+    }
 }
 
 impl DapAccess for ArmCommunicationInterface<Initialized> {
     fn read_raw_dp_register(&mut self, dp: DpAddress, address: u8) -> Result<u32, ArmError> {
         self.select_dp_and_dp_bank(dp, address)?;
-        let result = self.probe.raw_read_register(PortType::DebugPort, address)?;
-        Ok(result)
+        self.state.statistics.dp_transactions += 1;
+        let result = self.probe.raw_read_register(PortType::DebugPort, address);
+        trace(|| SequenceOperation::DpRead {
+            dp,
+            address,
+            value: result.as_ref().ok().copied(),
+        });
+        self.track_protocol_errors(result)
     }
 
     fn write_raw_dp_register(
@@ -674,9 +1472,20 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         value: u32,
     ) -> Result<(), ArmError> {
         self.select_dp_and_dp_bank(dp, address)?;
-        self.probe
-            .raw_write_register(PortType::DebugPort, address, value)?;
-        Ok(())
+        self.state.statistics.dp_transactions += 1;
+        let result = self
+            .probe
+            .raw_write_register(PortType::DebugPort, address, value);
+        trace(|| SequenceOperation::DpWrite { dp, address, value });
+
+        // Writes of ABORT with any of the flag clear bits set.
+        if address == Abort::ADDRESS && value & 0b1_1110 != 0 && result.is_ok() {
+            self.state
+                .telemetry
+                .record(ConnectionEventKind::StickyErrorClear);
+        }
+
+        self.track_protocol_errors(result)
     }
 
     fn read_raw_ap_register(
@@ -685,12 +1494,15 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         address: u8,
     ) -> std::result::Result<u32, ArmError> {
         self.select_ap_and_ap_bank(ap, address)?;
-
-        let result = self
-            .probe
-            .raw_read_register(PortType::AccessPort, address)?;
-
-        Ok(result)
+        self.state.statistics.record_ap_transaction(1);
+
+        let result = self.probe.raw_read_register(PortType::AccessPort, address);
+        trace(|| SequenceOperation::ApRead {
+            ap,
+            address,
+            values: result.iter().copied().collect(),
+        });
+        self.track_protocol_errors(result)
     }
 
     fn read_raw_ap_register_repeated(
@@ -700,10 +1512,21 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         values: &mut [u32],
     ) -> Result<(), ArmError> {
         self.select_ap_and_ap_bank(ap, address)?;
+        self.state.statistics.record_ap_transaction(values.len());
 
-        self.probe
-            .raw_read_block(PortType::AccessPort, address, values)?;
-        Ok(())
+        let result = self
+            .probe
+            .raw_read_block(PortType::AccessPort, address, values);
+        trace(|| SequenceOperation::ApRead {
+            ap,
+            address,
+            values: if result.is_ok() {
+                values.to_vec()
+            } else {
+                Vec::new()
+            },
+        });
+        self.track_protocol_errors(result)
     }
 
     fn write_raw_ap_register(
@@ -713,11 +1536,17 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         value: u32,
     ) -> Result<(), ArmError> {
         self.select_ap_and_ap_bank(ap, address)?;
+        self.state.statistics.record_ap_transaction(1);
 
-        self.probe
-            .raw_write_register(PortType::AccessPort, address, value)?;
-
-        Ok(())
+        let result = self
+            .probe
+            .raw_write_register(PortType::AccessPort, address, value);
+        trace(|| SequenceOperation::ApWrite {
+            ap,
+            address,
+            values: vec![value],
+        });
+        self.track_protocol_errors(result)
     }
 
     fn write_raw_ap_register_repeated(
@@ -727,10 +1556,21 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         values: &[u32],
     ) -> Result<(), ArmError> {
         self.select_ap_and_ap_bank(ap, address)?;
+        self.state.statistics.record_ap_transaction(values.len());
 
-        self.probe
-            .raw_write_block(PortType::AccessPort, address, values)?;
-        Ok(())
+        let result = self
+            .probe
+            .raw_write_block(PortType::AccessPort, address, values);
+        trace(|| SequenceOperation::ApWrite {
+            ap,
+            address,
+            values: values.to_vec(),
+        });
+        self.track_protocol_errors(result)
+    }
+
+    fn execute_batch(&mut self, batch: &DapBatch) -> Result<Vec<u32>, ArmError> {
+        self.execute_dap_batch(batch)
     }
 }
 
@@ -755,7 +1595,7 @@ impl ArmCommunicationInterface<Initialized> {
         dp: DpAddress,
     ) -> Result<Option<ArmChipInfo>, ArmError> {
         // faults on some chips need to be cleaned up.
-        let aps = valid_access_ports(self, dp);
+        let aps = self.access_port_candidates(dp)?;
 
         // Check sticky error and cleanup if necessary
         let ctrl_reg: crate::architecture::arm::dp::Ctrl = self.read_dp_register(dp)?;
@@ -779,7 +1619,9 @@ impl ArmCommunicationInterface<Initialized> {
 
                 let component = Component::try_parse(&mut *memory, baseaddr)?;
 
-                if let Component::Class1RomTable(component_id, _) = component {
+                if let Component::Class1RomTable(component_id, _)
+                | Component::Class9RomTable(component_id, _) = component
+                {
                     if let Some(jep106) = component_id.peripheral_id().jep106() {
                         return Ok(Some(ArmChipInfo {
                             manufacturer: jep106,
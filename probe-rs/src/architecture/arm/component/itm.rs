@@ -32,6 +32,11 @@ const _REGISTER_OFFSET_ITM_TPR: u32 = 0xE40;
 const REGISTER_OFFSET_ITM_TCR: u32 = 0xE80;
 const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
 
+/// TSENA: enable local timestamps.
+const TCR_TSENA: u32 = 1 << 1;
+/// The TSENA, TSPrescale and GTSFREQ fields of ITM_TCR.
+const TCR_TIMESTAMP_MASK: u32 = TCR_TSENA | 0b11 << 8 | 0b11 << 10;
+
 impl<'a> Itm<'a> {
     /// Create a new ITM interface from a probe and a ROM table component.
     pub fn new(
@@ -59,16 +64,22 @@ impl<'a> Itm<'a> {
     /// Enable the ITM TX to send tracing data to the TPIU.
     ///
     /// This enables the actual TX pin of the overarching TPIU which is the parent peripheral of the ITM that multiplexes all data.
+    /// Timestamps are configured with the defaults of [`ItmConfig`].
     pub fn tx_enable(&mut self) -> Result<(), Error> {
+        self.tx_enable_with_config(&ItmConfig::default())
+    }
+
+    /// Enable the ITM TX to send tracing data to the TPIU, with the timestamps configured by `config`.
+    pub fn tx_enable_with_config(&mut self, config: &ItmConfig) -> Result<(), Error> {
         let mut value = self
             .component
             .read_reg(self.interface, REGISTER_OFFSET_ITM_TCR)?;
 
+        value &= !TCR_TIMESTAMP_MASK;
         value |= 1 << 0; // ITMENA: enable ITM (master switch)
-        value |= 1 << 1; // TSENA: enable local timestamps
         value |= 1 << 2; // SYNENA: Enable sync pulses, note DWT_CTRL.SYNCTAP must be configured.
         value |= 1 << 3; // TXENA: forward DWT packets to ITM
-        value |= 1 << 11; // GTSFREQ: generate global timestamp every 8192 cycles
+        value |= config.tcr_timestamp_bits();
         value |= 13 << 16; // 7 bits trace bus ID
         self.component
             .write_reg(self.interface, REGISTER_OFFSET_ITM_TCR, value)?;
@@ -84,6 +95,118 @@ impl<'a> Itm<'a> {
     }
 }
 
+/// The prescaler of the ITM local timestamp counter.
+///
+/// The counter is clocked by the processor clock, divided by the prescaler.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LocalTimestampPrescaler {
+    /// No prescaling.
+    Div1,
+    /// Divide the clock by 4.
+    Div4,
+    /// Divide the clock by 16.
+    Div16,
+    /// Divide the clock by 64.
+    Div64,
+}
+
+impl LocalTimestampPrescaler {
+    /// The divisor of the processor clock.
+    pub fn divisor(self) -> u32 {
+        match self {
+            LocalTimestampPrescaler::Div1 => 1,
+            LocalTimestampPrescaler::Div4 => 4,
+            LocalTimestampPrescaler::Div16 => 16,
+            LocalTimestampPrescaler::Div64 => 64,
+        }
+    }
+}
+
+/// How often the ITM emits global timestamp packets.
+///
+/// Global timestamps are taken from the system wide timestamp counter, which is shared
+/// with other trace sources such as the ETM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GlobalTimestampFrequency {
+    /// Do not emit global timestamps.
+    Disabled,
+    /// Emit a global timestamp approximately every 128 cycles.
+    Every128Cycles,
+    /// Emit a global timestamp approximately every 8192 cycles.
+    Every8192Cycles,
+    /// Emit a global timestamp after every packet, if the output FIFO is empty.
+    EveryPacket,
+}
+
+/// The timestamp configuration of the ITM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ItmConfig {
+    /// The prescaler of local timestamps, or `None` if they are disabled.
+    local_timestamps: Option<LocalTimestampPrescaler>,
+
+    /// The frequency of global timestamps.
+    global_timestamps: GlobalTimestampFrequency,
+}
+
+impl Default for ItmConfig {
+    /// Local timestamps without prescaling, and a global timestamp every 8192 cycles.
+    fn default() -> Self {
+        ItmConfig {
+            local_timestamps: Some(LocalTimestampPrescaler::Div1),
+            global_timestamps: GlobalTimestampFrequency::Every8192Cycles,
+        }
+    }
+}
+
+impl ItmConfig {
+    /// Set the prescaler of local timestamps, or disable them with `None`.
+    pub fn set_local_timestamps(mut self, prescaler: Option<LocalTimestampPrescaler>) -> Self {
+        self.local_timestamps = prescaler;
+        self
+    }
+
+    /// Set the frequency of global timestamps.
+    pub fn set_global_timestamps(mut self, frequency: GlobalTimestampFrequency) -> Self {
+        self.global_timestamps = frequency;
+        self
+    }
+
+    /// The prescaler of local timestamps, or `None` if they are disabled.
+    pub fn local_timestamps(&self) -> Option<LocalTimestampPrescaler> {
+        self.local_timestamps
+    }
+
+    /// The frequency of global timestamps.
+    pub fn global_timestamps(&self) -> GlobalTimestampFrequency {
+        self.global_timestamps
+    }
+
+    /// The TSENA, TSPrescale and GTSFREQ fields of the ITM_TCR register.
+    fn tcr_timestamp_bits(&self) -> u32 {
+        let local = match self.local_timestamps {
+            None => 0,
+            Some(prescaler) => {
+                let tsprescale = match prescaler {
+                    LocalTimestampPrescaler::Div1 => 0b00,
+                    LocalTimestampPrescaler::Div4 => 0b01,
+                    LocalTimestampPrescaler::Div16 => 0b10,
+                    LocalTimestampPrescaler::Div64 => 0b11,
+                };
+                TCR_TSENA | tsprescale << 8
+            }
+        };
+
+        let gtsfreq = match self.global_timestamps {
+            GlobalTimestampFrequency::Disabled => 0b00,
+            GlobalTimestampFrequency::Every128Cycles => 0b01,
+            GlobalTimestampFrequency::Every8192Cycles => 0b10,
+            GlobalTimestampFrequency::EveryPacket => 0b11,
+        };
+
+        local | gtsfreq << 10
+    }
+}
+
 mod register {
     use crate::memory_mapped_bitfield_register;
 
@@ -137,3 +260,24 @@ mod register {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{GlobalTimestampFrequency, ItmConfig, LocalTimestampPrescaler};
+
+    #[test]
+    fn timestamp_bits() {
+        // The previously hardcoded configuration: TSENA and GTSFREQ every 8192 cycles.
+        assert_eq!(ItmConfig::default().tcr_timestamp_bits(), 1 << 1 | 1 << 11);
+
+        let config = ItmConfig::default()
+            .set_local_timestamps(Some(LocalTimestampPrescaler::Div16))
+            .set_global_timestamps(GlobalTimestampFrequency::Disabled);
+        assert_eq!(config.tcr_timestamp_bits(), 1 << 1 | 0b10 << 8);
+
+        let config = ItmConfig::default()
+            .set_local_timestamps(None)
+            .set_global_timestamps(GlobalTimestampFrequency::EveryPacket);
+        assert_eq!(config.tcr_timestamp_bits(), 0b11 << 10);
+    }
+}
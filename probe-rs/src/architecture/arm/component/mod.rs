@@ -2,28 +2,32 @@
 
 mod dwt;
 mod itm;
+mod mtb;
+mod pmu;
 mod scs;
 mod swo;
 mod tmc;
 mod tpiu;
 mod trace_funnel;
+mod trace_replicator;
 
-use super::ap::{GenericAp, MemoryAp};
 use super::memory::romtable::{CoresightComponent, PeripheralType, RomTableError};
-use super::memory::Component;
 use super::ArmError;
-use super::{ApAddress, ApInformation, DpAddress, MemoryApInformation};
+use super::DpAddress;
 use crate::architecture::arm::core::armv6m::Demcr;
 use crate::architecture::arm::{ArmProbeInterface, SwoConfig, SwoMode};
 use crate::{Core, Error, MemoryInterface, MemoryMappedRegister};
 
-pub use self::itm::Itm;
+pub use self::itm::{GlobalTimestampFrequency, Itm, ItmConfig, LocalTimestampPrescaler};
 pub use dwt::Dwt;
+pub use mtb::Mtb;
+pub use pmu::{events as pmu_events, Pmu, PmuCounters};
 pub use scs::Scs;
 pub use swo::Swo;
 pub use tmc::TraceMemoryController;
 pub use tpiu::Tpiu;
 pub use trace_funnel::TraceFunnel;
+pub use trace_replicator::TraceReplicator;
 
 /// Specifies the data sink (destination) for trace data.
 #[derive(Debug, Copy, Clone)]
@@ -41,6 +45,31 @@ pub enum TraceSink {
     TraceMemory,
 }
 
+/// A link component on the path of trace data from a source to a sink.
+///
+/// The links are identified by their base address, as found in the ROM table. Which funnel port
+/// a source is connected to, and which replicator port leads to a sink, depends on the SoC and is
+/// described in its reference manual.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TraceLink {
+    /// Enable the input ports set in `ports` of the trace funnel at `address`, and disable all
+    /// others.
+    Funnel {
+        /// The base address of the funnel.
+        address: u64,
+        /// The mask of the enabled input ports.
+        ports: u8,
+    },
+    /// Pass all trace data to master port `port` of the trace replicator at `address`, and block
+    /// it from the other port.
+    Replicator {
+        /// The base address of the replicator.
+        address: u64,
+        /// The master port which receives the trace data.
+        port: usize,
+    },
+}
+
 /// An error when operating a core ROM table component occurred.
 #[derive(thiserror::Error, Debug)]
 pub enum ComponentError {
@@ -107,48 +136,7 @@ pub fn get_arm_components(
     interface: &mut dyn ArmProbeInterface,
     dp: DpAddress,
 ) -> Result<Vec<CoresightComponent>, ArmError> {
-    let mut components = Vec::new();
-
-    for ap_index in 0..(interface.num_access_ports(dp)? as u8) {
-        let ap_information = interface
-            .ap_information(GenericAp::new(ApAddress { dp, ap: ap_index }))?
-            .clone();
-
-        let component = match ap_information {
-            ApInformation::MemoryAp(MemoryApInformation {
-                debug_base_address: 0,
-                ..
-            }) => Err(Error::Other(anyhow::anyhow!("AP has a base address of 0"))),
-            ApInformation::MemoryAp(MemoryApInformation {
-                address,
-                debug_base_address,
-                ..
-            }) => {
-                let ap = MemoryAp::new(address);
-                let mut memory = interface.memory_interface(ap)?;
-                let component = Component::try_parse(&mut *memory, debug_base_address)?;
-                Ok(CoresightComponent::new(component, ap))
-            }
-            ApInformation::Other { address, .. } => {
-                // Return an error, only possible to get Component from MemoryAP
-                Err(Error::Other(anyhow::anyhow!(
-                    "AP {:#x?} is not a MemoryAP, unable to get ARM component.",
-                    address
-                )))
-            }
-        };
-
-        match component {
-            Ok(component) => {
-                components.push(component);
-            }
-            Err(e) => {
-                tracing::info!("Not counting AP {} because of: {}", ap_index, e);
-            }
-        }
-    }
-
-    Ok(components)
+    interface.coresight_components(dp)
 }
 
 /// Goes through every component in the vector and tries to find the first component with the given type
@@ -164,6 +152,59 @@ pub fn find_component(
     Ok(component)
 }
 
+/// Goes through every component in the vector and tries to find the component with the given
+/// type at the given base address.
+pub fn find_component_at(
+    components: &[CoresightComponent],
+    peripheral_type: PeripheralType,
+    address: u64,
+) -> Result<&CoresightComponent, ArmError> {
+    let component = components
+        .iter()
+        .find_map(|component| {
+            component.iter().find(|component| {
+                let id = component.component.id();
+                id.peripheral_id().is_of_type(peripheral_type) && id.component_address() == address
+            })
+        })
+        .ok_or_else(|| RomTableError::ComponentNotFound(peripheral_type))?;
+
+    Ok(component)
+}
+
+/// Configure the trace funnels and replicators in `links` to route trace data from a source to
+/// a sink.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub fn configure_trace_route(
+    interface: &mut dyn ArmProbeInterface,
+    components: &[CoresightComponent],
+    links: &[TraceLink],
+) -> Result<(), ArmError> {
+    for link in links {
+        match *link {
+            TraceLink::Funnel { address, ports } => {
+                let mut funnel = TraceFunnel::new(
+                    interface,
+                    find_component_at(components, PeripheralType::TraceFunnel, address)?,
+                );
+                funnel.unlock()?;
+                funnel.enable_port(ports)?;
+            }
+            TraceLink::Replicator { address, port } => {
+                let mut replicator = TraceReplicator::new(
+                    interface,
+                    find_component_at(components, PeripheralType::TraceReplicator, address)?,
+                );
+                replicator.unlock()?;
+                replicator.route_to_port(port)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Configure the Trace Port Interface Unit
 ///
 /// # Note
@@ -207,6 +248,7 @@ pub(crate) fn setup_tracing(
     interface: &mut dyn ArmProbeInterface,
     components: &[CoresightComponent],
     sink: &TraceSink,
+    itm_config: &ItmConfig,
 ) -> Result<(), Error> {
     // Configure DWT
     let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
@@ -216,7 +258,7 @@ pub(crate) fn setup_tracing(
     // Configure ITM
     let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
     itm.unlock()?;
-    itm.tx_enable()?;
+    itm.tx_enable_with_config(itm_config)?;
 
     // Configure the trace destination.
     match sink {
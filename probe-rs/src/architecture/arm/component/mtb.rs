@@ -0,0 +1,172 @@
+//! Interface with the MTB (micro trace buffer).
+//!
+//! The MTB records the branches taken by a Cortex-M0+ core into a circular buffer in SRAM. Each
+//! branch is stored as a pair of words, the source and the destination address of the branch.
+//!
+//! See the CoreSight MTB-M0+ technical reference manual for details.
+
+use super::super::memory::romtable::CoresightComponent;
+use super::DebugComponentInterface;
+use crate::architecture::arm::{ArmError, ArmProbeInterface};
+use crate::debug::BranchRecord;
+use crate::memory_mapped_bitfield_register;
+
+/// A struct representing an MTB unit on target.
+pub struct Mtb<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+}
+
+impl<'a> Mtb<'a> {
+    /// Creates a new MTB component representation.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Mtb {
+            interface,
+            component,
+        }
+    }
+
+    /// Starts recording branches into the first `buffer_size` bytes of the SRAM of the MTB.
+    ///
+    /// The size has to be a power of two of at least 16 bytes, and the application must not use
+    /// this part of the SRAM.
+    pub fn enable(&mut self, buffer_size: u32) -> Result<(), ArmError> {
+        if !buffer_size.is_power_of_two() || buffer_size < 16 {
+            return Err(ArmError::Other(anyhow::anyhow!(
+                "The MTB buffer size has to be a power of two of at least 16 bytes, not {}",
+                buffer_size
+            )));
+        }
+
+        Position(0).store(self.component, self.interface)?;
+
+        let mut master = Master(0);
+        master.set_mask((buffer_size.trailing_zeros() - 4) as u8);
+        master.set_en(true);
+        master.store(self.component, self.interface)
+    }
+
+    /// Stops recording branches.
+    pub fn disable(&mut self) -> Result<(), ArmError> {
+        let mut master = Master::load(self.component, self.interface)?;
+        master.set_en(false);
+        master.store(self.component, self.interface)
+    }
+
+    /// Reads the recorded branches, oldest first.
+    ///
+    /// The MTB should be disabled, or the core halted, while the buffer is read.
+    pub fn read_branches(&mut self) -> Result<Vec<BranchRecord>, ArmError> {
+        let base = Base::load(self.component, self.interface)?.0 as u64;
+        let master = Master::load(self.component, self.interface)?;
+        let position = Position::load(self.component, self.interface)?;
+
+        let size = 1u64 << (master.mask() + 4);
+        // The pointer wraps within the buffer, which is aligned to its size.
+        let offset = position.pointer() as u64 * 8;
+        let buffer_start = base + (offset & !(size - 1));
+        let pointer = offset & (size - 1);
+
+        let mut buffer = vec![0; (size / 4) as usize];
+        self.interface
+            .memory_interface(self.component.ap)?
+            .read_32(buffer_start, &mut buffer)?;
+
+        // Until the buffer wrapped, it contains the packets up to the write pointer, afterwards
+        // the oldest packet is at the write pointer.
+        let (start, len) = if position.wrap() {
+            (pointer / 4, buffer.len())
+        } else {
+            (0, (pointer / 4) as usize)
+        };
+
+        let packets = buffer
+            .iter()
+            .cycle()
+            .skip(start as usize)
+            .take(len)
+            .copied()
+            .collect::<Vec<_>>();
+
+        Ok(decode_packets(&packets))
+    }
+}
+
+/// Decodes pairs of source and destination words into branches.
+fn decode_packets(words: &[u32]) -> Vec<BranchRecord> {
+    words
+        .chunks_exact(2)
+        .map(|packet| BranchRecord {
+            // Bit 0 of the source is the A bit, which marks exception entries and returns.
+            source: (packet[0] & !1) as u64,
+            destination: (packet[1] & !1) as u64,
+            // Bit 0 of the destination is the S bit, which marks the first packet after the
+            // trace was started.
+            trace_start: packet[1] & 1 != 0,
+        })
+        .collect()
+}
+
+memory_mapped_bitfield_register! {
+    pub struct Position(u32);
+    0x000, "MTB/POSITION",
+    impl From;
+    /// The packet in the buffer which is written next.
+    pub u32, pointer, set_pointer: 31, 3;
+    /// Set once the pointer wrapped around the end of the buffer.
+    pub wrap, set_wrap: 2;
+}
+
+impl DebugComponentInterface for Position {}
+
+memory_mapped_bitfield_register! {
+    pub struct Master(u32);
+    0x004, "MTB/MASTER",
+    impl From;
+    pub en, set_en: 31;
+    pub haltreq, set_haltreq: 9;
+    pub tstopen, set_tstopen: 6;
+    pub tstarten, set_tstarten: 5;
+    /// The size of the buffer is 2^(MASK + 4) bytes.
+    pub u8, mask, set_mask: 4, 0;
+}
+
+impl DebugComponentInterface for Master {}
+
+memory_mapped_bitfield_register! {
+    pub struct Base(u32);
+    0x00C, "MTB/BASE",
+    impl From;
+}
+
+impl DebugComponentInterface for Base {}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_packets;
+    use crate::debug::BranchRecord;
+
+    #[test]
+    fn packets_are_decoded() {
+        let branches = decode_packets(&[0x0000_0100, 0x0000_0201, 0x0000_0211, 0x0000_0150]);
+
+        assert_eq!(
+            branches,
+            vec![
+                BranchRecord {
+                    source: 0x100,
+                    destination: 0x200,
+                    trace_start: true,
+                },
+                BranchRecord {
+                    source: 0x210,
+                    destination: 0x150,
+                    trace_start: false,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Interface with the PMU (performance monitors unit) of Cortex-A cores.
+//!
+//! The PMU has a cycle counter and a number of event counters, which count architectural or
+//! implementation defined events, such as retired instructions or cache refills. It is the
+//! equivalent of the DWT counters of Cortex-M cores for application-class cores.
+//!
+//! The PMU is accessed through its external debug interface, so the counters can be configured
+//! and read while the core is running. Its base address is not discoverable in a portable way
+//! and has to be set as `pmu_base` of the core in the target description. The counters only
+//! count if non-invasive debug is enabled by the authentication signals of the SoC.
+//!
+//! See the ARM Architecture Reference Manuals for ARMv7-A (C12.8) and ARMv8-A (H8, I3).
+
+use crate::architecture::arm::ap::MemoryAp;
+use crate::architecture::arm::{ArmError, ArmProbeInterface};
+use crate::CoreType;
+
+/// Common architectural events, which can be counted by [`Pmu::start`].
+///
+/// The PMU implements a subset of these, and usually additional implementation defined events,
+/// which are listed in the technical reference manual of the core.
+pub mod events {
+    /// Software increment, counts writes to the software increment register.
+    pub const SW_INCR: u16 = 0x00;
+    /// Level 1 instruction cache refill.
+    pub const L1I_CACHE_REFILL: u16 = 0x01;
+    /// Level 1 data cache refill.
+    pub const L1D_CACHE_REFILL: u16 = 0x03;
+    /// Level 1 data cache access.
+    pub const L1D_CACHE: u16 = 0x04;
+    /// Instruction architecturally executed.
+    pub const INST_RETIRED: u16 = 0x08;
+    /// Exception taken.
+    pub const EXC_TAKEN: u16 = 0x09;
+    /// Mispredicted or not predicted branch speculatively executed.
+    pub const BR_MIS_PRED: u16 = 0x10;
+    /// Cycle.
+    pub const CPU_CYCLES: u16 = 0x11;
+    /// Predictable branch speculatively executed.
+    pub const BR_PRED: u16 = 0x12;
+    /// Data memory access.
+    pub const MEM_ACCESS: u16 = 0x13;
+}
+
+/// Values of the PMU counters, read by [`Pmu::read`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PmuCounters {
+    /// The value of the cycle counter.
+    pub cycles: u64,
+    /// The values of all event counters. Counter `n` counts the `n`-th event passed to
+    /// [`Pmu::start`].
+    pub events: Vec<u32>,
+    /// The overflow flags of the counters. Bit `n` is set if event counter `n` overflowed, and
+    /// bit 31 if the cycle counter overflowed.
+    pub overflow: u32,
+}
+
+/// A struct representing the PMU of a Cortex-A core.
+pub struct Pmu<'a> {
+    interface: &'a mut dyn ArmProbeInterface,
+    ap: MemoryAp,
+    base: u64,
+    core_type: CoreType,
+}
+
+impl<'a> Pmu<'a> {
+    /// Event counter registers, 4 bytes apart in ARMv7-A, and 8 bytes in ARMv8-A.
+    const PMEVCNTR: u64 = 0x000;
+    /// Cycle counter register in ARMv7-A, 32 bit.
+    const PMCCNTR_V7: u64 = 0x07C;
+    /// Cycle counter register in ARMv8-A, 64 bit.
+    const PMCCNTR_V8: u64 = 0x0F8;
+    /// Event type registers.
+    const PMEVTYPER: u64 = 0x400;
+    /// Cycle counter filter register, only in ARMv8-A.
+    const PMCCFILTR: u64 = 0x47C;
+    /// Count enable set register.
+    const PMCNTENSET: u64 = 0xC00;
+    /// Count enable clear register.
+    const PMCNTENCLR: u64 = 0xC20;
+    /// Overflow flag status clear register.
+    const PMOVSCLR: u64 = 0xC80;
+    /// Overflow flag status set register.
+    const PMOVSSET: u64 = 0xCC0;
+    /// Control register.
+    const PMCR: u64 = 0xE04;
+    /// Lock access register.
+    const PMLAR: u64 = 0xFB0;
+
+    /// PMCR.E, enables all counters.
+    const PMCR_E: u32 = 1 << 0;
+    /// PMCR.P, resets the event counters.
+    const PMCR_P: u32 = 1 << 1;
+    /// PMCR.C, resets the cycle counter.
+    const PMCR_C: u32 = 1 << 2;
+    /// The bit of the cycle counter in the enable and overflow registers.
+    const CYCLE_COUNTER: u32 = 1 << 31;
+
+    /// The key which unlocks the registers when written to PMLAR.
+    const UNLOCK_KEY: u32 = 0xC5AC_CE55;
+
+    /// Creates a new PMU representation, for the PMU at `base` in the memory of `ap`.
+    ///
+    /// Use [`Session::pmu`](crate::Session::pmu) to get the PMU of a core of the target.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        ap: MemoryAp,
+        base: u64,
+        core_type: CoreType,
+    ) -> Result<Self, ArmError> {
+        if !matches!(core_type, CoreType::Armv7a | CoreType::Armv8a) {
+            return Err(ArmError::ArchitectureRequired(&["ARMv7-A", "ARMv8-A"]));
+        }
+
+        Ok(Pmu {
+            interface,
+            ap,
+            base,
+            core_type,
+        })
+    }
+
+    /// The number of event counters of the PMU.
+    pub fn num_event_counters(&mut self) -> Result<usize, ArmError> {
+        let pmcr = self.read_reg(Self::PMCR)?;
+
+        Ok(((pmcr >> 11) & 0x1F) as usize)
+    }
+
+    /// Reset all counters, and start counting cycles and the given `events`, one event counter
+    /// per event.
+    ///
+    /// The event numbers are listed in [`events`] and in the technical reference manual of the
+    /// core.
+    pub fn start(&mut self, events: &[u16]) -> Result<(), ArmError> {
+        self.write_reg(Self::PMLAR, Self::UNLOCK_KEY)?;
+
+        let num_counters = self.num_event_counters()?;
+        if events.len() > num_counters {
+            return Err(ArmError::Other(anyhow::anyhow!(
+                "{} events were requested, but the PMU only has {} event counters",
+                events.len(),
+                num_counters
+            )));
+        }
+
+        let pmcr = self.read_reg(Self::PMCR)?;
+        self.write_reg(Self::PMCR, pmcr & !Self::PMCR_E)?;
+        self.write_reg(Self::PMCNTENCLR, u32::MAX)?;
+
+        for (counter, &event) in events.iter().enumerate() {
+            self.write_reg(Self::PMEVTYPER + 4 * counter as u64, event.into())?;
+        }
+
+        if self.core_type == CoreType::Armv8a {
+            // Count cycles in all exception levels.
+            self.write_reg(Self::PMCCFILTR, 0)?;
+        }
+
+        let enabled = (1 << events.len()) - 1;
+
+        self.write_reg(Self::PMOVSCLR, u32::MAX)?;
+        self.write_reg(Self::PMCNTENSET, Self::CYCLE_COUNTER | enabled)?;
+        self.write_reg(
+            Self::PMCR,
+            pmcr | Self::PMCR_E | Self::PMCR_P | Self::PMCR_C,
+        )
+    }
+
+    /// Stop all counters. Their values are kept, and can still be read with [`Pmu::read`].
+    pub fn stop(&mut self) -> Result<(), ArmError> {
+        self.write_reg(Self::PMCNTENCLR, u32::MAX)
+    }
+
+    /// Read the cycle counter and all event counters.
+    pub fn read(&mut self) -> Result<PmuCounters, ArmError> {
+        let num_events = self.num_event_counters()? as u64;
+
+        let (cycles, stride) = match self.core_type {
+            CoreType::Armv8a => {
+                let low = self.read_reg(Self::PMCCNTR_V8)?;
+                let high = self.read_reg(Self::PMCCNTR_V8 + 4)?;
+                (((high as u64) << 32) | low as u64, 8)
+            }
+            _ => (self.read_reg(Self::PMCCNTR_V7)?.into(), 4),
+        };
+
+        let events = (0..num_events)
+            .map(|counter| self.read_reg(Self::PMEVCNTR + stride * counter))
+            .collect::<Result<_, _>>()?;
+
+        Ok(PmuCounters {
+            cycles,
+            events,
+            overflow: self.read_reg(Self::PMOVSSET)?,
+        })
+    }
+
+    fn read_reg(&mut self, offset: u64) -> Result<u32, ArmError> {
+        self.interface
+            .memory_interface(self.ap)?
+            .read_word_32(self.base + offset)
+    }
+
+    fn write_reg(&mut self, offset: u64, value: u32) -> Result<(), ArmError> {
+        self.interface
+            .memory_interface(self.ap)?
+            .write_word_32(self.base + offset, value)
+    }
+}
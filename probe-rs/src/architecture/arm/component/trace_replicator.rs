@@ -0,0 +1,108 @@
+//! Arm trace replicator CoreSight Component
+//!
+//! # Description
+//! This module provides access and control of the trace replicator CoreSight component block.
+use super::DebugComponentInterface;
+use crate::architecture::arm::memory::romtable::CoresightComponent;
+use crate::architecture::arm::{ArmError, ArmProbeInterface};
+use crate::{memory_mapped_bitfield_register, MemoryMappedRegister};
+
+const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
+
+/// The number of master ports of a replicator.
+const MASTER_PORTS: usize = 2;
+
+/// Trace replicator unit
+///
+/// The replicator passes the trace data of its input to two master ports, for example the TPIU
+/// and the ETB. The trace IDs passed to each port can be filtered.
+pub struct TraceReplicator<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+}
+
+impl<'a> TraceReplicator<'a> {
+    /// Construct a new TraceReplicator component.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        TraceReplicator {
+            component,
+            interface,
+        }
+    }
+
+    /// Unlock the replicator and enable it for tracing the target.
+    pub fn unlock(&mut self) -> Result<(), ArmError> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_ACCESS, 0xC5AC_CE55)?;
+
+        Ok(())
+    }
+
+    /// Set the trace ID filter of master `port`.
+    ///
+    /// # Note
+    /// Each set bit `n` of `filter` blocks the trace IDs `0x{n}0` to `0x{n}F` from the port, so
+    /// `0x00` passes all trace data and `0xFF` blocks all of it.
+    pub fn set_id_filter(&mut self, port: usize, filter: u8) -> Result<(), ArmError> {
+        let mut id_filter = IdFilter::load_port(self.component, self.interface, port)?;
+        id_filter.set_filter(filter);
+        id_filter.store_port(self.component, self.interface, port)
+    }
+
+    /// Pass all trace data to master `port`, and block it from the other port.
+    pub fn route_to_port(&mut self, port: usize) -> Result<(), ArmError> {
+        if port >= MASTER_PORTS {
+            return Err(ArmError::Other(anyhow::anyhow!(
+                "Trace replicators have no master port {port}"
+            )));
+        }
+
+        for other in (0..MASTER_PORTS).filter(|&other| other != port) {
+            self.set_id_filter(other, 0xFF)?;
+        }
+        self.set_id_filter(port, 0x00)
+    }
+}
+
+memory_mapped_bitfield_register! {
+    /// The ID filter register of master port 0 is described in "DDI0480G CoreSight SoC-400
+    /// Technical Reference Manual" on page 3-160, the one of port 1 follows at offset 0x04.
+    #[derive(Default)]
+    pub struct IdFilter(u32);
+    0x00, "REPLICATOR/IDFILTER",
+    impl From;
+
+    /// Each set bit blocks a range of 16 trace IDs from the master port.
+    pub u8, filter, set_filter: 7, 0;
+}
+
+impl DebugComponentInterface for IdFilter {}
+
+impl IdFilter {
+    fn load_port(
+        component: &CoresightComponent,
+        interface: &mut dyn ArmProbeInterface,
+        port: usize,
+    ) -> Result<Self, ArmError> {
+        Ok(Self::from(component.read_reg(
+            interface,
+            Self::ADDRESS_OFFSET as u32 + 4 * port as u32,
+        )?))
+    }
+
+    fn store_port(
+        &self,
+        component: &CoresightComponent,
+        interface: &mut dyn ArmProbeInterface,
+        port: usize,
+    ) -> Result<(), ArmError> {
+        component.write_reg(
+            interface,
+            Self::ADDRESS_OFFSET as u32 + 4 * port as u32,
+            (*self).into(),
+        )
+    }
+}
@@ -1,11 +1,11 @@
 //! Register types and the core interface for armv6-M
 
-use super::{registers::cortex_m::*, CortexMState, Dfsr};
+use super::{cortex_m::DwtVersion, registers::cortex_m::*, CortexMState, Dfsr};
 use crate::{
     architecture::arm::{
         memory::adi_v5_memory_interface::ArmProbe, sequences::ArmDebugSequence, ArmError,
     },
-    core::{CoreRegisters, RegisterId, RegisterValue},
+    core::{CoreRegisters, RegisterId, RegisterValue, Watchpoint},
     error::Error,
     memory::valid_32bit_address,
     Architecture, CoreInformation, CoreInterface, CoreRegister, CoreStatus, CoreType,
@@ -449,6 +449,7 @@ impl<'probe> Armv6m<'probe> {
 
             state.current_state = core_state;
             state.initialize();
+            state.save_demcr(&mut *memory)?;
         }
 
         Ok(Self {
@@ -460,6 +461,12 @@ impl<'probe> Armv6m<'probe> {
     }
 
     fn set_core_status(&mut self, new_status: CoreStatus) {
+        if !(new_status.is_halted() && self.state.current_state.is_halted()) {
+            // The registers change while the core is not halted, so only a core which stays
+            // halted keeps them.
+            self.state.register_cache.invalidate();
+        }
+
         super::update_core_status(&mut self.memory, &mut self.state.current_state, new_status);
     }
 }
@@ -470,6 +477,8 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         let start = Instant::now();
 
         while start.elapsed() < timeout {
+            crate::cancellation::check()?;
+
             if self.core_halted()? {
                 return Ok(());
             }
@@ -490,6 +499,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
     fn status(&mut self) -> Result<crate::core::CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        self.state.check_reset(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             tracing::warn!(
@@ -513,6 +523,12 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         // TODO: Handle lockup
 
         if dhcsr.s_halt() {
+            if dhcsr.s_retire_st() || dhcsr.s_reset_st() {
+                // The core executed instructions or was reset since DHCSR was read last,
+                // so this is a new halt, even if we expected the core to be halted.
+                self.state.register_cache.invalidate();
+            }
+
             let dfsr = Dfsr(self.memory.read_word_32(Dfsr::get_mmio_address())?);
 
             let reason = dfsr.halt_reason();
@@ -539,6 +555,9 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
                 );
             }
 
+            let reason =
+                super::cortex_m::resolve_halt_reason(&mut *self.memory, DwtVersion::V1, reason)?;
+
             self.set_core_status(CoreStatus::Halted(reason));
 
             return Ok(CoreStatus::Halted(reason));
@@ -579,6 +598,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     fn run(&mut self) -> Result<(), Error> {
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
+        self.state.register_cache.flush(&mut *self.memory)?;
 
         let mut value = Dhcsr(0);
         value.set_c_halt(false);
@@ -597,6 +617,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     fn reset(&mut self) -> Result<(), Error> {
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv6m, None)?;
+        self.state.register_cache.invalidate();
         Ok(())
     }
 
@@ -605,6 +626,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv6m, None)?;
+        self.state.register_cache.invalidate();
 
         // Update core status
         let _ = self.status()?;
@@ -640,20 +662,12 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
             false
         };
 
-        let mut value = Dhcsr(0);
-        // Leave halted state.
-        // Step one instruction.
-        value.set_c_step(true);
-        value.set_c_halt(false);
-        value.set_c_debugen(true);
-        value.set_c_maskints(true);
-        value.enable_write();
-
-        self.memory
-            .write_word_32(Dhcsr::get_mmio_address(), value.into())?;
-        self.memory.flush()?;
+        // The step changes the registers, so write back the dirty ones and read them again after the step.
+        self.state.register_cache.flush(&mut *self.memory)?;
+        self.state.register_cache.invalidate();
 
-        self.wait_for_core_halted(Duration::from_millis(100))?;
+        let reason = super::cortex_m::step_and_wait(&mut *self.memory, Duration::from_millis(100))?;
+        self.set_core_status(CoreStatus::Halted(reason));
 
         // Try to read the new program counter.
         let mut pc_after_step = self.read_core_reg(self.program_counter().into())?;
@@ -680,28 +694,32 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     }
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
-        if self.state.current_state.is_halted() {
-            let val = super::cortex_m::read_core_reg(&mut *self.memory, address)?;
-            Ok(val.into())
-        } else {
-            Err(Error::Arm(ArmError::CoreNotHalted))
-        }
+        let values =
+            super::cortex_m::read_halted_core_regs(&mut *self.memory, self.state, &[address])?;
+        Ok(values[0])
     }
 
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
-        if self.state.current_state.is_halted() {
-            super::cortex_m::write_core_reg(&mut *self.memory, address, value.try_into()?)
-        } else {
-            Err(Error::Arm(ArmError::CoreNotHalted))
-        }
+        super::cortex_m::write_halted_core_regs(&mut *self.memory, self.state, &[(address, value)])
     }
 
-    fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
-        let result = self.memory.read_word_32(BpCtrl::get_mmio_address())?;
+    fn read_core_regs(&mut self, addresses: &[RegisterId]) -> Result<Vec<RegisterValue>, Error> {
+        super::cortex_m::read_halted_core_regs(&mut *self.memory, self.state, addresses)
+    }
 
-        let register = BpCtrl::from(result);
+    fn write_core_regs(&mut self, registers: &[(RegisterId, RegisterValue)]) -> Result<(), Error> {
+        super::cortex_m::write_halted_core_regs(&mut *self.memory, self.state, registers)
+    }
 
-        Ok(register.num_code())
+    fn flush_registers(&mut self) -> Result<(), Error> {
+        self.state.register_cache.flush(&mut *self.memory)
+    }
+
+    fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
+        let capabilities = self.state.capabilities(&mut *self.memory)?;
+
+        capabilities.fpb_version()?;
+        Ok(capabilities.breakpoint_comparators)
     }
 
     /// See docs on the [`CoreInterface::hw_breakpoints`] trait
@@ -744,9 +762,9 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
         // The highest 3 bits of the address have to be zero, otherwise the breakpoint cannot
         // be set at the address.
-        if addr >= 0x2000_0000 {
-            return Err(Error::Probe(DebugProbeError::Other(anyhow::anyhow!("Unsupported address {:#08x} for HW breakpoint. Breakpoint must be at address < 0x2000_0000.", addr))));
-        }
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_breakpoint(bp_register_index, Some(addr))?;
 
         let mut value = BpCompx(0);
         if addr % 4 < 2 {
@@ -768,6 +786,10 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     }
 
     fn clear_hw_breakpoint(&mut self, bp_unit_index: usize) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_breakpoint(bp_unit_index, None)?;
+
         let register_addr = BpCompx::get_mmio_address() + (bp_unit_index * size_of::<u32>()) as u64;
 
         let mut value = BpCompx::from(0);
@@ -778,6 +800,49 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        let capabilities = self.state.capabilities(&mut *self.memory)?;
+
+        Ok(capabilities.watchpoint_comparators)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        super::cortex_m::hw_watchpoints(&mut *self.memory, DwtVersion::V1)
+    }
+
+    fn free_watchpoint_units(&mut self) -> Result<Vec<bool>, Error> {
+        super::cortex_m::free_watchpoint_units(&mut *self.memory)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_watchpoint(unit_index)?;
+
+        super::cortex_m::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::V1,
+            unit_index,
+            watchpoint,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_watchpoint(unit_index)?;
+
+        super::cortex_m::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
+    fn triggered_hw_watchpoint(&mut self) -> Result<Option<Watchpoint>, Error> {
+        super::cortex_m::triggered_hw_watchpoint(&mut *self.memory, DwtVersion::V1)
+    }
+
     fn registers(&self) -> &'static CoreRegisters {
         &CORTEX_M_CORE_REGSISTERS
     }
@@ -828,6 +893,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
         self.sequence
             .reset_catch_set(&mut *self.memory, CoreType::Armv6m, None)?;
+        self.state.save_demcr(&mut *self.memory)?;
 
         Ok(())
     }
@@ -836,12 +902,33 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     fn reset_catch_clear(&mut self) -> Result<(), Error> {
         self.sequence
             .reset_catch_clear(&mut *self.memory, CoreType::Armv6m, None)?;
+        self.state.save_demcr(&mut *self.memory)?;
 
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    fn fault_catch_set(&mut self, enabled: bool) -> Result<(), Error> {
+        super::cortex_m::set_fault_catch(&mut *self.memory, false, enabled)?;
+        self.state.save_demcr(&mut *self.memory)?;
+        Ok(())
+    }
+
+    fn take_target_reset(&mut self) -> bool {
+        self.state.take_target_reset()
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn restore_debug_settings(&mut self) -> Result<(), Error> {
+        self.state.restore_debug_settings(&mut *self.memory)?;
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     fn debug_core_stop(&mut self) -> Result<(), Error> {
+        self.state.register_cache.flush(&mut *self.memory)?;
+        self.state.register_cache.invalidate();
+
         self.sequence
             .debug_core_stop(&mut *self.memory, CoreType::Armv6m)?;
 
@@ -313,6 +313,8 @@ impl<'probe> CoreInterface for Armv7a<'probe> {
         let address = Dbgdscr::get_mmio_address_from_base(self.base_address)?;
 
         while start.elapsed() < timeout {
+            crate::cancellation::check()?;
+
             let dbgdscr = Dbgdscr(self.memory.read_word_32(address)?);
             if dbgdscr.halted() {
                 return Ok(());
@@ -1,7 +1,7 @@
 //! Register types and the core interface for armv7-M
 
 use super::{
-    cortex_m::Mvfr0,
+    cortex_m::{DwtVersion, FpbVersion, Mvfr0},
     registers::cortex_m::{
         CORTEX_M_CORE_REGSISTERS, CORTEX_M_WITH_FP_CORE_REGSISTERS, FP, PC, RA, SP,
     },
@@ -14,7 +14,7 @@ use crate::{
     },
     core::{
         Architecture, CoreInformation, CoreInterface, CoreRegisters, CoreStatus, HaltReason,
-        MemoryMappedRegister, RegisterId, RegisterValue,
+        MemoryMappedRegister, RegisterId, RegisterValue, Watchpoint,
     },
     error::Error,
     memory::valid_32bit_address,
@@ -632,6 +632,7 @@ impl<'probe> Armv7m<'probe> {
             state.fp_present = Mvfr0(memory.read_word_32(Mvfr0::get_mmio_address())?).fp_present();
 
             state.initialize();
+            state.save_demcr(&mut *memory)?;
         }
 
         Ok(Self {
@@ -643,6 +644,12 @@ impl<'probe> Armv7m<'probe> {
     }
 
     fn set_core_status(&mut self, new_status: CoreStatus) {
+        if !(new_status.is_halted() && self.state.current_state.is_halted()) {
+            // The registers change while the core is not halted, so only a core which stays
+            // halted keeps them.
+            self.state.register_cache.invalidate();
+        }
+
         super::update_core_status(&mut self.memory, &mut self.state.current_state, new_status);
     }
 }
@@ -653,6 +660,8 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         let start = Instant::now();
 
         while !self.core_halted()? {
+            crate::cancellation::check()?;
+
             if start.elapsed() < timeout {
                 // Wait a bit before polling again.
                 std::thread::sleep(Duration::from_millis(1));
@@ -669,6 +678,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     fn status(&mut self) -> Result<CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        self.state.check_reset(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             tracing::error!(
@@ -692,6 +702,12 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         }
 
         if dhcsr.s_halt() {
+            if dhcsr.s_retire_st() || dhcsr.s_reset_st() {
+                // The core executed instructions or was reset since DHCSR was read last,
+                // so this is a new halt, even if we expected the core to be halted.
+                self.state.register_cache.invalidate();
+            }
+
             let dfsr = Dfsr(self.memory.read_word_32(Dfsr::get_mmio_address())?);
 
             let reason = dfsr.halt_reason();
@@ -718,6 +734,9 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
                 );
             }
 
+            let reason =
+                super::cortex_m::resolve_halt_reason(&mut *self.memory, DwtVersion::V1, reason)?;
+
             self.set_core_status(CoreStatus::Halted(reason));
 
             return Ok(CoreStatus::Halted(reason));
@@ -757,6 +776,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     fn run(&mut self) -> Result<(), Error> {
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
+        self.state.register_cache.flush(&mut *self.memory)?;
 
         let mut dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
 
@@ -786,6 +806,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     fn reset(&mut self) -> Result<(), Error> {
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv7m, None)?;
+        self.state.register_cache.invalidate();
         Ok(())
     }
 
@@ -796,6 +817,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv7m, None)?;
+        self.state.register_cache.invalidate();
 
         // Update core status
         let _ = self.status()?;
@@ -831,30 +853,12 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
             false
         };
 
-        let mut dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        // The step changes the registers, so write back the dirty ones and read them again after the step.
+        self.state.register_cache.flush(&mut *self.memory)?;
+        self.state.register_cache.invalidate();
 
-        // Follow the rules of the ... ARMv7-M Architecture reference, C1.6 Debug System Registers - DHCSR, with respect to setting maskints
-        if !dhcsr.c_debugen() {
-            tracing::warn!("Attempting to STEP while DHCSR->C_DEBUGEN is false");
-        }
-        if !dhcsr.c_maskints() {
-            dhcsr.set_c_maskints(true); // This must be reset to false when we run() again.
-            dhcsr.enable_write();
-            self.memory
-                .write_word_32(Dhcsr::get_mmio_address(), dhcsr.into())?;
-            self.memory.flush()?;
-        }
-
-        // Leave halted state.
-        // Step one instruction.
-        dhcsr.set_c_step(true);
-        dhcsr.set_c_halt(false);
-        dhcsr.enable_write();
-        self.memory
-            .write_word_32(Dhcsr::get_mmio_address(), dhcsr.into())?;
-        self.memory.flush()?;
-
-        self.wait_for_core_halted(Duration::from_millis(100))?;
+        let reason = super::cortex_m::step_and_wait(&mut *self.memory, Duration::from_millis(100))?;
+        self.set_core_status(CoreStatus::Halted(reason));
 
         // Try to read the new program counter.
         let mut pc_after_step = self.read_core_reg(self.program_counter().into())?;
@@ -881,66 +885,59 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     }
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
-        if self.state.current_state.is_halted() {
-            let val = super::cortex_m::read_core_reg(&mut *self.memory, address)?;
-            Ok(val.into())
-        } else {
-            Err(Error::Arm(ArmError::CoreNotHalted))
-        }
+        let values =
+            super::cortex_m::read_halted_core_regs(&mut *self.memory, self.state, &[address])?;
+        Ok(values[0])
     }
 
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
-        if self.state.current_state.is_halted() {
-            super::cortex_m::write_core_reg(&mut *self.memory, address, value.try_into()?)
-        } else {
-            Err(Error::Arm(ArmError::CoreNotHalted))
-        }
+        super::cortex_m::write_halted_core_regs(&mut *self.memory, self.state, &[(address, value)])
     }
 
-    fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
-        let raw_val = self.memory.read_word_32(FpCtrl::get_mmio_address())?;
+    fn read_core_regs(&mut self, addresses: &[RegisterId]) -> Result<Vec<RegisterValue>, Error> {
+        super::cortex_m::read_halted_core_regs(&mut *self.memory, self.state, addresses)
+    }
 
-        let reg = FpCtrl::from(raw_val);
+    fn write_core_regs(&mut self, registers: &[(RegisterId, RegisterValue)]) -> Result<(), Error> {
+        super::cortex_m::write_halted_core_regs(&mut *self.memory, self.state, registers)
+    }
 
-        if reg.rev() == 0 || reg.rev() == 1 {
-            Ok(reg.num_code())
-        } else {
-            tracing::warn!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", reg.rev());
-            Err(Error::Probe(DebugProbeError::CommandNotSupportedByProbe(
-                "get_available_breakpoint_units",
-            )))
-        }
+    fn flush_registers(&mut self) -> Result<(), Error> {
+        self.state.register_cache.flush(&mut *self.memory)
+    }
+
+    fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
+        let capabilities = self.state.capabilities(&mut *self.memory)?;
+
+        capabilities.fpb_version()?;
+        Ok(capabilities.breakpoint_comparators)
     }
 
     /// See docs on the [`CoreInterface::hw_breakpoints`] trait.
     fn hw_breakpoints(&mut self) -> Result<Vec<Option<u64>>, Error> {
+        let capabilities = self.state.capabilities(&mut *self.memory)?;
+        let version = capabilities.fpb_version()?;
+
         let mut breakpoints = vec![];
-        let num_hw_breakpoints = self.available_breakpoint_units()? as usize;
-        { 0..num_hw_breakpoints }.try_for_each(|bp_unit_index| {
-            let raw_val = self.memory.read_word_32(FpCtrl::get_mmio_address())?;
-            let ctrl_reg = FpCtrl::from(raw_val);
+        for bp_unit_index in 0..capabilities.breakpoint_comparators as usize {
             // FpRev1 and FpRev2 needs different decoding of the register value, but the location where we read from is the same ...
-            let reg_addr = FpRev1CompX::get_mmio_address() + (bp_unit_index * size_of::<u32>()) as u64;
+            let reg_addr =
+                FpRev1CompX::get_mmio_address() + (bp_unit_index * size_of::<u32>()) as u64;
             // The raw breakpoint address as read from memory.
             let register_value = self.memory.read_word_32(reg_addr)?;
-            // The breakpoint address after it has been adjusted for FpRev 1 or 2.
-            let breakpoint:u32;
+
+            // We only care about `enabled` breakpoints.
             if register_value & 0b1 == 0b1 {
-                // We only care about `enabled` breakpoints.
-                if ctrl_reg.rev() == 0 {
-                    breakpoint = FpRev1CompX::get_breakpoint_comparator(register_value)?;
-                } else if ctrl_reg.rev() == 1 {
-                    breakpoint = FpRev2CompX::from(register_value).bpaddr() << 1;
-                } else {
-                    tracing::warn!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", ctrl_reg.rev());
-                    return Err(Error::Other(anyhow!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", ctrl_reg.rev())));
-                }
+                // The breakpoint address after it has been adjusted for FpRev 1 or 2.
+                let breakpoint = match version {
+                    FpbVersion::V1 => FpRev1CompX::get_breakpoint_comparator(register_value)?,
+                    FpbVersion::V2 => FpRev2CompX::from(register_value).bpaddr() << 1,
+                };
                 breakpoints.push(Some(breakpoint as u64));
             } else {
                 breakpoints.push(None);
             }
-            Ok(())
-        })?;
+        }
         Ok(breakpoints)
     }
 
@@ -969,18 +966,17 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
             )));
         }
 
-        let raw_val = self.memory.read_word_32(FpCtrl::get_mmio_address())?;
-        let ctrl_reg = FpCtrl::from(raw_val);
+        // Version 1 of the FPB can only break in the code region, see
+        // `FpRev1CompX::breakpoint_configuration`.
+        let version = self
+            .state
+            .capabilities(&mut *self.memory)?
+            .check_breakpoint(bp_unit_index, None)?;
 
-        let val: u32;
-        if ctrl_reg.rev() == 0 {
-            val = FpRev1CompX::breakpoint_configuration(addr)?.into();
-        } else if ctrl_reg.rev() == 1 {
-            val = FpRev2CompX::breakpoint_configuration(addr).into();
-        } else {
-            tracing::warn!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", ctrl_reg.rev());
-            return Err(Error::Other(anyhow!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", ctrl_reg.rev())));
-        }
+        let val: u32 = match version {
+            FpbVersion::V1 => FpRev1CompX::breakpoint_configuration(addr)?.into(),
+            FpbVersion::V2 => FpRev2CompX::breakpoint_configuration(addr).into(),
+        };
 
         // This is fine as FpRev1CompX and Rev2CompX are just two different
         // interpretations of the same memory region as Rev2 can handle bigger
@@ -993,6 +989,10 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     }
 
     fn clear_hw_breakpoint(&mut self, bp_unit_index: usize) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_breakpoint(bp_unit_index, None)?;
+
         let mut val = FpRev1CompX::from(0);
         val.set_enable(false);
 
@@ -1003,6 +1003,49 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        let capabilities = self.state.capabilities(&mut *self.memory)?;
+
+        Ok(capabilities.watchpoint_comparators)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        super::cortex_m::hw_watchpoints(&mut *self.memory, DwtVersion::V1)
+    }
+
+    fn free_watchpoint_units(&mut self) -> Result<Vec<bool>, Error> {
+        super::cortex_m::free_watchpoint_units(&mut *self.memory)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_watchpoint(unit_index)?;
+
+        super::cortex_m::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::V1,
+            unit_index,
+            watchpoint,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_watchpoint(unit_index)?;
+
+        super::cortex_m::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
+    fn triggered_hw_watchpoint(&mut self) -> Result<Option<Watchpoint>, Error> {
+        super::cortex_m::triggered_hw_watchpoint(&mut *self.memory, DwtVersion::V1)
+    }
+
     fn registers(&self) -> &'static CoreRegisters {
         if self.state.fp_present {
             &CORTEX_M_WITH_FP_CORE_REGSISTERS
@@ -1055,6 +1098,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     fn reset_catch_set(&mut self) -> Result<(), Error> {
         self.sequence
             .reset_catch_set(&mut *self.memory, CoreType::Armv7m, None)?;
+        self.state.save_demcr(&mut *self.memory)?;
 
         Ok(())
     }
@@ -1063,12 +1107,33 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     fn reset_catch_clear(&mut self) -> Result<(), Error> {
         self.sequence
             .reset_catch_clear(&mut *self.memory, CoreType::Armv7m, None)?;
+        self.state.save_demcr(&mut *self.memory)?;
+
+        Ok(())
+    }
 
+    #[tracing::instrument(skip(self))]
+    fn fault_catch_set(&mut self, enabled: bool) -> Result<(), Error> {
+        super::cortex_m::set_fault_catch(&mut *self.memory, true, enabled)?;
+        self.state.save_demcr(&mut *self.memory)?;
+        Ok(())
+    }
+
+    fn take_target_reset(&mut self) -> bool {
+        self.state.take_target_reset()
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn restore_debug_settings(&mut self) -> Result<(), Error> {
+        self.state.restore_debug_settings(&mut *self.memory)?;
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
     fn debug_core_stop(&mut self) -> Result<(), Error> {
+        self.state.register_cache.flush(&mut *self.memory)?;
+        self.state.register_cache.invalidate();
+
         self.sequence
             .debug_core_stop(&mut *self.memory, CoreType::Armv7m)?;
 
@@ -1191,3 +1256,272 @@ fn unsupported_breakpoint_address() {
 
     FpRev1CompX::breakpoint_configuration(address).unwrap_err();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Armv7m, Demcr, Dhcsr};
+    use crate::architecture::arm::ap::MemoryAp;
+    use crate::architecture::arm::communication_interface::{Initialized, SwdSequence};
+    use crate::architecture::arm::core::mock::MockCortexM;
+    use crate::architecture::arm::core::CortexMState;
+    use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
+    use crate::architecture::arm::sequences::DefaultArmSequence;
+    use crate::architecture::arm::{ApAddress, ArmCommunicationInterface, ArmError, DpAddress};
+    use crate::core::{CoreInterface, MemoryMappedRegister, RegisterId};
+    use crate::{Core, DebugProbeError, Error};
+    use anyhow::anyhow;
+    use std::time::Duration;
+
+    /// Memory which keeps the written words, and reads zero elsewhere.
+    #[derive(Default)]
+    struct WordMemory {
+        words: HashMap<u64, u32>,
+    }
+
+    impl SwdSequence for WordMemory {
+        fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
+            unimplemented!()
+        }
+
+        fn swj_pins(
+            &mut self,
+            _pin_out: u32,
+            _pin_select: u32,
+            _pin_wait: u32,
+        ) -> Result<u32, DebugProbeError> {
+            unimplemented!()
+        }
+    }
+
+    impl ArmProbe for WordMemory {
+        fn read_8(&mut self, _address: u64, _data: &mut [u8]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+            for (i, word) in data.iter_mut().enumerate() {
+                *word = self
+                    .words
+                    .get(&(address + 4 * i as u64))
+                    .copied()
+                    .unwrap_or(0);
+            }
+            Ok(())
+        }
+
+        fn read_64(&mut self, _address: u64, _data: &mut [u64]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn write_8(&mut self, _address: u64, _data: &[u8]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError> {
+            for (i, word) in data.iter().enumerate() {
+                self.words.insert(address + 4 * i as u64, *word);
+            }
+            Ok(())
+        }
+
+        fn write_64(&mut self, _address: u64, _data: &[u64]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn flush(&mut self) -> Result<(), ArmError> {
+            Ok(())
+        }
+
+        fn supports_native_64bit_access(&mut self) -> bool {
+            false
+        }
+
+        fn supports_8bit_transfers(&self) -> Result<bool, ArmError> {
+            Ok(false)
+        }
+
+        fn ap(&mut self) -> MemoryAp {
+            MemoryAp::new(ApAddress {
+                dp: DpAddress::Default,
+                ap: 0,
+            })
+        }
+
+        fn get_arm_communication_interface(
+            &mut self,
+        ) -> Result<&mut ArmCommunicationInterface<Initialized>, DebugProbeError> {
+            Err(DebugProbeError::NotImplemented(
+                "get_arm_communication_interface",
+            ))
+        }
+    }
+
+    #[test]
+    fn target_reset_restores_debug_settings() {
+        let dhcsr = Dhcsr::get_mmio_address();
+        let demcr = Demcr::get_mmio_address();
+
+        let mut memory = WordMemory::default();
+        memory.words.insert(dhcsr, 0x0000_0001);
+        // TRCENA and VC_HARDERR, as configured when attaching.
+        memory.words.insert(demcr, 0x0100_0400);
+
+        let mut state = CortexMState::new();
+        let mut core = Armv7m::new(
+            Box::new(memory),
+            &mut state,
+            DefaultArmSequence::create(),
+            0,
+        )
+        .unwrap();
+
+        core.status().unwrap();
+        assert!(!core.take_target_reset());
+
+        // A watchdog reset of the whole chip, which also reset the debug logic.
+        core.memory.write_word_32(dhcsr, 0x0200_0000).unwrap();
+        core.memory.write_word_32(demcr, 0).unwrap();
+
+        core.status().unwrap();
+        assert!(core.take_target_reset());
+        assert!(!core.take_target_reset());
+
+        core.restore_debug_settings().unwrap();
+        let dhcsr = Dhcsr(core.memory.read_word_32(dhcsr).unwrap());
+        assert!(dhcsr.c_debugen());
+        assert_eq!(core.memory.read_word_32(demcr).unwrap(), 0x0100_0400);
+    }
+
+    #[test]
+    fn comparators_used_for_other_functions_are_not_free() {
+        let mut memory = WordMemory::default();
+        // DWT_CTRL with four comparators.
+        memory.words.insert(0xE000_1000, 0x4000_0000);
+        // DWT_FUNCTION1 samples the PC for data tracing, DWT_FUNCTION2 is a write watchpoint.
+        memory.words.insert(0xE000_1038, 0b0001);
+        memory.words.insert(0xE000_1048, 0b0110);
+
+        let mut state = CortexMState::new();
+        let mut core = Armv7m::new(
+            Box::new(memory),
+            &mut state,
+            DefaultArmSequence::create(),
+            0,
+        )
+        .unwrap();
+
+        let watchpoints = core.hw_watchpoints().unwrap();
+        assert!(watchpoints[1].is_none());
+        assert!(watchpoints[2].is_some());
+
+        assert_eq!(
+            core.free_watchpoint_units().unwrap(),
+            [true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn registers_are_read_again_after_the_core_halted_again() {
+        let mock = MockCortexM::halted();
+        mock.state_mut().registers.insert(0, 1);
+
+        let mut state = CortexMState::new();
+        let mut core = Armv7m::new(
+            Box::new(mock.clone()),
+            &mut state,
+            DefaultArmSequence::create(),
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(core.read_core_reg(RegisterId(0)).unwrap(), 1u32.into());
+
+        // Still the same halt, so the cached value is used.
+        core.status().unwrap();
+        mock.state_mut().registers.insert(0, 2);
+        assert_eq!(core.read_core_reg(RegisterId(0)).unwrap(), 1u32.into());
+
+        // The core ran and halted again between two polls.
+        mock.run_and_halt(&[(0, 3)]);
+        assert!(core.status().unwrap().is_halted());
+        assert_eq!(core.read_core_reg(RegisterId(0)).unwrap(), 3u32.into());
+    }
+
+    #[test]
+    fn register_writes_are_flushed_explicitly() {
+        let mock = MockCortexM::halted();
+
+        let mut state = CortexMState::new();
+        let mut core = Armv7m::new(
+            Box::new(mock.clone()),
+            &mut state,
+            DefaultArmSequence::create(),
+            0,
+        )
+        .unwrap();
+
+        core.write_core_reg(RegisterId(4), 0x1234u32.into())
+            .unwrap();
+        assert_eq!(mock.state_mut().registers.get(&4), None);
+
+        core.flush_registers().unwrap();
+        assert_eq!(mock.state_mut().registers.get(&4), Some(&0x1234));
+        drop(core);
+
+        // The write-back is done, and the value is still cached for the next handle.
+        let transfers = mock.state_mut().register_transfers;
+        let mut core = Armv7m::new(
+            Box::new(mock.clone()),
+            &mut state,
+            DefaultArmSequence::create(),
+            0,
+        )
+        .unwrap();
+        assert_eq!(core.read_core_reg(RegisterId(4)).unwrap(), 0x1234u32.into());
+        core.flush_registers().unwrap();
+        assert_eq!(mock.state_mut().register_transfers, transfers);
+    }
+
+    #[test]
+    fn with_halt_resumes_the_core() {
+        let mock = MockCortexM::default();
+        mock.state_mut().registers.insert(15, 0x100);
+
+        let mut state = CortexMState::new();
+        let armv7m = Armv7m::new(
+            Box::new(mock.clone()),
+            &mut state,
+            DefaultArmSequence::create(),
+            0,
+        )
+        .unwrap();
+        let mut core = Core::new(armv7m);
+
+        let pc = core
+            .with_halt(Duration::from_secs(1), |core| {
+                core.read_core_reg::<u32>(RegisterId(15))
+            })
+            .unwrap();
+        assert_eq!(pc, 0x100);
+        assert!(!mock.is_halted());
+
+        let result = core.with_halt(Duration::from_secs(1), |core| {
+            core.write_core_reg(RegisterId(0), 1u32)?;
+            Err::<(), _>(Error::Other(anyhow!("inspection failed")))
+        });
+        assert!(matches!(result, Err(Error::Other(_))));
+        assert!(!mock.is_halted());
+        // The register write is not lost.
+        assert_eq!(mock.state_mut().registers.get(&0), Some(&1));
+
+        // Register accesses after the deadline are cancelled.
+        let result = core.with_halt(Duration::from_millis(10), |core| {
+            std::thread::sleep(Duration::from_millis(20));
+            core.read_core_reg::<u32>(RegisterId(15))
+        });
+        assert!(matches!(result, Err(Error::Cancelled(_))));
+        assert!(!mock.is_halted());
+    }
+}
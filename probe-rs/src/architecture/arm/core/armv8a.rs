@@ -732,6 +732,8 @@ impl<'probe> CoreInterface for Armv8a<'probe> {
         let address = Edscr::get_mmio_address_from_base(self.base_address)?;
 
         while start.elapsed() < timeout {
+            crate::cancellation::check()?;
+
             let edscr = Edscr(self.memory.read_word_32(address)?);
             if edscr.halted() {
                 return Ok(());
@@ -1,7 +1,8 @@
 //! Register types and the core interface for armv8-M
 
 use super::{
-    cortex_m::Mvfr0,
+    cortex_m::{DwtVersion, Mvfr0},
+    exception_handling::armv8m::read_secure_fault,
     registers::cortex_m::{
         CORTEX_M_CORE_REGSISTERS, CORTEX_M_WITH_FP_CORE_REGSISTERS, FP, PC, RA, SP,
     },
@@ -12,11 +13,11 @@ use crate::{
         core::registers::cortex_m::XPSR, memory::adi_v5_memory_interface::ArmProbe,
         sequences::ArmDebugSequence, ArmError,
     },
-    core::{CoreRegisters, RegisterId, RegisterValue},
+    core::{CoreRegisters, RegisterId, RegisterValue, Watchpoint},
     error::Error,
     memory::valid_32bit_address,
-    Architecture, CoreInformation, CoreInterface, CoreRegister, CoreStatus, CoreType, HaltReason,
-    InstructionSet, MemoryInterface, MemoryMappedRegister,
+    Architecture, Core, CoreInformation, CoreInterface, CoreRegister, CoreStatus, CoreType,
+    HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister,
 };
 use anyhow::Result;
 use bitfield::bitfield;
@@ -26,6 +27,65 @@ use std::{
     time::{Duration, Instant},
 };
 
+mod regions;
+
+pub use regions::{
+    mpu_configuration, non_secure_mpu_configuration, sau_configuration, MemoryAttributes,
+    MpuAccess, MpuConfiguration, MpuRegion, SauConfiguration, SauRegion, SecurityAttribution,
+    Shareability,
+};
+
+/// A SecureFault, raised by ARMv8-M cores with the Security Extension on a TrustZone security
+/// violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SecureFault {
+    /// The cause of the fault, as decoded from SFSR.
+    pub cause: &'static str,
+    /// The address of the access which caused the fault, if SFAR is valid.
+    pub address: Option<u32>,
+}
+
+impl std::fmt::Display for SecureFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.address {
+            Some(address) => write!(
+                f,
+                "SecureFault ({}) at location: {address:#010x}",
+                self.cause
+            ),
+            None => write!(f, "SecureFault ({})", self.cause),
+        }
+    }
+}
+
+/// Read the SecureFault status of an ARMv8-M core, or `Ok(None)` if no SecureFault occurred.
+///
+/// The status can only be read if debugging Secure state is allowed.
+pub fn secure_fault(core: &mut Core) -> Result<Option<SecureFault>, Error> {
+    if core.core_type() != CoreType::Armv8m {
+        return Err(ArmError::ArchitectureRequired(&["ARMv8-M"]).into());
+    }
+
+    read_secure_fault(core)
+}
+
+/// Enable or disable halting an ARMv8-M core on SecureFault exceptions.
+///
+/// With the vector catch enabled, TrustZone security violations halt the core with
+/// [`HaltReason::Exception`] when the SecureFault is taken, and the cause can be read with
+/// [`secure_fault`].
+pub fn set_secure_fault_catch(core: &mut Core, enabled: bool) -> Result<(), Error> {
+    if core.core_type() != CoreType::Armv8m {
+        return Err(ArmError::ArchitectureRequired(&["ARMv8-M"]).into());
+    }
+
+    let mut demcr = Demcr(core.read_word_32(Demcr::get_mmio_address())?);
+    demcr.set_vc_sferr(enabled);
+    core.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
+
+    Ok(())
+}
+
 /// The state of a core that can be used to persist core state across calls to multiple different cores.
 pub struct Armv8m<'probe> {
     memory: Box<dyn ArmProbe + 'probe>,
@@ -74,6 +134,7 @@ impl<'probe> Armv8m<'probe> {
             state.fp_present = Mvfr0(memory.read_word_32(Mvfr0::get_mmio_address())?).fp_present();
 
             state.initialize();
+            state.save_demcr(&mut *memory)?;
         }
 
         Ok(Self {
@@ -85,6 +146,12 @@ impl<'probe> Armv8m<'probe> {
     }
 
     fn set_core_status(&mut self, new_status: CoreStatus) {
+        if !(new_status.is_halted() && self.state.current_state.is_halted()) {
+            // The registers change while the core is not halted, so only a core which stays
+            // halted keeps them.
+            self.state.register_cache.invalidate();
+        }
+
         super::update_core_status(&mut self.memory, &mut self.state.current_state, new_status);
     }
 }
@@ -95,6 +162,8 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         let start = Instant::now();
 
         while start.elapsed() < timeout {
+            crate::cancellation::check()?;
+
             if self.core_halted()? {
                 return Ok(());
             }
@@ -111,6 +180,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
     fn status(&mut self) -> Result<crate::core::CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::get_mmio_address())?);
+        self.state.check_reset(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             tracing::warn!(
@@ -136,6 +206,12 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         // TODO: Handle lockup
 
         if dhcsr.s_halt() {
+            if dhcsr.s_retire_st() || dhcsr.s_reset_st() {
+                // The core executed instructions or was reset since DHCSR was read last,
+                // so this is a new halt, even if we expected the core to be halted.
+                self.state.register_cache.invalidate();
+            }
+
             let dfsr = Dfsr(self.memory.read_word_32(Dfsr::get_mmio_address())?);
 
             let reason = dfsr.halt_reason();
@@ -162,6 +238,9 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
                 );
             }
 
+            let reason =
+                super::cortex_m::resolve_halt_reason(&mut *self.memory, DwtVersion::V2, reason)?;
+
             self.set_core_status(CoreStatus::Halted(reason));
 
             return Ok(CoreStatus::Halted(reason));
@@ -202,6 +281,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     fn run(&mut self) -> Result<(), Error> {
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
+        self.state.register_cache.flush(&mut *self.memory)?;
 
         let mut value = Dhcsr(0);
         value.set_c_halt(false);
@@ -221,6 +301,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     fn reset(&mut self) -> Result<(), Error> {
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv8m, None)?;
+        self.state.register_cache.invalidate();
         Ok(())
     }
 
@@ -231,6 +312,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv8m, None)?;
+        self.state.register_cache.invalidate();
 
         // Update core status
         let _ = self.status()?;
@@ -266,20 +348,12 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
             false
         };
 
-        let mut value = Dhcsr(0);
-        // Leave halted state.
-        // Step one instruction.
-        value.set_c_step(true);
-        value.set_c_halt(false);
-        value.set_c_debugen(true);
-        value.set_c_maskints(true);
-        value.enable_write();
-
-        self.memory
-            .write_word_32(Dhcsr::get_mmio_address(), value.into())?;
-        self.memory.flush()?;
+        // The step changes the registers, so write back the dirty ones and read them again after the step.
+        self.state.register_cache.flush(&mut *self.memory)?;
+        self.state.register_cache.invalidate();
 
-        self.wait_for_core_halted(Duration::from_millis(100))?;
+        let reason = super::cortex_m::step_and_wait(&mut *self.memory, Duration::from_millis(100))?;
+        self.set_core_status(CoreStatus::Halted(reason));
 
         // Try to read the new program counter.
         let mut pc_after_step = self.read_core_reg(self.program_counter().into())?;
@@ -306,28 +380,32 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     }
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
-        if self.state.current_state.is_halted() {
-            let value = super::cortex_m::read_core_reg(&mut *self.memory, address)?;
-            Ok(value.into())
-        } else {
-            Err(Error::Arm(ArmError::CoreNotHalted))
-        }
+        let values =
+            super::cortex_m::read_halted_core_regs(&mut *self.memory, self.state, &[address])?;
+        Ok(values[0])
     }
 
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
-        if self.state.current_state.is_halted() {
-            super::cortex_m::write_core_reg(&mut *self.memory, address, value.try_into()?)
-        } else {
-            Err(Error::Arm(ArmError::CoreNotHalted))
-        }
+        super::cortex_m::write_halted_core_regs(&mut *self.memory, self.state, &[(address, value)])
     }
 
-    fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
-        let raw_val = self.memory.read_word_32(FpCtrl::get_mmio_address())?;
+    fn read_core_regs(&mut self, addresses: &[RegisterId]) -> Result<Vec<RegisterValue>, Error> {
+        super::cortex_m::read_halted_core_regs(&mut *self.memory, self.state, addresses)
+    }
 
-        let reg = FpCtrl::from(raw_val);
+    fn write_core_regs(&mut self, registers: &[(RegisterId, RegisterValue)]) -> Result<(), Error> {
+        super::cortex_m::write_halted_core_regs(&mut *self.memory, self.state, registers)
+    }
+
+    fn flush_registers(&mut self) -> Result<(), Error> {
+        self.state.register_cache.flush(&mut *self.memory)
+    }
+
+    fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
+        let capabilities = self.state.capabilities(&mut *self.memory)?;
 
-        Ok(reg.num_code())
+        capabilities.fpb_version()?;
+        Ok(capabilities.breakpoint_comparators)
     }
 
     /// See docs on the [`CoreInterface::hw_breakpoints`] trait
@@ -366,6 +444,10 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     fn set_hw_breakpoint(&mut self, bp_unit_index: usize, addr: u64) -> Result<(), Error> {
         let addr = valid_32bit_address(addr)?;
 
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_breakpoint(bp_unit_index, Some(addr))?;
+
         let mut val = FpCompN::from(0);
 
         // clear bits which cannot be set and shift into position
@@ -382,6 +464,10 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     }
 
     fn clear_hw_breakpoint(&mut self, bp_unit_index: usize) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_breakpoint(bp_unit_index, None)?;
+
         let mut val = FpCompN::from(0);
         val.set_enable(false);
         val.set_bp_addr(0);
@@ -393,6 +479,49 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        let capabilities = self.state.capabilities(&mut *self.memory)?;
+
+        Ok(capabilities.watchpoint_comparators)
+    }
+
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        super::cortex_m::hw_watchpoints(&mut *self.memory, DwtVersion::V2)
+    }
+
+    fn free_watchpoint_units(&mut self) -> Result<Vec<bool>, Error> {
+        super::cortex_m::free_watchpoint_units(&mut *self.memory)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_watchpoint(unit_index)?;
+
+        super::cortex_m::set_hw_watchpoint(
+            &mut *self.memory,
+            DwtVersion::V2,
+            unit_index,
+            watchpoint,
+        )
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        self.state
+            .capabilities(&mut *self.memory)?
+            .check_watchpoint(unit_index)?;
+
+        super::cortex_m::clear_hw_watchpoint(&mut *self.memory, unit_index)
+    }
+
+    fn triggered_hw_watchpoint(&mut self) -> Result<Option<Watchpoint>, Error> {
+        super::cortex_m::triggered_hw_watchpoint(&mut *self.memory, DwtVersion::V2)
+    }
+
     fn registers(&self) -> &'static CoreRegisters {
         if self.state.fp_present {
             &CORTEX_M_WITH_FP_CORE_REGSISTERS
@@ -445,6 +574,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     fn reset_catch_set(&mut self) -> Result<(), Error> {
         self.sequence
             .reset_catch_set(&mut *self.memory, CoreType::Armv8m, None)?;
+        self.state.save_demcr(&mut *self.memory)?;
 
         Ok(())
     }
@@ -453,12 +583,33 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     fn reset_catch_clear(&mut self) -> Result<(), Error> {
         self.sequence
             .reset_catch_clear(&mut *self.memory, CoreType::Armv8m, None)?;
+        self.state.save_demcr(&mut *self.memory)?;
+
+        Ok(())
+    }
 
+    #[tracing::instrument(skip(self))]
+    fn fault_catch_set(&mut self, enabled: bool) -> Result<(), Error> {
+        super::cortex_m::set_fault_catch(&mut *self.memory, true, enabled)?;
+        self.state.save_demcr(&mut *self.memory)?;
+        Ok(())
+    }
+
+    fn take_target_reset(&mut self) -> bool {
+        self.state.take_target_reset()
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn restore_debug_settings(&mut self) -> Result<(), Error> {
+        self.state.restore_debug_settings(&mut *self.memory)?;
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
     fn debug_core_stop(&mut self) -> Result<(), Error> {
+        self.state.register_cache.flush(&mut *self.memory)?;
+        self.state.register_cache.invalidate();
+
         self.sequence
             .debug_core_stop(&mut *self.memory, CoreType::Armv8m)?;
 
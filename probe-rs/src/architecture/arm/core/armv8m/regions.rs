@@ -0,0 +1,544 @@
+//! The regions of the MPU and the SAU of ARMv8-M cores.
+//!
+//! A MemManage fault or a SecureFault is often caused by a region which is configured
+//! differently than the firmware expects. [`mpu_configuration`] and [`sau_configuration`] read
+//! and decode the regions of a halted core, and [`MpuConfiguration::regions_containing`] and
+//! [`SauConfiguration::attribution`] show how the faulting address is covered.
+
+use crate::{
+    architecture::arm::ArmError, memory_mapped_bitfield_register, Core, CoreType, Error,
+    MemoryInterface, MemoryMappedRegister,
+};
+
+/// The offset of the Non-secure alias of the System Control Space, through which a Secure
+/// debugger reads the Non-secure MPU.
+const NON_SECURE_ALIAS_OFFSET: u64 = 0x2_0000;
+
+memory_mapped_bitfield_register! {
+    /// MPU_TYPE - MPU Type Register
+    pub struct MpuType(u32);
+    0xE000_ED90, "MPU_TYPE",
+    impl From;
+    /// The number of regions of the MPU.
+    pub dregion, _: 15, 8;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU_CTRL - MPU Control Register
+    pub struct MpuCtrl(u32);
+    0xE000_ED94, "MPU_CTRL",
+    impl From;
+    /// The default memory map is used for privileged accesses outside of all regions.
+    pub privdefena, _: 2;
+    /// The MPU is enabled in HardFault and NMI handlers.
+    pub hfnmiena, _: 1;
+    /// The MPU is enabled.
+    pub enable, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU_RNR - MPU Region Number Register
+    pub struct MpuRnr(u32);
+    0xE000_ED98, "MPU_RNR",
+    impl From;
+    pub region, set_region: 7, 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU_RBAR - MPU Region Base Address Register
+    pub struct MpuRbar(u32);
+    0xE000_ED9C, "MPU_RBAR",
+    impl From;
+    /// Bits 31:5 of the first address of the region.
+    pub base, _: 31, 5;
+    /// The shareability of the region.
+    pub sh, _: 4, 3;
+    /// The region is read-only.
+    pub read_only, _: 2;
+    /// The region can be accessed by unprivileged code.
+    pub unprivileged, _: 1;
+    /// Instructions cannot be executed from the region.
+    pub xn, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU_RLAR - MPU Region Limit Address Register
+    pub struct MpuRlar(u32);
+    0xE000_EDA0, "MPU_RLAR",
+    impl From;
+    /// Bits 31:5 of the last address of the region.
+    pub limit, _: 31, 5;
+    /// The index of the memory attributes of the region in MPU_MAIR0 and MPU_MAIR1.
+    pub attrindx, _: 3, 1;
+    /// The region is enabled.
+    pub en, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU_MAIR0 - MPU Memory Attribute Indirection Register 0, the attributes 0 to 3.
+    pub struct MpuMair0(u32);
+    0xE000_EDC0, "MPU_MAIR0",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU_MAIR1 - MPU Memory Attribute Indirection Register 1, the attributes 4 to 7.
+    pub struct MpuMair1(u32);
+    0xE000_EDC4, "MPU_MAIR1",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU_CTRL - SAU Control Register
+    pub struct SauCtrl(u32);
+    0xE000_EDD0, "SAU_CTRL",
+    impl From;
+    /// Memory is Non-secure while the SAU is disabled.
+    pub allns, _: 1;
+    /// The SAU is enabled.
+    pub enable, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU_TYPE - SAU Type Register
+    pub struct SauType(u32);
+    0xE000_EDD4, "SAU_TYPE",
+    impl From;
+    /// The number of regions of the SAU.
+    pub sregion, _: 7, 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU_RNR - SAU Region Number Register
+    pub struct SauRnr(u32);
+    0xE000_EDD8, "SAU_RNR",
+    impl From;
+    pub region, set_region: 7, 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU_RBAR - SAU Region Base Address Register
+    pub struct SauRbar(u32);
+    0xE000_EDDC, "SAU_RBAR",
+    impl From;
+    /// Bits 31:5 of the first address of the region.
+    pub baddr, _: 31, 5;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU_RLAR - SAU Region Limit Address Register
+    pub struct SauRlar(u32);
+    0xE000_EDE0, "SAU_RLAR",
+    impl From;
+    /// Bits 31:5 of the last address of the region.
+    pub laddr, _: 31, 5;
+    /// The region is Non-secure callable.
+    pub nsc, _: 1;
+    /// The region is enabled.
+    pub enable, _: 0;
+}
+
+/// Who can access an [`MpuRegion`], and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpuAccess {
+    /// Privileged code can read and write the region, unprivileged code cannot access it.
+    PrivilegedReadWrite,
+    /// All code can read and write the region.
+    ReadWrite,
+    /// Privileged code can read the region, unprivileged code cannot access it.
+    PrivilegedReadOnly,
+    /// All code can read the region.
+    ReadOnly,
+}
+
+impl MpuAccess {
+    /// Returns `true` if a read, or with `write` a write, by privileged or unprivileged code is
+    /// allowed.
+    pub fn permits(&self, write: bool, privileged: bool) -> bool {
+        match self {
+            MpuAccess::PrivilegedReadWrite => privileged,
+            MpuAccess::ReadWrite => true,
+            MpuAccess::PrivilegedReadOnly => privileged && !write,
+            MpuAccess::ReadOnly => !write,
+        }
+    }
+}
+
+/// The shareability of an [`MpuRegion`] of Normal memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shareability {
+    /// The region is not shared.
+    NonShareable,
+    /// The encoding reserved by the architecture.
+    Reserved,
+    /// The region is shared with the outer shareable domain.
+    OuterShareable,
+    /// The region is shared with the inner shareable domain.
+    InnerShareable,
+}
+
+/// The memory type of an [`MpuRegion`], decoded from its attribute in MPU_MAIR0 or MPU_MAIR1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAttributes {
+    /// Device memory, with the `nGnRnE`, `nGnRE`, `nGRE` or `GRE` behaviour of bits 3:2.
+    Device(u8),
+    /// Normal memory, with the outer and inner cacheability.
+    Normal {
+        /// The outer cacheability, bits 7:4 of the attribute.
+        outer: u8,
+        /// The inner cacheability, bits 3:0 of the attribute.
+        inner: u8,
+    },
+}
+
+impl MemoryAttributes {
+    fn from_attribute(attribute: u8) -> Self {
+        match attribute >> 4 {
+            0 => MemoryAttributes::Device((attribute >> 2) & 0b11),
+            outer => MemoryAttributes::Normal {
+                outer,
+                inner: attribute & 0xF,
+            },
+        }
+    }
+}
+
+/// An enabled region of the MPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MpuRegion {
+    /// The number of the region.
+    pub number: u8,
+    /// The first address of the region.
+    pub base: u32,
+    /// The last address of the region.
+    pub limit: u32,
+    /// Who can access the region.
+    pub access: MpuAccess,
+    /// Instructions cannot be executed from the region.
+    pub execute_never: bool,
+    /// The shareability of the region.
+    pub shareability: Shareability,
+    /// The memory type of the region.
+    pub attributes: MemoryAttributes,
+}
+
+impl MpuRegion {
+    fn decode(number: u8, rbar: MpuRbar, rlar: MpuRlar, mair: u64) -> Option<Self> {
+        if !rlar.en() {
+            return None;
+        }
+
+        let access = match (rbar.read_only(), rbar.unprivileged()) {
+            (false, false) => MpuAccess::PrivilegedReadWrite,
+            (false, true) => MpuAccess::ReadWrite,
+            (true, false) => MpuAccess::PrivilegedReadOnly,
+            (true, true) => MpuAccess::ReadOnly,
+        };
+        let shareability = match rbar.sh() {
+            0b00 => Shareability::NonShareable,
+            0b01 => Shareability::Reserved,
+            0b10 => Shareability::OuterShareable,
+            _ => Shareability::InnerShareable,
+        };
+        let attribute = (mair >> (8 * rlar.attrindx())) as u8;
+
+        Some(Self {
+            number,
+            base: rbar.base() << 5,
+            limit: rlar.limit() << 5 | 0x1F,
+            access,
+            execute_never: rbar.xn(),
+            shareability,
+            attributes: MemoryAttributes::from_attribute(attribute),
+        })
+    }
+
+    /// Returns `true` if `address` is in the region.
+    pub fn contains(&self, address: u32) -> bool {
+        (self.base..=self.limit).contains(&address)
+    }
+}
+
+/// The configuration of an MPU, read by [`mpu_configuration`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MpuConfiguration {
+    /// The MPU is enabled.
+    pub enabled: bool,
+    /// The MPU is also enabled in HardFault and NMI handlers.
+    pub enabled_in_fault_handlers: bool,
+    /// Privileged code can access memory outside of all regions, with the default memory map.
+    pub privileged_default_map: bool,
+    /// The number of regions of the MPU.
+    pub region_count: u8,
+    /// The enabled regions.
+    pub regions: Vec<MpuRegion>,
+}
+
+impl MpuConfiguration {
+    /// The enabled regions which contain `address`.
+    ///
+    /// An access to an address which is in more than one region faults, like an access which is
+    /// in none, unless the default memory map applies to it.
+    pub fn regions_containing(&self, address: u32) -> Vec<&MpuRegion> {
+        self.regions
+            .iter()
+            .filter(|region| region.contains(address))
+            .collect()
+    }
+}
+
+/// An enabled region of the SAU, which marks memory as Non-secure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SauRegion {
+    /// The number of the region.
+    pub number: u8,
+    /// The first address of the region.
+    pub base: u32,
+    /// The last address of the region.
+    pub limit: u32,
+    /// The region is Secure, and Non-secure code can call the entry points in it.
+    pub non_secure_callable: bool,
+}
+
+impl SauRegion {
+    fn decode(number: u8, rbar: SauRbar, rlar: SauRlar) -> Option<Self> {
+        rlar.enable().then(|| Self {
+            number,
+            base: rbar.baddr() << 5,
+            limit: rlar.laddr() << 5 | 0x1F,
+            non_secure_callable: rlar.nsc(),
+        })
+    }
+
+    /// Returns `true` if `address` is in the region.
+    pub fn contains(&self, address: u32) -> bool {
+        (self.base..=self.limit).contains(&address)
+    }
+}
+
+/// The security of an address, as attributed by the SAU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityAttribution {
+    /// Only Secure code can access the address.
+    Secure,
+    /// The address is Secure, and Non-secure code can call the entry points at it.
+    NonSecureCallable,
+    /// Secure and Non-secure code can access the address.
+    NonSecure,
+}
+
+/// The configuration of the SAU, read by [`sau_configuration`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SauConfiguration {
+    /// The SAU is enabled.
+    pub enabled: bool,
+    /// All memory is Non-secure while the SAU is disabled.
+    pub all_non_secure: bool,
+    /// The number of regions of the SAU.
+    pub region_count: u8,
+    /// The enabled regions.
+    pub regions: Vec<SauRegion>,
+}
+
+impl SauConfiguration {
+    /// The security the SAU attributes to `address`.
+    ///
+    /// The implementation defined attribution unit (IDAU) of the chip can make an address more
+    /// secure than the SAU does, which is not included.
+    pub fn attribution(&self, address: u32) -> SecurityAttribution {
+        if !self.enabled {
+            return match self.all_non_secure {
+                true => SecurityAttribution::NonSecure,
+                false => SecurityAttribution::Secure,
+            };
+        }
+
+        let mut regions = self
+            .regions
+            .iter()
+            .filter(|region| region.contains(address));
+
+        match (regions.next(), regions.next()) {
+            (Some(region), None) if region.non_secure_callable => {
+                SecurityAttribution::NonSecureCallable
+            }
+            (Some(_), None) => SecurityAttribution::NonSecure,
+            // Addresses in no region or in more than one region are Secure.
+            _ => SecurityAttribution::Secure,
+        }
+    }
+}
+
+fn check_core(core: &mut Core) -> Result<(), Error> {
+    if core.core_type() != CoreType::Armv8m {
+        return Err(ArmError::ArchitectureRequired(&["ARMv8-M"]).into());
+    }
+
+    // The region number register is shared with the firmware.
+    if !core.core_halted()? {
+        return Err(ArmError::CoreNotHalted.into());
+    }
+
+    Ok(())
+}
+
+fn read_mpu(core: &mut Core, offset: u64) -> Result<MpuConfiguration, Error> {
+    check_core(core)?;
+
+    let read = |core: &mut Core, address: u64| core.read_word_32(address + offset);
+
+    let mpu_type = MpuType(read(core, MpuType::get_mmio_address())?);
+    let ctrl = MpuCtrl(read(core, MpuCtrl::get_mmio_address())?);
+    let mair = u64::from(read(core, MpuMair0::get_mmio_address())?)
+        | u64::from(read(core, MpuMair1::get_mmio_address())?) << 32;
+
+    let rnr_address = MpuRnr::get_mmio_address() + offset;
+    let saved_rnr = core.read_word_32(rnr_address)?;
+
+    let mut regions = vec![];
+    for number in 0..mpu_type.dregion() as u8 {
+        let mut rnr = MpuRnr(0);
+        rnr.set_region(number.into());
+        core.write_word_32(rnr_address, rnr.into())?;
+
+        let rbar = MpuRbar(read(core, MpuRbar::get_mmio_address())?);
+        let rlar = MpuRlar(read(core, MpuRlar::get_mmio_address())?);
+        regions.extend(MpuRegion::decode(number, rbar, rlar, mair));
+    }
+
+    core.write_word_32(rnr_address, saved_rnr)?;
+
+    Ok(MpuConfiguration {
+        enabled: ctrl.enable(),
+        enabled_in_fault_handlers: ctrl.hfnmiena(),
+        privileged_default_map: ctrl.privdefena(),
+        region_count: mpu_type.dregion() as u8,
+        regions,
+    })
+}
+
+/// Read the configuration of the MPU of a halted ARMv8-M core.
+///
+/// On a core with the Security Extension, this is the MPU of the security state the debugger
+/// accesses the core in, usually the Secure MPU, see [`non_secure_mpu_configuration`].
+pub fn mpu_configuration(core: &mut Core) -> Result<MpuConfiguration, Error> {
+    read_mpu(core, 0)
+}
+
+/// Read the configuration of the Non-secure MPU of a halted ARMv8-M core with the Security
+/// Extension, through the Non-secure alias of the System Control Space.
+///
+/// The alias can only be accessed when debugging Secure state is allowed.
+pub fn non_secure_mpu_configuration(core: &mut Core) -> Result<MpuConfiguration, Error> {
+    read_mpu(core, NON_SECURE_ALIAS_OFFSET)
+}
+
+/// Read the configuration of the SAU of a halted ARMv8-M core.
+///
+/// The SAU can only be read when debugging Secure state is allowed, it reads as zero, without
+/// regions, otherwise.
+pub fn sau_configuration(core: &mut Core) -> Result<SauConfiguration, Error> {
+    check_core(core)?;
+
+    let sau_type = SauType(core.read_word_32(SauType::get_mmio_address())?);
+    let ctrl = SauCtrl(core.read_word_32(SauCtrl::get_mmio_address())?);
+
+    let saved_rnr = core.read_word_32(SauRnr::get_mmio_address())?;
+
+    let mut regions = vec![];
+    for number in 0..sau_type.sregion() as u8 {
+        let mut rnr = SauRnr(0);
+        rnr.set_region(number.into());
+        core.write_word_32(SauRnr::get_mmio_address(), rnr.into())?;
+
+        let rbar = SauRbar(core.read_word_32(SauRbar::get_mmio_address())?);
+        let rlar = SauRlar(core.read_word_32(SauRlar::get_mmio_address())?);
+        regions.extend(SauRegion::decode(number, rbar, rlar));
+    }
+
+    core.write_word_32(SauRnr::get_mmio_address(), saved_rnr)?;
+
+    Ok(SauConfiguration {
+        enabled: ctrl.enable(),
+        all_non_secure: ctrl.allns(),
+        region_count: sau_type.sregion() as u8,
+        regions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MemoryAttributes, MpuAccess, MpuConfiguration, MpuRbar, MpuRegion, MpuRlar,
+        SauConfiguration, SauRbar, SauRegion, SauRlar, SecurityAttribution, Shareability,
+    };
+
+    #[test]
+    fn mpu_region_is_decoded() {
+        // A read-only region for all code, 0x0800_0000 to 0x0807_FFFF, with attribute 1.
+        let rbar = MpuRbar(0x0800_0000 | 0b11 << 3 | 0b11 << 1);
+        let rlar = MpuRlar(0x0807_FFE0 | 1 << 1 | 1);
+        // Attribute 0 is Device-nGnRE, attribute 1 Normal write-back memory.
+        let mair = 0xFF04;
+
+        let region = MpuRegion::decode(2, rbar, rlar, mair).unwrap();
+        assert_eq!(
+            region,
+            MpuRegion {
+                number: 2,
+                base: 0x0800_0000,
+                limit: 0x0807_FFFF,
+                access: MpuAccess::ReadOnly,
+                execute_never: false,
+                shareability: Shareability::InnerShareable,
+                attributes: MemoryAttributes::Normal {
+                    outer: 0xF,
+                    inner: 0xF
+                },
+            }
+        );
+        assert!(!region.access.permits(true, true));
+        assert!(region.access.permits(false, false));
+
+        let device = MpuRegion::decode(3, MpuRbar(0x4000_0001), MpuRlar(0x4FFF_FFE1), mair);
+        assert_eq!(device.unwrap().attributes, MemoryAttributes::Device(0b01));
+
+        // Disabled regions are skipped.
+        assert_eq!(MpuRegion::decode(4, rbar, MpuRlar(0x0807_FFE0), mair), None);
+
+        let mpu = MpuConfiguration {
+            enabled: true,
+            enabled_in_fault_handlers: false,
+            privileged_default_map: true,
+            region_count: 8,
+            regions: vec![region, device.unwrap()],
+        };
+        assert_eq!(mpu.regions_containing(0x0807_FFFF), [&region]);
+        assert!(mpu.regions_containing(0x2000_0000).is_empty());
+    }
+
+    #[test]
+    fn sau_attributes_security() {
+        let non_secure = SauRegion::decode(0, SauRbar(0x0010_0000), SauRlar(0x001F_FFE1));
+        let callable = SauRegion::decode(1, SauRbar(0x000F_F000), SauRlar(0x000F_FFE3));
+
+        let mut sau = SauConfiguration {
+            enabled: true,
+            all_non_secure: false,
+            region_count: 8,
+            regions: [non_secure, callable].into_iter().flatten().collect(),
+        };
+
+        assert_eq!(sau.attribution(0x0010_0000), SecurityAttribution::NonSecure);
+        assert_eq!(
+            sau.attribution(0x000F_F800),
+            SecurityAttribution::NonSecureCallable
+        );
+        assert_eq!(sau.attribution(0x0000_0000), SecurityAttribution::Secure);
+
+        sau.enabled = false;
+        assert_eq!(sau.attribution(0x0010_0000), SecurityAttribution::Secure);
+        sau.all_non_secure = true;
+        assert_eq!(sau.attribution(0x0000_0000), SecurityAttribution::NonSecure);
+    }
+}
@@ -1,10 +1,18 @@
 //! Common functions and data types for Cortex-M core variants
 
+use super::armv7m::{Demcr, FpCtrl};
+use super::{CortexMState, Dfsr};
 use crate::{
-    architecture::arm::{memory::adi_v5_memory_interface::ArmProbe, ArmError},
-    core::RegisterId,
+    architecture::arm::{
+        memory::adi_v5_memory_interface::{ArmProbe, WordAccess},
+        ArmError,
+    },
+    core::{HaltReason, RegisterId, RegisterValue, Watchpoint, WatchpointKind},
+    memory::valid_32bit_address,
     memory_mapped_bitfield_register, Error, MemoryMappedRegister,
 };
+use anyhow::anyhow;
+use std::collections::BTreeMap;
 use std::time::{Duration, Instant};
 
 memory_mapped_bitfield_register! {
@@ -114,6 +122,284 @@ pub(crate) fn write_core_reg(
     Ok(())
 }
 
+/// Read core registers of a halted core, through the register cache of `state`.
+///
+/// This implements [`read_core_reg`] and [`read_core_regs`] for
+/// all Cortex-M cores.
+///
+/// [`read_core_reg`]: crate::core::CoreInterface::read_core_reg
+/// [`read_core_regs`]: crate::core::CoreInterface::read_core_regs
+pub(crate) fn read_halted_core_regs(
+    memory: &mut dyn ArmProbe,
+    state: &mut CortexMState,
+    ids: &[RegisterId],
+) -> Result<Vec<RegisterValue>, Error> {
+    if !state.current_state.is_halted() {
+        return Err(Error::Arm(ArmError::CoreNotHalted));
+    }
+
+    let values = state.register_cache.read_many(memory, ids)?;
+    Ok(values.into_iter().map(RegisterValue::from).collect())
+}
+
+/// Write core registers of a halted core, through the register cache of `state`.
+///
+/// This implements [`write_core_reg`] and [`write_core_regs`] for
+/// all Cortex-M cores.
+///
+/// [`write_core_reg`]: crate::core::CoreInterface::write_core_reg
+/// [`write_core_regs`]: crate::core::CoreInterface::write_core_regs
+pub(crate) fn write_halted_core_regs(
+    memory: &mut dyn ArmProbe,
+    state: &mut CortexMState,
+    registers: &[(RegisterId, RegisterValue)],
+) -> Result<(), Error> {
+    if !state.current_state.is_halted() {
+        return Err(Error::Arm(ArmError::CoreNotHalted));
+    }
+
+    let registers = registers
+        .iter()
+        .map(|&(id, value)| Ok((id, value.try_into()?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    state.register_cache.write_many(memory, &registers)
+}
+
+/// Read several core registers with one [`ArmProbe::transfer_32`].
+///
+/// For every register, DCRSR selects it, DHCSR is read once and DCRDR is read. A register
+/// transfer takes a few cycles of the core, which is shorter than a DAP transfer, so S_REGRDY
+/// is already set when DHCSR is read. If it is not, the transfer of the next register may have
+/// been started too early, so this and all remaining registers are read again one by one.
+fn read_core_regs_pipelined(
+    memory: &mut dyn ArmProbe,
+    ids: &[RegisterId],
+) -> Result<Vec<u32>, Error> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut accesses = Vec::with_capacity(3 * ids.len());
+    for &id in ids {
+        let mut dcrsr = Dcrsr(0);
+        dcrsr.set_regwnr(false);
+        dcrsr.set_regsel(id.into());
+
+        accesses.push(WordAccess::Write(Dcrsr::get_mmio_address(), dcrsr.into()));
+        accesses.push(WordAccess::Read(Dhcsr::get_mmio_address()));
+        accesses.push(WordAccess::Read(Dcrdr::get_mmio_address()));
+    }
+
+    let results = memory.transfer_32(&accesses)?;
+
+    let mut values = results
+        .chunks_exact(2)
+        .take_while(|result| Dhcsr(result[0]).s_regrdy())
+        .map(|result| result[1])
+        .collect::<Vec<_>>();
+
+    for &id in &ids[values.len()..] {
+        tracing::debug!("Register transfer was not complete, reading {:?} again", id);
+        values.push(read_core_reg(memory, id)?);
+    }
+
+    Ok(values)
+}
+
+/// Write several core registers with one [`ArmProbe::transfer_32`].
+///
+/// For every register, its value is written to DCRDR, DCRSR selects it, and DHCSR is read
+/// once, like in [`read_core_regs_pipelined`]. If a transfer was not complete, this and all
+/// remaining registers are written again one by one.
+fn write_core_regs_pipelined(
+    memory: &mut dyn ArmProbe,
+    registers: &[(RegisterId, u32)],
+) -> Result<(), Error> {
+    let mut accesses = Vec::with_capacity(3 * registers.len());
+    for &(id, value) in registers {
+        let mut dcrsr = Dcrsr(0);
+        dcrsr.set_regwnr(true);
+        dcrsr.set_regsel(id.into());
+
+        accesses.push(WordAccess::Write(Dcrdr::get_mmio_address(), value));
+        accesses.push(WordAccess::Write(Dcrsr::get_mmio_address(), dcrsr.into()));
+        accesses.push(WordAccess::Read(Dhcsr::get_mmio_address()));
+    }
+
+    let results = memory.transfer_32(&accesses)?;
+
+    let complete = results
+        .iter()
+        .take_while(|&&dhcsr| Dhcsr(dhcsr).s_regrdy())
+        .count();
+
+    for &(id, value) in &registers[complete..] {
+        tracing::debug!("Register transfer was not complete, writing {:?} again", id);
+        write_core_reg(memory, id, value)?;
+    }
+
+    Ok(())
+}
+
+/// The core registers of a halted Cortex-M core which were accessed since it halted.
+///
+/// Registers are read from the core on first access, and served from the cache afterwards.
+/// Writes only update the cache and mark the register as dirty. All dirty registers are written
+/// to the core with [`RegisterCache::flush`], which has to be called before the core leaves
+/// the halted state. The cache is cleared whenever the core is found halted again after it ran.
+#[derive(Debug, Default)]
+pub(crate) struct RegisterCache {
+    /// The value of every cached register, and whether it still has to be written to the core.
+    registers: BTreeMap<u16, (u32, bool)>,
+}
+
+impl RegisterCache {
+    /// Read several registers, from the cache or from the core.
+    ///
+    /// The registers which are not cached yet are transferred together, see
+    /// [`read_core_regs_pipelined`], and each register is only read once, even if it is
+    /// requested several times.
+    pub(crate) fn read_many(
+        &mut self,
+        memory: &mut dyn ArmProbe,
+        ids: &[RegisterId],
+    ) -> Result<Vec<u32>, Error> {
+        let mut missing = Vec::new();
+        for &id in ids {
+            if !self.registers.contains_key(&id.0) && !missing.contains(&id) {
+                missing.push(id);
+            }
+        }
+
+        let values = read_core_regs_pipelined(memory, &missing)?;
+        for (id, value) in missing.into_iter().zip(values) {
+            self.registers.insert(id.0, (value, false));
+        }
+
+        Ok(ids.iter().map(|id| self.registers[&id.0].0).collect())
+    }
+
+    /// Write several registers.
+    ///
+    /// Writes to independent registers are cached, so they are written to the core together
+    /// when the cache is flushed.
+    pub(crate) fn write_many(
+        &mut self,
+        memory: &mut dyn ArmProbe,
+        registers: &[(RegisterId, u32)],
+    ) -> Result<(), Error> {
+        for &(id, value) in registers {
+            self.write(memory, id, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write `value` to `id`.
+    ///
+    /// Registers which also change other registers, for example CONTROL, which selects the
+    /// stack pointer, are written to the core directly, and the cache is flushed and cleared.
+    pub(crate) fn write(
+        &mut self,
+        memory: &mut dyn ArmProbe,
+        id: RegisterId,
+        value: u32,
+    ) -> Result<(), Error> {
+        if is_independent_register(id) {
+            self.registers.insert(id.0, (value, true));
+            return Ok(());
+        }
+
+        self.flush(memory)?;
+        write_core_reg(memory, id, value)?;
+        self.invalidate();
+
+        Ok(())
+    }
+
+    /// Write all dirty registers to the core, together, see [`write_core_regs_pipelined`].
+    pub(crate) fn flush(&mut self, memory: &mut dyn ArmProbe) -> Result<(), Error> {
+        let dirty = self
+            .registers
+            .iter()
+            .filter(|(_, (_, dirty))| *dirty)
+            .map(|(&id, &(value, _))| (RegisterId(id), value))
+            .collect::<Vec<_>>();
+
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        write_core_regs_pipelined(memory, &dirty)?;
+
+        for (_, dirty) in self.registers.values_mut() {
+            *dirty = false;
+        }
+
+        Ok(())
+    }
+
+    /// Forget all cached registers, including unflushed writes.
+    pub(crate) fn invalidate(&mut self) {
+        self.registers.clear();
+    }
+}
+
+/// Returns `true` if writing `id` does not change the value of any other register.
+///
+/// These are R0-R12, LR, PC, XPSR, FPSCR and the floating point registers. The stack pointers
+/// and the special registers are banked or combined, for example R13 is either MSP or PSP.
+fn is_independent_register(id: RegisterId) -> bool {
+    matches!(id.0, 0..=12 | 14 | 15 | 0x10 | 0x21 | 0x40..=0x5F)
+}
+
+/// Start a single step of a halted core, and wait until it halts again.
+///
+/// This uses fewer transactions than a general halt wait, which matters when stepping through
+/// a loop with a slow probe: the sticky DFSR bits and DHCSR are only written, and DHCSR is
+/// polled without a delay, as a step completes within a few cycles. Only when the core halted,
+/// DFSR is read to determine the halt reason.
+///
+/// Interrupts are masked during the step. C_MASKINTS may only be changed while the core is halted,
+/// so it is set before C_HALT is cleared.
+pub(crate) fn step_and_wait(
+    memory: &mut dyn ArmProbe,
+    timeout: Duration,
+) -> Result<HaltReason, Error> {
+    memory.write_word_32(Dfsr::get_mmio_address(), Dfsr::clear_all().into())?;
+
+    let mut dhcsr = Dhcsr(0);
+    dhcsr.set_c_debugen(true);
+    dhcsr.set_c_maskints(true);
+    dhcsr.set_c_halt(true);
+    dhcsr.enable_write();
+    memory.write_word_32(Dhcsr::get_mmio_address(), dhcsr.into())?;
+
+    dhcsr.set_c_halt(false);
+    dhcsr.set_c_step(true);
+    memory.write_word_32(Dhcsr::get_mmio_address(), dhcsr.into())?;
+
+    let start = Instant::now();
+
+    loop {
+        let dhcsr = Dhcsr(memory.read_word_32(Dhcsr::get_mmio_address())?);
+
+        if dhcsr.s_halt() {
+            let dfsr = Dfsr(memory.read_word_32(Dfsr::get_mmio_address())?);
+            memory.write_word_32(Dfsr::get_mmio_address(), Dfsr::clear_all().into())?;
+
+            return Ok(dfsr.halt_reason());
+        }
+
+        if start.elapsed() > timeout {
+            return Err(Error::Arm(ArmError::Timeout));
+        }
+
+        crate::cancellation::check()?;
+    }
+}
+
 fn wait_for_core_register_transfer(
     memory: &mut dyn ArmProbe,
     timeout: Duration,
@@ -123,6 +409,8 @@ fn wait_for_core_register_transfer(
     let start = Instant::now();
 
     while start.elapsed() < timeout {
+        crate::cancellation::check()?;
+
         let dhcsr_val = Dhcsr(memory.read_word_32(Dhcsr::get_mmio_address())?);
 
         if dhcsr_val.s_regrdy() {
@@ -131,3 +419,683 @@ fn wait_for_core_register_transfer(
     }
     Err(ArmError::Timeout)
 }
+
+memory_mapped_bitfield_register! {
+    /// DWT Control Register, DWT_CTRL
+    pub struct DwtCtrl(u32);
+    0xE000_1000, "DWT_CTRL",
+    impl From;
+    pub numcomp, _: 31, 28;
+}
+
+memory_mapped_bitfield_register! {
+    /// DWT Comparator register, DWT_COMPn. The address is the one of the first comparator.
+    pub struct DwtComp(u32);
+    0xE000_1020, "DWT_COMP",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// DWT Mask register, DWT_MASKn. The address is the one of the first comparator.
+    pub struct DwtMask(u32);
+    0xE000_1024, "DWT_MASK",
+    impl From;
+    pub mask, set_mask: 4, 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// DWT Function register, DWT_FUNCTIONn. The address is the one of the first comparator.
+    ///
+    /// The FUNCTION field is called MATCH on ARMv8-M, where it is encoded differently, see
+    /// [`DwtVersion`]. The ACTION and DATAVSIZE fields only exist on ARMv8-M.
+    pub struct DwtFunction(u32);
+    0xE000_1028, "DWT_FUNCTION",
+    impl From;
+    pub matched, _: 24;
+    pub datavsize, set_datavsize: 11, 10;
+    pub action, set_action: 5, 4;
+    pub function, set_function: 3, 0;
+}
+
+impl DwtFunction {
+    const FUNCTION_DISABLED: u32 = 0b0000;
+
+    const V1_READ: u32 = 0b0101;
+    const V1_WRITE: u32 = 0b0110;
+    const V1_READ_WRITE: u32 = 0b0111;
+
+    const V2_READ_WRITE: u32 = 0b0100;
+    const V2_WRITE: u32 = 0b0101;
+    const V2_READ: u32 = 0b0110;
+    /// ACTION value which halts the core when the comparator matches.
+    const V2_ACTION_DEBUG_EVENT: u32 = 0b01;
+
+    fn watchpoint_kind(&self, version: DwtVersion) -> Option<WatchpointKind> {
+        match version {
+            DwtVersion::V1 => match self.function() {
+                Self::V1_READ => Some(WatchpointKind::Read),
+                Self::V1_WRITE => Some(WatchpointKind::Write),
+                Self::V1_READ_WRITE => Some(WatchpointKind::ReadWrite),
+                _ => None,
+            },
+            // Comparators which only trigger trace packets are not watchpoints.
+            DwtVersion::V2 if self.action() != Self::V2_ACTION_DEBUG_EVENT => None,
+            DwtVersion::V2 => match self.function() {
+                Self::V2_READ => Some(WatchpointKind::Read),
+                Self::V2_WRITE => Some(WatchpointKind::Write),
+                Self::V2_READ_WRITE => Some(WatchpointKind::ReadWrite),
+                _ => None,
+            },
+        }
+    }
+
+    fn watchpoint_function(version: DwtVersion, kind: WatchpointKind) -> u32 {
+        match (version, kind) {
+            (DwtVersion::V1, WatchpointKind::Read) => Self::V1_READ,
+            (DwtVersion::V1, WatchpointKind::Write) => Self::V1_WRITE,
+            (DwtVersion::V1, WatchpointKind::ReadWrite) => Self::V1_READ_WRITE,
+            (DwtVersion::V2, WatchpointKind::Read) => Self::V2_READ,
+            (DwtVersion::V2, WatchpointKind::Write) => Self::V2_WRITE,
+            (DwtVersion::V2, WatchpointKind::ReadWrite) => Self::V2_READ_WRITE,
+        }
+    }
+}
+
+/// The version of the DWT, which decides how its comparators are configured as watchpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DwtVersion {
+    /// The DWT of ARMv6-M and ARMv7-M cores. A comparator watches a naturally aligned, power of
+    /// two sized range, selected by DWT_MASK.
+    V1,
+    /// The DWT of ARMv8-M cores, without DWT_MASK. A comparator watches a naturally aligned
+    /// byte, halfword or word, selected by DWT_FUNCTION.DATAVSIZE.
+    V2,
+}
+
+/// The version of a breakpoint unit, which decides where breakpoints can be set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FpbVersion {
+    /// Breakpoints can only be set in the code region, below `0x2000_0000`. This is the BPU of
+    /// ARMv6-M cores, and version 1 of the FPB of ARMv7-M cores.
+    V1,
+    /// Breakpoints can be set at any address.
+    V2,
+}
+
+/// The optional debug features of a Cortex-M core.
+///
+/// Only few debug features are mandatory on ARMv6-M. A Cortex-M0 or M0+ can be built without a
+/// breakpoint unit, without DWT comparators, and without halting on reset, and the registers of
+/// the missing features read as zero and ignore writes. The cores check these capabilities
+/// before they use a feature, and return [`ArmError::UnsupportedDebugFeature`] instead:
+///
+/// | Feature            | Requires                                    | Without it                   |
+/// |--------------------|---------------------------------------------|------------------------------|
+/// | HW breakpoints     | `FP_CTRL.NUM_CODE` comparators              | No breakpoint units          |
+/// | Breakpoints in RAM | FPB version 2                               | Only addresses < 0x2000_0000 |
+/// | Watchpoints        | `DWT_CTRL.NUMCOMP` comparators              | No watchpoint units          |
+/// | Halt on reset      | `DEMCR.VC_CORERESET`, checked when it is set | Reset catch fails            |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CortexMCapabilities {
+    /// The version of the breakpoint unit, or `None` if its revision is unknown.
+    pub fpb_version: Option<FpbVersion>,
+    /// The number of instruction address comparators of the breakpoint unit.
+    pub breakpoint_comparators: u32,
+    /// The number of DWT comparators, which can each be used as a watchpoint.
+    pub watchpoint_comparators: u32,
+}
+
+impl CortexMCapabilities {
+    /// Read the capabilities from FP_CTRL and DWT_CTRL.
+    ///
+    /// The FPB revision field is reserved on ARMv6-M and reads as zero, which matches the
+    /// address range of its breakpoint unit.
+    pub(crate) fn read(memory: &mut dyn ArmProbe) -> Result<Self, Error> {
+        let fp_ctrl = FpCtrl::from(memory.read_word_32(FpCtrl::get_mmio_address())?);
+
+        let fpb_version = match fp_ctrl.rev() {
+            0 => Some(FpbVersion::V1),
+            1 => Some(FpbVersion::V2),
+            rev => {
+                tracing::warn!(
+                    "This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.",
+                    rev
+                );
+                None
+            }
+        };
+
+        // The DWT registers are only accessible when TRCENA is set.
+        let mut demcr = Demcr(memory.read_word_32(Demcr::get_mmio_address())?);
+        if !demcr.trcena() {
+            demcr.set_trcena(true);
+            memory.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
+        }
+        let dwt_ctrl = DwtCtrl(memory.read_word_32(DwtCtrl::get_mmio_address())?);
+
+        let capabilities = Self {
+            fpb_version,
+            breakpoint_comparators: fp_ctrl.num_code(),
+            watchpoint_comparators: dwt_ctrl.numcomp(),
+        };
+        tracing::debug!("Cortex-M debug capabilities: {:?}", capabilities);
+
+        Ok(capabilities)
+    }
+
+    /// The version of the breakpoint unit, if probe-rs supports it.
+    pub(crate) fn fpb_version(&self) -> Result<FpbVersion, ArmError> {
+        self.fpb_version.ok_or_else(|| {
+            ArmError::UnsupportedDebugFeature(
+                "hardware breakpoints, its breakpoint unit revision is unknown".to_string(),
+            )
+        })
+    }
+
+    /// Check that breakpoint unit `unit_index` exists, and can break at `address`.
+    pub(crate) fn check_breakpoint(
+        &self,
+        unit_index: usize,
+        address: Option<u32>,
+    ) -> Result<FpbVersion, ArmError> {
+        let version = self.fpb_version()?;
+
+        if unit_index >= self.breakpoint_comparators as usize {
+            return Err(ArmError::UnsupportedDebugFeature(format!(
+                "hardware breakpoint {}, it has {} breakpoint comparators",
+                unit_index, self.breakpoint_comparators
+            )));
+        }
+
+        match address {
+            Some(address) if version == FpbVersion::V1 && address >= 0x2000_0000 => {
+                Err(ArmError::UnsupportedBreakpointAddress(address))
+            }
+            _ => Ok(version),
+        }
+    }
+
+    /// Check that DWT comparator `unit_index` exists.
+    pub(crate) fn check_watchpoint(&self, unit_index: usize) -> Result<(), ArmError> {
+        if unit_index >= self.watchpoint_comparators as usize {
+            return Err(ArmError::UnsupportedDebugFeature(format!(
+                "watchpoint {}, it has {} DWT comparators",
+                unit_index, self.watchpoint_comparators
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// The distance between the registers of two DWT comparators.
+const DWT_COMPARATOR_STRIDE: u64 = 0x10;
+
+fn dwt_register_address<R: MemoryMappedRegister<u32>>(unit_index: usize) -> u64 {
+    R::get_mmio_address() + unit_index as u64 * DWT_COMPARATOR_STRIDE
+}
+
+/// Calculate the DWT_MASK value needed to watch `length` bytes starting at `address`.
+///
+/// The DWT can only watch naturally aligned, power of two sized ranges.
+fn watchpoint_mask(address: u32, length: u64) -> Result<u32, Error> {
+    let unsupported = |reason| Error::NoWatchpointAvailable {
+        address: address.into(),
+        length,
+        reason,
+    };
+
+    if !length.is_power_of_two() || length > 1 << 31 {
+        return Err(unsupported("its length is not a power of two"));
+    }
+
+    if u64::from(address) % length != 0 {
+        return Err(unsupported("its address is not aligned to its length"));
+    }
+
+    Ok(length.trailing_zeros())
+}
+
+/// Returns the number of DWT comparators, which can each be used as a watchpoint.
+pub(crate) fn available_watchpoint_units(memory: &mut dyn ArmProbe) -> Result<u32, Error> {
+    let ctrl = DwtCtrl(memory.read_word_32(DwtCtrl::get_mmio_address())?);
+
+    Ok(ctrl.numcomp())
+}
+
+fn read_watchpoint(
+    memory: &mut dyn ArmProbe,
+    version: DwtVersion,
+    unit_index: usize,
+    function: DwtFunction,
+) -> Result<Option<Watchpoint>, Error> {
+    let Some(kind) = function.watchpoint_kind(version) else {
+        return Ok(None);
+    };
+
+    let address = memory.read_word_32(dwt_register_address::<DwtComp>(unit_index))?;
+    let size = match version {
+        DwtVersion::V1 => {
+            DwtMask(memory.read_word_32(dwt_register_address::<DwtMask>(unit_index))?).mask()
+        }
+        DwtVersion::V2 => function.datavsize(),
+    };
+
+    Ok(Some(Watchpoint {
+        address: address as u64,
+        length: 1 << size,
+        kind,
+    }))
+}
+
+/// Read the watchpoints configured in the DWT comparators.
+///
+/// Comparators which are configured for anything other than a data address watchpoint,
+/// for example for data tracing, are reported as unset.
+pub(crate) fn hw_watchpoints(
+    memory: &mut dyn ArmProbe,
+    version: DwtVersion,
+) -> Result<Vec<Option<Watchpoint>>, Error> {
+    let num_units = available_watchpoint_units(memory)? as usize;
+
+    (0..num_units)
+        .map(|unit_index| {
+            let function =
+                DwtFunction(memory.read_word_32(dwt_register_address::<DwtFunction>(unit_index))?);
+
+            read_watchpoint(memory, version, unit_index, function)
+        })
+        .collect()
+}
+
+/// Returns for each DWT comparator if it is disabled, and can be used as a watchpoint.
+///
+/// Unlike [`hw_watchpoints`], this reports comparators which are used for anything other than
+/// a watchpoint as occupied.
+pub(crate) fn free_watchpoint_units(memory: &mut dyn ArmProbe) -> Result<Vec<bool>, Error> {
+    let num_units = available_watchpoint_units(memory)? as usize;
+
+    (0..num_units)
+        .map(|unit_index| {
+            let function =
+                DwtFunction(memory.read_word_32(dwt_register_address::<DwtFunction>(unit_index))?);
+
+            Ok(function.function() == DwtFunction::FUNCTION_DISABLED)
+        })
+        .collect()
+}
+
+/// Configure DWT comparator `unit_index` as a data address watchpoint.
+pub(crate) fn set_hw_watchpoint(
+    memory: &mut dyn ArmProbe,
+    version: DwtVersion,
+    unit_index: usize,
+    watchpoint: Watchpoint,
+) -> Result<(), Error> {
+    let address = valid_32bit_address(watchpoint.address)?;
+    let mask = watchpoint_mask(address, watchpoint.length)?;
+
+    if version == DwtVersion::V2 && mask > 2 {
+        return Err(Error::NoWatchpointAvailable {
+            address: watchpoint.address,
+            length: watchpoint.length,
+            reason: "ARMv8-M watchpoints watch up to 4 bytes",
+        });
+    }
+
+    let function_address = dwt_register_address::<DwtFunction>(unit_index);
+    let mut function = DwtFunction(memory.read_word_32(function_address)?);
+
+    if function.function() != DwtFunction::FUNCTION_DISABLED
+        && function.watchpoint_kind(version).is_none()
+    {
+        return Err(Error::Other(anyhow!(
+            "DWT comparator {} is already in use",
+            unit_index
+        )));
+    }
+
+    // The DWT is only accessible when TRCENA is set.
+    let mut demcr = Demcr(memory.read_word_32(Demcr::get_mmio_address())?);
+    if !demcr.trcena() {
+        demcr.set_trcena(true);
+        memory.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
+    }
+
+    // Disable the comparator while it is reconfigured.
+    function.set_function(DwtFunction::FUNCTION_DISABLED);
+    memory.write_word_32(function_address, function.into())?;
+
+    memory.write_word_32(dwt_register_address::<DwtComp>(unit_index), address)?;
+
+    match version {
+        DwtVersion::V1 => {
+            let mask_address = dwt_register_address::<DwtMask>(unit_index);
+            let mut mask_reg = DwtMask(0);
+            mask_reg.set_mask(mask);
+            memory.write_word_32(mask_address, mask_reg.into())?;
+
+            // The maximum mask size is implementation defined, unsupported bits read as zero.
+            let written_mask = DwtMask(memory.read_word_32(mask_address)?);
+            if written_mask.mask() != mask {
+                return Err(Error::Other(anyhow!(
+                    "The watchpoint length {} is not supported by this core",
+                    watchpoint.length
+                )));
+            }
+        }
+        DwtVersion::V2 => {
+            function.set_action(DwtFunction::V2_ACTION_DEBUG_EVENT);
+            function.set_datavsize(mask);
+        }
+    }
+
+    let function_value = DwtFunction::watchpoint_function(version, watchpoint.kind);
+    function.set_function(function_value);
+    memory.write_word_32(function_address, function.into())?;
+
+    // Not every ARMv8-M comparator can match data addresses, the ones which cannot ignore the
+    // write.
+    if version == DwtVersion::V2 {
+        let written = DwtFunction(memory.read_word_32(function_address)?);
+        if written.function() != function_value {
+            return Err(Error::Other(anyhow!(
+                "DWT comparator {} cannot be used as a watchpoint",
+                unit_index
+            )));
+        }
+    }
+
+    memory.flush()?;
+
+    Ok(())
+}
+
+/// Disable DWT comparator `unit_index`.
+pub(crate) fn clear_hw_watchpoint(
+    memory: &mut dyn ArmProbe,
+    unit_index: usize,
+) -> Result<(), Error> {
+    let mut function = DwtFunction(0);
+    function.set_function(DwtFunction::FUNCTION_DISABLED);
+
+    memory.write_word_32(
+        dwt_register_address::<DwtFunction>(unit_index),
+        function.into(),
+    )?;
+
+    Ok(())
+}
+
+/// Find the watchpoint which matched, using the MATCHED bit of the DWT comparators.
+///
+/// Reading DWT_FUNCTION clears the MATCHED bit, so this only reports a watchpoint once.
+pub(crate) fn triggered_hw_watchpoint(
+    memory: &mut dyn ArmProbe,
+    version: DwtVersion,
+) -> Result<Option<Watchpoint>, Error> {
+    let num_units = available_watchpoint_units(memory)? as usize;
+
+    for unit_index in 0..num_units {
+        let function =
+            DwtFunction(memory.read_word_32(dwt_register_address::<DwtFunction>(unit_index))?);
+
+        if function.matched() {
+            if let Some(watchpoint) = read_watchpoint(memory, version, unit_index, function)? {
+                return Ok(Some(watchpoint));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Find the watchpoint which halted the core, if the DWT trap in DFSR reported `reason`.
+///
+/// This has to be done when the halt is first seen, because the MATCHED bits are cleared when
+/// they are read.
+pub(crate) fn resolve_halt_reason(
+    memory: &mut dyn ArmProbe,
+    version: DwtVersion,
+    reason: HaltReason,
+) -> Result<HaltReason, Error> {
+    match reason {
+        HaltReason::Watchpoint(None) => Ok(HaltReason::Watchpoint(triggered_hw_watchpoint(
+            memory, version,
+        )?)),
+        reason => Ok(reason),
+    }
+}
+
+/// Enable or disable halting the core when a fault exception is taken.
+///
+/// ARMv6-M cores only catch HardFaults. With `all_faults`, the MemManage, BusFault and
+/// UsageFault vector catches of ARMv7-M and ARMv8-M are changed as well.
+pub(crate) fn set_fault_catch(
+    memory: &mut dyn ArmProbe,
+    all_faults: bool,
+    enabled: bool,
+) -> Result<(), Error> {
+    let mut demcr = Demcr(memory.read_word_32(Demcr::get_mmio_address())?);
+    demcr.set_vc_harderr(enabled);
+    if all_faults {
+        demcr.set_vc_interr(enabled);
+        demcr.set_vc_buserr(enabled);
+        demcr.set_vc_staterr(enabled);
+        demcr.set_vc_chkerr(enabled);
+        demcr.set_vc_nocperr(enabled);
+        demcr.set_vc_mmerr(enabled);
+    }
+    memory.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_independent_register, watchpoint_mask, CortexMCapabilities, DwtFunction, DwtVersion,
+        FpbVersion, RegisterCache,
+    };
+    use crate::architecture::arm::core::mock::MockCortexM;
+    use crate::{architecture::arm::ArmError, core::RegisterId, WatchpointKind};
+
+    #[test]
+    fn registers_are_transferred_in_one_batch() {
+        let mut mock = MockCortexM::halted();
+        mock.state_mut()
+            .registers
+            .extend([(0, 10), (1, 11), (2, 12)]);
+
+        let mut cache = RegisterCache::default();
+        let ids = [RegisterId(0), RegisterId(1), RegisterId(2)];
+
+        assert_eq!(cache.read_many(&mut mock, &ids).unwrap(), [10, 11, 12]);
+        assert_eq!(mock.state_mut().batches, 1);
+
+        // Cached registers are not transferred again.
+        assert_eq!(cache.read_many(&mut mock, &ids).unwrap(), [10, 11, 12]);
+        assert_eq!(mock.state_mut().batches, 1);
+
+        cache
+            .write_many(&mut mock, &[(RegisterId(0), 20), (RegisterId(2), 22)])
+            .unwrap();
+        cache.flush(&mut mock).unwrap();
+        assert_eq!(mock.state_mut().batches, 2);
+        assert_eq!(mock.state_mut().registers[&0], 20);
+        assert_eq!(mock.state_mut().registers[&2], 22);
+        assert_eq!(mock.state_mut().register_transfers, 5);
+    }
+
+    #[test]
+    fn incomplete_pipelined_transfers_are_repeated() {
+        let mut mock = MockCortexM::halted();
+        mock.state_mut().registers.extend([(0, 10), (1, 11)]);
+        // S_REGRDY is only set on the second poll.
+        mock.state_mut().busy_polls = 1;
+
+        let mut cache = RegisterCache::default();
+        assert_eq!(
+            cache
+                .read_many(&mut mock, &[RegisterId(0), RegisterId(1)])
+                .unwrap(),
+            [10, 11]
+        );
+        // Both registers are read again, one by one.
+        assert_eq!(mock.state_mut().register_transfers, 4);
+
+        cache
+            .write_many(&mut mock, &[(RegisterId(0), 20), (RegisterId(1), 21)])
+            .unwrap();
+        cache.flush(&mut mock).unwrap();
+        assert_eq!(mock.state_mut().registers[&0], 20);
+        assert_eq!(mock.state_mut().registers[&1], 21);
+        assert_eq!(mock.state_mut().register_transfers, 8);
+    }
+
+    #[test]
+    fn register_cache_reads_once_and_writes_back_dirty_registers() {
+        let mut mock = MockCortexM::halted();
+        mock.state_mut()
+            .registers
+            .extend([(0, 10), (1, 11), (15, 0x100)]);
+
+        let mut cache = RegisterCache::default();
+
+        assert_eq!(cache.read_many(&mut mock, &[RegisterId(0)]).unwrap(), [10]);
+        assert_eq!(
+            cache
+                .read_many(&mut mock, &[RegisterId(0), RegisterId(1), RegisterId(1)])
+                .unwrap(),
+            [10, 11, 11]
+        );
+        assert_eq!(mock.state_mut().register_transfers, 2);
+
+        // Writes stay in the cache until it is flushed.
+        cache
+            .write_many(&mut mock, &[(RegisterId(0), 20), (RegisterId(15), 0x200)])
+            .unwrap();
+        assert_eq!(
+            cache.read_many(&mut mock, &[RegisterId(15)]).unwrap(),
+            [0x200]
+        );
+        assert_eq!(mock.state_mut().registers[&0], 10);
+        assert_eq!(mock.state_mut().register_transfers, 2);
+
+        cache.flush(&mut mock).unwrap();
+        assert_eq!(mock.state_mut().registers[&0], 20);
+        assert_eq!(mock.state_mut().registers[&15], 0x200);
+        assert_eq!(mock.state_mut().register_transfers, 4);
+
+        // Only dirty registers are written back.
+        cache.flush(&mut mock).unwrap();
+        assert_eq!(mock.state_mut().register_transfers, 4);
+
+        // CONTROL also switches the stack pointer, so it is written through, and the cache,
+        // which would hold the old stack pointer, is cleared.
+        cache.write(&mut mock, RegisterId(1), 21).unwrap();
+        cache.write(&mut mock, RegisterId(0x14), 0x2).unwrap();
+        assert_eq!(mock.state_mut().registers[&1], 21);
+        assert_eq!(mock.state_mut().registers[&0x14], 0x2);
+        assert_eq!(mock.state_mut().register_transfers, 6);
+
+        assert_eq!(cache.read_many(&mut mock, &[RegisterId(0)]).unwrap(), [20]);
+        assert_eq!(mock.state_mut().register_transfers, 7);
+    }
+
+    #[test]
+    fn armv6m_without_comparators() {
+        // A Cortex-M0+ with two breakpoint comparators and no DWT comparators.
+        let capabilities = CortexMCapabilities {
+            fpb_version: Some(FpbVersion::V1),
+            breakpoint_comparators: 2,
+            watchpoint_comparators: 0,
+        };
+
+        assert_eq!(
+            capabilities.check_breakpoint(1, Some(0x0000_1000)).unwrap(),
+            FpbVersion::V1
+        );
+        assert!(matches!(
+            capabilities.check_breakpoint(2, Some(0x0000_1000)),
+            Err(ArmError::UnsupportedDebugFeature(_))
+        ));
+        assert!(matches!(
+            capabilities.check_breakpoint(0, Some(0x2000_0000)),
+            Err(ArmError::UnsupportedBreakpointAddress(0x2000_0000))
+        ));
+        assert!(matches!(
+            capabilities.check_watchpoint(0),
+            Err(ArmError::UnsupportedDebugFeature(_))
+        ));
+
+        let unknown = CortexMCapabilities {
+            fpb_version: None,
+            ..capabilities
+        };
+        assert!(unknown.fpb_version().is_err());
+    }
+
+    #[test]
+    fn watchpoint_mask_for_aligned_ranges() {
+        assert_eq!(watchpoint_mask(0x2000_0000, 1).unwrap(), 0);
+        assert_eq!(watchpoint_mask(0x2000_0002, 2).unwrap(), 1);
+        assert_eq!(watchpoint_mask(0x2000_0010, 16).unwrap(), 4);
+    }
+
+    #[test]
+    fn watchpoint_mask_rejects_invalid_ranges() {
+        for (address, length) in [(0x2000_0000, 0), (0x2000_0000, 3), (0x2000_0002, 4)] {
+            assert!(matches!(
+                watchpoint_mask(address, length),
+                Err(crate::Error::NoWatchpointAvailable { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn dwt_function_encoding_depends_on_version() {
+        let kinds = [
+            WatchpointKind::Read,
+            WatchpointKind::Write,
+            WatchpointKind::ReadWrite,
+        ];
+
+        for version in [DwtVersion::V1, DwtVersion::V2] {
+            for kind in kinds {
+                let mut function = DwtFunction(0);
+                function.set_action(DwtFunction::V2_ACTION_DEBUG_EVENT);
+                function.set_function(DwtFunction::watchpoint_function(version, kind));
+
+                assert_eq!(function.watchpoint_kind(version), Some(kind));
+            }
+        }
+
+        // Read on ARMv6-M and ARMv7-M is write on ARMv8-M.
+        assert_eq!(
+            DwtFunction(0b0101).watchpoint_kind(DwtVersion::V1),
+            Some(WatchpointKind::Read)
+        );
+        assert_eq!(
+            DwtFunction(0b01_0101).watchpoint_kind(DwtVersion::V2),
+            Some(WatchpointKind::Write)
+        );
+
+        // An ARMv8-M comparator which only generates trace packets is no watchpoint.
+        assert_eq!(DwtFunction(0b10_0101).watchpoint_kind(DwtVersion::V2), None);
+    }
+
+    #[test]
+    fn banked_registers_are_not_cached_for_writes() {
+        // R0, PC and S0 can be written back later.
+        assert!(is_independent_register(RegisterId(0)));
+        assert!(is_independent_register(RegisterId(15)));
+        assert!(is_independent_register(RegisterId(0x40)));
+
+        // SP, MSP, PSP and CONTROL/FAULTMASK/BASEPRI/PRIMASK change other registers.
+        assert!(!is_independent_register(RegisterId(13)));
+        assert!(!is_independent_register(RegisterId(0x11)));
+        assert!(!is_independent_register(RegisterId(0x12)));
+        assert!(!is_independent_register(RegisterId(0x14)));
+    }
+}
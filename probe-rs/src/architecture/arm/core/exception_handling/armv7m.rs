@@ -12,7 +12,7 @@ memory_mapped_bitfield_register! {
     0xE000ED2C, "HFSR",
     impl From;
     debug_event, _: 31;
-    escalation_forced, _: 30;
+    pub escalation_forced, _: 30;
     vector_table_read_fault, _: 1;
 }
 
@@ -216,7 +216,10 @@ impl From<u32> for ExceptionReason {
 impl ExceptionReason {
     /// Expands the exception reason, by providing additional information about the exception from the
     /// HFSR and CFSR registers.
-    fn expanded_description<T: CoreInterface>(&self, core: &mut T) -> Result<String, Error> {
+    pub(crate) fn expanded_description<T: CoreInterface>(
+        &self,
+        core: &mut T,
+    ) -> Result<String, Error> {
         match self {
             ExceptionReason::ThreadMode => Ok("No active exception.".to_string()),
             ExceptionReason::Reset => Ok("Reset handler.".to_string()),
@@ -0,0 +1,142 @@
+use crate::{
+    architecture::arm::core::armv8m::{Armv8m, SecureFault},
+    core::{ExceptionInfo, ExceptionInterface},
+    debug::DebugRegisters,
+    memory_mapped_bitfield_register, Error, MemoryInterface, MemoryMappedRegister,
+};
+
+use super::{
+    armv6m_armv7m_shared::{calling_frame_registers, exception_details, Xpsr},
+    armv7m::{ExceptionReason, Hfsr},
+};
+
+/// The exception number of the SecureFault, which is reserved on cores without the Security
+/// Extension.
+const SECURE_FAULT_EXCEPTION: u32 = 7;
+
+memory_mapped_bitfield_register! {
+    /// SFSR - Secure Fault Status Register
+    pub struct Sfsr(u32);
+    0xE000EDE4, "SFSR",
+    impl From;
+    /// A fault occurred during lazy state activation or deactivation.
+    lserr, _: 7;
+    /// SFAR has valid contents.
+    sfarvalid, _: 6;
+    /// A fault occurred during lazy preservation of the floating-point state.
+    lsperr, _: 5;
+    /// A branch to Non-secure state did not use an instruction which switches the security state.
+    invtran, _: 4;
+    /// An attempt was made to access a Secure address from Non-secure state.
+    auviol, _: 3;
+    /// An exception return was invalid, for example because of an incorrect EXC_RETURN value.
+    inver, _: 2;
+    /// The integrity signature in an exception stack frame was invalid.
+    invis, _: 1;
+    /// A Non-secure function call branched to a Secure address which is not a valid entry point.
+    invep, _: 0;
+}
+
+impl Sfsr {
+    /// The cause of the SecureFault, or `None` if no SecureFault occurred.
+    fn cause(&self) -> Option<&'static str> {
+        let cause = if self.invep() {
+            "Invalid entry point"
+        } else if self.invis() {
+            "Invalid integrity signature"
+        } else if self.inver() {
+            "Invalid exception return"
+        } else if self.auviol() {
+            "Attribution unit violation"
+        } else if self.invtran() {
+            "Invalid transition from Secure to Non-secure state"
+        } else if self.lsperr() {
+            "Fault occurred during FP lazy state preservation"
+        } else if self.lserr() {
+            "Fault occurred during lazy state activation or deactivation"
+        } else {
+            return None;
+        };
+
+        Some(cause)
+    }
+}
+
+memory_mapped_bitfield_register! {
+    /// SFAR - Secure Fault Address Register
+    pub struct Sfar(u32);
+    0xE000EDE8, "SFAR",
+    impl From;
+}
+
+/// Read and decode the SecureFault status of the core, or `Ok(None)` if no SecureFault occurred.
+///
+/// SFSR and SFAR can only be read when debugging Secure state is allowed, they read as zero
+/// otherwise.
+pub(crate) fn read_secure_fault(
+    core: &mut impl MemoryInterface,
+) -> Result<Option<SecureFault>, Error> {
+    let sfsr = Sfsr(core.read_word_32(Sfsr::get_mmio_address())?);
+
+    let Some(cause) = sfsr.cause() else {
+        return Ok(None);
+    };
+
+    let address = if sfsr.sfarvalid() {
+        Some(core.read_word_32(Sfar::get_mmio_address())?)
+    } else {
+        None
+    };
+
+    Ok(Some(SecureFault { cause, address }))
+}
+
+impl<'probe> ExceptionInterface for Armv8m<'probe> {
+    fn calling_frame_registers(
+        &mut self,
+        stackframe_registers: &DebugRegisters,
+    ) -> Result<DebugRegisters, Error> {
+        calling_frame_registers(self, stackframe_registers)
+    }
+
+    fn exception_description(
+        &mut self,
+        stackframe_registers: &DebugRegisters,
+    ) -> Result<String, Error> {
+        // Load the provided xPSR register as a bitfield.
+        let exception_number = Xpsr(
+            stackframe_registers
+                .get_register_value_by_role(&crate::core::RegisterRole::ProcessorStatus)?
+                as u32,
+        )
+        .exception_number();
+
+        let exception = ExceptionReason::from(exception_number);
+
+        let description = if exception_number == SECURE_FAULT_EXCEPTION {
+            match read_secure_fault(self)? {
+                Some(fault) => format!("SecureFault handler. Cause: {fault}."),
+                None => "SecureFault handler. Cause: Unknown.".to_string(),
+            }
+        } else if exception == ExceptionReason::HardFault
+            && Hfsr(self.read_word_32(Hfsr::get_mmio_address())?).escalation_forced()
+        {
+            // A SecureFault escalates to a HardFault if it is disabled or cannot preempt.
+            match read_secure_fault(self)? {
+                Some(fault) => format!("HardFault handler. Cause: Escalated {fault}."),
+                None => exception.expanded_description(self)?,
+            }
+        } else {
+            exception.expanded_description(self)?
+        };
+
+        Ok(format!("{description:?}"))
+    }
+
+    fn exception_details(
+        &mut self,
+        stackframe_registers: &DebugRegisters,
+    ) -> Result<Option<ExceptionInfo>, Error> {
+        exception_details(self, stackframe_registers)
+    }
+}
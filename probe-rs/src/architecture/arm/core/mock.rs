@@ -0,0 +1,240 @@
+//! A Cortex-M debug interface for tests.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::cortex_m::{Dcrdr, Dcrsr, Dhcsr};
+use super::Dfsr;
+use crate::architecture::arm::ap::MemoryAp;
+use crate::architecture::arm::communication_interface::{Initialized, SwdSequence};
+use crate::architecture::arm::memory::adi_v5_memory_interface::{ArmProbe, WordAccess};
+use crate::architecture::arm::{ApAddress, ArmCommunicationInterface, ArmError, DpAddress};
+use crate::{DebugProbeError, MemoryMappedRegister};
+
+/// The state of the emulated core, shared by all handles of a [`MockCortexM`].
+#[derive(Debug, Default)]
+pub(crate) struct MockCortexMState {
+    /// Memory, which reads zero where nothing was written.
+    pub words: HashMap<u64, u32>,
+    /// The core registers by their DCRSR REGSEL number, which read zero if they were not set.
+    pub registers: HashMap<u16, u32>,
+    /// The number of register transfers started through DCRSR.
+    pub register_transfers: usize,
+    /// The number of calls of [`ArmProbe::transfer_32`].
+    pub batches: usize,
+    /// The number of DHCSR reads after the start of a register transfer which report it as not
+    /// complete yet.
+    pub busy_polls: usize,
+    busy: usize,
+    /// The control bits written to DHCSR.
+    control: u32,
+    halted: bool,
+    /// The sticky S_RETIRE_ST bit.
+    retired: bool,
+    dcrdr: u32,
+    dfsr: u32,
+}
+
+/// A Cortex-M core behind a memory AP, which emulates the halting and core register
+/// transfers of the debug registers.
+///
+/// Clones share the state, so a test can keep a handle to inspect the core after passing one to
+/// a core implementation.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MockCortexM {
+    state: Rc<RefCell<MockCortexMState>>,
+}
+
+impl MockCortexM {
+    /// A core which is halted on a debug request.
+    pub fn halted() -> Self {
+        let mock = Self::default();
+        {
+            let mut state = mock.state_mut();
+            state.control = 0b11;
+            state.halted = true;
+            state.dfsr = 0b1;
+        }
+        mock
+    }
+
+    /// Access the state of the core.
+    pub fn state_mut(&self) -> std::cell::RefMut<'_, MockCortexMState> {
+        self.state.borrow_mut()
+    }
+
+    /// Returns `true` if the core is halted.
+    pub fn is_halted(&self) -> bool {
+        self.state.borrow().halted
+    }
+
+    /// Let the core run and halt again, without a debugger being involved, and set `registers`
+    /// to new values while it runs.
+    pub fn run_and_halt(&self, registers: &[(u16, u32)]) {
+        let mut state = self.state_mut();
+        state.registers.extend(registers.iter().copied());
+        state.retired = true;
+        state.halted = true;
+        state.dfsr |= 0b1;
+    }
+
+    fn read_word(&self, address: u64) -> u32 {
+        let mut state = self.state_mut();
+
+        if address == Dhcsr::get_mmio_address() {
+            let mut value = state.control;
+            if state.busy == 0 {
+                // S_REGRDY
+                value |= 1 << 16;
+            } else {
+                state.busy -= 1;
+            }
+            if state.halted {
+                value |= 1 << 17;
+            }
+            if std::mem::take(&mut state.retired) {
+                value |= 1 << 24;
+            }
+            value
+        } else if address == Dcrdr::get_mmio_address() {
+            state.dcrdr
+        } else if address == Dfsr::get_mmio_address() {
+            state.dfsr
+        } else {
+            state.words.get(&address).copied().unwrap_or(0)
+        }
+    }
+
+    fn write_word(&self, address: u64, value: u32) {
+        let mut state = self.state_mut();
+
+        if address == Dhcsr::get_mmio_address() {
+            if value >> 16 != 0xa05f {
+                return;
+            }
+            let dhcsr = Dhcsr(value);
+            state.control = value & 0xffff;
+
+            if dhcsr.c_halt() {
+                if !state.halted {
+                    state.halted = true;
+                    state.dfsr |= 0b1;
+                }
+            } else if dhcsr.c_step() {
+                // A single step, which halts again right away.
+                state.retired = true;
+                state.halted = true;
+                state.dfsr |= 0b1;
+            } else {
+                state.retired = true;
+                state.halted = false;
+            }
+        } else if address == Dcrsr::get_mmio_address() {
+            let regsel = (value & 0x7f) as u16;
+            state.register_transfers += 1;
+            state.busy = state.busy_polls;
+
+            if value & (1 << 16) != 0 {
+                let dcrdr = state.dcrdr;
+                state.registers.insert(regsel, dcrdr);
+            } else {
+                state.dcrdr = state.registers.get(&regsel).copied().unwrap_or(0);
+            }
+        } else if address == Dcrdr::get_mmio_address() {
+            state.dcrdr = value;
+        } else if address == Dfsr::get_mmio_address() {
+            state.dfsr &= !value;
+        } else {
+            state.words.insert(address, value);
+        }
+    }
+}
+
+impl SwdSequence for MockCortexM {
+    fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
+        unimplemented!()
+    }
+
+    fn swj_pins(
+        &mut self,
+        _pin_out: u32,
+        _pin_select: u32,
+        _pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        unimplemented!()
+    }
+}
+
+impl ArmProbe for MockCortexM {
+    fn read_8(&mut self, _address: u64, _data: &mut [u8]) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+        for (i, word) in data.iter_mut().enumerate() {
+            *word = self.read_word(address + 4 * i as u64);
+        }
+        Ok(())
+    }
+
+    fn read_64(&mut self, _address: u64, _data: &mut [u64]) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn write_8(&mut self, _address: u64, _data: &[u8]) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError> {
+        for (i, &word) in data.iter().enumerate() {
+            self.write_word(address + 4 * i as u64, word);
+        }
+        Ok(())
+    }
+
+    fn write_64(&mut self, _address: u64, _data: &[u64]) -> Result<(), ArmError> {
+        unimplemented!()
+    }
+
+    fn transfer_32(&mut self, accesses: &[WordAccess]) -> Result<Vec<u32>, ArmError> {
+        self.state_mut().batches += 1;
+
+        let mut values = Vec::new();
+        for access in accesses {
+            match *access {
+                WordAccess::Read(address) => values.push(self.read_word(address)),
+                WordAccess::Write(address, value) => self.write_word(address, value),
+            }
+        }
+
+        Ok(values)
+    }
+
+    fn flush(&mut self) -> Result<(), ArmError> {
+        Ok(())
+    }
+
+    fn supports_native_64bit_access(&mut self) -> bool {
+        false
+    }
+
+    fn supports_8bit_transfers(&self) -> Result<bool, ArmError> {
+        Ok(false)
+    }
+
+    fn ap(&mut self) -> MemoryAp {
+        MemoryAp::new(ApAddress {
+            dp: DpAddress::Default,
+            ap: 0,
+        })
+    }
+
+    fn get_arm_communication_interface(
+        &mut self,
+    ) -> Result<&mut ArmCommunicationInterface<Initialized>, DebugProbeError> {
+        Err(DebugProbeError::NotImplemented(
+            "get_arm_communication_interface",
+        ))
+    }
+}
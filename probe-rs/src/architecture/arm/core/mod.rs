@@ -1,6 +1,6 @@
 use crate::{
     core::{BreakpointCause, RegisterValue},
-    memory_mapped_bitfield_register, CoreStatus, HaltReason,
+    memory_mapped_bitfield_register, CoreStatus, HaltReason, MemoryMappedRegister,
 };
 
 pub mod armv6m;
@@ -14,6 +14,8 @@ pub(crate) mod armv8a_debug_regs;
 pub(crate) mod cortex_m;
 pub(crate) mod exception_handling;
 pub(crate) mod instructions;
+#[cfg(test)]
+pub(crate) mod mock;
 pub(crate) mod registers;
 
 /// Core information data which is downloaded from the target, represents its state and can be used for debugging.
@@ -77,7 +79,7 @@ impl Dfsr {
         } else if self.external() {
             HaltReason::External
         } else if self.dwttrap() {
-            HaltReason::Watchpoint
+            HaltReason::Watchpoint(None)
         } else if self.halted() {
             HaltReason::Request
         } else if self.vcatch() {
@@ -113,6 +115,16 @@ pub struct CortexMState {
     current_state: CoreStatus,
 
     fp_present: bool,
+
+    register_cache: cortex_m::RegisterCache,
+
+    capabilities: Option<cortex_m::CortexMCapabilities>,
+
+    /// The value of DEMCR which is restored if the target resets the debug logic.
+    demcr: Option<u32>,
+
+    /// Whether DHCSR reported a reset which the debugger did not request.
+    target_reset: bool,
 }
 
 impl CortexMState {
@@ -122,6 +134,10 @@ impl CortexMState {
             hw_breakpoints_enabled: false,
             current_state: CoreStatus::Unknown,
             fp_present: false,
+            register_cache: Default::default(),
+            capabilities: None,
+            demcr: None,
+            target_reset: false,
         }
     }
 
@@ -132,6 +148,65 @@ impl CortexMState {
     fn initialized(&self) -> bool {
         self.initialized
     }
+
+    /// The debug capabilities of the core, read on first use.
+    fn capabilities(
+        &mut self,
+        memory: &mut dyn super::memory::adi_v5_memory_interface::ArmProbe,
+    ) -> Result<cortex_m::CortexMCapabilities, crate::Error> {
+        if let Some(capabilities) = self.capabilities {
+            return Ok(capabilities);
+        }
+
+        let capabilities = cortex_m::CortexMCapabilities::read(memory)?;
+        self.capabilities = Some(capabilities);
+        Ok(capabilities)
+    }
+
+    /// Save DEMCR, to restore it if the target resets the debug logic of the core.
+    fn save_demcr(
+        &mut self,
+        memory: &mut dyn super::memory::adi_v5_memory_interface::ArmProbe,
+    ) -> Result<(), super::ArmError> {
+        self.demcr = Some(memory.read_word_32(armv7m::Demcr::get_mmio_address())?);
+        Ok(())
+    }
+
+    /// Record a reset of the core if the sticky DHCSR.S_RESET_ST bit is set. The bit is
+    /// cleared by reading DHCSR.
+    fn check_reset(&mut self, s_reset_st: bool) {
+        if s_reset_st {
+            tracing::debug!("DHCSR reports a reset of the core");
+            self.target_reset = true;
+        }
+    }
+
+    fn take_target_reset(&mut self) -> bool {
+        std::mem::take(&mut self.target_reset)
+    }
+
+    /// Enable halting debug again and restore DEMCR, in case the reset cleared them.
+    fn restore_debug_settings(
+        &mut self,
+        memory: &mut dyn super::memory::adi_v5_memory_interface::ArmProbe,
+    ) -> Result<(), super::ArmError> {
+        let mut dhcsr = cortex_m::Dhcsr(memory.read_word_32(cortex_m::Dhcsr::get_mmio_address())?);
+        if !dhcsr.c_debugen() {
+            tracing::warn!("Halting debug was disabled by the reset, enabling it again");
+            dhcsr.set_c_debugen(true);
+            dhcsr.enable_write();
+            memory.write_word_32(cortex_m::Dhcsr::get_mmio_address(), dhcsr.into())?;
+        }
+
+        if let Some(demcr) = self.demcr {
+            if memory.read_word_32(armv7m::Demcr::get_mmio_address())? != demcr {
+                tracing::warn!("DEMCR was changed by the reset, restoring it");
+                memory.write_word_32(armv7m::Demcr::get_mmio_address(), demcr)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,142 @@
+//! Health checks of a debug port
+
+use super::{Ctrl, DpAccess};
+use crate::architecture::arm::{ArmError, ArmProbeInterface, DpAddress};
+use crate::events::SessionEvent;
+use crate::Session;
+
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// The health of a debug port, decoded from its CTRL/STAT register.
+///
+/// Marginal hardware setups, like long cables or an unstable target supply, often show up
+/// here before they cause failed transfers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct DpHealth {
+    /// The debug power domain is not powered, although power up was requested.
+    pub debug_power_lost: bool,
+    /// The system power domain is not powered, although power up was requested.
+    pub system_power_lost: bool,
+    /// The power up requests were cleared, which happens when the debug port is reset,
+    /// for example by a power cycle of the target.
+    pub reset_detected: bool,
+    /// An AP transaction failed (STICKYERR).
+    pub sticky_error: bool,
+    /// An overrun occurred (STICKYORUN).
+    pub sticky_overrun: bool,
+    /// A pushed-compare or pushed-verify operation failed (STICKYCMP).
+    pub sticky_compare: bool,
+    /// The data phase of a write was corrupted (WDATAERR).
+    pub write_data_error: bool,
+}
+
+impl DpHealth {
+    /// Decode the health from the CTRL/STAT register of a debug port which was powered up.
+    pub fn from_ctrl(ctrl: &Ctrl) -> Self {
+        let reset_detected = !ctrl.cdbgpwrupreq() && !ctrl.csyspwrupreq();
+
+        Self {
+            debug_power_lost: ctrl.cdbgpwrupreq() && !ctrl.cdbgpwrupack(),
+            system_power_lost: ctrl.csyspwrupreq() && !ctrl.csyspwrupack(),
+            reset_detected,
+            sticky_error: ctrl.sticky_err(),
+            sticky_overrun: ctrl.sticky_orun(),
+            sticky_compare: ctrl.stick_cmp(),
+            write_data_error: ctrl.w_data_err(),
+        }
+    }
+
+    /// Read the CTRL/STAT register of `dp` and decode it.
+    pub fn read(interface: &mut dyn ArmProbeInterface, dp: DpAddress) -> Result<Self, ArmError> {
+        let ctrl: Ctrl = interface.read_dp_register(dp)?;
+
+        Ok(Self::from_ctrl(&ctrl))
+    }
+
+    /// Returns `true` if no problem was detected.
+    pub fn is_healthy(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Periodically checks the health of a debug port.
+///
+/// Call [`DpHealthMonitor::poll`] regularly, for example from the loop which reads RTT data.
+/// The CTRL/STAT register is only read when the interval has elapsed, and a
+/// [`SessionEvent::DpHealth`] is only emitted when a problem appears or changes.
+#[derive(Debug, Clone)]
+pub struct DpHealthMonitor {
+    dp: DpAddress,
+    interval: Duration,
+    last_check: Option<Instant>,
+    last_health: DpHealth,
+}
+
+impl DpHealthMonitor {
+    /// Create a monitor which checks `dp` every `interval`.
+    pub fn new(dp: DpAddress, interval: Duration) -> Self {
+        Self {
+            dp,
+            interval,
+            last_check: None,
+            last_health: DpHealth::default(),
+        }
+    }
+
+    /// Check the health, if the interval has elapsed since the last check.
+    ///
+    /// Returns `None` if no check was done.
+    pub fn poll(&mut self, session: &mut Session) -> Result<Option<DpHealth>, ArmError> {
+        if let Some(last_check) = self.last_check {
+            if last_check.elapsed() < self.interval {
+                return Ok(None);
+            }
+        }
+        self.last_check = Some(Instant::now());
+
+        let health = DpHealth::read(session.get_arm_interface()?, self.dp)?;
+
+        if !health.is_healthy() && health != self.last_health {
+            report(self.dp, health);
+        }
+        self.last_health = health;
+
+        Ok(Some(health))
+    }
+}
+
+/// Log a detected problem, and emit it as a session event.
+pub(crate) fn report(dp: DpAddress, health: DpHealth) {
+    tracing::warn!("Debug port {:x?} is not healthy: {:?}", dp, health);
+
+    crate::events::emit(|| SessionEvent::DpHealth {
+        targetsel: match dp {
+            DpAddress::Default => None,
+            DpAddress::Multidrop(targetsel) => Some(targetsel),
+        },
+        health,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ctrl, DpHealth};
+
+    #[test]
+    fn decode_ctrl_stat() {
+        // Power up requested and acknowledged.
+        let healthy = Ctrl(0xf000_0000);
+        assert!(DpHealth::from_ctrl(&healthy).is_healthy());
+
+        // The debug power up ack was lost, and STICKYERR is set.
+        let health = DpHealth::from_ctrl(&Ctrl(0xd000_0020));
+        assert!(health.debug_power_lost);
+        assert!(health.sticky_error);
+        assert!(!health.system_power_lost);
+        assert!(!health.reset_detected);
+
+        // The power up requests were cleared by a reset.
+        assert!(DpHealth::from_ctrl(&Ctrl(0)).reset_detected);
+    }
+}
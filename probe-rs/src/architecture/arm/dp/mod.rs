@@ -2,6 +2,12 @@
 
 #[macro_use]
 mod register_generation;
+pub(crate) mod health;
+mod multidrop;
+
+pub use health::{DpHealth, DpHealthMonitor};
+pub(crate) use multidrop::targetsel_candidates;
+pub use multidrop::{known_multidrop_targetids, MultidropTarget};
 
 use super::{
     communication_interface::RegisterParseError, ArmError, DapAccess, DapError, DpAddress, Register,
@@ -42,6 +48,10 @@ pub enum DebugPortError {
     #[error("Debug port not supported: {0}")]
     Unsupported(String),
 
+    /// The debug port did not respond over any of the wire protocols of the probe.
+    #[error("The debug port does not respond over SWD or JTAG.")]
+    NotResponding,
+
     /// An error occurred in the communication with an access port or debug port.
     #[error("An error occurred in the communication with an access port or debug port.")]
     Dap(#[from] DapError),
@@ -67,7 +77,7 @@ pub trait DpAccess {
     ) -> Result<(), ArmError>;
 }
 
-impl<T: DapAccess> DpAccess for T {
+impl<T: DapAccess + ?Sized> DpAccess for T {
     fn read_dp_register<R: DpRegister>(&mut self, dp: DpAddress) -> Result<R, ArmError> {
         tracing::debug!("Reading DP register {}", R::NAME);
         let result = self.read_raw_dp_register(dp, R::ADDRESS)?;
@@ -338,6 +348,10 @@ bitfield! {
     /// After a powerup reset, this field is 0x0. Note
     /// Some previous ADI revisions have described DPBANKSEL as a single-bit field called CTRSEL, defined only for SW-DP. From issue B of this document, DPBANKSEL is redefined. The new definition is backwards-compatible.
     pub u8, dp_bank_sel, set_dp_bank_sel: 3, 0;
+    /// Bits `[31:4]` of the address in the debug address space of a DPv3 debug port (ADIv6),
+    /// which replace the APSEL and APBANKSEL fields. The upper bits of the address are in
+    /// [`SELECT1`].
+    pub u32, addr, set_addr: 31, 4;
 }
 
 impl TryFrom<u32> for Select {
@@ -383,7 +397,8 @@ bitfield! {
     ///
     /// `0x0`: Reserved. Implementations of DPv0 do not implement DPIDR.\
     /// `0x1`: DPv1 is implemented.\
-    /// `0x2`: DPv2 is implemented.
+    /// `0x2`: DPv2 is implemented.\
+    /// `0x3`: DPv3 is implemented (ADIv6).
     ///
     /// All remaining values are reserved.
     pub u8, version, _: 15, 12;
@@ -459,6 +474,14 @@ bitfield! {
     pub u16, tdesigner, _: 11, 1;
 }
 
+impl TARGETID {
+    /// The JEP106 code of the designer of the part, decoded from [`TARGETID::tdesigner`].
+    pub fn designer(&self) -> JEP106Code {
+        let designer = self.tdesigner();
+        JEP106Code::new((designer >> 7) as u8, (designer & 0x7f) as u8)
+    }
+}
+
 impl TryFrom<u32> for TARGETID {
     type Error = RegisterParseError;
 
@@ -482,6 +505,139 @@ impl Register for TARGETID {
     const NAME: &'static str = "TARGETID";
 }
 
+bitfield! {
+    /// DPIDR1, Debug Port Identification register 1 (see ADI v6.0 B2.2.6)
+    ///
+    /// DPIDR1 is only present in DPv3 and higher.
+    #[derive(Clone)]
+    pub struct DPIDR1(u32);
+    impl Debug;
+    /// Error mode. `0b1` if errors of AP accesses are reported with each access, and the
+    /// sticky error flag is not set.
+    pub errmode, _: 7;
+    /// The size of the debug address space of the debug port, in bits.
+    pub u8, asize, _: 6, 0;
+}
+
+impl TryFrom<u32> for DPIDR1 {
+    type Error = RegisterParseError;
+
+    fn try_from(raw: u32) -> Result<Self, Self::Error> {
+        Ok(Self(raw))
+    }
+}
+
+impl From<DPIDR1> for u32 {
+    fn from(raw: DPIDR1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for DPIDR1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for DPIDR1 {
+    const ADDRESS: u8 = 0x10;
+    const NAME: &'static str = "DPIDR1";
+}
+
+bitfield! {
+    /// BASEPTR0, Base Pointer register 0 (see ADI v6.0 B2.2.2)
+    ///
+    /// BASEPTR0 holds the address of the first component in the debug address space of a DPv3
+    /// debug port, usually a ROM table which lists the APs.
+    #[derive(Clone)]
+    pub struct BASEPTR0(u32);
+    impl Debug;
+    /// Bits `[31:12]` of the base address.
+    pub u32, ptr, _: 31, 12;
+    /// `0b1` if the base address is valid.
+    pub valid, _: 0;
+}
+
+impl TryFrom<u32> for BASEPTR0 {
+    type Error = RegisterParseError;
+
+    fn try_from(raw: u32) -> Result<Self, Self::Error> {
+        Ok(Self(raw))
+    }
+}
+
+impl From<BASEPTR0> for u32 {
+    fn from(raw: BASEPTR0) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for BASEPTR0 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for BASEPTR0 {
+    const ADDRESS: u8 = 0x20;
+    const NAME: &'static str = "BASEPTR0";
+}
+
+/// BASEPTR1, Base Pointer register 1 (see ADI v6.0 B2.2.3)
+///
+/// Bits `[63:32]` of the base address in [`BASEPTR0`], for debug address spaces larger than
+/// 32 bits.
+#[derive(Debug, Clone)]
+pub struct BASEPTR1(pub u32);
+
+impl TryFrom<u32> for BASEPTR1 {
+    type Error = RegisterParseError;
+
+    fn try_from(raw: u32) -> Result<Self, Self::Error> {
+        Ok(Self(raw))
+    }
+}
+
+impl From<BASEPTR1> for u32 {
+    fn from(raw: BASEPTR1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for BASEPTR1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for BASEPTR1 {
+    const ADDRESS: u8 = 0x30;
+    const NAME: &'static str = "BASEPTR1";
+}
+
+/// SELECT1, AP Select register 1 (see ADI v6.0 B2.2.12)
+///
+/// Bits `[63:32]` of the address in [`Select`], for debug address spaces larger than 32 bits.
+#[derive(Debug, Clone)]
+pub struct SELECT1(pub u32);
+
+impl TryFrom<u32> for SELECT1 {
+    type Error = RegisterParseError;
+
+    fn try_from(raw: u32) -> Result<Self, Self::Error> {
+        Ok(Self(raw))
+    }
+}
+
+impl From<SELECT1> for u32 {
+    fn from(raw: SELECT1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for SELECT1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for SELECT1 {
+    const ADDRESS: u8 = 0x54;
+    const NAME: &'static str = "SELECT1";
+}
+
 /// The ID of a debug port. Can be used to detect and select devices in a multidrop setup.
 #[derive(Debug)]
 pub struct DebugPortId {
@@ -581,6 +737,8 @@ pub enum DebugPortVersion {
     DPv1,
     /// Version 2 (**very** rare (only known example is the RP2040))
     DPv2,
+    /// Version 3, the debug port of ADIv6, which addresses APs in its debug address space
+    DPv3,
     /// Some unsupported value was encountered!
     Unsupported(u8),
 }
@@ -593,6 +751,7 @@ impl From<DebugPortVersion> for u8 {
             DPv0 => 0,
             DPv1 => 1,
             DPv2 => 2,
+            DPv3 => 3,
             Unsupported(val) => val,
         }
     }
@@ -615,6 +774,7 @@ impl Display for DebugPortVersion {
             DPv0 => write!(f, "DPv0"),
             DPv1 => write!(f, "DPv1"),
             DPv2 => write!(f, "DPv2"),
+            DPv3 => write!(f, "DPv3"),
             Unsupported(version) => write!(f, "<unsupported Debugport Version {version}>"),
         }
     }
@@ -626,6 +786,7 @@ impl From<u8> for DebugPortVersion {
             0 => DebugPortVersion::DPv0,
             1 => DebugPortVersion::DPv1,
             2 => DebugPortVersion::DPv2,
+            3 => DebugPortVersion::DPv3,
             value => DebugPortVersion::Unsupported(value),
         }
     }
@@ -0,0 +1,109 @@
+//! Discovery of the debug ports on a multidrop SWD bus
+
+use super::{DPIDR, TARGETID};
+use crate::architecture::arm::DpAddress;
+use crate::config::RegistryError;
+use probe_rs_target::CoreAccessOptions;
+
+use serde::Serialize;
+
+/// The TINSTANCE field of a TARGETSEL value.
+const INSTANCE_MASK: u32 = 0xf000_0000;
+
+/// A debug port which responded on a multidrop SWD bus, found by
+/// [`ArmProbeInterface::scan_multidrop`](crate::architecture::arm::ArmProbeInterface::scan_multidrop).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MultidropTarget {
+    /// The TARGETSEL value which selects the debug port.
+    pub targetsel: u32,
+    /// The DPIDR of the debug port.
+    pub dpidr: u32,
+    /// The TARGETID of the debug port.
+    pub targetid: u32,
+}
+
+impl MultidropTarget {
+    /// The address to access the debug port with.
+    pub fn address(&self) -> DpAddress {
+        DpAddress::Multidrop(self.targetsel)
+    }
+
+    /// The instance number of the debug port, which distinguishes debug ports with the same
+    /// TARGETID on one bus.
+    pub fn instance(&self) -> u8 {
+        (self.targetsel >> 28) as u8
+    }
+
+    /// The decoded DPIDR.
+    pub fn dpidr(&self) -> DPIDR {
+        DPIDR(self.dpidr)
+    }
+
+    /// The decoded TARGETID.
+    pub fn targetid(&self) -> TARGETID {
+        TARGETID(self.targetid)
+    }
+}
+
+/// The TARGETSEL values of all 16 instances of the parts with the given TARGETIDs.
+///
+/// The instance number of the TARGETIDs is ignored.
+pub(crate) fn targetsel_candidates(targetids: &[u32]) -> Vec<u32> {
+    let mut candidates = Vec::with_capacity(targetids.len() * 16);
+
+    for targetid in targetids {
+        for instance in 0..16 {
+            let targetsel = (instance << 28) | (targetid & !INSTANCE_MASK);
+            if !candidates.contains(&targetsel) {
+                candidates.push(targetsel);
+            }
+        }
+    }
+
+    candidates
+}
+
+/// The TARGETIDs of the multidrop debug ports of all targets in the registry, without
+/// instance numbers.
+///
+/// They can be passed to
+/// [`ArmProbeInterface::scan_multidrop`](crate::architecture::arm::ArmProbeInterface::scan_multidrop)
+/// to find the known parts on a bus.
+pub fn known_multidrop_targetids() -> Result<Vec<u32>, RegistryError> {
+    let mut targetids = Vec::new();
+
+    for family in crate::config::families()? {
+        for variant in &family.variants {
+            for core in &variant.cores {
+                let CoreAccessOptions::Arm(options) = &core.core_access_options else {
+                    continue;
+                };
+
+                let targetid = options.psel & !INSTANCE_MASK;
+                if options.psel != 0 && !targetids.contains(&targetid) {
+                    targetids.push(targetid);
+                }
+            }
+        }
+    }
+
+    Ok(targetids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{known_multidrop_targetids, targetsel_candidates};
+
+    #[test]
+    fn rp2040_candidates() {
+        let targetids = known_multidrop_targetids().unwrap();
+        // Both cores of the RP2040 share the TARGETID, and differ in the instance number.
+        assert!(targetids.contains(&0x0100_2927));
+
+        let candidates = targetsel_candidates(&[0x0100_2927, 0x1100_2927]);
+        assert_eq!(candidates.len(), 16);
+        assert_eq!(candidates[0], 0x0100_2927);
+        assert_eq!(candidates[1], 0x1100_2927);
+        assert_eq!(candidates[15], 0xf100_2927);
+    }
+}
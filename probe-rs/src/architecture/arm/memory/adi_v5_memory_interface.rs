@@ -1,12 +1,15 @@
 use super::super::ap::{
-    AccessPortError, AddressIncrement, ApAccess, ApRegister, DataSize, MemoryAp, CSW, DRW, TAR,
-    TAR2,
+    AccessPort, AccessPortError, AddressIncrement, ApAccess, ApRegister, DataSize, MemoryAp, CSW,
+    DRW, TAR, TAR2,
 };
-use crate::architecture::arm::communication_interface::{FlushableArmAccess, SwdSequence};
+use crate::architecture::arm::communication_interface::{
+    FlushableArmAccess, Register, SwdSequence,
+};
+use crate::architecture::arm::sequences::trace::{trace, SequenceOperation};
 use crate::architecture::arm::{
     communication_interface::Initialized, dp::DpAccess, MemoryApInformation,
 };
-use crate::architecture::arm::{ArmCommunicationInterface, ArmError};
+use crate::architecture::arm::{ArmCommunicationInterface, ArmError, DapAccess, DapBatch};
 use crate::{CoreStatus, DebugProbeError};
 use std::convert::TryInto;
 use std::ops::Range;
@@ -128,6 +131,76 @@ pub trait ArmProbe: SwdSequence {
         Ok(())
     }
 
+    /// Read the 32 bit word at `address`, pass it to `f`, and write the result back.
+    ///
+    /// The word is read and written with single 32 bit accesses, so this can be used for
+    /// peripheral registers. Returns the written value.
+    ///
+    /// The address has to be word aligned, otherwise [`ArmError::MemoryNotAligned`] is returned
+    /// without accessing the target.
+    fn modify_word_32(&mut self, address: u64, f: impl FnOnce(u32) -> u32) -> Result<u32, ArmError>
+    where
+        Self: Sized,
+    {
+        if address % 4 != 0 {
+            return Err(ArmError::alignment_error(address, 4));
+        }
+
+        let value = f(self.read_word_32(address)?);
+        self.write_word_32(address, value)?;
+
+        Ok(value)
+    }
+
+    /// Set the bits of the 32 bit word at `address` which are set in `mask` to the bits of
+    /// `value`, and keep the other bits, see [`ArmProbe::modify_word_32`].
+    fn update_bits_32(&mut self, address: u64, mask: u32, value: u32) -> Result<(), ArmError> {
+        if address % 4 != 0 {
+            return Err(ArmError::alignment_error(address, 4));
+        }
+
+        let word = self.read_word_32(address)?;
+        self.write_word_32(address, (word & !mask) | (value & mask))
+    }
+
+    /// Set the bits in `mask` of the 32 bit word at `address`, see
+    /// [`ArmProbe::update_bits_32`].
+    fn set_bits_32(&mut self, address: u64, mask: u32) -> Result<(), ArmError> {
+        self.update_bits_32(address, mask, mask)
+    }
+
+    /// Clear the bits in `mask` of the 32 bit word at `address`, see
+    /// [`ArmProbe::update_bits_32`].
+    fn clear_bits_32(&mut self, address: u64, mask: u32) -> Result<(), ArmError> {
+        self.update_bits_32(address, mask, 0)
+    }
+
+    /// Set the bits of the byte at `address` which are set in `mask` to the bits of `value`,
+    /// and keep the other bits.
+    ///
+    /// The byte is read and written with single 8 bit accesses. If the access port does not
+    /// support them, [`ArmError::MemoryNotAligned`] is returned instead of accessing the
+    /// surrounding word.
+    fn update_bits_8(&mut self, address: u64, mask: u8, value: u8) -> Result<(), ArmError> {
+        if !self.supports_8bit_transfers()? {
+            return Err(ArmError::alignment_error(address, 4));
+        }
+
+        let byte = self.read_word_8(address)?;
+        self.write_word_8(address, (byte & !mask) | (value & mask))
+    }
+
+    /// Perform the 32 bit accesses in `accesses` in order, and return the values which were
+    /// read.
+    ///
+    /// Memory interfaces which can queue transfers send all accesses in one [`DapBatch`],
+    /// without waiting for the result of every read. The accesses cannot depend on values
+    /// read before, so this is for sequences whose timing is known, like a core register
+    /// transfer, which waits for DHCSR to report it complete.
+    fn transfer_32(&mut self, accesses: &[WordAccess]) -> Result<Vec<u32>, ArmError> {
+        transfer_32_one_by_one(self, accesses)
+    }
+
     fn flush(&mut self) -> Result<(), ArmError>;
 
     fn supports_native_64bit_access(&mut self) -> bool;
@@ -153,6 +226,32 @@ pub trait ArmProbe: SwdSequence {
     }
 }
 
+/// A 32 bit memory access of [`ArmProbe::transfer_32`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordAccess {
+    /// Read the word at the address.
+    Read(u64),
+    /// Write the value to the word at the address.
+    Write(u64, u32),
+}
+
+/// Perform `accesses` with one memory access after the other, see [`ArmProbe::transfer_32`].
+fn transfer_32_one_by_one<P>(probe: &mut P, accesses: &[WordAccess]) -> Result<Vec<u32>, ArmError>
+where
+    P: ArmProbe + ?Sized,
+{
+    let mut values = Vec::new();
+
+    for access in accesses {
+        match *access {
+            WordAccess::Read(address) => values.push(probe.read_word_32(address)?),
+            WordAccess::Write(address, value) => probe.write_word_32(address, value)?,
+        }
+    }
+
+    Ok(values)
+}
+
 /// A struct to give access to a targets memory using a certain DAP.
 pub(crate) struct ADIMemoryInterface<'interface, AP>
 where
@@ -236,6 +335,42 @@ where
         }
     }
 
+    /// Build the batch of AP register accesses for [`ArmProbe::transfer_32`], and return it with
+    /// the CSW value it sets.
+    fn word_batch(&self, accesses: &[WordAccess]) -> Result<(DapBatch, CSW), ArmError> {
+        let ap = self.memory_ap.ap_address();
+        let csw = self.build_csw_register(DataSize::U32);
+
+        let mut batch = DapBatch::new();
+        if self.cached_csw_value != Some(csw) {
+            batch.write_ap(ap, CSW::ADDRESS, csw.into());
+        }
+
+        for access in accesses {
+            let (WordAccess::Read(address) | WordAccess::Write(address, _)) = *access;
+
+            if address % 4 != 0 {
+                return Err(ArmError::alignment_error(address, 4));
+            }
+
+            batch.write_ap(ap, TAR::ADDRESS, address as u32);
+            if self.ap_information.has_large_address_extension {
+                batch.write_ap(ap, TAR2::ADDRESS, (address >> 32) as u32);
+            } else if address >> 32 != 0 {
+                return Err(ArmError::OutOfBounds);
+            }
+
+            match *access {
+                WordAccess::Read(_) => {
+                    batch.read_ap(ap, DRW::ADDRESS);
+                }
+                WordAccess::Write(_, value) => batch.write_ap(ap, DRW::ADDRESS, value),
+            }
+        }
+
+        Ok((batch, csw))
+    }
+
     fn write_tar_register(&mut self, access_port: MemoryAp, address: u64) -> Result<(), ArmError> {
         let address_lower = address as u32;
         let address_upper = (address >> 32) as u32;
@@ -902,6 +1037,12 @@ where
             self.read_8(self.memory_ap, address, data)?;
         }
 
+        trace(|| SequenceOperation::MemoryRead {
+            address,
+            width: 8,
+            values: data.iter().map(|&value| value.into()).collect(),
+        });
+
         Ok(())
     }
 
@@ -912,6 +1053,12 @@ where
             self.read_32(self.memory_ap, address, data)?;
         }
 
+        trace(|| SequenceOperation::MemoryRead {
+            address,
+            width: 32,
+            values: data.iter().map(|&value| value.into()).collect(),
+        });
+
         Ok(())
     }
 
@@ -920,10 +1067,22 @@ where
             *d = self.read_word_64(self.memory_ap, address + (i as u64 * 8))?;
         }
 
+        trace(|| SequenceOperation::MemoryRead {
+            address,
+            width: 64,
+            values: data.to_vec(),
+        });
+
         Ok(())
     }
 
     fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::MemoryWrite {
+            address,
+            width: 8,
+            values: data.iter().map(|&value| value.into()).collect(),
+        });
+
         if data.len() == 1 {
             self.write_word_8(self.memory_ap, address, data[0])?;
         } else {
@@ -934,6 +1093,12 @@ where
     }
 
     fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::MemoryWrite {
+            address,
+            width: 32,
+            values: data.iter().map(|&value| value.into()).collect(),
+        });
+
         if data.len() == 1 {
             self.write_word_32(self.memory_ap, address, data[0])?;
         } else {
@@ -944,6 +1109,12 @@ where
     }
 
     fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::MemoryWrite {
+            address,
+            width: 64,
+            values: data.to_vec(),
+        });
+
         for (i, d) in data.iter().enumerate() {
             self.write_word_64(self.memory_ap, address + (i as u64 * 8), *d)?;
         }
@@ -955,6 +1126,26 @@ where
         Ok(!self.ap_information.supports_only_32bit_data_size)
     }
 
+    fn transfer_32(&mut self, accesses: &[WordAccess]) -> Result<Vec<u32>, ArmError> {
+        let (batch, csw) = self.word_batch(accesses)?;
+
+        let interface = match FlushableArmAccess::get_arm_communication_interface(self.interface) {
+            Ok(interface) => interface,
+            // Without a communication interface, like in tests, there is nothing to queue on.
+            Err(DebugProbeError::NotImplemented(_)) => {
+                return transfer_32_one_by_one(self, accesses)
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        // If the batch fails, it is unknown which of its writes were done.
+        self.cached_csw_value = None;
+        let values = interface.execute_batch(&batch)?;
+        self.cached_csw_value = Some(csw);
+
+        Ok(values)
+    }
+
     fn flush(&mut self) -> Result<(), ArmError> {
         self.interface.flush()?;
 
@@ -1000,7 +1191,9 @@ mod tests {
 
     use super::super::super::ap::memory_ap::mock::MockMemoryAp;
     use super::super::super::ap::memory_ap::MemoryAp;
-    use super::ADIMemoryInterface;
+    use super::super::super::ap::{DataSize, CSW, DRW, TAR};
+    use super::{ADIMemoryInterface, ArmProbe, WordAccess};
+    use crate::architecture::arm::{communication_interface::Register, DapOperation};
 
     const DUMMY_AP: MemoryAp = MemoryAp::new(ApAddress {
         dp: DpAddress::Default,
@@ -1038,6 +1231,70 @@ mod tests {
     // DATA8 interpreted as little endian 32-bit words
     const DATA32: &[u32] = &[0x83828180, 0x87868584, 0x8b8a8988, 0x8f8e8d8c];
 
+    #[test]
+    fn word_accesses_are_batched() {
+        let mut mock = MockMemoryAp::with_pattern();
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+        let ap = DUMMY_AP.ap_address();
+
+        let accesses = [WordAccess::Write(0x10, 0x1234_5678), WordAccess::Read(0x20)];
+        let (batch, csw) = mi.word_batch(&accesses).unwrap();
+        assert_eq!(csw, mi.build_csw_register(DataSize::U32));
+        assert_eq!(
+            batch.operations(),
+            [
+                DapOperation::WriteAp {
+                    ap,
+                    addr: CSW::ADDRESS,
+                    value: csw.into(),
+                },
+                DapOperation::WriteAp {
+                    ap,
+                    addr: TAR::ADDRESS,
+                    value: 0x10,
+                },
+                DapOperation::WriteAp {
+                    ap,
+                    addr: DRW::ADDRESS,
+                    value: 0x1234_5678,
+                },
+                DapOperation::WriteAp {
+                    ap,
+                    addr: TAR::ADDRESS,
+                    value: 0x20,
+                },
+                DapOperation::ReadAp {
+                    ap,
+                    addr: DRW::ADDRESS,
+                },
+            ]
+        );
+
+        // CSW is only written if it changes.
+        mi.cached_csw_value = Some(csw);
+        let (batch, _) = mi.word_batch(&accesses).unwrap();
+        assert_eq!(batch.operations().len(), 4);
+        assert_eq!(batch.reads(), 1);
+
+        assert!(mi.word_batch(&[WordAccess::Read(0x22)]).is_err());
+    }
+
+    #[test]
+    fn transfer_32() {
+        let mut mock = MockMemoryAp::with_pattern();
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        let values = mi
+            .transfer_32(&[
+                WordAccess::Write(0x10, 0x1234_5678),
+                WordAccess::Read(0x10),
+                WordAccess::Read(0x0),
+            ])
+            .unwrap();
+
+        assert_eq!(values, [0x1234_5678, 0x0403_0201]);
+    }
+
     #[test]
     fn read_word_32() {
         let mut mock = MockMemoryAp::with_pattern();
@@ -1052,6 +1309,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn update_bits() {
+        let mut mock = MockMemoryAp::with_pattern();
+        mock.memory[..8].copy_from_slice(&DATA8[..8]);
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        mi.update_bits_32(4, 0x00ff_00f0, 0x1234_5678).unwrap();
+        mi.set_bits_32(0, 0x0000_0003).unwrap();
+        mi.clear_bits_32(0, 0x8000_0000).unwrap();
+        mi.update_bits_8(2, 0x0f, 0x05).unwrap();
+        assert_eq!(
+            mi.modify_word_32(0, |word| word ^ 0x1).unwrap(),
+            0x0385_8182
+        );
+
+        // Unaligned words are not accessed.
+        assert!(mi.update_bits_32(2, 0x1, 0x1).is_err());
+
+        assert_eq!(
+            mi.mock_memory()[..8],
+            [0x82, 0x81, 0x85, 0x03, 0x74, 0x85, 0x34, 0x87]
+        );
+    }
+
     #[test]
     fn read_word_8() {
         let mut mock = MockMemoryAp::with_pattern();
@@ -2,6 +2,10 @@
 
 pub(crate) mod adi_v5_memory_interface;
 pub(crate) mod romtable;
+mod topology;
 
 use super::ap::AccessPortError;
-pub use romtable::{Component, ComponentId, CoresightComponent, PeripheralType};
+pub use romtable::{
+    Component, ComponentDump, ComponentId, CoresightComponent, PeripheralType, RawComponentId,
+};
+pub use topology::ApMemoryMap;
@@ -32,17 +32,56 @@ impl RomTableError {
     }
 }
 
+/// The ARCHPART of the DEVARCH register of a Class 0x9 ROM table.
+const CLASS9_ROM_TABLE_ARCH_PART: u16 = 0xAF7;
+
+/// The format of a ROM table, which determines the layout of its entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RomTableClass {
+    /// A Class 0x1 ROM table, described in chapter D3 of the ADIv5.2 spec.
+    Class1,
+    /// A Class 0x9 ROM table with 32-bit entries, described in chapter D4 of the ADIv5.2 spec.
+    Class9,
+}
+
+impl RomTableClass {
+    /// The offset of the last entry which can be present in the table.
+    fn last_entry_offset(self) -> u64 {
+        match self {
+            RomTableClass::Class1 => 0xEFC,
+            RomTableClass::Class9 => 0x7FC,
+        }
+    }
+
+    /// Returns `true` if `raw` marks the end of the table.
+    fn is_end(self, raw: u32) -> bool {
+        match self {
+            // End of entries is marked by an all zero entry
+            RomTableClass::Class1 => raw == 0,
+            // The PRESENT field is 0b00 for the end of the table, and 0b10 for entries which are
+            // not present, but are followed by more entries.
+            RomTableClass::Class9 => raw & 0b11 == 0,
+        }
+    }
+}
+
 /// A lazy romtable reader that is used to create an iterator over all romtable entries.
 struct RomTableReader<'probe: 'memory, 'memory> {
     base_address: u64,
+    class: RomTableClass,
     memory: &'memory mut (dyn ArmProbe + 'probe),
 }
 
 /// Iterates over a ROM table non recursively.
 impl<'probe: 'memory, 'memory> RomTableReader<'probe, 'memory> {
-    fn new(memory: &'memory mut (dyn ArmProbe + 'probe), base_address: u64) -> Self {
+    fn new(
+        memory: &'memory mut (dyn ArmProbe + 'probe),
+        base_address: u64,
+        class: RomTableClass,
+    ) -> Self {
         RomTableReader {
             base_address,
+            class,
             memory,
         }
     }
@@ -75,6 +114,11 @@ impl<'probe, 'memory, 'reader> Iterator for RomTableIterator<'probe, 'memory, 'r
     type Item = Result<RomTableEntryRaw, RomTableError>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.offset > self.rom_table_reader.class.last_entry_offset() {
+            tracing::info!("Reached the last possible rom table entry, stopping.");
+            return None;
+        }
+
         let component_address = self.rom_table_reader.base_address + self.offset;
         tracing::info!("Reading rom table entry at {:08x}", component_address);
 
@@ -90,9 +134,8 @@ impl<'probe, 'memory, 'reader> Iterator for RomTableIterator<'probe, 'memory, 'r
             return Some(Err(RomTableError::memory(e)));
         }
 
-        // End of entries is marked by an all zero entry
-        if entry_data[0] == 0 {
-            tracing::info!("Entry consists of all zeroes, stopping.");
+        if self.rom_table_reader.class.is_end(entry_data[0]) {
+            tracing::info!("Entry marks the end of the table, stopping.");
             return None;
         }
 
@@ -104,7 +147,7 @@ impl<'probe, 'memory, 'reader> Iterator for RomTableIterator<'probe, 'memory, 'r
     }
 }
 
-/// Encapsulates information about a CoreSight ROM table (class 1).
+/// Encapsulates information about a CoreSight ROM table (class 1 or class 9).
 #[derive(Clone, Debug, PartialEq)]
 pub struct RomTable {
     /// ALL the entries in the romtable in flattened fashion.
@@ -117,7 +160,11 @@ impl RomTable {
     ///
     /// This does not check whether the data actually signalizes
     /// to contain a ROM table but assumes this was checked beforehand.
-    fn try_parse(memory: &mut dyn ArmProbe, base_address: u64) -> Result<RomTable, RomTableError> {
+    fn try_parse(
+        memory: &mut dyn ArmProbe,
+        base_address: u64,
+        class: RomTableClass,
+    ) -> Result<RomTable, RomTableError> {
         // This is required for the collect down below.
         let mut entries = vec![];
 
@@ -125,7 +172,7 @@ impl RomTable {
 
         // Read all the raw romtable entries and flatten them.
 
-        let reader = RomTableReader::new(memory, base_address)
+        let reader = RomTableReader::new(memory, base_address, class)
             .entries()
             .filter_map(Result::ok)
             // This is not a needless collect! It fixes the borrowing issue with &mut Memory that clippy cannot detect!
@@ -133,6 +180,8 @@ impl RomTable {
 
         // Iterate all entries and get their data.
         for raw_entry in reader.into_iter() {
+            crate::cancellation::check().map_err(|e| RomTableError::memory(e.into()))?;
+
             let entry_base_addr = raw_entry.component_address();
 
             tracing::info!("Parsing entry at {:x?}", entry_base_addr);
@@ -233,6 +282,11 @@ impl RomTableEntry {
     pub fn component(&self) -> &Component {
         &self.component.component
     }
+
+    /// The power domain of the component, if the ROM table specifies it.
+    pub fn power_domain(&self) -> Option<u8> {
+        self.power_domain_valid.then_some(self.power_domain_id)
+    }
 }
 
 /// Component Identification information
@@ -241,8 +295,10 @@ impl RomTableEntry {
 #[derive(Clone, Debug, PartialEq)]
 pub struct ComponentId {
     component_address: u64,
-    class: RawComponent,
+    class: Option<RawComponent>,
     peripheral_id: PeripheralID,
+    raw: RawComponentId,
+    dump: Option<ComponentDump>,
 }
 
 impl ComponentId {
@@ -255,6 +311,120 @@ impl ComponentId {
     pub fn peripheral_id(&self) -> &PeripheralID {
         &self.peripheral_id
     }
+
+    /// Retreive the raw identification registers of the component.
+    pub fn raw(&self) -> &RawComponentId {
+        &self.raw
+    }
+
+    /// The first words of the component, if probe-rs does not know the component.
+    ///
+    /// This is read for components with a reserved class, and for components which are not ROM
+    /// tables and whose part is unknown.
+    pub fn dump(&self) -> Option<&ComponentDump> {
+        self.dump.as_ref()
+    }
+}
+
+/// The identification registers of a component, as read from the target.
+///
+/// These are kept for every component, so that components which probe-rs cannot identify can be
+/// reported with all information needed to add support for them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawComponentId {
+    /// PIDR0 to PIDR7.
+    pub pidr: [u32; 8],
+    /// CIDR0 to CIDR3.
+    pub cidr: [u32; 4],
+    /// DEVTYPE.
+    pub devtype: u32,
+    /// DEVARCH, which is zero on components that do not implement it.
+    pub devarch: u32,
+}
+
+impl RawComponentId {
+    /// The component class from CIDR1.
+    pub fn class(&self) -> u8 {
+        ((self.cidr[1] >> 4) & 0x0F) as u8
+    }
+
+    /// Returns `true` if the preamble bits of CIDR0 to CIDR3 have their fixed values.
+    ///
+    /// Components with invalid preambles are still parsed, but usually are not CoreSight
+    /// components, or are not powered.
+    pub fn has_valid_preamble(&self) -> bool {
+        self.cidr[0] & 0xFF == 0x0D
+            && self.cidr[1] & 0x0F == 0x0
+            && self.cidr[2] & 0xFF == 0x05
+            && self.cidr[3] & 0xFF == 0xB1
+    }
+}
+
+impl std::fmt::Display for RawComponentId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PIDR: {:02x?}, CIDR: {:02x?}, DEVTYPE: {:#04x}, DEVARCH: {:#010x}",
+            self.pidr, self.cidr, self.devtype, self.devarch
+        )
+    }
+}
+
+/// The first words of a component, to report components which probe-rs does not know.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentDump {
+    address: u64,
+    words: Vec<u32>,
+}
+
+impl ComponentDump {
+    /// The number of words which are read from the start of a component.
+    const LEN: usize = 16;
+
+    /// Read the dump of the component at `address`.
+    ///
+    /// The registers of an unknown component can have side effects on read, but the first words
+    /// are usually control and status registers. If they cannot be read, the dump is empty.
+    fn read(memory: &mut dyn ArmProbe, address: u64) -> Self {
+        let mut words = vec![0; Self::LEN];
+
+        if let Err(error) = memory.read_32(address, &mut words) {
+            tracing::warn!(
+                "Failed to read the start of the component at {:#x}: {}",
+                address,
+                error
+            );
+            words.clear();
+        }
+
+        ComponentDump { address, words }
+    }
+
+    /// The address of the first word.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// The words at the start of the component.
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+}
+
+impl std::fmt::Display for ComponentDump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, line) in self.words.chunks(4).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:#010x}:", self.address + i as u64 * 16)?;
+            for word in line {
+                write!(f, " {word:08x}")?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// A reader to extract infromation from a CoreSight component table.
@@ -274,11 +444,10 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
         }
     }
 
-    /// Reads the component class from a component information table.
+    /// Reads the identification registers from a component information table.
     ///
     /// This function does a direct memory access and is meant for internal use only.
-    fn component_class(&mut self) -> Result<RawComponent, RomTableError> {
-        #![allow(clippy::verbose_bit_mask)]
+    fn raw_id(&mut self) -> Result<RawComponentId, RomTableError> {
         let mut cidr = [0u32; 4];
 
         self.memory
@@ -287,36 +456,7 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
 
         tracing::debug!("CIDR: {:x?}", cidr);
 
-        let preambles = [
-            cidr[0] & 0xff,
-            cidr[1] & 0x0f,
-            cidr[2] & 0xff,
-            cidr[3] & 0xff,
-        ];
-
-        let expected = [0x0D, 0x0, 0x05, 0xB1];
-
-        for i in 0..4 {
-            if preambles[i] != expected[i] {
-                tracing::warn!(
-                    "Component at 0x{:x}: CIDR{} has invalid preamble (expected 0x{:x}, got 0x{:x})",
-                    self.base_address, i, expected[i], preambles[i],
-                );
-                // Technically invalid preambles are a no-go.
-                // We are not sure if we need to abort earlier or if just emitting a warning is okay.
-                // For now this works, so we emit a warning and continue on.
-            }
-        }
-
-        FromPrimitive::from_u32((cidr[1] >> 4) & 0x0F)
-            .ok_or(RomTableError::CSComponentIdentification)
-    }
-
-    /// Reads the peripheral ID from a component information table.
-    ///
-    /// This function does a direct memory access and is meant for internal use only.
-    fn peripheral_id(&mut self) -> Result<PeripheralID, RomTableError> {
-        let mut data = [0u32; 8];
+        let mut pidr = [0u32; 8];
 
         let peripheral_id_address = self.base_address + 0xFD0;
 
@@ -326,52 +466,81 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
         );
 
         self.memory
-            .read_32(self.base_address + 0xFD0, &mut data[4..])
+            .read_32(self.base_address + 0xFD0, &mut pidr[4..])
             .map_err(RomTableError::memory)?;
         self.memory
-            .read_32(self.base_address + 0xFE0, &mut data[..4])
+            .read_32(self.base_address + 0xFE0, &mut pidr[..4])
             .map_err(RomTableError::memory)?;
 
-        tracing::debug!("Raw peripheral id: {:x?}", data);
+        tracing::debug!("Raw peripheral id: {:x?}", pidr);
 
         const DEV_TYPE_OFFSET: u64 = 0xFCC;
-        const DEV_TYPE_MASK: u32 = 0xFF;
+        const ARCH_ID_OFFSET: u64 = 0xFBC;
 
-        let dev_type = self
+        let devtype = self
             .memory
             .read_word_32(self.base_address + DEV_TYPE_OFFSET)
-            .map_err(RomTableError::memory)
-            .map(|v| (v & DEV_TYPE_MASK) as u8)?;
-
-        const ARCH_ID_OFFSET: u64 = 0xFBC;
-        const ARCH_ID_MASK: u32 = 0xFFFF;
-        const ARCH_ID_PRESENT_BIT: u32 = 1 << 20;
-
-        let arch_id = self
+            .map_err(RomTableError::memory)?;
+        let devarch = self
             .memory
             .read_word_32(self.base_address + ARCH_ID_OFFSET)
-            .map_err(RomTableError::memory)
-            .map(|v| {
-                if v & ARCH_ID_PRESENT_BIT > 0 {
-                    (v & ARCH_ID_MASK) as u16
-                } else {
-                    0
-                }
-            })?;
-
-        tracing::debug!("Dev type: {:x}, arch id: {:x}", dev_type, arch_id);
+            .map_err(RomTableError::memory)?;
 
-        Ok(PeripheralID::from_raw(&data, dev_type, arch_id))
+        Ok(RawComponentId {
+            pidr,
+            cidr,
+            devtype,
+            devarch,
+        })
     }
 
     /// Reads all component properties from a component info table
     ///
     /// This function does a direct memory access and is meant for internal use only.
     fn read_all(&mut self) -> Result<ComponentId, RomTableError> {
+        let raw = self.raw_id()?;
+
+        if !raw.has_valid_preamble() {
+            // Technically invalid preambles are a no-go.
+            // We are not sure if we need to abort earlier or if just emitting a warning is okay.
+            // For now this works, so we emit a warning and continue on.
+            tracing::warn!(
+                "Component at 0x{:x}: CIDR has an invalid preamble ({:x?})",
+                self.base_address,
+                raw.cidr,
+            );
+        }
+
+        const DEV_TYPE_MASK: u32 = 0xFF;
+        const ARCH_ID_MASK: u32 = 0xFFFF;
+        const ARCH_ID_PRESENT_BIT: u32 = 1 << 20;
+
+        let dev_type = (raw.devtype & DEV_TYPE_MASK) as u8;
+        let arch_id = if raw.devarch & ARCH_ID_PRESENT_BIT > 0 {
+            (raw.devarch & ARCH_ID_MASK) as u16
+        } else {
+            0
+        };
+
+        tracing::debug!("Dev type: {:x}, arch id: {:x}", dev_type, arch_id);
+
+        let class = FromPrimitive::from_u8(raw.class());
+        let peripheral_id = PeripheralID::from_raw(&raw.pidr, dev_type, arch_id);
+
+        let is_rom_table = match class {
+            Some(RawComponent::RomTable) => true,
+            Some(RawComponent::CoreSightComponent) => arch_id & 0xFFF == CLASS9_ROM_TABLE_ARCH_PART,
+            _ => false,
+        };
+        let dump = (class.is_none() || (!is_rom_table && peripheral_id.determine_part().is_none()))
+            .then(|| ComponentDump::read(self.memory, self.base_address));
+
         Ok(ComponentId {
             component_address: self.base_address,
-            class: self.component_class()?,
-            peripheral_id: self.peripheral_id()?,
+            class,
+            peripheral_id,
+            raw,
+            dump,
         })
     }
 }
@@ -406,12 +575,17 @@ pub enum Component {
 
     /// A CoreSight component can be a Class 0x9 ROM Table, which can be identified from the DEVARCH.ARCHID having the value 0x0AF7. See also _ROM Table Types on page D2-237_. For detailed information about Class 0x9 ROM Tables, see _Chapter D4 Class 0x9 ROM Tables_.
     CoresightComponent(ComponentId),
+    /// Class 0x9 ROM Table, a CoreSight component with the DEVARCH.ARCHID 0x0AF7.
+    Class9RomTable(ComponentId, RomTable),
     /// Peripheral Test Block.
     PeripheralTestBlock(ComponentId),
     /// Generic IP component.
     GenericIPComponent(ComponentId),
     /// CoreLink, PrimeCell, or system component with no standardized register layout, for backwards compatibility.
     CoreLinkOrPrimeCellOrSystemComponent(ComponentId),
+    /// A component with a reserved class in CIDR1. Its raw identification registers and the
+    /// start of the component are available in [`ComponentId::raw`] and [`ComponentId::dump`].
+    Unknown(ComponentId),
 }
 
 impl Component {
@@ -437,15 +611,55 @@ impl Component {
             tracing::info!("\tComponent is known: {}", info);
         }
 
-        let class = match component_id.class {
+        let Some(class) = component_id.class.clone() else {
+            tracing::warn!(
+                "Component at {:#x} has the reserved class {:#x}: {}",
+                baseaddr,
+                component_id.raw.class(),
+                component_id.raw
+            );
+            return Ok(Component::Unknown(component_id));
+        };
+
+        let class = match class {
             RawComponent::GenericVerificationComponent => {
                 Component::GenericVerificationComponent(component_id)
             }
             RawComponent::RomTable => {
-                let rom_table = RomTable::try_parse(memory, component_id.component_address)?;
+                let rom_table = RomTable::try_parse(
+                    memory,
+                    component_id.component_address,
+                    RomTableClass::Class1,
+                )?;
 
                 Component::Class1RomTable(component_id, rom_table)
             }
+            RawComponent::CoreSightComponent
+                if component_id.peripheral_id.arch_id & 0xFFF == CLASS9_ROM_TABLE_ARCH_PART =>
+            {
+                const DEVID_OFFSET: u64 = 0xFC8;
+                const DEVID_FORMAT_MASK: u32 = 0xF;
+
+                let devid = memory
+                    .read_word_32(component_id.component_address + DEVID_OFFSET)
+                    .map_err(RomTableError::memory)?;
+
+                let rom_table = if devid & DEVID_FORMAT_MASK == 0 {
+                    RomTable::try_parse(
+                        memory,
+                        component_id.component_address,
+                        RomTableClass::Class9,
+                    )?
+                } else {
+                    tracing::warn!(
+                        "Class 0x9 ROM table at {:#x} has 64-bit entries, which are not supported",
+                        component_id.component_address
+                    );
+                    RomTable { entries: vec![] }
+                };
+
+                Component::Class9RomTable(component_id, rom_table)
+            }
             RawComponent::CoreSightComponent => Component::CoresightComponent(component_id),
             RawComponent::PeripheralTestBlock => Component::PeripheralTestBlock(component_id),
             RawComponent::GenericIPComponent => Component::GenericIPComponent(component_id),
@@ -463,9 +677,11 @@ impl Component {
             Component::GenericVerificationComponent(component_id) => component_id,
             Component::Class1RomTable(component_id, ..) => component_id,
             Component::CoresightComponent(component_id, ..) => component_id,
+            Component::Class9RomTable(component_id, ..) => component_id,
             Component::PeripheralTestBlock(component_id) => component_id,
             Component::GenericIPComponent(component_id) => component_id,
             Component::CoreLinkOrPrimeCellOrSystemComponent(component_id) => component_id,
+            Component::Unknown(component_id) => component_id,
         }
     }
 }
@@ -570,9 +786,11 @@ impl<'a> Iterator for CoresightComponentIter<'a> {
         if let Some(component) = self.components.get(self.current) {
             // If it has children, remember to iterate them next.
             self.children = match &component.component {
-                Component::Class1RomTable(_, v) => Some(Box::new(CoresightComponentIter::new(
-                    v.entries.iter().map(|v| &v.component).collect(),
-                ))),
+                Component::Class1RomTable(_, v) | Component::Class9RomTable(_, v) => {
+                    Some(Box::new(CoresightComponentIter::new(
+                        v.entries.iter().map(|v| &v.component).collect(),
+                    )))
+                }
                 _ => None,
             };
             // Advance the pointer by one.
@@ -661,6 +879,30 @@ impl PeripheralID {
         self.PART
     }
 
+    /// Returns the REVISION of the peripheral ID register, the major revision by the designer.
+    pub fn revision(&self) -> u8 {
+        self.REVISION
+    }
+
+    /// Returns the REVAND of the peripheral ID register, the minor errata fixes by the designer.
+    pub fn revand(&self) -> u8 {
+        self.REVAND
+    }
+
+    /// Returns the CMOD of the peripheral ID register, the modification by the implementer,
+    /// or `None` if the component was not modified.
+    pub fn modification(&self) -> Option<u8> {
+        match self.CMOD {
+            ComponentModification::No => None,
+            ComponentModification::Yes(modification) => Some(modification),
+        }
+    }
+
+    /// Returns the number of 4 KiB blocks the component occupies.
+    pub fn size(&self) -> u8 {
+        self.SIZE
+    }
+
     pub fn arch_id(&self) -> u16 {
         self.arch_id
     }
@@ -700,6 +942,7 @@ impl PeripheralID {
             ("ARM Ltd", 0x4C4, 0x00, 0x0000) => Some(PartInfo::new("Cortex-M4 ROM", PeripheralType::Rom)),
             ("ARM Ltd", 0x907, 0x21, 0x0000) => Some(PartInfo::new("CoreSight ETB", PeripheralType::Etb)),
             ("ARM Ltd", 0x908, 0x12, 0x0000) => Some(PartInfo::new("CoreSight TraceFunnel", PeripheralType::TraceFunnel)),
+            ("ARM Ltd", 0x909, 0x22, 0x0000) => Some(PartInfo::new("CoreSight Replicator", PeripheralType::TraceReplicator)),
             ("ARM Ltd", 0x910, 0x00, 0x0000) => Some(PartInfo::new("CoreSight ETM9", PeripheralType::Etm)),
             ("ARM Ltd", 0x912, 0x11, 0x0000) => Some(PartInfo::new("CoreSight TPIU", PeripheralType::Tpiu)),
             ("ARM Ltd", 0x913, 0x00, 0x0000) => Some(PartInfo::new("CoreSight ITM", PeripheralType::Itm)),
@@ -708,6 +951,7 @@ impl PeripheralID {
             ("ARM Ltd", 0x923, 0x11, 0x0000) => Some(PartInfo::new("Cortex-M3 TPIU", PeripheralType::Tpiu)),
             ("ARM Ltd", 0x924, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M3 ETM", PeripheralType::Etm)),
             ("ARM Ltd", 0x925, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M4 ETM", PeripheralType::Etm)),
+            ("ARM Ltd", 0x932, 0x31, 0x0a31) => Some(PartInfo::new("CoreSight MTB-M0+", PeripheralType::Mtb)),
             ("ARM Ltd", 0x961, _, 0x0000) => Some(PartInfo::new("CoreSight TMC", PeripheralType::Tmc)),
             ("ARM Ltd", 0x962, 0x00, 0x0000) => Some(PartInfo::new("CoreSight STM", PeripheralType::Stm)),
             ("ARM Ltd", 0x963, 0x63, 0x0a63) => Some(PartInfo::new("CoreSight STM", PeripheralType::Stm)),
@@ -727,6 +971,10 @@ impl PeripheralID {
             ("ARM Ltd", 0xD21, 0x11, 0x0000) => Some(PartInfo::new("Cortex-M33 TPIU", PeripheralType::Tpiu)),
             ("ARM Ltd", 0xD21, 0x14, 0x1A14) => Some(PartInfo::new("Cortex-M33 CTI", PeripheralType::Cti)),
             ("ARM Ltd", 0x9A3, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M0 MTB", PeripheralType::Mtb)),
+            ("ARM Ltd", 0x9EB, 0x12, 0x0000) => Some(PartInfo::new("CoreSight SoC-600 TraceFunnel", PeripheralType::TraceFunnel)),
+            ("ARM Ltd", 0x9EC, 0x22, 0x0000) => Some(PartInfo::new("CoreSight SoC-600 Replicator", PeripheralType::TraceReplicator)),
+            ("ARM Ltd", 0x906, 0x14, 0x0000) => Some(PartInfo::new("CoreSight CTI", PeripheralType::Cti)),
+            (_, _, 0x00, 0x0AF7) => Some(PartInfo::new("ROM Table (Class 0x9)", PeripheralType::Rom)),
             _ => None,
         }
     }
@@ -793,6 +1041,8 @@ pub enum PeripheralType {
     Swo,
     /// CoreSight Trace funnel
     TraceFunnel,
+    /// CoreSight Trace replicator
+    TraceReplicator,
     /// System Trace Macrocell
     Stm,
     /// Timestamp Generator
@@ -820,6 +1070,7 @@ impl std::fmt::Display for PeripheralType {
             PeripheralType::Swo => write!(f, "Swo (Single Wire Output)"),
             PeripheralType::Stm => write!(f, "Stm (System Trace Macrocell)"),
             PeripheralType::TraceFunnel => write!(f, "Trace Funnel"),
+            PeripheralType::TraceReplicator => write!(f, "Trace Replicator"),
             PeripheralType::Tsgen => write!(f, "Tsgen (Time Stamp Generator)"),
             PeripheralType::Tmc => write!(f, "Tmc (Trace Memory Controller)"),
             PeripheralType::Mtb => write!(f, "MTB (Micro Trace Buffer)"),
@@ -827,3 +1078,208 @@ impl std::fmt::Display for PeripheralType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Component, PeripheralType};
+    use crate::architecture::arm::ap::MemoryAp;
+    use crate::architecture::arm::communication_interface::{Initialized, SwdSequence};
+    use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
+    use crate::architecture::arm::{ApAddress, ArmCommunicationInterface, ArmError, DpAddress};
+    use crate::DebugProbeError;
+
+    /// Memory with CoreSight component registers, reading zero elsewhere.
+    #[derive(Default)]
+    struct ComponentMemory {
+        words: HashMap<u64, u32>,
+    }
+
+    impl ComponentMemory {
+        /// Add the identification registers of a component at `base`.
+        fn add_component(&mut self, base: u64, class: u32, part: u16, dev_type: u8, arch_id: u16) {
+            // ARM Ltd, JEP106 continuation code 4, identity code 0x3B.
+            let pidr = [
+                u32::from(part & 0xff),
+                0xb0 | u32::from(part >> 8),
+                0x0b,
+                0x00,
+                0x04,
+            ];
+            for (i, value) in pidr.iter().enumerate().take(4) {
+                self.words.insert(base + 0xFE0 + 4 * i as u64, *value);
+            }
+            self.words.insert(base + 0xFD0, pidr[4]);
+
+            let cidr = [0x0d, class << 4, 0x05, 0xb1];
+            for (i, value) in cidr.iter().enumerate() {
+                self.words.insert(base + 0xFF0 + 4 * i as u64, *value);
+            }
+
+            self.words.insert(base + 0xFCC, u32::from(dev_type));
+            if arch_id != 0 {
+                self.words
+                    .insert(base + 0xFBC, (0x23b << 21) | (1 << 20) | u32::from(arch_id));
+            }
+        }
+    }
+
+    impl SwdSequence for ComponentMemory {
+        fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
+            unimplemented!()
+        }
+
+        fn swj_pins(
+            &mut self,
+            _pin_out: u32,
+            _pin_select: u32,
+            _pin_wait: u32,
+        ) -> Result<u32, DebugProbeError> {
+            unimplemented!()
+        }
+    }
+
+    impl ArmProbe for ComponentMemory {
+        fn read_8(&mut self, _address: u64, _data: &mut [u8]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+            for (i, word) in data.iter_mut().enumerate() {
+                *word = self
+                    .words
+                    .get(&(address + 4 * i as u64))
+                    .copied()
+                    .unwrap_or(0);
+            }
+            Ok(())
+        }
+
+        fn read_64(&mut self, _address: u64, _data: &mut [u64]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn write_8(&mut self, _address: u64, _data: &[u8]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn write_32(&mut self, _address: u64, _data: &[u32]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn write_64(&mut self, _address: u64, _data: &[u64]) -> Result<(), ArmError> {
+            unimplemented!()
+        }
+
+        fn flush(&mut self) -> Result<(), ArmError> {
+            Ok(())
+        }
+
+        fn supports_native_64bit_access(&mut self) -> bool {
+            false
+        }
+
+        fn supports_8bit_transfers(&self) -> Result<bool, ArmError> {
+            Ok(false)
+        }
+
+        fn ap(&mut self) -> MemoryAp {
+            MemoryAp::new(ApAddress {
+                dp: DpAddress::Default,
+                ap: 0,
+            })
+        }
+
+        fn get_arm_communication_interface(
+            &mut self,
+        ) -> Result<&mut ArmCommunicationInterface<Initialized>, DebugProbeError> {
+            Err(DebugProbeError::NotImplemented(
+                "get_arm_communication_interface",
+            ))
+        }
+    }
+
+    #[test]
+    fn class9_rom_table() {
+        let mut memory = ComponentMemory::default();
+
+        // A Class 0x9 ROM table with an ITM, a not present entry, and a nested Class 0x1
+        // ROM table with a CTI.
+        memory.add_component(0xE00F_E000, 9, 0x4C9, 0x00, 0x0AF7);
+        memory.words.insert(0xE00F_E000, 0xFFF0_3003);
+        memory.words.insert(0xE00F_E004, 0x0000_1002);
+        memory.words.insert(0xE00F_E008, 0x0000_2003);
+        memory.add_component(0xE000_1000, 9, 0xD21, 0x43, 0x1A01);
+        memory.add_component(0xE010_0000, 1, 0x4C4, 0x00, 0);
+        memory.words.insert(0xE010_0000, 0x0004_2003);
+        memory.add_component(0xE014_2000, 9, 0x906, 0x14, 0);
+
+        let component = Component::try_parse(&mut memory, 0xE00F_E000).unwrap();
+
+        let Component::Class9RomTable(_, table) = &component else {
+            panic!("Expected a Class 0x9 ROM table, got {component:?}");
+        };
+        let entries = table.entries().map(|e| e.component()).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+
+        let itm = entries[0].id();
+        assert_eq!(itm.component_address(), 0xE000_1000);
+        assert!(itm.peripheral_id().is_of_type(PeripheralType::Itm));
+
+        let Component::Class1RomTable(_, nested) = entries[1] else {
+            panic!("Expected a Class 0x1 ROM table, got {:?}", entries[1]);
+        };
+        let cti = nested.entries().next().unwrap().component().id();
+        assert_eq!(cti.component_address(), 0xE014_2000);
+        assert!(cti.peripheral_id().is_of_type(PeripheralType::Cti));
+        assert_eq!(
+            cti.peripheral_id().jep106().and_then(|j| j.get()),
+            Some("ARM Ltd")
+        );
+        assert!(cti.dump().is_none());
+    }
+
+    #[test]
+    fn unknown_components_are_kept() {
+        let mut memory = ComponentMemory::default();
+
+        // A Class 0x1 ROM table with a component of the reserved class 0x2, and a CoreSight
+        // component with an unknown part.
+        memory.add_component(0xE00F_F000, 1, 0x4C4, 0x00, 0);
+        memory.words.insert(0xE00F_F000, 0xFFFF_2003);
+        memory.words.insert(0xE00F_F004, 0xFFFF_3003);
+        memory.add_component(0xE00F_1000, 2, 0x123, 0x00, 0);
+        memory.words.insert(0xE00F_1000, 0xDEAD_BEEF);
+        memory.add_component(0xE00F_2000, 9, 0xABC, 0x11, 0);
+
+        let component = Component::try_parse(&mut memory, 0xE00F_F000).unwrap();
+        assert!(component.id().dump().is_none());
+
+        let Component::Class1RomTable(_, table) = &component else {
+            panic!("Expected a Class 0x1 ROM table, got {component:?}");
+        };
+        let entries = table.entries().map(|e| e.component()).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+
+        let Component::Unknown(unknown) = entries[0] else {
+            panic!("Expected an unknown component, got {:?}", entries[0]);
+        };
+        assert_eq!(unknown.raw().class(), 2);
+        assert_eq!(unknown.raw().pidr[0], 0x23);
+        assert!(unknown.raw().has_valid_preamble());
+        let dump = unknown.dump().unwrap();
+        assert_eq!(dump.words().len(), 16);
+        assert_eq!(dump.words()[0], 0xDEAD_BEEF);
+        assert_eq!(
+            dump.to_string().lines().next(),
+            Some("0xe00f1000: deadbeef 00000000 00000000 00000000")
+        );
+
+        let Component::CoresightComponent(unknown_part) = entries[1] else {
+            panic!("Expected a CoreSight component, got {:?}", entries[1]);
+        };
+        assert_eq!(unknown_part.raw().devtype, 0x11);
+        assert!(unknown_part.dump().is_some());
+    }
+}
@@ -0,0 +1,191 @@
+//! Maps of the memory each memory AP can access.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::architecture::arm::ap::{clear_sticky_errors, AccessPort, MemoryAp};
+use crate::architecture::arm::{ArmError, ArmProbeInterface};
+
+/// Ranges which are not split further while probing, see [`ApMemoryMap::probe`].
+const MIN_PROBE_RANGE: u64 = 0x1000;
+
+/// The address ranges which respond to accesses through a memory AP.
+///
+/// On multicore chips the APs often see different parts of the memory, for example the
+/// tightly coupled memory of a core is only accessible through its own AP, and some buses
+/// are not connected to every AP at all.
+#[derive(Debug, Clone)]
+pub struct ApMemoryMap {
+    /// The access port.
+    pub access_port: MemoryAp,
+    /// The accessible address ranges, sorted and without overlaps.
+    pub accessible: Vec<Range<u64>>,
+}
+
+impl ApMemoryMap {
+    /// Find the parts of `regions` which are accessible through `access_port`.
+    ///
+    /// Each region is probed with single word reads at its start, middle and end. Regions
+    /// that do not respond uniformly are split in halves and probed again, down to a size of
+    /// 4 KiB. Faults of the test reads are cleared, so they do not affect later accesses.
+    ///
+    /// Only reads are used, but reading some peripheral registers has side effects, so the
+    /// regions should be memories, as in the memory map of a target.
+    pub fn probe(
+        interface: &mut dyn ArmProbeInterface,
+        access_port: MemoryAp,
+        regions: &[Range<u64>],
+    ) -> Result<Self, ArmError> {
+        // Fail early if the AP cannot be used at all, instead of reporting it as inaccessible.
+        interface.memory_interface(access_port)?;
+
+        let dp = access_port.ap_address().dp;
+        let accessible = accessible_ranges(regions, |address| {
+            let result = interface
+                .memory_interface(access_port)
+                .and_then(|mut memory| memory.read_word_32(address));
+
+            match result {
+                Ok(_) => true,
+                Err(error) => {
+                    tracing::trace!(
+                        "Probe read of {:#010x} through {:?} failed: {}",
+                        address,
+                        access_port,
+                        error
+                    );
+                    clear_sticky_errors(interface, dp);
+                    false
+                }
+            }
+        });
+
+        Ok(Self {
+            access_port,
+            accessible,
+        })
+    }
+
+    /// Returns `true` if `address` is accessible through the AP.
+    pub fn contains(&self, address: u64) -> bool {
+        self.accessible.iter().any(|range| range.contains(&address))
+    }
+
+    /// Returns `true` if the whole `range` is accessible through the AP.
+    pub fn contains_range(&self, range: &Range<u64>) -> bool {
+        range.is_empty()
+            || self
+                .accessible
+                .iter()
+                .any(|accessible| accessible.start <= range.start && range.end <= accessible.end)
+    }
+}
+
+/// The parts of `regions` for which `probe` reports that the word at an address is readable.
+fn accessible_ranges(regions: &[Range<u64>], probe: impl FnMut(u64) -> bool) -> Vec<Range<u64>> {
+    let mut prober = Prober {
+        probe,
+        results: HashMap::new(),
+    };
+
+    let mut accessible = Vec::new();
+    for region in regions {
+        // Only whole words are probed.
+        let start = (region.start + 3) & !3;
+        let end = region.end & !3;
+        if start < end {
+            prober.probe_range(start..end, &mut accessible);
+        }
+    }
+
+    accessible.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(accessible.len());
+    for range in accessible {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+struct Prober<F> {
+    probe: F,
+    /// Addresses are probed once, also where the split ranges meet.
+    results: HashMap<u64, bool>,
+}
+
+impl<F: FnMut(u64) -> bool> Prober<F> {
+    fn read(&mut self, address: u64) -> bool {
+        let probe = &mut self.probe;
+        *self
+            .results
+            .entry(address)
+            .or_insert_with(|| probe(address))
+    }
+
+    /// Probe a word aligned range, and add its accessible parts to `accessible`.
+    fn probe_range(&mut self, range: Range<u64>, accessible: &mut Vec<Range<u64>>) {
+        let first = self.read(range.start);
+        let last = self.read(range.end - 4);
+
+        if range.end - range.start <= MIN_PROBE_RANGE {
+            // Small ranges with partial access are left out, they cannot be used reliably.
+            if first && last {
+                accessible.push(range);
+            }
+            return;
+        }
+
+        let middle = (range.start + (range.end - range.start) / 2) & !3;
+        let uniform = first == last && self.read(middle) == first;
+
+        if uniform {
+            if first {
+                accessible.push(range);
+            }
+            return;
+        }
+
+        self.probe_range(range.start..middle, accessible);
+        self.probe_range(middle..range.end, accessible);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::accessible_ranges;
+
+    #[test]
+    fn uniform_regions() {
+        let mut reads = 0;
+        let accessible =
+            accessible_ranges(&[0x2000_0000..0x2002_0000, 0x3000_0000..0x3000_1000], |a| {
+                reads += 1;
+                a < 0x3000_0000
+            });
+
+        assert_eq!(accessible, [0x2000_0000..0x2002_0000]);
+        // Start, middle and end of the first, start and end of the second region.
+        assert_eq!(reads, 5);
+    }
+
+    #[test]
+    fn partially_accessible_region() {
+        // Only the first 40 KiB of the region respond.
+        let accessible = accessible_ranges(&[0x2000_0000..0x2001_0000], |a| a < 0x2000_a000);
+
+        assert_eq!(accessible, [0x2000_0000..0x2000_a000]);
+    }
+
+    #[test]
+    fn adjacent_regions_are_merged() {
+        let accessible =
+            accessible_ranges(&[0x1000..0x2002, 0x2000..0x3000, 0x8001..0x8004], |_| true);
+
+        // The unaligned bytes at the ends of the regions are not probed.
+        assert_eq!(accessible, [0x1000..0x3000]);
+    }
+}
@@ -1,30 +1,35 @@
 //! All the interface bits for ARM.
 
 pub mod ap;
+pub mod clock;
 pub(crate) mod communication_interface;
 pub mod component;
 pub(crate) mod core;
 pub mod dp;
 pub mod memory;
 pub mod sequences;
+mod statistics;
 pub mod swo;
+mod telemetry;
 mod traits;
 
 pub use self::core::{armv6m, armv7a, armv7m, armv8a, armv8m, Dump};
 use self::{
     ap::{AccessPort, AccessPortError},
-    communication_interface::RegisterParseError,
     dp::DebugPortError,
     memory::romtable::RomTableError,
     sequences::ArmDebugSequenceError,
     {armv7a::Armv7aError, armv8a::Armv8aError},
 };
+use crate::cancellation::Cancelled;
 use crate::DebugProbeError;
 pub use communication_interface::{
     ApInformation, ArmChipInfo, ArmCommunicationInterface, ArmProbeInterface, DapError,
-    MemoryApInformation, Register,
+    MemoryApInformation, Register, RegisterParseError,
 };
-pub use swo::{SwoAccess, SwoConfig, SwoMode, SwoReader};
+pub use statistics::DapStatistics;
+pub use swo::{SwoAccess, SwoChunk, SwoConfig, SwoMode, SwoReader, SwoStatistics, SwoTimestamp};
+pub use telemetry::{ConnectionEvent, ConnectionEventKind, ConnectionTelemetry};
 pub use traits::*;
 
 /// ARM-specific errors
@@ -37,6 +42,9 @@ pub enum ArmError {
     /// A timeout occurred during an operation
     #[error("Timeout occurred during operation.")]
     Timeout,
+    /// The operation was aborted with a [`CancellationToken`](crate::cancellation::CancellationToken).
+    #[error("The operation was aborted")]
+    Cancelled(#[from] Cancelled),
     /// The address is too large for the 32 bit address space.
     #[error("Address is not in 32 bit address space.")]
     AddressOutOf32BitAddressSpace,
@@ -103,6 +111,11 @@ pub enum ArmError {
     #[error("Unable to create a breakpoint at address {0:#010X}. Hardware breakpoints are only supported at addresses < 0x2000'0000.")]
     UnsupportedBreakpointAddress(u32),
 
+    /// The core does not implement a debug feature needed for the operation, for example on
+    /// ARMv6-M parts where most debug features are optional.
+    #[error("The core does not support {0}.")]
+    UnsupportedDebugFeature(String),
+
     /// ARMv8a specifc erorr occurred.
     Armv8a(#[from] Armv8aError),
 
@@ -115,6 +128,10 @@ pub enum ArmError {
     /// Tracing has not been configured.
     TracingUnconfigured,
 
+    /// The PMU of a core was used, but its base address is not in the target description.
+    #[error("The pmu_base of the core is not specified in the target description.")]
+    PmuBaseNotSpecified,
+
     /// Error parsing a register.
     RegisterParse(#[from] RegisterParseError),
 
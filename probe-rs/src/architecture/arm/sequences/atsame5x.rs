@@ -5,8 +5,10 @@ use crate::{
     architecture::{
         self,
         arm::{
-            ap::MemoryAp, memory::adi_v5_memory_interface::ArmProbe, ApAddress, ArmError,
-            ArmProbeInterface, DpAddress,
+            ap::MemoryAp,
+            component::TraceSink,
+            memory::{adi_v5_memory_interface::ArmProbe, CoresightComponent},
+            ApAddress, ArmError, ArmProbeInterface, DpAddress,
         },
     },
     session::MissingPermissions,
@@ -187,6 +189,20 @@ impl<'a> architecture::arm::communication_interface::SwdSequence for SwdSequence
     }
 }
 
+/// The address of the GCLK peripheral channel control register of GCLK_CM4_TRACE.
+const GCLK_PCHCTRL_CM4_TRACE: u64 = 0x4000_1C80 + 4 * 47;
+/// Enables the peripheral channel in a PCHCTRL register. The generator bits are left at 0, which
+/// selects GCLK0, the clock generator of the CPU.
+const GCLK_PCHCTRL_CHEN: u32 = 1 << 6;
+
+/// The base address of port group B of the PORT peripheral.
+const PORT_GROUP_B: u64 = 0x4100_8080;
+/// The SWO pin, PB30, uses peripheral function H.
+const SWO_PIN: u64 = 30;
+const SWO_PMUX_FUNCTION_H: u8 = 0x7;
+/// The PMUXEN bit of a PINCFG register, which hands the pin to the selected peripheral function.
+const PORT_PINCFG_PMUXEN: u8 = 1 << 0;
+
 /// Marker struct indicating initialization sequencing for ATSAM D5x/E5x family parts.
 pub struct AtSAME5x {}
 
@@ -196,6 +212,35 @@ impl AtSAME5x {
         Arc::new(Self {})
     }
 
+    /// Route the SWO pin and enable the trace clock for the given trace sink.
+    ///
+    /// The trace clock GCLK_CM4_TRACE is fed from GCLK0, so the TPIU clock is the CPU clock.
+    /// SWO is output on PB30, which is switched to peripheral function H. Parallel trace pins
+    /// are not configured.
+    pub fn configure_trace_pins(
+        memory: &mut dyn ArmProbe,
+        sink: &TraceSink,
+    ) -> Result<(), ArmError> {
+        if matches!(sink, TraceSink::TraceMemory) {
+            return Ok(());
+        }
+
+        memory.write_word_32(GCLK_PCHCTRL_CM4_TRACE, GCLK_PCHCTRL_CHEN)?;
+
+        if matches!(sink, TraceSink::Swo(_)) {
+            // PB30 is an even pin, so its function is in the lower nibble of the PMUX register.
+            let pmux_address = PORT_GROUP_B + 0x30 + SWO_PIN / 2;
+            let pmux = memory.read_word_8(pmux_address)?;
+            memory.write_word_8(pmux_address, (pmux & 0xF0) | SWO_PMUX_FUNCTION_H)?;
+
+            let pincfg_address = PORT_GROUP_B + 0x40 + SWO_PIN;
+            let pincfg = memory.read_word_8(pincfg_address)?;
+            memory.write_word_8(pincfg_address, pincfg | PORT_PINCFG_PMUXEN)?;
+        }
+
+        Ok(())
+    }
+
     /// Perform a Chip-Erase operation
     ///
     /// Issue a Chip-Erase command to the device provided that `permission` grants `erase-all`.
@@ -408,6 +453,16 @@ impl ArmDebugSequence for AtSAME5x {
     fn debug_erase_sequence(&self) -> Option<Arc<dyn DebugEraseSequence>> {
         Some(Self::create())
     }
+
+    fn trace_start(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        components: &[CoresightComponent],
+        sink: &TraceSink,
+    ) -> Result<(), ArmError> {
+        let mut memory = interface.memory_interface(components[0].ap)?;
+        Self::configure_trace_pins(&mut *memory, sink)
+    }
 }
 
 impl DebugEraseSequence for AtSAME5x {
@@ -11,6 +11,7 @@ pub mod nxp;
 pub mod stm32_armv6;
 pub mod stm32_armv7;
 pub mod stm32h7;
+pub mod trace;
 
 use std::{
     error::Error,
@@ -21,7 +22,9 @@ use std::{
 
 use probe_rs_target::CoreType;
 
-use crate::{architecture::arm::ArmProbeInterface, DebugProbeError, MemoryMappedRegister};
+use crate::{
+    architecture::arm::ArmProbeInterface, DebugProbeError, MemoryMappedRegister, WireProtocol,
+};
 
 use super::{
     ap::{AccessPortError, MemoryAp},
@@ -59,6 +62,50 @@ impl ArmDebugSequenceError {
     }
 }
 
+/// The boot mode selected by the boot mode pins of a target, see [`BootModePins`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootMode {
+    /// Boot the application in flash.
+    Normal,
+    /// Boot the bootloader of the chip, for example the system memory bootloader of STM32 chips
+    /// or the ISP mode of LPC chips.
+    Bootloader,
+}
+
+/// Probe pins which are connected to the boot mode straps of a target, for example BOOT0 or ISP.
+///
+/// The pins are driven with `swj_pins` when attaching, right after the probe connected, as
+/// connecting configures the pins of the probe. They keep their level until the probe is
+/// detached, so the straps are sampled when reset is released after attaching under reset, and
+/// by every following reset of the target in the session.
+///
+/// The pins can be declared by the target description, see `boot_mode_pins` of
+/// [`Chip`](crate::config::Chip), or by the debug sequence, see
+/// [`ArmDebugSequence::boot_mode_pins`].
+#[derive(Debug, Clone, Copy)]
+pub struct BootModePins {
+    /// The pins which are connected to boot mode straps.
+    pub select: Pins,
+    /// The levels of the selected pins which select [`BootMode::Bootloader`].
+    ///
+    /// The inverted levels select [`BootMode::Normal`].
+    pub bootloader: Pins,
+}
+
+impl BootModePins {
+    /// Drive the pins to select `mode`.
+    pub fn apply(&self, interface: &mut dyn DapProbe, mode: BootMode) -> Result<(), ArmError> {
+        let levels = match mode {
+            BootMode::Bootloader => self.bootloader.0,
+            BootMode::Normal => !self.bootloader.0,
+        };
+
+        interface.swj_pins((levels & self.select.0) as u32, self.select.0 as u32, 0)?;
+
+        Ok(())
+    }
+}
+
 /// The default sequences that is used for ARM chips that do not specify a specific sequence.
 pub struct DefaultArmSequence(pub(crate) ());
 
@@ -358,6 +405,15 @@ fn cortex_m_reset_catch_set(core: &mut dyn ArmProbe) -> Result<(), ArmError> {
 
     core.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
 
+    // Reset vector catch is optional on ARMv6-M, the bit reads as zero if it is not implemented
+    // and the core would not halt.
+    let demcr = Demcr(core.read_word_32(Demcr::get_mmio_address())?);
+    if !demcr.vc_corereset() {
+        return Err(ArmError::UnsupportedDebugFeature(
+            "halting on reset, DEMCR.VC_CORERESET is not implemented".to_string(),
+        ));
+    }
+
     // Clear the status bits by reading from DHCSR
     let _ = core.read_word_32(Dhcsr::get_mmio_address())?;
 
@@ -377,6 +433,8 @@ fn cortex_m_reset_system(interface: &mut dyn ArmProbe) -> Result<(), ArmError> {
     let start = Instant::now();
 
     while start.elapsed() < Duration::from_micros(50_0000) {
+        crate::cancellation::check()?;
+
         let dhcsr = match interface.read_word_32(Dhcsr::get_mmio_address()) {
             Ok(val) => Dhcsr(val),
             // Some combinations of debug probe and target (in
@@ -399,6 +457,86 @@ fn cortex_m_reset_system(interface: &mut dyn ArmProbe) -> Result<(), ArmError> {
     Err(ArmError::Timeout)
 }
 
+/// Switch the SWJ-DP of the target to `protocol` with the SWJ-DP switch sequences, and leave
+/// the line in idle state.
+///
+/// The switch sequence is preceded by a line reset, so this works independent of the protocol
+/// the SWJ-DP was using before.
+pub(crate) fn swj_dp_switch(
+    interface: &mut dyn DapProbe,
+    protocol: WireProtocol,
+) -> Result<(), ArmError> {
+    // Ensure current debug interface is in reset state.
+    interface.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
+
+    match protocol {
+        WireProtocol::Jtag => {
+            // Execute SWJ-DP Switch Sequence SWD to JTAG (0xE73C).
+            interface.swj_sequence(16, 0xE73C)?;
+        }
+        WireProtocol::Swd => {
+            // Execute SWJ-DP Switch Sequence JTAG to SWD (0xE79E).
+            // Change if SWJ-DP uses deprecated switch code (0xEDB6).
+            interface.swj_sequence(16, 0xE79E)?;
+        }
+    }
+
+    interface.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?; // > 50 cycles SWDIO/TMS High.
+
+    // At least 2 idle cycles (SWDIO/TMS Low), plus the configured additional ones.
+    let idle_cycles = interface.swd_timing().idle_cycles.saturating_add(3).min(64);
+    interface.swj_sequence(idle_cycles, 0x00)?;
+
+    Ok(())
+}
+
+/// The selection alert sequence of the SWJ-DP dormant state, least significant bit first.
+const SELECTION_ALERT: u128 = 0x19BC_0EA2_E3DD_AFE9_8685_2D95_6209_F392;
+
+/// Wake up the SWJ-DP of the target from the dormant state into `protocol`, and leave the line
+/// in idle state.
+///
+/// SWJ-DPs of ADIv5.2 and ADIv6 can start in the dormant state, in which they ignore the
+/// JTAG-to-SWD sequence of [`swj_dp_switch`]. The SWJ-DP is moved into the dormant state first,
+/// from SWD and from JTAG, so this works independent of the state it was in before.
+pub(crate) fn swj_dp_wake_from_dormant(
+    interface: &mut dyn DapProbe,
+    protocol: WireProtocol,
+) -> Result<(), ArmError> {
+    // SWD to dormant: line reset, then 0xE3BC.
+    interface.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
+    interface.swj_sequence(16, 0xE3BC)?;
+
+    // JTAG to dormant: Test-Logic-Reset, then 0x33BBBBBA.
+    interface.swj_sequence(5, 0x1F)?;
+    interface.swj_sequence(31, 0x33BB_BBBA)?;
+
+    // At least 8 cycles SWDIO/TMS high, then the selection alert.
+    interface.swj_sequence(8, 0xFF)?;
+    interface.swj_sequence(64, SELECTION_ALERT as u64)?;
+    interface.swj_sequence(64, (SELECTION_ALERT >> 64) as u64)?;
+
+    // 4 cycles SWDIO/TMS low, then the activation code.
+    match protocol {
+        WireProtocol::Jtag => {
+            // JTAG activation code 0x00A (12 bits).
+            interface.swj_sequence(16, 0x00A0)?;
+            // Test-Logic-Reset.
+            interface.swj_sequence(5, 0x1F)?;
+        }
+        WireProtocol::Swd => {
+            // SWD activation code 0x1A (8 bits).
+            interface.swj_sequence(12, 0x01A0)?;
+            // Line reset, and at least 2 idle cycles.
+            interface.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
+            let idle_cycles = interface.swd_timing().idle_cycles.saturating_add(3).min(64);
+            interface.swj_sequence(idle_cycles, 0x00)?;
+        }
+    }
+
+    Ok(())
+}
+
 /// A interface to operate debug sequences for ARM targets.
 ///
 /// Should be implemented on a custom handle for chips that require special sequence code.
@@ -433,6 +571,8 @@ pub trait ArmDebugSequence: Send + Sync {
             let start = Instant::now();
 
             while start.elapsed() < Duration::from_secs(1) {
+                crate::cancellation::check()?;
+
                 if Pins(memory.swj_pins(n_reset, n_reset, 0)? as u8).nreset() {
                     return Ok(());
                 }
@@ -457,31 +597,13 @@ pub trait ArmDebugSequence: Send + Sync {
 
         // TODO: Use atomic block
 
-        // Ensure current debug interface is in reset state.
-        interface.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
-
         // Make sure the debug port is in the correct mode based on what the probe
         // has selected via active_protocol
-        match interface.active_protocol() {
-            Some(crate::WireProtocol::Jtag) => {
-                // Execute SWJ-DP Switch Sequence SWD to JTAG (0xE73C).
-                interface.swj_sequence(16, 0xE73C)?;
-            }
-            Some(crate::WireProtocol::Swd) => {
-                // Execute SWJ-DP Switch Sequence JTAG to SWD (0xE79E).
-                // Change if SWJ-DP uses deprecated switch code (0xEDB6).
-                interface.swj_sequence(16, 0xE79E)?;
-            }
-            _ => {
-                return Err(ArmDebugSequenceError::SequenceSpecific(
-                    "Cannot detect current protocol".into(),
-                )
-                .into());
-            }
-        }
+        let protocol = interface.active_protocol().ok_or_else(|| {
+            ArmDebugSequenceError::SequenceSpecific("Cannot detect current protocol".into())
+        })?;
 
-        interface.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?; // > 50 cycles SWDIO/TMS High.
-        interface.swj_sequence(3, 0x00)?; // At least 2 idle cycles (SWDIO/TMS Low).
+        swj_dp_switch(interface, protocol)?;
 
         // End of atomic block.
 
@@ -529,6 +651,8 @@ pub trait ArmDebugSequence: Send + Sync {
             let start = Instant::now();
             let mut timeout = true;
             while start.elapsed() < Duration::from_micros(100_0000) {
+                crate::cancellation::check()?;
+
                 let ctrl = interface.read_dp_register::<Ctrl>(dp)?;
                 if ctrl.csyspwrupack() && ctrl.cdbgpwrupack() {
                     timeout = false;
@@ -745,6 +869,71 @@ pub trait ArmDebugSequence: Send + Sync {
     fn debug_erase_sequence(&self) -> Option<Arc<dyn DebugEraseSequence>> {
         None
     }
+
+    /// Return the lifecycle sequence implementation if the device supports it
+    fn lifecycle_sequence(&self) -> Option<Arc<dyn LifecycleSequence>> {
+        None
+    }
+
+    /// Return the probe pins which select the boot mode, if the target declares them.
+    ///
+    /// They are used when a boot mode is requested with [`Probe::set_boot_mode`] without pins.
+    ///
+    /// [`Probe::set_boot_mode`]: crate::Probe::set_boot_mode
+    fn boot_mode_pins(&self) -> Option<BootModePins> {
+        None
+    }
+}
+
+/// The lifecycle state of a device, which restricts access through the debug interface.
+///
+/// The states are ordered: a device can only be advanced to a later state with
+/// [`Session::advance_lifecycle`](crate::Session::advance_lifecycle). Going back to an earlier
+/// state requires vendor specific means, which usually erase the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum LifecycleState {
+    /// Debug access is not restricted.
+    Open,
+    /// Debug access to the secure state is blocked after the next reset, the non-secure state
+    /// can still be debugged. It can be restored by erasing the device.
+    SecureProtected,
+    /// Debug access is blocked after the next reset. It can be restored by erasing the device.
+    Protected,
+}
+
+impl std::fmt::Display for LifecycleState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LifecycleState::Open => write!(f, "open"),
+            LifecycleState::SecureProtected => write!(f, "secure-protected"),
+            LifecycleState::Protected => write!(f, "protected"),
+        }
+    }
+}
+
+/// Lifecycle Handling via the Device's Debug Interface
+pub trait LifecycleSequence: Send + Sync {
+    /// Read the lifecycle state the device is in, or enters with the next reset.
+    fn lifecycle_state(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+    ) -> Result<LifecycleState, ArmError>;
+
+    /// Advance the device to the lifecycle state `state`, which is later than its current state.
+    ///
+    /// The change is programmed into the non-volatile memory of the device, and usually takes
+    /// effect after the next reset.
+    fn advance_lifecycle(
+        &self,
+        _interface: &mut dyn ArmProbeInterface,
+        _state: LifecycleState,
+    ) -> Result<(), ArmError> {
+        Err(
+            DebugProbeError::NotImplemented("Lifecycle changes are not available on this device")
+                .into(),
+        )
+    }
 }
 
 /// Chip-Erase Handling via the Device's Debug Interface
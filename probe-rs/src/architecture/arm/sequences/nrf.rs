@@ -1,17 +1,22 @@
 //! Sequences for the nRF devices.
 
-use super::ArmDebugSequence;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{ArmDebugSequence, DebugEraseSequence, LifecycleSequence, LifecycleState};
 use crate::architecture::arm::ap::MemoryAp;
+use crate::architecture::arm::component::TraceSink;
 use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
+use crate::architecture::arm::memory::CoresightComponent;
 use crate::architecture::arm::sequences::ArmDebugSequenceError;
 use crate::architecture::arm::ArmError;
 use crate::architecture::arm::{
     communication_interface::Initialized, ApAddress, ArmCommunicationInterface, ArmProbeInterface,
-    DapAccess,
+    DapAccess, DpAddress,
 };
 use crate::session::MissingPermissions;
 
-pub trait Nrf: Sync + Send {
+pub trait Nrf: Sync + Send + Clone + 'static {
     /// Returns the ahb_ap and ctrl_ap of every core
     fn core_aps(&self, interface: &mut dyn ArmProbe) -> Vec<(ApAddress, ApAddress)>;
 
@@ -25,11 +30,95 @@ pub trait Nrf: Sync + Send {
 
     /// Returns true if a network core is present
     fn has_network_core(&self) -> bool;
+
+    /// Returns the trace pins of the device
+    fn trace_port(&self) -> TracePort;
+
+    /// Returns how the access port protection in the UICR is decoded, which depends on the
+    /// revision of the device.
+    fn approtect_kind(&self, memory: &mut dyn ArmProbe) -> Result<ApprotectKind, ArmError>;
+
+    /// Returns the address of UICR.SECUREAPPROTECT of the application core.
+    fn uicr_secureapprotect(&self) -> u64;
+}
+
+/// How a device decodes UICR.APPROTECT and UICR.SECUREAPPROTECT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprotectKind {
+    /// The access port is only protected if the PALL field is 0, the erased UICR leaves it
+    /// open.
+    Legacy,
+    /// The access port is protected unless the register holds [`APPROTECT_UNPROTECTED`], so the
+    /// erased UICR protects it after the next reset. This is the case for devices with
+    /// hardware APPROTECT.
+    Hardware,
+}
+
+impl ApprotectKind {
+    /// Returns `true` if `value` in UICR.APPROTECT or UICR.SECUREAPPROTECT protects the access
+    /// port.
+    pub fn is_protected(self, value: u32) -> bool {
+        match self {
+            ApprotectKind::Legacy => value & APPROTECT_PALL_MASK == 0,
+            ApprotectKind::Hardware => value != APPROTECT_UNPROTECTED,
+        }
+    }
+}
+
+/// The trace pins of a device with a TAD (Trace and debug control) peripheral.
+pub struct TracePort {
+    /// The PSEL value of the TRACECLK pin.
+    pub clock_pin: u32,
+    /// The PSEL values of the TRACEDATA pins. TRACEDATA0 is also used as SWO.
+    pub data_pins: [u32; 4],
+    /// The TPIU clock for each value of TRACEPORTSPEED.
+    pub port_speeds: [u32; 4],
+    /// Whether the pins have to be assigned to the trace subsystem in their GPIO configuration.
+    pub select_pins: bool,
 }
 
 const ERASEALL: u8 = 0x04;
 const ERASEALLSTATUS: u8 = 0x08;
 
+/// The base address of the TAD peripheral.
+const TAD: u64 = 0xE008_0000;
+const TAD_TASKS_CLOCKSTART: u64 = TAD + 0x004;
+const TAD_ENABLE: u64 = TAD + 0x500;
+const TAD_PSEL_TRACECLK: u64 = TAD + 0x504;
+const TAD_PSEL_TRACEDATA0: u64 = TAD + 0x508;
+const TAD_TRACEPORTSPEED: u64 = TAD + 0x518;
+
+/// The CONNECT bit of a PSEL register, which disconnects the pin when set.
+const PSEL_DISCONNECTED: u32 = 1 << 31;
+
+/// The base address of the secure GPIO port P0, followed by P1.
+const GPIO_P0_S: u64 = 0x5084_2500;
+const GPIO_PORT_SIZE: u64 = 0x300;
+const GPIO_PIN_CNF: u64 = 0x700;
+/// The MCUSEL field of PIN_CNF, set to TND to hand the pin to the trace subsystem.
+const PIN_CNF_MCUSEL_MASK: u32 = 0b111 << 28;
+const PIN_CNF_MCUSEL_TND: u32 = 0b111 << 28;
+
+/// UICR.APPROTECT of the application core.
+const UICR_APPROTECT: u64 = 0x00FF_8000;
+/// The value of UICR.APPROTECT which protects the access port after the next reset.
+const APPROTECT_PROTECTED: u32 = 0;
+/// The PALL field of UICR.APPROTECT on devices without hardware APPROTECT.
+const APPROTECT_PALL_MASK: u32 = 0xFF;
+/// The only value of UICR.APPROTECT which leaves the access port open on devices with
+/// hardware APPROTECT.
+pub const APPROTECT_UNPROTECTED: u32 = 0x50FA_50FA;
+
+/// The base address of the secure NVMC peripheral.
+const NVMC_S: u64 = 0x5003_9000;
+const NVMC_READY: u64 = NVMC_S + 0x400;
+const NVMC_CONFIG: u64 = NVMC_S + 0x504;
+/// NVMC_CONFIG.WEN values, which enable or disable writes to the flash and UICR.
+const NVMC_CONFIG_WRITE_ENABLE: u32 = 1;
+const NVMC_CONFIG_READ_ONLY: u32 = 0;
+/// A write to the UICR takes less than a millisecond, this leaves plenty of margin.
+const NVMC_READY_TIMEOUT: Duration = Duration::from_millis(100);
+
 const APPLICATION_RESET_S_NETWORK_FORCEOFF_REGISTER: u32 = 0x50005614;
 const RELEASE_FORCEOFF: u32 = 0;
 
@@ -44,9 +133,17 @@ fn unlock_core(
         .erase_all()
         .map_err(|MissingPermissions(desc)| ArmError::MissingPermissions(desc))?;
 
-    arm_interface.write_raw_ap_register(ap_address, ERASEALL, 1)?;
+    erase_all_through_ctrl_ap(arm_interface, ap_address)
+}
+
+/// Erases the flash, UICR and RAM of the core with the ERASEALL task of its ctrl ap.
+fn erase_all_through_ctrl_ap(
+    interface: &mut (impl DapAccess + ?Sized),
+    ctrl_ap_address: ApAddress,
+) -> Result<(), ArmError> {
+    interface.write_raw_ap_register(ctrl_ap_address, ERASEALL, 1)?;
 
-    while arm_interface.read_raw_ap_register(ap_address, ERASEALLSTATUS)? != 0 {}
+    while interface.read_raw_ap_register(ctrl_ap_address, ERASEALLSTATUS)? != 0 {}
 
     Ok(())
 }
@@ -60,6 +157,74 @@ fn set_network_core_running(interface: &mut dyn ArmProbe) -> Result<(), ArmError
     Ok(())
 }
 
+/// Routes the trace pins of `port` to the trace subsystem.
+///
+/// For SWO only TRACEDATA0 is connected, for the TPIU the clock and all data pins are connected.
+/// All other trace pins are disconnected, so they can be used as GPIOs.
+pub fn configure_trace_port(
+    memory: &mut dyn ArmProbe,
+    port: &TracePort,
+    sink: &TraceSink,
+) -> Result<(), ArmError> {
+    let (config, data_pin_count, use_clock) = match sink {
+        TraceSink::Swo(config) => (config, 1, false),
+        TraceSink::Tpiu(config) => (config, port.data_pins.len(), true),
+        TraceSink::TraceMemory => {
+            return Err(
+                ArmDebugSequenceError::custom("nRF devices do not have a trace buffer").into(),
+            )
+        }
+    };
+
+    let portspeed = port
+        .port_speeds
+        .iter()
+        .position(|&clock| clock == config.tpiu_clk())
+        .ok_or_else(|| {
+            ArmDebugSequenceError::custom(format!(
+                "Nordic does not support TPIU CLK value of {}",
+                config.tpiu_clk()
+            ))
+        })?;
+
+    memory.write_word_32(TAD_TASKS_CLOCKSTART, 1)?;
+    memory.write_word_32(TAD_ENABLE, 1)?;
+    memory.write_word_32(TAD_TRACEPORTSPEED, portspeed as u32)?;
+
+    let mut connected = Vec::new();
+
+    let clock_psel = if use_clock {
+        connected.push(port.clock_pin);
+        port.clock_pin
+    } else {
+        PSEL_DISCONNECTED
+    };
+    memory.write_word_32(TAD_PSEL_TRACECLK, clock_psel)?;
+
+    for (index, &pin) in port.data_pins.iter().enumerate() {
+        let psel = if index < data_pin_count {
+            connected.push(pin);
+            pin
+        } else {
+            PSEL_DISCONNECTED
+        };
+        memory.write_word_32(TAD_PSEL_TRACEDATA0 + 4 * index as u64, psel)?;
+    }
+
+    if port.select_pins {
+        for pin in connected {
+            let address = GPIO_P0_S
+                + GPIO_PORT_SIZE * ((pin >> 5) & 1) as u64
+                + GPIO_PIN_CNF
+                + 4 * (pin & 0x1F) as u64;
+
+            memory.update_bits_32(address, PIN_CNF_MCUSEL_MASK, PIN_CNF_MCUSEL_TND)?;
+        }
+    }
+
+    Ok(())
+}
+
 impl<T: Nrf> ArmDebugSequence for T {
     fn debug_device_unlock(
         &self,
@@ -114,4 +279,160 @@ impl<T: Nrf> ArmDebugSequence for T {
 
         Ok(())
     }
+    fn trace_start(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        components: &[CoresightComponent],
+        sink: &TraceSink,
+    ) -> Result<(), ArmError> {
+        let mut memory = interface.memory_interface(components[0].ap)?;
+        configure_trace_port(&mut *memory, &self.trace_port(), sink)
+    }
+
+    fn debug_erase_sequence(&self) -> Option<Arc<dyn DebugEraseSequence>> {
+        Some(Arc::new(self.clone()))
+    }
+
+    fn lifecycle_sequence(&self) -> Option<Arc<dyn LifecycleSequence>> {
+        Some(Arc::new(self.clone()))
+    }
+}
+
+/// The lifecycle state of the application core is its access port protection in the UICR.
+///
+/// The protection is enabled by programming UICR.APPROTECT, or UICR.SECUREAPPROTECT for the
+/// secure state only, and only removed by erasing the device through the ctrl ap, which also
+/// erases the UICR.
+impl<T: Nrf> LifecycleSequence for T {
+    fn lifecycle_state(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+    ) -> Result<LifecycleState, ArmError> {
+        let mut memory = interface.memory_interface(default_ap())?;
+
+        let kind = self.approtect_kind(&mut *memory)?;
+        let approtect = memory.read_word_32(UICR_APPROTECT)?;
+        let secureapprotect = memory.read_word_32(self.uicr_secureapprotect())?;
+
+        Ok(decode_lifecycle_state(kind, approtect, secureapprotect))
+    }
+
+    fn advance_lifecycle(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        state: LifecycleState,
+    ) -> Result<(), ArmError> {
+        let register = match state {
+            LifecycleState::SecureProtected => self.uicr_secureapprotect(),
+            LifecycleState::Protected => UICR_APPROTECT,
+            _ => {
+                return Err(ArmDebugSequenceError::custom(format!(
+                    "nRF devices cannot be advanced to lifecycle state {state}"
+                ))
+                .into())
+            }
+        };
+
+        let mut memory = interface.memory_interface(default_ap())?;
+
+        memory.write_word_32(NVMC_CONFIG, NVMC_CONFIG_WRITE_ENABLE)?;
+        memory.write_word_32(register, APPROTECT_PROTECTED)?;
+        let ready = wait_for_nvmc_ready(&mut *memory);
+        memory.write_word_32(NVMC_CONFIG, NVMC_CONFIG_READ_ONLY)?;
+
+        ready
+    }
+}
+
+/// Decode the lifecycle state from UICR.APPROTECT and UICR.SECUREAPPROTECT.
+fn decode_lifecycle_state(
+    kind: ApprotectKind,
+    approtect: u32,
+    secureapprotect: u32,
+) -> LifecycleState {
+    if kind.is_protected(approtect) {
+        LifecycleState::Protected
+    } else if kind.is_protected(secureapprotect) {
+        LifecycleState::SecureProtected
+    } else {
+        LifecycleState::Open
+    }
+}
+
+/// Wait until the NVMC finished writing to the flash or UICR.
+fn wait_for_nvmc_ready(memory: &mut dyn ArmProbe) -> Result<(), ArmError> {
+    let start = Instant::now();
+    while memory.read_word_32(NVMC_READY)? == 0 {
+        if start.elapsed() > NVMC_READY_TIMEOUT {
+            return Err(ArmError::Timeout);
+        }
+    }
+
+    Ok(())
+}
+
+/// The AHB-AP of the application core.
+fn default_ap() -> MemoryAp {
+    MemoryAp::new(ApAddress {
+        dp: DpAddress::Default,
+        ap: 0,
+    })
+}
+
+impl<T: Nrf> DebugEraseSequence for T {
+    fn erase_all(&self, interface: &mut dyn ArmProbeInterface) -> Result<(), ArmError> {
+        let core_aps = {
+            let mut memory = interface.memory_interface(default_ap())?;
+            self.core_aps(&mut *memory)
+        };
+
+        for (core_index, (_, core_ctrl_ap_address)) in core_aps.into_iter().enumerate() {
+            tracing::info!("Erasing core {} through its ctrl ap", core_index);
+            erase_all_through_ctrl_ap(interface, core_ctrl_ap_address)?;
+        }
+
+        // The erase resets the debug logic, so the debug port has to be set up again.
+        Err(ArmError::ReAttachRequired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifecycle_state_depends_on_the_approtect_kind() {
+        let erased = 0xFFFF_FFFF;
+
+        assert_eq!(
+            decode_lifecycle_state(ApprotectKind::Legacy, erased, erased),
+            LifecycleState::Open
+        );
+        assert_eq!(
+            decode_lifecycle_state(ApprotectKind::Legacy, 0xFFFF_FF00, erased),
+            LifecycleState::Protected
+        );
+        assert_eq!(
+            decode_lifecycle_state(ApprotectKind::Legacy, erased, 0),
+            LifecycleState::SecureProtected
+        );
+
+        // With hardware APPROTECT, the erased UICR protects the device.
+        assert_eq!(
+            decode_lifecycle_state(ApprotectKind::Hardware, erased, erased),
+            LifecycleState::Protected
+        );
+        assert_eq!(
+            decode_lifecycle_state(ApprotectKind::Hardware, APPROTECT_UNPROTECTED, erased),
+            LifecycleState::SecureProtected
+        );
+        assert_eq!(
+            decode_lifecycle_state(
+                ApprotectKind::Hardware,
+                APPROTECT_UNPROTECTED,
+                APPROTECT_UNPROTECTED
+            ),
+            LifecycleState::Open
+        );
+    }
 }
@@ -2,7 +2,7 @@
 
 use std::sync::Arc;
 
-use super::{ArmDebugSequence, ArmDebugSequenceError};
+use super::{ArmDebugSequence, ArmDebugSequenceError, DebugEraseSequence};
 use crate::architecture::arm::{
     ap::MemoryAp, component::TraceSink, memory::CoresightComponent, ApAddress, ArmError,
     ArmProbeInterface, DpAddress,
@@ -43,8 +43,36 @@ impl Nrf52 {
         let status = iface.read_raw_ap_register(ctrl_ap, APPROTECTSTATUS)?;
         Ok(status != 0)
     }
+
+    /// Erases the flash, UICR and RAM with the ERASEALL task of the ctrl ap.
+    fn erase_all(
+        &self,
+        iface: &mut dyn ArmProbeInterface,
+        ctrl_ap: ApAddress,
+    ) -> Result<(), ArmError> {
+        // Reset
+        iface.write_raw_ap_register(ctrl_ap, RESET, 1)?;
+        iface.write_raw_ap_register(ctrl_ap, RESET, 0)?;
+
+        // Start erase
+        iface.write_raw_ap_register(ctrl_ap, ERASEALL, 1)?;
+
+        // Wait for erase done
+        while iface.read_raw_ap_register(ctrl_ap, ERASEALLSTATUS)? != 0 {}
+
+        // Reset again
+        iface.write_raw_ap_register(ctrl_ap, RESET, 1)?;
+        iface.write_raw_ap_register(ctrl_ap, RESET, 0)?;
+
+        Ok(())
+    }
 }
 
+const CTRL_AP: ApAddress = ApAddress {
+    ap: 1,
+    dp: DpAddress::Default,
+};
+
 mod clock {
     use crate::architecture::arm::{memory::adi_v5_memory_interface::ArmProbe, ArmError};
     use bitfield::bitfield;
@@ -87,10 +115,7 @@ impl ArmDebugSequence for Nrf52 {
         _default_ap: MemoryAp,
         permissions: &crate::Permissions,
     ) -> Result<(), ArmError> {
-        let ctrl_ap = ApAddress {
-            ap: 1,
-            dp: DpAddress::Default,
-        };
+        let ctrl_ap = CTRL_AP;
 
         tracing::info!("Checking if core is unlocked");
         if self.is_core_unlocked(iface, ctrl_ap)? {
@@ -103,19 +128,7 @@ impl ArmDebugSequence for Nrf52 {
             .erase_all()
             .map_err(|MissingPermissions(desc)| ArmError::MissingPermissions(desc))?;
 
-        // Reset
-        iface.write_raw_ap_register(ctrl_ap, RESET, 1)?;
-        iface.write_raw_ap_register(ctrl_ap, RESET, 0)?;
-
-        // Start erase
-        iface.write_raw_ap_register(ctrl_ap, ERASEALL, 1)?;
-
-        // Wait for erase done
-        while iface.read_raw_ap_register(ctrl_ap, ERASEALLSTATUS)? != 0 {}
-
-        // Reset again
-        iface.write_raw_ap_register(ctrl_ap, RESET, 1)?;
-        iface.write_raw_ap_register(ctrl_ap, RESET, 0)?;
+        self.erase_all(iface, ctrl_ap)?;
 
         if !self.is_core_unlocked(iface, ctrl_ap)? {
             return Err(ArmDebugSequenceError::custom("Could not unlock core").into());
@@ -165,6 +178,18 @@ impl ArmDebugSequence for Nrf52 {
 
         Ok(())
     }
+
+    fn debug_erase_sequence(&self) -> Option<Arc<dyn DebugEraseSequence>> {
+        Some(Self::create())
+    }
+}
+
+impl DebugEraseSequence for Nrf52 {
+    fn erase_all(&self, interface: &mut dyn ArmProbeInterface) -> Result<(), ArmError> {
+        Nrf52::erase_all(self, interface, CTRL_AP)?;
+
+        Err(ArmError::ReAttachRequired)
+    }
 }
 
 impl From<ComponentError> for ArmError {
@@ -2,7 +2,10 @@
 
 use std::sync::Arc;
 
-use super::{nrf::Nrf, ArmDebugSequence};
+use super::{
+    nrf::{ApprotectKind, Nrf, TracePort},
+    ArmDebugSequence,
+};
 use crate::architecture::arm::ap::{AccessPort, CSW};
 use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
 use crate::architecture::arm::ArmError;
@@ -11,6 +14,7 @@ use crate::architecture::arm::{
 };
 
 /// The sequence handle for the nRF5340.
+#[derive(Clone)]
 pub struct Nrf5340(());
 
 impl Nrf5340 {
@@ -58,4 +62,23 @@ impl Nrf for Nrf5340 {
     fn has_network_core(&self) -> bool {
         true
     }
+
+    fn trace_port(&self) -> TracePort {
+        // TRACECLK is P0.12, TRACEDATA0 to TRACEDATA3 are P0.11 to P0.08.
+        TracePort {
+            clock_pin: 12,
+            data_pins: [11, 10, 9, 8],
+            port_speeds: [64_000_000, 32_000_000, 16_000_000, 8_000_000],
+            select_pins: true,
+        }
+    }
+
+    fn approtect_kind(&self, _memory: &mut dyn ArmProbe) -> Result<ApprotectKind, ArmError> {
+        // All production revisions of the nRF5340 have hardware APPROTECT.
+        Ok(ApprotectKind::Hardware)
+    }
+
+    fn uicr_secureapprotect(&self) -> u64 {
+        0x00FF_801C
+    }
 }
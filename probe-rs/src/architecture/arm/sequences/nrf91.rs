@@ -2,7 +2,10 @@
 
 use std::sync::Arc;
 
-use super::{nrf::Nrf, ArmDebugSequence};
+use super::{
+    nrf::{ApprotectKind, Nrf, TracePort},
+    ArmDebugSequence,
+};
 use crate::architecture::arm::ap::AccessPort;
 use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
 use crate::architecture::arm::ArmError;
@@ -10,7 +13,12 @@ use crate::architecture::arm::{
     communication_interface::Initialized, ApAddress, ArmCommunicationInterface, DapAccess,
 };
 
+/// FICR.INFO.PART, the part code of the device.
+const FICR_INFO_PART: u64 = 0x00FF_020C;
+const PART_NRF9160: u32 = 0x9160;
+
 /// The sequence handle for the nRF9160.
+#[derive(Clone)]
 pub struct Nrf9160(());
 
 impl Nrf9160 {
@@ -56,4 +64,26 @@ impl Nrf for Nrf9160 {
     fn has_network_core(&self) -> bool {
         false
     }
+
+    fn trace_port(&self) -> TracePort {
+        // TRACECLK is P0.21, TRACEDATA0 to TRACEDATA3 are P0.22 to P0.25.
+        TracePort {
+            clock_pin: 21,
+            data_pins: [22, 23, 24, 25],
+            port_speeds: [32_000_000, 16_000_000, 8_000_000, 4_000_000],
+            select_pins: false,
+        }
+    }
+
+    fn approtect_kind(&self, memory: &mut dyn ArmProbe) -> Result<ApprotectKind, ArmError> {
+        // The nRF9160 decodes only the PALL field, the nRF91x1 devices have hardware APPROTECT.
+        match memory.read_word_32(FICR_INFO_PART)? {
+            PART_NRF9160 => Ok(ApprotectKind::Legacy),
+            _ => Ok(ApprotectKind::Hardware),
+        }
+    }
+
+    fn uicr_secureapprotect(&self) -> u64 {
+        0x00FF_802C
+    }
 }
@@ -28,6 +28,25 @@ impl Stm32Armv7 {
     pub fn create() -> Arc<Self> {
         Arc::new(Self {})
     }
+
+    /// Route the trace pins, including TRACESWO, for the given trace sink.
+    ///
+    /// This sets TRACE_IOEN in DBGMCU_CR, which assigns the trace pins to their trace function
+    /// and enables the trace clock. Without it, no SWO data leaves the chip. The pins are
+    /// released again if trace data is written to memory.
+    pub fn configure_trace_pins(
+        memory: &mut dyn ArmProbe,
+        sink: &TraceSink,
+    ) -> Result<(), ArmError> {
+        let mut cr = dbgmcu::Control::read(memory)?;
+
+        // TRACE_MODE 0 is asynchronous trace, which is used for SWO and is the only mode
+        // supported by the TPIU configuration.
+        cr.set_traceioen(matches!(sink, TraceSink::Tpiu(_) | TraceSink::Swo(_)));
+        cr.set_tracemode(0);
+
+        cr.write(memory)
+    }
 }
 
 mod dbgmcu {
@@ -106,17 +125,6 @@ impl ArmDebugSequence for Stm32Armv7 {
         sink: &TraceSink,
     ) -> Result<(), ArmError> {
         let mut memory = interface.memory_interface(components[0].ap)?;
-        let mut cr = dbgmcu::Control::read(&mut *memory)?;
-
-        if matches!(sink, TraceSink::Tpiu(_) | TraceSink::Swo(_)) {
-            cr.set_traceioen(true);
-            cr.set_tracemode(0);
-        } else {
-            cr.set_traceioen(false);
-            cr.set_tracemode(0);
-        }
-
-        cr.write(&mut *memory)?;
-        Ok(())
+        Self::configure_trace_pins(&mut *memory, sink)
     }
 }
@@ -7,11 +7,8 @@ use probe_rs_target::CoreType;
 use super::ArmDebugSequence;
 use crate::architecture::arm::{
     ap::MemoryAp,
-    component::{TraceFunnel, TraceSink},
-    memory::{
-        adi_v5_memory_interface::ArmProbe, romtable::RomTableError, CoresightComponent,
-        PeripheralType,
-    },
+    component::{find_component_at, TraceFunnel, TraceSink},
+    memory::{adi_v5_memory_interface::ArmProbe, CoresightComponent, PeripheralType},
     ApAddress, ArmError, ArmProbeInterface, DpAddress,
 };
 
@@ -128,20 +125,7 @@ fn find_trace_funnel(
     components: &[CoresightComponent],
     trace_funnel: TraceFunnelId,
 ) -> Result<&CoresightComponent, ArmError> {
-    components
-        .iter()
-        .find_map(|comp| {
-            comp.iter().find(|component| {
-                let id = component.component.id();
-                id.peripheral_id().is_of_type(PeripheralType::TraceFunnel)
-                    && id.component_address() == trace_funnel as u64
-            })
-        })
-        .ok_or_else(|| {
-            ArmError::from(RomTableError::ComponentNotFound(
-                PeripheralType::TraceFunnel,
-            ))
-        })
+    find_component_at(components, PeripheralType::TraceFunnel, trace_funnel as u64)
 }
 
 impl ArmDebugSequence for Stm32h7 {
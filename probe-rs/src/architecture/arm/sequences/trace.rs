@@ -0,0 +1,426 @@
+//! Tracing of the operations performed by debug sequences.
+//!
+//! [`SequenceTrace::record`] records the DAP register, SWJ and memory accesses a closure
+//! performs through the ARM communication interface, with the debug sequence step they belong
+//! to if the sequence is wrapped in a [`TracingSequence`]. Every operation is also logged at the
+//! info level.
+//!
+//! Together with the [`FakeProbe`](crate::FakeProbe), this is a dry run of the sequences of a
+//! target, which lets sequence authors check vendor init code without hardware, and users audit
+//! what attaching does to their chip.
+//!
+//! # Example
+//!
+//! ```
+//! use probe_rs::architecture::arm::sequences::trace::{SequenceTrace, TracingSequence};
+//! use probe_rs::config::{get_target_by_name, DebugSequence};
+//! use probe_rs::{FakeProbe, Permissions};
+//!
+//! let mut target = get_target_by_name("nrf51822_xxAC")?;
+//! if let DebugSequence::Arm(sequence) = target.debug_sequence {
+//!     target.debug_sequence = DebugSequence::Arm(TracingSequence::wrap(sequence));
+//! }
+//!
+//! let (session, trace) =
+//!     SequenceTrace::record(|| FakeProbe::new().into_probe().attach(target, Permissions::default()));
+//! session?;
+//!
+//! println!("{trace}");
+//! # Ok::<(), probe_rs::Error>(())
+//! ```
+//!
+//! Accesses which are batched by [`DapAccess::execute_batch`](crate::architecture::arm::DapAccess::execute_batch),
+//! or which go to the probe directly, for example in
+//! [`ArmDebugSequence::debug_port_setup`], are not recorded.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+use probe_rs_target::CoreType;
+
+use super::{ArmDebugSequence, BootModePins, DebugEraseSequence, LifecycleSequence};
+use crate::architecture::arm::{
+    ap::MemoryAp,
+    communication_interface::{DapProbe, Initialized},
+    component::TraceSink,
+    memory::{adi_v5_memory_interface::ArmProbe, romtable::CoresightComponent},
+    ApAddress, ArmCommunicationInterface, ArmError, ArmProbeInterface, DpAddress,
+};
+
+thread_local! {
+    static CURRENT_TRACE: RefCell<Option<Vec<SequenceOperation>>> = const { RefCell::new(None) };
+}
+
+/// An operation recorded by [`SequenceTrace::record`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceOperation {
+    /// A step of a [`TracingSequence`] started, named like in the ARM SVD Debug Description,
+    /// for example `DebugPortStart`.
+    Step(&'static str),
+    /// A debug port register was read.
+    DpRead {
+        /// The debug port.
+        dp: DpAddress,
+        /// The register address, with the bank in the upper 4 bits.
+        address: u8,
+        /// The value read, if the access succeeded.
+        value: Option<u32>,
+    },
+    /// A debug port register was written.
+    DpWrite {
+        /// The debug port.
+        dp: DpAddress,
+        /// The register address, with the bank in the upper 4 bits.
+        address: u8,
+        /// The value written.
+        value: u32,
+    },
+    /// An access port register was read, once or repeatedly.
+    ApRead {
+        /// The access port.
+        ap: ApAddress,
+        /// The register address.
+        address: u8,
+        /// The values read, empty if the access failed.
+        values: Vec<u32>,
+    },
+    /// An access port register was written, once or repeatedly.
+    ApWrite {
+        /// The access port.
+        ap: ApAddress,
+        /// The register address.
+        address: u8,
+        /// The values written.
+        values: Vec<u32>,
+    },
+    /// A bit sequence was sent on SWDIO/TMS.
+    SwjSequence {
+        /// The number of bits.
+        bit_len: u8,
+        /// The bits, least significant first.
+        bits: u64,
+    },
+    /// The probe pins were set or read.
+    SwjPins {
+        /// The values of the pins to set.
+        pin_out: u32,
+        /// The mask of the pins to set.
+        pin_select: u32,
+    },
+    /// Memory was read. Failed reads are not recorded.
+    MemoryRead {
+        /// The start address.
+        address: u64,
+        /// The access width in bits.
+        width: u8,
+        /// The values read.
+        values: Vec<u64>,
+    },
+    /// Memory was written.
+    MemoryWrite {
+        /// The start address.
+        address: u64,
+        /// The access width in bits.
+        width: u8,
+        /// The values written.
+        values: Vec<u64>,
+    },
+}
+
+impl fmt::Display for SequenceOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SequenceOperation::Step(name) => write!(f, "{name}"),
+            SequenceOperation::DpRead { dp, address, value } => {
+                write!(f, "  DP {dp:x?} read  {address:#04x}")?;
+                match value {
+                    Some(value) => write!(f, " = {value:#010x}"),
+                    None => write!(f, " failed"),
+                }
+            }
+            SequenceOperation::DpWrite { dp, address, value } => {
+                write!(f, "  DP {dp:x?} write {address:#04x} = {value:#010x}")
+            }
+            SequenceOperation::ApRead {
+                ap,
+                address,
+                values,
+            } => {
+                write!(f, "  AP {} read  {address:#04x} = {values:#010x?}", ap.ap)
+            }
+            SequenceOperation::ApWrite {
+                ap,
+                address,
+                values,
+            } => {
+                write!(f, "  AP {} write {address:#04x} = {values:#010x?}", ap.ap)
+            }
+            SequenceOperation::SwjSequence { bit_len, bits } => {
+                write!(f, "  SWJ sequence of {bit_len} bits {bits:#x}")
+            }
+            SequenceOperation::SwjPins {
+                pin_out,
+                pin_select,
+            } => write!(f, "  SWJ pins {pin_out:#04x}, mask {pin_select:#04x}"),
+            SequenceOperation::MemoryRead {
+                address,
+                width,
+                values,
+            } => write!(f, "  read{width}  {address:#010x} = {values:#x?}"),
+            SequenceOperation::MemoryWrite {
+                address,
+                width,
+                values,
+            } => write!(f, "  write{width} {address:#010x} = {values:#x?}"),
+        }
+    }
+}
+
+/// The operations recorded by [`SequenceTrace::record`], see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SequenceTrace {
+    /// The operations, in the order in which they were performed.
+    pub operations: Vec<SequenceOperation>,
+}
+
+impl SequenceTrace {
+    /// Run `f`, and record the operations it performs on this thread.
+    ///
+    /// Recordings can be nested, the operations of an inner recording are not part of the outer
+    /// one.
+    pub fn record<T>(f: impl FnOnce() -> T) -> (T, SequenceTrace) {
+        let previous = CURRENT_TRACE.with(|trace| trace.replace(Some(Vec::new())));
+        let result = f();
+        let operations = CURRENT_TRACE
+            .with(|trace| trace.replace(previous))
+            .unwrap_or_default();
+
+        (result, SequenceTrace { operations })
+    }
+
+    /// Returns `true` if operations are currently recorded on this thread.
+    pub fn is_recording() -> bool {
+        CURRENT_TRACE.with(|trace| trace.borrow().is_some())
+    }
+}
+
+impl fmt::Display for SequenceTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for operation in &self.operations {
+            writeln!(f, "{operation}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Record the operation created by `operation`, if operations are recorded on this thread.
+pub(crate) fn trace(operation: impl FnOnce() -> SequenceOperation) {
+    CURRENT_TRACE.with(|trace| {
+        if let Some(operations) = trace.borrow_mut().as_mut() {
+            let operation = operation();
+            tracing::info!("Sequence trace: {}", operation);
+            operations.push(operation);
+        }
+    });
+}
+
+/// A debug sequence which marks the start of each of its steps in a [`SequenceTrace`], and
+/// otherwise behaves like the wrapped sequence.
+pub struct TracingSequence {
+    inner: Arc<dyn ArmDebugSequence>,
+}
+
+impl TracingSequence {
+    /// Wrap `sequence`.
+    pub fn wrap(sequence: Arc<dyn ArmDebugSequence>) -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self { inner: sequence })
+    }
+}
+
+impl fmt::Debug for TracingSequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TracingSequence").finish_non_exhaustive()
+    }
+}
+
+impl ArmDebugSequence for TracingSequence {
+    fn reset_hardware_assert(&self, interface: &mut dyn DapProbe) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("ResetHardwareAssert"));
+        self.inner.reset_hardware_assert(interface)
+    }
+
+    fn reset_hardware_deassert(&self, memory: &mut dyn ArmProbe) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("ResetHardwareDeassert"));
+        self.inner.reset_hardware_deassert(memory)
+    }
+
+    fn debug_port_setup(&self, interface: &mut dyn DapProbe) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("DebugPortSetup"));
+        self.inner.debug_port_setup(interface)
+    }
+
+    fn debug_port_start(
+        &self,
+        interface: &mut ArmCommunicationInterface<Initialized>,
+        dp: DpAddress,
+    ) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("DebugPortStart"));
+        self.inner.debug_port_start(interface, dp)
+    }
+
+    fn debug_core_start(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        core_ap: MemoryAp,
+        core_type: CoreType,
+        debug_base: Option<u64>,
+        cti_base: Option<u64>,
+    ) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("DebugCoreStart"));
+        self.inner
+            .debug_core_start(interface, core_ap, core_type, debug_base, cti_base)
+    }
+
+    fn reset_catch_set(
+        &self,
+        core: &mut dyn ArmProbe,
+        core_type: CoreType,
+        debug_base: Option<u64>,
+    ) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("ResetCatchSet"));
+        self.inner.reset_catch_set(core, core_type, debug_base)
+    }
+
+    fn reset_catch_clear(
+        &self,
+        core: &mut dyn ArmProbe,
+        core_type: CoreType,
+        debug_base: Option<u64>,
+    ) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("ResetCatchClear"));
+        self.inner.reset_catch_clear(core, core_type, debug_base)
+    }
+
+    fn trace_start(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        components: &[CoresightComponent],
+        sink: &TraceSink,
+    ) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("TraceStart"));
+        self.inner.trace_start(interface, components, sink)
+    }
+
+    fn reset_system(
+        &self,
+        interface: &mut dyn ArmProbe,
+        core_type: CoreType,
+        debug_base: Option<u64>,
+    ) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("ResetSystem"));
+        self.inner.reset_system(interface, core_type, debug_base)
+    }
+
+    fn debug_device_unlock(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permissions: &crate::Permissions,
+    ) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("DebugDeviceUnlock"));
+        self.inner
+            .debug_device_unlock(interface, default_ap, permissions)
+    }
+
+    fn recover_support_start(&self, interface: &mut dyn ArmProbe) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("RecoverSupportStart"));
+        self.inner.recover_support_start(interface)
+    }
+
+    fn debug_core_stop(
+        &self,
+        interface: &mut dyn ArmProbe,
+        core_type: CoreType,
+    ) -> Result<(), ArmError> {
+        trace(|| SequenceOperation::Step("DebugCoreStop"));
+        self.inner.debug_core_stop(interface, core_type)
+    }
+
+    fn debug_erase_sequence(&self) -> Option<Arc<dyn DebugEraseSequence>> {
+        self.inner.debug_erase_sequence()
+    }
+
+    fn lifecycle_sequence(&self) -> Option<Arc<dyn LifecycleSequence>> {
+        self.inner.lifecycle_sequence()
+    }
+
+    fn boot_mode_pins(&self) -> Option<BootModePins> {
+        self.inner.boot_mode_pins()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{trace, SequenceOperation, SequenceTrace, TracingSequence};
+    use crate::config::{get_target_by_name, DebugSequence};
+    use crate::{FakeProbe, Permissions};
+
+    #[test]
+    fn recordings_are_nested() {
+        trace(|| unreachable!("nothing is recorded outside of a recording"));
+
+        let ((), outer) = SequenceTrace::record(|| {
+            trace(|| SequenceOperation::Step("DebugPortStart"));
+            let ((), inner) = SequenceTrace::record(|| {
+                assert!(SequenceTrace::is_recording());
+                trace(|| SequenceOperation::Step("DebugCoreStart"));
+            });
+            assert_eq!(inner.operations.len(), 1);
+            trace(|| SequenceOperation::MemoryWrite {
+                address: 0xE000_EDF0,
+                width: 32,
+                values: vec![0xA05F_0001],
+            });
+        });
+
+        assert!(!SequenceTrace::is_recording());
+        assert_eq!(
+            outer.to_string(),
+            "DebugPortStart\n  write32 0xe000edf0 = [\n    0xa05f0001,\n]\n"
+        );
+    }
+
+    #[test]
+    fn attach_is_recorded_with_fake_probe() {
+        let mut target = get_target_by_name("nrf51822_xxAC").unwrap();
+        let DebugSequence::Arm(sequence) = target.debug_sequence else {
+            panic!("nrf51822 has an ARM debug sequence");
+        };
+        target.debug_sequence = DebugSequence::Arm(TracingSequence::wrap(sequence));
+
+        let (session, trace) = SequenceTrace::record(|| {
+            FakeProbe::new()
+                .into_probe()
+                .attach(target, Permissions::default())
+        });
+        session.unwrap();
+
+        let steps: Vec<_> = trace
+            .operations
+            .iter()
+            .filter_map(|operation| match operation {
+                SequenceOperation::Step(name) => Some(*name),
+                _ => None,
+            })
+            .collect();
+        assert!(steps.contains(&"DebugCoreStart"), "{steps:?}");
+
+        // DebugCoreStart enables halting debug in DHCSR.
+        assert!(trace.operations.iter().any(|operation| matches!(
+            operation,
+            SequenceOperation::MemoryWrite { address: 0xE000_EDF0, values, .. }
+                if values.first().is_some_and(|value| value & 0xFFFF_0001 == 0xA05F_0001)
+        )));
+    }
+}
@@ -0,0 +1,58 @@
+//! Counters of the debug port and access port accesses of a session.
+
+use serde::Serialize;
+
+/// Counters of the DAP accesses done through an [`ArmProbeInterface`](super::ArmProbeInterface).
+///
+/// The counters can be used to quantify how many transactions an operation needs, for example
+/// to compare probe firmware versions or to detect performance regressions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DapStatistics {
+    /// The number of DP register reads and writes, including writes to SELECT.
+    pub dp_transactions: u64,
+    /// The number of single AP register reads and writes.
+    pub ap_transactions: u64,
+    /// The number of repeated AP register reads and writes, which are used for block transfers
+    /// of memory.
+    pub block_transfers: u64,
+    /// The number of bytes read from or written to AP registers.
+    pub bytes_transferred: u64,
+    /// The number of accesses for which the AP and bank were already selected, so no write to
+    /// SELECT was needed.
+    pub select_cache_hits: u64,
+    /// The number of accesses which changed the AP or bank selection.
+    pub select_cache_misses: u64,
+    /// The number of DP and AP accesses which failed.
+    pub failed_transactions: u64,
+    /// The number of times the SWD connection was recovered, see
+    /// [`ArmProbeInterface::recover_swd`](super::ArmProbeInterface::recover_swd).
+    pub swd_recoveries: u64,
+}
+
+impl DapStatistics {
+    /// Count an access of `words` AP registers.
+    pub(crate) fn record_ap_transaction(&mut self, words: usize) {
+        if words == 1 {
+            self.ap_transactions += 1;
+        } else {
+            self.block_transfers += 1;
+        }
+        self.bytes_transferred += 4 * words as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DapStatistics;
+
+    #[test]
+    fn block_transfers_are_counted_separately() {
+        let mut statistics = DapStatistics::default();
+        statistics.record_ap_transaction(1);
+        statistics.record_ap_transaction(256);
+
+        assert_eq!(statistics.ap_transactions, 1);
+        assert_eq!(statistics.block_transfers, 1);
+        assert_eq!(statistics.bytes_transferred, 4 + 1024);
+    }
+}
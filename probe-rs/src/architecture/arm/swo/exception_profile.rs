@@ -0,0 +1,326 @@
+//! Interrupt load statistics from exception trace packets.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// What the processor did with an exception, as reported by an exception trace packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ExceptionAction {
+    /// The exception handler was entered.
+    Entered,
+    /// The exception handler was exited.
+    Exited,
+    /// The processor returned to the exception, after a handler which preempted it.
+    Returned,
+}
+
+/// A decoded exception trace packet, with the time at which it was generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionEvent {
+    /// The time of the event, relative to the start of the trace.
+    pub time: Duration,
+    /// The exception number, 0 for thread mode, 16 and above for external interrupts.
+    pub exception: u16,
+    /// What happened to the exception.
+    pub action: ExceptionAction,
+}
+
+/// The statistics of one exception over the profiling window, see [`ExceptionProfiler`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExceptionStatistics {
+    /// The exception number.
+    pub exception: u16,
+    /// How often the handler ran to completion.
+    pub count: u64,
+    /// The time spent in the handler, over all runs.
+    pub total: Duration,
+    /// The shortest run of the handler.
+    pub min: Duration,
+    /// The longest run of the handler.
+    pub max: Duration,
+    /// The fraction of the window spent in the handler, from 0 to 1.
+    pub load: f64,
+}
+
+impl ExceptionStatistics {
+    /// The name of the exception, for example `SysTick` or `IRQ 5`.
+    pub fn name(&self) -> String {
+        exception_name(self.exception)
+    }
+}
+
+/// The interrupt load of a trace capture, see [`ExceptionProfiler::profile`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExceptionProfile {
+    /// The time between the first and the last event.
+    pub window: Duration,
+    /// The statistics of the exceptions which occurred, ordered by exception number.
+    pub exceptions: Vec<ExceptionStatistics>,
+    /// The number of events which did not match the exceptions known to be active, which
+    /// happens when trace packets are lost. The runs they belong to are not counted.
+    pub unmatched_events: u64,
+}
+
+impl ExceptionProfile {
+    /// The fraction of the window spent in exception handlers, from 0 to 1.
+    pub fn total_load(&self) -> f64 {
+        self.exceptions.iter().map(|exception| exception.load).sum()
+    }
+}
+
+/// An exception whose handler is running or was preempted.
+#[derive(Debug)]
+struct ActiveException {
+    exception: u16,
+    /// The handler time before it was preempted.
+    elapsed: Duration,
+    /// The time since which the handler runs, or `None` while it is preempted.
+    running_since: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct Runs {
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+/// Collects per-exception statistics from a stream of exception trace events.
+///
+/// The duration of a handler run is the time the handler itself executed, without the time of
+/// higher priority handlers which preempted it. So the loads of all exceptions add up to the
+/// time the processor spent outside of thread mode.
+///
+/// Handlers which were already running when the trace started, or which are still running
+/// when it ends, are not counted.
+#[derive(Debug, Default)]
+pub struct ExceptionProfiler {
+    /// The active exceptions, the running one last.
+    active: Vec<ActiveException>,
+    runs: BTreeMap<u16, Runs>,
+    first_event: Option<Duration>,
+    last_event: Duration,
+    unmatched_events: u64,
+}
+
+impl ExceptionProfiler {
+    /// Create a profiler without events.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next event of the trace.
+    pub fn record(&mut self, event: ExceptionEvent) {
+        let time = event.time;
+        self.first_event.get_or_insert(time);
+        self.last_event = self.last_event.max(time);
+
+        match event.action {
+            ExceptionAction::Entered => {
+                if let Some(preempted) = self.active.last_mut() {
+                    preempted.pause(time);
+                }
+                self.active.push(ActiveException {
+                    exception: event.exception,
+                    elapsed: Duration::ZERO,
+                    running_since: Some(time),
+                });
+            }
+            ExceptionAction::Exited => {
+                let Some(position) = self
+                    .active
+                    .iter()
+                    .rposition(|active| active.exception == event.exception)
+                else {
+                    self.unmatched_events += 1;
+                    return;
+                };
+
+                // Exceptions above it missed their exit packets.
+                self.unmatched_events += (self.active.len() - position - 1) as u64;
+                self.active.truncate(position + 1);
+
+                if let Some(mut exited) = self.active.pop() {
+                    exited.pause(time);
+                    self.add_run(exited.exception, exited.elapsed);
+                }
+                if let Some(resumed) = self.active.last_mut() {
+                    resumed.running_since = Some(time);
+                }
+            }
+            ExceptionAction::Returned => {
+                let expected = self.active.last().map_or(0, |active| active.exception);
+                if event.exception != expected {
+                    self.unmatched_events += 1;
+                }
+            }
+        }
+    }
+
+    /// Add all `events`, see [`ExceptionProfiler::record`].
+    pub fn record_all(&mut self, events: impl IntoIterator<Item = ExceptionEvent>) {
+        for event in events {
+            self.record(event);
+        }
+    }
+
+    /// The statistics of the events recorded so far.
+    pub fn profile(&self) -> ExceptionProfile {
+        let window = self
+            .first_event
+            .map_or(Duration::ZERO, |first| self.last_event - first);
+
+        let exceptions = self
+            .runs
+            .iter()
+            .map(|(&exception, runs)| ExceptionStatistics {
+                exception,
+                count: runs.count,
+                total: runs.total,
+                min: runs.min,
+                max: runs.max,
+                load: if window.is_zero() {
+                    0.0
+                } else {
+                    runs.total.as_secs_f64() / window.as_secs_f64()
+                },
+            })
+            .collect();
+
+        ExceptionProfile {
+            window,
+            exceptions,
+            unmatched_events: self.unmatched_events,
+        }
+    }
+
+    fn add_run(&mut self, exception: u16, duration: Duration) {
+        let runs = self.runs.entry(exception).or_default();
+
+        runs.min = if runs.count == 0 {
+            duration
+        } else {
+            runs.min.min(duration)
+        };
+        runs.max = runs.max.max(duration);
+        runs.total += duration;
+        runs.count += 1;
+    }
+}
+
+impl ActiveException {
+    fn pause(&mut self, time: Duration) {
+        if let Some(since) = self.running_since.take() {
+            self.elapsed += time.saturating_sub(since);
+        }
+    }
+}
+
+/// The name of an ARMv7-M exception number.
+fn exception_name(exception: u16) -> String {
+    match exception {
+        0 => "Thread".to_owned(),
+        1 => "Reset".to_owned(),
+        2 => "NMI".to_owned(),
+        3 => "HardFault".to_owned(),
+        4 => "MemManage".to_owned(),
+        5 => "BusFault".to_owned(),
+        6 => "UsageFault".to_owned(),
+        7 => "SecureFault".to_owned(),
+        11 => "SVCall".to_owned(),
+        12 => "DebugMonitor".to_owned(),
+        14 => "PendSV".to_owned(),
+        15 => "SysTick".to_owned(),
+        16.. => format!("IRQ {}", exception - 16),
+        _ => format!("Exception {exception}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExceptionAction, ExceptionEvent, ExceptionProfiler};
+    use std::time::Duration;
+
+    fn event(micros: u64, exception: u16, action: ExceptionAction) -> ExceptionEvent {
+        ExceptionEvent {
+            time: Duration::from_micros(micros),
+            exception,
+            action,
+        }
+    }
+
+    #[test]
+    fn nested_exceptions() {
+        use ExceptionAction::*;
+
+        let mut profiler = ExceptionProfiler::new();
+        profiler.record_all([
+            event(0, 15, Entered),
+            event(10, 15, Exited),
+            event(10, 0, Returned),
+            // IRQ 3 is preempted by IRQ 1 for 20 us.
+            event(100, 19, Entered),
+            event(110, 17, Entered),
+            event(130, 17, Exited),
+            event(130, 19, Returned),
+            event(150, 19, Exited),
+            event(150, 0, Returned),
+            event(200, 15, Entered),
+            event(230, 15, Exited),
+            event(230, 0, Returned),
+            // Still running at the end of the trace.
+            event(600, 19, Entered),
+            event(1000, 0, Returned),
+        ]);
+
+        let profile = profiler.profile();
+        assert_eq!(profile.window, Duration::from_micros(1000));
+        assert_eq!(profile.unmatched_events, 1);
+
+        let summary = profile
+            .exceptions
+            .iter()
+            .map(|e| {
+                (
+                    e.name(),
+                    e.count,
+                    e.total.as_micros(),
+                    e.min.as_micros(),
+                    e.max.as_micros(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("SysTick".to_owned(), 2, 40, 10, 30),
+                ("IRQ 1".to_owned(), 1, 20, 20, 20),
+                ("IRQ 3".to_owned(), 1, 30, 30, 30),
+            ]
+        );
+
+        assert!((profile.total_load() - 0.09).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lost_exit_packets() {
+        use ExceptionAction::*;
+
+        let mut profiler = ExceptionProfiler::new();
+        profiler.record_all([
+            event(0, 20, Entered),
+            // The exit of IRQ 5 was lost.
+            event(5, 21, Entered),
+            event(10, 20, Exited),
+            event(20, 22, Exited),
+        ]);
+
+        let profile = profiler.profile();
+        assert_eq!(profile.unmatched_events, 2);
+        assert_eq!(profile.exceptions.len(), 1);
+        assert_eq!(profile.exceptions[0].total, Duration::from_micros(5));
+    }
+}
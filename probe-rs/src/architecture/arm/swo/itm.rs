@@ -0,0 +1,460 @@
+//! Decoding of the ITM and DWT packets in SWO data.
+//!
+//! The packet format is described in appendix D4 (Debug ITM and DWT Packet Protocol) of the
+//! ARMv7-M Architecture Reference Manual. ARMv8-M uses the same format.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::{ExceptionAction, SwoAccess};
+use crate::architecture::arm::ArmError;
+
+/// The relation of a local timestamp to the packet it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampRelation {
+    /// The timestamp is synchronous to the packet.
+    Sync,
+    /// The timestamp is delayed relative to the packet.
+    Delayed,
+    /// The packet is delayed relative to the timestamp.
+    PacketDelayed,
+    /// Both the packet and the timestamp are delayed.
+    BothDelayed,
+}
+
+/// The kind of a data access reported by a DWT data trace packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataAccess {
+    /// A read of the watched address.
+    Read,
+    /// A write to the watched address.
+    Write,
+}
+
+/// A decoded ITM or DWT packet, see [`ItmDecoder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItmPacket {
+    /// A synchronization packet.
+    Sync,
+    /// The ITM or DWT dropped packets because its buffer was full.
+    Overflow,
+    /// Data written to a stimulus port of the ITM by the software of the target.
+    Instrumentation {
+        /// The stimulus port, from 0 to 255.
+        port: u8,
+        /// The written data, 1, 2 or 4 bytes.
+        payload: Vec<u8>,
+    },
+    /// The number of timestamp clock cycles since the previous local timestamp.
+    LocalTimestamp {
+        /// The cycles since the previous local timestamp.
+        delta: u32,
+        /// The relation to the packet the timestamp belongs to.
+        relation: TimestampRelation,
+    },
+    /// The lower bits of the global timestamp, up to bit 25.
+    GlobalTimestampLow {
+        /// The timestamp bits.
+        value: u32,
+        /// The upper bits of the timestamp changed since the last upper bits packet.
+        wrapped: bool,
+        /// The clock of the timestamp changed.
+        clock_changed: bool,
+    },
+    /// The upper bits of the global timestamp, starting at bit 26.
+    GlobalTimestampHigh {
+        /// The timestamp bits, shifted down by 26.
+        value: u32,
+    },
+    /// A DWT event counter wrapped around.
+    EventCounter {
+        /// The counters which wrapped, as in the payload of the packet: bit 0 is CPICNT,
+        /// then EXCCNT, SLEEPCNT, LSUCNT, FOLDCNT and POSTCNT.
+        counters: u8,
+    },
+    /// The processor entered, exited or returned to an exception.
+    ExceptionTrace {
+        /// The exception number.
+        exception: u16,
+        /// What happened to the exception.
+        action: ExceptionAction,
+    },
+    /// A periodic sample of the program counter.
+    PcSample {
+        /// The program counter, or `None` if the processor was sleeping.
+        pc: Option<u32>,
+    },
+    /// The program counter of an access which matched a DWT comparator.
+    DataTracePc {
+        /// The index of the comparator.
+        comparator: u8,
+        /// The program counter of the access.
+        pc: u32,
+    },
+    /// The lower 16 bits of the address of an access which matched a DWT comparator.
+    DataTraceAddress {
+        /// The index of the comparator.
+        comparator: u8,
+        /// The lower bits of the address.
+        address: u16,
+    },
+    /// The data of an access which matched a DWT comparator.
+    DataTraceValue {
+        /// The index of the comparator.
+        comparator: u8,
+        /// Whether the data was read or written.
+        access: DataAccess,
+        /// The data, 1, 2 or 4 bytes.
+        value: Vec<u8>,
+    },
+    /// A packet with a reserved header, or an unknown DWT packet.
+    Unknown {
+        /// The bytes of the packet.
+        bytes: Vec<u8>,
+    },
+}
+
+/// Decodes ITM and DWT packets from the raw bytes of an SWO capture.
+///
+/// Bytes can be added in chunks of any size, a packet which is split across calls is decoded
+/// once all its bytes were added. The stream is expected to start at a packet boundary, which is
+/// the case when the ITM is enabled after SWO capture started.
+///
+/// ```
+/// use probe_rs::architecture::arm::swo::{ItmDecoder, ItmPacket};
+///
+/// let mut decoder = ItmDecoder::new();
+/// // The second byte of the packet is missing.
+/// decoder.push(&[0x0a, b'h']);
+/// assert_eq!(decoder.packets().next(), None);
+///
+/// decoder.push(&[b'i']);
+/// assert_eq!(
+///     decoder.packets().collect::<Vec<_>>(),
+///     [ItmPacket::Instrumentation { port: 1, payload: b"hi".to_vec() }]
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct ItmDecoder {
+    buffer: VecDeque<u8>,
+    /// The number of zero bytes before the current byte, to find synchronization packets.
+    zeros: usize,
+    /// The stimulus port page of the following instrumentation packets.
+    page: u8,
+    /// The sum of all local timestamps.
+    local_time: u64,
+}
+
+impl ItmDecoder {
+    /// The maximum size of a packet.
+    const MAX_PACKET_LEN: usize = 5;
+
+    /// Create a decoder without data.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add raw SWO bytes.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend(bytes);
+    }
+
+    /// The packets which were completely added, in order.
+    pub fn packets(&mut self) -> ItmPackets<'_> {
+        ItmPackets { decoder: self }
+    }
+
+    /// Add `bytes`, and pass all packets which are complete to `callback`.
+    pub fn decode(&mut self, bytes: &[u8], mut callback: impl FnMut(ItmPacket)) {
+        self.push(bytes);
+        for packet in self.packets() {
+            callback(packet);
+        }
+    }
+
+    /// Read the SWO data which arrives in `timeout`, and decode it.
+    pub fn read(
+        &mut self,
+        swo: &mut (impl SwoAccess + ?Sized),
+        timeout: Duration,
+    ) -> Result<Vec<ItmPacket>, ArmError> {
+        let bytes = swo.read_swo_timeout(timeout)?;
+        self.push(&bytes);
+        Ok(self.packets().collect())
+    }
+
+    /// The sum of all local timestamps decoded so far, in timestamp clock cycles.
+    pub fn local_time(&self) -> u64 {
+        self.local_time
+    }
+
+    fn next_packet(&mut self) -> Option<ItmPacket> {
+        loop {
+            let header = *self.buffer.front()?;
+
+            if header == 0 {
+                self.buffer.pop_front();
+                self.zeros += 1;
+                continue;
+            }
+
+            let zeros = std::mem::take(&mut self.zeros);
+            if header == 0x80 {
+                self.buffer.pop_front();
+                return Some(if zeros >= 5 {
+                    ItmPacket::Sync
+                } else {
+                    ItmPacket::Unknown {
+                        bytes: vec![header],
+                    }
+                });
+            }
+
+            let len = match packet_len(&self.buffer) {
+                Some(len) => len,
+                None if self.buffer.len() < Self::MAX_PACKET_LEN => {
+                    // Wait for the rest of the packet.
+                    self.zeros = zeros;
+                    return None;
+                }
+                // A continuation bit is set in all bytes.
+                None => Self::MAX_PACKET_LEN,
+            };
+            if self.buffer.len() < len {
+                self.zeros = zeros;
+                return None;
+            }
+
+            let bytes: Vec<u8> = self.buffer.drain(..len).collect();
+            if let Some(packet) = self.parse(bytes) {
+                return Some(packet);
+            }
+        }
+    }
+
+    /// Parse a complete packet, `None` for packets which only change the decoder state.
+    fn parse(&mut self, bytes: Vec<u8>) -> Option<ItmPacket> {
+        let header = bytes[0];
+
+        let packet = match header {
+            0x70 => ItmPacket::Overflow,
+            // Source packets
+            _ if header & 0b11 != 0 => {
+                let address = header >> 3;
+                let payload = bytes[1..].to_vec();
+
+                if header & 0b100 == 0 {
+                    ItmPacket::Instrumentation {
+                        port: self.page.wrapping_mul(32).wrapping_add(address),
+                        payload,
+                    }
+                } else {
+                    parse_hardware_packet(address, bytes)
+                }
+            }
+            // Extension packet with the stimulus port page
+            _ if header & 0b1111 == 0b1000 => {
+                if header & 0b100 == 0 && bytes.len() == 1 {
+                    self.page = (header >> 4) & 0b111;
+                    return None;
+                }
+                ItmPacket::Unknown { bytes }
+            }
+            // Local timestamp with a continuation byte
+            _ if header & 0b1100_1111 == 0b1100_0000 => {
+                let delta = continuation_value(&bytes[1..]);
+                self.local_time += u64::from(delta);
+
+                ItmPacket::LocalTimestamp {
+                    delta,
+                    relation: match (header >> 4) & 0b11 {
+                        0b00 => TimestampRelation::Sync,
+                        0b01 => TimestampRelation::Delayed,
+                        0b10 => TimestampRelation::PacketDelayed,
+                        _ => TimestampRelation::BothDelayed,
+                    },
+                }
+            }
+            // Local timestamp in the header
+            _ if header & 0b1000_1111 == 0 => {
+                let delta = u32::from(header >> 4);
+                self.local_time += u64::from(delta);
+
+                ItmPacket::LocalTimestamp {
+                    delta,
+                    relation: TimestampRelation::Sync,
+                }
+            }
+            0x94 => {
+                let value = continuation_value(&bytes[1..]);
+                // The flags are in the last byte, if it is the fourth payload byte.
+                let flags = if bytes.len() == 5 { bytes[4] } else { 0 };
+
+                ItmPacket::GlobalTimestampLow {
+                    value: value & 0x03FF_FFFF,
+                    wrapped: flags & 0b100_0000 != 0,
+                    clock_changed: flags & 0b10_0000 != 0,
+                }
+            }
+            0xB4 => ItmPacket::GlobalTimestampHigh {
+                value: continuation_value(&bytes[1..]),
+            },
+            _ => ItmPacket::Unknown { bytes },
+        };
+
+        Some(packet)
+    }
+}
+
+/// An iterator over the complete packets of an [`ItmDecoder`].
+pub struct ItmPackets<'decoder> {
+    decoder: &'decoder mut ItmDecoder,
+}
+
+impl Iterator for ItmPackets<'_> {
+    type Item = ItmPacket;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decoder.next_packet()
+    }
+}
+
+/// The length of the packet at the start of `buffer`, or `None` if it is not complete.
+fn packet_len(buffer: &VecDeque<u8>) -> Option<usize> {
+    let header = buffer[0];
+
+    match header & 0b11 {
+        0b01 => Some(2),
+        0b10 => Some(3),
+        0b11 => Some(5),
+        // Protocol packets with continuation bytes, which end at the first byte without the
+        // continuation bit.
+        _ if header & 0x80 != 0 => buffer
+            .iter()
+            .skip(1)
+            .take(ItmDecoder::MAX_PACKET_LEN - 1)
+            .position(|byte| byte & 0x80 == 0)
+            .map(|position| position + 2),
+        _ => Some(1),
+    }
+}
+
+/// The value of the 7 bit groups of continuation bytes, least significant first.
+fn continuation_value(bytes: &[u8]) -> u32 {
+    bytes.iter().enumerate().fold(0, |value, (i, byte)| {
+        value | u32::from(byte & 0x7F) << (7 * i)
+    })
+}
+
+/// Parse a DWT packet with the discriminator `id`.
+fn parse_hardware_packet(id: u8, bytes: Vec<u8>) -> ItmPacket {
+    let payload = &bytes[1..];
+    let value = payload
+        .iter()
+        .rev()
+        .fold(0u32, |value, byte| value << 8 | u32::from(*byte));
+
+    match (id, payload.len()) {
+        (0, 1) => ItmPacket::EventCounter {
+            counters: payload[0],
+        },
+        (1, 2) => {
+            let action = match (payload[1] >> 4) & 0b11 {
+                0b01 => ExceptionAction::Entered,
+                0b10 => ExceptionAction::Exited,
+                0b11 => ExceptionAction::Returned,
+                _ => return ItmPacket::Unknown { bytes },
+            };
+
+            ItmPacket::ExceptionTrace {
+                exception: (value & 0x1FF) as u16,
+                action,
+            }
+        }
+        (2, 4) => ItmPacket::PcSample { pc: Some(value) },
+        (2, 1) => ItmPacket::PcSample { pc: None },
+        (8..=15, _) if id & 1 == 0 && payload.len() == 4 => ItmPacket::DataTracePc {
+            comparator: (id >> 1) & 0b11,
+            pc: value,
+        },
+        (8..=15, 2) => ItmPacket::DataTraceAddress {
+            comparator: (id >> 1) & 0b11,
+            address: value as u16,
+        },
+        (16..=23, _) => ItmPacket::DataTraceValue {
+            comparator: (id >> 1) & 0b11,
+            access: if id & 1 == 0 {
+                DataAccess::Read
+            } else {
+                DataAccess::Write
+            },
+            value: payload.to_vec(),
+        },
+        _ => ItmPacket::Unknown { bytes },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataAccess, ItmDecoder, ItmPacket, TimestampRelation};
+    use crate::architecture::arm::swo::ExceptionAction;
+
+    #[test]
+    fn decode_packet_stream() {
+        let mut decoder = ItmDecoder::new();
+        let mut packets = Vec::new();
+
+        // Synchronization, a 4 byte write to stimulus port 3, and an exception entry.
+        decoder.decode(
+            &[0, 0, 0, 0, 0, 0x80, 0x1b, 1, 2, 3, 4, 0x0e, 0x1f, 0x10],
+            |packet| packets.push(packet),
+        );
+        // A local timestamp split across chunks, and a PC sample.
+        decoder.decode(&[0xc0, 0x81], |packet| packets.push(packet));
+        decoder.decode(&[0x01, 0x17, 0x00, 0x10, 0x00, 0x08], |packet| {
+            packets.push(packet)
+        });
+        // Stimulus port page 1, overflow, single byte timestamp, and a data write.
+        decoder.decode(
+            &[0x18, 0x09, b'x', 0x70, 0x30, 0x8e, 0x34, 0x12],
+            |packet| packets.push(packet),
+        );
+
+        assert_eq!(
+            packets,
+            [
+                ItmPacket::Sync,
+                ItmPacket::Instrumentation {
+                    port: 3,
+                    payload: vec![1, 2, 3, 4]
+                },
+                ItmPacket::ExceptionTrace {
+                    exception: 31,
+                    action: ExceptionAction::Entered
+                },
+                ItmPacket::LocalTimestamp {
+                    delta: 129,
+                    relation: TimestampRelation::Sync
+                },
+                ItmPacket::PcSample {
+                    pc: Some(0x0800_1000)
+                },
+                ItmPacket::Instrumentation {
+                    port: 33,
+                    payload: vec![b'x']
+                },
+                ItmPacket::Overflow,
+                ItmPacket::LocalTimestamp {
+                    delta: 3,
+                    relation: TimestampRelation::Sync
+                },
+                ItmPacket::DataTraceValue {
+                    comparator: 0,
+                    access: DataAccess::Write,
+                    value: vec![0x34, 0x12]
+                },
+            ]
+        );
+        assert_eq!(decoder.local_time(), 132);
+    }
+}
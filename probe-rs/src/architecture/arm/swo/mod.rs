@@ -1,9 +1,20 @@
 //! SWO tracing related functions.
 
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
 use crate::architecture::arm::communication_interface::ArmProbeInterface;
 
 use super::ArmError;
 
+mod exception_profile;
+mod itm;
+
+pub use self::itm::{DataAccess, ItmDecoder, ItmPacket, ItmPackets, TimestampRelation};
+pub use exception_profile::{
+    ExceptionAction, ExceptionEvent, ExceptionProfile, ExceptionProfiler, ExceptionStatistics,
+};
+
 /// The protocol the SWO pin should use for data transmission.
 #[derive(Debug, Copy, Clone)]
 pub enum SwoMode {
@@ -105,6 +116,37 @@ impl SwoConfig {
     }
 }
 
+/// The time at which a chunk of SWO data was received.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SwoTimestamp {
+    /// The host time at which the probe returned the data.
+    Host(Instant),
+    /// The time since SWO was enabled at which the probe captured the first byte of the data,
+    /// reported by CMSIS-DAP probes with a test domain timer.
+    Probe(Duration),
+}
+
+/// A chunk of SWO data received at the same time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwoChunk {
+    /// The received bytes.
+    pub data: Vec<u8>,
+    /// The time at which the chunk was received.
+    pub timestamp: SwoTimestamp,
+    /// Set if the probe discarded data received before this chunk, because its buffer
+    /// overflowed.
+    pub overflow: bool,
+}
+
+/// Statistics of the SWO data read by a [`SwoReader`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SwoStatistics {
+    /// The number of bytes returned by the reader.
+    pub bytes: u64,
+    /// The number of times the probe reported that its buffer overflowed, so data was lost.
+    pub probe_overflows: u64,
+}
+
 /// An interface to operate SWO to be implemented on drivers that support SWO.
 pub trait SwoAccess {
     /// Configure a SwoAccess interface for reading SWO data.
@@ -127,6 +169,31 @@ pub trait SwoAccess {
     /// May return earlier than `timeout` if the receive buffer fills up.
     fn read_swo_timeout(&mut self, timeout: std::time::Duration) -> Result<Vec<u8>, ArmError>;
 
+    /// Read SWO data for up to `timeout` duration, in chunks with the time they were received.
+    ///
+    /// A probe should implement this if it provides timestamps for the received data, or
+    /// receives it in multiple chunks during `timeout`, so that trace events can be placed
+    /// more precisely than at the end of the read.
+    ///
+    /// The default implementation returns the data of [`SwoAccess::read_swo_timeout`] as one
+    /// chunk, with the host time at which it returned. No chunks are returned if no data was
+    /// received. Probes which report overflows of their buffer should implement this, and
+    /// return a chunk with [`SwoChunk::overflow`] set, also if it contains no data.
+    fn read_swo_chunks(&mut self, timeout: std::time::Duration) -> Result<Vec<SwoChunk>, ArmError> {
+        let data = self.read_swo_timeout(timeout)?;
+        let timestamp = SwoTimestamp::Host(Instant::now());
+
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(vec![SwoChunk {
+            data,
+            timestamp,
+            overflow: false,
+        }])
+    }
+
     /// Request an estimated best time to wait between polls of `read_swo`.
     ///
     /// A probe can implement this if it can work out a sensible time to
@@ -168,45 +235,165 @@ pub(crate) fn poll_interval_from_buf_size(
 }
 
 /// A reader interface to pull SWO data from the underlying driver.
+///
+/// The time at which the last returned byte was received is available
+/// through [`SwoReader::timestamp`].
+///
+/// Data lost because the buffer of the probe overflowed is reported by
+/// [`SwoReader::data_lost`], so consumers know where the trace has gaps. A read never returns
+/// data from both sides of a gap. Overflows of the ITM of the target are reported in the trace
+/// itself, as overflow packets.
 pub struct SwoReader<'a> {
     interface: &'a mut dyn ArmProbeInterface,
-    buf: Vec<u8>,
+    chunks: VecDeque<SwoChunk>,
+    timestamp: Option<SwoTimestamp>,
+    data_lost: bool,
+    statistics: SwoStatistics,
 }
 
 impl<'a> SwoReader<'a> {
     pub(crate) fn new(interface: &'a mut dyn ArmProbeInterface) -> Self {
         Self {
             interface,
-            buf: Vec::new(),
+            chunks: VecDeque::new(),
+            timestamp: None,
+            data_lost: false,
+            statistics: SwoStatistics::default(),
         }
     }
+
+    /// Whether data was lost right before the data returned by the last call of
+    /// [`std::io::Read::read`], because the buffer of the probe overflowed.
+    pub fn data_lost(&self) -> bool {
+        self.data_lost
+    }
+
+    /// The statistics of the data read so far.
+    pub fn statistics(&self) -> SwoStatistics {
+        self.statistics
+    }
+
+    /// The time at which the chunk containing the last byte returned by
+    /// [`std::io::Read::read`] was received, or `None` if no data was read yet.
+    pub fn timestamp(&self) -> Option<SwoTimestamp> {
+        self.timestamp
+    }
 }
 
 impl<'a> std::io::Read for SwoReader<'a> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        use core::cmp;
-        use std::{
-            io::{Error, ErrorKind},
-            mem,
-        };
+        use std::io::{Error, ErrorKind};
 
         // Always buffer: this pulls data as quickly as possible from
         // the target to clear it's embedded trace buffer, minimizing
         // the chance of an overflow event during which packets are
         // lost.
-        self.buf.append(
-            &mut self
-                .interface
-                .read_swo()
-                .map_err(|e| Error::new(ErrorKind::Other, e))?,
-        );
+        let chunks = self
+            .interface
+            .read_swo_chunks(Duration::from_millis(10))
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+        self.statistics.probe_overflows +=
+            chunks.iter().filter(|chunk| chunk.overflow).count() as u64;
+        self.chunks.extend(chunks);
+
+        let (len, data_lost, timestamp) = read_chunks(&mut self.chunks, buf);
+        self.data_lost = data_lost;
+        self.timestamp = timestamp.or(self.timestamp);
+
+        self.statistics.bytes += len as u64;
+
+        Ok(len)
+    }
+}
 
-        let swo = {
-            let next_buf = self.buf.split_off(cmp::min(self.buf.len(), buf.len()));
-            mem::replace(&mut self.buf, next_buf)
+/// Move the data of the `chunks` into `buf`, up to the first overflow after the start.
+///
+/// Returns the number of bytes read, whether data was lost before them, and the time of the
+/// chunk of the last byte.
+fn read_chunks(
+    chunks: &mut VecDeque<SwoChunk>,
+    buf: &mut [u8],
+) -> (usize, bool, Option<SwoTimestamp>) {
+    let mut len = 0;
+    let mut data_lost = false;
+    let mut timestamp = None;
+
+    while len < buf.len() {
+        let Some(chunk) = chunks.front_mut() else {
+            break;
         };
 
-        buf[..swo.len()].copy_from_slice(&swo);
-        Ok(swo.len())
+        if chunk.overflow {
+            if len > 0 {
+                // Return the data before the gap first.
+                break;
+            }
+
+            data_lost = true;
+            chunk.overflow = false;
+        }
+
+        let n = chunk.data.len().min(buf.len() - len);
+        buf[len..len + n].copy_from_slice(&chunk.data[..n]);
+        chunk.data.drain(..n);
+        len += n;
+        timestamp = Some(chunk.timestamp);
+
+        if chunk.data.is_empty() {
+            chunks.pop_front();
+        }
+    }
+
+    (len, data_lost, timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_chunks, SwoChunk, SwoTimestamp};
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    fn chunk(data: &[u8], overflow: bool) -> SwoChunk {
+        SwoChunk {
+            data: data.to_vec(),
+            timestamp: SwoTimestamp::Probe(Duration::from_millis(data.len() as u64)),
+            overflow,
+        }
+    }
+
+    #[test]
+    fn reads_stop_at_overflows() {
+        let mut chunks = VecDeque::from([
+            chunk(&[1, 2], false),
+            chunk(&[3], false),
+            chunk(&[4, 5, 6], true),
+            chunk(&[], true),
+            chunk(&[7], false),
+        ]);
+        let mut buf = [0; 8];
+
+        assert_eq!(
+            read_chunks(&mut chunks, &mut buf),
+            (
+                3,
+                false,
+                Some(SwoTimestamp::Probe(Duration::from_millis(1)))
+            )
+        );
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+
+        let (len, data_lost, _) = read_chunks(&mut chunks, &mut buf[..2]);
+        assert_eq!((len, data_lost), (2, true));
+        assert_eq!(&buf[..2], &[4, 5]);
+
+        let (len, data_lost, _) = read_chunks(&mut chunks, &mut buf);
+        assert_eq!((len, data_lost), (1, false));
+        assert_eq!(buf[0], 6);
+
+        // The overflow without data is reported with the data after it.
+        let (len, data_lost, _) = read_chunks(&mut chunks, &mut buf);
+        assert_eq!((len, data_lost), (1, true));
+        assert_eq!(buf[0], 7);
+        assert!(chunks.is_empty());
     }
 }
@@ -0,0 +1,233 @@
+//! Connection problems of a session, with the time at which they happened.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::DapError;
+
+/// The number of events kept by [`ConnectionTelemetry`], older events are only counted.
+const MAX_EVENTS: usize = 256;
+
+/// A problem with the connection to the target, see [`ConnectionTelemetry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ConnectionEventKind {
+    /// A transfer failed with a protocol error, a missing acknowledge or a parity error. The
+    /// transfer is retried by the caller.
+    ProtocolError,
+    /// A transfer failed because the target responded with WAIT for too long.
+    WaitTimeout,
+    /// The SWD connection was initialized again, see
+    /// [`ArmProbeInterface::recover_swd`](super::ArmProbeInterface::recover_swd).
+    Reinitialization,
+    /// The sticky error flags of a debug port were cleared after a failed access.
+    StickyErrorClear,
+}
+
+impl ConnectionEventKind {
+    /// The event for a transfer which failed with `error`, if it is a connection problem.
+    pub(crate) fn from_dap_error(error: &DapError) -> Option<Self> {
+        match error {
+            DapError::SwdProtocol | DapError::NoAcknowledge | DapError::IncorrectParity => {
+                Some(ConnectionEventKind::ProtocolError)
+            }
+            DapError::WaitResponse => Some(ConnectionEventKind::WaitTimeout),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConnectionEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionEventKind::ProtocolError => write!(f, "protocol error"),
+            ConnectionEventKind::WaitTimeout => write!(f, "WAIT timeout"),
+            ConnectionEventKind::Reinitialization => write!(f, "re-initialization"),
+            ConnectionEventKind::StickyErrorClear => write!(f, "sticky error clear"),
+        }
+    }
+}
+
+/// A connection problem, with the time at which it happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ConnectionEvent {
+    /// What happened.
+    pub kind: ConnectionEventKind,
+    /// The time of the event, relative to the start of the telemetry.
+    pub time: Duration,
+}
+
+/// Connection problems of a session, to tell bad wiring from firmware or host issues.
+///
+/// Bad wiring and too high clock speeds show up as protocol errors and re-initializations
+/// spread over the whole session. WAIT timeouts and sticky error clears without protocol errors
+/// point to the target, for example a core in a low power mode, or firmware which disables the
+/// debug logic.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionTelemetry {
+    #[serde(skip)]
+    started: Instant,
+    /// The number of transfers which failed with a protocol error.
+    pub protocol_errors: u64,
+    /// The number of transfers which failed with a WAIT timeout.
+    pub wait_timeouts: u64,
+    /// The number of times the connection was initialized again.
+    pub reinitializations: u64,
+    /// The number of times sticky error flags were cleared.
+    pub sticky_error_clears: u64,
+    /// The most recent events, oldest first. Only the last 256 events are kept.
+    pub events: VecDeque<ConnectionEvent>,
+}
+
+impl Default for ConnectionTelemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectionTelemetry {
+    /// Telemetry without events, which starts now.
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            protocol_errors: 0,
+            wait_timeouts: 0,
+            reinitializations: 0,
+            sticky_error_clears: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// The time since the telemetry was started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns `true` if no connection problems were recorded.
+    pub fn is_clean(&self) -> bool {
+        self.protocol_errors == 0
+            && self.wait_timeouts == 0
+            && self.reinitializations == 0
+            && self.sticky_error_clears == 0
+    }
+
+    /// Record an event which happens now.
+    pub(crate) fn record(&mut self, kind: ConnectionEventKind) {
+        self.record_at(kind, self.started.elapsed());
+    }
+
+    fn record_at(&mut self, kind: ConnectionEventKind, time: Duration) {
+        match kind {
+            ConnectionEventKind::ProtocolError => self.protocol_errors += 1,
+            ConnectionEventKind::WaitTimeout => self.wait_timeouts += 1,
+            ConnectionEventKind::Reinitialization => self.reinitializations += 1,
+            ConnectionEventKind::StickyErrorClear => self.sticky_error_clears += 1,
+        }
+
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(ConnectionEvent { kind, time });
+    }
+
+    /// The events which happened in the last `window`.
+    pub fn events_since(&self, window: Duration) -> impl Iterator<Item = &ConnectionEvent> {
+        let start = self.elapsed().saturating_sub(window);
+        self.events.iter().filter(move |event| event.time >= start)
+    }
+}
+
+impl fmt::Display for ConnectionTelemetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} protocol errors, {} WAIT timeouts, {} re-initializations, {} sticky error clears in {:.1?}",
+            self.protocol_errors,
+            self.wait_timeouts,
+            self.reinitializations,
+            self.sticky_error_clears,
+            self.elapsed()
+        )?;
+
+        if let Some(last) = self.events.back() {
+            write!(f, ", last {} at {:.1?}", last.kind, last.time)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Logs a summary of the telemetry when the interval has passed since the last summary.
+#[derive(Debug)]
+pub(crate) struct TelemetryLog {
+    interval: Duration,
+    last: Instant,
+}
+
+impl TelemetryLog {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Instant::now(),
+        }
+    }
+
+    /// Log a summary of `telemetry` if it is due.
+    pub(crate) fn poll(&mut self, telemetry: &ConnectionTelemetry) {
+        if self.last.elapsed() < self.interval {
+            return;
+        }
+        self.last = Instant::now();
+
+        if telemetry.is_clean() {
+            tracing::info!(
+                "Connection telemetry: no problems in {:.1?}",
+                telemetry.elapsed()
+            );
+        } else {
+            tracing::warn!("Connection telemetry: {}", telemetry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConnectionEventKind, ConnectionTelemetry, MAX_EVENTS};
+    use crate::architecture::arm::DapError;
+    use std::time::Duration;
+
+    #[test]
+    fn events_are_counted_and_bounded() {
+        let mut telemetry = ConnectionTelemetry::new();
+        assert!(telemetry.is_clean());
+
+        for i in 0..MAX_EVENTS as u64 {
+            telemetry.record_at(ConnectionEventKind::ProtocolError, Duration::from_millis(i));
+        }
+        telemetry.record_at(
+            ConnectionEventKind::Reinitialization,
+            Duration::from_secs(1),
+        );
+
+        assert!(!telemetry.is_clean());
+        assert_eq!(telemetry.protocol_errors, MAX_EVENTS as u64);
+        assert_eq!(telemetry.reinitializations, 1);
+        assert_eq!(telemetry.events.len(), MAX_EVENTS);
+        assert_eq!(telemetry.events[0].time, Duration::from_millis(1));
+        assert_eq!(
+            telemetry.events.back().unwrap().kind,
+            ConnectionEventKind::Reinitialization
+        );
+
+        assert_eq!(
+            ConnectionEventKind::from_dap_error(&DapError::IncorrectParity),
+            Some(ConnectionEventKind::ProtocolError)
+        );
+        assert_eq!(
+            ConnectionEventKind::from_dap_error(&DapError::FaultResponse),
+            None
+        );
+    }
+}
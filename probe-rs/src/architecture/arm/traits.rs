@@ -50,6 +50,27 @@ pub struct ApAddress {
     pub ap: u8,
 }
 
+/// A register access in a batch of [`RawDapAccess::raw_batch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawDapOperation {
+    /// Read a register.
+    Read {
+        /// The port of the register.
+        port: PortType,
+        /// The address of the register, only the lowest 4 bits are used.
+        addr: u8,
+    },
+    /// Write a register.
+    Write {
+        /// The port of the register.
+        port: PortType,
+        /// The address of the register, only the lowest 4 bits are used.
+        addr: u8,
+        /// The value to write.
+        value: u32,
+    },
+}
+
 /// Low-level DAP register access.
 ///
 /// Operations on this trait closely match the transactions on the wire. Implementors
@@ -115,6 +136,29 @@ pub trait RawDapAccess {
         Ok(())
     }
 
+    /// Execute the `operations` in order, and return the values of the reads.
+    ///
+    /// Probes which support it send the whole batch in as few packets as possible, instead of
+    /// waiting for the result of every read. Execution stops at the first failed operation.
+    ///
+    /// Bank switching is the caller's responsibility.
+    fn raw_batch(&mut self, operations: &[RawDapOperation]) -> Result<Vec<u32>, ArmError> {
+        let mut values = Vec::new();
+
+        for operation in operations {
+            match *operation {
+                RawDapOperation::Read { port, addr } => {
+                    values.push(self.raw_read_register(port, addr)?)
+                }
+                RawDapOperation::Write { port, addr, value } => {
+                    self.raw_write_register(port, addr, value)?
+                }
+            }
+        }
+
+        Ok(values)
+    }
+
     /// Flush any outstanding writes.
     ///
     /// By default, this does nothing -- but in probes that implement write
@@ -153,6 +197,127 @@ pub trait RawDapAccess {
     fn core_status_notification(&mut self, state: CoreStatus) -> Result<(), DebugProbeError>;
 }
 
+/// A DP or AP register access in a [`DapBatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DapOperation {
+    /// Read a DP register.
+    ReadDp {
+        /// The debug port.
+        dp: DpAddress,
+        /// The address of the register, with the bank number in the highest 4 bits.
+        addr: u8,
+    },
+    /// Write a DP register.
+    WriteDp {
+        /// The debug port.
+        dp: DpAddress,
+        /// The address of the register, with the bank number in the highest 4 bits.
+        addr: u8,
+        /// The value to write.
+        value: u32,
+    },
+    /// Read an AP register.
+    ReadAp {
+        /// The access port.
+        ap: ApAddress,
+        /// The address of the register, with the bank number in the highest 4 bits.
+        addr: u8,
+    },
+    /// Write an AP register.
+    WriteAp {
+        /// The access port.
+        ap: ApAddress,
+        /// The address of the register, with the bank number in the highest 4 bits.
+        addr: u8,
+        /// The value to write.
+        value: u32,
+    },
+}
+
+impl DapOperation {
+    /// The debug port the register belongs to.
+    pub fn dp(&self) -> DpAddress {
+        match self {
+            DapOperation::ReadDp { dp, .. } | DapOperation::WriteDp { dp, .. } => *dp,
+            DapOperation::ReadAp { ap, .. } | DapOperation::WriteAp { ap, .. } => ap.dp,
+        }
+    }
+}
+
+/// DP and AP register accesses, which are queued and then executed together with
+/// [`DapAccess::execute_batch`].
+///
+/// Probes which support it send the whole batch in a few USB packets, instead of waiting for
+/// the result of every read, which saves a round-trip per read.
+///
+/// ```
+/// # use probe_rs::architecture::arm::{ApAddress, DapBatch, DpAddress};
+/// let ap = ApAddress { dp: DpAddress::Default, ap: 0 };
+///
+/// let mut batch = DapBatch::new();
+/// batch.write_ap(ap, 0x04, 0x2000_0000);
+/// let first = batch.read_ap(ap, 0x0C);
+/// batch.write_ap(ap, 0x04, 0x2000_1000);
+/// let second = batch.read_ap(ap, 0x0C);
+///
+/// assert_eq!((first, second), (0, 1));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DapBatch {
+    operations: Vec<DapOperation>,
+    reads: usize,
+}
+
+impl DapBatch {
+    /// Create an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a read of a DP register, and return the index of its value in the results.
+    pub fn read_dp(&mut self, dp: DpAddress, addr: u8) -> usize {
+        self.push_read(DapOperation::ReadDp { dp, addr })
+    }
+
+    /// Queue a write of a DP register.
+    pub fn write_dp(&mut self, dp: DpAddress, addr: u8, value: u32) {
+        self.operations
+            .push(DapOperation::WriteDp { dp, addr, value });
+    }
+
+    /// Queue a read of an AP register, and return the index of its value in the results.
+    pub fn read_ap(&mut self, ap: ApAddress, addr: u8) -> usize {
+        self.push_read(DapOperation::ReadAp { ap, addr })
+    }
+
+    /// Queue a write of an AP register.
+    pub fn write_ap(&mut self, ap: ApAddress, addr: u8, value: u32) {
+        self.operations
+            .push(DapOperation::WriteAp { ap, addr, value });
+    }
+
+    /// The queued operations, in order.
+    pub fn operations(&self) -> &[DapOperation] {
+        &self.operations
+    }
+
+    /// The number of queued reads, which is the number of results.
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+
+    /// Returns `true` if no operations are queued.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    fn push_read(&mut self, operation: DapOperation) -> usize {
+        self.operations.push(operation);
+        self.reads += 1;
+        self.reads - 1
+    }
+}
+
 /// High-level DAP register access.
 ///
 /// Operations on this trait perform logical register reads/writes. Implementations
@@ -238,4 +403,32 @@ pub trait DapAccess {
         }
         Ok(())
     }
+
+    /// Execute the operations of `batch` in order, and return the values of its reads.
+    ///
+    /// Implementations send the batch to the probe with as few round-trips as possible, and
+    /// fall back to executing one operation after the other. Execution stops at the first
+    /// failed operation.
+    fn execute_batch(&mut self, batch: &DapBatch) -> Result<Vec<u32>, ArmError> {
+        let mut values = Vec::with_capacity(batch.reads());
+
+        for operation in batch.operations() {
+            match *operation {
+                DapOperation::ReadDp { dp, addr } => {
+                    values.push(self.read_raw_dp_register(dp, addr)?)
+                }
+                DapOperation::WriteDp { dp, addr, value } => {
+                    self.write_raw_dp_register(dp, addr, value)?
+                }
+                DapOperation::ReadAp { ap, addr } => {
+                    values.push(self.read_raw_ap_register(ap, addr)?)
+                }
+                DapOperation::WriteAp { ap, addr, value } => {
+                    self.write_raw_ap_register(ap, addr, value)?
+                }
+            }
+        }
+
+        Ok(values)
+    }
 }
@@ -63,12 +63,18 @@ pub enum RiscvError {
     /// The given trigger type is not available for the address breakpoint.
     #[error("Unexpected trigger type {0} for address breakpoint.")]
     UnexpectedTriggerType(u32),
+    /// The trigger is already used by a breakpoint or a watchpoint.
+    #[error("Trigger {0} is already used by a breakpoint or watchpoint.")]
+    TriggerInUse(usize),
     /// The connected target is not a RISCV device.
     #[error("Connected target is not a RISCV device.")]
     NoRiscvTarget,
     /// The target does not support halt after reset.
     #[error("The target does not support halt after reset.")]
     ResetHaltRequestNotSupported,
+    /// The hart does not exist in the debug module.
+    #[error("Hart {0} does not exist, the debug module has {1} harts.")]
+    HartUnavailable(u32, u32),
 }
 
 impl From<RiscvError> for ProbeRsError {
@@ -206,6 +212,9 @@ pub struct RiscvCommunicationInterfaceState {
     /// Number of harts
     num_harts: u32,
 
+    /// The hart which is selected in `dmcontrol`
+    selected_hart: u32,
+
     memory_access_info: HashMap<RiscvBusAccess, MemoryAccessMethod>,
 
     /// describes, if the given register can be read / written with an
@@ -248,6 +257,8 @@ impl RiscvCommunicationInterfaceState {
             // We assume only a singe hart exisits initially
             num_harts: 1,
 
+            selected_hart: 0,
+
             memory_access_info: HashMap::new(),
 
             abstract_cmd_register_info: HashMap::new(),
@@ -304,6 +315,37 @@ impl RiscvCommunicationInterface {
         self.dtm.read_idcode()
     }
 
+    /// The number of harts of the debug module.
+    pub fn hart_count(&self) -> u32 {
+        self.state.num_harts
+    }
+
+    /// Select the hart which is controlled and accessed from now on.
+    ///
+    /// All run control and abstract commands go to the selected hart. Hart 0 is selected
+    /// initially.
+    pub fn select_hart(&mut self, hart: u32) -> Result<(), RiscvError> {
+        if hart >= self.state.num_harts {
+            return Err(RiscvError::HartUnavailable(hart, self.state.num_harts));
+        }
+
+        if hart != self.state.selected_hart {
+            tracing::debug!("Selecting hart {}", hart);
+        }
+        self.state.selected_hart = hart;
+
+        let mut control = self.dmcontrol();
+        control.set_dmactive(true);
+        self.write_dm_register(control)
+    }
+
+    /// A `dmcontrol` value which selects the current hart, with all other fields cleared.
+    pub(super) fn dmcontrol(&self) -> Dmcontrol {
+        let mut control = Dmcontrol(0);
+        control.set_hartsel(self.state.selected_hart);
+        control
+    }
+
     fn enter_debug_mode(&mut self) -> Result<(), RiscvError> {
         // We need a jtag interface
 
@@ -388,9 +430,8 @@ impl RiscvCommunicationInterface {
 
         self.state.num_harts = num_harts;
 
-        // Select hart 0 again
-        let mut control = Dmcontrol(0);
-        control.set_hartsel(0);
+        // Select the hart again
+        let mut control = self.dmcontrol();
         control.set_dmactive(true);
 
         self.write_dm_register(control)?;
@@ -982,7 +1023,7 @@ impl RiscvCommunicationInterface {
         // resumereq    = 0
         // ackhavereset = 0
 
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.dmcontrol();
         dmcontrol.set_haltreq(false);
         dmcontrol.set_resumereq(false);
         dmcontrol.set_ackhavereset(false);
@@ -9,7 +9,7 @@ use crate::{
     },
     memory::valid_32bit_address,
     memory_mapped_bitfield_register, CoreInterface, CoreRegister, CoreStatus, CoreType, Error,
-    HaltReason, InstructionSet, MemoryInterface,
+    HaltReason, InstructionSet, MemoryInterface, Watchpoint, WatchpointKind,
 };
 use anyhow::{anyhow, Result};
 use bitfield::bitfield;
@@ -81,8 +81,10 @@ impl<'probe> Riscv32<'probe> {
 
     // Resume the core.
     fn resume_core(&mut self) -> Result<(), crate::Error> {
+        self.state.triggered_watchpoint = None;
+
         // set resume request.
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_resumereq(true);
         dmcontrol.set_dmactive(true);
         self.interface.write_dm_register(dmcontrol)?;
@@ -94,13 +96,78 @@ impl<'probe> Riscv32<'probe> {
         };
 
         // clear resume request.
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_dmactive(true);
         self.interface.write_dm_register(dmcontrol)?;
 
         Ok(())
     }
 
+    /// Returns the watchpoint configured in the selected trigger, with its `tdata1` value
+    /// `mcontrol`.
+    fn read_watchpoint(&mut self, mcontrol: &Mcontrol) -> Result<Option<Watchpoint>, crate::Error> {
+        let Some(kind) = mcontrol.watchpoint_kind() else {
+            return Ok(None);
+        };
+
+        let tdata2 = self.read_csr(TDATA2)?;
+
+        let (address, length) = match mcontrol.match_() {
+            Mcontrol::MATCH_EQUAL => (tdata2, 1),
+            Mcontrol::MATCH_NAPOT => {
+                let length = 2u64 << tdata2.trailing_ones();
+                (tdata2 & !((length - 1) as u32), length)
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Watchpoint {
+            address: address as u64,
+            length,
+            kind,
+        }))
+    }
+
+    /// Find the watchpoint trigger which halted the core, by its `hit` bit, and clear the bit.
+    ///
+    /// The `hit` bit is optional, so this returns `None` on harts which do not implement it.
+    fn find_triggered_watchpoint(&mut self) -> Result<Option<Watchpoint>, crate::Error> {
+        for unit_index in 0..self.available_breakpoint_units()? as usize {
+            self.write_csr(TSELECT, unit_index as u32)?;
+            let mut mcontrol = Mcontrol(self.read_csr(TDATA1)?);
+
+            if !mcontrol.hit() {
+                continue;
+            }
+
+            if let Some(watchpoint) = self.read_watchpoint(&mcontrol)? {
+                mcontrol.set_hit(false);
+                self.write_csr(TDATA1, mcontrol.0)?;
+
+                return Ok(Some(watchpoint));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Enable or disable the watchpoint triggers set by probe-rs, like
+    /// [`CoreInterface::enable_breakpoints`] does for breakpoints.
+    fn enable_watchpoints(&mut self, state: bool) -> Result<(), crate::Error> {
+        for unit_index in 0..self.available_breakpoint_units()? as usize {
+            self.write_csr(TSELECT, unit_index as u32)?;
+            let mut mcontrol = Mcontrol(self.read_csr(TDATA1)?);
+
+            if mcontrol.is_data_trigger() && mcontrol.m() != state {
+                mcontrol.set_m(state);
+                mcontrol.set_u(state);
+                self.write_csr(TDATA1, mcontrol.0)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Check if the connected device supports halt after reset.
     ///
     /// Returns a cached value if available, otherwise queries the
@@ -123,6 +190,8 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         let start = Instant::now();
 
         while start.elapsed() < timeout {
+            crate::cancellation::check()?;
+
             let dmstatus: Dmstatus = self.interface.read_dm_register()?;
 
             tracing::trace!("{:?}", dmstatus);
@@ -154,8 +223,22 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             let reason = match dcsr.cause() {
                 // An ebreak instruction was hit
                 1 => HaltReason::Breakpoint(BreakpointCause::Software),
-                // Trigger module caused halt
-                2 => HaltReason::Breakpoint(BreakpointCause::Hardware),
+                // Trigger module caused halt, the trigger is either a breakpoint or a watchpoint
+                2 => {
+                    let watchpoint = match self.state.triggered_watchpoint {
+                        Some(watchpoint) => watchpoint,
+                        None => {
+                            let watchpoint = self.find_triggered_watchpoint()?;
+                            self.state.triggered_watchpoint = Some(watchpoint);
+                            watchpoint
+                        }
+                    };
+
+                    match watchpoint {
+                        Some(watchpoint) => HaltReason::Watchpoint(Some(watchpoint)),
+                        None => HaltReason::Breakpoint(BreakpointCause::Hardware),
+                    }
+                }
                 // Debugger requested a halt
                 3 => HaltReason::Request,
                 // Core halted after single step
@@ -186,7 +269,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             self.interface.read_dm_register::<Dmcontrol>()?
         );
 
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
 
         dmcontrol.set_haltreq(true);
         dmcontrol.set_dmactive(true);
@@ -196,7 +279,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         self.wait_for_core_halted(timeout)?;
 
         // clear the halt request
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
 
         dmcontrol.set_dmactive(true);
 
@@ -237,7 +320,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
 
         self.reset_catch_set()?;
 
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_dmactive(true);
         dmcontrol.set_hartreset(true);
         dmcontrol.set_haltreq(true);
@@ -260,7 +343,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             //
             // TODO: Cache this
             tracing::debug!("Hartreset bit not supported, using ndmreset");
-            let mut dmcontrol = Dmcontrol(0);
+            let mut dmcontrol = self.interface.dmcontrol();
             dmcontrol.set_dmactive(true);
             dmcontrol.set_ndmreset(true);
             dmcontrol.set_haltreq(true);
@@ -268,7 +351,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             self.interface.write_dm_register(dmcontrol)?;
 
             tracing::debug!("Clearing ndmreset bit");
-            let mut dmcontrol = Dmcontrol(0);
+            let mut dmcontrol = self.interface.dmcontrol();
             dmcontrol.set_dmactive(true);
             dmcontrol.set_ndmreset(false);
             dmcontrol.set_haltreq(true);
@@ -284,13 +367,14 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         }
 
         // acknowledge the reset, clear the halt request
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_dmactive(true);
         dmcontrol.set_ackhavereset(true);
 
         self.interface.write_dm_register(dmcontrol)?;
 
         self.reset_catch_clear()?;
+        self.state.triggered_watchpoint = None;
 
         let pc = self.read_core_reg(RegisterId(0x7b1))?;
 
@@ -325,6 +409,18 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             self.enable_breakpoints(false)?;
         }
 
+        // A watchpoint can halt the core before the access, and would halt it again. Without the
+        // optional `hit` bit, a watchpoint looks like a hardware breakpoint.
+        let halted_by_trigger = matches!(
+            halt_reason,
+            CoreStatus::Halted(
+                HaltReason::Breakpoint(BreakpointCause::Hardware) | HaltReason::Watchpoint(_)
+            )
+        );
+        if halted_by_trigger {
+            self.enable_watchpoints(false)?;
+        }
+
         let mut dcsr = Dcsr(self.read_core_reg(RegisterId(0x7b0))?.try_into()?);
         // Set it up, so that the next `self.run()` will only do a single step
         dcsr.set_step(true);
@@ -355,6 +451,9 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             // If we are halted on a hardware breakpoint.
             self.enable_breakpoints(true)?;
         }
+        if halted_by_trigger {
+            self.enable_watchpoints(true)?;
+        }
 
         Ok(CoreInformation { pc: pc.try_into()? })
     }
@@ -547,6 +646,11 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             return Err(RiscvError::UnexpectedTriggerType(trigger_type).into());
         }
 
+        // Breakpoints and watchpoints share the triggers.
+        if tdata_value.is_data_trigger() {
+            return Err(RiscvError::TriggerInUse(bp_unit_index).into());
+        }
+
         // Setup the trigger
 
         let mut instruction_breakpoint = Mcontrol(0);
@@ -587,6 +691,109 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         Ok(())
     }
 
+    fn available_watchpoint_units(&mut self) -> Result<u32, crate::Error> {
+        // Watchpoints use the same triggers as breakpoints.
+        self.available_breakpoint_units()
+    }
+
+    /// See docs on the [`CoreInterface::hw_watchpoints`] trait
+    /// NOTE: For riscv, triggers used by breakpoints are reported as unset.
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, crate::Error> {
+        let mut watchpoints = vec![];
+
+        for unit_index in 0..self.available_watchpoint_units()? as usize {
+            self.write_csr(TSELECT, unit_index as u32)?;
+            let mcontrol = Mcontrol(self.read_csr(TDATA1)?);
+
+            watchpoints.push(self.read_watchpoint(&mcontrol)?);
+        }
+
+        Ok(watchpoints)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), crate::Error> {
+        let address = valid_32bit_address(watchpoint.address)?;
+        let unsupported = |reason| Error::NoWatchpointAvailable {
+            address: watchpoint.address,
+            length: watchpoint.length,
+            reason,
+        };
+
+        let (match_, tdata2) = match watchpoint.length {
+            1 => (Mcontrol::MATCH_EQUAL, address),
+            length if length.is_power_of_two() && length <= 1 << 31 => {
+                if u64::from(address) % length != 0 {
+                    return Err(unsupported("its address is not aligned to its length"));
+                }
+
+                // The trailing ones select the size of a NAPOT range.
+                (Mcontrol::MATCH_NAPOT, address | (length as u32 / 2 - 1))
+            }
+            _ => return Err(unsupported("its length is not a power of two")),
+        };
+
+        self.write_csr(TSELECT, unit_index as u32)?;
+
+        let current = Mcontrol(self.read_csr(TDATA1)?);
+        if current.type_() != 0b10 {
+            return Err(RiscvError::UnexpectedTriggerType(current.type_()).into());
+        }
+        if current.execute() {
+            return Err(RiscvError::TriggerInUse(unit_index).into());
+        }
+
+        let mut data_watchpoint = Mcontrol(0);
+
+        // Enter debug mode
+        data_watchpoint.set_action(1);
+        data_watchpoint.set_dmode(true);
+        data_watchpoint.set_match(match_);
+        data_watchpoint.set_m(true);
+        data_watchpoint.set_u(true);
+
+        // Halt after the access, if the hart supports it
+        data_watchpoint.set_timing(true);
+
+        let (load, store) = match watchpoint.kind {
+            WatchpointKind::Read => (true, false),
+            WatchpointKind::Write => (false, true),
+            WatchpointKind::ReadWrite => (true, true),
+        };
+        data_watchpoint.set_load(load);
+        data_watchpoint.set_store(store);
+
+        self.write_csr(TDATA1, data_watchpoint.0)?;
+        self.write_csr(TDATA2, tdata2)?;
+
+        // The fields of tdata1 are WARL, a trigger which cannot watch the range keeps other values.
+        let written = Mcontrol(self.read_csr(TDATA1)?);
+        if written.match_() != match_ || written.load() != load || written.store() != store {
+            self.write_csr(TDATA1, 0)?;
+
+            tracing::debug!("Trigger {} cannot watch {:?}", unit_index, watchpoint);
+
+            return Err(unsupported("the trigger cannot watch this range"));
+        }
+
+        Ok(())
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), crate::Error> {
+        self.write_csr(TSELECT, unit_index as u32)?;
+        self.write_csr(TDATA1, 0)?;
+        self.write_csr(TDATA2, 0)?;
+
+        Ok(())
+    }
+
+    fn triggered_hw_watchpoint(&mut self) -> Result<Option<Watchpoint>, crate::Error> {
+        Ok(self.state.triggered_watchpoint.flatten())
+    }
+
     fn registers(&self) -> &'static CoreRegisters {
         &RISCV_CORE_REGSISTERS
     }
@@ -758,6 +965,10 @@ pub struct RiscVState {
 
     /// Store the value of the `hasresethaltreq` bit of the `dmcstatus` register.
     hasresethaltreq: Option<bool>,
+
+    /// The watchpoint which halted the core, `None` if it was not determined since the core was
+    /// last resumed. The `hit` bit of the trigger is cleared when it is read.
+    triggered_watchpoint: Option<Option<Watchpoint>>,
 }
 
 impl RiscVState {
@@ -765,6 +976,7 @@ impl RiscVState {
         Self {
             hw_breakpoints_enabled: false,
             hasresethaltreq: None,
+            triggered_watchpoint: None,
         }
     }
 }
@@ -929,6 +1141,43 @@ bitfield! {
     load, set_load: 0;
 }
 
+/// Trigger select register.
+const TSELECT: u16 = 0x7a0;
+/// First data register of the selected trigger, [`Mcontrol`] for address triggers.
+const TDATA1: u16 = 0x7a1;
+/// Second data register of the selected trigger, the address of address triggers.
+const TDATA2: u16 = 0x7a2;
+
+impl Mcontrol {
+    /// Match the address in `tdata2`.
+    const MATCH_EQUAL: u32 = 0;
+    /// Match the naturally aligned, power of two sized range encoded in `tdata2`.
+    const MATCH_NAPOT: u32 = 1;
+
+    /// Returns `true` if the trigger is an address trigger for data accesses, which enters
+    /// debug mode. This includes triggers disabled by clearing their mode bits.
+    fn is_data_trigger(&self) -> bool {
+        self.type_() == 0b10
+            && self.action() == 1
+            && !self.execute()
+            && (self.load() || self.store())
+    }
+
+    /// Returns the kind of access the trigger halts the core on, if it is an enabled data
+    /// watchpoint.
+    fn watchpoint_kind(&self) -> Option<WatchpointKind> {
+        if !self.is_data_trigger() || !(self.m() || self.s() || self.u()) {
+            return None;
+        }
+
+        match (self.load(), self.store()) {
+            (true, true) => Some(WatchpointKind::ReadWrite),
+            (true, false) => Some(WatchpointKind::Read),
+            _ => Some(WatchpointKind::Write),
+        }
+    }
+}
+
 bitfield! {
     /// Isa and Extensions (see RISC-V Privileged Spec, 3.1.1)
     pub struct Misa(u32);
@@ -939,3 +1188,49 @@ bitfield! {
     /// Standard RISC-V extensions
     extensions, _: 25, 0;
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Dmcontrol, Mcontrol};
+    use crate::WatchpointKind;
+
+    #[test]
+    fn hartsel_is_split_into_hartsello_and_hartselhi() {
+        let mut control = Dmcontrol(0);
+        control.set_hartsel(0x4_0403);
+
+        assert_eq!(control.hartsello(), 0x003);
+        assert_eq!(control.hartselhi(), 0x101);
+        assert_eq!(control.hartsel(), 0x4_0403);
+        assert_eq!(control.0, (0x003 << 16) | (0x101 << 6));
+    }
+
+    #[test]
+    fn data_triggers_are_watchpoints() {
+        let mut trigger = Mcontrol(0);
+        trigger.set_type(0b10);
+        trigger.set_action(1);
+        trigger.set_m(true);
+        trigger.set_u(true);
+
+        let mut breakpoint = Mcontrol(trigger.0);
+        breakpoint.set_execute(true);
+        assert!(!breakpoint.is_data_trigger());
+        assert_eq!(breakpoint.watchpoint_kind(), None);
+
+        let mut watchpoint = Mcontrol(trigger.0);
+        watchpoint.set_store(true);
+        assert_eq!(watchpoint.watchpoint_kind(), Some(WatchpointKind::Write));
+        watchpoint.set_load(true);
+        assert_eq!(
+            watchpoint.watchpoint_kind(),
+            Some(WatchpointKind::ReadWrite)
+        );
+
+        // A watchpoint disabled while stepping is still a data trigger.
+        watchpoint.set_m(false);
+        watchpoint.set_u(false);
+        assert!(watchpoint.is_data_trigger());
+        assert_eq!(watchpoint.watchpoint_kind(), None);
+    }
+}
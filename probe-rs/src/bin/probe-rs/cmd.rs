@@ -1,4 +1,5 @@
 pub mod benchmark;
+pub mod board;
 pub mod cargo_embed;
 pub mod cargo_flash;
 pub mod chip;
@@ -7,11 +8,18 @@ pub mod debug;
 pub mod download;
 pub mod dump;
 pub mod erase;
+pub mod firmware;
 pub mod gdb;
 pub mod info;
 pub mod itm;
+pub mod lifecycle;
 pub mod list;
+pub mod live_watch;
+pub mod probe_setting;
 pub mod profile;
+pub mod register_diff;
 pub mod reset;
 pub mod run;
 pub mod trace;
+pub mod verify;
+pub mod watch;
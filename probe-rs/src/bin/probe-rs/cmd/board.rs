@@ -0,0 +1,61 @@
+use anyhow::Context;
+
+use crate::util::board_config::{BoardConfig, BoardConfigs};
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+/// Manage the settings stored for boards, by the serial number of their probe
+enum Subcommand {
+    /// Lists the stored settings of all boards.
+    #[clap(name = "list")]
+    List,
+    /// Stores the settings of a board, replacing previously stored settings.
+    #[clap(name = "set")]
+    Set {
+        /// The serial number of the probe connected to the board.
+        serial: String,
+        #[clap(flatten)]
+        config: BoardConfig,
+    },
+    /// Removes the stored settings of a board.
+    #[clap(name = "remove")]
+    Remove {
+        /// The serial number of the probe connected to the board.
+        serial: String,
+    },
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let path = BoardConfigs::default_path()
+            .context("the application configuration directory could not be determined")?;
+        let mut configs = BoardConfigs::load(&path)?;
+
+        match self.subcommand {
+            Subcommand::List => {
+                for (serial, config) in configs.iter() {
+                    println!("{serial}: {config:?}");
+                }
+            }
+            Subcommand::Set { serial, config } => {
+                configs.set(serial, config);
+                configs.save(&path)?;
+            }
+            Subcommand::Remove { serial } => {
+                if configs.remove(&serial).is_none() {
+                    anyhow::bail!(
+                        "No settings are stored for the probe with serial number {serial}"
+                    );
+                }
+                configs.save(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -72,6 +72,8 @@ pub struct General {
     pub derives: Option<String>,
     /// Use this flag to assert the nreset & ntrst pins during attaching the probe to the chip.
     pub connect_under_reset: bool,
+    /// Use this flag to fail attaching if another debugger seems to be attached to the chip.
+    pub deny_debugger_takeover: bool,
 }
 
 /// The rtt config struct holding all the possible rtt options.
@@ -236,11 +236,14 @@ fn main_try(mut args: Vec<OsString>, offset: UtcOffset) -> Result<()> {
 
     log::info!("Protocol speed {} kHz", protocol_speed);
 
-    let permissions = if config.flashing.enabled || config.gdb.enabled {
+    let mut permissions = if config.flashing.enabled || config.gdb.enabled {
         Permissions::new().allow_erase_all()
     } else {
         Permissions::new()
     };
+    if config.general.deny_debugger_takeover {
+        permissions = permissions.deny_debugger_takeover();
+    }
 
     let mut session = if config.general.connect_under_reset {
         probe
@@ -640,6 +643,10 @@ fn flash(
                         fp.finish()
                     };
                 }
+                StartedVerifying { .. }
+                | DataVerified { .. }
+                | FailedVerifying
+                | FinishedVerifying => (),
                 DiagnosticMessage { .. } => todo!(),
             }
         });
@@ -78,6 +78,13 @@ pub(crate) fn render_diagnostics(error: OperationError) {
             ],
         ),
         OperationError::FlashingFailed { source, target, target_spec, .. } => generate_flash_error_hints(source, target, target_spec),
+        OperationError::ImageDoesNotFit { .. } => (
+            error.to_string(),
+            vec![
+                "The memory usage above lists the regions which are exceeded.".into(),
+                "Reduce the size of the image, for example by building with optimizations or `opt-level = \"s\"`.".into(),
+            ],
+        ),
         OperationError::FailedChipDescriptionParsing { .. } => (
             error.to_string(),
             vec![],
@@ -163,6 +170,25 @@ pub(crate) fn render_diagnostics(error: OperationError) {
                 format!("Try specifying a speed lower than {speed} kHz")
             ],
         ),
+        OperationError::FailedToConfigureSwdTiming { .. } => (
+            error.to_string(),
+            vec![
+                "Not all probes support changing the SWD timing, try using the default values.".into(),
+                "The SWD turnaround period must be between 1 and 4 cycles.".into(),
+            ],
+        ),
+        OperationError::FailedToConfigureResetTiming { .. } => (
+            error.to_string(),
+            vec![
+                "Not all probes support changing the reset timing, try using the default values.".into(),
+            ],
+        ),
+        OperationError::FailedToConfigureUsb { .. } => (
+            error.to_string(),
+            vec![
+                "Not all probes support changing the USB transfers, the USB timeout of J-Link probes is fixed.".into(),
+            ],
+        ),
         OperationError::AttachingFailed { source, connect_under_reset } => match source {
             ProbeRsError::ChipNotFound(RegistryError::ChipAutodetectFailed) => (
                 error.to_string(),
@@ -171,6 +197,19 @@ pub(crate) fn render_diagnostics(error: OperationError) {
                     "You can list all the available chips by running `probe-rs chip list`.".into(),
                 ],
             ),
+            ProbeRsError::StateNotPreserved { .. } => (
+                error.to_string(),
+                vec![
+                    "The core was reset or halted by the firmware, by the chip itself or by its debug sequence while attaching. RAM contents may no longer be the ones from before attaching.".into(),
+                ],
+            ),
+            ProbeRsError::TargetAlreadyDebugged { .. } => (
+                error.to_string(),
+                vec![
+                    "Close other debuggers connected to the target, or power cycle the target if a previous session did not end cleanly.".into(),
+                    "Run without `--deny-debugger-takeover` to attach anyway. This can disturb the other debugger.".into(),
+                ],
+            ),
             _ => if !connect_under_reset {
                 (
                     error.to_string(),
@@ -304,6 +343,9 @@ fn generate_flash_error_hints(
 
                 hints
             },
+            FlashError::ReadOnlyNvm { .. } => vec![
+                "The target description marks this region as read-only, for example because it is one-time programmable. Remove the section placed in it from the image.".into(),
+            ],
             FlashError::EraseFailed { ..} => vec![
                 "Perhaps your chip has write protected sectors that need to be cleared?".into(),
                 "Perhaps you need the --nmagic linker arg. See https://github.com/rust-embedded/cortex-m-quickstart/pull/95 for more information.".into()
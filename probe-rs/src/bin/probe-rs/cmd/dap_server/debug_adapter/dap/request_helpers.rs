@@ -11,7 +11,7 @@ use capstone::{
 use num_traits::Zero;
 use probe_rs::{
     debug::{ColumnType, SourceLocation},
-    CoreType, InstructionSet, MemoryInterface,
+    Core, CoreType, InstructionSet, MemoryInterface,
 };
 use std::time::Duration;
 
@@ -24,7 +24,7 @@ pub(crate) fn disassemble_target_memory(
     memory_reference: u64,
     instruction_count: i64,
 ) -> Result<Vec<DisassembledInstruction>, DebuggerError> {
-    let cs = get_capstone(target_core)?;
+    let cs = get_capstone(&mut target_core.core)?;
     let target_instruction_set = target_core.core.instruction_set()?;
     let instruction_offset_as_bytes = match target_instruction_set {
         InstructionSet::Thumb2 | InstructionSet::RV32C => {
@@ -222,14 +222,14 @@ pub(crate) fn disassemble_target_memory(
     Ok(assembly_lines)
 }
 
-pub(crate) fn get_capstone(target_core: &mut CoreHandle) -> Result<Capstone, DebuggerError> {
-    let mut cs = match target_core.core.instruction_set()? {
+pub(crate) fn get_capstone(core: &mut Core) -> Result<Capstone, DebuggerError> {
+    let mut cs = match core.instruction_set()? {
         InstructionSet::Thumb2 => {
             let mut capstone_builder = Capstone::new()
                 .arm()
                 .mode(armArchMode::Thumb)
                 .endian(Endian::Little);
-            if matches!(target_core.core.core_type(), CoreType::Armv8m) {
+            if matches!(core.core_type(), CoreType::Armv8m) {
                 capstone_builder = capstone_builder
                     .extra_mode(std::iter::once(capstone::arch::arm::ArchExtraMode::V8));
             }
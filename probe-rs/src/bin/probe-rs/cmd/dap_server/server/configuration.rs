@@ -37,6 +37,11 @@ pub struct SessionConfig {
     #[serde(default)]
     pub(crate) allow_erase_all: bool,
 
+    /// Fail to attach if another debugger seems to be attached to the target, instead of only
+    /// logging a warning.
+    #[serde(default)]
+    pub(crate) deny_debugger_takeover: bool,
+
     /// Flashing configuration
     #[serde(default)]
     pub(crate) flashing_config: FlashingConfig,
@@ -249,9 +249,9 @@ impl<'p> CoreHandle<'p> {
         }
 
         self.core
-            .set_hw_breakpoint(address)
+            .set_breakpoint(address)
             .map_err(DebuggerError::ProbeRs)?;
-        // Wait until the set of the hw breakpoint succeeded, before we cache it here ...
+        // Wait until the set of the breakpoint succeeded, before we cache it here ...
         self.core_data
             .breakpoints
             .push(session_data::ActiveBreakpoint {
@@ -264,7 +264,7 @@ impl<'p> CoreHandle<'p> {
     /// Clear a single breakpoint from target configuration.
     pub(crate) fn clear_breakpoint(&mut self, address: u64) -> Result<()> {
         self.core
-            .clear_hw_breakpoint(address)
+            .clear_breakpoint(address)
             .map_err(DebuggerError::ProbeRs)?;
         if let Some((breakpoint_position, _)) = self.find_breakpoint_in_cache(address) {
             self.core_data.breakpoints.remove(breakpoint_position);
@@ -132,17 +132,18 @@ impl Debugger {
                 // Attach to the core. so that we have the handle available for processing the request.
 
                 let Some(target_core_config) = self.config.core_configs.get_mut(core_id) else {
-                        return Err(DebuggerError::Other(anyhow!(
-                            "No core configuration found for core id {}",
-                            core_id
-                        )));
-                    };
+                    return Err(DebuggerError::Other(anyhow!(
+                        "No core configuration found for core id {}",
+                        core_id
+                    )));
+                };
 
-                let Ok(mut target_core) = session_data.attach_core(target_core_config.core_index) else {
-                        return Err(DebuggerError::Other(anyhow!(
-                            "Unable to connect to target core"
-                        )));
-                    };
+                let Ok(mut target_core) = session_data.attach_core(target_core_config.core_index)
+                else {
+                    return Err(DebuggerError::Other(anyhow!(
+                        "Unable to connect to target core"
+                    )));
+                };
 
                 // For some operations, we need to make sure the core isn't sleeping, by calling `Core::halt()`.
                 // When we do this, we need to flag it (`unhalt_me = true`), and later call `Core::run()` again.
@@ -443,11 +444,11 @@ impl Debugger {
                 ))
             })?;
             let Some(path_to_elf) = target_core_config.program_binary.clone() else {
-                    let err =  DebuggerError::Other(anyhow!("Please specify use the `program-binary` option in `launch.json` to specify an executable"));
+                let err =  DebuggerError::Other(anyhow!("Please specify use the `program-binary` option in `launch.json` to specify an executable"));
 
-                    debug_adapter.show_error_message(&err)?;
-                    return Err(err);
-                };
+                debug_adapter.show_error_message(&err)?;
+                return Err(err);
+            };
 
             // Store timestamp of flashed binary
             self.binary_timestamp = get_file_timestamp(&path_to_elf);
@@ -529,11 +530,11 @@ impl Debugger {
                 ))
             })?;
             let Some(path_to_elf) = target_core_config.program_binary.clone() else {
-                    let err =  DebuggerError::Other(anyhow!("Please specify use the `program-binary` option in `launch.json` to specify an executable"));
+                let err =  DebuggerError::Other(anyhow!("Please specify use the `program-binary` option in `launch.json` to specify an executable"));
 
-                    debug_adapter.show_error_message(&err)?;
-                    return Err(err);
-                };
+                debug_adapter.show_error_message(&err)?;
+                return Err(err);
+            };
 
             if is_file_newer(&mut self.binary_timestamp, &path_to_elf) {
                 // If there is a new binary as part of a restart, there are some key things that
@@ -723,6 +724,10 @@ impl Debugger {
                             .update_progress(Some(1.0), Some("Flashing Pages Complete!"), id)
                             .ok();
                     }
+                    probe_rs::flashing::ProgressEvent::StartedVerifying { .. }
+                    | probe_rs::flashing::ProgressEvent::DataVerified { .. }
+                    | probe_rs::flashing::ProgressEvent::FailedVerifying
+                    | probe_rs::flashing::ProgressEvent::FinishedVerifying => (),
                     probe_rs::flashing::ProgressEvent::DiagnosticMessage { .. } => (),
                 }
             })
@@ -123,6 +123,9 @@ impl SessionData {
         if config.allow_erase_all {
             permissions = permissions.allow_erase_all();
         }
+        if config.deny_debugger_takeover {
+            permissions = permissions.deny_debugger_takeover();
+        }
 
         // Attach to the probe.
         let target_session = if config.connect_under_reset {
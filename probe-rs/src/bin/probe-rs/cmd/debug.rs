@@ -16,7 +16,8 @@ use probe_rs::{
     debug::{
         debug_info::DebugInfo, registers::DebugRegisters, stack_frame::StackFrame, VariableName,
     },
-    Core, CoreRegister, CoreType, InstructionSet, MemoryInterface, RegisterId, RegisterValue,
+    BreakpointKind, Core, CoreRegister, CoreType, HaltEscalation, InstructionSet, MemoryInterface,
+    RegisterId, RegisterValue,
 };
 use rustyline::DefaultEditor;
 
@@ -149,11 +150,17 @@ impl DebugCli {
 
         cli.add_command(Command {
             name: "halt",
-            help_text: "Stop the CPU",
+            help_text: "Stop the CPU, 'halt reset' resets the CPU if it does not stop otherwise",
 
-            function: |cli_data, _args| {
-                let cpu_info = cli_data.core.halt(Duration::from_millis(100))?;
-                println!("Core stopped at address 0x{:08x}", cpu_info.pc);
+            function: |cli_data, args| {
+                let escalation =
+                    HaltEscalation::default().reset_and_halt(args.first() == Some(&"reset"));
+                let outcome = cli_data.core.halt_with_escalation(&escalation)?;
+                let cpu_info = outcome.info;
+                println!(
+                    "Core stopped at address 0x{:08x} by {}",
+                    cpu_info.pc, outcome.path
+                );
 
                 let mut code = [0u8; 16 * 2];
 
@@ -473,9 +480,16 @@ impl DebugCli {
             function: |cli_data, args| {
                 let address = get_int_argument(args, 0)?;
 
-                cli_data.core.set_hw_breakpoint(address)?;
+                let breakpoint = cli_data.core.set_breakpoint(address)?;
 
-                println!("Set new breakpoint at address {address:#08x}");
+                match breakpoint.kind {
+                    BreakpointKind::Hardware { unit } => {
+                        println!("Set new breakpoint at address {address:#08x} in unit {unit}")
+                    }
+                    BreakpointKind::Software => {
+                        println!("Set new software breakpoint at address {address:#08x}")
+                    }
+                }
 
                 Ok(CliState::Continue)
             },
@@ -488,7 +502,7 @@ impl DebugCli {
             function: |cli_data, args| {
                 let address = get_int_argument(args, 0)?;
 
-                cli_data.core.clear_hw_breakpoint(address)?;
+                cli_data.core.clear_breakpoint(address)?;
 
                 Ok(CliState::Continue)
             },
@@ -1,9 +1,14 @@
 use std::fs::File;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
+use probe_rs::config::TargetSelector;
 use probe_rs::flashing::FileDownloadError;
 use probe_rs::flashing::Format;
+use probe_rs::flashing::Stm32UartBootloader;
+use probe_rs::flashing::{BinOptions, DownloadOptions};
 
 use crate::util::common_options::ProbeOptions;
 use crate::util::common_options::{CargoOptions, FlashOptions};
@@ -30,12 +35,70 @@ pub struct Cmd {
     #[clap(long = "disable-double-buffering")]
     disable_double_buffering: bool,
 
+    /// Print the flash and RAM usage of the image, and fail if it does not fit
+    #[clap(long)]
+    memory_usage: bool,
+
+    /// Record the progress of the download in this file, to detect and resume an interrupted download
+    #[clap(long)]
+    journal: Option<PathBuf>,
+
+    /// Record the data programmed to flash in this Intel HEX file, to verify devices against it
+    /// later with `probe-rs verify`
+    #[clap(long)]
+    golden_image: Option<PathBuf>,
+
+    /// Cache ELF files by their build ID in this directory, so flashing the same ELF file again
+    /// neither parses it nor computes its flash layout again
+    #[clap(long)]
+    image_cache: Option<PathBuf>,
+
+    /// Only update the flash sectors whose contents change. Changed sectors are sent as a binary
+    /// diff against their current contents and patched on the target where possible
+    #[clap(long, conflicts_with_all = ["chip_erase", "stm32_bootloader"])]
+    delta: bool,
+
+    /// Read back the written data after flashing, and check that it matches the image
+    #[clap(long, conflicts_with = "stm32_bootloader")]
+    verify: bool,
+
+    /// Program through the STM32 system bootloader on this serial port, for example the UART
+    /// channel of an FTDI probe, instead of a debug probe. The whole flash is erased, and
+    /// `--chip` is required.
+    #[clap(long)]
+    stm32_bootloader: Option<String>,
+
+    /// The baud rate of the `--stm32-bootloader` serial port
+    #[clap(long, default_value = "115200")]
+    bootloader_baud: u32,
+
+    /// Program a binary image while it is read, without loading the whole image into memory.
+    /// A path of `-` reads the image from stdin, for example from a decompressor. Requires
+    /// `--format bin`.
+    #[clap(long, conflicts_with_all = ["journal", "golden_image", "image_cache", "memory_usage", "stm32_bootloader", "delta"])]
+    stream: bool,
+
+    /// The size of the chunks in which a `--stream` is programmed, in bytes
+    #[clap(long, default_value = "65536", requires = "stream")]
+    stream_chunk_size: usize,
+
     #[clap(flatten)]
     format_options: FormatOptions,
 }
 
 impl Cmd {
     pub fn run(self) -> anyhow::Result<()> {
+        if let Some(port) = &self.stm32_bootloader {
+            return self.run_bootloader(port);
+        }
+
+        if self.stream {
+            let Format::Bin(options) = self.format_options.clone().into_format()? else {
+                bail!("Only binary images can be programmed from a stream, use --format bin.");
+            };
+            return self.run_stream(options);
+        }
+
         let mut session = self.common.simple_attach()?;
 
         let mut file = match File::open(&self.path) {
@@ -48,7 +111,10 @@ impl Cmd {
         let format = self.format_options.into_format()?;
         match format {
             Format::Bin(options) => loader.load_bin_data(&mut file, options),
-            Format::Elf => loader.load_elf_data(&mut file),
+            Format::Elf => match &self.image_cache {
+                Some(directory) => loader.load_elf_data_cached(&mut file, directory),
+                None => loader.load_elf_data(&mut file),
+            },
             Format::Hex => loader.load_hex_data(&mut file),
             Format::Idf(options) => loader.load_idf_data(&mut session, &mut file, options),
         }?;
@@ -62,9 +128,16 @@ impl Cmd {
                 reset_halt: false,
                 log: None,
                 restore_unwritten: false,
+                gap_fill: None,
+                stamp_checksums: false,
                 flash_layout_output_path: None,
+                memory_usage: self.memory_usage,
                 elf: None,
                 work_dir: None,
+                journal: self.journal,
+                golden_image: self.golden_image,
+                delta: self.delta,
+                verify: self.verify,
                 cargo_options: CargoOptions::default(),
                 probe_options: self.common,
             },
@@ -74,4 +147,79 @@ impl Cmd {
 
         Ok(())
     }
+
+    /// Program the binary image at `path` while it is read.
+    fn run_stream(&self, options: BinOptions) -> anyhow::Result<()> {
+        let mut stream: Box<dyn Read> = if self.path == "-" {
+            Box::new(std::io::stdin().lock())
+        } else {
+            Box::new(File::open(&self.path).context("Failed to open binary file.")?)
+        };
+        std::io::copy(
+            &mut (&mut stream).take(u64::from(options.skip)),
+            &mut std::io::sink(),
+        )?;
+
+        let mut session = self.common.simple_attach()?;
+        let loader = session.target().flash_loader();
+
+        let mut download_options = DownloadOptions::default();
+        download_options.do_chip_erase = self.chip_erase;
+        download_options.disable_double_buffering = self.disable_double_buffering;
+        download_options.dry_run = self.common.dry_run;
+        download_options.verify = self.verify;
+
+        let start = std::time::Instant::now();
+        let programmed = loader.commit_stream(
+            &mut session,
+            options.base_address.unwrap_or(0),
+            &mut stream,
+            self.stream_chunk_size,
+            download_options,
+        )?;
+
+        println!(
+            "Programmed {} bytes in {:.2}s",
+            programmed,
+            start.elapsed().as_secs_f64()
+        );
+
+        Ok(())
+    }
+
+    /// Download the file through the STM32 system bootloader on the serial `port`.
+    fn run_bootloader(&self, port: &str) -> anyhow::Result<()> {
+        self.common.maybe_load_chip_desc()?;
+        let TargetSelector::Specified(target) = self.common.get_target_selector()? else {
+            bail!("The chip has to be specified with --chip to program through the bootloader.");
+        };
+
+        let mut file = File::open(&self.path).context("Failed to open binary file.")?;
+        let mut loader = target.flash_loader();
+        match self.format_options.clone().into_format()? {
+            Format::Bin(options) => loader.load_bin_data(&mut file, options),
+            Format::Elf => loader.load_elf_data(&mut file),
+            Format::Hex => loader.load_hex_data(&mut file),
+            Format::Idf(_) => {
+                bail!("IDF images cannot be programmed through the STM32 bootloader.")
+            }
+        }?;
+
+        let port = serialport::new(port, self.bootloader_baud)
+            .parity(serialport::Parity::Even)
+            .timeout(Duration::from_millis(100))
+            .open()
+            .with_context(|| format!("Failed to open the serial port {port}"))?;
+        let mut bootloader = Stm32UartBootloader::connect(port)
+            .context("Failed to connect to the bootloader, is the chip in bootloader mode?")?;
+        log::info!(
+            "Connected to bootloader version {:#04x} of product {:#06x}",
+            bootloader.version(),
+            bootloader.product_id()
+        );
+
+        loader.commit_bootloader(&mut bootloader, true)?;
+
+        Ok(())
+    }
 }
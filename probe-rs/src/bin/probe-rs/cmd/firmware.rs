@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use probe_rs::{DebugProbeError, DebugProbeSelector, Probe};
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+/// Check and update the firmware of debug probes
+enum Subcommand {
+    /// Reports the firmware version of all connected probes, and whether it should be updated.
+    #[clap(name = "check")]
+    Check,
+    /// Writes a firmware image to a probe. Only DAPLink probes are supported, which have to
+    /// run their bootloader, otherwise the target is programmed with the image.
+    #[clap(name = "update")]
+    Update {
+        /// The probe to update, as VID:PID or VID:PID:Serial.
+        #[clap(long = "probe")]
+        probe_selector: DebugProbeSelector,
+        /// The firmware image, as binary or Intel HEX file.
+        image: PathBuf,
+    },
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        match self.subcommand {
+            Subcommand::Check => {
+                let probes = Probe::list_all();
+
+                if probes.is_empty() {
+                    println!("No debug probes were found.");
+                }
+
+                for info in probes {
+                    print!("{info:?}: ");
+
+                    match Probe::open(&info).and_then(|mut probe| probe.firmware_info()) {
+                        Ok(Some(firmware)) => {
+                            println!("firmware {}, {}", firmware.version, firmware.status);
+                            if let Some(hint) = firmware.update_hint {
+                                if firmware.status.needs_update() {
+                                    println!("    {hint}");
+                                }
+                            }
+                        }
+                        Ok(None) => println!("the firmware version is not reported by the probe"),
+                        Err(DebugProbeError::ProbeFirmwareOutdated) => {
+                            println!("update required: the firmware is not supported by probe-rs")
+                        }
+                        Err(e) => println!("the probe could not be opened: {e}"),
+                    }
+                }
+            }
+            Subcommand::Update {
+                probe_selector,
+                image,
+            } => {
+                let image =
+                    std::fs::read(&image).context(format!("Failed to read {}", image.display()))?;
+
+                let mut probe = Probe::open(probe_selector)?;
+                probe.update_firmware(&image)?;
+
+                println!("The image was written to the probe, which restarts once it is updated.");
+            }
+        }
+
+        Ok(())
+    }
+}
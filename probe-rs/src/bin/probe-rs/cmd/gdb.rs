@@ -1,6 +1,9 @@
+use std::path::PathBuf;
 use std::sync::Mutex;
 use std::time::Duration;
 
+use probe_rs::rtos::{detect_rtos, RtosSymbols};
+
 use crate::util::common_options::ProbeOptions;
 
 #[derive(clap::Parser)]
@@ -18,6 +21,18 @@ pub struct Cmd {
     )]
     reset_halt: bool,
 
+    #[clap(
+        long,
+        help = "The ELF file of the running application. If it uses a supported RTOS, its threads are shown in GDB."
+    )]
+    rtos_elf: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Allow the target to open files through semihosting, below this directory. Without it, the target can only use the console."
+    )]
+    semihosting_root: Option<PathBuf>,
+
     #[clap(flatten)]
     common: ProbeOptions,
 }
@@ -36,11 +51,30 @@ impl Cmd {
             .gdb_connection_string
             .unwrap_or_else(|| "localhost:1337".to_string());
 
-        let instances = probe_rs::gdb_server::GdbInstanceConfiguration::from_session(
+        let mut instances = probe_rs::gdb_server::GdbInstanceConfiguration::from_session(
             &session,
             Some(gdb_connection_string),
         );
 
+        if let Some(path) = &self.rtos_elf {
+            let symbols = RtosSymbols::from_elf(&std::fs::read(path)?)?;
+
+            match detect_rtos(&symbols) {
+                Some(rtos) => {
+                    println!("Found {} in {}", rtos.name(), path.display());
+
+                    for instance in instances.iter_mut() {
+                        instance.rtos = Some(rtos.clone());
+                    }
+                }
+                None => println!("No supported RTOS found in {}", path.display()),
+            }
+        }
+
+        for instance in instances.iter_mut() {
+            instance.semihosting_root = self.semihosting_root.clone();
+        }
+
         for instance in instances.iter() {
             println!(
                 "Firing up GDB stub for {:?} cores at {:?}",
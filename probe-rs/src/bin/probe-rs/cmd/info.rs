@@ -5,13 +5,13 @@ use anyhow::Result;
 use probe_rs::{
     architecture::{
         arm::{
-            ap::{GenericAp, MemoryAp},
+            ap::{AccessPort, MemoryAp},
             armv6m::Demcr,
             component::Scs,
-            dp::{DPIDR, TARGETID},
-            memory::{Component, CoresightComponent, PeripheralType},
+            dp::{known_multidrop_targetids, DPIDR, TARGETID},
+            memory::{Component, ComponentId, CoresightComponent, PeripheralType},
             sequences::DefaultArmSequence,
-            ApAddress, ApInformation, ArmProbeInterface, DpAddress, MemoryApInformation, Register,
+            ApInformation, ArmProbeInterface, DpAddress, MemoryApInformation, Register,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
@@ -88,7 +88,14 @@ fn try_show_info(
                         if let Err(e) = show_arm_info(&mut *interface) {
                             // Log error?
                             println!("Error showing ARM chip information:");
-                            println!("{e:?}")
+                            println!("{e:?}");
+
+                            // Multidrop targets do not respond on the default DP.
+                            if protocol == WireProtocol::Swd {
+                                if let Err(e) = show_multidrop_targets(&mut *interface) {
+                                    println!("Error scanning for multidrop targets: {e}");
+                                }
+                            }
                         }
 
                         probe = interface.close();
@@ -143,6 +150,31 @@ fn try_show_info(
     (probe, Ok(()))
 }
 
+fn show_multidrop_targets(interface: &mut dyn ArmProbeInterface) -> Result<()> {
+    let targets = interface.scan_multidrop(&known_multidrop_targetids()?)?;
+
+    if targets.is_empty() {
+        println!("No multidrop debug ports of known parts found.");
+    }
+
+    for target in targets {
+        let targetid = target.targetid();
+        let designer = targetid.designer();
+
+        println!(
+            "Multidrop Debug Port: TARGETSEL {:#010x}, Instance {}, Designer: {}, Part: {:#x}, Revision: {:#x}, DP Version {}",
+            target.targetsel,
+            target.instance(),
+            designer.get().unwrap_or("<unknown>"),
+            targetid.tpartno(),
+            targetid.trevision(),
+            target.dpidr().version(),
+        );
+    }
+
+    Ok(())
+}
+
 fn show_arm_info(interface: &mut dyn ArmProbeInterface) -> Result<()> {
     let dp_info = interface.read_raw_dp_register(DpAddress::Default, DPIDR::ADDRESS)?;
     let dp_info = DPIDR(dp_info);
@@ -164,13 +196,7 @@ fn show_arm_info(interface: &mut dyn ArmProbeInterface) -> Result<()> {
 
         let part_no = target_id.tpartno();
         let revision = target_id.trevision();
-
-        let designer_id = target_id.tdesigner();
-
-        let cc = (designer_id >> 7) as u8;
-        let id = (designer_id & 0x7f) as u8;
-
-        let designer = jep106::JEP106Code::new(cc, id);
+        let designer = target_id.designer();
 
         write!(
             dp_node,
@@ -190,15 +216,7 @@ fn show_arm_info(interface: &mut dyn ArmProbeInterface) -> Result<()> {
     let mut tree = Tree::new(dp_node);
 
     let dp = DpAddress::Default;
-    let num_access_ports = interface.num_access_ports(dp)?;
-
-    for ap_index in 0..num_access_ports {
-        let ap = ApAddress {
-            ap: ap_index as u8,
-            dp,
-        };
-        let access_port = GenericAp::new(ap);
-
+    for access_port in interface.access_ports(dp)? {
         let ap_information = interface.ap_information(access_port)?;
 
         match ap_information {
@@ -222,6 +240,16 @@ fn show_arm_info(interface: &mut dyn ArmProbeInterface) -> Result<()> {
                 tree.push(ap_nodes);
             }
 
+            ApInformation::Vendor { ap, idr } => {
+                tree.push(format!(
+                    "{} {} (Variant: {:#x}, Revision: {:#x})",
+                    ap.ap_address().ap,
+                    ap.name(),
+                    idr.VARIANT,
+                    idr.REVISION
+                ));
+            }
+
             ApInformation::Other { address, idr } => {
                 let designer = idr.DESIGNER;
 
@@ -279,8 +307,13 @@ fn coresight_component_tree(
 ) -> Result<Tree<String>> {
     let tree = match &component {
         Component::GenericVerificationComponent(_) => Tree::new("Generic".to_string()),
-        Component::Class1RomTable(_, table) => {
-            let mut rom_table = Tree::new("ROM Table (Class 1)".to_string());
+        Component::Class1RomTable(_, table) | Component::Class9RomTable(_, table) => {
+            let class = if matches!(component, Component::Class1RomTable(..)) {
+                "1"
+            } else {
+                "9"
+            };
+            let mut rom_table = Tree::new(format!("ROM Table (Class {class})"));
 
             for entry in table.entries() {
                 let component = entry.component().clone();
@@ -308,7 +341,7 @@ fn coresight_component_tree(
                 )
             };
 
-            Tree::new(component_description)
+            unknown_component_details(Tree::new(component_description), id)
         }
 
         Component::PeripheralTestBlock(_) => Tree::new("Peripheral test block".to_string()),
@@ -337,11 +370,34 @@ fn coresight_component_tree(
         Component::CoreLinkOrPrimeCellOrSystemComponent(_) => {
             Tree::new("Core Link / Prime Cell / System component".to_string())
         }
+        Component::Unknown(id) => unknown_component_details(
+            Tree::new(format!("Unknown component (Class {:#x})", id.raw().class())),
+            id,
+        ),
     };
 
     Ok(tree)
 }
 
+/// Add the raw identification registers and the start of a component which probe-rs does not
+/// know, so it can be reported.
+fn unknown_component_details(mut tree: Tree<String>, id: &ComponentId) -> Tree<String> {
+    let Some(dump) = id.dump() else {
+        return tree;
+    };
+
+    tree.push(format!("Address: {:#010x}", id.component_address()));
+    tree.push(id.raw().to_string());
+    if !id.raw().has_valid_preamble() {
+        tree.push("The CIDR preamble is invalid".to_string());
+    }
+    for line in dump.to_string().lines() {
+        tree.push(line.to_string());
+    }
+
+    tree
+}
+
 fn cpu_info_tree(scs: &mut Scs) -> Result<Tree<String>> {
     let mut tree = Tree::new("CPUID".into());
 
@@ -1,6 +1,19 @@
 //! Provides ITM tracing capabilities.
 
-use probe_rs::architecture::arm::{component::TraceSink, swo::SwoConfig};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use itm::{
+    cortex_m::VectActive, ExceptionAction, LocalTimestampOptions, Timestamp,
+    TimestampsConfiguration, TracePacket,
+};
+use probe_rs::architecture::arm::{
+    component::{GlobalTimestampFrequency, ItmConfig, LocalTimestampPrescaler, TraceSink},
+    swo::{self, ExceptionEvent, ExceptionProfile, ExceptionProfiler, SwoConfig},
+    SwoReader, SwoStatistics, SwoTimestamp,
+};
 
 use crate::util::{common_options::ProbeOptions, parse_u64};
 use crate::CoreOptions;
@@ -23,6 +36,67 @@ pub(crate) enum ItmSource {
     },
 }
 
+/// The prescaler of the ITM local timestamps.
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum LocalTimestamps {
+    /// Disable local timestamps.
+    Off,
+    /// Count every cycle.
+    Div1,
+    /// Count every 4th cycle.
+    Div4,
+    /// Count every 16th cycle.
+    Div16,
+    /// Count every 64th cycle.
+    Div64,
+}
+
+impl LocalTimestamps {
+    fn prescaler(self) -> Option<LocalTimestampPrescaler> {
+        match self {
+            LocalTimestamps::Off => None,
+            LocalTimestamps::Div1 => Some(LocalTimestampPrescaler::Div1),
+            LocalTimestamps::Div4 => Some(LocalTimestampPrescaler::Div4),
+            LocalTimestamps::Div16 => Some(LocalTimestampPrescaler::Div16),
+            LocalTimestamps::Div64 => Some(LocalTimestampPrescaler::Div64),
+        }
+    }
+
+    fn decoder_options(self) -> LocalTimestampOptions {
+        match self {
+            LocalTimestamps::Off => LocalTimestampOptions::Disabled,
+            LocalTimestamps::Div1 => LocalTimestampOptions::Enabled,
+            LocalTimestamps::Div4 => LocalTimestampOptions::EnabledDiv4,
+            LocalTimestamps::Div16 => LocalTimestampOptions::EnabledDiv16,
+            LocalTimestamps::Div64 => LocalTimestampOptions::EnabledDiv64,
+        }
+    }
+}
+
+/// How often the ITM emits global timestamps.
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum GlobalTimestamps {
+    /// Disable global timestamps.
+    Off,
+    /// Approximately every 128 cycles.
+    Every128,
+    /// Approximately every 8192 cycles.
+    Every8192,
+    /// After every packet.
+    All,
+}
+
+impl From<GlobalTimestamps> for GlobalTimestampFrequency {
+    fn from(value: GlobalTimestamps) -> Self {
+        match value {
+            GlobalTimestamps::Off => GlobalTimestampFrequency::Disabled,
+            GlobalTimestamps::Every128 => GlobalTimestampFrequency::Every128Cycles,
+            GlobalTimestamps::Every8192 => GlobalTimestampFrequency::Every8192Cycles,
+            GlobalTimestamps::All => GlobalTimestampFrequency::EveryPacket,
+        }
+    }
+}
+
 #[derive(clap::Parser)]
 pub struct Cmd {
     #[clap(flatten)]
@@ -34,21 +108,66 @@ pub struct Cmd {
     #[clap(value_parser = parse_u64)]
     duration_ms: u64,
 
+    /// The prescaler of the ITM local timestamps, which count processor clock cycles.
+    #[clap(long, value_enum, default_value = "div1")]
+    local_timestamps: LocalTimestamps,
+
+    /// How often the ITM emits global timestamps.
+    #[clap(long, value_enum, default_value = "every8192")]
+    global_timestamps: GlobalTimestamps,
+
+    /// Print the time at which the data of each packet was received from the probe,
+    /// relative to the start of the trace.
+    #[clap(long)]
+    receive_time: bool,
+
+    /// Instead of printing the packets, print the count, duration and CPU load of each
+    /// exception handler at the end of the trace. Requires local timestamps and SWO.
+    #[clap(long)]
+    exception_profile: bool,
+
     #[clap(subcommand)]
     source: ItmSource,
 }
 
 impl Cmd {
     pub fn run(self) -> anyhow::Result<()> {
-        let sink = match self.source {
-            ItmSource::TraceMemory => TraceSink::TraceMemory,
-            ItmSource::Swo { clk, baud } => TraceSink::Swo(SwoConfig::new(clk).set_baud(baud)),
+        let (sink, clock) = match self.source {
+            ItmSource::TraceMemory => (TraceSink::TraceMemory, None),
+            ItmSource::Swo { clk, baud } => (
+                TraceSink::Swo(SwoConfig::new(clk).set_baud(baud)),
+                Some(clk),
+            ),
+        };
+
+        let itm_config = ItmConfig::default()
+            .set_local_timestamps(self.local_timestamps.prescaler())
+            .set_global_timestamps(self.global_timestamps.into());
+
+        // Timestamps can only be converted to a time if the clock of the timestamp counter
+        // is known, which is assumed to be the TPIU clock.
+        let timestamps = match (clock, self.local_timestamps) {
+            (_, LocalTimestamps::Off) | (None, _) => None,
+            (Some(clock_frequency), local_timestamps) => Some(TimestampsConfiguration {
+                clock_frequency,
+                lts_prescaler: local_timestamps.decoder_options(),
+                expect_malformed: true,
+            }),
         };
+
+        if self.exception_profile && timestamps.is_none() {
+            bail!("The exception profile needs local timestamps and the clock frequency of SWO.");
+        }
+
         itm_trace(
             &self.shared,
             &self.common,
             sink,
-            std::time::Duration::from_millis(self.duration_ms),
+            &itm_config,
+            timestamps,
+            self.receive_time,
+            self.exception_profile,
+            Duration::from_millis(self.duration_ms),
         )
     }
 }
@@ -59,34 +178,198 @@ impl Cmd {
 /// * `shared_options` - Specifies information about which core to trace.
 /// * `common` - Specifies information about the probe to use for tracing.
 /// * `sink` - Specifies the destination for trace data.
+/// * `itm_config` - Specifies the timestamps generated by the ITM.
+/// * `timestamps` - Specifies how timestamps are decoded, or `None` to print packets without them.
+/// * `receive_time` - Specifies whether the time at which packets were received is printed.
+/// * `exception_profile` - Specifies whether exception statistics are printed instead of packets.
 /// * `duration` - Specifies the duration to trace for.
 /// * `output_file` - An optionally specified filename to write ITM binary data into.
+#[allow(clippy::too_many_arguments)]
 fn itm_trace(
     shared_options: &CoreOptions,
     common: &ProbeOptions,
     sink: TraceSink,
-    duration: std::time::Duration,
+    itm_config: &ItmConfig,
+    timestamps: Option<TimestampsConfiguration>,
+    receive_time: bool,
+    exception_profile: bool,
+    duration: Duration,
 ) -> anyhow::Result<()> {
     let mut session = common.simple_attach()?;
 
-    session.setup_tracing(shared_options.core, sink)?;
+    session.setup_tracing_with_config(shared_options.core, sink, itm_config)?;
 
+    let received = Rc::new(Cell::new(None));
+    let data_lost = Rc::new(Cell::new(false));
+    let statistics = Rc::new(Cell::new(SwoStatistics::default()));
     let decoder = itm::Decoder::new(
-        session.swo_reader()?,
+        ReceiveTimeReader {
+            reader: session.swo_reader()?,
+            timestamp: received.clone(),
+            data_lost: data_lost.clone(),
+            statistics: statistics.clone(),
+        },
         itm::DecoderOptions { ignore_eof: true },
     );
 
-    let start = std::time::Instant::now();
-    let iter = decoder.singles();
+    let start = Instant::now();
+    let prefix = || match (receive_time, received.get()) {
+        (false, _) | (true, None) => String::new(),
+        (true, Some(SwoTimestamp::Host(time))) => {
+            format!("{:?} ", time.saturating_duration_since(start))
+        }
+        (true, Some(SwoTimestamp::Probe(time))) => format!("{time:?} "),
+    };
+    let mut itm_overflows = 0;
+    let mut check_data_lost = |packet: &TracePacket| {
+        if data_lost.take() {
+            println!(
+                "{}Data was lost: the SWO buffer of the probe overflowed",
+                prefix()
+            );
+        }
+        if *packet == TracePacket::Overflow {
+            itm_overflows += 1;
+        }
+    };
+
+    let mut profiler = ExceptionProfiler::new();
 
-    // Decode and print the ITM data for display.
-    for packet in iter {
-        if start.elapsed() > duration {
-            return Ok(());
+    if let Some(timestamps) = timestamps {
+        // Decode the packets together with the timestamp of the target.
+        for packets in decoder.timestamps(timestamps) {
+            if start.elapsed() > duration {
+                break;
+            }
+
+            match packets {
+                Ok(packets) => {
+                    for packet in packets.packets {
+                        check_data_lost(&packet);
+                        if exception_profile {
+                            if let Some(event) = exception_event(&packets.timestamp, &packet) {
+                                profiler.record(event);
+                            }
+                            continue;
+                        }
+                        println!("{}{:?} {packet:?}", prefix(), packets.timestamp);
+                    }
+                    for packet in packets.malformed_packets {
+                        println!("{}{:?} {packet:?}", prefix(), packets.timestamp);
+                    }
+                }
+                Err(e) => println!("{e:?}"),
+            }
         }
+    } else {
+        // Decode and print the ITM data for display.
+        for packet in decoder.singles() {
+            if start.elapsed() > duration {
+                break;
+            }
 
-        println!("{packet:?}");
+            if let Ok(packet) = &packet {
+                check_data_lost(packet);
+            }
+            println!("{}{packet:?}", prefix());
+        }
     }
 
+    if exception_profile {
+        print_exception_profile(&profiler.profile());
+    }
+
+    let statistics = statistics.get();
+    println!(
+        "Received {} bytes. Data was lost {} times because the probe buffer overflowed, and {} times because the ITM overflowed.",
+        statistics.bytes, statistics.probe_overflows, itm_overflows
+    );
+
     Ok(())
 }
+
+/// The exception trace event of `packet`, or `None` if it is another packet.
+fn exception_event(timestamp: &Timestamp, packet: &TracePacket) -> Option<ExceptionEvent> {
+    let TracePacket::ExceptionTrace { exception, action } = packet else {
+        return None;
+    };
+
+    // For delayed packets, the latest possible time of the event is used.
+    let time = match *timestamp {
+        Timestamp::Sync(time) | Timestamp::AssocEventDelay(time) => time,
+        Timestamp::UnknownDelay { curr, .. } | Timestamp::UnknownAssocEventDelay { curr, .. } => {
+            curr
+        }
+    };
+
+    let exception = match *exception {
+        VectActive::ThreadMode => 0,
+        VectActive::Exception(exception) => (16 + exception.irqn() as i16) as u16,
+        VectActive::Interrupt { irqn } => 16 + irqn,
+    };
+
+    let action = match action {
+        ExceptionAction::Entered => swo::ExceptionAction::Entered,
+        ExceptionAction::Exited => swo::ExceptionAction::Exited,
+        ExceptionAction::Returned => swo::ExceptionAction::Returned,
+    };
+
+    Some(ExceptionEvent {
+        time,
+        exception,
+        action,
+    })
+}
+
+fn print_exception_profile(profile: &ExceptionProfile) {
+    println!(
+        "{:<14} {:>10} {:>14} {:>12} {:>12} {:>8}",
+        "Exception", "Count", "Total", "Min", "Max", "Load"
+    );
+    for exception in &profile.exceptions {
+        println!(
+            "{:<14} {:>10} {:>14} {:>12} {:>12} {:>7.2}%",
+            exception.name(),
+            exception.count,
+            format!("{:?}", exception.total),
+            format!("{:?}", exception.min),
+            format!("{:?}", exception.max),
+            exception.load * 100.0
+        );
+    }
+    println!(
+        "{:.2}% of {:?} were spent in exception handlers.",
+        profile.total_load() * 100.0,
+        profile.window
+    );
+    if profile.unmatched_events > 0 {
+        println!(
+            "{} exception events did not match the active exceptions, trace packets were lost.",
+            profile.unmatched_events
+        );
+    }
+}
+
+/// Records the time at which the SWO data read by the decoder was received.
+///
+/// The decoder reads ahead of the packets it returns, so the time belongs to
+/// data received up to 32 bytes after the packet.
+struct ReceiveTimeReader<'a> {
+    reader: SwoReader<'a>,
+    timestamp: Rc<Cell<Option<SwoTimestamp>>>,
+    /// Set when data was lost before the data read by the decoder, until it is reported.
+    data_lost: Rc<Cell<bool>>,
+    statistics: Rc<Cell<SwoStatistics>>,
+}
+
+impl std::io::Read for ReceiveTimeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.timestamp.set(self.reader.timestamp());
+        if self.reader.data_lost() {
+            self.data_lost.set(true);
+        }
+        self.statistics.set(self.reader.statistics());
+        Ok(len)
+    }
+}
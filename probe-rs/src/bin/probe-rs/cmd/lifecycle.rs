@@ -0,0 +1,82 @@
+use probe_rs::architecture::arm::sequences::LifecycleState;
+use probe_rs::Permissions;
+
+use crate::util::common_options::ProbeOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+/// Read and advance the lifecycle state of the target, which restricts debug access
+enum Subcommand {
+    /// Reports the lifecycle state of the target.
+    #[clap(name = "show")]
+    Show {
+        #[clap(flatten)]
+        common: ProbeOptions,
+    },
+    /// Advances the target to a later lifecycle state. Debug access is restricted afterwards,
+    /// and can usually only be restored by erasing the target. This may be irreversible.
+    #[clap(name = "advance")]
+    Advance {
+        #[clap(flatten)]
+        common: ProbeOptions,
+        /// The lifecycle state to enter.
+        #[clap(value_enum)]
+        state: State,
+        /// Confirms that debug access to the target may be restricted irreversibly.
+        #[clap(long)]
+        allow_lifecycle_change: bool,
+    },
+}
+
+/// The lifecycle states which can be entered.
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum State {
+    SecureProtected,
+    Protected,
+}
+
+impl From<State> for LifecycleState {
+    fn from(state: State) -> Self {
+        match state {
+            State::SecureProtected => LifecycleState::SecureProtected,
+            State::Protected => LifecycleState::Protected,
+        }
+    }
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        match self.subcommand {
+            Subcommand::Show { common } => {
+                let mut session = common.simple_attach()?;
+
+                println!("{}", session.lifecycle_state()?);
+            }
+            Subcommand::Advance {
+                common,
+                state,
+                allow_lifecycle_change,
+            } => {
+                let mut permissions = Permissions::new();
+                if allow_lifecycle_change {
+                    permissions = permissions.allow_lifecycle_change();
+                }
+
+                let mut session = common.simple_attach()?;
+                session.advance_lifecycle(state.into(), &permissions)?;
+
+                println!(
+                    "The target was advanced to lifecycle state {}, which takes effect after the next reset.",
+                    LifecycleState::from(state)
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -11,6 +11,11 @@ impl Cmd {
             println!("The following debug probes were found:");
             for (num, link) in probes.iter().enumerate() {
                 println!("[{num}]: {link:?}");
+
+                // Listing the serial ports is best effort, a probe is usable without them.
+                for port in link.serial_ports().unwrap_or_default() {
+                    println!("     Serial port: {port}");
+                }
             }
         } else {
             println!("No debug probes were found.");
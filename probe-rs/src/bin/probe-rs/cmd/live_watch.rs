@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use probe_rs::debug::{DebugInfo, LiveWatch};
+
+use crate::util::common_options::ProbeOptions;
+use crate::CoreOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The ELF file of the program running on the target.
+    #[clap(value_parser)]
+    elf: PathBuf,
+
+    /// The expressions to watch, paths to static variables such as `COUNTER` or `STATE.mode`.
+    #[clap(required = true)]
+    expressions: Vec<String>,
+
+    /// The time between two samples, in milliseconds.
+    #[clap(long, default_value = "100")]
+    interval_ms: u64,
+
+    /// Print each sample as a line of JSON, instead of a table.
+    #[clap(long)]
+    json: bool,
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let debug_info = DebugInfo::from_file(&self.elf)
+            .with_context(|| format!("Failed to read the debug info of {}", self.elf.display()))?;
+
+        let mut watch = LiveWatch::new();
+        for expression in &self.expressions {
+            watch.add(&debug_info, expression)?;
+        }
+
+        let mut session = self.common.simple_attach()?;
+        let mut core = session.core(self.shared.core)?;
+
+        watch.run(
+            &mut core,
+            Duration::from_millis(self.interval_ms),
+            |samples| {
+                if self.json {
+                    match serde_json::to_string(samples) {
+                        Ok(line) => println!("{line}"),
+                        Err(error) => tracing::warn!("Failed to serialize the samples: {error}"),
+                    }
+                    return true;
+                }
+
+                for sample in samples {
+                    let value = sample
+                        .value
+                        .as_ref()
+                        .map_or_else(|| "unreadable".to_owned(), |value| value.to_string());
+                    println!(
+                        "[{:>10.3}s] {} = {}",
+                        sample.timestamp.as_secs_f64(),
+                        sample.expression,
+                        value
+                    );
+                }
+                true
+            },
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use probe_rs::{DebugProbeSelector, Probe, ProbeSetting};
+
+#[derive(clap::Parser)]
+/// Change a setting of a debug probe. Only switching the target power of J-Link probes is
+/// supported, which lasts until the probe is power cycled.
+pub struct Cmd {
+    /// The probe to configure, as VID:PID or VID:PID:Serial.
+    #[clap(long = "probe")]
+    probe_selector: DebugProbeSelector,
+    /// The setting to change.
+    setting: ProbeSetting,
+    /// Whether the setting is switched on or off.
+    state: State,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum State {
+    On,
+    Off,
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let mut probe = Probe::open(self.probe_selector)?;
+        let enabled = matches!(self.state, State::On);
+
+        probe.write_setting(self.setting, enabled)?;
+
+        println!(
+            "Switched {} {}.",
+            self.setting,
+            if enabled { "on" } else { "off" }
+        );
+
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
+mod export;
+
 use std::collections::HashMap;
 use std::fs::File;
-use std::path::Path;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use anyhow::Context;
@@ -44,6 +47,12 @@ pub struct Cmd {
     /// Limit the number of entries to output
     #[clap(long, default_value_t = 25)]
     limit: usize,
+    /// Write the profile as folded stacks, for flamegraph tools such as `inferno-flamegraph`
+    #[clap(long)]
+    flamegraph: Option<PathBuf>,
+    /// Write the profile in the callgrind format, for tools such as KCachegrind
+    #[clap(long)]
+    callgrind: Option<PathBuf>,
     /// Profile Method
     #[clap(subcommand)]
     method: ProfileMethod,
@@ -104,9 +113,16 @@ impl Cmd {
                     reset_halt: false,
                     log: None,
                     restore_unwritten: false,
+                    gap_fill: None,
+                    stamp_checksums: false,
                     flash_layout_output_path: None,
+                    memory_usage: false,
                     elf: None,
                     work_dir: None,
+                    journal: None,
+                    golden_image: None,
+                    delta: false,
+                    verify: false,
                     cargo_options: CargoOptions::default(),
                     probe_options: self.run.common,
                 },
@@ -168,6 +184,22 @@ impl Cmd {
             }
         }
 
+        if let Some(path) = &self.flamegraph {
+            let mut writer = BufWriter::new(File::create(path)?);
+            export::write_folded_stacks(&mut writer, &samples, |address| {
+                symbols.get_frames(address)
+            })
+            .with_context(|| format!("Failed to write the flamegraph to {}", path.display()))?;
+        }
+
+        if let Some(path) = &self.callgrind {
+            let mut writer = BufWriter::new(File::create(path)?);
+            export::write_callgrind(&mut writer, &self.run.path, &samples, |address| {
+                symbols.get_frames(address)
+            })
+            .with_context(|| format!("Failed to write the callgrind file to {}", path.display()))?;
+        }
+
         let mut v = Vec::from_iter(samples);
         // sort by frequency
         v.sort_by(|&(_, a), &(_, b)| b.cmp(&a));
@@ -239,6 +271,44 @@ impl<'sym> Symbols<'sym> {
             })
     }
 
+    /// Returns the functions at the given address, the innermost inlined function first.
+    pub fn get_frames(&self, addr: u64) -> Vec<export::Frame> {
+        let mut frames = match self.ctx.find_frames(addr) {
+            LookupResult::Output(Ok(frames)) => frames,
+            _ => return Vec::new(),
+        };
+
+        let mut result = Vec::new();
+        while let Ok(Some(frame)) = frames.next() {
+            let function = frame
+                .function
+                .and_then(|name| name.demangle().map(|s| s.into_owned()).ok());
+            let (file, line) = match frame.location {
+                Some(location) => (location.file.map(|f| f.to_owned()), location.line),
+                None => (None, None),
+            };
+
+            result.push(export::Frame {
+                function: function.unwrap_or_else(|| "???".to_owned()),
+                file,
+                line: line.unwrap_or(0),
+            });
+        }
+
+        // Without debug information, fall back to the symbol table.
+        if result.is_empty() {
+            if let Some(name) = self.get_name(addr) {
+                result.push(export::Frame {
+                    function: name,
+                    file: None,
+                    line: 0,
+                });
+            }
+        }
+
+        result
+    }
+
     /// Returns the file name and line number of the function at the given address, if one can be.
     pub fn get_location(&self, addr: u64) -> Option<(String, u32)> {
         // Find the location which `addr` is in. If we can dedetermine a file name and
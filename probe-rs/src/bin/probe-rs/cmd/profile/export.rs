@@ -0,0 +1,243 @@
+//! Export of profiles to the file formats of existing visualization tools.
+//!
+//! - Folded stacks, one line per call stack with the number of samples, as read by
+//!   `flamegraph.pl` and `inferno-flamegraph`.
+//! - The callgrind format, as read by KCachegrind and QCachegrind.
+//!
+//! The call stacks of a sample are the inlined functions at the sampled address, as the
+//! samples only contain the program counter.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+/// A function at a sampled address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Frame {
+    /// The demangled name of the function.
+    pub function: String,
+    /// The source file of the location in the function, if known.
+    pub file: Option<String>,
+    /// The source line of the location in the function, or 0 if unknown.
+    pub line: u32,
+}
+
+/// The frames at `address`, the innermost function first, or the address as function name
+/// if no symbol is found.
+fn frames_or_address(lookup: &impl Fn(u64) -> Vec<Frame>, address: u32) -> Vec<Frame> {
+    let frames = lookup(address as u64);
+
+    if frames.is_empty() {
+        vec![Frame {
+            function: format!("UNKNOWN - {address:08X}"),
+            file: None,
+            line: 0,
+        }]
+    } else {
+        frames
+    }
+}
+
+/// Write `samples`, the number of samples per address, as folded stacks.
+///
+/// `lookup` returns the frames at an address, the innermost function first.
+pub(crate) fn write_folded_stacks(
+    writer: &mut impl Write,
+    samples: &HashMap<u32, u64>,
+    lookup: impl Fn(u64) -> Vec<Frame>,
+) -> io::Result<()> {
+    let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+
+    for (&address, &count) in samples {
+        let frames = frames_or_address(&lookup, address);
+        let stack = frames
+            .iter()
+            .rev()
+            .map(|frame| frame.function.replace(';', ":"))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        *stacks.entry(stack).or_default() += count;
+    }
+
+    for (stack, count) in stacks {
+        writeln!(writer, "{stack} {count}")?;
+    }
+
+    Ok(())
+}
+
+/// A line of a function in a callgrind file.
+enum CostLine {
+    /// Samples in the function itself.
+    Own { address: u32, line: u32, count: u64 },
+    /// Samples in an inlined function called at `line`.
+    Call {
+        callee: (String, String),
+        callee_line: u32,
+        address: u32,
+        line: u32,
+        count: u64,
+    },
+}
+
+/// Write `samples`, the number of samples per address, in the callgrind format.
+///
+/// `object` is the path of the profiled ELF file, and `lookup` returns the frames at an
+/// address, the innermost function first.
+pub(crate) fn write_callgrind(
+    writer: &mut impl Write,
+    object: &str,
+    samples: &HashMap<u32, u64>,
+    lookup: impl Fn(u64) -> Vec<Frame>,
+) -> io::Result<()> {
+    let mut functions: BTreeMap<(String, String), Vec<CostLine>> = BTreeMap::new();
+
+    let mut addresses: Vec<_> = samples.iter().collect();
+    addresses.sort();
+
+    for (&address, &count) in addresses {
+        let frames = frames_or_address(&lookup, address);
+        let key = |frame: &Frame| {
+            (
+                frame.file.clone().unwrap_or_else(|| "???".to_owned()),
+                frame.function.clone(),
+            )
+        };
+
+        functions
+            .entry(key(&frames[0]))
+            .or_default()
+            .push(CostLine::Own {
+                address,
+                line: frames[0].line,
+                count,
+            });
+
+        for pair in frames.windows(2) {
+            let (callee, caller) = (&pair[0], &pair[1]);
+            functions
+                .entry(key(caller))
+                .or_default()
+                .push(CostLine::Call {
+                    callee: key(callee),
+                    callee_line: callee.line,
+                    address,
+                    line: caller.line,
+                    count,
+                });
+        }
+    }
+
+    let total: u64 = samples.values().sum();
+
+    writeln!(writer, "# callgrind format")?;
+    writeln!(writer, "version: 1")?;
+    writeln!(writer, "creator: probe-rs")?;
+    writeln!(writer, "positions: instr line")?;
+    writeln!(writer, "events: Samples")?;
+    writeln!(writer, "summary: {total}")?;
+    writeln!(writer)?;
+    writeln!(writer, "ob={object}")?;
+
+    for ((file, function), lines) in functions {
+        writeln!(writer)?;
+        writeln!(writer, "fl={file}")?;
+        writeln!(writer, "fn={function}")?;
+
+        for line in lines {
+            match line {
+                CostLine::Own {
+                    address,
+                    line,
+                    count,
+                } => writeln!(writer, "{address:#x} {line} {count}")?,
+                CostLine::Call {
+                    callee: (callee_file, callee_function),
+                    callee_line,
+                    address,
+                    line,
+                    count,
+                } => {
+                    writeln!(writer, "cfl={callee_file}")?;
+                    writeln!(writer, "cfn={callee_function}")?;
+                    writeln!(writer, "calls=1 {address:#x} {callee_line}")?;
+                    writeln!(writer, "{address:#x} {line} {count}")?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_callgrind, write_folded_stacks, Frame};
+    use std::collections::HashMap;
+
+    fn frame(function: &str, line: u32) -> Frame {
+        Frame {
+            function: function.to_owned(),
+            file: Some("src/main.rs".to_owned()),
+            line,
+        }
+    }
+
+    /// `inner` is inlined into `main` at 0x100, 0x200 is in `main` itself.
+    fn lookup(address: u64) -> Vec<Frame> {
+        match address {
+            0x100 => vec![frame("inner", 3), frame("main", 10)],
+            0x200 => vec![frame("main", 12)],
+            _ => vec![],
+        }
+    }
+
+    fn samples() -> HashMap<u32, u64> {
+        HashMap::from([(0x100, 5), (0x200, 2), (0x300, 1)])
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let mut output = Vec::new();
+        write_folded_stacks(&mut output, &samples(), lookup).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "UNKNOWN - 00000300 1\nmain 2\nmain;inner 5\n"
+        );
+    }
+
+    #[test]
+    fn callgrind() {
+        let mut output = Vec::new();
+        write_callgrind(&mut output, "firmware.elf", &samples(), lookup).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# callgrind format\n\
+             version: 1\n\
+             creator: probe-rs\n\
+             positions: instr line\n\
+             events: Samples\n\
+             summary: 8\n\
+             \n\
+             ob=firmware.elf\n\
+             \n\
+             fl=???\n\
+             fn=UNKNOWN - 00000300\n\
+             0x300 0 1\n\
+             \n\
+             fl=src/main.rs\n\
+             fn=inner\n\
+             0x100 3 5\n\
+             \n\
+             fl=src/main.rs\n\
+             fn=main\n\
+             cfl=src/main.rs\n\
+             cfn=inner\n\
+             calls=1 0x100 3\n\
+             0x100 10 5\n\
+             0x200 12 2\n"
+        );
+    }
+}
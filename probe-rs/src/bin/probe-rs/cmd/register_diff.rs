@@ -0,0 +1,164 @@
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use probe_rs::register_snapshot::{RegisterSnapshot, WatchedRegister};
+use svd_parser::svd::Access;
+
+use crate::util::{common_options::ProbeOptions, parse_u64};
+use crate::CoreOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// Registers to compare, as `NAME=ADDRESS` or `ADDRESS`, for example `RCC.CR=0x40021000`.
+    #[clap(value_parser = parse_register)]
+    registers: Vec<WatchedRegister>,
+
+    /// An SVD file of the chip, from which the registers of the `--peripheral`s are taken.
+    #[clap(long, requires = "peripherals")]
+    svd: Option<PathBuf>,
+
+    /// The peripherals of the SVD file whose registers are compared, for example `RCC`.
+    #[clap(long = "peripheral", id = "peripherals", requires = "svd")]
+    peripherals: Vec<String>,
+
+    /// Instead of waiting for Enter, let the core run for this many milliseconds between the
+    /// snapshots, and halt it again.
+    #[clap(long)]
+    run_ms: Option<u64>,
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let mut registers = self.registers;
+        if let Some(svd) = &self.svd {
+            registers.extend(svd_registers(svd, &self.peripherals)?);
+        }
+        if registers.is_empty() {
+            bail!("No registers to compare, pass addresses or an SVD file and peripherals.");
+        }
+
+        let mut session = self.common.simple_attach()?;
+        let mut core = session.core(self.shared.core)?;
+
+        let before = RegisterSnapshot::capture(&mut core, &registers)?;
+        for register in before.registers.iter().filter(|r| r.value.is_none()) {
+            println!(
+                "Warning: {} at {:#010x} cannot be read.",
+                register.register.name, register.register.address
+            );
+        }
+
+        let mut action_error = None;
+        match self.run_ms {
+            Some(run_ms) => {
+                core.run()?;
+                std::thread::sleep(Duration::from_millis(run_ms));
+                // Halting fails if the firmware disabled debug access, the registers which can
+                // still be read are compared anyway.
+                action_error = core.halt(Duration::from_millis(100)).err();
+            }
+            None => {
+                println!("Perform the action, then press Enter.");
+                std::io::stdin().lock().read_line(&mut String::new())?;
+            }
+        }
+
+        let after = RegisterSnapshot::capture(&mut core, &registers)?;
+        let changes = before.diff(&after);
+
+        for change in &changes {
+            let value = |value: Option<u32>| {
+                value.map_or_else(|| "unreadable".to_owned(), |value| format!("{value:#010x}"))
+            };
+            print!(
+                "{} ({:#010x}): {} -> {}",
+                change.name,
+                change.address,
+                value(change.before),
+                value(change.after)
+            );
+            match change.changed_bits() {
+                Some(bits) => println!(" (changed bits {bits:#010x})"),
+                None => println!(),
+            }
+        }
+        println!(
+            "{} of {} registers changed.",
+            changes.len(),
+            registers.len()
+        );
+
+        if after.registers.iter().all(|r| r.value.is_none()) {
+            println!("No register could be read after the action, debug access might be lost.");
+        }
+        if let Some(error) = action_error {
+            println!("The core could not be halted after running: {error}");
+        }
+
+        Ok(())
+    }
+}
+
+/// The readable registers of the `peripherals` in the SVD file at `path`.
+///
+/// Registers which cannot be read, or whose read has side effects, are left out.
+fn svd_registers(path: &Path, peripherals: &[String]) -> anyhow::Result<Vec<WatchedRegister>> {
+    let xml = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the SVD file {}", path.display()))?;
+    let device = svd_parser::parse_with_config(
+        &xml,
+        &svd_parser::Config::default()
+            .expand(true)
+            .ignore_enums(true),
+    )
+    .with_context(|| format!("Failed to parse the SVD file {}", path.display()))?;
+
+    let mut registers = Vec::new();
+    for name in peripherals {
+        let peripheral = device
+            .peripherals
+            .iter()
+            .find(|peripheral| peripheral.name.eq_ignore_ascii_case(name))
+            .with_context(|| format!("The SVD file has no peripheral {name}"))?;
+
+        registers.extend(
+            peripheral
+                .all_registers()
+                .filter(|register| {
+                    register.read_action.is_none()
+                        && !matches!(
+                            register.properties.access,
+                            Some(Access::WriteOnly | Access::WriteOnce)
+                        )
+                })
+                .map(|register| {
+                    WatchedRegister::new(
+                        format!("{}.{}", peripheral.name, register.name),
+                        peripheral.base_address + register.address_offset as u64,
+                    )
+                }),
+        );
+    }
+
+    Ok(registers)
+}
+
+/// Parse a register given as `NAME=ADDRESS` or `ADDRESS`.
+fn parse_register(input: &str) -> Result<WatchedRegister, String> {
+    let (name, address) = match input.split_once('=') {
+        Some((name, address)) => (name.to_owned(), address),
+        None => (input.to_owned(), input),
+    };
+
+    let address = parse_u64(address).map_err(|e| format!("invalid address: {e}"))?;
+
+    Ok(WatchedRegister::new(name, address))
+}
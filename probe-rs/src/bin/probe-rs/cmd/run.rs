@@ -1,17 +1,24 @@
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use anyhow::Context;
+use probe_rs::architecture::arm::armv6m::Demcr;
+use probe_rs::debug::{DebugInfo, FaultReport};
 use probe_rs::flashing::{FileDownloadError, Format};
+use probe_rs::{Core, MemoryInterface, MemoryMappedRegister};
 use time::UtcOffset;
 
+use crate::cmd::dap_server::debug_adapter::dap::request_helpers::get_capstone;
 use crate::util::common_options::{CargoOptions, FlashOptions, ProbeOptions};
 use crate::util::flash::run_flash_download;
 use crate::util::rtt;
 use crate::FormatOptions;
 
+/// The number of stack frames in a fault report.
+const MAX_REPORT_FRAMES: usize = 16;
+
 #[derive(clap::Parser)]
 pub struct Cmd {
     #[clap(flatten)]
@@ -28,6 +35,17 @@ pub struct Cmd {
     #[clap(long = "disable-double-buffering")]
     pub(crate) disable_double_buffering: bool,
 
+    /// Halt on a fault, and write a JSON report of the registers, fault status, backtrace and
+    /// faulting instruction to this file
+    #[clap(long)]
+    pub(crate) fault_report: Option<PathBuf>,
+
+    /// Forward RTT output to the log as events of the `firmware` target, with their level and
+    /// location, instead of printing it. Use `RUST_LOG=firmware=trace` to also show them on
+    /// the console.
+    #[clap(long)]
+    pub(crate) rtt_to_log: bool,
+
     #[clap(flatten)]
     pub(crate) format_options: FormatOptions,
 }
@@ -60,9 +78,16 @@ impl Cmd {
                 reset_halt: false,
                 log: None,
                 restore_unwritten: false,
+                gap_fill: None,
+                stamp_checksums: false,
                 flash_layout_output_path: None,
+                memory_usage: false,
                 elf: None,
                 work_dir: None,
+                journal: None,
+                golden_image: None,
+                delta: false,
+                verify: false,
                 cargo_options: CargoOptions::default(),
                 probe_options: self.common,
             },
@@ -75,6 +100,11 @@ impl Cmd {
         let memory_map = session.target().memory_map.clone();
 
         let mut core = session.core(0)?;
+
+        if self.fault_report.is_some() {
+            enable_fault_catch(&mut core)?;
+        }
+
         core.reset()?;
 
         let mut rtta = match rtt::attach_to_rtt(
@@ -91,21 +121,82 @@ impl Cmd {
             }
         };
 
-        if let Some(rtta) = &mut rtta {
-            let mut stdout = std::io::stdout();
-            loop {
-                for (_ch, data) in rtta.poll_rtt_fallible(&mut core)? {
-                    stdout.write_all(data.as_bytes())?;
+        if rtta.is_none() && self.fault_report.is_none() {
+            return Ok(());
+        }
+
+        let mut stdout = std::io::stdout();
+        loop {
+            if let Some(rtta) = &mut rtta {
+                if self.rtt_to_log {
+                    for record in rtta.poll_rtt_records(&mut core)? {
+                        record.emit();
+                    }
+                } else {
+                    for (_ch, data) in rtta.poll_rtt_fallible(&mut core)? {
+                        stdout.write_all(data.as_bytes())?;
+                    }
                 }
+            }
 
-                // Poll RTT with a frequency of 10 Hz
-                //
-                // If the polling frequency is too high,
-                // the USB connection to the probe can become unstable.
-                std::thread::sleep(Duration::from_millis(100));
+            if let Some(path) = &self.fault_report {
+                if core.core_halted()? {
+                    return write_fault_report(&mut core, Path::new(&self.path), path);
+                }
             }
+
+            // Poll RTT with a frequency of 10 Hz
+            //
+            // If the polling frequency is too high,
+            // the USB connection to the probe can become unstable.
+            std::thread::sleep(Duration::from_millis(100));
         }
+    }
+}
 
-        Ok(())
+/// Halt Cortex-M cores on HardFaults, to which all faults escalate if their handlers are not
+/// enabled. Breakpoints, for example of `panic-probe`, halt the core without this.
+fn enable_fault_catch(core: &mut Core) -> anyhow::Result<()> {
+    if core.core_type().is_cortex_m() {
+        let mut demcr = Demcr(core.read_word_32(Demcr::get_mmio_address())?);
+        demcr.set_vc_harderr(true);
+        core.write_word_32(Demcr::get_mmio_address(), demcr.into())?;
     }
+
+    Ok(())
+}
+
+/// Write a [`FaultReport`] of the halted `core` to `path`, with a backtrace if the ELF file at
+/// `elf` has debug information.
+fn write_fault_report(core: &mut Core, elf: &Path, path: &Path) -> anyhow::Result<()> {
+    let debug_info = DebugInfo::from_file(elf).ok();
+    let mut report = FaultReport::collect(core, debug_info.as_ref(), MAX_REPORT_FRAMES);
+
+    if let Some(instruction) = &mut report.instruction {
+        let capstone = get_capstone(core)?;
+        instruction.disassembly = capstone
+            .disasm_count(&instruction.bytes, instruction.address, 1)
+            .ok()
+            .and_then(|instructions| {
+                instructions.first().map(|instruction| {
+                    format!(
+                        "{} {}",
+                        instruction.mnemonic().unwrap_or_default(),
+                        instruction.op_str().unwrap_or_default()
+                    )
+                })
+            });
+    }
+
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create the fault report {}", path.display()))?;
+    serde_json::to_writer_pretty(file, &report)?;
+
+    match &report.exception {
+        Some(exception) => println!("The core halted in {exception}"),
+        None => println!("The core halted"),
+    }
+    println!("The fault report was written to {}", path.display());
+
+    Ok(())
 }
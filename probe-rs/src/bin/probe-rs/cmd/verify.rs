@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use probe_rs::flashing::GoldenImage;
+
+use crate::util::common_options::ProbeOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The golden image recorded with `--golden-image` while flashing
+    golden_image: PathBuf,
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let image = GoldenImage::read(&self.golden_image)?;
+
+        let mut session = self.common.simple_attach()?;
+        image.verify(&mut session)?;
+
+        println!("The flash contents match the golden image.");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use std::ops::Range;
+use std::time::Duration;
+
+use probe_rs::memory_watch::MemoryWatch;
+
+use crate::util::{common_options::ProbeOptions, parse_u64};
+use crate::CoreOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The memory regions to watch, as `ADDRESS:SIZE` in bytes, for example `0x20000000:256`.
+    #[clap(value_parser = parse_region, required = true)]
+    regions: Vec<Range<u64>>,
+
+    /// The time between two reads of the regions, in milliseconds.
+    #[clap(long, default_value = "100")]
+    interval_ms: u64,
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let mut session = self.common.simple_attach()?;
+
+        let mut core = session.core(self.shared.core)?;
+
+        let mut watch = MemoryWatch::new(self.regions);
+        watch.run(
+            &mut core,
+            Duration::from_millis(self.interval_ms),
+            |change| {
+                println!(
+                    "[{:>10.3}s] {:#010x}..{:#010x}: {:02x?} -> {:02x?}",
+                    change.timestamp.as_secs_f64(),
+                    change.range.start,
+                    change.range.end,
+                    change.old,
+                    change.new
+                );
+                true
+            },
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Parse a memory region given as `ADDRESS:SIZE`.
+fn parse_region(input: &str) -> Result<Range<u64>, String> {
+    let (address, size) = input
+        .split_once(':')
+        .ok_or_else(|| "expected ADDRESS:SIZE".to_owned())?;
+
+    let address = parse_u64(address).map_err(|e| format!("invalid address: {e}"))?;
+    let size = parse_u64(size).map_err(|e| format!("invalid size: {e}"))?;
+
+    Ok(address..address + size)
+}
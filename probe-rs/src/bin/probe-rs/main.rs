@@ -9,6 +9,7 @@ use std::{ffi::OsString, fs::File, path::PathBuf};
 
 use anyhow::{Context, Result};
 use clap::Parser;
+use probe_rs::events::{set_event_sink, NdjsonEventSink};
 use probe_rs::flashing::{BinOptions, Format, IdfOptions};
 use serde::{de::Error, Deserialize, Deserializer};
 use serde_json::Value;
@@ -35,6 +36,9 @@ struct Cli {
     /// If no location is specified, the log file will be stored in a default directory.
     #[clap(long, global = true)]
     log_file: Option<PathBuf>,
+    /// Write session events (attach, flashing phases, resets, errors) as NDJSON to this file
+    #[clap(long, global = true)]
+    event_log: Option<PathBuf>,
     #[clap(subcommand)]
     subcommand: Subcommand,
 }
@@ -59,16 +63,32 @@ enum Subcommand {
     Download(cmd::download::Cmd),
     /// Erase all nonvolatile memory of attached target
     Erase(cmd::erase::Cmd),
+    /// Verify the flash of attached target against a golden image
+    Verify(cmd::verify::Cmd),
     /// Flash and run an ELF program
     #[clap(name = "run")]
     Run(cmd::run::Cmd),
     /// Trace a memory location on the target
     #[clap(name = "trace")]
     Trace(cmd::trace::Cmd),
+    /// Report changes of memory regions made by the running target
+    #[clap(name = "watch")]
+    Watch(cmd::watch::Cmd),
+    /// Periodically print the values of static variables of the running program
+    #[clap(name = "live-watch")]
+    LiveWatch(cmd::live_watch::Cmd),
+    /// Compare peripheral registers before and after an action on the target
+    #[clap(name = "register-diff")]
+    RegisterDiff(cmd::register_diff::Cmd),
     /// Configure and monitor ITM trace packets from the target.
     #[clap(name = "itm")]
     Itm(cmd::itm::Cmd),
     Chip(cmd::chip::Cmd),
+    Board(cmd::board::Cmd),
+    Firmware(cmd::firmware::Cmd),
+    #[clap(name = "probe-setting")]
+    ProbeSetting(cmd::probe_setting::Cmd),
+    Lifecycle(cmd::lifecycle::Cmd),
     Benchmark(cmd::benchmark::Cmd),
     Profile(cmd::profile::Cmd),
 }
@@ -240,6 +260,12 @@ fn main() -> Result<()> {
 
     tracing::info!("Writing log to {:?}", log_path);
 
+    if let Some(event_log) = &matches.event_log {
+        let event_log = File::create(event_log)
+            .with_context(|| format!("Failed to create event log {}", event_log.display()))?;
+        set_event_sink(NdjsonEventSink::new(event_log));
+    }
+
     let result = match matches.subcommand {
         Subcommand::DapServer { .. } => unreachable!(), // handled above.
         Subcommand::List(cmd) => cmd.run(),
@@ -251,9 +277,17 @@ fn main() -> Result<()> {
         Subcommand::Download(cmd) => cmd.run(),
         Subcommand::Run(cmd) => cmd.run(utc_offset),
         Subcommand::Erase(cmd) => cmd.run(),
+        Subcommand::Verify(cmd) => cmd.run(),
         Subcommand::Trace(cmd) => cmd.run(),
+        Subcommand::Watch(cmd) => cmd.run(),
+        Subcommand::LiveWatch(cmd) => cmd.run(),
+        Subcommand::RegisterDiff(cmd) => cmd.run(),
         Subcommand::Itm(cmd) => cmd.run(),
         Subcommand::Chip(cmd) => cmd.run(),
+        Subcommand::Board(cmd) => cmd.run(),
+        Subcommand::Firmware(cmd) => cmd.run(),
+        Subcommand::ProbeSetting(cmd) => cmd.run(),
+        Subcommand::Lifecycle(cmd) => cmd.run(),
         Subcommand::Benchmark(cmd) => cmd.run(),
         Subcommand::Profile(cmd) => cmd.run(),
     };
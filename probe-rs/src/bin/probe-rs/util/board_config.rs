@@ -0,0 +1,133 @@
+//! Settings of boards, stored by the serial number of their probe.
+//!
+//! Labs with many boards can store the chip, protocol, speed and reset strategy of each board
+//! once with `probe-rs board set`, instead of passing them to every command. The settings are
+//! applied when the probe with the serial number is opened, options given on the command line
+//! take precedence.
+//!
+//! The settings are stored as JSON in `boards.json`, in the configuration directory of probe-rs.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use probe_rs::WireProtocol;
+use serde::{Deserialize, Serialize};
+
+/// The stored settings of a board.
+#[derive(clap::Args, Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoardConfig {
+    /// The chip on the board.
+    #[clap(long)]
+    pub chip: Option<String>,
+    /// The protocol used to connect to the chip. Possible options: [swd, jtag]
+    #[clap(long)]
+    pub protocol: Option<WireProtocol>,
+    /// The protocol speed in kHz.
+    #[clap(long)]
+    pub speed: Option<u32>,
+    /// Attach to the chip under reset.
+    #[clap(long)]
+    pub connect_under_reset: bool,
+    /// The minimum time the reset line is asserted for, in milliseconds.
+    #[clap(long)]
+    pub reset_assert_ms: Option<u64>,
+    /// The time to wait after releasing the reset line, in milliseconds.
+    #[clap(long)]
+    pub reset_release_ms: Option<u64>,
+}
+
+/// The settings of all boards, by probe serial number.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BoardConfigs {
+    boards: BTreeMap<String, BoardConfig>,
+}
+
+impl BoardConfigs {
+    /// The default location of the settings file.
+    pub fn default_path() -> Option<PathBuf> {
+        let project_dirs = directories::ProjectDirs::from("rs", "probe-rs", "probe-rs")?;
+
+        Some(project_dirs.config_dir().join("boards.json"))
+    }
+
+    /// Read the settings from `path`, no settings are stored if the file does not exist.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+        };
+
+        serde_json::from_slice(&contents).context(format!("Failed to parse {}", path.display()))
+    }
+
+    /// Write the settings to `path`.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)
+                .context(format!("{} could not be created", directory.display()))?;
+        }
+
+        let contents = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, contents).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// The settings of the board connected to the probe with serial number `serial`.
+    pub fn get(&self, serial: &str) -> Option<&BoardConfig> {
+        self.boards.get(serial)
+    }
+
+    /// Store the settings of the board connected to the probe with serial number `serial`.
+    pub fn set(&mut self, serial: String, config: BoardConfig) {
+        self.boards.insert(serial, config);
+    }
+
+    /// Remove the settings of the board connected to the probe with serial number `serial`.
+    pub fn remove(&mut self, serial: &str) -> Option<BoardConfig> {
+        self.boards.remove(serial)
+    }
+
+    /// All stored settings, by probe serial number.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &BoardConfig)> {
+        self.boards.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoardConfig, BoardConfigs};
+    use probe_rs::WireProtocol;
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir()
+            .join(format!("probe-rs-boards-{}", std::process::id()))
+            .join("boards.json");
+
+        assert_eq!(BoardConfigs::load(&path).unwrap(), BoardConfigs::default());
+
+        let mut configs = BoardConfigs::default();
+        configs.set(
+            "000683000001".to_owned(),
+            BoardConfig {
+                chip: Some("nRF52840_xxAA".to_owned()),
+                protocol: Some(WireProtocol::Swd),
+                speed: Some(4000),
+                connect_under_reset: true,
+                ..Default::default()
+            },
+        );
+        configs.save(&path).unwrap();
+
+        let loaded = BoardConfigs::load(&path).unwrap();
+        assert_eq!(loaded, configs);
+        assert_eq!(loaded.get("000683000001").unwrap().speed, Some(4000));
+        assert_eq!(loaded.get("other"), None);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -31,16 +31,22 @@
 //!     // ...
 //! }
 //! ```
+use super::board_config::{BoardConfig, BoardConfigs};
 use super::ArtifactError;
 
-use std::{fs::File, path::Path, path::PathBuf};
+use std::{fs::File, path::Path, path::PathBuf, time::Duration};
 
 use clap;
+use once_cell::unsync::OnceCell;
 use probe_rs::{
+    architecture::arm::{
+        sequences::{BootMode, BootModePins},
+        Pins,
+    },
     config::{RegistryError, TargetSelector},
-    flashing::{FileDownloadError, FlashError, FlashLoader},
-    DebugProbeError, DebugProbeSelector, FakeProbe, Permissions, Probe, Session, Target,
-    WireProtocol,
+    flashing::{FileDownloadError, FlashError, FlashLoader, GapFill},
+    AttachMethod, DebugProbeError, DebugProbeSelector, FakeProbe, PartialAttach, Permissions,
+    Probe, ResetTiming, Session, SwdTiming, Target, UsbSettings, WireProtocol,
 };
 
 /// Common options when flashing a target device.
@@ -73,12 +79,31 @@ pub struct FlashOptions {
         help = "Enable this flag to restore all bytes erased in the sector erase but not overwritten by any page."
     )]
     pub restore_unwritten: bool,
+    #[clap(
+        name = "gap-fill",
+        long = "gap-fill",
+        value_parser = parse_gap_fill,
+        help = "Fill gaps between the data of the image inside a sector with the given byte, for example 0xFF or 0x00, or keep their contents with `preserve`."
+    )]
+    pub gap_fill: Option<GapFill>,
+    #[clap(
+        name = "stamp-checksums",
+        long = "stamp-checksums",
+        help = "Compute the checksums the target requires to boot the image, such as the vector table checksum of LPC parts, and patch them into the image."
+    )]
+    pub stamp_checksums: bool,
     #[clap(
         name = "filename",
         long = "flash-layout",
         help = "Requests the flash builder to output the layout into the given file in SVG format."
     )]
     pub flash_layout_output_path: Option<String>,
+    #[clap(
+        name = "memory-usage",
+        long = "memory-usage",
+        help = "Print the flash and RAM usage of the image per memory region, and fail if it does not fit."
+    )]
+    pub memory_usage: bool,
     #[clap(
         name = "elf file",
         long = "elf",
@@ -91,6 +116,28 @@ pub struct FlashOptions {
         help = "The work directory from which cargo-flash should operate from."
     )]
     pub work_dir: Option<PathBuf>,
+    #[clap(
+        name = "journal file",
+        long = "journal",
+        help = "Record the progress of the download in the given file, to detect and resume an interrupted download."
+    )]
+    pub journal: Option<PathBuf>,
+    #[clap(
+        name = "golden image file",
+        long = "golden-image",
+        help = "Record the data programmed to flash in the given Intel HEX file, to verify devices against it later with `probe-rs verify`."
+    )]
+    pub golden_image: Option<PathBuf>,
+    #[clap(
+        long = "delta",
+        help = "Only update the flash sectors whose contents change. Changed sectors are sent as a binary diff against their current contents and patched on the target where possible."
+    )]
+    pub delta: bool,
+    #[clap(
+        long = "verify",
+        help = "Read back the written data after flashing, and check that it matches the image."
+    )]
+    pub verify: bool,
     #[clap(flatten)]
     /// Arguments which are forwarded to 'cargo build'.
     pub cargo_options: CargoOptions,
@@ -99,6 +146,47 @@ pub struct FlashOptions {
     pub probe_options: ProbeOptions,
 }
 
+fn parse_gap_fill(input: &str) -> Result<GapFill, String> {
+    if input == "preserve" {
+        Ok(GapFill::PreserveExisting)
+    } else {
+        parse_int::parse(input)
+            .map(GapFill::Byte)
+            .map_err(|e| format!("expected a byte value or `preserve`: {e}"))
+    }
+}
+
+/// The boot modes which can be selected on the command line.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum BootModeArg {
+    Normal,
+    Bootloader,
+}
+
+impl From<BootModeArg> for BootMode {
+    fn from(mode: BootModeArg) -> Self {
+        match mode {
+            BootModeArg::Normal => BootMode::Normal,
+            BootModeArg::Bootloader => BootMode::Bootloader,
+        }
+    }
+}
+
+fn parse_boot_mode_pins(input: &str) -> Result<BootModePins, String> {
+    let (select, bootloader) = input
+        .split_once(':')
+        .ok_or_else(|| "expected `<PINS>:<BOOTLOADER LEVELS>`, for example 0x20:0x20".to_owned())?;
+
+    let parse = |value: &str| {
+        parse_int::parse::<u8>(value).map_err(|e| format!("invalid pin mask `{value}`: {e}"))
+    };
+
+    Ok(BootModePins {
+        select: Pins(parse(select)?),
+        bootloader: Pins(parse(bootloader)?),
+    })
+}
+
 /// Common options and logic when interfacing with a [Probe].
 #[derive(clap::Parser, Debug)]
 pub struct ProbeOptions {
@@ -122,11 +210,46 @@ pub struct ProbeOptions {
         help_heading = "PROBE CONFIGURATION"
     )]
     pub speed: Option<u32>,
+    /// Additional SWD idle cycles inserted after each transfer, for slow targets or long cables.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub swd_idle_cycles: Option<u8>,
+    /// The length of the SWD turnaround period in clock cycles, from 1 to 4.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub swd_turnaround: Option<u8>,
+    /// The minimum time the reset line is asserted for, in milliseconds.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub reset_assert_ms: Option<u64>,
+    /// The time to wait after releasing the reset line, in milliseconds.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub reset_release_ms: Option<u64>,
+    /// The timeout of USB transfers to the probe in milliseconds, for slow USB hubs.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub usb_timeout_ms: Option<u64>,
+    /// The maximum number of data bytes sent to the probe in a single command.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub usb_chunk_size: Option<usize>,
+    /// Select the boot mode of the target, by driving its boot mode pins while attaching.
+    #[clap(long, value_enum, help_heading = "PROBE CONFIGURATION")]
+    pub boot_mode: Option<BootModeArg>,
+    /// The probe pins connected to the boot mode straps, as `<PINS>:<BOOTLOADER LEVELS>` bit masks
+    /// of the CMSIS-DAP `DAP_SWJ_Pins` command, for example `0x20:0x20` for nTRST.
+    /// Defaults to the pins declared by the target.
+    #[clap(
+        long,
+        value_parser = parse_boot_mode_pins,
+        requires = "boot_mode",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub boot_mode_pins: Option<BootModePins>,
     #[structopt(
         long = "connect-under-reset",
         help = "Use this flag to assert the nreset & ntrst pins during attaching the probe to the chip."
     )]
     pub connect_under_reset: bool,
+    /// Attach without resetting, halting or erasing the target, and fail if a core was reset or
+    /// halted while attaching. Use this to read RTT buffers or RAM logs after a crash.
+    #[clap(long, conflicts_with = "connect_under_reset")]
+    pub preserve_state: bool,
     #[structopt(long = "dry-run")]
     pub dry_run: bool,
     #[structopt(
@@ -135,6 +258,23 @@ pub struct ProbeOptions {
         even when it has read-only protection."
     )]
     pub allow_erase_all: bool,
+    /// Do not attach if another debugger seems to be attached to the target. Without this, only
+    /// a warning is logged, as a previous session which did not end cleanly looks the same.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub deny_debugger_takeover: bool,
+    /// Retry a failed attach this many times, continuing from the step which failed.
+    #[clap(long, default_value = "0", help_heading = "PROBE CONFIGURATION")]
+    pub attach_retries: u32,
+    /// Log a summary of the connection problems, like protocol errors and re-initializations,
+    /// every this many seconds.
+    #[clap(long, value_name = "SECONDS", help_heading = "PROBE CONFIGURATION")]
+    pub telemetry_interval: Option<u64>,
+    /// Do not apply the settings stored for the board with `probe-rs board set`.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub no_board_config: bool,
+    /// The stored settings of the board, loaded on first use.
+    #[clap(skip)]
+    board_config: OnceCell<Option<BoardConfig>>,
 }
 
 impl ProbeOptions {
@@ -156,9 +296,45 @@ impl ProbeOptions {
         }
     }
 
+    /// The settings stored for the board connected to the selected probe, if any.
+    ///
+    /// The probe is identified by the serial number in the probe selector, or of the only
+    /// connected probe.
+    fn board_config(&self) -> Option<&BoardConfig> {
+        self.board_config
+            .get_or_init(|| {
+                if self.no_board_config || self.dry_run {
+                    return None;
+                }
+
+                let serial = match &self.probe_selector {
+                    Some(selector) => selector.serial_number.clone(),
+                    None => match Probe::list_all().as_slice() {
+                        [info] => info.serial_number.clone(),
+                        _ => None,
+                    },
+                }?;
+
+                let path = BoardConfigs::default_path()?;
+                match BoardConfigs::load(&path) {
+                    Ok(configs) => configs.get(&serial).cloned(),
+                    Err(e) => {
+                        log::warn!("Ignoring stored board settings: {:?}", e);
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
     /// Resolves a resultant target selector from passed [ProbeOptions].
     pub fn get_target_selector(&self) -> Result<TargetSelector, OperationError> {
-        let target = if let Some(chip_name) = &self.chip {
+        let chip = self
+            .chip
+            .as_ref()
+            .or_else(|| self.board_config()?.chip.as_ref());
+
+        let target = if let Some(chip_name) = chip {
             let target = probe_rs::config::get_target_by_name(chip_name).map_err(|error| {
                 OperationError::ChipNotFound {
                     source: error,
@@ -204,7 +380,9 @@ impl ProbeOptions {
             }
         }?;
 
-        if let Some(protocol) = self.protocol {
+        let board = self.board_config().cloned().unwrap_or_default();
+
+        if let Some(protocol) = self.protocol.or(board.protocol) {
             // Select protocol and speed
             probe.select_protocol(protocol).map_err(|error| {
                 OperationError::FailedToSelectProtocol {
@@ -214,7 +392,7 @@ impl ProbeOptions {
             })?;
         }
 
-        if let Some(speed) = self.speed {
+        if let Some(speed) = self.speed.or(board.speed) {
             let _actual_speed = probe.set_speed(speed).map_err(|error| {
                 OperationError::FailedToSelectProtocolSpeed {
                     source: error,
@@ -223,6 +401,47 @@ impl ProbeOptions {
             })?;
         }
 
+        if self.swd_idle_cycles.is_some() || self.swd_turnaround.is_some() {
+            let default = SwdTiming::default();
+            let timing = SwdTiming {
+                idle_cycles: self.swd_idle_cycles.unwrap_or(default.idle_cycles),
+                turnaround_cycles: self.swd_turnaround.unwrap_or(default.turnaround_cycles),
+            };
+
+            probe
+                .set_swd_timing(timing)
+                .map_err(|error| OperationError::FailedToConfigureSwdTiming { source: error })?;
+        }
+
+        let reset_assert_ms = self.reset_assert_ms.or(board.reset_assert_ms);
+        let reset_release_ms = self.reset_release_ms.or(board.reset_release_ms);
+
+        if reset_assert_ms.is_some() || reset_release_ms.is_some() {
+            let timing = ResetTiming {
+                assert_duration: Duration::from_millis(reset_assert_ms.unwrap_or(0)),
+                release_delay: Duration::from_millis(reset_release_ms.unwrap_or(0)),
+            };
+
+            probe
+                .set_reset_timing(timing)
+                .map_err(|error| OperationError::FailedToConfigureResetTiming { source: error })?;
+        }
+
+        if self.usb_timeout_ms.is_some() || self.usb_chunk_size.is_some() {
+            let settings = UsbSettings {
+                timeout: self.usb_timeout_ms.map(Duration::from_millis),
+                max_chunk_size: self.usb_chunk_size,
+            };
+
+            probe
+                .set_usb_settings(settings)
+                .map_err(|error| OperationError::FailedToConfigureUsb { source: error })?;
+        }
+
+        if let Some(mode) = self.boot_mode {
+            probe.set_boot_mode(mode.into(), self.boot_mode_pins);
+        }
+
         Ok(probe)
     }
 
@@ -237,16 +456,55 @@ impl ProbeOptions {
         if self.allow_erase_all {
             permissions = permissions.allow_erase_all();
         }
+        if self.deny_debugger_takeover {
+            permissions = permissions.deny_debugger_takeover();
+        }
 
-        let session = if self.connect_under_reset {
-            probe.attach_under_reset(target, permissions)
+        let connect_under_reset = if self.preserve_state {
+            false
+        } else if self.connect_under_reset {
+            true
         } else {
-            probe.attach(target, permissions)
+            match self.board_config() {
+                Some(board) => board.connect_under_reset,
+                None => false,
+            }
+        };
+
+        let attach_method = if self.preserve_state {
+            AttachMethod::Preserve
+        } else if connect_under_reset {
+            AttachMethod::UnderReset
+        } else {
+            AttachMethod::Normal
+        };
+
+        let mut attach = probe.attach_with_diagnostics(target, attach_method, permissions);
+        let mut retries = self.attach_retries;
+
+        let mut session = loop {
+            match attach {
+                Ok(session) => break session,
+                Err(partial) => {
+                    log_partial_attach(&partial);
+
+                    if retries == 0 || !partial.can_retry() {
+                        return Err(OperationError::AttachingFailed {
+                            source: partial.into_error(),
+                            connect_under_reset,
+                        });
+                    }
+
+                    retries -= 1;
+                    tracing::info!("Retrying the attach from the failed step");
+                    attach = partial.retry();
+                }
+            }
+        };
+
+        if let Some(seconds) = self.telemetry_interval {
+            session.set_telemetry_log_interval(Some(Duration::from_secs(seconds)));
         }
-        .map_err(|error| OperationError::AttachingFailed {
-            source: error,
-            connect_under_reset: self.connect_under_reset,
-        })?;
 
         Ok(session)
     }
@@ -289,6 +547,29 @@ impl ProbeOptions {
     }
 }
 
+/// Log how far a failed attach got, and what was measured about the connection.
+fn log_partial_attach(partial: &PartialAttach) {
+    tracing::warn!("{}: {}", partial, partial.error());
+
+    let completed = partial
+        .completed_steps()
+        .iter()
+        .map(|step| step.to_string())
+        .collect::<Vec<_>>();
+    tracing::info!("Completed attach steps: {}", completed.join(", "));
+
+    let diagnostics = partial.diagnostics();
+    if let Some(voltage) = diagnostics.target_voltage {
+        tracing::info!("Target voltage: {:.2} V", voltage);
+    }
+    if let Some(dpidr) = diagnostics.dpidr {
+        tracing::info!("DPIDR: {:#010x}", dpidr);
+    }
+    if let Some(health) = &diagnostics.dp_health {
+        tracing::info!("Debug port state: {:?}", health);
+    }
+}
+
 /// Common options used when building artifacts with cargo.
 #[derive(clap::Parser, Debug, Default)]
 pub struct CargoOptions {
@@ -465,6 +746,21 @@ pub enum OperationError {
         source: DebugProbeError,
         speed: u32,
     },
+    #[error("The SWD timing could not be configured.")]
+    FailedToConfigureSwdTiming {
+        #[source]
+        source: DebugProbeError,
+    },
+    #[error("The reset timing could not be configured.")]
+    FailedToConfigureResetTiming {
+        #[source]
+        source: DebugProbeError,
+    },
+    #[error("The USB transfers of the probe could not be configured.")]
+    FailedToConfigureUsb {
+        #[source]
+        source: DebugProbeError,
+    },
     #[error("Connecting to the chip was unsuccessful.")]
     AttachingFailed {
         #[source]
@@ -479,6 +775,8 @@ pub enum OperationError {
     TargetResetHaltFailed(#[source] probe_rs::Error),
     #[error("Failed to write to file")]
     IOError(#[source] std::io::Error),
+    #[error("The image '{path}' does not fit into the memory of the target.")]
+    ImageDoesNotFit { path: PathBuf },
     #[error("Failed to parse CLI arguments.")]
     CliArgument(#[from] clap::Error),
 }
@@ -529,4 +827,13 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn boot_mode_pins_are_parsed() {
+        let pins = parse_boot_mode_pins("0x20:0").unwrap();
+        assert_eq!((pins.select.0, pins.bootloader.0), (0x20, 0));
+
+        assert!(parse_boot_mode_pins("0x20").is_err());
+        assert!(parse_boot_mode_pins("0x20:0x100").is_err());
+    }
 }
@@ -20,14 +20,32 @@ pub fn run_flash_download(
     loader: FlashLoader,
     do_chip_erase: bool,
 ) -> Result<(), OperationError> {
+    if opt.memory_usage {
+        if let Some(memory_usage) = loader.memory_usage() {
+            logging::eprintln(format!("{}\n{memory_usage}", "Memory usage".green().bold()));
+
+            if !memory_usage.fits() {
+                return Err(OperationError::ImageDoesNotFit {
+                    path: path.to_path_buf(),
+                });
+            }
+        }
+    }
+
     // Start timer.
     let instant = Instant::now();
 
     let mut download_option = DownloadOptions::default();
     download_option.keep_unwritten_bytes = opt.restore_unwritten;
+    download_option.gap_fill = opt.gap_fill.unwrap_or_default();
+    download_option.stamp_checksums = opt.stamp_checksums;
     download_option.dry_run = opt.probe_options.dry_run;
     download_option.do_chip_erase = do_chip_erase;
     download_option.disable_double_buffering = opt.disable_double_buffering;
+    download_option.journal = opt.journal.clone();
+    download_option.golden_image = opt.golden_image.clone();
+    download_option.delta = opt.delta;
+    download_option.verify = opt.verify;
 
     if !opt.disable_progressbars {
         // Create progress bars.
@@ -57,9 +75,19 @@ pub fn run_flash_download(
 
         // Create a new progress bar for the program progress.
         let program_progress = multi_progress.add(ProgressBar::new(0));
-        program_progress.set_style(style);
+        program_progress.set_style(style.clone());
         program_progress.set_message(" Programming pages  ");
 
+        // Create a new progress bar for the verify progress if verification is enabled.
+        let verify_progress = if opt.verify {
+            let verify_progress = multi_progress.add(ProgressBar::new(0));
+            verify_progress.set_style(style);
+            verify_progress.set_message("     Verifying data ");
+            Some(verify_progress)
+        } else {
+            None
+        };
+
         // Register callback to update the progress.
         let flash_layout_output_path = opt.flash_layout_output_path.clone();
         let progress = FlashProgress::new(move |event| {
@@ -133,6 +161,28 @@ pub fn run_flash_download(
                         fp.finish()
                     };
                 }
+                StartedVerifying { size } => {
+                    if let Some(vp) = verify_progress.as_ref() {
+                        vp.set_length(size);
+                        vp.enable_steady_tick(Duration::from_millis(100));
+                        vp.reset_elapsed();
+                    }
+                }
+                DataVerified { size, .. } => {
+                    if let Some(vp) = verify_progress.as_ref() {
+                        vp.inc(size)
+                    };
+                }
+                FailedVerifying => {
+                    if let Some(vp) = verify_progress.as_ref() {
+                        vp.abandon()
+                    };
+                }
+                FinishedVerifying => {
+                    if let Some(vp) = verify_progress.as_ref() {
+                        vp.finish()
+                    };
+                }
                 DiagnosticMessage { .. } => (),
             }
         });
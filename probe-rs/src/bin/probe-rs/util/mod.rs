@@ -1,3 +1,4 @@
+pub mod board_config;
 pub mod common_options;
 pub mod flash;
 pub mod logging;
@@ -1,10 +1,12 @@
 use crate::*;
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use defmt_decoder::DecodeError;
 use num_traits::Zero;
 use probe_rs::config::MemoryRegion;
 pub use probe_rs::rtt::ChannelMode;
-use probe_rs::rtt::{DownChannel, Rtt, ScanRegion, UpChannel};
+use probe_rs::rtt::{
+    DownChannel, Rtt, ScanRegion, TargetLogRecord, TextLogDecoder, UpChannel, MAILBOX_SYMBOL,
+};
 use probe_rs::Core;
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -26,17 +28,27 @@ pub fn attach_to_rtt(
     timestamp_offset: UtcOffset,
 ) -> Result<RttActiveTarget, anyhow::Error> {
     log::info!("Initializing RTT");
-    let rtt_header_address = if let Ok(mut file) = File::open(elf_file) {
-        if let Some(address) = RttActiveTarget::get_rtt_symbol(&mut file) {
-            ScanRegion::Exact(address as u32)
+    let mailbox_address = File::open(elf_file)
+        .ok()
+        .and_then(|mut file| RttActiveTarget::get_symbol(&mut file, MAILBOX_SYMBOL));
+
+    let rtt = if let Some(address) = mailbox_address {
+        Rtt::attach_mailbox(core, memory_map, address as u32)
+    } else {
+        let rtt_header_address = if let Ok(mut file) = File::open(elf_file) {
+            if let Some(address) = RttActiveTarget::get_rtt_symbol(&mut file) {
+                ScanRegion::Exact(address as u32)
+            } else {
+                ScanRegion::Ram
+            }
         } else {
             ScanRegion::Ram
-        }
-    } else {
-        ScanRegion::Ram
+        };
+
+        Rtt::attach_region(core, memory_map, &rtt_header_address)
     };
 
-    match Rtt::attach_region(core, memory_map, &rtt_header_address) {
+    match rtt {
         Ok(rtt) => {
             log::info!("RTT initialized.");
             let app = RttActiveTarget::new(rtt, elf_file, rtt_config, timestamp_offset)?;
@@ -119,6 +131,8 @@ pub struct RttActiveChannel {
     /// Data that will be written to the down_channel (host to target)
     _input_data: String,
     rtt_buffer: RttBuffer,
+    /// Splits the data of a `String` channel into log records, see [`Self::get_log_records`].
+    text_log: TextLogDecoder,
     show_timestamps: bool,
     show_location: bool,
 
@@ -186,6 +200,7 @@ impl RttActiveChannel {
         Self {
             up_channel,
             down_channel,
+            text_log: TextLogDecoder::new(name.clone()),
             channel_name: name,
             data_format,
             _input_data: String::new(),
@@ -264,46 +279,35 @@ impl RttActiveChannel {
                             DataFormat::Defmt => {
                                 match defmt_state {
                                     Some((table, locs)) => {
-                                        let mut stream_decoder = table.new_stream_decoder();
-                                        stream_decoder.received(&self.rtt_buffer.0[..bytes_read]);
-                                        loop {
-                                            match stream_decoder.decode() {
-                                                Ok(frame) => {
-                                                    let loc = locs.as_ref().and_then(|locs| locs.get(&frame.index()) );
-                                                    writeln!(formatted_data, "{}", frame.display(false)).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
-                                                    if self.show_location {
-                                                        if let Some(loc) = loc {
-                                                            let relpath = if let Ok(relpath) =
-                                                                loc.file.strip_prefix(&std::env::current_dir().unwrap())
-                                                            {
-                                                                relpath
-                                                            } else {
-                                                                // not relative; use full path
-                                                                &loc.file
-                                                            };
-                                                            writeln!(formatted_data,
-                                                                "└─ {}:{}",
-                                                                relpath.display(),
-                                                                loc.line
-                                                            ).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
-                                                        } else {
-                                                            writeln!(formatted_data, "└─ <invalid location: defmt frame-index: {}>", frame.index()).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
-                                                        }
-                                                    }
-                                                    continue;
-                                                },
-                                                Err(DecodeError::UnexpectedEof) => break,
-                                                Err(DecodeError::Malformed) => match table.encoding().can_recover() {
-                                                    // If recovery is impossible, break out of here and propagate the error.
-                                                    false => {
-                                                        return Err(anyhow!("Unrecoverable error while decoding Defmt data and some data may have been lost: {:?}", DecodeError::Malformed));
-                                                    },
-                                                    // If recovery is possible, skip the current frame and continue with new data.
-                                                    true => continue,
-                                                },
-
+                                        let current_dir = if self.show_location {
+                                            Some(std::env::current_dir().context("Failed to determine the current directory")?)
+                                        } else {
+                                            None
+                                        };
+                                        decode_defmt_frames(table, &self.rtt_buffer.0[..bytes_read], |frame| {
+                                            let loc = locs.as_ref().and_then(|locs| locs.get(&frame.index()) );
+                                            writeln!(formatted_data, "{}", frame.display(false)).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                            if self.show_location {
+                                                if let Some(loc) = loc {
+                                                    let relpath = if let Some(relpath) = current_dir
+                                                        .as_ref()
+                                                        .and_then(|dir| loc.file.strip_prefix(dir).ok())
+                                                    {
+                                                        relpath
+                                                    } else {
+                                                        // not relative; use full path
+                                                        &loc.file
+                                                    };
+                                                    writeln!(formatted_data,
+                                                        "└─ {}:{}",
+                                                        relpath.display(),
+                                                        loc.line
+                                                    ).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                                } else {
+                                                    writeln!(formatted_data, "└─ <invalid location: defmt frame-index: {}>", frame.index()).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                                }
                                             }
-                                        }
+                                        })?;
                                     }
                                     None => {
                                         write!(formatted_data, "Running rtt in defmt mode but table or locations could not be loaded.")
@@ -318,6 +322,56 @@ impl RttActiveChannel {
             }).transpose()
     }
 
+    /// Retrieves available data from the channel as log records, to be forwarded to the host
+    /// log with [`TargetLogRecord::emit`].
+    ///
+    /// Errors are handled like in [`Self::get_rtt_data`].
+    pub fn get_log_records(
+        &mut self,
+        core: &mut Core,
+        defmt_state: Option<&(defmt_decoder::Table, Option<defmt_decoder::Locations>)>,
+    ) -> Result<Vec<TargetLogRecord>, anyhow::Error> {
+        let Some(bytes_read) = self.poll_rtt(core) else {
+            return Ok(Vec::new());
+        };
+        let data = &self.rtt_buffer.0[..bytes_read];
+
+        Ok(match self.data_format {
+            DataFormat::String => self.text_log.received(data),
+            DataFormat::BinaryLE => {
+                let message = data
+                    .iter()
+                    .map(|byte| format!("{byte:#04x}"))
+                    .collect::<String>();
+                vec![TargetLogRecord::new(self.channel_name.clone(), message)]
+            }
+            DataFormat::Defmt => {
+                let Some((table, locs)) = defmt_state else {
+                    return Err(anyhow!(
+                        "Running rtt in defmt mode but table or locations could not be loaded."
+                    ));
+                };
+
+                let mut records = Vec::new();
+                decode_defmt_frames(table, data, |frame| {
+                    let loc = locs.as_ref().and_then(|locs| locs.get(&frame.index()));
+                    records.push(TargetLogRecord {
+                        level: frame.level().and_then(|level| level.as_str().parse().ok()),
+                        timestamp: frame.display_timestamp().map(|ts| ts.to_string()),
+                        module: loc.map(|loc| loc.module.clone()),
+                        file: loc.map(|loc| loc.file.display().to_string()),
+                        line: loc.map(|loc| loc.line),
+                        ..TargetLogRecord::new(
+                            self.channel_name.clone(),
+                            frame.display_message().to_string(),
+                        )
+                    });
+                })?;
+                records
+            }
+        })
+    }
+
     pub fn _push_rtt(&mut self, core: &mut Core) {
         if let Some(down_channel) = self.down_channel.as_mut() {
             self._input_data += "\n";
@@ -427,12 +481,25 @@ impl RttActiveTarget {
     }
 
     pub fn get_rtt_symbol<T: Read + Seek>(file: &mut T) -> Option<u64> {
+        let address = Self::get_symbol(file, "_SEGGER_RTT");
+
+        if address.is_none() {
+            log::warn!(
+                "No RTT header info was present in the ELF file. Does your firmware run RTT?"
+            );
+        }
+
+        address
+    }
+
+    /// Returns the address of the symbol `symbol` in the ELF `file`.
+    pub fn get_symbol<T: Read + Seek>(file: &mut T, symbol: &str) -> Option<u64> {
         let mut buffer = Vec::new();
         if file.read_to_end(&mut buffer).is_ok() {
             if let Ok(binary) = goblin::elf::Elf::parse(buffer.as_slice()) {
                 for sym in &binary.syms {
                     if let Some(name) = binary.strtab.get_at(sym.st_name) {
-                        if name == "_SEGGER_RTT" {
+                        if name == symbol {
                             return Some(sym.st_value);
                         }
                     }
@@ -440,7 +507,6 @@ impl RttActiveTarget {
             }
         }
 
-        log::warn!("No RTT header info was present in the ELF file. Does your firmware run RTT?");
         None
     }
 
@@ -460,11 +526,49 @@ impl RttActiveTarget {
         Ok(data)
     }
 
+    /// Polls the RTT target on all channels and returns the available data as log records.
+    /// An error on any channel will return an error instead of incomplete data.
+    pub fn poll_rtt_records(&mut self, core: &mut Core) -> Result<Vec<TargetLogRecord>> {
+        let defmt_state = self.defmt_state.as_ref();
+        let mut records = Vec::new();
+        for channel in self.active_channels.iter_mut() {
+            records.extend(channel.get_log_records(core, defmt_state)?);
+        }
+        Ok(records)
+    }
+
     // pub fn push_rtt(&mut self) {
     //     self.tabs[self.current_tab].push_rtt();
     // }
 }
 
+/// Decodes the complete defmt frames in `data`, and calls `on_frame` for each of them.
+fn decode_defmt_frames(
+    table: &defmt_decoder::Table,
+    data: &[u8],
+    mut on_frame: impl FnMut(defmt_decoder::Frame<'_>),
+) -> Result<()> {
+    let mut stream_decoder = table.new_stream_decoder();
+    stream_decoder.received(data);
+
+    loop {
+        match stream_decoder.decode() {
+            Ok(frame) => on_frame(frame),
+            Err(DecodeError::UnexpectedEof) => break,
+            Err(DecodeError::Malformed) => match table.encoding().can_recover() {
+                // If recovery is impossible, break out of here and propagate the error.
+                false => {
+                    return Err(anyhow!("Unrecoverable error while decoding Defmt data and some data may have been lost: {:?}", DecodeError::Malformed));
+                }
+                // If recovery is possible, skip the current frame and continue with new data.
+                true => continue,
+            },
+        }
+    }
+
+    Ok(())
+}
+
 struct RttBuffer(Vec<u8>);
 impl RttBuffer {
     /// Initialize the buffer and ensure it has enough capacity to match the size of the RTT channel on the target at the time of instantiation. Doing this now prevents later performance impact if the buffer capacity has to be grown dynamically.
@@ -0,0 +1,198 @@
+//! Cancellation of long-running operations.
+//!
+//! Attaching to a target, flashing, scanning ROM tables and waiting for a core to halt can take
+//! a long time, or wait for internal timeouts when the target does not respond. An embedding
+//! application can abort them by running them with a [`CancellationToken`], which is cancelled
+//! from another thread or has a deadline.
+//!
+//! Cancelled operations return [`Cancelled`], wrapped in the error type of the operation,
+//! for example [`Error::Cancelled`](crate::Error::Cancelled). The target may be left in an
+//! intermediate state, for example with a partially programmed flash.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe_rs::cancellation::CancellationToken;
+//! use probe_rs::{Permissions, Session};
+//! use std::time::Duration;
+//!
+//! let token = CancellationToken::with_timeout(Duration::from_secs(10));
+//!
+//! // Cancel the attach if the user presses a button, or after 10 seconds at the latest.
+//! let cancel = token.clone();
+//! std::thread::spawn(move || {
+//!     // ...
+//!     cancel.cancel();
+//! });
+//!
+//! let session = token.run(|| Session::auto_attach("nrf52840_xxAA", Permissions::default()))?;
+//! # Ok::<(), probe_rs::Error>(())
+//! ```
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    /// The tokens of the nested [`CancellationToken::run`] calls, innermost last.
+    static ACTIVE_TOKENS: RefCell<Vec<CancellationToken>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The reason an operation was aborted.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cancelled {
+    /// [`CancellationToken::cancel`] was called.
+    #[error("The operation was cancelled")]
+    Cancelled,
+    /// The deadline of the [`CancellationToken`] has passed.
+    #[error("The deadline of the operation has passed")]
+    DeadlineExceeded,
+}
+
+#[derive(Debug)]
+struct Inner {
+    cancelled: AtomicBool,
+    deadline: Option<Instant>,
+}
+
+/// A token to abort long-running operations, see the [module documentation](self).
+///
+/// Clones of a token share their state, so cancelling one cancels all of them.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    /// Create a token without a deadline, which is only cancelled by [`CancellationToken::cancel`].
+    pub fn new() -> Self {
+        Self::with_optional_deadline(None)
+    }
+
+    /// Create a token which is cancelled at `deadline`.
+    pub fn with_deadline(deadline: Instant) -> Self {
+        Self::with_optional_deadline(Some(deadline))
+    }
+
+    /// Create a token which is cancelled once `timeout` has passed from now.
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self::with_deadline(Instant::now() + timeout)
+    }
+
+    fn with_optional_deadline(deadline: Option<Instant>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                deadline,
+            }),
+        }
+    }
+
+    /// Cancel all operations running with this token.
+    ///
+    /// The operations return at their next cancellation point, which is usually within a few
+    /// milliseconds.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// The deadline of the token, if it has one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.inner.deadline
+    }
+
+    /// Returns `true` if the token was cancelled or its deadline has passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    /// Returns an error if the token was cancelled or its deadline has passed.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.inner.cancelled.load(Ordering::Relaxed) {
+            return Err(Cancelled::Cancelled);
+        }
+
+        match self.inner.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(Cancelled::DeadlineExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `f` on the current thread, and abort all operations of probe-rs in it when the
+    /// token is cancelled.
+    ///
+    /// Calls can be nested, the operations are aborted when any of the tokens is cancelled.
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        /// Removes the token again, also if `f` panics.
+        struct Restore;
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                ACTIVE_TOKENS.with(|tokens| tokens.borrow_mut().pop());
+            }
+        }
+
+        ACTIVE_TOKENS.with(|tokens| tokens.borrow_mut().push(self.clone()));
+        let _restore = Restore;
+
+        f()
+    }
+}
+
+/// Returns an error if any token of the current operation was cancelled.
+///
+/// This is a cancellation point, and should be called in every polling loop and between the
+/// steps of long-running operations.
+pub(crate) fn check() -> Result<(), Cancelled> {
+    ACTIVE_TOKENS.with(|tokens| {
+        tokens
+            .borrow()
+            .iter()
+            .try_for_each(CancellationToken::check)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, CancellationToken, Cancelled};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn token_applies_within_run() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        assert_eq!(check(), Ok(()));
+        assert_eq!(token.run(check), Err(Cancelled::Cancelled));
+        assert_eq!(check(), Ok(()));
+    }
+
+    #[test]
+    fn nested_tokens() {
+        let outer = CancellationToken::with_deadline(Instant::now());
+        let inner = CancellationToken::with_timeout(Duration::from_secs(3600));
+
+        // The outer deadline also applies to operations running with the inner token.
+        outer.run(|| {
+            assert_eq!(inner.run(check), Err(Cancelled::DeadlineExceeded));
+            assert_eq!(check(), Err(Cancelled::DeadlineExceeded));
+        });
+
+        let outer = CancellationToken::with_timeout(Duration::from_secs(3600));
+        let inner = CancellationToken::new();
+        inner.cancel();
+
+        outer.run(|| {
+            assert_eq!(inner.run(check), Err(Cancelled::Cancelled));
+            assert_eq!(check(), Ok(()));
+        });
+        assert_eq!(check(), Ok(()));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::architecture::arm::ArmChipInfo;
+use crate::architecture::arm::{dp::TARGETID, ArmChipInfo};
 
 /// Information about a chip which is used
 /// for automatic detection of the connected chip.
@@ -10,12 +10,11 @@ use crate::architecture::arm::ArmChipInfo;
 #[derive(Debug)]
 pub(crate) enum ChipInfo {
     /// ARM specific information for chip
-    /// auto-detection. See [ArmChipInfo].
-    Arm(ArmChipInfo),
-}
-
-impl From<ArmChipInfo> for ChipInfo {
-    fn from(info: ArmChipInfo) -> Self {
-        ChipInfo::Arm(info)
-    }
+    /// auto-detection.
+    Arm {
+        /// The manufacturer and part number in the ROM table, see [ArmChipInfo].
+        rom_table: Option<ArmChipInfo>,
+        /// The TARGETID register of the debug port, if it has one.
+        target_id: Option<TARGETID>,
+    },
 }
@@ -21,22 +21,33 @@
 //! To add a target at runtime, the [add_target_from_yaml] function can
 //! be used to read targets from a YAML file.
 //!
+//! The same function also reads overlays, which patch single fields of a family already in
+//! the registry, for example to add a memory region or to change the debug base of a core,
+//! instead of copying the whole family. See [add_target_from_yaml] for the format.
+//!
 
 mod chip_info;
+mod overlay;
+#[cfg(feature = "cmsis-pack")]
+pub mod pack;
 mod registry;
 mod target;
 
 pub use probe_rs_target::{
-    Chip, ChipFamily, Core, CoreType, FlashProperties, GenericRegion, InstructionSet, MemoryRange,
-    MemoryRegion, NvmRegion, PageInfo, RamRegion, RawFlashAlgorithm, SectorDescription, SectorInfo,
-    TargetDescriptionSource,
+    Chip, ChipDetection, ChipFamily, Core, CoreType, FlashProperties, GenericRegion, ImageChecksum,
+    InstructionSet, MemoryRange, MemoryRegion, NvmRegion, PageInfo, RamRegion, RawFlashAlgorithm,
+    SectorDescription, SectorInfo, TargetDescriptionSource, UniqueIdPart,
 };
 
 pub use registry::{
-    add_target_from_yaml, families, get_target_by_name, search_chips, RegistryError,
+    add_target_from_yaml, families, get_target_by_name, load_target_directories, search_chips,
+    set_target_directories, target_directories, RegistryError,
 };
 pub use target::{DebugSequence, Target, TargetParseError, TargetSelector};
 
+#[cfg(feature = "cmsis-pack")]
+pub use registry::add_target_from_pack;
+
 // Crate-internal API
 pub(crate) use chip_info::ChipInfo;
 pub(crate) use registry::get_target_by_chip_info;
@@ -0,0 +1,158 @@
+//! Overlays which patch target descriptions of the registry, see
+//! [`add_target_from_yaml`](super::add_target_from_yaml).
+
+use serde_yaml::{Mapping, Value};
+
+use super::{ChipFamily, RegistryError};
+
+/// The field of an overlay which names the family it patches.
+const OVERLAY_KEY: &str = "overlay";
+
+/// The field of a chip in an overlay which names the chip it is copied from.
+const EXTENDS_KEY: &str = "extends";
+
+/// Returns the name of the family `description` patches, if it is an overlay.
+pub(super) fn overlay_family(description: &Value) -> Option<Result<&str, RegistryError>> {
+    let value = description.get(OVERLAY_KEY)?;
+
+    Some(value.as_str().ok_or_else(|| {
+        RegistryError::InvalidOverlay(format!(
+            "the `{OVERLAY_KEY}` field has to be the name of a family"
+        ))
+    }))
+}
+
+/// Apply the overlay `description` to `family`.
+pub(super) fn apply_overlay(
+    family: &ChipFamily,
+    mut description: Value,
+) -> Result<ChipFamily, RegistryError> {
+    let Some(patch) = description.as_mapping_mut() else {
+        return Err(RegistryError::InvalidOverlay(
+            "an overlay has to be a mapping".to_string(),
+        ));
+    };
+    patch.remove(OVERLAY_KEY);
+
+    // Target descriptions are serialized with addresses as hex strings, which cannot be
+    // deserialized again.
+    let mut base = serde_yaml::to_value(family)?;
+    parse_hex_strings(&mut base);
+
+    if let Some(Value::Sequence(variants)) = patch.get_mut("variants") {
+        for variant in variants.iter_mut() {
+            extend_variant(&base, variant)?;
+        }
+    }
+
+    merge(&mut base, description);
+
+    Ok(serde_yaml::from_value(base)?)
+}
+
+/// Replace a chip of an overlay with an `extends` field by a copy of the named chip, with the
+/// chip of the overlay merged into it.
+fn extend_variant(base: &Value, variant: &mut Value) -> Result<(), RegistryError> {
+    let Some(extends) = variant.as_mapping_mut().and_then(|v| v.remove(EXTENDS_KEY)) else {
+        return Ok(());
+    };
+    let Some(name) = extends.as_str() else {
+        return Err(RegistryError::InvalidOverlay(format!(
+            "the `{EXTENDS_KEY}` field has to be the name of a chip"
+        )));
+    };
+
+    let parent = base
+        .get("variants")
+        .and_then(Value::as_sequence)
+        .and_then(|variants| {
+            variants
+                .iter()
+                .find(|v| v.get("name").and_then(Value::as_str) == Some(name))
+        })
+        .ok_or_else(|| RegistryError::ChipNotFound(name.to_string()))?;
+
+    let mut extended = parent.clone();
+    merge(&mut extended, std::mem::take(variant));
+    *variant = extended;
+
+    Ok(())
+}
+
+/// Merge `patch` into `base`, see [`add_target_from_yaml`](super::add_target_from_yaml).
+fn merge(base: &mut Value, patch: Value) {
+    match (base, patch) {
+        (Value::Mapping(base), Value::Mapping(patch)) => merge_mapping(base, patch),
+        (Value::Tagged(base), Value::Tagged(patch)) if base.tag == patch.tag => {
+            merge(&mut base.value, patch.value)
+        }
+        (Value::Sequence(base), Value::Sequence(patch))
+            if !patch.is_empty() && patch.iter().all(|element| untagged(element).is_mapping()) =>
+        {
+            for element in patch {
+                match base.iter_mut().find(|b| same_element(b, &element)) {
+                    Some(existing) => merge(existing, element),
+                    None => base.push(element),
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+fn merge_mapping(base: &mut Mapping, patch: Mapping) {
+    for (key, value) in patch {
+        match base.get_mut(&key) {
+            Some(existing) => merge(existing, value),
+            None => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Returns `true` if the list elements `a` and `b` describe the same item.
+fn same_element(a: &Value, b: &Value) -> bool {
+    if let (Value::Tagged(a), Value::Tagged(b)) = (a, b) {
+        if a.tag != b.tag {
+            return false;
+        }
+    }
+    let (a, b) = (untagged(a), untagged(b));
+
+    let name = |v: &Value| v.get("name").filter(|name| !name.is_null()).cloned();
+    if let (Some(a), Some(b)) = (name(a), name(b)) {
+        return a == b;
+    }
+
+    let start = |v: &Value| v.get("range").and_then(|r| r.get("start")).cloned();
+    match (start(a), start(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn untagged(value: &Value) -> &Value {
+    match value {
+        Value::Tagged(tagged) => &tagged.value,
+        value => value,
+    }
+}
+
+/// Replace hex strings like `0x1000` by numbers.
+fn parse_hex_strings(value: &mut Value) {
+    match value {
+        Value::String(string) => {
+            if let Some(number) = string
+                .strip_prefix("0x")
+                .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            {
+                *value = Value::Number(number.into());
+            }
+        }
+        Value::Sequence(sequence) => sequence.iter_mut().for_each(parse_hex_strings),
+        Value::Mapping(mapping) => mapping.values_mut().for_each(parse_hex_strings),
+        Value::Tagged(tagged) => parse_hex_strings(&mut tagged.value),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
@@ -0,0 +1,520 @@
+//! Target descriptions from CMSIS-Packs, see [`add_target_from_pack`](super::add_target_from_pack).
+//!
+//! A CMSIS-Pack is a ZIP archive with a `.pdsc` file, which describes the memory maps, the cores
+//! and the flash algorithms of the devices in the pack. The flash algorithms are FLM files, ELF
+//! files with the functions of the algorithm and a `FlashDevice` structure, which are converted
+//! by [`extract_flash_algo`].
+
+mod algorithm_binary;
+mod flash_device;
+mod parser;
+
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use cmsis_pack::pdsc::{Core, Device, Package, Processor};
+use cmsis_pack::utils::FromElem;
+use probe_rs_target::{ArmCoreAccessOptions, CoreAccessOptions, RiscvCoreAccessOptions};
+
+use crate::config::{
+    Chip, ChipFamily, Core as ProbeCore, GenericRegion, MemoryRegion, NvmRegion, RamRegion,
+    RawFlashAlgorithm, TargetDescriptionSource,
+};
+use crate::{Architecture, CoreType};
+
+pub use parser::extract_flash_algo;
+
+/// An error while reading a CMSIS-Pack.
+#[derive(Debug, thiserror::Error)]
+pub enum PackError {
+    /// An IO error which occurred when trying to read the pack.
+    #[error("An IO error was encountered")]
+    Io(#[from] std::io::Error),
+    /// The pack is not a valid ZIP archive.
+    #[error("The pack could not be read as a ZIP archive")]
+    Archive(#[from] zip::result::ZipError),
+    /// The pack contains no `.pdsc` file.
+    #[error("The pack contains no .pdsc file")]
+    MissingPdsc,
+    /// The `.pdsc` file of the pack is malformed.
+    #[error("The .pdsc file '{0}' could not be parsed")]
+    InvalidPdsc(String, #[source] anyhow::Error),
+    /// A device of the pack has a core which is not supported in probe-rs.
+    #[error("Core '{0}' is not yet supported for target generation.")]
+    UnsupportedCore(String),
+    /// A flash algorithm of the pack is not a valid ELF file.
+    #[error("The flash algorithm is not a valid ELF file")]
+    Elf(#[from] goblin::error::Error),
+    /// A flash algorithm of the pack is malformed.
+    #[error("Failed to extract the flash algorithm from the ELF file '{}': {1}", .0.display())]
+    InvalidFlashAlgorithm(PathBuf, String),
+}
+
+/// Where the flash algorithms of a `.pdsc` file are read from.
+enum PackFiles<'a, T>
+where
+    T: Seek + Read,
+{
+    Archive(&'a mut zip::ZipArchive<T>),
+    Directory(&'a Path),
+}
+
+/// Read the chip families of the CMSIS-Pack at `path`, which is either a `.pack` file or the
+/// directory a pack was unzipped into.
+pub fn families_from_pack(path: impl AsRef<Path>) -> Result<Vec<ChipFamily>, PackError> {
+    let path = path.as_ref();
+    let mut families = vec![];
+
+    if path.is_dir() {
+        add_families_from_directory(path, &mut families)?;
+    } else {
+        tracing::info!("Trying to open pack file: {}.", path.display());
+        add_families_from_archive(fs::File::open(path)?, &mut families, |_| true)?;
+    }
+
+    Ok(families)
+}
+
+/// Add the chip families of all `.pdsc` files in `path` and its subdirectories to `families`.
+///
+/// The flash algorithms are read relative to the directory of each `.pdsc` file.
+pub fn add_families_from_directory(
+    path: &Path,
+    families: &mut Vec<ChipFamily>,
+) -> Result<(), PackError> {
+    for entry in fs::read_dir(path)? {
+        let entry_path = entry?.path();
+
+        if entry_path.is_dir() {
+            add_families_from_directory(&entry_path, families)?;
+        } else if entry_path
+            .extension()
+            .is_some_and(|extension| extension == "pdsc")
+        {
+            tracing::info!("Found .pdsc file: {}", entry_path.display());
+
+            let package = Package::from_path(&entry_path)
+                .map_err(|e| PackError::InvalidPdsc(entry_path.display().to_string(), e))?;
+
+            add_package::<fs::File>(package, PackFiles::Directory(path), families, |_| true)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Add the chip families of the CMSIS-Pack archive `reader` to `families`.
+///
+/// Only the devices of the families for which `include` returns `true` are added, so their
+/// flash algorithms are not parsed otherwise.
+pub fn add_families_from_archive<T>(
+    reader: T,
+    families: &mut Vec<ChipFamily>,
+    include: impl Fn(&str) -> bool,
+) -> Result<(), PackError>
+where
+    T: Seek + Read,
+{
+    let mut archive = zip::ZipArchive::new(reader)?;
+
+    let index = find_pdsc_in_archive(&mut archive)?.ok_or(PackError::MissingPdsc)?;
+
+    let mut pdsc_file = archive.by_index(index)?;
+    let mut pdsc = String::new();
+    pdsc_file.read_to_string(&mut pdsc)?;
+
+    let package = Package::from_string(&pdsc)
+        .map_err(|e| PackError::InvalidPdsc(pdsc_file.name().to_string(), e))?;
+
+    drop(pdsc_file);
+
+    add_package(package, PackFiles::Archive(&mut archive), families, include)
+}
+
+fn add_package<T>(
+    pdsc: Package,
+    mut files: PackFiles<T>,
+    families: &mut Vec<ChipFamily>,
+    include: impl Fn(&str) -> bool,
+) -> Result<(), PackError>
+where
+    T: Seek + Read,
+{
+    // Forge a definition file for each device in the .pdsc file.
+    let pack_file_release = Some(pdsc.releases.latest_release().version.clone());
+    let mut devices = pdsc.devices.0.into_iter().collect::<Vec<_>>();
+    devices.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (device_name, device) in devices {
+        if !include(&device.family) {
+            tracing::debug!("Skipping chip family {}", device.family);
+            continue;
+        }
+
+        // Check if this device family is already known.
+        let family = match families
+            .iter()
+            .position(|family| family.name == device.family)
+        {
+            Some(index) => &mut families[index],
+            None => {
+                families.push(ChipFamily {
+                    name: device.family.clone(),
+                    manufacturer: None,
+                    generated_from_pack: true,
+                    pack_file_release: pack_file_release.clone(),
+                    variants: Vec::new(),
+                    flash_algorithms: Vec::new(),
+                    image_checksums: Vec::new(),
+                    unique_id: Vec::new(),
+                    chip_detection: Vec::new(),
+                    source: TargetDescriptionSource::External,
+                });
+                // This unwrap is always safe as we insert at least one item previously.
+                families.last_mut().unwrap()
+            }
+        };
+
+        // Extract the flash algorithm, block & sector size and the erased byte value from the ELF binary.
+        let mut flash_algorithm_names: Vec<String> = vec![];
+        for flash_algorithm in &device.algorithms {
+            let algo = match read_flash_algorithm(&mut files, &flash_algorithm.file_name) {
+                Ok(file) => extract_flash_algo(
+                    file.as_slice(),
+                    &flash_algorithm.file_name,
+                    flash_algorithm.default,
+                    false, // Algorithms from CMSIS-Pack files are position independent
+                ),
+                Err(error) => Err(error),
+            };
+
+            let algo: RawFlashAlgorithm = match algo {
+                Ok(algo) => algo,
+                Err(error) => {
+                    tracing::warn!("Failed to parse flash algorithm.");
+                    tracing::warn!("Reason: {:?}", error);
+                    continue;
+                }
+            };
+
+            // Sometimes the algos are referenced twice, for example in the multicore H7s
+            // Deduplicate while keeping order.
+            if !flash_algorithm_names.contains(&algo.name) {
+                flash_algorithm_names.push(algo.name.clone());
+            }
+
+            // We add this algo directly to the algos of the family if it's not already added.
+            // Make sure we never add an algo twice to save file size.
+            if !family.flash_algorithms.contains(&algo) {
+                family.flash_algorithms.push(algo);
+            }
+        }
+
+        let cores = device
+            .processors
+            .iter()
+            .map(create_core)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        family.variants.push(Chip {
+            name: device_name,
+            part: None,
+            cores,
+            memory_map: get_mem_map(&device),
+            flash_algorithms: flash_algorithm_names,
+            access_ports: None,
+            boot_mode_pins: None,
+        });
+    }
+
+    Ok(())
+}
+
+fn read_flash_algorithm<T>(files: &mut PackFiles<T>, file_name: &Path) -> Result<Vec<u8>, PackError>
+where
+    T: Seek + Read,
+{
+    let mut contents = vec![];
+
+    match files {
+        PackFiles::Archive(archive) => {
+            archive
+                .by_name(&file_name.to_string_lossy())?
+                .read_to_end(&mut contents)?;
+        }
+        PackFiles::Directory(path) => {
+            contents = fs::read(path.join(file_name))?;
+        }
+    }
+
+    Ok(contents)
+}
+
+fn create_core(processor: &Processor) -> Result<ProbeCore, PackError> {
+    let core_type = core_to_probe_core(&processor.core)?;
+    Ok(ProbeCore {
+        name: processor
+            .name
+            .as_ref()
+            .map(|s| s.to_ascii_lowercase())
+            .unwrap_or_else(|| "main".to_string()),
+        core_type,
+        core_access_options: match core_type.architecture() {
+            Architecture::Arm => CoreAccessOptions::Arm(ArmCoreAccessOptions {
+                ap: processor.ap,
+                psel: 0,
+                debug_base: None,
+                cti_base: None,
+                pmu_base: None,
+            }),
+            Architecture::Riscv => CoreAccessOptions::Riscv(RiscvCoreAccessOptions::default()),
+        },
+    })
+}
+
+fn core_to_probe_core(value: &Core) -> Result<CoreType, PackError> {
+    Ok(match value {
+        Core::CortexM0 => CoreType::Armv6m,
+        Core::CortexM0Plus => CoreType::Armv6m,
+        Core::CortexM4 => CoreType::Armv7em,
+        Core::CortexM3 => CoreType::Armv7m,
+        Core::CortexM23 => CoreType::Armv8m,
+        Core::CortexM33 => CoreType::Armv8m,
+        Core::CortexM7 => CoreType::Armv7em,
+        Core::StarMC1 => CoreType::Armv8m,
+        c => return Err(PackError::UnsupportedCore(format!("{c:?}"))),
+    })
+}
+
+/// Returns the index of the `.pdsc` file in a ZIP archive.
+fn find_pdsc_in_archive<T>(archive: &mut zip::ZipArchive<T>) -> Result<Option<usize>, PackError>
+where
+    T: Seek + Read,
+{
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+
+        // Malformed paths, which would point outside of the archive, are skipped.
+        let is_pdsc = file
+            .enclosed_name()
+            .and_then(Path::extension)
+            .is_some_and(|extension| extension == "pdsc");
+
+        if is_pdsc {
+            return Ok(Some(i));
+        }
+    }
+
+    Ok(None)
+}
+
+/// A flag to indicate what type of memory this is.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum MemoryType {
+    /// A RAM memory.
+    Ram,
+    /// A Non Volatile memory.
+    Nvm,
+    /// Generic
+    Generic,
+}
+
+/// A struct to combine essential information from [`cmsis_pack::pdsc::Device::memories`].
+/// This is used to apply the necessary sorting and filtering in creating [`MemoryRegion`]s.
+// The sequence of the fields is important for the sorting by derived natural order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct DeviceMemory {
+    memory_type: MemoryType,
+    p_name: Option<String>,
+    is_boot_memory: bool,
+    memory_start: u64,
+    memory_end: u64,
+    name: String,
+}
+
+// From PR's https://github.com/probe-rs/target-gen/pull/20 and https://github.com/probe-rs/target-gen/pull/25:
+// TODO: What is the logic that justifies PR#20 selecting the largest memory? Shouldn't we match flash algo's with RAM based on load_address?
+// Flash and RAM regions are not guaranteed to be sorted in the PDSC file, so we:
+// - Sort them here.
+// - Merge contiguous regions.
+// Update: For multiple cores, we have to take processor access into account during this merge.
+/// Sorts the memory regions in the package and merges contiguous regions with the same attributes.
+fn get_mem_map(device: &Device) -> Vec<MemoryRegion> {
+    let mut device_memories: Vec<DeviceMemory> = device
+        .memories
+        .0
+        .iter()
+        .map(|(name, memory)| DeviceMemory {
+            name: name.clone(),
+            p_name: memory.p_name.clone(),
+            memory_type: if memory.default && memory.access.read && memory.access.write {
+                MemoryType::Ram
+            } else if memory.default
+                && memory.access.read
+                && memory.access.execute
+                && !memory.access.write
+            {
+                MemoryType::Nvm
+            } else {
+                MemoryType::Generic
+            },
+            memory_start: memory.start,
+            memory_end: memory.start + memory.size,
+            is_boot_memory: memory.startup,
+        })
+        .collect();
+
+    // Merge memory regions with the same attributes.
+    if device_memories.len() > 1 {
+        // Sort by memory type, then by processor name, then by boot memory, then by start address.
+        device_memories.sort();
+
+        let mut merged: Vec<DeviceMemory> = Vec::new();
+        let mut cur = device_memories.first().cloned().unwrap();
+        for region in device_memories.iter().skip(1) {
+            if region.is_boot_memory == cur.is_boot_memory && region.memory_start == cur.memory_end
+            {
+                // Merge with previous region.
+                cur.memory_end = region.memory_end;
+                cur.name = format!("{} + {}", cur.name, region.name);
+            } else {
+                merged.push(cur);
+                cur = region.clone();
+            }
+        }
+        merged.push(cur);
+        device_memories = merged;
+    }
+
+    // Finally, sort so that the LARGEST contiguous region is first for each core.
+    device_memories.sort_by_cached_key(|region| {
+        (
+            region.memory_type.clone(),
+            region.p_name.clone(),
+            (region.memory_start as i128 - region.memory_end as i128),
+        )
+    });
+
+    // Convert DeviceMemory's to MemoryRegion's, and assign cores to shared reqions.
+    let mut mem_map = vec![];
+    for region in &device_memories {
+        let current_core = region
+            .p_name
+            .as_ref()
+            .map(|s| s.to_ascii_lowercase())
+            .unwrap_or_else(|| "main".to_string());
+        match region.memory_type {
+            MemoryType::Ram => if let Some(MemoryRegion::Ram(existing_region)) = mem_map.iter_mut().find(|existing_region|{
+                matches!(existing_region, MemoryRegion::Ram(ram_region) if ram_region.name == Some(region.name.clone()))})
+                {
+                    existing_region.cores.push(current_core);
+                } else {
+                    mem_map.push(MemoryRegion::Ram(RamRegion {
+                    name: Some(region.name.clone()),
+                    range: region.memory_start..region.memory_end,
+                    is_boot_memory: region.is_boot_memory,
+                    cores: vec![current_core],
+                    access: Default::default(),
+                    }));
+                },
+            MemoryType::Nvm => if let Some(MemoryRegion::Nvm(existing_region)) = mem_map.iter_mut().find(|existing_region|{
+                matches!(existing_region, MemoryRegion::Nvm(nvm_region) if nvm_region.name == Some(region.name.clone()))})
+                {
+                    existing_region.cores.push(current_core);
+                } else {
+                    mem_map.push(MemoryRegion::Nvm(NvmRegion {
+                    name: Some(region.name.clone()),
+                    range: region.memory_start..region.memory_end,
+                    is_boot_memory: region.is_boot_memory,
+                    cores: vec![current_core],
+                    access: Default::default(),
+                    sectors: vec![],
+                    bank_swap: None,
+                    }));
+                },
+            MemoryType::Generic => if let Some(MemoryRegion::Generic(existing_region)) = mem_map.iter_mut().find(|existing_region|{
+                matches!(existing_region, MemoryRegion::Generic(generic_region) if generic_region.name == Some(region.name.clone()))})
+                {
+                    existing_region.cores.push(current_core);
+                } else {
+                    mem_map.push(MemoryRegion::Generic(GenericRegion {
+                    name: Some(region.name.clone()),
+                    range: region.memory_start..region.memory_end,
+                    cores: vec![current_core],
+                    access: Default::default(),
+                    }));
+                },
+        };
+    }
+    mem_map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::families_from_pack;
+    use crate::config::{CoreType, MemoryRegion};
+    use probe_rs_target::CoreAccessOptions;
+
+    const PDSC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package schemaVersion="1.7.7" xmlns:xs="http://www.w3.org/2001/XMLSchema-instance">
+  <vendor>Example</vendor>
+  <name>Example_DFP</name>
+  <description>Example devices</description>
+  <url>https://example.com/packs/</url>
+  <releases>
+    <release version="1.2.0">Second release</release>
+    <release version="1.0.0">First release</release>
+  </releases>
+  <devices>
+    <family Dfamily="EX32 Series" Dvendor="Example:0">
+      <processor Dcore="Cortex-M33" DcoreVersion="r0p4" Dfpu="SP_FPU" Dmpu="MPU" Dendian="Little-endian"/>
+      <device Dname="EX32A">
+        <debug __ap="2"/>
+        <memory id="IROM1" start="0x00000000" size="0x20000" startup="1" default="1"/>
+        <memory id="IROM2" start="0x00020000" size="0x20000" startup="1" default="1"/>
+        <memory id="IRAM1" start="0x20000000" size="0x8000" default="1"/>
+      </device>
+    </family>
+  </devices>
+</package>
+"#;
+
+    #[test]
+    fn devices_are_converted() {
+        let directory = std::env::temp_dir().join(format!("probe-rs-pack-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("Example.Example_DFP.pdsc"), PDSC).unwrap();
+
+        let families = families_from_pack(&directory).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(families.len(), 1);
+        let family = &families[0];
+        assert_eq!(family.name, "EX32 Series");
+        assert_eq!(family.pack_file_release.as_deref(), Some("1.2.0"));
+
+        let chip = &family.variants[0];
+        assert_eq!(chip.name, "EX32A");
+        assert_eq!(chip.cores[0].core_type, CoreType::Armv8m);
+        let CoreAccessOptions::Arm(options) = &chip.cores[0].core_access_options else {
+            panic!("The core is not an ARM core");
+        };
+        assert_eq!(options.ap, 2);
+
+        // The contiguous flash regions are merged.
+        let ranges: Vec<_> = chip
+            .memory_map
+            .iter()
+            .map(|region| match region {
+                MemoryRegion::Ram(ram) => ("ram", ram.range.clone()),
+                MemoryRegion::Nvm(nvm) => ("nvm", nvm.range.clone()),
+                MemoryRegion::Generic(generic) => ("generic", generic.range.clone()),
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            [("ram", 0x2000_0000..0x2000_8000), ("nvm", 0x0..0x4_0000)]
+        );
+    }
+}
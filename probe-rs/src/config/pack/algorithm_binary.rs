@@ -1,10 +1,8 @@
+use crate::config::MemoryRange;
 use goblin::{
     elf::program_header::PT_LOAD,
     elf64::section_header::{SHT_NOBITS, SHT_PROGBITS},
 };
-use probe_rs::config::MemoryRange;
-
-use anyhow::{anyhow, Result};
 
 const CODE_SECTION_KEY: (&str, u32) = ("PrgCode", SHT_PROGBITS);
 const DATA_SECTION_KEY: (&str, u32) = ("PrgData", SHT_PROGBITS);
@@ -41,7 +39,7 @@ pub(crate) struct AlgorithmBinary {
 
 impl AlgorithmBinary {
     /// Extract a new flash algorithm binary blob from an ELF data blob.
-    pub(crate) fn new(elf: &goblin::elf::Elf<'_>, buffer: &[u8]) -> Result<Self> {
+    pub(crate) fn new(elf: &goblin::elf::Elf<'_>, buffer: &[u8]) -> Result<Self, String> {
         let mut code_section = None;
         let mut data_section = None;
         let mut bss_section = None;
@@ -55,7 +53,7 @@ impl AlgorithmBinary {
             if ph.p_type == PT_LOAD && ph.p_filesz > 0 {
                 let sector = ph.p_offset..ph.p_offset + ph.p_filesz;
 
-                log::debug!("Program header: LOAD to VMA {:#010x}", ph.p_vaddr);
+                tracing::debug!("Program header: LOAD to VMA {:#010x}", ph.p_vaddr);
 
                 // Scan all sectors if they contain any part of the sections found.
                 for sh in &elf.section_headers {
@@ -88,18 +86,18 @@ impl AlgorithmBinary {
         }
 
         if !suspicious_sections.is_empty() {
-            log::warn!("The ELF file contains some unexpected sections, which should not be part of a flash loader: ");
+            tracing::warn!("The ELF file contains some unexpected sections, which should not be part of a flash loader: ");
 
             for section in suspicious_sections {
-                log::warn!("\t{}", section);
+                tracing::warn!("\t{}", section);
             }
 
-            log::warn!("Code should be placed in the '{}' section, and data should be placed in the '{}' section.", CODE_SECTION_KEY.0, DATA_SECTION_KEY.0);
+            tracing::warn!("Code should be placed in the '{}' section, and data should be placed in the '{}' section.", CODE_SECTION_KEY.0, DATA_SECTION_KEY.0);
         }
 
         // Check all the sections for validity and return the binary blob if possible.
         let code_section = code_section.ok_or_else(|| {
-            anyhow!(
+            format!(
                 "Section '{}' not found, which is required to be present.",
                 CODE_SECTION_KEY.0
             )
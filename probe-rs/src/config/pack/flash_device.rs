@@ -1,6 +1,6 @@
 use scroll::Pread;
 
-use anyhow::{anyhow, Result};
+use super::parser::read_elf_bin_data;
 
 /// A struct to describe one sector in Flash.
 #[derive(Clone, Debug)]
@@ -64,13 +64,17 @@ impl FlashDevice {
     const MAX_ID_STRING_LENGTH: usize = 128;
 
     /// Parses the `FlashDevice` struct from ELF binary data.
-    pub(crate) fn new(elf: &goblin::elf::Elf<'_>, buffer: &[u8], address: u32) -> Result<Self> {
+    pub(crate) fn new(
+        elf: &goblin::elf::Elf<'_>,
+        buffer: &[u8],
+        address: u32,
+    ) -> Result<Self, String> {
         // Extract all the sector data from the ELF blob.
         let sectors = Self::parse_sectors(elf, buffer, address);
 
         // Get the rest of the data stored in the struct.
-        let data = crate::parser::read_elf_bin_data(elf, buffer, address, Self::INFO_SIZE)
-            .ok_or_else(|| anyhow!("Failed to read binary data for flash device. Read address: {:#010x}, size: {} bytes", address, Self::INFO_SIZE))?;
+        let data = read_elf_bin_data(elf, buffer, address, Self::INFO_SIZE)
+            .ok_or_else(|| format!("Failed to read binary data for flash device. Read address: {:#010x}, size: {} bytes", address, Self::INFO_SIZE))?;
 
         // Get the string length of the name
         let hypothetical_length = data[2..2 + Self::MAX_ID_STRING_LENGTH]
@@ -105,7 +109,7 @@ impl FlashDevice {
         let mut offset = Self::INFO_SIZE;
         // As long as we find new sectors, keep em comming.
         while let Some(data) =
-            crate::parser::read_elf_bin_data(elf, buffer, address + offset, Self::SECTOR_INFO_SIZE)
+            read_elf_bin_data(elf, buffer, address + offset, Self::SECTOR_INFO_SIZE)
         {
             if let Some(sector) = SectorInfo::new(data) {
                 sectors.push(sector);
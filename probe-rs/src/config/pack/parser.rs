@@ -1,7 +1,7 @@
-use crate::flash_device::FlashDevice;
-use probe_rs::config::{FlashProperties, RawFlashAlgorithm, SectorDescription};
-
-use anyhow::{anyhow, Context, Result};
+use super::algorithm_binary::AlgorithmBinary;
+use super::flash_device::FlashDevice;
+use super::PackError;
+use crate::config::{FlashProperties, RawFlashAlgorithm, SectorDescription};
 
 /// Extract a chunk of data from an ELF binary.
 ///
@@ -18,8 +18,8 @@ pub(crate) fn read_elf_bin_data<'a>(
         let segment_address = ph.p_paddr as u32;
         let segment_size = ph.p_memsz.min(ph.p_filesz) as u32;
 
-        log::debug!("Segment address: {:#010x}", segment_address);
-        log::debug!("Segment size:    {} bytes", segment_size);
+        tracing::debug!("Segment address: {:#010x}", segment_address);
+        tracing::debug!("Segment size:    {} bytes", segment_size);
 
         // If the requested data is above the current segment, skip the segment.
         if address > segment_address + segment_size {
@@ -41,7 +41,7 @@ pub(crate) fn read_elf_bin_data<'a>(
     None
 }
 
-fn extract_flash_device(elf: &goblin::elf::Elf, buffer: &[u8]) -> Result<FlashDevice> {
+fn extract_flash_device(elf: &goblin::elf::Elf, buffer: &[u8]) -> Result<FlashDevice, String> {
     // Extract the flash device info.
     for sym in elf.syms.iter() {
         let name = &elf.strtab[sym.st_name];
@@ -54,16 +54,18 @@ fn extract_flash_device(elf: &goblin::elf::Elf, buffer: &[u8]) -> Result<FlashDe
     }
 
     // Failed to find flash device
-    Err(anyhow!("Failed to find 'FlashDevice' symbol in ELF file."))
+    Err("Failed to find 'FlashDevice' symbol in ELF file.".to_string())
 }
 
 /// Extracts a position & memory independent flash algorithm blob from the proveided ELF file.
+///
+/// The ELF file is an FLM file of a CMSIS-Pack, or a flash algorithm built like one.
 pub fn extract_flash_algo(
     mut file: impl std::io::Read,
     file_name: &std::path::Path,
     default: bool,
     fixed_load_address: bool,
-) -> Result<RawFlashAlgorithm> {
+) -> Result<RawFlashAlgorithm, PackError> {
     let mut buffer = vec![];
     file.read_to_end(&mut buffer)?;
 
@@ -71,13 +73,12 @@ pub fn extract_flash_algo(
 
     let elf = goblin::elf::Elf::parse(buffer.as_slice())?;
 
-    let flash_device = extract_flash_device(&elf, &buffer).context(format!(
-        "Failed to extract flash information from ELF file '{}'.",
-        file_name.display()
-    ))?;
+    let invalid = |reason| PackError::InvalidFlashAlgorithm(file_name.to_path_buf(), reason);
+
+    let flash_device = extract_flash_device(&elf, &buffer).map_err(invalid)?;
 
     // Extract binary blob.
-    let algorithm_binary = crate::algorithm_binary::AlgorithmBinary::new(&elf, &buffer)?;
+    let algorithm_binary = AlgorithmBinary::new(&elf, &buffer).map_err(invalid)?;
     algo.instructions = algorithm_binary.blob();
 
     let code_section_offset = algorithm_binary.code_section.start;
@@ -95,7 +96,7 @@ pub fn extract_flash_algo(
             "ProgramPage" => algo.pc_program_page = sym.st_value - code_section_offset as u64,
             "_SEGGER_RTT" => {
                 algo.rtt_location = Some(sym.st_value);
-                log::debug!("Found RTT control block at address {:#010x}", sym.st_value);
+                tracing::debug!("Found RTT control block at address {:#010x}", sym.st_value);
             }
 
             _ => {}
@@ -103,16 +104,18 @@ pub fn extract_flash_algo(
     }
 
     if fixed_load_address {
-        log::debug!(
+        tracing::debug!(
             "Flash algorithm will be loaded at fixed address {:#010x}",
             algorithm_binary.code_section.load_address
         );
 
-        anyhow::ensure!(
-            algorithm_binary.is_continuous_in_ram(),
-            "If the flash algorithm is not position independent, all sections have to follow each other in RAM. \
-            Please check your linkerscript."
-        );
+        if !algorithm_binary.is_continuous_in_ram() {
+            return Err(invalid(
+                "If the flash algorithm is not position independent, all sections have to follow each other in RAM. \
+                Please check your linkerscript."
+                    .to_string(),
+            ));
+        }
 
         algo.load_address = Some(algorithm_binary.code_section.load_address as u64);
     }
@@ -1,11 +1,19 @@
 //! Internal target registry
 
+use super::overlay::{apply_overlay, overlay_family};
 use super::{Chip, ChipFamily, ChipInfo, Core, Target, TargetDescriptionSource};
+use crate::architecture::arm::{dp::TARGETID, ArmChipInfo};
 use crate::config::CoreType;
 use once_cell::sync::Lazy;
-use probe_rs_target::{CoreAccessOptions, RiscvCoreAccessOptions};
+use probe_rs_target::{
+    chip_name_matches, ChipDetection, CoreAccessOptions, RiscvCoreAccessOptions,
+};
+use std::collections::HashMap;
+use std::fs;
 use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 static REGISTRY: Lazy<Arc<Mutex<Registry>>> =
     Lazy::new(|| Arc::new(Mutex::new(Registry::from_builtin_families())));
@@ -37,6 +45,19 @@ pub enum RegistryError {
     /// An invalid [`ChipFamily`] was encountered.
     #[error("Invalid chip family definition ({})", .0.name)]
     InvalidChipFamilyDefinition(Box<ChipFamily>, String),
+    /// The family patched by an overlay was not found in the registry.
+    #[error("The family '{0}' patched by the overlay was not found in the list of known targets.")]
+    ChipFamilyNotFound(String),
+    /// A target description overlay is malformed.
+    #[error("Invalid target description overlay: {0}")]
+    InvalidOverlay(String),
+    /// A target description file of a target directory could not be loaded.
+    #[error("The target description file '{}' could not be loaded", .0.display())]
+    InvalidTargetFile(PathBuf, #[source] Box<RegistryError>),
+    /// An error occurred while reading a CMSIS-Pack.
+    #[cfg(feature = "cmsis-pack")]
+    #[error("The CMSIS-Pack could not be read")]
+    Pack(#[from] super::pack::PackError),
 }
 
 fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
@@ -53,6 +74,9 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
             ],
 
             flash_algorithms: vec![],
+            image_checksums: vec![],
+            unique_id: vec![],
+            chip_detection: vec![],
             source: TargetDescriptionSource::Generic,
         },
         ChipFamily {
@@ -62,6 +86,9 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
             pack_file_release: None,
             variants: vec![Chip::generic_arm("Cortex-M3", CoreType::Armv7m)],
             flash_algorithms: vec![],
+            image_checksums: vec![],
+            unique_id: vec![],
+            chip_detection: vec![],
             source: TargetDescriptionSource::Generic,
         },
         ChipFamily {
@@ -74,6 +101,9 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 Chip::generic_arm("Cortex-M7", CoreType::Armv7em),
             ],
             flash_algorithms: vec![],
+            image_checksums: vec![],
+            unique_id: vec![],
+            chip_detection: vec![],
             source: TargetDescriptionSource::Generic,
         },
         ChipFamily {
@@ -88,6 +118,9 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 Chip::generic_arm("Cortex-M55", CoreType::Armv8m),
             ],
             flash_algorithms: vec![],
+            image_checksums: vec![],
+            unique_id: vec![],
+            chip_detection: vec![],
             source: TargetDescriptionSource::Generic,
         },
         ChipFamily {
@@ -101,24 +134,60 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 cores: vec![Core {
                     name: "core".to_owned(),
                     core_type: CoreType::Riscv,
-                    core_access_options: CoreAccessOptions::Riscv(RiscvCoreAccessOptions {}),
+                    core_access_options: CoreAccessOptions::Riscv(RiscvCoreAccessOptions::default()),
                 }],
                 memory_map: vec![],
                 flash_algorithms: vec![],
+                access_ports: None,
+                boot_mode_pins: None,
             }],
             flash_algorithms: vec![],
+            image_checksums: vec![],
+            unique_id: vec![],
+            chip_detection: vec![],
             source: TargetDescriptionSource::Generic,
         },
     ]);
 }
 
+/// Returns `probe-rs/targets` in the configuration directory of the user, which is
+/// `$XDG_CONFIG_HOME` or `~/.config`, and `%APPDATA%` on Windows.
+fn default_target_directories() -> Vec<PathBuf> {
+    let config_directory = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|directory| !directory.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+
+    config_directory
+        .map(|directory| directory.join("probe-rs").join("targets"))
+        .into_iter()
+        .collect()
+}
+
 /// Registry of all available targets.
 struct Registry {
     /// All the available chips.
     families: Vec<ChipFamily>,
+    /// The directories which are scanned for target descriptions.
+    target_directories: Vec<PathBuf>,
+    /// The target description files loaded from the target directories, and when they were
+    /// modified.
+    loaded_files: HashMap<PathBuf, SystemTime>,
 }
 
 impl Registry {
+    fn new(families: Vec<ChipFamily>) -> Self {
+        Self {
+            families,
+            target_directories: default_target_directories(),
+            loaded_files: HashMap::new(),
+        }
+    }
+
     #[cfg(feature = "builtin-targets")]
     fn from_builtin_families() -> Self {
         const BUILTIN_TARGETS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/targets.bincode"));
@@ -134,7 +203,7 @@ impl Registry {
         // Additionally, validation for existing targets is done in the tests `validate_generic_targets` and
         // `validate_builtin` as well, to ensure we do not ship broken target definitions.
 
-        Self { families }
+        Self::new(families)
     }
 
     #[cfg(not(feature = "builtin-targets"))]
@@ -146,7 +215,7 @@ impl Registry {
         // Additionally, validation for existing targets is done in the tests `validate_generic_targets` and
         // `validate_builtin` as well, to ensure we do not ship broken target definitions.
 
-        Self { families }
+        Self::new(families)
     }
 
     fn families(&self) -> &Vec<ChipFamily> {
@@ -230,45 +299,42 @@ impl Registry {
         targets
     }
 
-    fn get_target_by_chip_info(&self, chip_info: ChipInfo) -> Result<Target, RegistryError> {
-        let (family, chip) = {
-            match chip_info {
-                ChipInfo::Arm(chip_info) => {
-                    // Try get the corresponding chip.
-
-                    let families = self.families.iter().filter(|f| {
-                        f.manufacturer
-                            .map(|m| m == chip_info.manufacturer)
-                            .unwrap_or(false)
-                    });
+    fn get_target_by_chip_info(
+        &self,
+        chip_info: ChipInfo,
+        read_register: &mut dyn FnMut(&Chip, u64) -> Option<u32>,
+    ) -> Result<Target, RegistryError> {
+        let ChipInfo::Arm {
+            rom_table,
+            target_id,
+        } = &chip_info;
 
-                    let mut identified_chips = Vec::new();
+        let mut identified_chips = Vec::new();
 
-                    for family in families {
-                        tracing::debug!("Checking family {}", family.name);
+        for family in &self.families {
+            tracing::debug!("Checking family {}", family.name);
 
-                        let chips = family
-                            .variants()
-                            .iter()
-                            .filter(|v| v.part.map(|p| p == chip_info.part).unwrap_or(false))
-                            .map(|c| (family, c));
+            let chips = identify_arm_chips(
+                family,
+                rom_table.as_ref(),
+                target_id.as_ref(),
+                &mut *read_register,
+            );
 
-                        identified_chips.extend(chips)
-                    }
+            identified_chips.extend(chips.into_iter().map(|chip| (family, chip)));
+        }
 
-                    if identified_chips.len() == 1 {
-                        identified_chips.pop().unwrap()
-                    } else {
-                        tracing::debug!(
-                        "Found {} matching chips for information {:?}, unable to determine chip",
-                        identified_chips.len(),
-                        chip_info
-                    );
-                        return Err(RegistryError::ChipAutodetectFailed);
-                    }
-                }
-            }
+        let (family, chip) = if identified_chips.len() == 1 {
+            identified_chips.pop().unwrap()
+        } else {
+            tracing::debug!(
+                "Found {} matching chips for information {:?}, unable to determine chip",
+                identified_chips.len(),
+                chip_info
+            );
+            return Err(RegistryError::ChipAutodetectFailed);
         };
+
         self.get_target(family, chip)
     }
 
@@ -281,8 +347,33 @@ impl Registry {
     where
         R: Read,
     {
-        let family: ChipFamily = serde_yaml::from_reader(yaml_reader)?;
+        let family = self.parse_family(yaml_reader)?;
+        self.add_family(family)
+    }
+
+    fn parse_family<R>(&self, yaml_reader: R) -> Result<ChipFamily, RegistryError>
+    where
+        R: Read,
+    {
+        let description: serde_yaml::Value = serde_yaml::from_reader(yaml_reader)?;
+
+        let family: ChipFamily = match overlay_family(&description) {
+            Some(name) => {
+                let name = name?;
+                let base = self
+                    .families
+                    .iter()
+                    .find(|family| family.name == name)
+                    .ok_or_else(|| RegistryError::ChipFamilyNotFound(name.to_string()))?;
+                apply_overlay(base, description)?
+            }
+            None => serde_yaml::from_value(description)?,
+        };
+
+        Ok(family)
+    }
 
+    fn add_family(&mut self, family: ChipFamily) -> Result<(), RegistryError> {
         family
             .validate()
             .map_err(|e| RegistryError::InvalidChipFamilyDefinition(Box::new(family.clone()), e))?;
@@ -298,6 +389,67 @@ impl Registry {
 
         Ok(())
     }
+
+    /// Load the target descriptions of the target directories which are new or were modified
+    /// since they were loaded.
+    ///
+    /// The `.yaml` and `.yml` files of each directory are loaded in the order of their names. The
+    /// chips of a file replace the chips with the same name of other families.
+    fn load_target_directories(&mut self) -> Result<(), RegistryError> {
+        for directory in self.target_directories.clone() {
+            let entries = match fs::read_dir(&directory) {
+                Ok(entries) => entries,
+                // The default directory does not have to exist.
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let mut files = vec![];
+            for entry in entries {
+                let path = entry?.path();
+                let is_yaml = path
+                    .extension()
+                    .is_some_and(|extension| extension == "yaml" || extension == "yml");
+                if is_yaml && path.is_file() {
+                    files.push(path);
+                }
+            }
+            files.sort();
+
+            for path in files {
+                let modified = fs::metadata(&path)?.modified()?;
+                if self.loaded_files.get(&path) == Some(&modified) {
+                    continue;
+                }
+
+                tracing::debug!("Loading the target description {}", path.display());
+
+                self.load_target_file(&path)
+                    .map_err(|e| RegistryError::InvalidTargetFile(path.clone(), Box::new(e)))?;
+                self.loaded_files.insert(path, modified);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_target_file(&mut self, path: &Path) -> Result<(), RegistryError> {
+        let family = self.parse_family(fs::File::open(path)?)?;
+
+        // Chips described in the file are no longer looked up in the families they were in.
+        for other in self.families.iter_mut().filter(|f| f.name != family.name) {
+            other.variants.retain(|chip| {
+                !family
+                    .variants
+                    .iter()
+                    .any(|new| new.name.eq_ignore_ascii_case(&chip.name))
+            });
+        }
+        self.families
+            .retain(|f| f.name == family.name || !f.variants.is_empty());
+
+        self.add_family(family)
+    }
 }
 
 /// Get a target from the internal registry based on its name.
@@ -311,8 +463,111 @@ pub fn search_chips(name: impl AsRef<str>) -> Result<Vec<String>, RegistryError>
 }
 
 /// Try to retrieve a target based on [ChipInfo] read from a target.
-pub(crate) fn get_target_by_chip_info(chip_info: ChipInfo) -> Result<Target, RegistryError> {
-    REGISTRY.lock().unwrap().get_target_by_chip_info(chip_info)
+///
+/// `read_register` reads the ID registers of the [`ChipDetection`] methods, on a target which
+/// might be the given chip. It returns `None` if the register cannot be read.
+pub(crate) fn get_target_by_chip_info(
+    chip_info: ChipInfo,
+    mut read_register: impl FnMut(&Chip, u64) -> Option<u32>,
+) -> Result<Target, RegistryError> {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .get_target_by_chip_info(chip_info, &mut read_register)
+}
+
+/// The chips of `family` which match the information read from an Arm target.
+///
+/// The chips are identified by the TARGETID of the debug port first, and by the part number in
+/// the ROM table otherwise. If neither identifies a chip, but the manufacturer matches, all chips
+/// of the family are candidates. Then the ID registers of the family narrow them down.
+fn identify_arm_chips<'a>(
+    family: &'a ChipFamily,
+    rom_table: Option<&ArmChipInfo>,
+    target_id: Option<&TARGETID>,
+    read_register: &mut dyn FnMut(&Chip, u64) -> Option<u32>,
+) -> Vec<&'a Chip> {
+    let Some(manufacturer) = family.manufacturer else {
+        return vec![];
+    };
+
+    let named = |names: &[String]| -> Vec<&'a Chip> {
+        family
+            .variants()
+            .iter()
+            .filter(|chip| {
+                names.is_empty() || names.iter().any(|name| chip_name_matches(&chip.name, name))
+            })
+            .collect()
+    };
+
+    let target_id = target_id.filter(|target_id| target_id.designer() == manufacturer);
+    let rom_table = rom_table.filter(|info| info.manufacturer == manufacturer);
+
+    let by_target_id = target_id.and_then(|target_id| {
+        family
+            .chip_detection
+            .iter()
+            .find_map(|method| match method {
+                ChipDetection::ArmTargetId { part, chips } if *part == target_id.tpartno() => {
+                    Some(chips)
+                }
+                _ => None,
+            })
+    });
+
+    let mut chips = match by_target_id {
+        Some(names) => named(names),
+        None => rom_table
+            .map(|info| {
+                family
+                    .variants()
+                    .iter()
+                    .filter(|chip| chip.part == Some(info.part))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    let has_id_registers = family
+        .chip_detection
+        .iter()
+        .any(|method| matches!(method, ChipDetection::IdRegister { .. }));
+
+    if chips.is_empty() && has_id_registers && (target_id.is_some() || rom_table.is_some()) {
+        chips = named(&[]);
+    }
+
+    for method in &family.chip_detection {
+        let ChipDetection::IdRegister {
+            address,
+            mask,
+            values,
+        } = method
+        else {
+            continue;
+        };
+        let Some(&first) = chips.first() else {
+            break;
+        };
+
+        let value = read_register(first, *address).map(|value| value & mask);
+        tracing::debug!(
+            "ID register {:#010x} of {}: {:x?}",
+            address,
+            family.name,
+            value
+        );
+
+        match value.and_then(|value| values.get(&value)) {
+            Some(names) => {
+                chips.retain(|chip| names.iter().any(|name| chip_name_matches(&chip.name, name)))
+            }
+            None => chips.clear(),
+        }
+    }
+
+    chips
 }
 
 /// Parse a target description and add the contained targets
@@ -338,6 +593,36 @@ pub(crate) fn get_target_by_chip_info(chip_info: ChipInfo) -> Result<Target, Reg
 /// const BUILTIN_TARGET_YAML: &[u8] = include_bytes!("/path/target.yaml");
 /// probe_rs::config::add_target_from_yaml(BUILTIN_TARGET_YAML)?;
 /// ```
+///
+/// # Overlays
+///
+/// A YAML file with an `overlay` field patches the family named by that field, instead of
+/// adding a new family. Only the fields which change have to be given:
+///
+/// ```yaml
+/// overlay: nRF52 Series
+/// variants:
+///   - name: nRF52840_xxAA
+///     memory_map:
+///       - !Ram
+///           name: EXTRAM
+///           range:
+///             start: 0x60000000
+///             end: 0x60100000
+///           cores:
+///             - main
+/// ```
+///
+/// The overlay is merged into the family field by field:
+///
+/// - Mappings are merged key by key, other values replace the values of the family.
+/// - Lists of mappings are merged element by element. Elements with the same `name`, or memory
+///   regions with the same start address, are merged, all others are added to the list.
+/// - Lists of other values, for example the flash algorithms of a chip, are replaced.
+///
+/// A chip of the overlay with an `extends` field is added as a copy of the named chip of the
+/// family, as it was before the overlay, with the other fields of the chip merged into it. If the overlay changes the
+/// `name` of the family, the patched family is added next to the original one.
 pub fn add_target_from_yaml<R>(yaml_reader: R) -> Result<(), RegistryError>
 where
     R: Read,
@@ -345,6 +630,58 @@ where
     REGISTRY.lock().unwrap().add_target_from_yaml(yaml_reader)
 }
 
+/// Read a CMSIS-Pack and add the contained targets to the internal target registry.
+///
+/// `path` is either a `.pack` file or the directory a pack was unzipped into. The memory maps,
+/// cores and flash algorithms of all devices in the pack are converted, like `target-gen pack`
+/// does, and families which are in the registry already are replaced.
+///
+/// ```no_run
+/// probe_rs::config::add_target_from_pack("/path/Vendor.DeviceFamilyPack.1.0.0.pack")?;
+///
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[cfg(feature = "cmsis-pack")]
+pub fn add_target_from_pack(path: impl AsRef<std::path::Path>) -> Result<(), RegistryError> {
+    let families = super::pack::families_from_pack(path)?;
+
+    let mut registry = REGISTRY.lock().unwrap();
+    for family in families {
+        registry.add_family(family)?;
+    }
+
+    Ok(())
+}
+
+/// Set the directories which are scanned for target descriptions by
+/// [`load_target_directories`].
+///
+/// By default, this is `probe-rs/targets` in the configuration directory of the user, for
+/// example `~/.config/probe-rs/targets`.
+pub fn set_target_directories(directories: impl IntoIterator<Item = PathBuf>) {
+    REGISTRY.lock().unwrap().target_directories = directories.into_iter().collect();
+}
+
+/// Get the directories which are scanned for target descriptions by [`load_target_directories`].
+pub fn target_directories() -> Vec<PathBuf> {
+    REGISTRY.lock().unwrap().target_directories.clone()
+}
+
+/// Load the YAML target descriptions of the target directories into the internal target
+/// registry, see [`set_target_directories`].
+///
+/// The `.yaml` and `.yml` files of each directory are loaded like by [`add_target_from_yaml`],
+/// in the order of their names. The chips of a file replace the chips with the same name in the
+/// built-in targets and in the files loaded before it.
+///
+/// This is done when a session is created with a target name or with target detection, so
+/// changes to the files are picked up by the next session. Files which were loaded already are
+/// only loaded again if they were modified, and removed files keep their targets in the
+/// registry.
+pub fn load_target_directories() -> Result<(), RegistryError> {
+    REGISTRY.lock().unwrap().load_target_directories()
+}
+
 /// Get a list of all families which are contained in the internal
 /// registry.
 pub fn families() -> Result<Vec<ChipFamily>, RegistryError> {
@@ -369,6 +706,7 @@ fn match_name_prefix(pattern: &str, name: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use probe_rs_target::{MemoryRegion, UniqueIdPart};
 
     #[test]
     fn try_fetch_not_unique() {
@@ -410,6 +748,24 @@ mod tests {
         assert!(registry.get_target_by_name("nrf51822_Xxaa").is_ok());
     }
 
+    #[test]
+    fn unique_id_location() {
+        let registry = Registry::from_builtin_families();
+
+        let target = registry.get_target_by_name("STM32F407VGTx").unwrap();
+        assert_eq!(
+            target.unique_id,
+            [UniqueIdPart {
+                address: 0x1FFF_7A10,
+                size: 12
+            }]
+        );
+
+        let target = registry.get_target_by_name("ATSAMD21G18A").unwrap();
+        assert_eq!(target.unique_id.len(), 4);
+        assert_eq!(target.unique_id[0].address, 0x0080_A00C);
+    }
+
     #[test]
     fn validate_generic_targets() {
         let mut families = vec![];
@@ -431,5 +787,195 @@ mod tests {
             .map(|family| family.validate())
             .collect::<Result<Vec<_>, _>>()
             .unwrap();
+
+        // The chip detection methods only name chips of their family.
+        for family in registry.families() {
+            for name in family.chip_detection.iter().flat_map(ChipDetection::chips) {
+                assert!(
+                    family
+                        .variants()
+                        .iter()
+                        .any(|chip| chip_name_matches(&chip.name, name)),
+                    "{} names the unknown chip {}",
+                    family.name,
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn overlay_patches_builtin_family() {
+        let mut registry = Registry::from_builtin_families();
+        let overlay = br#"
+overlay: nRF52 Series
+variants:
+  - name: nRF52840_xxAA
+    cores:
+      - name: main
+        core_access_options: !Arm
+          debug_base: 0xE000EDF0
+    memory_map:
+      - !Nvm
+          range:
+            start: 0x0
+            end: 0x80000
+      - !Ram
+          name: EXTRAM
+          range:
+            start: 0x60000000
+            end: 0x60100000
+          is_boot_memory: false
+          cores:
+            - main
+  - name: nRF52840_custom
+    extends: nRF52840_xxAA
+    flash_algorithms: []
+"#;
+        registry.add_target_from_yaml(&overlay[..]).unwrap();
+
+        let target = registry.get_target_by_name("nRF52840_xxAA").unwrap();
+        let CoreAccessOptions::Arm(options) = &target.cores[0].core_access_options else {
+            panic!("nRF52840 has an ARM core");
+        };
+        assert_eq!(options.debug_base, Some(0xE000_EDF0));
+        assert_eq!(target.memory_map.len(), 4);
+        assert!(target.memory_map.iter().any(|region| matches!(
+            region,
+            MemoryRegion::Nvm(nvm) if nvm.range == (0..0x8_0000) && nvm.is_boot_memory
+        )));
+        assert!(target
+            .memory_map
+            .iter()
+            .any(|region| matches!(region, MemoryRegion::Ram(ram) if ram.name.as_deref() == Some("EXTRAM"))));
+        assert!(!target.flash_algorithms.is_empty());
+        assert_eq!(target.unique_id.len(), 1);
+
+        // The copy is made before the other chips of the overlay are patched.
+        let custom = registry.get_target_by_name("nRF52840_custom").unwrap();
+        assert_eq!(custom.memory_map.len(), 3);
+        assert!(custom.flash_algorithms.is_empty());
+
+        assert!(matches!(
+            registry.add_target_from_yaml(&b"overlay: Unknown Family"[..]),
+            Err(RegistryError::ChipFamilyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn target_directories_override_chips() {
+        let directory =
+            std::env::temp_dir().join(format!("probe-rs-targets-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let description = |ram_end: u32| {
+            format!(
+                r#"
+name: Custom nRF52
+variants:
+  - name: nRF52840_xxAA
+    cores:
+      - name: main
+        type: armv7em
+        core_access_options: !Arm
+          ap: 0
+          psel: 0
+    memory_map:
+      - !Ram
+          range:
+            start: 0x20000000
+            end: {ram_end:#x}
+          cores:
+            - main
+flash_algorithms: []
+"#
+            )
+        };
+        let path = directory.join("nrf52840.yaml");
+        fs::write(&path, description(0x2004_0000)).unwrap();
+
+        let mut registry = Registry::from_builtin_families();
+        registry.target_directories = vec![directory.clone(), directory.join("missing")];
+        registry.load_target_directories().unwrap();
+
+        // The chip of the file replaces the built-in chip, instead of making the name ambiguous.
+        let target = registry.get_target_by_name("nRF52840_xxAA").unwrap();
+        assert_eq!(target.memory_map.len(), 1);
+        assert!(registry.get_target_by_name("nRF52832_xxAA").is_ok());
+
+        // Modified files are loaded again. The file may be written within the timestamp
+        // resolution of the file system, so the time it was loaded with is moved back instead.
+        fs::write(&path, description(0x2008_0000)).unwrap();
+        registry.loaded_files.insert(path, SystemTime::UNIX_EPOCH);
+        registry.load_target_directories().unwrap();
+
+        let target = registry.get_target_by_name("nRF52840_xxAA").unwrap();
+        assert_eq!(
+            target.memory_map[0].address_range(),
+            &(0x2000_0000..0x2008_0000)
+        );
+
+        fs::write(directory.join("invalid.yml"), "name: [").unwrap();
+        assert!(matches!(
+            registry.load_target_directories(),
+            Err(RegistryError::InvalidTargetFile(path, _)) if path.ends_with("invalid.yml")
+        ));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn chips_are_identified_by_id_registers() {
+        let registry = Registry::from_builtin_families();
+
+        // All nRF52 parts have the same part number in the ROM table.
+        let nordic = ChipInfo::Arm {
+            rom_table: Some(ArmChipInfo {
+                manufacturer: jep106::JEP106Code::new(0x2, 0x44),
+                part: 0x6,
+            }),
+            target_id: None,
+        };
+        let mut reads = vec![];
+        let target = registry
+            .get_target_by_chip_info(nordic, &mut |_, address| {
+                reads.push(address);
+                match address {
+                    0x1000_0100 => Some(0x52840),
+                    // "AAD0"
+                    0x1000_0104 => Some(0x4141_4430),
+                    _ => None,
+                }
+            })
+            .unwrap();
+        assert_eq!(target.name, "nRF52840_xxAA");
+        // Only the registers of the families of the manufacturer are read.
+        assert_eq!(reads, [0x1000_0100, 0x1000_0104]);
+
+        // STM32U575 and STM32U585 parts have the same TARGETID.
+        let family = registry
+            .families
+            .iter()
+            .find(|family| family.name == "STM32U5 Series")
+            .unwrap();
+        let chips = identify_arm_chips(
+            family,
+            None,
+            Some(&TARGETID(0x0482_0041)),
+            &mut |_, _| unreachable!(),
+        );
+        assert!(!chips.is_empty());
+        assert!(chips
+            .iter()
+            .all(|chip| chip.name.starts_with("STM32U575") || chip.name.starts_with("STM32U585")));
+
+        // Another part number of the designer does not match.
+        let chips = identify_arm_chips(
+            family,
+            None,
+            Some(&TARGETID(0x0481_0041)),
+            &mut |_, _| unreachable!(),
+        );
+        assert!(chips.is_empty());
     }
 }
@@ -1,8 +1,8 @@
-use probe_rs_target::{Architecture, ChipFamily};
+use probe_rs_target::{Architecture, ChipFamily, ImageChecksum, UniqueIdPart};
 
 use super::{Core, MemoryRegion, RawFlashAlgorithm, RegistryError, TargetDescriptionSource};
 use crate::architecture::arm::{
-    ap::MemoryAp,
+    ap::{ApScanOptions, MemoryAp},
     sequences::{
         atsame5x::AtSAME5x,
         efm32xg2::EFM32xG2,
@@ -14,9 +14,9 @@ use crate::architecture::arm::{
         stm32_armv6::{Stm32Armv6, Stm32Armv6Family},
         stm32_armv7::Stm32Armv7,
         stm32h7::Stm32h7,
-        ArmDebugSequence,
+        ArmDebugSequence, BootModePins,
     },
-    ApAddress, DpAddress,
+    ApAddress, DpAddress, Pins,
 };
 use crate::architecture::riscv::sequences::{esp32c3::ESP32C3, esp32c6::ESP32C6};
 use crate::architecture::riscv::sequences::{DefaultRiscvSequence, RiscvDebugSequence};
@@ -30,6 +30,9 @@ use crate::architecture::arm::sequences::DefaultArmSequence;
 pub struct Target {
     /// The name of the target.
     pub name: String,
+    /// Whether the boot ROM only starts an image if word 7 of its vector table is the two's
+    /// complement of the sum of the first 7 words, like on the NXP LPC8xx and LPC11xx families.
+    pub(crate) checks_vector_table: bool,
     /// The cores of the target.
     pub cores: Vec<Core>,
     /// The name of the flash algorithm.
@@ -40,6 +43,15 @@ pub struct Target {
     pub(crate) source: TargetDescriptionSource,
     /// Debug sequences for the given target.
     pub debug_sequence: DebugSequence,
+    /// Checksums which have to be stored in images for the target to boot them.
+    pub image_checksums: Vec<ImageChecksum>,
+    /// Where the unique ID of the target is stored, see
+    /// [`Session::read_unique_id`](crate::Session::read_unique_id).
+    pub unique_id: Vec<UniqueIdPart>,
+    /// How the access ports of ARM targets are found.
+    pub ap_scan: ApScanOptions,
+    /// The probe pins which select the boot mode, if the target description declares them.
+    pub boot_mode_pins: Option<BootModePins>,
 }
 
 impl std::fmt::Debug for Target {
@@ -173,11 +185,23 @@ impl Target {
 
         Ok(Target {
             name: chip.name.clone(),
+            checks_vector_table: family.name.starts_with("LPC8")
+                || family.name.starts_with("LPC11"),
             cores: chip.cores.clone(),
             flash_algorithms,
             source: family.source.clone(),
             memory_map: chip.memory_map.clone(),
             debug_sequence,
+            image_checksums: family.image_checksums.clone(),
+            unique_id: family.unique_id.clone(),
+            ap_scan: ApScanOptions {
+                access_ports: chip.access_ports.clone(),
+                ..Default::default()
+            },
+            boot_mode_pins: chip.boot_mode_pins.map(|pins| BootModePins {
+                select: Pins(pins.select),
+                bootloader: Pins(pins.bootloader),
+            }),
         })
     }
 
@@ -250,24 +274,30 @@ pub enum TargetSelector {
     /// Try to automatically identify the target,
     /// by reading identifying information from
     /// the probe and / or target.
+    ///
+    /// The target name `auto` selects this as well.
     Auto,
 }
 
 impl From<&str> for TargetSelector {
     fn from(value: &str) -> Self {
-        TargetSelector::Unspecified(value.into())
+        value.to_string().into()
     }
 }
 
 impl From<&String> for TargetSelector {
     fn from(value: &String) -> Self {
-        TargetSelector::Unspecified(value.into())
+        value.clone().into()
     }
 }
 
 impl From<String> for TargetSelector {
     fn from(value: String) -> Self {
-        TargetSelector::Unspecified(value)
+        if value.eq_ignore_ascii_case("auto") {
+            TargetSelector::Auto
+        } else {
+            TargetSelector::Unspecified(value)
+        }
     }
 }
 
@@ -1,19 +1,29 @@
 use crate::{
-    architecture::arm::sequences::ArmDebugSequence, debug::DebugRegisters, error, CoreType, Error,
-    InstructionSet, MemoryInterface, Target,
+    architecture::arm::{core::armv8m, sequences::ArmDebugSequence, ArmError},
+    cancellation::CancellationToken,
+    debug::DebugRegisters,
+    error, CoreType, Error, InstructionSet, MemoryInterface, SessionHooks, Target,
 };
 use anyhow::{anyhow, Result};
 pub use probe_rs_target::{Architecture, CoreAccessOptions};
-use probe_rs_target::{ArmCoreAccessOptions, RiscvCoreAccessOptions};
+use probe_rs_target::{ArmCoreAccessOptions, MemoryRange, MemoryRegion, RiscvCoreAccessOptions};
 use std::{sync::Arc, time::Duration};
 
+pub mod breakpoints;
 pub mod core_state;
 pub mod core_status;
+pub mod halt_escalation;
 pub mod memory_mapped_registers;
 pub mod registers;
 
+pub use breakpoints::{
+    Breakpoint, BreakpointBatch, BreakpointKind, BreakpointManager, BreakpointUnit,
+    UnsatisfiedBreakpoint,
+};
+use breakpoints::{Placement, SoftwareBreakpoint};
 pub use core_state::*;
 pub use core_status::*;
+pub use halt_escalation::{HaltEscalation, HaltOutcome, HaltPath};
 pub use memory_mapped_registers::MemoryMappedRegister;
 pub use registers::*;
 
@@ -24,6 +34,28 @@ pub struct CoreInformation {
     pub pc: u64,
 }
 
+/// The kind of memory access which triggers a [`Watchpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchpointKind {
+    /// Halt when the watched memory is read.
+    Read,
+    /// Halt when the watched memory is written.
+    Write,
+    /// Halt when the watched memory is read or written.
+    ReadWrite,
+}
+
+/// A hardware watchpoint, which halts the core when a range of memory is accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// The start address of the watched memory.
+    pub address: u64,
+    /// The number of watched bytes.
+    pub length: u64,
+    /// The kind of access which triggers the watchpoint.
+    pub kind: WatchpointKind,
+}
+
 /// A generic interface to control a MCU core.
 pub trait CoreInterface: MemoryInterface + ExceptionInterface {
     /// Numerical ID of the core. Can be used as an argument to `Session::core()`.
@@ -75,6 +107,41 @@ pub trait CoreInterface: MemoryInterface + ExceptionInterface {
         value: registers::RegisterValue,
     ) -> Result<(), error::Error>;
 
+    /// Read the values of several core registers.
+    ///
+    /// Cores which can transfer several registers with fewer transactions than one by one
+    /// override this.
+    fn read_core_regs(
+        &mut self,
+        addresses: &[registers::RegisterId],
+    ) -> Result<Vec<registers::RegisterValue>, error::Error> {
+        addresses
+            .iter()
+            .map(|&address| self.read_core_reg(address))
+            .collect()
+    }
+
+    /// Write the values of several core registers.
+    ///
+    /// Cores which can transfer several registers with fewer transactions than one by one
+    /// override this.
+    fn write_core_regs(
+        &mut self,
+        registers: &[(registers::RegisterId, registers::RegisterValue)],
+    ) -> Result<(), error::Error> {
+        for &(address, value) in registers {
+            self.write_core_reg(address, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the core registers which were written while the core is halted to the core, if the
+    /// core implementation buffers these writes.
+    fn flush_registers(&mut self) -> Result<(), error::Error> {
+        Ok(())
+    }
+
     /// Returns all the available breakpoint units of the core.
     fn available_breakpoint_units(&mut self) -> Result<u32, error::Error>;
 
@@ -92,6 +159,44 @@ pub trait CoreInterface: MemoryInterface + ExceptionInterface {
     /// Clears the breakpoint configured in unit `unit_index`.
     fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), error::Error>;
 
+    /// Returns the number of available watchpoint units of the core.
+    fn available_watchpoint_units(&mut self) -> Result<u32, error::Error> {
+        Ok(0)
+    }
+
+    /// Read the hardware watchpoints configured on the core.
+    /// A value of None in any position of the Vector indicates that the position is unset/available.
+    fn hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, error::Error> {
+        Ok(vec![])
+    }
+
+    /// Returns for each watchpoint unit if it is free, that is if it is neither configured as
+    /// a watchpoint nor used for another function, like data tracing.
+    fn free_watchpoint_units(&mut self) -> Result<Vec<bool>, error::Error> {
+        Ok(self.hw_watchpoints()?.iter().map(Option::is_none).collect())
+    }
+
+    /// Sets `watchpoint` using unit `unit_index`.
+    fn set_hw_watchpoint(
+        &mut self,
+        _unit_index: usize,
+        _watchpoint: Watchpoint,
+    ) -> Result<(), error::Error> {
+        Err(Error::NotImplemented("hardware watchpoints"))
+    }
+
+    /// Clears the watchpoint configured in unit `unit_index`.
+    fn clear_hw_watchpoint(&mut self, _unit_index: usize) -> Result<(), error::Error> {
+        Err(Error::NotImplemented("hardware watchpoints"))
+    }
+
+    /// Returns the watchpoint which caused the core to halt, if it can be determined.
+    ///
+    /// This is only meaningful while the core is halted because of a watchpoint.
+    fn triggered_hw_watchpoint(&mut self) -> Result<Option<Watchpoint>, error::Error> {
+        Ok(None)
+    }
+
     /// Returns a list of all the registers of this core.
     fn registers(&self) -> &'static registers::CoreRegisters;
 
@@ -144,6 +249,27 @@ pub trait CoreInterface: MemoryInterface + ExceptionInterface {
     /// This will reset the changes done by `reset_catch_set`.
     fn reset_catch_clear(&mut self) -> Result<(), Error>;
 
+    /// Enable or disable halting the core when a fault exception is taken.
+    ///
+    /// This can halt a core which does not react to halt requests, but still runs into faults.
+    fn fault_catch_set(&mut self, _enabled: bool) -> Result<(), Error> {
+        Err(Error::NotImplemented("fault vector catch"))
+    }
+
+    /// Returns `true` if the target reset the core on its own since the last call, for example
+    /// because of a watchdog.
+    ///
+    /// Cores which can detect such resets do so in [`CoreInterface::status`].
+    fn take_target_reset(&mut self) -> bool {
+        false
+    }
+
+    /// Restore the debug settings which are lost when the target resets the debug logic of the
+    /// core, see [`CoreInterface::take_target_reset`].
+    fn restore_debug_settings(&mut self) -> Result<(), Error> {
+        Err(Error::NotImplemented("restoring debug settings"))
+    }
+
     /// Called when we stop debugging a core.
     fn debug_core_stop(&mut self) -> Result<(), Error>;
 }
@@ -154,58 +280,72 @@ impl<'probe> MemoryInterface for Core<'probe> {
     }
 
     fn read_word_64(&mut self, address: u64) -> Result<u64, Error> {
+        self.check_memory_access(address, 8, MemoryOperation::Read)?;
         self.inner.read_word_64(address)
     }
 
     fn read_word_32(&mut self, address: u64) -> Result<u32, Error> {
+        self.check_memory_access(address, 4, MemoryOperation::Read)?;
         self.inner.read_word_32(address)
     }
 
     fn read_word_8(&mut self, address: u64) -> Result<u8, Error> {
+        self.check_memory_access(address, 1, MemoryOperation::Read)?;
         self.inner.read_word_8(address)
     }
 
     fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), Error> {
+        self.check_memory_access(address, data.len() as u64 * 8, MemoryOperation::Read)?;
         self.inner.read_64(address, data)
     }
 
     fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), Error> {
+        self.check_memory_access(address, data.len() as u64 * 4, MemoryOperation::Read)?;
         self.inner.read_32(address, data)
     }
 
     fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        self.check_memory_access(address, data.len() as u64, MemoryOperation::Read)?;
         self.inner.read_8(address, data)
     }
 
     fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        self.check_memory_access(address, data.len() as u64, MemoryOperation::Read)?;
         self.inner.read(address, data)
     }
 
     fn write_word_64(&mut self, addr: u64, data: u64) -> Result<(), Error> {
+        self.check_memory_access(addr, 8, MemoryOperation::Write)?;
         self.inner.write_word_64(addr, data)
     }
 
     fn write_word_32(&mut self, addr: u64, data: u32) -> Result<(), Error> {
+        self.check_memory_access(addr, 4, MemoryOperation::Write)?;
         self.inner.write_word_32(addr, data)
     }
 
     fn write_word_8(&mut self, addr: u64, data: u8) -> Result<(), Error> {
+        self.check_memory_access(addr, 1, MemoryOperation::Write)?;
         self.inner.write_word_8(addr, data)
     }
 
     fn write_64(&mut self, addr: u64, data: &[u64]) -> Result<(), Error> {
+        self.check_memory_access(addr, data.len() as u64 * 8, MemoryOperation::Write)?;
         self.inner.write_64(addr, data)
     }
 
     fn write_32(&mut self, addr: u64, data: &[u32]) -> Result<(), Error> {
+        self.check_memory_access(addr, data.len() as u64 * 4, MemoryOperation::Write)?;
         self.inner.write_32(addr, data)
     }
 
     fn write_8(&mut self, addr: u64, data: &[u8]) -> Result<(), Error> {
+        self.check_memory_access(addr, data.len() as u64, MemoryOperation::Write)?;
         self.inner.write_8(addr, data)
     }
 
     fn write(&mut self, addr: u64, data: &[u8]) -> Result<(), Error> {
+        self.check_memory_access(addr, data.len() as u64, MemoryOperation::Write)?;
         self.inner.write(addr, data)
     }
 
@@ -293,6 +433,13 @@ impl<'probe> ExceptionInterface for Core<'probe> {
     }
 }
 
+/// The kind of an access checked by [`Core::check_memory_access`].
+#[derive(Clone, Copy)]
+enum MemoryOperation {
+    Read,
+    Write,
+}
+
 /// Generic core handle representing a physical core on an MCU.
 ///
 /// This should be considered as a temporary view of the core which locks the debug probe driver to as single consumer by borrowing it.
@@ -301,6 +448,10 @@ impl<'probe> ExceptionInterface for Core<'probe> {
 /// to allow potential other shareholders of the session struct to grab a core handle too.
 pub struct Core<'probe> {
     inner: Box<dyn CoreInterface + 'probe>,
+    hooks: Option<&'probe SessionHooks>,
+    memory_map: &'probe [MemoryRegion],
+    core_name: &'probe str,
+    breakpoints: Option<&'probe mut BreakpointManager>,
 }
 
 impl<'probe> Core<'probe> {
@@ -308,7 +459,112 @@ impl<'probe> Core<'probe> {
     pub(crate) fn new(core: impl CoreInterface + 'probe) -> Core<'probe> {
         Self {
             inner: Box::new(core),
+            hooks: None,
+            memory_map: &[],
+            core_name: "",
+            breakpoints: None,
+        }
+    }
+
+    /// Run the post-reset and on-halt `hooks` of the session for this core.
+    pub(crate) fn with_hooks(self, hooks: &'probe SessionHooks) -> Core<'probe> {
+        Self {
+            hooks: Some(hooks),
+            ..self
+        }
+    }
+
+    /// Keep the software breakpoints of the core in `breakpoints`.
+    pub(crate) fn with_breakpoints(
+        self,
+        breakpoints: &'probe mut BreakpointManager,
+    ) -> Core<'probe> {
+        Self {
+            breakpoints: Some(breakpoints),
+            ..self
+        }
+    }
+
+    /// Enforce the access restrictions of the regions of `memory_map` which are accessible by
+    /// the core named `core_name`.
+    pub(crate) fn with_memory_map(
+        self,
+        memory_map: &'probe [MemoryRegion],
+        core_name: &'probe str,
+    ) -> Core<'probe> {
+        Self {
+            memory_map,
+            core_name,
+            ..self
+        }
+    }
+
+    /// Check an access of `len` bytes at `address` against the access restrictions of the
+    /// memory regions of the target.
+    ///
+    /// Memory accesses are cancellation points, so this also fails if the current operation was
+    /// cancelled.
+    fn check_memory_access(
+        &mut self,
+        address: u64,
+        len: u64,
+        operation: MemoryOperation,
+    ) -> Result<(), Error> {
+        crate::cancellation::check()?;
+
+        let range = address..address.saturating_add(len);
+        let memory_map = self.memory_map;
+
+        for region in memory_map {
+            let access = region.access();
+
+            if access.is_unrestricted()
+                || !region.address_range().intersects_range(&range)
+                || !region.cores().iter().any(|core| core == self.core_name)
+            {
+                continue;
+            }
+
+            let reason = match operation {
+                MemoryOperation::Write if access.read_only => Some("the region is read-only"),
+                MemoryOperation::Read if access.write_only => Some("the region is write-only"),
+                _ => None,
+            };
+
+            let reason = match reason {
+                Some(reason) => Some(reason),
+                None if access.no_access_while_running && !self.inner.core_halted()? => {
+                    Some("the region cannot be accessed while the core is running")
+                }
+                None if access.requires_secure && !self.secure_debug_enabled()? => {
+                    Some("the region requires secure debug access")
+                }
+                None => None,
+            };
+
+            if let Some(reason) = reason {
+                return Err(Error::MemoryAccessDenied {
+                    address,
+                    region: region.name().unwrap_or("unnamed").to_owned(),
+                    reason,
+                });
+            }
         }
+
+        Ok(())
+    }
+
+    /// Whether the debugger may access Secure memory. Only ARMv8-M cores with the Security
+    /// Extension have a Secure state, other cores can always access all memory.
+    fn secure_debug_enabled(&mut self) -> Result<bool, Error> {
+        if self.inner.core_type() != CoreType::Armv8m {
+            return Ok(true);
+        }
+
+        let dhcsr =
+            armv8m::Dhcsr::from(self.inner.read_word_32(armv8m::Dhcsr::get_mmio_address())?);
+
+        Ok(dhcsr.s_sde())
     }
 
     /// Creates a new [`CoreState`]
@@ -357,7 +613,13 @@ impl<'probe> Core<'probe> {
     /// a [`DebugProbeError::Timeout`](crate::DebugProbeError::Timeout) error will be returned.
     #[tracing::instrument(skip(self))]
     pub fn wait_for_core_halted(&mut self, timeout: Duration) -> Result<(), error::Error> {
-        self.inner.wait_for_core_halted(timeout)
+        self.inner.wait_for_core_halted(timeout)?;
+
+        if let Some(hooks) = self.hooks {
+            hooks.run_on_halt(self)?;
+        }
+
+        Ok(())
     }
 
     /// Check if the core is halted. If the core does not halt on its own,
@@ -370,22 +632,167 @@ impl<'probe> Core<'probe> {
     /// returns a [`DebugProbeError::Timeout`](crate::DebugProbeError::Timeout) otherwise.
     #[tracing::instrument(skip(self))]
     pub fn halt(&mut self, timeout: Duration) -> Result<CoreInformation, error::Error> {
-        self.inner.halt(timeout)
+        let info = self.inner.halt(timeout)?;
+
+        if let Some(hooks) = self.hooks {
+            hooks.run_on_halt(self)?;
+        }
+
+        Ok(info)
     }
 
     /// Continue to execute instructions.
     #[tracing::instrument(skip(self))]
     pub fn run(&mut self) -> Result<(), error::Error> {
+        self.step_over_sw_breakpoint()?;
         self.inner.run()
     }
 
+    /// Halt the core, run `f`, and resume the core within `max_pause`.
+    ///
+    /// This allows to inspect timing-sensitive systems, which tolerate short pauses. The pause
+    /// starts when the core is asked to halt. Memory and core register accesses of `f` after
+    /// `max_pause` has passed fail with [`Error::Cancelled`], so `f` should return errors instead
+    /// of retrying. The core is resumed in any case, also if `f` failed, unless it was already
+    /// halted before.
+    ///
+    /// The pause can exceed `max_pause` by the duration of a single access of `f`, and by the
+    /// time needed to resume the core.
+    pub fn with_halt<T>(
+        &mut self,
+        max_pause: Duration,
+        f: impl FnOnce(&mut Core<'probe>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if self.core_halted()? {
+            return f(self);
+        }
+
+        let token = CancellationToken::with_timeout(max_pause);
+
+        let result = token.run(|| {
+            self.halt(max_pause)?;
+            f(self)
+        });
+
+        let resumed = self.run();
+
+        let value = result?;
+        resumed?;
+
+        Ok(value)
+    }
+
+    /// Halt the core, and escalate to more disruptive steps if it does not react to the halt
+    /// request, see [`HaltEscalation`].
+    ///
+    /// If no step halts the core, [`Error::HaltFailed`] lists the steps which were tried.
+    #[tracing::instrument(skip(self))]
+    pub fn halt_with_escalation(
+        &mut self,
+        escalation: &HaltEscalation,
+    ) -> Result<HaltOutcome, error::Error> {
+        let attempts = escalation.retries + 1;
+        for attempt in 1..=attempts {
+            match self.halt(escalation.timeout) {
+                Ok(info) => {
+                    let path = HaltPath::HaltRequest { attempts: attempt };
+                    if attempt > 1 {
+                        tracing::info!("Core {} halted by {}", self.id(), path);
+                    }
+                    return Ok(HaltOutcome { info, path });
+                }
+                Err(error) if halt_escalation::is_halt_timeout(&error) => {
+                    tracing::warn!(
+                        "Core {} did not halt after halt request {}/{}",
+                        self.id(),
+                        attempt,
+                        attempts
+                    );
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut tried = vec![format!("{attempts} halt requests")];
+
+        if escalation.vector_catch {
+            match self.inner.fault_catch_set(true) {
+                Ok(()) => {
+                    let halted = self.inner.wait_for_core_halted(escalation.timeout);
+                    self.inner.fault_catch_set(false)?;
+
+                    match halted {
+                        Ok(()) => {
+                            // The core is halted already, this only reads its state.
+                            let info = self.halt(escalation.timeout)?;
+                            tracing::info!(
+                                "Core {} halted by {}",
+                                self.id(),
+                                HaltPath::VectorCatch
+                            );
+                            return Ok(HaltOutcome {
+                                info,
+                                path: HaltPath::VectorCatch,
+                            });
+                        }
+                        Err(error) if halt_escalation::is_halt_timeout(&error) => {
+                            tracing::warn!("Core {} did not fault while waiting", self.id());
+                            tried.push("fault vector catch".to_string());
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+                Err(Error::NotImplemented(_)) => {
+                    tracing::debug!("Core {} does not support fault vector catch", self.id());
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        if escalation.reset_and_halt {
+            match self.reset_and_halt(escalation.timeout) {
+                Ok(info) => {
+                    tracing::warn!("Core {} halted by {}", self.id(), HaltPath::ResetAndHalt);
+                    return Ok(HaltOutcome {
+                        info,
+                        path: HaltPath::ResetAndHalt,
+                    });
+                }
+                Err(error) if halt_escalation::is_halt_timeout(&error) => {
+                    tried.push("reset and halt".to_string());
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        Err(Error::HaltFailed {
+            core: self.id(),
+            tried: tried.join(", "),
+            hint: if escalation.reset_and_halt {
+                "Check that the core is powered and clocked"
+            } else {
+                "Allow a reset and halt to halt the core, its state will be lost"
+            },
+        })
+    }
+
     /// Reset the core, and then continue to execute instructions. If the core
     /// should be halted after reset, use the [`reset_and_halt`] function.
     ///
     /// [`reset_and_halt`]: Core::reset_and_halt
     #[tracing::instrument(skip(self))]
     pub fn reset(&mut self) -> Result<(), error::Error> {
-        self.inner.reset()
+        let result = self.inner.reset();
+
+        self.emit_reset_event(&result, false);
+        result?;
+        self.discard_target_reset()?;
+
+        if let Some(hooks) = self.hooks {
+            hooks.run_post_reset(self)?;
+        }
+
+        Ok(())
     }
 
     /// Reset the core, and then immediately halt. To continue execution after
@@ -394,19 +801,127 @@ impl<'probe> Core<'probe> {
     /// [`reset`]: Core::reset
     #[tracing::instrument(skip(self))]
     pub fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, error::Error> {
-        self.inner.reset_and_halt(timeout)
+        let result = self.inner.reset_and_halt(timeout);
+
+        self.emit_reset_event(&result, true);
+        let info = result?;
+        self.discard_target_reset()?;
+
+        if let Some(hooks) = self.hooks {
+            hooks.run_post_reset(self)?;
+        }
+
+        Ok(info)
+    }
+
+    /// Forget about the reset of the core by the debugger, which is not a reset by the target.
+    fn discard_target_reset(&mut self) -> Result<(), error::Error> {
+        // Reading the status clears the sticky reset flag of the core.
+        self.inner.status()?;
+        self.inner.take_target_reset();
+        Ok(())
+    }
+
+    fn emit_reset_event<T>(&self, result: &Result<T, error::Error>, halted: bool) {
+        match result {
+            Ok(_) => crate::events::emit(|| crate::events::SessionEvent::Reset {
+                core: self.id(),
+                halted,
+            }),
+            Err(error) => {
+                crate::events::emit(|| crate::events::SessionEvent::error("reset", error))
+            }
+        }
     }
 
     /// Steps one instruction and then enters halted state again.
     #[tracing::instrument(skip(self))]
     pub fn step(&mut self) -> Result<CoreInformation, error::Error> {
+        if let Some(info) = self.step_over_sw_breakpoint()? {
+            return Ok(info);
+        }
+
         self.inner.step()
     }
 
     /// Returns the current status of the core.
+    ///
+    /// If the target reset the core on its own since the last call, for example because of a
+    /// watchdog, a [`SessionEvent::TargetReset`](crate::events::SessionEvent::TargetReset) is
+    /// emitted, and the debug settings and breakpoints which the reset cleared are restored.
+    /// This is supported for Cortex-M cores.
     #[tracing::instrument(skip(self))]
     pub fn status(&mut self) -> Result<CoreStatus, error::Error> {
-        self.inner.status()
+        let status = self.inner.status()?;
+
+        if self.inner.take_target_reset() {
+            tracing::warn!(
+                "Core {} was reset by the target, restoring its debug settings and breakpoints",
+                self.id()
+            );
+            crate::events::emit(|| crate::events::SessionEvent::TargetReset { core: self.id() });
+
+            self.restore_after_target_reset()?;
+        }
+
+        Ok(status)
+    }
+
+    /// Restore the debug settings, and the breakpoints and watchpoints set through the core,
+    /// after the target reset the core.
+    fn restore_after_target_reset(&mut self) -> Result<(), error::Error> {
+        self.inner.restore_debug_settings()?;
+
+        let Some(manager) = self.breakpoints.as_deref() else {
+            return Ok(());
+        };
+        let software: Vec<_> = manager
+            .software()
+            .map(|(address, breakpoint)| (address, breakpoint.clone()))
+            .collect();
+        let hardware: Vec<_> = manager.hardware().collect();
+        let watchpoints: Vec<_> = manager.watchpoints().collect();
+
+        if !hardware.is_empty() {
+            // The breakpoint unit can be disabled by the reset.
+            self.inner.enable_breakpoints(true)?;
+
+            let units = self.inner.hw_breakpoints()?;
+            for (unit, address) in hardware {
+                if units.get(unit) != Some(&Some(address)) {
+                    self.inner.set_hw_breakpoint(unit, address)?;
+                }
+            }
+        }
+
+        if !watchpoints.is_empty() {
+            let units = self.inner.hw_watchpoints()?;
+            for (unit, watchpoint) in watchpoints {
+                if units.get(unit) != Some(&Some(watchpoint)) {
+                    self.inner.set_hw_watchpoint(unit, watchpoint)?;
+                }
+            }
+        }
+
+        // The reset does not change RAM, but the startup code of the target can load the
+        // replaced instructions again.
+        for (address, breakpoint) in software {
+            let mut current = vec![0; breakpoint.original.len()];
+            self.read_8(address, &mut current)?;
+            if current == breakpoint.original {
+                self.write_8(address, breakpoint.instruction)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record a change of the breakpoint or watchpoint units, to configure them again after the
+    /// target reset the core.
+    fn record_units(&mut self, f: impl FnOnce(&mut BreakpointManager)) {
+        if let Some(manager) = self.breakpoints.as_deref_mut() {
+            f(manager);
+        }
     }
 
     /// Read the value of a core register.
@@ -436,6 +951,7 @@ impl<'probe> Core<'probe> {
 
         tracing::Span::current().record("address", format!("{address:?}"));
 
+        crate::cancellation::check()?;
         let value = self.inner.read_core_reg(address)?;
 
         value.try_into().into_crate_error()
@@ -457,9 +973,40 @@ impl<'probe> Core<'probe> {
     {
         let address = address.into();
 
+        crate::cancellation::check()?;
         self.inner.write_core_reg(address, value.into())
     }
 
+    /// Read the values of several core registers, in the order of `addresses`.
+    ///
+    /// This needs fewer transactions than reading the registers one by one on some cores, which
+    /// matters for clients reading all registers at once, like the GDB server.
+    pub fn read_core_regs(
+        &mut self,
+        addresses: &[registers::RegisterId],
+    ) -> Result<Vec<registers::RegisterValue>, error::Error> {
+        crate::cancellation::check()?;
+        self.inner.read_core_regs(addresses)
+    }
+
+    /// Write the values of several core registers, in the order of `registers`.
+    pub fn write_core_regs(
+        &mut self,
+        registers: &[(registers::RegisterId, registers::RegisterValue)],
+    ) -> Result<(), error::Error> {
+        crate::cancellation::check()?;
+        self.inner.write_core_regs(registers)
+    }
+
+    /// Write the core registers which were written while the core is halted to the core.
+    ///
+    /// Some cores, like the Cortex-M cores, buffer register writes while they are halted, and
+    /// write them to the core before it runs, steps or is reset, and when the session ends.
+    /// Call this to write them earlier, and to get the errors of these writes.
+    pub fn flush_registers(&mut self) -> Result<(), error::Error> {
+        self.inner.flush_registers()
+    }
+
     /// Returns all the available breakpoint units of the core.
     pub fn available_breakpoint_units(&mut self) -> Result<u32, error::Error> {
         self.inner.available_breakpoint_units()
@@ -548,6 +1095,9 @@ impl<'probe> Core<'probe> {
         // Actually set the breakpoint. Even if it has been set, set it again so it will be active.
         self.inner
             .set_hw_breakpoint(breakpoint_comparator_index, address)?;
+        self.record_units(|manager| {
+            manager.set_hardware(breakpoint_comparator_index, Some(address))
+        });
         Ok(())
     }
 
@@ -571,6 +1121,7 @@ impl<'probe> Core<'probe> {
         match bp_position {
             Some(bp_position) => {
                 self.inner.clear_hw_breakpoint(bp_position)?;
+                self.record_units(|manager| manager.set_hardware(bp_position, None));
                 Ok(())
             }
             None => Err(error::Error::Other(anyhow!(
@@ -593,6 +1144,476 @@ impl<'probe> Core<'probe> {
         Ok(())
     }
 
+    /// Returns the breakpoint units of the core, and the addresses they are used for.
+    pub fn breakpoint_units(&mut self) -> Result<Vec<BreakpointUnit>, error::Error> {
+        Ok(self
+            .inner
+            .hw_breakpoints()?
+            .into_iter()
+            .enumerate()
+            .map(|(index, address)| BreakpointUnit { index, address })
+            .collect())
+    }
+
+    /// Returns the breakpoints of the core, those in breakpoint units first.
+    pub fn breakpoints(&mut self) -> Result<Vec<Breakpoint>, error::Error> {
+        let mut breakpoints: Vec<_> = self
+            .breakpoint_units()?
+            .into_iter()
+            .filter_map(|unit| {
+                Some(Breakpoint {
+                    address: unit.address?,
+                    kind: BreakpointKind::Hardware { unit: unit.index },
+                })
+            })
+            .collect();
+
+        if let Some(manager) = &self.breakpoints {
+            breakpoints.extend(manager.software_breakpoints().map(|address| Breakpoint {
+                address,
+                kind: BreakpointKind::Software,
+            }));
+        }
+
+        Ok(breakpoints)
+    }
+
+    /// Set a breakpoint at `address`, in a free breakpoint unit if possible, and as a software
+    /// breakpoint otherwise, see [`breakpoints`].
+    ///
+    /// If a breakpoint is set at `address` already, it is returned.
+    #[tracing::instrument(skip(self))]
+    pub fn set_breakpoint(&mut self, address: u64) -> Result<Breakpoint, error::Error> {
+        if let Some(breakpoint) = self
+            .breakpoints()?
+            .into_iter()
+            .find(|breakpoint| breakpoint.address == address)
+        {
+            return Ok(breakpoint);
+        }
+
+        let free_unit = self
+            .breakpoint_units()?
+            .into_iter()
+            .find(BreakpointUnit::is_free);
+
+        let hardware = match free_unit {
+            Some(unit) => {
+                if !self.inner.hw_breakpoints_enabled() {
+                    self.enable_breakpoints(true)?;
+                }
+
+                match self.inner.set_hw_breakpoint(unit.index, address) {
+                    Ok(()) => {
+                        self.record_units(|manager| {
+                            manager.set_hardware(unit.index, Some(address))
+                        });
+                        return Ok(Breakpoint {
+                            address,
+                            kind: BreakpointKind::Hardware { unit: unit.index },
+                        });
+                    }
+                    Err(Error::Arm(ArmError::UnsupportedBreakpointAddress(_))) => {
+                        "the breakpoint units cannot match the address"
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            None => "all breakpoint units are in use",
+        };
+
+        tracing::debug!(
+            "Setting a software breakpoint at {:#010x}, {}",
+            address,
+            hardware
+        );
+
+        self.set_sw_breakpoint(address, hardware)
+    }
+
+    /// Set the breakpoints at `addresses` together, see [`Core::set_breakpoint`].
+    ///
+    /// Unlike [`Core::set_breakpoint`] for one address after the other, the breakpoint units are
+    /// given to the addresses which cannot have a software breakpoint first, so a breakpoint in RAM
+    /// does not take the unit a breakpoint in flash needs. The breakpoints which cannot be set are
+    /// returned in [`BreakpointBatch::unsatisfied`].
+    ///
+    /// If setting a breakpoint fails with an error, the breakpoints the batch set already are
+    /// cleared again before the error is returned.
+    #[tracing::instrument(skip(self))]
+    pub fn set_breakpoints(&mut self, addresses: &[u64]) -> Result<BreakpointBatch, error::Error> {
+        let existing = self.breakpoints()?;
+
+        let mut batch = BreakpointBatch::default();
+        let mut new = vec![];
+        for &address in addresses {
+            match existing
+                .iter()
+                .find(|breakpoint| breakpoint.address == address)
+            {
+                Some(breakpoint) if !batch.breakpoints.contains(breakpoint) => {
+                    batch.breakpoints.push(*breakpoint)
+                }
+                Some(_) => {}
+                None => new.push(address),
+            }
+        }
+
+        // The free units are used in ascending order, like by `set_breakpoint`.
+        let mut free_units: Vec<usize> = self
+            .breakpoint_units()?
+            .into_iter()
+            .filter(BreakpointUnit::is_free)
+            .map(|unit| unit.index)
+            .rev()
+            .collect();
+
+        let plan = breakpoints::plan_breakpoints(&new, free_units.len(), |address| {
+            self.sw_breakpoint_unavailable(address)
+        });
+
+        let set_already = batch.breakpoints.len();
+        for (address, placement) in plan {
+            match self.apply_breakpoint(address, placement, &mut free_units) {
+                Ok(Ok(breakpoint)) => batch.breakpoints.push(breakpoint),
+                Ok(Err(unsatisfied)) => batch.unsatisfied.push(unsatisfied),
+                Err(error) => {
+                    for breakpoint in batch.breakpoints.split_off(set_already) {
+                        if let Err(e) = self.clear_breakpoint(breakpoint.address) {
+                            tracing::warn!(
+                                "Failed to clear the breakpoint at {:#010x}: {}",
+                                breakpoint.address,
+                                e
+                            );
+                        }
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(batch)
+    }
+
+    /// Set a breakpoint of [`Core::set_breakpoints`] as planned, falling back to a software
+    /// breakpoint if the breakpoint unit cannot match the address.
+    fn apply_breakpoint(
+        &mut self,
+        address: u64,
+        placement: Placement,
+        free_units: &mut Vec<usize>,
+    ) -> Result<Result<Breakpoint, UnsatisfiedBreakpoint>, error::Error> {
+        let hardware = match (placement, free_units.pop()) {
+            (Placement::Unavailable { hardware, software }, unit) => {
+                free_units.extend(unit);
+                return Ok(Err(UnsatisfiedBreakpoint {
+                    address,
+                    hardware,
+                    software,
+                }));
+            }
+            (Placement::Software, unit) => {
+                free_units.extend(unit);
+                "all breakpoint units are in use"
+            }
+            (Placement::Hardware, None) => "all breakpoint units are in use",
+            (Placement::Hardware, Some(unit)) => {
+                if !self.inner.hw_breakpoints_enabled() {
+                    self.enable_breakpoints(true)?;
+                }
+
+                match self.inner.set_hw_breakpoint(unit, address) {
+                    Ok(()) => {
+                        self.record_units(|manager| manager.set_hardware(unit, Some(address)));
+                        return Ok(Ok(Breakpoint {
+                            address,
+                            kind: BreakpointKind::Hardware { unit },
+                        }));
+                    }
+                    Err(Error::Arm(ArmError::UnsupportedBreakpointAddress(_))) => {
+                        free_units.push(unit);
+                        "the breakpoint units cannot match the address"
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        };
+
+        match self.set_sw_breakpoint(address, hardware) {
+            Ok(breakpoint) => Ok(Ok(breakpoint)),
+            Err(Error::NoBreakpointAvailable {
+                address,
+                hardware,
+                software,
+            }) => Ok(Err(UnsatisfiedBreakpoint {
+                address,
+                hardware,
+                software,
+            })),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Returns why no software breakpoint can be set at `address`, or `None` if one can.
+    fn sw_breakpoint_unavailable(&self, address: u64) -> Option<&'static str> {
+        if self.breakpoints.is_none() {
+            return Some("the core does not keep software breakpoints");
+        }
+
+        let in_ram = self.memory_map.iter().any(|region| {
+            matches!(region, MemoryRegion::Ram(_))
+                && region.address_range().contains(&address)
+                && region.cores().iter().any(|core| core == self.core_name)
+        });
+        if !in_ram {
+            return Some("software breakpoints are only set in RAM");
+        }
+
+        None
+    }
+
+    /// Replace the instruction at `address` by a breakpoint instruction.
+    fn set_sw_breakpoint(
+        &mut self,
+        address: u64,
+        hardware: &'static str,
+    ) -> Result<Breakpoint, error::Error> {
+        let unavailable = |software| Error::NoBreakpointAvailable {
+            address,
+            hardware,
+            software,
+        };
+
+        if let Some(software) = self.sw_breakpoint_unavailable(address) {
+            return Err(unavailable(software));
+        }
+
+        let mut first_halfword = [0; 2];
+        self.read_8(address, &mut first_halfword)?;
+        let instruction =
+            breakpoints::breakpoint_instruction(self.instruction_set()?, first_halfword);
+
+        let mut original = vec![0; instruction.len()];
+        self.read_8(address, &mut original)?;
+        self.write_8(address, instruction)?;
+
+        let mut written = vec![0; instruction.len()];
+        self.read_8(address, &mut written)?;
+        if written != instruction {
+            self.write_8(address, &original)?;
+            return Err(unavailable(
+                "the breakpoint instruction could not be written",
+            ));
+        }
+
+        self.debug_on_sw_breakpoint(true)?;
+
+        if let Some(manager) = self.breakpoints.as_mut() {
+            manager.insert(
+                address,
+                SoftwareBreakpoint {
+                    original,
+                    instruction,
+                },
+            );
+        }
+
+        Ok(Breakpoint {
+            address,
+            kind: BreakpointKind::Software,
+        })
+    }
+
+    /// Clear the breakpoint at `address`, which was set by [`Core::set_breakpoint`] or
+    /// [`Core::set_hw_breakpoint`].
+    #[tracing::instrument(skip(self))]
+    pub fn clear_breakpoint(&mut self, address: u64) -> Result<(), error::Error> {
+        let breakpoint = self
+            .breakpoints
+            .as_mut()
+            .and_then(|manager| manager.remove(address));
+
+        let Some(breakpoint) = breakpoint else {
+            return self.clear_hw_breakpoint(address);
+        };
+
+        if let Err(error) = self.write_8(address, &breakpoint.original) {
+            if let Some(manager) = self.breakpoints.as_mut() {
+                manager.insert(address, breakpoint);
+            }
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    /// Clear all breakpoints, the software breakpoints set by [`Core::set_breakpoint`] and all
+    /// hardware breakpoints like [`Core::clear_all_hw_breakpoints`].
+    #[tracing::instrument(skip(self))]
+    pub fn clear_all_breakpoints(&mut self) -> Result<(), error::Error> {
+        let software: Vec<_> = self
+            .breakpoints
+            .as_ref()
+            .map(|manager| manager.software_breakpoints().collect())
+            .unwrap_or_default();
+
+        for address in software {
+            self.clear_breakpoint(address)?;
+        }
+
+        self.clear_all_hw_breakpoints()
+    }
+
+    /// If the core is halted at a software breakpoint, execute the replaced instruction, and
+    /// set the breakpoint again.
+    ///
+    /// Returns the state of the core after the step, or `None` if the core is not halted at a
+    /// software breakpoint.
+    fn step_over_sw_breakpoint(&mut self) -> Result<Option<CoreInformation>, error::Error> {
+        let has_sw_breakpoints = self
+            .breakpoints
+            .as_ref()
+            .is_some_and(|manager| manager.software_breakpoints().next().is_some());
+
+        if !has_sw_breakpoints || !self.core_halted()? {
+            return Ok(None);
+        }
+
+        let pc: u64 = self.read_core_reg(self.program_counter())?;
+        let original = self
+            .breakpoints
+            .as_ref()
+            .and_then(|manager| manager.original_instruction(pc))
+            .map(<[u8]>::to_vec);
+
+        let Some(original) = original else {
+            return Ok(None);
+        };
+
+        let mut instruction = vec![0; original.len()];
+        self.read_8(pc, &mut instruction)?;
+        self.write_8(pc, &original)?;
+
+        let result = self.inner.step();
+        self.write_8(pc, &instruction)?;
+
+        result.map(Some)
+    }
+
+    /// Returns the number of available watchpoint units of the core.
+    pub fn available_watchpoint_units(&mut self) -> Result<u32, error::Error> {
+        self.inner.available_watchpoint_units()
+    }
+
+    /// Set a watchpoint on the `length` bytes at `address`, which halts the core when they are
+    /// accessed by `kind`.
+    ///
+    /// This is a shorthand for [`Core::set_hw_watchpoint`]. When the watchpoint is hit, the core
+    /// reports it in [`HaltReason::Watchpoint`], if it can tell which one was hit.
+    pub fn set_watchpoint(
+        &mut self,
+        address: u64,
+        length: u64,
+        kind: WatchpointKind,
+    ) -> Result<Watchpoint, error::Error> {
+        let watchpoint = Watchpoint {
+            address,
+            length,
+            kind,
+        };
+
+        self.set_hw_watchpoint(watchpoint)?;
+
+        Ok(watchpoint)
+    }
+
+    /// Set a hardware watchpoint
+    ///
+    /// This function will try to set `watchpoint` using a free watchpoint unit.
+    /// If the same watchpoint is already set, it is configured again.
+    ///
+    /// The amount of hardware watchpoints which are supported is chip specific,
+    /// and can be queried using the `available_watchpoint_units` function.
+    #[tracing::instrument(skip(self))]
+    pub fn set_hw_watchpoint(&mut self, watchpoint: Watchpoint) -> Result<(), error::Error> {
+        let watchpoints = self.inner.hw_watchpoints()?;
+        let free_units = self.inner.free_watchpoint_units()?;
+
+        // Free units are used from the last one, because RISC-V cores use the same triggers for
+        // breakpoints, which are set from the first one.
+        let unit_index = watchpoints
+            .iter()
+            .position(|&wp| wp == Some(watchpoint))
+            .or_else(|| free_units.iter().rposition(|&free| free))
+            .ok_or(error::Error::NoWatchpointAvailable {
+                address: watchpoint.address,
+                length: watchpoint.length,
+                reason: "no watchpoint unit is free",
+            })?;
+
+        tracing::debug!(
+            "Trying to set HW watchpoint #{} for {:?}",
+            unit_index,
+            watchpoint
+        );
+
+        self.inner.set_hw_watchpoint(unit_index, watchpoint)?;
+        self.record_units(|manager| manager.set_watchpoint(unit_index, Some(watchpoint)));
+        Ok(())
+    }
+
+    /// Clear a hardware watchpoint
+    ///
+    /// This function will try to clear `watchpoint` if it is set.
+    #[tracing::instrument(skip(self))]
+    pub fn clear_hw_watchpoint(&mut self, watchpoint: Watchpoint) -> Result<(), error::Error> {
+        match self
+            .inner
+            .hw_watchpoints()?
+            .iter()
+            .position(|&wp| wp == Some(watchpoint))
+        {
+            Some(unit_index) => {
+                self.inner.clear_hw_watchpoint(unit_index)?;
+                self.record_units(|manager| manager.set_watchpoint(unit_index, None));
+                Ok(())
+            }
+            None => Err(error::Error::Other(anyhow!(
+                "No watchpoint found at address {:#010x}",
+                watchpoint.address
+            ))),
+        }
+    }
+
+    /// Clear all hardware watchpoints which are configured on the target.
+    #[tracing::instrument(skip(self))]
+    pub fn clear_all_hw_watchpoints(&mut self) -> Result<(), error::Error> {
+        let watchpoints = self.inner.hw_watchpoints()?;
+
+        for (unit_index, _) in watchpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, wp)| wp.is_some())
+        {
+            self.inner.clear_hw_watchpoint(unit_index)?;
+            self.record_units(|manager| manager.set_watchpoint(unit_index, None));
+        }
+        Ok(())
+    }
+
+    /// Returns the watchpoint which caused the core to halt, if it can be determined.
+    ///
+    /// This is the watchpoint reported in [`HaltReason::Watchpoint`] by [`Core::status`].
+    pub fn triggered_hw_watchpoint(&mut self) -> Result<Option<Watchpoint>, error::Error> {
+        match self.status()? {
+            CoreStatus::Halted(HaltReason::Watchpoint(Some(watchpoint))) => Ok(Some(watchpoint)),
+            CoreStatus::Halted(HaltReason::Watchpoint(None)) => {
+                self.inner.triggered_hw_watchpoint()
+            }
+            _ => Ok(None),
+        }
+    }
+
     /// Returns the architecture of the core.
     pub fn architecture(&self) -> Architecture {
         self.inner.architecture()
@@ -617,6 +1638,11 @@ impl<'probe> Core<'probe> {
         self.inner.fpu_support()
     }
 
+    /// Enable or disable halting the core when a fault exception is taken.
+    pub fn fault_catch_set(&mut self, enabled: bool) -> Result<(), Error> {
+        self.inner.fault_catch_set(enabled)
+    }
+
     pub(crate) fn reset_catch_clear(&mut self) -> Result<(), Error> {
         self.inner.reset_catch_clear()
     }
@@ -0,0 +1,334 @@
+//! Breakpoints which use a breakpoint unit of the core if one is free, and are patched into
+//! memory otherwise.
+//!
+//! [`Core::set_breakpoint`](crate::Core::set_breakpoint) allocates a free breakpoint unit, for
+//! example an FPB comparator of a Cortex-M core. If all units are in use, or the units cannot
+//! match the address, the instruction at the address is replaced by a breakpoint instruction,
+//! as long as the address is in RAM. The replaced instruction is restored when the breakpoint is
+//! cleared, and stepped over when the core is resumed at the breakpoint.
+//!
+//! If the target resets a core on its own, for example because of a watchdog, the breakpoint
+//! and watchpoint units may be cleared. The breakpoints and watchpoints set through the
+//! [`Core`](crate::Core) are set again when [`Core::status`](crate::Core::status) detects such a
+//! reset.
+
+use std::collections::BTreeMap;
+
+use crate::{InstructionSet, Watchpoint};
+
+/// How a [`Breakpoint`] is implemented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointKind {
+    /// The breakpoint uses a breakpoint unit of the core.
+    Hardware {
+        /// The index of the breakpoint unit.
+        unit: usize,
+    },
+    /// The instruction at the address of the breakpoint is replaced by a breakpoint instruction.
+    Software,
+}
+
+/// A breakpoint set by [`Core::set_breakpoint`](crate::Core::set_breakpoint).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// The address of the breakpoint.
+    pub address: u64,
+    /// How the breakpoint is implemented.
+    pub kind: BreakpointKind,
+}
+
+/// A breakpoint which [`Core::set_breakpoints`](crate::Core::set_breakpoints) could not set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsatisfiedBreakpoint {
+    /// The address of the breakpoint.
+    pub address: u64,
+    /// Why no breakpoint unit can be used.
+    pub hardware: &'static str,
+    /// Why no software breakpoint can be set.
+    pub software: &'static str,
+}
+
+/// The result of [`Core::set_breakpoints`](crate::Core::set_breakpoints).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BreakpointBatch {
+    /// The requested breakpoints which are set, those which were set already first.
+    pub breakpoints: Vec<Breakpoint>,
+    /// The breakpoints which could not be set, in the order they were requested.
+    pub unsatisfied: Vec<UnsatisfiedBreakpoint>,
+}
+
+/// A breakpoint unit of a core, for example an FPB comparator of a Cortex-M core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakpointUnit {
+    /// The index of the unit.
+    pub index: usize,
+    /// The address the unit is configured for, `None` if the unit is free.
+    pub address: Option<u64>,
+}
+
+impl BreakpointUnit {
+    /// Returns `true` if the unit is not used by a breakpoint.
+    pub fn is_free(&self) -> bool {
+        self.address.is_none()
+    }
+}
+
+/// A software breakpoint, see [`BreakpointManager`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SoftwareBreakpoint {
+    /// The instruction replaced by the breakpoint.
+    pub(crate) original: Vec<u8>,
+    /// The breakpoint instruction.
+    pub(crate) instruction: &'static [u8],
+}
+
+/// The software breakpoints of a core, which are kept in the session between uses of the
+/// [`Core`](crate::Core).
+///
+/// Hardware breakpoints are read from the breakpoint units of the core instead, so they also
+/// show up if they were set by another tool. The units configured through the core are only
+/// recorded, to configure them again after the target reset the core.
+#[derive(Debug, Default)]
+pub struct BreakpointManager {
+    /// The software breakpoints, by address.
+    software: BTreeMap<u64, SoftwareBreakpoint>,
+    /// The addresses of the breakpoint units configured through the core, by unit.
+    hardware: BTreeMap<usize, u64>,
+    /// The watchpoint units configured through the core, by unit.
+    watchpoints: BTreeMap<usize, Watchpoint>,
+}
+
+impl BreakpointManager {
+    /// The addresses of the software breakpoints, in ascending order.
+    pub fn software_breakpoints(&self) -> impl Iterator<Item = u64> + '_ {
+        self.software.keys().copied()
+    }
+
+    /// Returns the instruction replaced by the software breakpoint at `address`.
+    pub(crate) fn original_instruction(&self, address: u64) -> Option<&[u8]> {
+        self.software
+            .get(&address)
+            .map(|breakpoint| breakpoint.original.as_slice())
+    }
+
+    pub(crate) fn insert(&mut self, address: u64, breakpoint: SoftwareBreakpoint) {
+        self.software.insert(address, breakpoint);
+    }
+
+    pub(crate) fn remove(&mut self, address: u64) -> Option<SoftwareBreakpoint> {
+        self.software.remove(&address)
+    }
+
+    /// Record the address breakpoint unit `unit` is configured for, `None` if it was cleared.
+    pub(crate) fn set_hardware(&mut self, unit: usize, address: Option<u64>) {
+        match address {
+            Some(address) => self.hardware.insert(unit, address),
+            None => self.hardware.remove(&unit),
+        };
+    }
+
+    /// Record the watchpoint unit `unit` is configured for, `None` if it was cleared.
+    pub(crate) fn set_watchpoint(&mut self, unit: usize, watchpoint: Option<Watchpoint>) {
+        match watchpoint {
+            Some(watchpoint) => self.watchpoints.insert(unit, watchpoint),
+            None => self.watchpoints.remove(&unit),
+        };
+    }
+
+    /// The software breakpoints, by address.
+    pub(crate) fn software(&self) -> impl Iterator<Item = (u64, &SoftwareBreakpoint)> + '_ {
+        self.software
+            .iter()
+            .map(|(&address, breakpoint)| (address, breakpoint))
+    }
+
+    /// The breakpoint units configured through the core, and their addresses.
+    pub(crate) fn hardware(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.hardware
+            .iter()
+            .map(|(&unit, &address)| (unit, address))
+    }
+
+    /// The watchpoint units configured through the core, and their watchpoints.
+    pub(crate) fn watchpoints(&self) -> impl Iterator<Item = (usize, Watchpoint)> + '_ {
+        self.watchpoints
+            .iter()
+            .map(|(&unit, &watchpoint)| (unit, watchpoint))
+    }
+}
+
+/// How a breakpoint of a batch is planned to be set, see [`plan_breakpoints`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Placement {
+    /// In a free breakpoint unit.
+    Hardware,
+    /// As a software breakpoint.
+    Software,
+    /// Not at all.
+    Unavailable {
+        hardware: &'static str,
+        software: &'static str,
+    },
+}
+
+/// Plan how the breakpoints at `addresses` are set, with `free_units` free breakpoint units.
+///
+/// `software` returns why no software breakpoint can be set at an address, for example because
+/// it is in flash. These addresses get the breakpoint units first, the units left over are used
+/// for the other addresses in the order they are given, and the rest of them are set as software
+/// breakpoints. Duplicate addresses are planned once.
+pub(crate) fn plan_breakpoints(
+    addresses: &[u64],
+    free_units: usize,
+    software: impl Fn(u64) -> Option<&'static str>,
+) -> Vec<(u64, Placement)> {
+    let mut plan: Vec<(u64, Option<&'static str>)> = vec![];
+    for &address in addresses {
+        if plan.iter().all(|&(planned, _)| planned != address) {
+            plan.push((address, software(address)));
+        }
+    }
+
+    let mut free_units = free_units;
+    let mut placements = vec![None; plan.len()];
+
+    for (placement, &(_, software)) in placements.iter_mut().zip(&plan) {
+        let Some(software) = software else {
+            continue;
+        };
+        *placement = Some(if free_units > 0 {
+            free_units -= 1;
+            Placement::Hardware
+        } else {
+            Placement::Unavailable {
+                hardware: "all breakpoint units are in use",
+                software,
+            }
+        });
+    }
+
+    for placement in placements
+        .iter_mut()
+        .filter(|placement| placement.is_none())
+    {
+        *placement = Some(if free_units > 0 {
+            free_units -= 1;
+            Placement::Hardware
+        } else {
+            Placement::Software
+        });
+    }
+
+    plan.into_iter()
+        .zip(placements.into_iter().flatten())
+        .map(|((address, _), placement)| (address, placement))
+        .collect()
+}
+
+/// Returns the breakpoint instruction which replaces an instruction starting with the halfword
+/// `first_halfword`, in little endian byte order.
+///
+/// The instructions halt a core with halting debug enabled: `BKPT` for Thumb and A32, `HLT` for
+/// A64, and `EBREAK` or `C.EBREAK` for RISC-V, with the length of the replaced instruction.
+pub(crate) fn breakpoint_instruction(
+    instruction_set: InstructionSet,
+    first_halfword: [u8; 2],
+) -> &'static [u8] {
+    match instruction_set {
+        // BKPT #0
+        InstructionSet::Thumb2 => &[0x00, 0xBE],
+        // BKPT #0
+        InstructionSet::A32 => &[0x70, 0x00, 0x20, 0xE1],
+        // HLT #0
+        InstructionSet::A64 => &[0x00, 0x00, 0x40, 0xD4],
+        // C.EBREAK, for compressed instructions. Their two lowest bits are not both set.
+        InstructionSet::RV32C if first_halfword[0] & 0b11 != 0b11 => &[0x02, 0x90],
+        // EBREAK
+        InstructionSet::RV32 | InstructionSet::RV32C => &[0x73, 0x00, 0x10, 0x00],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        breakpoint_instruction, plan_breakpoints, BreakpointManager, Placement, SoftwareBreakpoint,
+    };
+    use crate::InstructionSet;
+
+    #[test]
+    fn breakpoint_instruction_matches_replaced_instruction() {
+        assert_eq!(
+            breakpoint_instruction(InstructionSet::Thumb2, [0x70, 0x47]),
+            [0x00, 0xBE]
+        );
+
+        // `c.addi a0, 1` is compressed, `addi a0, a0, 1` is not.
+        assert_eq!(
+            breakpoint_instruction(InstructionSet::RV32C, [0x05, 0x05]),
+            [0x02, 0x90]
+        );
+        assert_eq!(
+            breakpoint_instruction(InstructionSet::RV32C, [0x13, 0x05]),
+            [0x73, 0x00, 0x10, 0x00]
+        );
+
+        let breakpoint = |original: &[u8]| SoftwareBreakpoint {
+            original: original.to_vec(),
+            instruction: &[0x00, 0xBE],
+        };
+
+        let mut manager = BreakpointManager::default();
+        manager.insert(0x2000_0100, breakpoint(&[0x70, 0x47]));
+        manager.insert(0x2000_0010, breakpoint(&[0x00, 0xBF]));
+        assert_eq!(
+            manager.software_breakpoints().collect::<Vec<_>>(),
+            [0x2000_0010, 0x2000_0100]
+        );
+        assert_eq!(manager.remove(0x2000_0100), Some(breakpoint(&[0x70, 0x47])));
+        assert_eq!(manager.original_instruction(0x2000_0100), None);
+        assert_eq!(
+            manager.original_instruction(0x2000_0010),
+            Some(&[0x00, 0xBF][..])
+        );
+    }
+
+    #[test]
+    fn breakpoints_in_flash_get_the_breakpoint_units() {
+        let in_flash = |address: u64| {
+            (address < 0x2000_0000).then_some("software breakpoints are only set in RAM")
+        };
+        let addresses = [
+            0x2000_0100,
+            0x0800_0100,
+            0x2000_0200,
+            0x0800_0200,
+            0x0800_0100,
+        ];
+
+        assert_eq!(
+            plan_breakpoints(&addresses, 2, in_flash),
+            [
+                (0x2000_0100, Placement::Software),
+                (0x0800_0100, Placement::Hardware),
+                (0x2000_0200, Placement::Software),
+                (0x0800_0200, Placement::Hardware),
+            ]
+        );
+
+        assert_eq!(
+            plan_breakpoints(&addresses, 3, in_flash)[0],
+            (0x2000_0100, Placement::Hardware)
+        );
+
+        assert_eq!(
+            plan_breakpoints(&addresses, 1, in_flash)[3],
+            (
+                0x0800_0200,
+                Placement::Unavailable {
+                    hardware: "all breakpoint units are in use",
+                    software: "software breakpoints are only set in RAM",
+                }
+            )
+        );
+    }
+}
@@ -2,12 +2,12 @@ use crate::{
     architecture::{
         arm::{
             ap::MemoryAp,
-            core::{CortexAState, CortexMState},
+            core::{armv7m::Dhcsr, CortexAState, CortexMState},
             ApAddress, ArmProbeInterface, DpAddress,
         },
         riscv::{communication_interface::RiscvCommunicationInterface, RiscVState},
     },
-    Core, CoreType, Error,
+    BreakpointManager, Core, CoreType, Error, MemoryMappedRegister,
 };
 pub use probe_rs_target::{Architecture, CoreAccessOptions};
 
@@ -46,7 +46,7 @@ impl CombinedCoreState {
             }
         };
 
-        Ok(match &mut self.specific_state {
+        let core = match &mut self.specific_state {
             SpecificCoreState::Armv6m(s) => Core::new(
                 crate::architecture::arm::armv6m::Armv6m::new(memory, s, debug_sequence, self.id)?,
             ),
@@ -80,7 +80,9 @@ impl CombinedCoreState {
                     "Core architecture and Probe mismatch.",
                 ))
             }
-        })
+        };
+
+        Ok(core.with_breakpoints(&mut self.core_state.breakpoints))
     }
 
     pub(crate) fn enable_arm_debug(
@@ -109,6 +111,18 @@ impl CombinedCoreState {
         Ok(())
     }
 
+    /// Read the debug halting control and status register of a Cortex-M core.
+    pub(crate) fn cortex_m_dhcsr(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+    ) -> Result<Dhcsr, Error> {
+        let mut memory_interface = interface.memory_interface(self.arm_memory_ap())?;
+
+        Ok(Dhcsr(
+            memory_interface.read_word_32(Dhcsr::get_mmio_address())?,
+        ))
+    }
+
     pub(crate) fn arm_reset_catch_set(
         &self,
         interface: &mut dyn ArmProbeInterface,
@@ -139,7 +153,11 @@ impl CombinedCoreState {
         &'probe mut self,
         interface: &'probe mut RiscvCommunicationInterface,
     ) -> Result<Core<'probe>, Error> {
-        Ok(match &mut self.specific_state {
+        if let ResolvedCoreOptions::Riscv { options } = &self.core_state.core_access_options {
+            interface.select_hart(options.hart_id.unwrap_or(0))?;
+        }
+
+        let core = match &mut self.specific_state {
             SpecificCoreState::Riscv(s) => Core::new(crate::architecture::riscv::Riscv32::new(
                 interface, s, self.id,
             )),
@@ -148,7 +166,9 @@ impl CombinedCoreState {
                     "Core architecture and Probe mismatch.",
                 ))
             }
-        })
+        };
+
+        Ok(core.with_breakpoints(&mut self.core_state.breakpoints))
     }
 
     /// Get the memory AP for this core.
@@ -166,6 +186,9 @@ impl CombinedCoreState {
 pub struct CoreState {
     /// Information needed to access the core
     core_access_options: ResolvedCoreOptions,
+
+    /// The software breakpoints of the core.
+    breakpoints: BreakpointManager,
 }
 
 impl CoreState {
@@ -173,6 +196,7 @@ impl CoreState {
     pub fn new(core_access_options: ResolvedCoreOptions) -> Self {
         Self {
             core_access_options,
+            breakpoints: BreakpointManager::default(),
         }
     }
 
@@ -1,3 +1,5 @@
+use crate::Watchpoint;
+
 /// The status of the core.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum CoreStatus {
@@ -50,8 +52,10 @@ pub enum HaltReason {
     /// Core halted due to an exception, e.g. an
     /// an interrupt.
     Exception,
-    /// Core halted due to a data watchpoint
-    Watchpoint,
+    /// Core halted due to a data watchpoint.
+    ///
+    /// The payload is the watchpoint which was hit, if the core can tell which one.
+    Watchpoint(Option<Watchpoint>),
     /// Core halted after single step
     Step,
     /// Core halted because of a debugger request
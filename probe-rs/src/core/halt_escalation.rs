@@ -0,0 +1,150 @@
+//! Halting cores which do not react to a halt request.
+//!
+//! A core can ignore the halt request of the debugger, for example when it is stuck in a
+//! low power mode, or when the firmware keeps disabling the debug logic. [`HaltEscalation`]
+//! describes the steps [`Core::halt_with_escalation`](crate::Core::halt_with_escalation) takes to
+//! halt such a core, from the least to the most disruptive one.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::{
+    architecture::{arm::ArmError, riscv::communication_interface::RiscvError},
+    CoreInformation, DebugProbeError, Error,
+};
+
+/// The steps taken to halt a core, see [`Core::halt_with_escalation`](crate::Core::halt_with_escalation).
+///
+/// The steps are tried in this order:
+///
+/// 1. The halt request is sent `1 + retries` times.
+/// 2. With `vector_catch`, the core is halted the next time it takes a fault exception.
+/// 3. With `reset_and_halt`, the core is reset and halted at the reset vector. The state of the
+///    core is lost, so this is disabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HaltEscalation {
+    /// How long to wait for the core to halt in each step.
+    pub timeout: Duration,
+    /// How often the halt request is repeated after the first one timed out.
+    pub retries: u32,
+    /// Halt the core on the next fault exception.
+    pub vector_catch: bool,
+    /// Reset the core and halt it at the reset vector.
+    pub reset_and_halt: bool,
+}
+
+impl Default for HaltEscalation {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100))
+    }
+}
+
+impl HaltEscalation {
+    /// Two retries of the halt request and the vector catch, waiting `timeout` in each step.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            retries: 2,
+            vector_catch: true,
+            reset_and_halt: false,
+        }
+    }
+
+    /// Set how long to wait for the core to halt in each step.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set how often the halt request is repeated.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Enable or disable the vector catch step.
+    pub fn vector_catch(mut self, enabled: bool) -> Self {
+        self.vector_catch = enabled;
+        self
+    }
+
+    /// Enable or disable the reset and halt step.
+    pub fn reset_and_halt(mut self, enabled: bool) -> Self {
+        self.reset_and_halt = enabled;
+        self
+    }
+}
+
+/// The step of a [`HaltEscalation`] which halted the core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltPath {
+    /// The core reacted to a halt request.
+    HaltRequest {
+        /// The number of halt requests sent, including the successful one.
+        attempts: u32,
+    },
+    /// The core was halted when it took a fault exception.
+    VectorCatch,
+    /// The core was reset, and halted at the reset vector.
+    ResetAndHalt,
+}
+
+impl fmt::Display for HaltPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HaltPath::HaltRequest { attempts: 1 } => write!(f, "halt request"),
+            HaltPath::HaltRequest { attempts } => {
+                write!(f, "halt request, after {attempts} attempts")
+            }
+            HaltPath::VectorCatch => write!(f, "fault vector catch"),
+            HaltPath::ResetAndHalt => write!(f, "reset and halt"),
+        }
+    }
+}
+
+/// A halted core, and how it was halted.
+#[derive(Debug, Clone)]
+pub struct HaltOutcome {
+    /// The state of the halted core.
+    pub info: CoreInformation,
+    /// The step which halted the core.
+    pub path: HaltPath,
+}
+
+/// Returns `true` if `error` means that the core did not halt in time.
+pub(crate) fn is_halt_timeout(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Timeout
+            | Error::Arm(ArmError::Timeout)
+            | Error::Riscv(RiscvError::Timeout)
+            | Error::Probe(DebugProbeError::Timeout)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_halt_timeout, HaltEscalation, HaltPath};
+    use crate::{architecture::arm::ArmError, Error};
+    use std::time::Duration;
+
+    #[test]
+    fn escalation_defaults_keep_the_core_state() {
+        let escalation = HaltEscalation::default();
+        assert_eq!(escalation.timeout, Duration::from_millis(100));
+        assert!(escalation.vector_catch);
+        assert!(!escalation.reset_and_halt);
+
+        let escalation = escalation.retries(0).reset_and_halt(true);
+        assert_eq!(escalation.retries, 0);
+        assert!(escalation.reset_and_halt);
+
+        assert!(is_halt_timeout(&Error::Arm(ArmError::Timeout)));
+        assert!(!is_halt_timeout(&Error::NotImplemented("halt")));
+
+        assert_eq!(
+            HaltPath::HaltRequest { attempts: 3 }.to_string(),
+            "halt request, after 3 attempts"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use super::{DebugInfo, SourceLocation};
+use gimli::ColumnType;
+use std::{num::NonZeroU64, path::PathBuf};
+
+/// A branch taken by the core, as recorded by a branch trace such as the MTB or the ETM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BranchRecord {
+    /// The address of the branch instruction, or of the instruction which was interrupted by an
+    /// exception.
+    pub source: u64,
+    /// The address of the first instruction executed after the branch.
+    pub destination: u64,
+    /// Set if the trace was restarted at this branch, so the code between the previous branch and
+    /// this one was not traced.
+    pub trace_start: bool,
+}
+
+/// A source line which was executed, see [`DebugInfo::execution_history`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutedLine {
+    /// The address of the first executed instruction of the line.
+    pub address: u64,
+    /// The source location of the line.
+    pub location: SourceLocation,
+}
+
+/// A row of the line tables of all units, sorted by address.
+#[derive(Debug, Clone)]
+struct LineRow {
+    address: u64,
+    file: Option<String>,
+    directory: Option<PathBuf>,
+    line: Option<u64>,
+    column: ColumnType,
+    sequence: (u64, u64),
+}
+
+impl LineRow {
+    fn is_same_line(&self, other: &LineRow) -> bool {
+        self.file == other.file && self.directory == other.directory && self.line == other.line
+    }
+
+    fn executed_line(&self, address: u64) -> ExecutedLine {
+        ExecutedLine {
+            address,
+            location: SourceLocation {
+                line: self.line,
+                column: Some(self.column.into()),
+                file: self.file.clone(),
+                directory: self.directory.clone(),
+                low_pc: Some(self.sequence.0 as u32),
+                high_pc: Some(self.sequence.1 as u32),
+            },
+        }
+    }
+}
+
+impl DebugInfo {
+    /// The last `max_lines` source lines executed before the core stopped at `pc`, as recorded
+    /// by the taken `branches`, oldest first.
+    ///
+    /// The branches have to be in the order in which they were taken. The code between two
+    /// branches is executed sequentially, so each line table row between the destination of a
+    /// branch and the source of the next branch is reported as executed. Repeated rows of the
+    /// same line are merged, and code without debug information is skipped.
+    pub fn execution_history(
+        &self,
+        branches: &[BranchRecord],
+        pc: u64,
+        max_lines: usize,
+    ) -> Vec<ExecutedLine> {
+        let rows = self.line_rows();
+        let mut lines = executed_lines(&rows, &executed_ranges(branches, pc));
+
+        if lines.len() > max_lines {
+            lines.drain(..lines.len() - max_lines);
+        }
+
+        lines
+    }
+
+    /// All rows of the line programs of all units, sorted by address.
+    fn line_rows(&self) -> Vec<LineRow> {
+        let mut rows = Vec::new();
+        let mut units = self.dwarf.units();
+
+        while let Ok(Some(header)) = units.next() {
+            let Ok(unit) = self.dwarf.unit(header) else {
+                continue;
+            };
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+            let Ok((program, sequences)) = program.sequences() else {
+                continue;
+            };
+
+            for sequence in &sequences {
+                let mut sequence_rows = program.resume_from(sequence);
+
+                while let Ok(Some((header, row))) = sequence_rows.next_row() {
+                    if row.end_sequence() {
+                        continue;
+                    }
+
+                    let (file, directory) = row
+                        .file(header)
+                        .and_then(|file_entry| {
+                            self.find_file_and_directory(&unit, header, file_entry)
+                        })
+                        .unwrap_or_default();
+
+                    rows.push(LineRow {
+                        address: row.address(),
+                        file,
+                        directory,
+                        line: row.line().map(NonZeroU64::get),
+                        column: row.column(),
+                        sequence: (sequence.start, sequence.end),
+                    });
+                }
+            }
+        }
+
+        rows.sort_by_key(|row| row.address);
+        rows
+    }
+}
+
+/// The address ranges executed sequentially between the `branches`, up to `pc`, with inclusive
+/// ends.
+fn executed_ranges(branches: &[BranchRecord], pc: u64) -> Vec<(u64, u64)> {
+    let mut ranges = Vec::new();
+
+    for (branch, next) in branches.iter().zip(branches.iter().skip(1)) {
+        if !next.trace_start && branch.destination <= next.source {
+            ranges.push((branch.destination, next.source));
+        }
+    }
+
+    if let Some(last) = branches.last() {
+        if last.destination <= pc {
+            ranges.push((last.destination, pc));
+        }
+    }
+
+    ranges
+}
+
+/// The lines of the `rows` which are in the executed `ranges`.
+fn executed_lines(rows: &[LineRow], ranges: &[(u64, u64)]) -> Vec<ExecutedLine> {
+    let mut lines: Vec<ExecutedLine> = Vec::new();
+    let mut previous: Option<&LineRow> = None;
+
+    for &(start, end) in ranges {
+        // The row containing the start of the range, which might be in the middle of the row.
+        let first = rows
+            .partition_point(|row| row.address <= start)
+            .saturating_sub(1);
+
+        for row in rows[first..].iter().take_while(|row| row.address <= end) {
+            let address = row.address.max(start);
+
+            // The start of the range can be in a gap between sequences without debug information.
+            if address >= row.sequence.1 {
+                continue;
+            }
+
+            if previous.is_some_and(|previous| previous.is_same_line(row)) {
+                continue;
+            }
+
+            lines.push(row.executed_line(address));
+            previous = Some(row);
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{executed_lines, executed_ranges, BranchRecord, LineRow};
+    use gimli::ColumnType;
+
+    fn row(address: u64, line: u64) -> LineRow {
+        LineRow {
+            address,
+            file: Some("main.rs".to_owned()),
+            directory: None,
+            line: Some(line),
+            column: ColumnType::LeftEdge,
+            sequence: (0x100, 0x200),
+        }
+    }
+
+    fn branch(source: u64, destination: u64) -> BranchRecord {
+        BranchRecord {
+            source,
+            destination,
+            trace_start: false,
+        }
+    }
+
+    #[test]
+    fn ranges_between_branches() {
+        let branches = [
+            branch(0x110, 0x140),
+            branch(0x148, 0x120),
+            branch(0x12A, 0x180),
+        ];
+
+        assert_eq!(
+            executed_ranges(&branches, 0x184),
+            vec![(0x140, 0x148), (0x120, 0x12A), (0x180, 0x184)]
+        );
+    }
+
+    #[test]
+    fn untraced_code_is_skipped() {
+        let mut restart = branch(0x160, 0x170);
+        restart.trace_start = true;
+
+        assert_eq!(
+            executed_ranges(&[branch(0x110, 0x140), restart], 0x174),
+            vec![(0x170, 0x174)]
+        );
+    }
+
+    #[test]
+    fn lines_of_ranges() {
+        let rows = [
+            row(0x100, 1),
+            row(0x104, 2),
+            row(0x108, 2),
+            row(0x10C, 3),
+            row(0x110, 4),
+        ];
+
+        let lines = executed_lines(&rows, &[(0x106, 0x10E), (0x100, 0x102)])
+            .into_iter()
+            .map(|line| (line.address, line.location.line))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![(0x106, Some(2)), (0x10C, Some(3)), (0x100, Some(1))]
+        );
+    }
+}
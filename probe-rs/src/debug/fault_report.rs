@@ -0,0 +1,185 @@
+use super::{DebugInfo, DebugRegisters};
+use crate::{
+    core::{ExceptionInterface, RegisterValue},
+    Core, CoreType, MemoryInterface,
+};
+use serde::{Deserialize, Serialize};
+
+/// The fault status and address registers of ARMv7-M and ARMv8-M cores.
+const FAULT_STATUS_REGISTERS: &[(&str, u64)] = &[
+    ("CFSR", 0xE000_ED28),
+    ("HFSR", 0xE000_ED2C),
+    ("MMFAR", 0xE000_ED34),
+    ("BFAR", 0xE000_ED38),
+];
+
+/// The SecureFault status and address registers of ARMv8-M cores.
+const SECURE_FAULT_STATUS_REGISTERS: &[(&str, u64)] =
+    &[("SFSR", 0xE000_EDE4), ("SFAR", 0xE000_EDE8)];
+
+/// The number of bytes of the faulting instruction in a [`FaultReport`], which covers the
+/// longest instruction of the supported architectures.
+const INSTRUCTION_BYTES: usize = 4;
+
+/// The state of a core which halted because of a fault, collected by [`FaultReport::collect`].
+///
+/// The report contains everything which is usually needed to triage a crash, and can be
+/// serialized, for example to JSON, to be attached to a bug report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultReport {
+    /// The type of the core.
+    pub core_type: CoreType,
+    /// A description of the exception which is handled, if the core is in an exception handler.
+    pub exception: Option<String>,
+    /// The registers of the core.
+    pub registers: Vec<ReportRegister>,
+    /// The fault status and address registers of the core, for Cortex-M cores.
+    pub fault_status: Vec<ReportRegister>,
+    /// The registers of the code which was interrupted by the exception, as stacked on exception
+    /// entry.
+    pub stacked_frame: Vec<ReportRegister>,
+    /// The innermost frames of the stack, if debug information was available.
+    pub backtrace: Vec<ReportFrame>,
+    /// The instruction which caused the fault, or the current instruction if the core is not
+    /// in an exception handler.
+    pub instruction: Option<ReportInstruction>,
+}
+
+/// A register in a [`FaultReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportRegister {
+    /// The name of the register.
+    pub name: String,
+    /// The value of the register.
+    pub value: u64,
+}
+
+/// A stack frame in a [`FaultReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportFrame {
+    /// The name of the function.
+    pub function: String,
+    /// The program counter in the frame.
+    pub pc: u64,
+    /// The source file of the program counter.
+    pub file: Option<String>,
+    /// The source line of the program counter.
+    pub line: Option<u64>,
+}
+
+/// The faulting instruction in a [`FaultReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportInstruction {
+    /// The address of the instruction.
+    pub address: u64,
+    /// The memory at the address, which starts with the instruction.
+    pub bytes: Vec<u8>,
+    /// The disassembled instruction, which is not set by [`FaultReport::collect`], as probe-rs
+    /// does not contain a disassembler.
+    pub disassembly: Option<String>,
+}
+
+impl FaultReport {
+    /// Collect a report of the state of the halted `core`.
+    ///
+    /// With `debug_info`, the report contains a backtrace of at most `max_frames` frames.
+    /// Parts of the state which cannot be read are left out, so a report is returned also for
+    /// cores in an inconsistent state.
+    pub fn collect(
+        core: &mut Core,
+        debug_info: Option<&DebugInfo>,
+        max_frames: usize,
+    ) -> FaultReport {
+        let core_type = core.core_type();
+        let registers = DebugRegisters::from_core(core);
+
+        let exception = core.exception_details(&registers).ok().flatten();
+
+        let fault_status_registers = match core_type {
+            CoreType::Armv7m | CoreType::Armv7em => FAULT_STATUS_REGISTERS.to_vec(),
+            CoreType::Armv8m => [FAULT_STATUS_REGISTERS, SECURE_FAULT_STATUS_REGISTERS].concat(),
+            _ => Vec::new(),
+        };
+        // Registers which cannot be read, such as the SecureFault registers from Non-secure
+        // state, are left out.
+        let fault_status = fault_status_registers
+            .iter()
+            .filter_map(|&(name, address)| {
+                let value = core.read_word_32(address).ok()?;
+                Some(ReportRegister {
+                    name: name.to_owned(),
+                    value: value.into(),
+                })
+            })
+            .collect();
+
+        let stacked_frame = exception
+            .as_ref()
+            .map(|exception| report_registers(&exception.calling_frame_registers))
+            .unwrap_or_default();
+
+        let instruction_registers = exception
+            .as_ref()
+            .map_or(&registers, |exception| &exception.calling_frame_registers);
+        let instruction = register_value(instruction_registers.get_program_counter())
+            .map(|address| {
+                let mut bytes = vec![0; INSTRUCTION_BYTES];
+                core.read(address, &mut bytes).map(|_| ReportInstruction {
+                    address,
+                    bytes,
+                    disassembly: None,
+                })
+            })
+            .transpose()
+            .ok()
+            .flatten();
+
+        let backtrace = match (debug_info, register_value(registers.get_program_counter())) {
+            (Some(debug_info), Some(pc)) => debug_info
+                .unwind(core, pc)
+                .unwrap_or_default()
+                .into_iter()
+                .take(max_frames)
+                .map(|frame| ReportFrame {
+                    function: frame.function_name,
+                    pc: frame.pc.try_into().unwrap_or_default(),
+                    file: frame
+                        .source_location
+                        .as_ref()
+                        .and_then(|location| location.file.clone()),
+                    line: frame.source_location.and_then(|location| location.line),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        FaultReport {
+            core_type,
+            exception: exception.map(|exception| exception.description),
+            registers: report_registers(&registers),
+            fault_status,
+            stacked_frame,
+            backtrace,
+            instruction,
+        }
+    }
+}
+
+fn register_value(register: Option<&super::DebugRegister>) -> Option<u64> {
+    register
+        .and_then(|register| register.value)
+        .and_then(|value: RegisterValue| value.try_into().ok())
+}
+
+fn report_registers(registers: &DebugRegisters) -> Vec<ReportRegister> {
+    registers
+        .0
+        .iter()
+        .filter_map(|register| {
+            Some(ReportRegister {
+                name: register.get_register_name(),
+                value: register_value(Some(register))?,
+            })
+        })
+        .collect()
+}
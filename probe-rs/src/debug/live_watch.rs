@@ -0,0 +1,750 @@
+use super::{DebugInfo, DwarfReader};
+use crate::{Error, MemoryInterface};
+use gimli::{AttributeValue, DebuggingInformationEntry, Operation, Unit, UnitOffset};
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// An error while resolving a watch expression, see [`WatchExpression::resolve`].
+#[derive(Debug, thiserror::Error)]
+pub enum WatchExpressionError {
+    /// The expression is not a variable path.
+    #[error("Invalid watch expression {0:?}, expected a path like `STATE.field[2]`.")]
+    Syntax(String),
+    /// No static variable with the name was found.
+    #[error("No static variable {0} was found.")]
+    NotFound(String),
+    /// The type has no member with the name.
+    #[error("{expression} has no field {field}.")]
+    UnknownField {
+        /// The expression up to the field.
+        expression: String,
+        /// The name of the field.
+        field: String,
+    },
+    /// The array index is out of bounds, or the value is not an array.
+    #[error("{expression} has no element {index}.")]
+    InvalidIndex {
+        /// The expression up to the index.
+        expression: String,
+        /// The index.
+        index: u64,
+    },
+    /// The value of the expression is not of a base, pointer or enumeration type.
+    #[error("{0} is not a value of a base, pointer or C-like enumeration type.")]
+    UnsupportedType(String),
+    /// Parsing the debug information failed.
+    #[error("Error parsing debug data")]
+    Parse(#[from] gimli::read::Error),
+}
+
+/// A typed value of a [`WatchExpression`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum LiveValue {
+    /// An unsigned integer.
+    Unsigned(u64),
+    /// A signed integer.
+    Signed(i64),
+    /// A floating-point number.
+    Float(f64),
+    /// A boolean.
+    Bool(bool),
+    /// A character.
+    Char(char),
+    /// A pointer or reference.
+    Pointer(u64),
+    /// A variant of an enumeration.
+    Enum {
+        /// The discriminant of the variant.
+        value: u64,
+        /// The name of the variant, if the discriminant is known.
+        variant: Option<String>,
+    },
+}
+
+impl fmt::Display for LiveValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LiveValue::Unsigned(value) => write!(f, "{value}"),
+            LiveValue::Signed(value) => write!(f, "{value}"),
+            LiveValue::Float(value) => write!(f, "{value}"),
+            LiveValue::Bool(value) => write!(f, "{value}"),
+            LiveValue::Char(value) => write!(f, "{value:?}"),
+            LiveValue::Pointer(value) => write!(f, "{value:#010x}"),
+            LiveValue::Enum {
+                variant: Some(variant),
+                ..
+            } => write!(f, "{variant}"),
+            LiveValue::Enum { value, .. } => write!(f, "<unknown variant {value}>"),
+        }
+    }
+}
+
+/// How the bytes of a [`WatchExpression`] are decoded.
+#[derive(Debug, Clone, PartialEq)]
+enum ValueKind {
+    Unsigned,
+    Signed,
+    Float,
+    Bool,
+    Char,
+    Pointer,
+    Enum(Vec<(u64, String)>),
+}
+
+/// A part of the path of a watch expression, after the variable.
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Field(String),
+    Index(u64),
+}
+
+/// A watch expression, resolved to the location and type of its value.
+///
+/// Expressions are paths to static variables, with the fields of structures and unions and
+/// the elements of arrays, for example `COUNTER`, `app::STATE.mode` or `BUFFER[3].len`.
+/// Module paths can be left out, as long as the variable name is unique.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchExpression {
+    expression: String,
+    address: u64,
+    size: usize,
+    kind: ValueKind,
+}
+
+impl WatchExpression {
+    /// Resolve `expression` with the debug information of the program.
+    pub fn resolve(debug_info: &DebugInfo, expression: &str) -> Result<Self, WatchExpressionError> {
+        let (variable, path) = parse_expression(expression)?;
+
+        let mut units = debug_info.dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = debug_info.dwarf.unit(header)?;
+            if let Some((address, type_offset)) = find_static(debug_info, &unit, &variable)? {
+                let resolver = TypeResolver {
+                    debug_info,
+                    unit: &unit,
+                };
+                return resolver.resolve(expression, &variable, address, type_offset, &path);
+            }
+        }
+
+        Err(WatchExpressionError::NotFound(variable))
+    }
+
+    /// The expression, as given to [`WatchExpression::resolve`].
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// The address of the value.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// The size of the value in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Read the current value from `memory`.
+    pub fn read(&self, memory: &mut impl MemoryInterface) -> Result<Option<LiveValue>, Error> {
+        let mut bytes = vec![0; self.size];
+        memory.read(self.address, &mut bytes)?;
+        Ok(self.decode(&bytes))
+    }
+
+    /// Decode the little-endian `bytes` of the value, or `None` if they are no valid value.
+    fn decode(&self, bytes: &[u8]) -> Option<LiveValue> {
+        let mut raw = [0; 8];
+        raw.get_mut(..bytes.len())?.copy_from_slice(bytes);
+        let unsigned = u64::from_le_bytes(raw);
+
+        Some(match &self.kind {
+            ValueKind::Unsigned => LiveValue::Unsigned(unsigned),
+            ValueKind::Signed => {
+                let shift = 64 - 8 * bytes.len() as u32;
+                LiveValue::Signed(((unsigned << shift) as i64) >> shift)
+            }
+            ValueKind::Float => match bytes.len() {
+                4 => LiveValue::Float(f32::from_bits(unsigned as u32) as f64),
+                8 => LiveValue::Float(f64::from_bits(unsigned)),
+                _ => return None,
+            },
+            ValueKind::Bool => LiveValue::Bool(unsigned != 0),
+            ValueKind::Char => LiveValue::Char(char::from_u32(unsigned as u32)?),
+            ValueKind::Pointer => LiveValue::Pointer(unsigned),
+            ValueKind::Enum(variants) => LiveValue::Enum {
+                value: unsigned,
+                variant: variants
+                    .iter()
+                    .find(|(value, _)| *value == unsigned)
+                    .map(|(_, name)| name.clone()),
+            },
+        })
+    }
+}
+
+/// Split an expression into the variable path and the field and index accesses.
+fn parse_expression(expression: &str) -> Result<(String, Vec<PathSegment>), WatchExpressionError> {
+    let syntax_error = || WatchExpressionError::Syntax(expression.to_owned());
+    let is_identifier = |s: &str| {
+        !s.is_empty()
+            && !s.starts_with(|c: char| c.is_ascii_digit())
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+
+    let expression = expression.trim();
+    let variable_end = expression.find(['.', '[']).unwrap_or(expression.len());
+    let variable = &expression[..variable_end];
+    if !variable.split("::").all(is_identifier) {
+        return Err(syntax_error());
+    }
+
+    let mut path = Vec::new();
+    let mut rest = &expression[variable_end..];
+    while !rest.is_empty() {
+        if let Some(field) = rest.strip_prefix('.') {
+            let end = field.find(['.', '[']).unwrap_or(field.len());
+            // Tuple fields, such as `.0`, are valid field names.
+            if !field[..end]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                || end == 0
+            {
+                return Err(syntax_error());
+            }
+            path.push(PathSegment::Field(field[..end].to_owned()));
+            rest = &field[end..];
+        } else if let Some(index) = rest.strip_prefix('[') {
+            let (index, remainder) = index.split_once(']').ok_or_else(syntax_error)?;
+            let index = index.trim().parse().map_err(|_| syntax_error())?;
+            path.push(PathSegment::Index(index));
+            rest = remainder;
+        } else {
+            return Err(syntax_error());
+        }
+    }
+
+    Ok((variable.to_owned(), path))
+}
+
+/// The address and type of the static variable with the (partially) qualified name `variable`
+/// in `unit`.
+fn find_static(
+    debug_info: &DebugInfo,
+    unit: &Unit<DwarfReader>,
+    variable: &str,
+) -> Result<Option<(u64, UnitOffset)>, gimli::Error> {
+    let suffix = format!("::{variable}");
+    let mut namespaces: Vec<(isize, String)> = Vec::new();
+    let mut depth = 0;
+
+    let mut entries = unit.entries();
+    while let Some((delta, entry)) = entries.next_dfs()? {
+        depth += delta;
+        namespaces.retain(|(namespace_depth, _)| *namespace_depth < depth);
+
+        let Some(name) = entry_name(debug_info, unit, entry)? else {
+            continue;
+        };
+
+        match entry.tag() {
+            gimli::DW_TAG_namespace => namespaces.push((depth, name)),
+            gimli::DW_TAG_variable => {
+                let qualified = namespaces
+                    .iter()
+                    .map(|(_, namespace)| namespace.as_str())
+                    .chain([name.as_str()])
+                    .collect::<Vec<_>>()
+                    .join("::");
+                if qualified != variable && !qualified.ends_with(&suffix) {
+                    continue;
+                }
+
+                if let (Some(address), Some(type_offset)) =
+                    (static_address(unit, entry)?, type_of(entry)?)
+                {
+                    return Ok(Some((address, type_offset)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// The address of a variable whose location is a fixed address.
+fn static_address(
+    unit: &Unit<DwarfReader>,
+    entry: &DebuggingInformationEntry<DwarfReader>,
+) -> Result<Option<u64>, gimli::Error> {
+    let Some(AttributeValue::Exprloc(expression)) = entry.attr_value(gimli::DW_AT_location)? else {
+        return Ok(None);
+    };
+
+    let mut operations = expression.operations(unit.encoding());
+    match (operations.next()?, operations.next()?) {
+        (Some(Operation::Address { address }), None) => Ok(Some(address)),
+        _ => Ok(None),
+    }
+}
+
+fn entry_name(
+    debug_info: &DebugInfo,
+    unit: &Unit<DwarfReader>,
+    entry: &DebuggingInformationEntry<DwarfReader>,
+) -> Result<Option<String>, gimli::Error> {
+    entry
+        .attr_value(gimli::DW_AT_name)?
+        .map(|name| {
+            let name = debug_info.dwarf.attr_string(unit, name)?;
+            Ok(String::from_utf8_lossy(&name).into_owned())
+        })
+        .transpose()
+}
+
+fn type_of(
+    entry: &DebuggingInformationEntry<DwarfReader>,
+) -> Result<Option<UnitOffset>, gimli::Error> {
+    Ok(match entry.attr_value(gimli::DW_AT_type)? {
+        Some(AttributeValue::UnitRef(offset)) => Some(offset),
+        _ => None,
+    })
+}
+
+fn udata(
+    entry: &DebuggingInformationEntry<DwarfReader>,
+    attribute: gimli::DwAt,
+) -> Result<Option<u64>, gimli::Error> {
+    Ok(entry
+        .attr(attribute)?
+        .and_then(|attribute| attribute.udata_value()))
+}
+
+/// Resolves the path of an expression through the types of a unit.
+struct TypeResolver<'a> {
+    debug_info: &'a DebugInfo,
+    unit: &'a Unit<DwarfReader>,
+}
+
+impl TypeResolver<'_> {
+    fn resolve(
+        &self,
+        expression: &str,
+        variable: &str,
+        mut address: u64,
+        mut type_offset: UnitOffset,
+        path: &[PathSegment],
+    ) -> Result<WatchExpression, WatchExpressionError> {
+        let mut resolved = variable.to_owned();
+
+        for segment in path {
+            let entry = self.unit.entry(self.strip_modifiers(type_offset)?)?;
+
+            match segment {
+                PathSegment::Field(field) => {
+                    let (offset, member_type) = self.member(&entry, field)?.ok_or_else(|| {
+                        WatchExpressionError::UnknownField {
+                            expression: resolved.clone(),
+                            field: field.clone(),
+                        }
+                    })?;
+                    address += offset;
+                    type_offset = member_type;
+                    resolved = format!("{resolved}.{field}");
+                }
+                PathSegment::Index(index) => {
+                    let invalid_index = || WatchExpressionError::InvalidIndex {
+                        expression: resolved.clone(),
+                        index: *index,
+                    };
+                    if entry.tag() != gimli::DW_TAG_array_type {
+                        return Err(invalid_index());
+                    }
+                    let element_type = type_of(&entry)?.ok_or_else(invalid_index)?;
+                    if self.array_len(&entry)?.is_some_and(|len| *index >= len) {
+                        return Err(invalid_index());
+                    }
+                    let element_size = self.size_of(element_type)?.ok_or_else(invalid_index)?;
+
+                    address += index * element_size;
+                    type_offset = element_type;
+                    resolved = format!("{resolved}[{index}]");
+                }
+            }
+        }
+
+        let entry = self.unit.entry(self.strip_modifiers(type_offset)?)?;
+        let unsupported = || WatchExpressionError::UnsupportedType(expression.to_owned());
+
+        let kind = match entry.tag() {
+            gimli::DW_TAG_base_type => {
+                match entry.attr_value(gimli::DW_AT_encoding)? {
+                    Some(AttributeValue::Encoding(encoding)) => match encoding {
+                        gimli::DW_ATE_unsigned => ValueKind::Unsigned,
+                        gimli::DW_ATE_signed | gimli::DW_ATE_signed_char => ValueKind::Signed,
+                        gimli::DW_ATE_float => ValueKind::Float,
+                        gimli::DW_ATE_boolean => ValueKind::Bool,
+                        // Rust emits `char` as `DW_ATE_unsigned_char`.
+                        gimli::DW_ATE_UTF | gimli::DW_ATE_unsigned_char => ValueKind::Char,
+                        _ => return Err(unsupported()),
+                    },
+                    _ => return Err(unsupported()),
+                }
+            }
+            gimli::DW_TAG_pointer_type
+            | gimli::DW_TAG_reference_type
+            | gimli::DW_TAG_rvalue_reference_type => ValueKind::Pointer,
+            gimli::DW_TAG_enumeration_type => ValueKind::Enum(self.enumerators(&entry)?),
+            _ => return Err(unsupported()),
+        };
+
+        let size = self
+            .size_of(type_offset)?
+            .filter(|size| (1..=8).contains(size))
+            .ok_or_else(unsupported)?;
+
+        Ok(WatchExpression {
+            expression: expression.to_owned(),
+            address,
+            size: size as usize,
+            kind,
+        })
+    }
+
+    /// Follow typedefs and type qualifiers to the underlying type.
+    fn strip_modifiers(&self, mut offset: UnitOffset) -> Result<UnitOffset, gimli::Error> {
+        loop {
+            let entry = self.unit.entry(offset)?;
+            match entry.tag() {
+                gimli::DW_TAG_typedef
+                | gimli::DW_TAG_const_type
+                | gimli::DW_TAG_volatile_type
+                | gimli::DW_TAG_atomic_type
+                | gimli::DW_TAG_restrict_type => match type_of(&entry)? {
+                    Some(inner) => offset = inner,
+                    None => return Ok(offset),
+                },
+                _ => return Ok(offset),
+            }
+        }
+    }
+
+    /// The size of the type in bytes.
+    fn size_of(&self, offset: UnitOffset) -> Result<Option<u64>, gimli::Error> {
+        let offset = self.strip_modifiers(offset)?;
+        let entry = self.unit.entry(offset)?;
+
+        if let Some(size) = udata(&entry, gimli::DW_AT_byte_size)? {
+            return Ok(Some(size));
+        }
+
+        Ok(match entry.tag() {
+            gimli::DW_TAG_pointer_type
+            | gimli::DW_TAG_reference_type
+            | gimli::DW_TAG_rvalue_reference_type => Some(self.unit.encoding().address_size as u64),
+            gimli::DW_TAG_array_type => match (self.array_len(&entry)?, type_of(&entry)?) {
+                (Some(len), Some(element)) => self.size_of(element)?.map(|size| size * len),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    /// The number of elements of an array type, if it is known.
+    fn array_len(
+        &self,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<Option<u64>, gimli::Error> {
+        let mut tree = self.unit.entries_tree(Some(entry.offset()))?;
+        let mut children = tree.root()?.children();
+
+        while let Some(child) = children.next()? {
+            let child = child.entry();
+            if child.tag() == gimli::DW_TAG_subrange_type {
+                if let Some(count) = udata(child, gimli::DW_AT_count)? {
+                    return Ok(Some(count));
+                }
+                let lower = udata(child, gimli::DW_AT_lower_bound)?.unwrap_or(0);
+                return Ok(udata(child, gimli::DW_AT_upper_bound)?
+                    .map(|upper| (upper + 1).saturating_sub(lower)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The offset and type of the member `field` of a structure or union type.
+    fn member(
+        &self,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+        field: &str,
+    ) -> Result<Option<(u64, UnitOffset)>, gimli::Error> {
+        if !matches!(
+            entry.tag(),
+            gimli::DW_TAG_structure_type | gimli::DW_TAG_union_type | gimli::DW_TAG_class_type
+        ) {
+            return Ok(None);
+        }
+
+        let mut tree = self.unit.entries_tree(Some(entry.offset()))?;
+        let mut children = tree.root()?.children();
+
+        while let Some(child) = children.next()? {
+            let child = child.entry();
+            if child.tag() != gimli::DW_TAG_member
+                || entry_name(self.debug_info, self.unit, child)?.as_deref() != Some(field)
+            {
+                continue;
+            }
+
+            let offset = udata(child, gimli::DW_AT_data_member_location)?.unwrap_or(0);
+            return Ok(type_of(child)?.map(|member_type| (offset, member_type)));
+        }
+
+        Ok(None)
+    }
+
+    /// The discriminants and names of the variants of an enumeration type.
+    fn enumerators(
+        &self,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<Vec<(u64, String)>, gimli::Error> {
+        let mut tree = self.unit.entries_tree(Some(entry.offset()))?;
+        let mut children = tree.root()?.children();
+        let mut enumerators = Vec::new();
+
+        while let Some(child) = children.next()? {
+            let child = child.entry();
+            if child.tag() != gimli::DW_TAG_enumerator {
+                continue;
+            }
+
+            let value = match child.attr_value(gimli::DW_AT_const_value)? {
+                Some(AttributeValue::Sdata(value)) => value as u64,
+                Some(value) => match value.udata_value() {
+                    Some(value) => value,
+                    None => continue,
+                },
+                None => continue,
+            };
+            if let Some(name) = entry_name(self.debug_info, self.unit, child)? {
+                enumerators.push((value, name));
+            }
+        }
+
+        Ok(enumerators)
+    }
+}
+
+/// The value of a [`WatchExpression`] read by a [`LiveWatch`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiveSample {
+    /// The watch expression.
+    pub expression: String,
+    /// The time the value was read, relative to the first sample of the watch.
+    pub timestamp: Duration,
+    /// The value, or `None` if it could not be read, or is not a valid value of its type.
+    pub value: Option<LiveValue>,
+}
+
+/// Periodic evaluation of watch expressions, for live views of variables while the target is
+/// running.
+///
+/// The expressions are resolved once with the debug information, each sample only reads the
+/// memory of the values. Reading memory while the core is running requires that the target
+/// supports memory access in the background, as ARM targets do.
+#[derive(Debug, Default)]
+pub struct LiveWatch {
+    expressions: Vec<WatchExpression>,
+    /// The time of the first sample, or `None` before it.
+    start: Option<Instant>,
+}
+
+impl LiveWatch {
+    /// Create a watch without expressions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve and add a watch expression, see [`WatchExpression::resolve`].
+    pub fn add(
+        &mut self,
+        debug_info: &DebugInfo,
+        expression: &str,
+    ) -> Result<(), WatchExpressionError> {
+        self.expressions
+            .push(WatchExpression::resolve(debug_info, expression)?);
+        Ok(())
+    }
+
+    /// Remove the watch expression `expression`.
+    pub fn remove(&mut self, expression: &str) {
+        self.expressions
+            .retain(|watched| watched.expression != expression);
+    }
+
+    /// The watched expressions.
+    pub fn expressions(&self) -> &[WatchExpression] {
+        &self.expressions
+    }
+
+    /// Read the current values of all expressions, in the order in which they were added.
+    ///
+    /// Values which cannot be read are reported as `None`. Only the error of flushing the
+    /// memory interface, which means that the probe cannot be used anymore, is returned.
+    pub fn sample(&mut self, memory: &mut impl MemoryInterface) -> Result<Vec<LiveSample>, Error> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+
+        let samples = self
+            .expressions
+            .iter()
+            .map(|expression| LiveSample {
+                expression: expression.expression.clone(),
+                value: expression.read(memory).ok().flatten(),
+                timestamp: start.elapsed(),
+            })
+            .collect();
+
+        memory.flush()?;
+
+        Ok(samples)
+    }
+
+    /// Sample the expressions every `interval` and call `on_sample` with the values, until it
+    /// returns `false`.
+    ///
+    /// This is a cancellation point, see [`cancellation`](crate::cancellation).
+    pub fn run(
+        &mut self,
+        memory: &mut impl MemoryInterface,
+        interval: Duration,
+        mut on_sample: impl FnMut(&[LiveSample]) -> bool,
+    ) -> Result<(), Error> {
+        loop {
+            crate::cancellation::check()?;
+
+            let sample_start = Instant::now();
+
+            if !on_sample(&self.sample(memory)?) {
+                return Ok(());
+            }
+
+            if let Some(remaining) = interval.checked_sub(sample_start.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::{parse_expression, LiveValue, LiveWatch, PathSegment, WatchExpression};
+    use crate::debug::DebugInfo;
+    use crate::rtos::test_memory::TestMemory;
+
+    fn test_debug_info() -> DebugInfo {
+        DebugInfo::from_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/probe-rs-debugger-test"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn expressions_are_parsed() {
+        assert_eq!(
+            parse_expression("app::STATE.channels[2].len").unwrap(),
+            (
+                "app::STATE".to_owned(),
+                vec![
+                    PathSegment::Field("channels".to_owned()),
+                    PathSegment::Index(2),
+                    PathSegment::Field("len".to_owned()),
+                ]
+            )
+        );
+        assert_eq!(
+            parse_expression("PAIR.0").unwrap().1,
+            vec![PathSegment::Field("0".to_owned())]
+        );
+
+        for invalid in ["", "1ABC", "A.", "A[x]", "A[1", "A b", "A..b"] {
+            assert!(parse_expression(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn statics_are_resolved() {
+        let debug_info = test_debug_info();
+
+        let u32_static = WatchExpression::resolve(&debug_info, "U32").unwrap();
+        assert_eq!((u32_static.address(), u32_static.size()), (0x2000_0020, 4));
+
+        let qualified =
+            WatchExpression::resolve(&debug_info, "probe_rs_debugger_test::I8").unwrap();
+        assert_eq!((qualified.address(), qualified.size()), (0x2000_0008, 1));
+
+        let field = WatchExpression::resolve(
+            &debug_info,
+            "CONTROL_BLOCK.value.value.header.max_down_channels",
+        )
+        .unwrap();
+        assert_eq!((field.address(), field.size()), (0x2000_004C + 0x14, 4));
+
+        let element =
+            WatchExpression::resolve(&debug_info, "CONTROL_BLOCK.value.value.header.id[3]")
+                .unwrap();
+        assert_eq!((element.address(), element.size()), (0x2000_004C + 3, 1));
+
+        assert!(WatchExpression::resolve(&debug_info, "NO_SUCH_STATIC").is_err());
+        assert!(WatchExpression::resolve(&debug_info, "U32.field").is_err());
+        assert!(
+            WatchExpression::resolve(&debug_info, "CONTROL_BLOCK.value.value.header.id[16]")
+                .is_err()
+        );
+        // Structures have no single value.
+        assert!(WatchExpression::resolve(&debug_info, "CONTROL_BLOCK.value.value.header").is_err());
+    }
+
+    #[test]
+    fn samples_are_typed() {
+        let debug_info = test_debug_info();
+
+        let mut memory = TestMemory::default();
+        memory.set_bytes(0x2000_0008, &[0xFE]);
+        memory.set_word(0x2000_0020, 0x1234_5678);
+        memory.set_bytes(0x2000_0030, &1.5f32.to_le_bytes());
+        memory.set_bytes(0x2000_0048, &[1]);
+        memory.set_word(0x2000_0004, 'x' as u32);
+
+        let mut watch = LiveWatch::new();
+        for expression in ["I8", "U32", "F32", "B", "C"] {
+            watch.add(&debug_info, expression).unwrap();
+        }
+
+        let values = watch
+            .sample(&mut memory)
+            .unwrap()
+            .into_iter()
+            .map(|sample| sample.value)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            values,
+            [
+                Some(LiveValue::Signed(-2)),
+                Some(LiveValue::Unsigned(0x1234_5678)),
+                Some(LiveValue::Float(1.5)),
+                Some(LiveValue::Bool(true)),
+                Some(LiveValue::Char('x')),
+            ]
+        );
+
+        watch.remove("U32");
+        assert_eq!(watch.expressions().len(), 4);
+    }
+}
@@ -12,8 +12,14 @@
 pub mod debug_info;
 /// Stepping through a program during debug, at various granularities.
 pub mod debug_step;
+/// The source lines executed by the core, reconstructed from branch trace.
+pub mod execution_history;
+/// Serializable reports of the state of a core after a fault.
+pub mod fault_report;
 /// References to the DIE (debug information entry) of functions.
 pub mod function_die;
+/// Periodic evaluation of watch expressions on a running target.
+pub mod live_watch;
 /// Target Register definitions, expanded from [`crate::core::registers::CoreRegister`] to include unwind specific information.
 pub mod registers;
 /// The source statement information used while identifying haltpoints for debug stepping and breakpoints.
@@ -28,7 +34,14 @@ pub mod variable;
 pub mod variable_cache;
 
 pub use self::{
-    debug_info::*, debug_step::SteppingMode, registers::*, stack_frame::StackFrame, variable::*,
+    debug_info::*,
+    debug_step::SteppingMode,
+    execution_history::{BranchRecord, ExecutedLine},
+    fault_report::FaultReport,
+    live_watch::{LiveSample, LiveValue, LiveWatch, WatchExpression, WatchExpressionError},
+    registers::*,
+    stack_frame::StackFrame,
+    variable::*,
     variable_cache::VariableCache,
 };
 use crate::{core::Core, MemoryInterface};
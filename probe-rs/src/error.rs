@@ -1,7 +1,9 @@
 #![warn(missing_docs)]
 
+use crate::architecture::arm::sequences::LifecycleState;
 use crate::architecture::arm::ArmError;
 use crate::architecture::riscv::communication_interface::RiscvError;
+use crate::cancellation::Cancelled;
 use crate::config::RegistryError;
 use crate::DebugProbeError;
 
@@ -54,6 +56,92 @@ pub enum Error {
     #[error("A timeout occurred.")]
     Timeout,
 
+    /// The operation was aborted with a [`CancellationToken`](crate::cancellation::CancellationToken).
+    #[error("The operation was aborted")]
+    Cancelled(#[from] Cancelled),
+
+    /// A hook registered in [`SessionHooks`](crate::SessionHooks) failed.
+    #[error("The {0} hook failed")]
+    Hook(&'static str, #[source] crate::flashing::HookError),
+
+    /// Memory cannot be read without halting the core, see
+    /// [`Session::read_while_running`](crate::Session::read_while_running).
+    #[error("Memory cannot be read while the core is running: {0}")]
+    ReadWhileRunningNotSupported(&'static str),
+
+    /// A core was reset or halted while attaching with
+    /// [`Probe::attach_preserving`](crate::Probe::attach_preserving).
+    #[error("Core {core} was {operation} while attaching, its state was not preserved")]
+    StateNotPreserved {
+        /// The index of the core.
+        core: usize,
+        /// The operation performed on the core, `"reset"` or `"halted"`.
+        operation: &'static str,
+    },
+
+    /// Debugging is already enabled on a core, so another debugger seems to be attached to the
+    /// target. See [`Permissions::deny_debugger_takeover`](crate::Permissions::deny_debugger_takeover).
+    #[error("Core {core} is already being debugged, another debugger seems to be attached to the target")]
+    TargetAlreadyDebugged {
+        /// The index of the core.
+        core: usize,
+    },
+
+    /// The device cannot be advanced to the requested lifecycle state.
+    #[error("The device cannot be advanced from lifecycle state {from} to {to}, only later states can be entered")]
+    InvalidLifecycleTransition {
+        /// The current lifecycle state.
+        from: LifecycleState,
+        /// The requested lifecycle state.
+        to: LifecycleState,
+    },
+
+    /// A memory access was denied by the access restrictions of a memory region of the target.
+    #[error("Access to {address:#010x} in memory region {region} was denied: {reason}")]
+    MemoryAccessDenied {
+        /// The address of the access.
+        address: u64,
+        /// The name of the memory region.
+        region: String,
+        /// Why the access was denied.
+        reason: &'static str,
+    },
+
+    /// A core did not halt with any of the steps of a
+    /// [`HaltEscalation`](crate::HaltEscalation).
+    #[error("Core {core} did not halt, tried {tried}. {hint}.")]
+    HaltFailed {
+        /// The index of the core.
+        core: usize,
+        /// The steps which were tried.
+        tried: String,
+        /// What else can be done to halt the core.
+        hint: &'static str,
+    },
+
+    /// No breakpoint unit can be used for a breakpoint, and a software breakpoint cannot be set
+    /// at its address either.
+    #[error("No breakpoint can be set at {address:#010x}: {hardware}, and {software}")]
+    NoBreakpointAvailable {
+        /// The address of the breakpoint.
+        address: u64,
+        /// Why no breakpoint unit can be used.
+        hardware: &'static str,
+        /// Why no software breakpoint can be set.
+        software: &'static str,
+    },
+
+    /// No watchpoint unit is free, or the hardware cannot watch the range of a watchpoint.
+    #[error("No watchpoint can be set for {length} bytes at {address:#010x}: {reason}")]
+    NoWatchpointAvailable {
+        /// The address of the watchpoint.
+        address: u64,
+        /// The number of bytes the watchpoint watches.
+        length: u64,
+        /// Why the watchpoint cannot be set.
+        reason: &'static str,
+    },
+
     /// Unaligned memory access
     #[error("Alignment error")]
     MemoryNotAligned {
@@ -68,6 +156,7 @@ impl From<ArmError> for Error {
     fn from(value: ArmError) -> Self {
         match value {
             ArmError::Timeout => Error::Timeout,
+            ArmError::Cancelled(cancelled) => Error::Cancelled(cancelled),
             ArmError::MemoryNotAligned { address, alignment } => {
                 Error::MemoryNotAligned { address, alignment }
             }
@@ -75,3 +164,34 @@ impl From<ArmError> for Error {
         }
     }
 }
+
+impl Error {
+    /// A short, stable identifier of the kind of error, for machine readable output.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Probe(_) => "probe",
+            Error::Arm(_) => "arm",
+            Error::Riscv(_) => "riscv",
+            Error::UnableToOpenProbe(_) => "unable_to_open_probe",
+            Error::CoreNotFound(_) => "core_not_found",
+            Error::ChipNotFound(_) => "chip_not_found",
+            Error::MissingPermissions(_) => "missing_permissions",
+            Error::GenericCoreError(_) => "generic_core_error",
+            Error::Register(_) => "register",
+            Error::NotImplemented(_) => "not_implemented",
+            Error::Other(_) => "other",
+            Error::Timeout => "timeout",
+            Error::Cancelled(_) => "cancelled",
+            Error::Hook(..) => "hook",
+            Error::ReadWhileRunningNotSupported(_) => "read_while_running_not_supported",
+            Error::StateNotPreserved { .. } => "state_not_preserved",
+            Error::MemoryAccessDenied { .. } => "memory_access_denied",
+            Error::TargetAlreadyDebugged { .. } => "target_already_debugged",
+            Error::InvalidLifecycleTransition { .. } => "invalid_lifecycle_transition",
+            Error::HaltFailed { .. } => "halt_failed",
+            Error::NoBreakpointAvailable { .. } => "no_breakpoint_available",
+            Error::NoWatchpointAvailable { .. } => "no_watchpoint_available",
+            Error::MemoryNotAligned { .. } => "memory_not_aligned",
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! Machine readable log of significant session events.
+//!
+//! Tools which drive probe-rs, for example CI pipelines or IDEs, can install an [`EventSink`]
+//! with [`set_event_sink`] to be informed about attaching to a target, chip detection,
+//! the phases of the flashing procedure, resets, debug port health problems and errors.
+//!
+//! The [`NdjsonEventSink`] writes every event as a single line of JSON:
+//!
+//! ```text
+//! {"timestamp_ms":1690000000000,"event":"attached","chip":"nRF52840_xxAA","probe":"J-Link","under_reset":false}
+//! ```
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe_rs::events::{set_event_sink, NdjsonEventSink};
+//!
+//! let log = std::fs::File::create("session.ndjson")?;
+//! set_event_sink(NdjsonEventSink::new(log));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::architecture::arm::dp::DpHealth;
+use crate::flashing::FlashError;
+use crate::Error;
+
+use serde::Serialize;
+use std::io::Write;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static EVENT_SINK: RwLock<Option<Arc<dyn EventSink>>> = RwLock::new(None);
+
+/// A significant event during a debug session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// The chip was determined by reading information from the target.
+    ChipDetected {
+        /// The name of the detected chip.
+        chip: String,
+    },
+    /// A session was opened.
+    Attached {
+        /// The name of the chip the session is attached to.
+        chip: String,
+        /// The name of the probe used for the session.
+        probe: String,
+        /// Whether the target was held in reset while attaching.
+        under_reset: bool,
+    },
+    /// A phase of the flashing procedure changed its status.
+    Flash {
+        /// The phase of the flashing procedure.
+        phase: FlashPhase,
+        /// The new status of the phase.
+        status: PhaseStatus,
+    },
+    /// A core was reset.
+    Reset {
+        /// The index of the core in the session.
+        core: usize,
+        /// Whether the core was halted directly after the reset.
+        halted: bool,
+    },
+    /// The target reset a core on its own, for example because of a watchdog. The debug settings
+    /// and breakpoints of the core are restored, see [`Core::status`](crate::Core::status).
+    TargetReset {
+        /// The index of the core in the session.
+        core: usize,
+    },
+    /// A problem with a debug port was detected by its CTRL/STAT register.
+    DpHealth {
+        /// The TARGETSEL value of a multidrop debug port, or `None` for the default debug port.
+        targetsel: Option<u32>,
+        /// The detected problems.
+        #[serde(flatten)]
+        health: DpHealth,
+    },
+    /// An operation failed.
+    Error {
+        /// What was being done when the error occurred, for example `attach` or `flash`.
+        context: &'static str,
+        /// A stable identifier of the kind of error, see [`Error::code`].
+        code: &'static str,
+        /// The human readable error message.
+        message: String,
+    },
+}
+
+impl SessionEvent {
+    /// Create an [`SessionEvent::Error`] for `error`, which occurred during `context`.
+    pub fn error(context: &'static str, error: &Error) -> Self {
+        SessionEvent::Error {
+            context,
+            code: error.code(),
+            message: error_message(error),
+        }
+    }
+
+    /// Create an [`SessionEvent::Error`] for a failed flashing procedure.
+    pub fn flash_error(error: &FlashError) -> Self {
+        let code = match error {
+            FlashError::Core(error) => error.code(),
+            _ => "flash",
+        };
+
+        SessionEvent::Error {
+            context: "flash",
+            code,
+            message: error_message(error),
+        }
+    }
+}
+
+/// The phases of the flashing procedure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlashPhase {
+    /// The flash algorithm was loaded and the flash layout determined.
+    Initialize,
+    /// Pages are filled with the existing flash contents.
+    Fill,
+    /// Sectors are erased.
+    Erase,
+    /// Pages are programmed.
+    Program,
+    /// The written data is read back and compared.
+    Verify,
+}
+
+/// The status of a [`FlashPhase`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhaseStatus {
+    /// The phase started.
+    Started,
+    /// The phase finished successfully.
+    Finished,
+    /// The phase failed.
+    Failed,
+}
+
+/// A receiver of [`SessionEvent`]s.
+///
+/// Events are emitted from the thread performing the operation,
+/// so `emit` should return quickly.
+pub trait EventSink: Send + Sync {
+    /// Handle a single event.
+    fn emit(&self, event: &SessionEvent);
+}
+
+impl<F: Fn(&SessionEvent) + Send + Sync> EventSink for F {
+    fn emit(&self, event: &SessionEvent) {
+        self(event)
+    }
+}
+
+/// An [`EventSink`] which writes newline delimited JSON to a writer.
+///
+/// Each line is a JSON object with the fields of the event, a `timestamp_ms` field with the
+/// milliseconds since the UNIX epoch, and an `event` field with the kind of the event.
+pub struct NdjsonEventSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> NdjsonEventSink<W> {
+    /// Create a sink which writes to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp_ms: u128,
+    #[serde(flatten)]
+    event: &'a SessionEvent,
+}
+
+impl<W: Write + Send> EventSink for NdjsonEventSink<W> {
+    fn emit(&self, event: &SessionEvent) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or_default();
+
+        let mut line = match serde_json::to_vec(&Record {
+            timestamp_ms,
+            event,
+        }) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Failed to serialize session event: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(e) = writer.write_all(&line).and_then(|_| writer.flush()) {
+            tracing::warn!("Failed to write session event: {}", e);
+        }
+    }
+}
+
+/// Install `sink` as the receiver of all session events of this process.
+///
+/// A previously installed sink is replaced.
+pub fn set_event_sink(sink: impl EventSink + 'static) {
+    *EVENT_SINK.write().unwrap() = Some(Arc::new(sink));
+}
+
+/// Remove the installed event sink, if any.
+pub fn clear_event_sink() {
+    *EVENT_SINK.write().unwrap() = None;
+}
+
+/// Send `event` to the installed sink.
+///
+/// The event is only created if a sink is installed.
+pub(crate) fn emit(event: impl FnOnce() -> SessionEvent) {
+    let sink = EVENT_SINK.read().unwrap().clone();
+
+    if let Some(sink) = sink {
+        sink.emit(&event());
+    }
+}
+
+/// The error message including all its sources, as the top level messages are often generic.
+fn error_message(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        message.push_str(": ");
+        message.push_str(&error.to_string());
+        source = error.source();
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventSink, FlashPhase, NdjsonEventSink, PhaseStatus, SessionEvent};
+    use crate::Error;
+
+    fn written_lines(events: &[SessionEvent]) -> Vec<serde_json::Value> {
+        let sink = NdjsonEventSink::new(Vec::new());
+        for event in events {
+            sink.emit(event);
+        }
+
+        let output = sink.writer.into_inner().unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn events_are_written_as_lines() {
+        let lines = written_lines(&[
+            SessionEvent::Flash {
+                phase: FlashPhase::Erase,
+                status: PhaseStatus::Started,
+            },
+            SessionEvent::Reset {
+                core: 0,
+                halted: true,
+            },
+        ]);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "flash");
+        assert_eq!(lines[0]["phase"], "erase");
+        assert_eq!(lines[0]["status"], "started");
+        assert!(lines[0]["timestamp_ms"].is_u64());
+        assert_eq!(lines[1]["event"], "reset");
+        assert_eq!(lines[1]["halted"], true);
+    }
+
+    #[test]
+    fn errors_include_code_and_sources() {
+        let error = Error::Arm(crate::architecture::arm::ArmError::Timeout);
+
+        let lines = written_lines(&[SessionEvent::error("attach", &error)]);
+
+        assert_eq!(lines[0]["event"], "error");
+        assert_eq!(lines[0]["context"], "attach");
+        assert_eq!(lines[0]["code"], "arm");
+        assert_eq!(
+            lines[0]["message"],
+            "An ARM specific error occurred.: Timeout occurred during operation."
+        );
+    }
+}
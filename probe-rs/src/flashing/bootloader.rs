@@ -0,0 +1,436 @@
+//! Programming through the bootloader of a chip instead of its debug port.
+
+use std::io::{ErrorKind, Read, Write};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// A connection to the ROM bootloader of a chip, which can program its flash without debug
+/// access, for example when the debug port is disabled or its pins are reconfigured.
+///
+/// The data of a [`FlashLoader`](super::FlashLoader) is programmed through a bootloader with
+/// [`FlashLoader::commit_bootloader`](super::FlashLoader::commit_bootloader).
+pub trait BootloaderTransport {
+    /// The largest number of bytes which can be written or read with one call.
+    fn max_transfer_size(&self) -> usize;
+
+    /// Erase the flash of the `ranges`. Implementations might erase more, up to the whole flash.
+    fn erase(&mut self, ranges: &[Range<u64>]) -> Result<(), BootloaderError>;
+
+    /// Write at most [`max_transfer_size`](Self::max_transfer_size) bytes of `data` to the
+    /// erased flash at `address`.
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), BootloaderError>;
+
+    /// Read at most [`max_transfer_size`](Self::max_transfer_size) bytes of memory at
+    /// `address` into `data`.
+    fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), BootloaderError>;
+
+    /// Leave the bootloader and start the application at `address`.
+    fn go(&mut self, address: u64) -> Result<(), BootloaderError>;
+}
+
+/// An error in the communication with a bootloader.
+#[derive(thiserror::Error, Debug)]
+pub enum BootloaderError {
+    /// The connection to the bootloader failed.
+    #[error("The connection to the bootloader failed.")]
+    Io(#[from] std::io::Error),
+    /// The bootloader did not respond in time.
+    #[error("The bootloader did not respond within {0:?}.")]
+    Timeout(Duration),
+    /// The bootloader rejected a command or its arguments.
+    #[error("The bootloader rejected command {0:#04x}.")]
+    Rejected(u8),
+    /// The bootloader sent an unexpected response.
+    #[error("The bootloader sent the unexpected response {0:#04x}.")]
+    UnexpectedResponse(u8),
+    /// The bootloader does not support a command which is required for an operation.
+    #[error("The bootloader does not support the command {0:#04x}.")]
+    UnsupportedCommand(u8),
+    /// The address is outside of the 32-bit address space of the bootloader.
+    #[error("The address {0:#010x} cannot be accessed through the bootloader.")]
+    InvalidAddress(u64),
+}
+
+const ACK: u8 = 0x79;
+const NACK: u8 = 0x1F;
+const SYNC: u8 = 0x7F;
+
+const CMD_GET: u8 = 0x00;
+const CMD_GET_ID: u8 = 0x02;
+const CMD_READ_MEMORY: u8 = 0x11;
+const CMD_GO: u8 = 0x21;
+const CMD_WRITE_MEMORY: u8 = 0x31;
+const CMD_ERASE: u8 = 0x43;
+const CMD_EXTENDED_ERASE: u8 = 0x44;
+
+/// The time a response to a command can take, except for erasing.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+/// The time erasing can take, which is the mass erase of a large flash in the worst case.
+const ERASE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The most bytes which can be transferred with one read or write command.
+const MAX_TRANSFER_SIZE: usize = 256;
+/// The most pages which are erased with one erase command.
+const MAX_ERASE_PAGES: usize = 256;
+
+/// The system bootloader of STM32 chips on a USART, as described in ST application note
+/// AN3155.
+///
+/// The bootloader is usually started by resetting the chip with the BOOT0 pin high. `port` is
+/// the serial connection to the USART, for example the UART channel of an FTDI probe, which
+/// has to be configured with 8 data bits, even parity and one stop bit, at most 115200 baud,
+/// and a read timeout.
+pub struct Stm32UartBootloader<T: Read + Write> {
+    port: T,
+    version: u8,
+    commands: Vec<u8>,
+    product_id: u16,
+    pages: Option<(u64, u64)>,
+}
+
+impl<T: Read + Write> Stm32UartBootloader<T> {
+    /// Connect to the bootloader on `port`, and read its version and supported commands.
+    pub fn connect(port: T) -> Result<Self, BootloaderError> {
+        let mut bootloader = Self {
+            port,
+            version: 0,
+            commands: Vec::new(),
+            product_id: 0,
+            pages: None,
+        };
+
+        // The bootloader detects the baud rate from the first byte. If it was detected before,
+        // for example by a previous connection, it rejects the byte.
+        bootloader.port.write_all(&[SYNC])?;
+        match bootloader.read_byte(RESPONSE_TIMEOUT)? {
+            ACK | NACK => {}
+            other => return Err(BootloaderError::UnexpectedResponse(other)),
+        }
+
+        bootloader.send_command(CMD_GET)?;
+        let len = bootloader.read_byte(RESPONSE_TIMEOUT)? as usize + 1;
+        let mut response = vec![0; len];
+        bootloader.read_exact(&mut response)?;
+        bootloader.wait_ack(CMD_GET, RESPONSE_TIMEOUT)?;
+        bootloader.version = response[0];
+        bootloader.commands = response[1..].to_vec();
+
+        bootloader.send_command(CMD_GET_ID)?;
+        let len = bootloader.read_byte(RESPONSE_TIMEOUT)? as usize + 1;
+        let mut id = vec![0; len];
+        bootloader.read_exact(&mut id)?;
+        bootloader.wait_ack(CMD_GET_ID, RESPONSE_TIMEOUT)?;
+        bootloader.product_id = id
+            .iter()
+            .fold(0, |product_id, &byte| product_id << 8 | byte as u16);
+
+        Ok(bootloader)
+    }
+
+    /// Erase only the pages which contain data, instead of the whole flash, for a flash
+    /// starting at `flash_base` with pages of `page_size` bytes.
+    #[must_use]
+    pub fn with_pages(mut self, flash_base: u64, page_size: u64) -> Self {
+        self.pages = Some((flash_base, page_size));
+        self
+    }
+
+    /// The version of the bootloader protocol, for example `0x31` for version 3.1.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The product ID of the chip, for example `0x0413` for the STM32F405.
+    pub fn product_id(&self) -> u16 {
+        self.product_id
+    }
+
+    /// Return the serial connection, dropping the bootloader.
+    pub fn into_inner(self) -> T {
+        self.port
+    }
+
+    fn supports(&self, command: u8) -> bool {
+        self.commands.contains(&command)
+    }
+
+    fn send_command(&mut self, command: u8) -> Result<(), BootloaderError> {
+        if command != CMD_GET && !self.supports(command) {
+            return Err(BootloaderError::UnsupportedCommand(command));
+        }
+
+        self.port.write_all(&[command, !command])?;
+        self.wait_ack(command, RESPONSE_TIMEOUT)
+    }
+
+    /// Send `data` followed by its XOR checksum.
+    fn send_with_checksum(&mut self, data: &[u8]) -> Result<(), BootloaderError> {
+        let checksum = data.iter().fold(0, |checksum, byte| checksum ^ byte);
+        self.port.write_all(data)?;
+        self.port.write_all(&[checksum])?;
+        Ok(())
+    }
+
+    fn send_address(&mut self, command: u8, address: u32) -> Result<(), BootloaderError> {
+        self.send_with_checksum(&address.to_be_bytes())?;
+        self.wait_ack(command, RESPONSE_TIMEOUT)
+    }
+
+    fn wait_ack(&mut self, command: u8, timeout: Duration) -> Result<(), BootloaderError> {
+        match self.read_byte(timeout)? {
+            ACK => Ok(()),
+            NACK => Err(BootloaderError::Rejected(command)),
+            other => Err(BootloaderError::UnexpectedResponse(other)),
+        }
+    }
+
+    fn read_byte(&mut self, timeout: Duration) -> Result<u8, BootloaderError> {
+        let start = Instant::now();
+        let mut byte = [0];
+
+        loop {
+            match self.port.read(&mut byte) {
+                Ok(1) => return Ok(byte[0]),
+                Ok(_) => {}
+                Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {}
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            if start.elapsed() > timeout {
+                return Err(BootloaderError::Timeout(timeout));
+            }
+        }
+    }
+
+    fn read_exact(&mut self, data: &mut [u8]) -> Result<(), BootloaderError> {
+        for byte in data {
+            *byte = self.read_byte(RESPONSE_TIMEOUT)?;
+        }
+        Ok(())
+    }
+
+    /// The numbers of the pages which contain the `ranges`.
+    fn pages_of(&self, ranges: &[Range<u64>]) -> Option<Vec<u16>> {
+        let (base, page_size) = self.pages?;
+
+        let mut pages = ranges
+            .iter()
+            .filter(|range| !range.is_empty())
+            .flat_map(|range| {
+                let first = range.start.saturating_sub(base) / page_size;
+                let last = (range.end - 1).saturating_sub(base) / page_size;
+                first..=last
+            })
+            .map(|page| page as u16)
+            .collect::<Vec<_>>();
+        pages.sort_unstable();
+        pages.dedup();
+
+        Some(pages)
+    }
+}
+
+/// The 32-bit address of the bootloader protocol.
+fn bootloader_address(address: u64) -> Result<u32, BootloaderError> {
+    u32::try_from(address).map_err(|_| BootloaderError::InvalidAddress(address))
+}
+
+impl<T: Read + Write> BootloaderTransport for Stm32UartBootloader<T> {
+    fn max_transfer_size(&self) -> usize {
+        MAX_TRANSFER_SIZE
+    }
+
+    fn erase(&mut self, ranges: &[Range<u64>]) -> Result<(), BootloaderError> {
+        let extended = self.supports(CMD_EXTENDED_ERASE);
+        let command = if extended {
+            CMD_EXTENDED_ERASE
+        } else {
+            CMD_ERASE
+        };
+
+        let Some(pages) = self.pages_of(ranges) else {
+            self.send_command(command)?;
+            if extended {
+                self.send_with_checksum(&[0xFF, 0xFF])?;
+            } else {
+                self.port.write_all(&[0xFF, 0x00])?;
+            }
+            return self.wait_ack(command, ERASE_TIMEOUT);
+        };
+
+        for pages in pages.chunks(MAX_ERASE_PAGES) {
+            let count = pages.len() as u16 - 1;
+            let mut data = Vec::new();
+            if extended {
+                data.extend_from_slice(&count.to_be_bytes());
+                data.extend(pages.iter().flat_map(|page| page.to_be_bytes()));
+            } else {
+                data.push(count as u8);
+                data.extend(pages.iter().map(|&page| page as u8));
+            }
+
+            self.send_command(command)?;
+            self.send_with_checksum(&data)?;
+            self.wait_ack(command, ERASE_TIMEOUT)?;
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), BootloaderError> {
+        // The length has to be a multiple of 4, the padding is the erased value of the flash.
+        let mut data = data.to_vec();
+        data.resize((data.len() + 3) & !3, 0xFF);
+        let address = bootloader_address(address)?;
+
+        self.send_command(CMD_WRITE_MEMORY)?;
+        self.send_address(CMD_WRITE_MEMORY, address)?;
+
+        let mut frame = vec![(data.len() - 1) as u8];
+        frame.extend_from_slice(&data);
+        self.send_with_checksum(&frame)?;
+        self.wait_ack(CMD_WRITE_MEMORY, RESPONSE_TIMEOUT)
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), BootloaderError> {
+        let address = bootloader_address(address)?;
+
+        self.send_command(CMD_READ_MEMORY)?;
+        self.send_address(CMD_READ_MEMORY, address)?;
+
+        let count = (data.len() - 1) as u8;
+        self.port.write_all(&[count, !count])?;
+        self.wait_ack(CMD_READ_MEMORY, RESPONSE_TIMEOUT)?;
+
+        self.read_exact(data)
+    }
+
+    fn go(&mut self, address: u64) -> Result<(), BootloaderError> {
+        let address = bootloader_address(address)?;
+
+        self.send_command(CMD_GO)?;
+        self.send_address(CMD_GO, address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BootloaderError, BootloaderTransport, Stm32UartBootloader, ACK, NACK};
+    use std::collections::VecDeque;
+    use std::io::{Read, Write};
+
+    /// A serial port which returns scripted responses, and records what is sent.
+    #[derive(Default)]
+    struct ScriptedPort {
+        responses: VecDeque<u8>,
+        sent: Vec<u8>,
+    }
+
+    impl Read for ScriptedPort {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(self.responses.pop_front().map_or(0, |byte| {
+                buf[0] = byte;
+                1
+            }))
+        }
+    }
+
+    impl Write for ScriptedPort {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.sent.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The responses of a bootloader of version 3.1 with extended erase, for a STM32F405.
+    const CONNECT: &[u8] = &[
+        ACK, // Sync
+        ACK, 7, 0x31, 0x00, 0x02, 0x11, 0x21, 0x31, 0x44, 0x63, ACK, // Get
+        ACK, 1, 0x04, 0x13, ACK, // Get ID
+    ];
+
+    fn connect(responses: &[u8]) -> Stm32UartBootloader<ScriptedPort> {
+        let port = ScriptedPort {
+            responses: CONNECT.iter().chain(responses).copied().collect(),
+            sent: Vec::new(),
+        };
+        Stm32UartBootloader::connect(port).unwrap()
+    }
+
+    fn sent_after_connect(bootloader: Stm32UartBootloader<ScriptedPort>) -> Vec<u8> {
+        // Sync, Get and Get ID.
+        bootloader.into_inner().sent[5..].to_vec()
+    }
+
+    #[test]
+    fn connect_reads_version_and_id() {
+        let bootloader = connect(&[]);
+
+        assert_eq!(bootloader.version(), 0x31);
+        assert_eq!(bootloader.product_id(), 0x0413);
+        assert_eq!(bootloader.into_inner().sent, [0x7F, 0x00, 0xFF, 0x02, 0xFD]);
+    }
+
+    #[test]
+    fn write_is_padded_and_checksummed() {
+        let mut bootloader = connect(&[ACK, ACK, ACK]);
+
+        bootloader.write(0x0800_0000, &[1, 2, 3, 4, 5]).unwrap();
+
+        assert_eq!(
+            sent_after_connect(bootloader),
+            [
+                0x31, 0xCE, // Command
+                0x08, 0x00, 0x00, 0x00, 0x08, // Address and checksum
+                7, 1, 2, 3, 4, 5, 0xFF, 0xFF, 0xFF, 0xF9, // Length, data and checksum
+            ]
+        );
+    }
+
+    #[test]
+    fn read_returns_data() {
+        let mut bootloader = connect(&[ACK, ACK, ACK, 0xAA, 0xBB]);
+
+        let mut data = [0; 2];
+        bootloader.read(0x2000_0000, &mut data).unwrap();
+
+        assert_eq!(data, [0xAA, 0xBB]);
+        assert_eq!(
+            sent_after_connect(bootloader),
+            [0x11, 0xEE, 0x20, 0x00, 0x00, 0x00, 0x20, 0x01, 0xFE]
+        );
+    }
+
+    #[test]
+    fn pages_are_erased_with_extended_erase() {
+        let mut bootloader = connect(&[ACK, ACK]).with_pages(0x0800_0000, 0x800);
+
+        bootloader
+            .erase(&[0x0800_0100..0x0800_0900, 0x0800_1000..0x0800_1004])
+            .unwrap();
+
+        assert_eq!(
+            sent_after_connect(bootloader),
+            [0x44, 0xBB, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x01]
+        );
+    }
+
+    #[test]
+    fn rejected_commands_are_reported() {
+        let mut bootloader = connect(&[ACK, NACK]);
+
+        assert!(matches!(
+            bootloader.go(0x1_0000_0000),
+            Err(BootloaderError::InvalidAddress(0x1_0000_0000))
+        ));
+        assert!(matches!(
+            bootloader.go(0x0800_0000),
+            Err(BootloaderError::Rejected(0x21))
+        ));
+    }
+}
@@ -3,11 +3,12 @@ use std::fmt::{Debug, Formatter};
 use std::ops::Range;
 
 use probe_rs_target::{MemoryRange, NvmRegion, PageInfo};
+use serde::{Deserialize, Serialize};
 
-use super::{FlashAlgorithm, FlashError, FlashVisualizer};
+use super::{FlashAlgorithm, FlashError, FlashVisualizer, GapFill};
 
 /// The description of a page in flash.
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FlashPage {
     address: u64,
     data: Vec<u8>,
@@ -53,7 +54,7 @@ impl FlashPage {
 }
 
 /// The description of a sector in flash.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct FlashSector {
     address: u64,
     size: u64,
@@ -73,7 +74,7 @@ impl FlashSector {
 
 /// A struct to hold all the information about one region
 /// in the flash that is erased during flashing and has to be restored to its original value afterwards.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct FlashFill {
     address: u64,
     size: u64,
@@ -98,7 +99,7 @@ impl FlashFill {
 }
 
 /// The built layout of the data in flash.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FlashLayout {
     sectors: Vec<FlashSector>,
     pages: Vec<FlashPage>,
@@ -122,6 +123,34 @@ impl FlashLayout {
         &mut self.pages
     }
 
+    /// The layout of the sectors at `addresses` and their pages.
+    pub(super) fn select_sectors(&self, addresses: &[u64]) -> FlashLayout {
+        let sectors: Vec<_> = self
+            .sectors
+            .iter()
+            .filter(|sector| addresses.contains(&sector.address))
+            .cloned()
+            .collect();
+
+        let pages = self
+            .pages
+            .iter()
+            .filter(|page| {
+                sectors.iter().any(|sector| {
+                    (sector.address..sector.address + sector.size).contains(&page.address())
+                })
+            })
+            .cloned()
+            .collect();
+
+        FlashLayout {
+            sectors,
+            pages,
+            fills: Vec::new(),
+            data_blocks: self.data_blocks.clone(),
+        }
+    }
+
     /// Get the fills of the flash layout.
     pub fn fills(&self) -> &[FlashFill] {
         &self.fills
@@ -138,7 +167,7 @@ impl FlashLayout {
 }
 
 /// A block of data that is to be written to flash.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FlashDataBlockSpan {
     address: u64,
     size: u64,
@@ -157,7 +186,7 @@ impl FlashDataBlockSpan {
 }
 
 /// A helper structure to build a flash layout from a set of data blocks.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub(super) struct FlashBuilder {
     pub(super) data: BTreeMap<u64, Vec<u8>>,
 }
@@ -213,6 +242,51 @@ impl FlashBuilder {
         Ok(())
     }
 
+    /// Stages a chunk of data, overwriting any previously staged data it overlaps.
+    pub(super) fn patch_data(&mut self, address: u64, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+
+        let end = address + data.len() as u64;
+
+        // Collect all chunks which overlap or touch the new data, they are merged into one chunk.
+        let neighbours: Vec<u64> = self
+            .data
+            .range(..=end)
+            .filter(|(&chunk_address, chunk)| chunk_address + chunk.len() as u64 >= address)
+            .map(|(&chunk_address, _)| chunk_address)
+            .collect();
+
+        let mut start = address;
+        let mut merged_end = end;
+        let mut chunks = Vec::with_capacity(neighbours.len());
+        for chunk_address in neighbours {
+            let chunk = self.data.remove(&chunk_address).unwrap();
+            start = start.min(chunk_address);
+            merged_end = merged_end.max(chunk_address + chunk.len() as u64);
+            chunks.push((chunk_address, chunk));
+        }
+
+        let mut merged = vec![0; (merged_end - start) as usize];
+        for (chunk_address, chunk) in chunks {
+            let offset = (chunk_address - start) as usize;
+            merged[offset..offset + chunk.len()].copy_from_slice(&chunk);
+        }
+
+        let offset = (address - start) as usize;
+        merged[offset..offset + data.len()].copy_from_slice(data);
+
+        self.data.insert(start, merged);
+    }
+
+    /// Check whether `range` in `sector` lies between staged data, i.e. there is data in the
+    /// sector both before and after it.
+    pub(super) fn is_gap(&self, range: &Range<u64>, sector: &Range<u64>) -> bool {
+        self.has_data_in_range(&(sector.start..range.start))
+            && self.has_data_in_range(&(range.end..sector.end))
+    }
+
     /// Check whether there is staged data for a given address range.
     pub(crate) fn has_data_in_range(&self, range: &Range<u64>) -> bool {
         self.data_in_range(range).next().is_some()
@@ -259,25 +333,23 @@ impl FlashBuilder {
     }
 
     /// Layouts the contents of a flash memory according to the contents of the flash loader.
+    ///
+    /// Gaps between data in a sector are filled according to `gap_fill`.
     pub(super) fn build_sectors_and_pages(
         &self,
         region: &NvmRegion,
         flash_algorithm: &FlashAlgorithm,
         include_empty_pages: bool,
+        gap_fill: GapFill,
     ) -> Result<FlashLayout, FlashError> {
         let mut sectors: Vec<FlashSector> = Vec::new();
         let mut pages: Vec<FlashPage> = Vec::new();
         let mut fills: Vec<FlashFill> = Vec::new();
         let mut data_blocks: Vec<FlashDataBlockSpan> = Vec::new();
 
-        for info in flash_algorithm.iter_sectors() {
+        for info in flash_algorithm.region_sectors(region) {
             let range = info.base_address..info.base_address + info.size;
 
-            // Ignore the sector if it's outside the NvmRegion.
-            if !region.range.contains_range(&range) {
-                continue;
-            }
-
             let page = flash_algorithm.page_info(info.base_address).unwrap();
             let page_range = page.base_address..page.base_address + page.size as u64;
             let sector_has_data = self.has_data_in_range(&range);
@@ -303,13 +375,18 @@ impl FlashBuilder {
                 continue;
             }
 
-            let sector = flash_algorithm.sector_info(info.base_address).unwrap();
+            let sector = flash_algorithm
+                .region_sector_info(region, info.base_address)
+                .unwrap();
             let sector_range = sector.base_address..sector.base_address + sector.size;
             let sector_has_data = self.has_data_in_range(&sector_range);
             let page_has_data = self.has_data_in_range(&range);
 
+            // Pages in a gap are included if the gap is not left to the unwritten bytes handling.
+            let page_in_gap = gap_fill != GapFill::Unwritten && self.is_gap(&range, &sector_range);
+
             // If include_empty_pages, include the page if there's data in is sector, even if there's no data in the page.
-            if !page_has_data && (!include_empty_pages || !sector_has_data) {
+            if !page_has_data && !page_in_gap && (!include_empty_pages || !sector_has_data) {
                 continue;
             }
 
@@ -317,6 +394,7 @@ impl FlashBuilder {
                 FlashPage::new(&info, flash_algorithm.flash_properties.erased_byte_value);
 
             let mut fill_start_addr = info.base_address;
+            let mut page_fills = Vec::new();
 
             // Loop over all datablocks in the page.
             for (address, data) in self.data_in_range(&range) {
@@ -326,20 +404,28 @@ impl FlashBuilder {
 
                 // Fill the hole between the previous data block (or page start if there are no blocks) and current block.
                 if address > fill_start_addr {
-                    fills.push(FlashFill {
-                        address: fill_start_addr,
-                        size: address - fill_start_addr,
-                        page_index: pages.len(),
-                    });
+                    page_fills.push(fill_start_addr..address);
                 }
                 fill_start_addr = address + data.len() as u64;
             }
 
             // Fill the hole between the last data block (or page start if there are no blocks) and page end.
             if fill_start_addr < page_end {
+                page_fills.push(fill_start_addr..page_end);
+            }
+
+            for fill in page_fills {
+                if let GapFill::Byte(byte) = gap_fill {
+                    if self.is_gap(&fill, &sector_range) {
+                        let offset = (fill.start - info.base_address) as usize;
+                        let size = (fill.end - fill.start) as usize;
+                        page.data[offset..offset + size].fill(byte);
+                    }
+                }
+
                 fills.push(FlashFill {
-                    address: fill_start_addr,
-                    size: page_end - fill_start_addr,
+                    address: fill.start,
+                    size: fill.end - fill.start,
                     page_index: pages.len(),
                 });
             }
@@ -393,6 +479,9 @@ mod tests {
             is_boot_memory: true,
             range: 0..1 << 16,
             cores: vec!["main".into()],
+            access: Default::default(),
+            sectors: vec![],
+            bank_swap: None,
         };
 
         (region, flash_algorithm)
@@ -421,6 +510,9 @@ mod tests {
             is_boot_memory: true,
             range: 0..1 << 16,
             cores: vec!["main".into()],
+            access: Default::default(),
+            sectors: vec![],
+            bank_swap: None,
         };
 
         (region, flash_algorithm)
@@ -432,7 +524,7 @@ mod tests {
         let mut flash_builder = FlashBuilder::new();
         flash_builder.add_data(0, &[42]).unwrap();
         let flash_layout = flash_builder
-            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .build_sectors_and_pages(&region, &flash_algorithm, true, GapFill::Unwritten)
             .unwrap();
 
         let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
@@ -502,7 +594,7 @@ mod tests {
         let mut flash_builder = FlashBuilder::new();
         flash_builder.add_data(0, &[42; 1024]).unwrap();
         let flash_layout = flash_builder
-            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .build_sectors_and_pages(&region, &flash_algorithm, true, GapFill::Unwritten)
             .unwrap();
 
         let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
@@ -563,7 +655,7 @@ mod tests {
         let mut flash_builder = FlashBuilder::new();
         flash_builder.add_data(0, &[42; 1025]).unwrap();
         let flash_layout = flash_builder
-            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .build_sectors_and_pages(&region, &flash_algorithm, true, GapFill::Unwritten)
             .unwrap();
 
         let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
@@ -628,7 +720,7 @@ mod tests {
         let mut flash_builder = FlashBuilder::new();
         flash_builder.add_data(0, &[42; 1025]).unwrap();
         let flash_layout = flash_builder
-            .build_sectors_and_pages(&region, &flash_algorithm, false)
+            .build_sectors_and_pages(&region, &flash_algorithm, false, GapFill::Unwritten)
             .unwrap();
 
         let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
@@ -673,7 +765,7 @@ mod tests {
         let mut flash_builder = FlashBuilder::new();
         flash_builder.add_data(42, &[42; 1024]).unwrap();
         let flash_layout = flash_builder
-            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .build_sectors_and_pages(&region, &flash_algorithm, true, GapFill::Unwritten)
             .unwrap();
 
         let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
@@ -751,7 +843,7 @@ mod tests {
         let mut flash_builder = FlashBuilder::new();
         flash_builder.add_data(0, &[42; 5024]).unwrap();
         let flash_layout = flash_builder
-            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .build_sectors_and_pages(&region, &flash_algorithm, true, GapFill::Unwritten)
             .unwrap();
 
         let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
@@ -846,7 +938,7 @@ mod tests {
         flash_builder.add_data(0, &[42; 5024]).unwrap();
         flash_builder.add_data(7860, &[42; 5024]).unwrap();
         let flash_layout = flash_builder
-            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .build_sectors_and_pages(&region, &flash_algorithm, true, GapFill::Unwritten)
             .unwrap();
 
         let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
@@ -1019,7 +1111,7 @@ mod tests {
         flash_builder.add_data(0, &[42; 5024]).unwrap();
         flash_builder.add_data(7860, &[42; 5024]).unwrap();
         let flash_layout = flash_builder
-            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .build_sectors_and_pages(&region, &flash_algorithm, true, GapFill::Unwritten)
             .unwrap();
 
         let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
@@ -1139,4 +1231,97 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn patch_data_inside_chunk() {
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0, &[1; 8]).unwrap();
+        flash_builder.patch_data(2, &[2; 2]);
+
+        assert_eq!(flash_builder.data.len(), 1);
+        assert_eq!(flash_builder.data[&0], vec![1, 1, 2, 2, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn patch_data_merges_neighbours() {
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0, &[1; 4]).unwrap();
+        flash_builder.add_data(8, &[3; 4]).unwrap();
+        flash_builder.patch_data(2, &[2; 7]);
+
+        assert_eq!(flash_builder.data.len(), 1);
+        assert_eq!(
+            flash_builder.data[&0],
+            vec![1, 1, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn patch_data_without_overlap() {
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0, &[1; 4]).unwrap();
+        flash_builder.patch_data(16, &[2; 4]);
+
+        assert_eq!(flash_builder.data.len(), 2);
+        assert_eq!(flash_builder.data[&16], vec![2; 4]);
+    }
+
+    #[test]
+    fn gaps_between_data_are_filled_with_byte() {
+        let (region, flash_algorithm) = assemble_demo_flash1();
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0x0000, &[42; 16]).unwrap();
+        flash_builder.add_data(0x0810, &[42; 16]).unwrap();
+        let flash_layout = flash_builder
+            .build_sectors_and_pages(&region, &flash_algorithm, false, GapFill::Byte(0x00))
+            .unwrap();
+
+        let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
+
+        // The page in the gap is programmed with zeros, the end of the sector stays erased.
+        let pages: Vec<_> = flash_layout
+            .pages()
+            .iter()
+            .map(|page| (page.address(), page.data()[0], page.data()[0x3FF]))
+            .collect();
+        assert_eq!(
+            pages,
+            vec![
+                (0x0000, 42, 0x00),
+                (0x0400, 0x00, 0x00),
+                (0x0800, 0x00, erased_byte_value),
+            ]
+        );
+    }
+
+    #[test]
+    fn sector_map_of_region_replaces_algorithm_sectors() {
+        let (mut region, flash_algorithm) = assemble_demo_flash1();
+        region.sectors = vec![
+            SectorDescription {
+                size: 0x2000,
+                address: 0,
+            },
+            SectorDescription {
+                size: 0x8000,
+                address: 0x8000,
+            },
+        ];
+
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0x1000, &[42; 16]).unwrap();
+        flash_builder.add_data(0x9000, &[42; 16]).unwrap();
+        let flash_layout = flash_builder
+            .build_sectors_and_pages(&region, &flash_algorithm, true, GapFill::Unwritten)
+            .unwrap();
+
+        let sectors: Vec<_> = flash_layout
+            .sectors()
+            .iter()
+            .map(|sector| (sector.address(), sector.size()))
+            .collect();
+        assert_eq!(sectors, vec![(0x0000, 0x2000), (0x8000, 0x8000)]);
+        // All pages of the large sector are restored, not only those of the algorithm sector.
+        assert_eq!(flash_layout.pages().len(), 8 + 32);
+    }
 }
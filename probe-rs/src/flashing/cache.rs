@@ -0,0 +1,256 @@
+use object::Object;
+use probe_rs_target::{FlashProperties, MemoryRegion, NvmRegion};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use super::builder::{FlashBuilder, FlashLayout};
+use super::journal::image_hash;
+use super::{FlashAlgorithm, GapFill, MemoryUsageReport};
+
+/// The version of the cache file format. It is part of the file name, so files written by
+/// other versions of probe-rs are ignored.
+const CACHE_VERSION: u32 = 1;
+
+/// Returns the path of the cache file for the ELF file `elf_data` in `directory`, or `None`
+/// if the ELF file has no build ID.
+///
+/// The build ID is written by the linker, for example with `-Wl,--build-id`, and changes with
+/// every change of the image.
+pub(super) fn cache_path(directory: &Path, elf_data: &[u8]) -> Option<PathBuf> {
+    let file = object::File::parse(elf_data).ok()?;
+    let build_id = file.build_id().ok()??;
+
+    let name: String = build_id.iter().map(|byte| format!("{byte:02x}")).collect();
+
+    Some(directory.join(format!("{name}.v{CACHE_VERSION}.bin")))
+}
+
+/// The loadable data, memory usage and flash layouts of an ELF image, cached on the host by the
+/// build ID of the image, see
+/// [`FlashLoader::load_elf_data_cached`](super::FlashLoader::load_elf_data_cached).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct CachedImage {
+    /// The memory map the image was loaded for.
+    pub(super) memory_map: Vec<MemoryRegion>,
+    /// The loadable data of the image, by address.
+    pub(super) data: BTreeMap<u64, Vec<u8>>,
+    /// The memory usage of the image.
+    pub(super) memory_usage: MemoryUsageReport,
+    /// The flash layouts which were computed when the image was programmed.
+    pub(super) layouts: LayoutCache,
+}
+
+impl CachedImage {
+    /// Read the cached image at `path`, or return `None` if there is none for `memory_map`.
+    ///
+    /// A cache file which cannot be read is treated like a missing one.
+    pub(super) fn read(path: &Path, memory_map: &[MemoryRegion]) -> Option<Self> {
+        let contents = fs::read(path).ok()?;
+
+        let image: Self = match bincode::deserialize(&contents) {
+            Ok(image) => image,
+            Err(e) => {
+                tracing::warn!(
+                    "Ignoring the invalid cached image {}: {}",
+                    path.display(),
+                    e
+                );
+                return None;
+            }
+        };
+
+        if image.memory_map != memory_map {
+            tracing::debug!(
+                "The cached image {} was loaded for a different memory map",
+                path.display()
+            );
+            return None;
+        }
+
+        Some(image)
+    }
+
+    /// Write the image to `path`.
+    ///
+    /// The cache only saves time, so a failure is logged, and not returned.
+    pub(super) fn write(&self, path: &Path) {
+        let result = bincode::serialize(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+            .and_then(|contents| {
+                if let Some(directory) = path.parent() {
+                    fs::create_dir_all(directory)?;
+                }
+
+                // Like the journal, the image is written to a temporary file first, so an
+                // interrupted write does not leave a truncated image behind.
+                let mut temporary = path.as_os_str().to_owned();
+                temporary.push(".tmp");
+
+                fs::write(&temporary, contents).and_then(|_| fs::rename(&temporary, path))
+            });
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to cache the image in {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Everything a flash layout depends on, see [`LayoutCache`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct LayoutKey {
+    region: Range<u64>,
+    algorithm: String,
+    flash_properties: FlashProperties,
+    restore_unwritten_bytes: bool,
+    gap_fill: GapFill,
+    /// A hash of the data, which can be patched by pre-programming hooks and checksums.
+    image: u64,
+}
+
+impl LayoutKey {
+    pub(super) fn new(
+        region: &NvmRegion,
+        flash_algorithm: &FlashAlgorithm,
+        flash_builder: &FlashBuilder,
+        restore_unwritten_bytes: bool,
+        gap_fill: GapFill,
+    ) -> Self {
+        Self {
+            region: region.range.clone(),
+            algorithm: flash_algorithm.name.clone(),
+            flash_properties: flash_algorithm.flash_properties.clone(),
+            restore_unwritten_bytes,
+            gap_fill,
+            image: image_hash(flash_builder),
+        }
+    }
+}
+
+/// The flash layouts of a cached image, before the fills were read from the flash.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct LayoutCache {
+    layouts: Vec<(LayoutKey, FlashLayout)>,
+}
+
+impl LayoutCache {
+    pub(super) fn get(&self, key: &LayoutKey) -> Option<&FlashLayout> {
+        self.layouts
+            .iter()
+            .find(|(cached, _)| cached == key)
+            .map(|(_, layout)| layout)
+    }
+
+    pub(super) fn insert(&mut self, key: LayoutKey, layout: FlashLayout) {
+        self.layouts.retain(|(cached, _)| *cached != key);
+        self.layouts.push((key, layout));
+    }
+
+    /// The number of cached layouts.
+    pub(super) fn len(&self) -> usize {
+        self.layouts.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_path, CachedImage, LayoutCache, LayoutKey};
+    use crate::flashing::{FlashAlgorithm, FlashBuilder, GapFill, MemoryUsageReport};
+    use probe_rs_target::{FlashProperties, MemoryRegion, NvmRegion, RamRegion, SectorDescription};
+
+    fn memory_map() -> Vec<MemoryRegion> {
+        vec![
+            MemoryRegion::Nvm(NvmRegion {
+                name: Some("FLASH".to_owned()),
+                range: 0x0800_0000..0x0810_0000,
+                is_boot_memory: true,
+                cores: vec!["main".to_owned()],
+                access: Default::default(),
+                sectors: vec![],
+                bank_swap: None,
+            }),
+            MemoryRegion::Ram(RamRegion {
+                name: Some("RAM".to_owned()),
+                range: 0x2000_0000..0x2002_0000,
+                is_boot_memory: false,
+                cores: vec!["main".to_owned()],
+                access: Default::default(),
+            }),
+        ]
+    }
+
+    #[test]
+    fn cached_image_round_trip() {
+        let elf = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/probe-rs-debugger-test"
+        ))
+        .unwrap();
+        let directory =
+            std::env::temp_dir().join(format!("probe-rs-image-cache-{}", std::process::id()));
+
+        // The test ELF file was linked without a build ID.
+        assert_eq!(cache_path(&directory, &elf), None);
+
+        let flash_algorithm = FlashAlgorithm {
+            flash_properties: FlashProperties {
+                address_range: 0x0800_0000..0x0810_0000,
+                page_size: 0x400,
+                erased_byte_value: 0xFF,
+                sectors: vec![SectorDescription {
+                    size: 0x4000,
+                    address: 0,
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let memory_map = memory_map();
+        let MemoryRegion::Nvm(region) = &memory_map[0] else {
+            unreachable!()
+        };
+
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0x0800_0000, &[0xAA; 0x600]).unwrap();
+        let layout = builder
+            .build_sectors_and_pages(region, &flash_algorithm, false, GapFill::Unwritten)
+            .unwrap();
+        let key = LayoutKey::new(
+            region,
+            &flash_algorithm,
+            &builder,
+            false,
+            GapFill::Unwritten,
+        );
+
+        let mut layouts = LayoutCache::default();
+        layouts.insert(key.clone(), layout.clone());
+        assert_eq!(layouts.get(&key), Some(&layout));
+
+        let patched_key = LayoutKey::new(
+            region,
+            &flash_algorithm,
+            &builder,
+            false,
+            GapFill::Byte(0x00),
+        );
+        assert_eq!(layouts.get(&patched_key), None);
+
+        let image = CachedImage {
+            memory_map: memory_map.clone(),
+            data: builder.data.clone(),
+            memory_usage: MemoryUsageReport::from_elf(&elf, &memory_map).unwrap(),
+            layouts,
+        };
+        let path = directory.join("0123456789abcdef.v1.bin");
+        image.write(&path);
+
+        assert_eq!(CachedImage::read(&path, &memory_map), Some(image));
+        // An image cached for another memory map is not used.
+        assert_eq!(CachedImage::read(&path, &memory_map[..1]), None);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
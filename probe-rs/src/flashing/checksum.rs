@@ -0,0 +1,235 @@
+use probe_rs_target::{ImageChecksum, MemoryRegion};
+
+use super::{FlashError, StagedImage};
+use crate::Target;
+
+/// The checksums `target` needs to boot an image, which are always stamped.
+///
+/// These are the vector table checksums the target description declares, or, if it declares
+/// none, the vector table checksum of the LPC families whose boot ROM checks it.
+pub(super) fn builtin_checksums(target: &Target) -> Vec<ImageChecksum> {
+    let declared = target
+        .image_checksums
+        .iter()
+        .filter(|checksum| matches!(checksum, ImageChecksum::VectorTable { .. }))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if !declared.is_empty() || !target.checks_vector_table {
+        return declared;
+    }
+
+    let address = target
+        .memory_map
+        .iter()
+        .find_map(|region| match region {
+            MemoryRegion::Nvm(region) if region.is_boot_memory => Some(region.range.start),
+            _ => None,
+        })
+        .unwrap_or(0);
+
+    vec![ImageChecksum::VectorTable {
+        address,
+        entries: 7,
+    }]
+}
+
+/// Compute `checksums` over `image` and patch them into it.
+///
+/// Checksums whose start is not part of the image, for example when only a data blob is
+/// flashed, are skipped.
+pub(super) fn stamp_checksums(
+    image: &mut StagedImage<'_>,
+    checksums: &[ImageChecksum],
+) -> Result<(), FlashError> {
+    for checksum in checksums {
+        match *checksum {
+            ImageChecksum::VectorTable { address, entries } => {
+                let mut table = vec![0; entries as usize * 4];
+                if !image.read(address, &mut table) {
+                    tracing::debug!(
+                        "Skipping vector table checksum, the image contains no vector table at {:#010x}",
+                        address
+                    );
+                    continue;
+                }
+
+                let sum = table
+                    .chunks_exact(4)
+                    .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                    .fold(0u32, u32::wrapping_add);
+                let value = 0u32.wrapping_sub(sum);
+
+                tracing::info!("Stamping vector table checksum {:#010x}", value);
+                image.write(address + table.len() as u64, &value.to_le_bytes())?;
+            }
+            ImageChecksum::Crc32 {
+                start,
+                end,
+                crc_address,
+                length_address,
+            } => {
+                if !image.read(start, &mut [0]) {
+                    tracing::debug!(
+                        "Skipping CRC, the image contains no data at {:#010x}",
+                        start
+                    );
+                    continue;
+                }
+
+                let end = end.or_else(|| image_end(image, start)).unwrap_or(start);
+
+                if let Some(length_address) = length_address {
+                    image.write(length_address, &((end - start) as u32).to_le_bytes())?;
+                }
+
+                let mut data = vec![0; (end - start) as usize];
+                if !image.read(start, &mut data) {
+                    return Err(FlashError::IncompleteChecksumRange { start, end });
+                }
+
+                // The CRC field is computed as zero if it is part of the checked range.
+                if (start..end).contains(&crc_address) {
+                    let offset = (crc_address - start) as usize;
+                    let field_end = (offset + 4).min(data.len());
+                    data[offset..field_end].fill(0);
+                }
+
+                let value = crc32fast::hash(&data);
+
+                tracing::info!(
+                    "Stamping CRC {:#010x} over {:#010x}..{:#010x}",
+                    value,
+                    start,
+                    end
+                );
+                image.write(crc_address, &value.to_le_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The end of the staged data in the memory region which contains `address`.
+fn image_end(image: &StagedImage<'_>, address: u64) -> Option<u64> {
+    let region = image.memory_map.iter().find_map(|region| match region {
+        MemoryRegion::Nvm(region) if region.range.contains(&address) => Some(&region.range),
+        MemoryRegion::Ram(region) if region.range.contains(&address) => Some(&region.range),
+        _ => None,
+    })?;
+
+    image
+        .builder
+        .data_in_range(region)
+        .map(|(chunk_address, chunk)| chunk_address + chunk.len() as u64)
+        .last()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builtin_checksums, stamp_checksums};
+    use crate::flashing::{FlashBuilder, StagedImage};
+    use probe_rs_target::{ImageChecksum, MemoryRegion, NvmRegion, TargetDescriptionSource};
+
+    fn stamp(builder: &mut FlashBuilder, checksum: ImageChecksum) {
+        let memory_map = vec![MemoryRegion::Nvm(NvmRegion {
+            name: None,
+            range: 0..0x1_0000,
+            is_boot_memory: true,
+            cores: vec!["main".to_owned()],
+            access: Default::default(),
+            sectors: vec![],
+            bank_swap: None,
+        })];
+
+        stamp_checksums(
+            &mut StagedImage {
+                builder,
+                memory_map: &memory_map,
+                source: &TargetDescriptionSource::BuiltIn,
+            },
+            &[checksum],
+        )
+        .unwrap();
+    }
+
+    fn word(builder: &FlashBuilder, address: u64) -> u32 {
+        let (_, data) = builder
+            .data_in_range(&(address..address + 4))
+            .next()
+            .unwrap();
+        u32::from_le_bytes(data.try_into().unwrap())
+    }
+
+    #[test]
+    fn builtin_vector_table_checksum_for_lpc8xx() {
+        let lpc = crate::config::get_target_by_name("LPC802M001JDH16").unwrap();
+        assert_eq!(
+            builtin_checksums(&lpc),
+            vec![ImageChecksum::VectorTable {
+                address: 0,
+                entries: 7,
+            }]
+        );
+
+        let mut nrf = crate::config::get_target_by_name("nRF52840_xxAA").unwrap();
+        assert!(builtin_checksums(&nrf).is_empty());
+
+        // Declared vector table checksums are always stamped, the other ones only on request.
+        let declared = ImageChecksum::VectorTable {
+            address: 0x1000,
+            entries: 4,
+        };
+        nrf.image_checksums = vec![
+            ImageChecksum::Crc32 {
+                start: 0x1000,
+                end: None,
+                crc_address: 0x2000,
+                length_address: None,
+            },
+            declared.clone(),
+        ];
+        assert_eq!(builtin_checksums(&nrf), vec![declared]);
+    }
+
+    #[test]
+    fn vector_table_sums_to_zero() {
+        let mut builder = FlashBuilder::new();
+        let vectors: Vec<u8> = (1..=8u32).flat_map(|word| word.to_le_bytes()).collect();
+        builder.add_data(0, &vectors).unwrap();
+
+        stamp(
+            &mut builder,
+            ImageChecksum::VectorTable {
+                address: 0,
+                entries: 7,
+            },
+        );
+
+        let sum = (0..8)
+            .map(|i| word(&builder, i * 4))
+            .fold(0u32, u32::wrapping_add);
+        assert_eq!(sum, 0);
+    }
+
+    #[test]
+    fn crc_and_length_of_image() {
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0x1000, &[0; 8]).unwrap();
+        builder.add_data(0x1008, b"123456789").unwrap();
+
+        stamp(
+            &mut builder,
+            ImageChecksum::Crc32 {
+                start: 0x1008,
+                end: None,
+                crc_address: 0x1000,
+                length_address: Some(0x1004),
+            },
+        );
+
+        assert_eq!(word(&builder, 0x1000), 0xCBF4_3926);
+        assert_eq!(word(&builder, 0x1004), 9);
+    }
+}
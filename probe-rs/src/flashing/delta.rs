@@ -0,0 +1,511 @@
+//! Binary diffs of flash sectors, for delta updates through an on-target patcher.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+const TAG_COPY: u8 = 0x01;
+const TAG_FILL: u8 = 0x02;
+const TAG_LITERAL: u8 = 0x03;
+
+const COPY_LEN: usize = 9;
+const FILL_LEN: usize = 6;
+const LITERAL_HEADER_LEN: usize = 5;
+
+/// Copies and fills shorter than this are stored as literal data instead.
+const MIN_MATCH: usize = 12;
+/// The number of bytes used to look up matches in the old contents.
+const HASH_LEN: usize = 8;
+
+/// One operation of a [`DeltaPatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaOp {
+    /// Copy `len` bytes from `offset` in the old contents of the sector.
+    Copy {
+        /// The offset in the old contents.
+        offset: u32,
+        /// The number of bytes.
+        len: u32,
+    },
+    /// Repeat `byte` `len` times.
+    Fill {
+        /// The value of the bytes.
+        byte: u8,
+        /// The number of bytes.
+        len: u32,
+    },
+    /// Bytes which are not contained in the old contents.
+    Literal(Vec<u8>),
+}
+
+impl DeltaOp {
+    fn encoded_len(&self) -> usize {
+        match self {
+            DeltaOp::Copy { .. } => COPY_LEN,
+            DeltaOp::Fill { .. } => FILL_LEN,
+            DeltaOp::Literal(data) => LITERAL_HEADER_LEN + data.len(),
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            DeltaOp::Copy { offset, len } => {
+                out.push(TAG_COPY);
+                out.extend_from_slice(&offset.to_le_bytes());
+                out.extend_from_slice(&len.to_le_bytes());
+            }
+            DeltaOp::Fill { byte, len } => {
+                out.push(TAG_FILL);
+                out.push(*byte);
+                out.extend_from_slice(&len.to_le_bytes());
+            }
+            DeltaOp::Literal(data) => {
+                out.push(TAG_LITERAL);
+                out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                out.extend_from_slice(data);
+            }
+        }
+    }
+}
+
+/// The difference between the old and the new contents of a flash sector.
+///
+/// A patch describes the new contents of one sector in terms of its current contents. It is a
+/// sequence of operations, each starting with a tag byte, with all numbers in little endian:
+///
+/// | Operation | Encoding                                | Output                                    |
+/// |-----------|-----------------------------------------|-------------------------------------------|
+/// | Copy      | `0x01`, `offset: u32`, `len: u32`       | `len` bytes of the old sector at `offset` |
+/// | Fill      | `0x02`, `byte: u8`, `len: u32`          | `len` times `byte`                        |
+/// | Literal   | `0x03`, `len: u32`, `len` bytes of data | The data                                  |
+///
+/// The outputs of the operations, in order, make up the whole new sector. Copy operations
+/// always refer to the old contents of the sector, so the whole sector is decoded into RAM
+/// before it is erased and programmed from there by the flash algorithm.
+///
+/// The patch is transferred in chunks through the page buffer of the flash algorithm, see
+/// [`DeltaPatch::encode_chunks`], and decoded by a small patcher loaded to the RAM after the
+/// flash algorithm.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeltaPatch {
+    ops: Vec<DeltaOp>,
+}
+
+impl DeltaPatch {
+    /// Compute a patch which turns `old` into `new`.
+    ///
+    /// Unchanged parts and parts which moved within the sector become copies, runs of the same
+    /// byte, like erased flash, become fills, and everything else is stored literally.
+    pub fn diff(old: &[u8], new: &[u8]) -> Self {
+        let mut index: HashMap<&[u8], usize> = HashMap::new();
+        for offset in 0..old.len().saturating_sub(HASH_LEN - 1) {
+            index
+                .entry(&old[offset..offset + HASH_LEN])
+                .or_insert(offset);
+        }
+
+        let mut patch = Self::default();
+        let mut literal = Vec::new();
+        let mut position = 0;
+
+        while position < new.len() {
+            let rest = &new[position..];
+            let fill_len = rest.iter().take_while(|&&byte| byte == rest[0]).count();
+
+            // Code which did not move is the most common match, so it is tried first.
+            let mut copy = (
+                position,
+                common_prefix(old.get(position..).unwrap_or(&[]), rest),
+            );
+            if let Some(&offset) = rest.get(..HASH_LEN).and_then(|key| index.get(key)) {
+                let len = common_prefix(&old[offset..], rest);
+                if len > copy.1 {
+                    copy = (offset, len);
+                }
+            }
+
+            let op = if fill_len >= MIN_MATCH && fill_len >= copy.1 {
+                DeltaOp::Fill {
+                    byte: rest[0],
+                    len: fill_len as u32,
+                }
+            } else if copy.1 >= MIN_MATCH {
+                DeltaOp::Copy {
+                    offset: copy.0 as u32,
+                    len: copy.1 as u32,
+                }
+            } else {
+                literal.push(rest[0]);
+                position += 1;
+                continue;
+            };
+
+            if !literal.is_empty() {
+                patch
+                    .ops
+                    .push(DeltaOp::Literal(std::mem::take(&mut literal)));
+            }
+            position += match &op {
+                DeltaOp::Copy { len, .. } | DeltaOp::Fill { len, .. } => *len as usize,
+                DeltaOp::Literal(data) => data.len(),
+            };
+            patch.ops.push(op);
+        }
+
+        if !literal.is_empty() {
+            patch.ops.push(DeltaOp::Literal(literal));
+        }
+
+        patch
+    }
+
+    /// The operations of the patch.
+    pub fn ops(&self) -> &[DeltaOp] {
+        &self.ops
+    }
+
+    /// Returns `true` if the patch only copies the old contents to the same place.
+    pub fn is_identity(&self) -> bool {
+        self.ops
+            .iter()
+            .try_fold(0, |position, op| match op {
+                DeltaOp::Copy { offset, len } if *offset == position => Some(position + len),
+                _ => None,
+            })
+            .is_some()
+    }
+
+    /// The size of the encoded patch in bytes.
+    pub fn encoded_len(&self) -> usize {
+        self.ops.iter().map(DeltaOp::encoded_len).sum()
+    }
+
+    /// Encode the patch.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.encoded_len());
+        for op in &self.ops {
+            op.encode(&mut out);
+        }
+        out
+    }
+
+    /// Encode the patch in chunks of at most `max_len` bytes, which end at operation
+    /// boundaries. Literal data is split over several chunks where necessary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_len` cannot hold the largest operation header.
+    pub fn encode_chunks(&self, max_len: usize) -> Vec<Vec<u8>> {
+        assert!(
+            max_len > COPY_LEN.max(LITERAL_HEADER_LEN),
+            "Patch chunks of {max_len} bytes are too small"
+        );
+
+        let mut chunks = Vec::new();
+        let mut chunk = Vec::new();
+
+        for op in &self.ops {
+            if let DeltaOp::Literal(data) = op {
+                let mut data = &data[..];
+                while !data.is_empty() {
+                    if chunk.len() + LITERAL_HEADER_LEN >= max_len {
+                        chunks.push(std::mem::take(&mut chunk));
+                    }
+                    let len = data.len().min(max_len - chunk.len() - LITERAL_HEADER_LEN);
+                    DeltaOp::Literal(data[..len].to_vec()).encode(&mut chunk);
+                    data = &data[len..];
+                }
+                continue;
+            }
+
+            if chunk.len() + op.encoded_len() > max_len {
+                chunks.push(std::mem::take(&mut chunk));
+            }
+            op.encode(&mut chunk);
+        }
+
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+
+        chunks
+    }
+}
+
+/// Apply an encoded patch to `old`, as the patcher on the target does.
+///
+/// Returns `None` if the patch is malformed or refers to data outside of `old`.
+pub fn apply_patch(old: &[u8], mut patch: &[u8]) -> Option<Vec<u8>> {
+    fn take<'a>(patch: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        let (head, tail) = (patch.get(..len)?, patch.get(len..)?);
+        *patch = tail;
+        Some(head)
+    }
+
+    fn take_u32(patch: &mut &[u8]) -> Option<usize> {
+        let bytes = take(patch, 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+    }
+
+    let mut new = Vec::with_capacity(old.len());
+
+    while let Some(tag) = take(&mut patch, 1) {
+        match tag[0] {
+            TAG_COPY => {
+                let offset = take_u32(&mut patch)?;
+                let len = take_u32(&mut patch)?;
+                new.extend_from_slice(old.get(offset..offset.checked_add(len)?)?);
+            }
+            TAG_FILL => {
+                let byte = take(&mut patch, 1)?[0];
+                let len = take_u32(&mut patch)?;
+                new.resize(new.len() + len, byte);
+            }
+            TAG_LITERAL => {
+                let len = take_u32(&mut patch)?;
+                new.extend_from_slice(take(&mut patch, len)?);
+            }
+            _ => return None,
+        }
+    }
+
+    Some(new)
+}
+
+/// The patcher for Thumb cores, which decodes one chunk of an encoded [`DeltaPatch`].
+///
+/// It only uses ARMv6-M instructions and byte accesses, so it runs on all Cortex-M cores, and
+/// neither the old contents nor the chunk have to be aligned. It is called with the address of
+/// the old contents of the sector in `r0`, the address and the length of the chunk in `r1` and
+/// `r2`, and the address at which the output continues in `r3`. It returns the end of its
+/// output, or 0 if the chunk contains an unknown operation.
+///
+/// ```text
+/// patch:          push  {r4, r5, r6, lr}
+///                 adds  r2, r1, r2        @ The end of the chunk.
+/// next_op:        cmp   r1, r2
+///                 bhs   done
+///                 ldrb  r4, [r1]
+///                 adds  r1, r1, #1
+///                 cmp   r4, #2
+///                 beq   fill
+///                 bhi   literal
+///                 cmp   r4, #1
+///                 bne   malformed
+///                 bl    read_u32          @ Copy
+///                 adds  r4, r0, r5
+///                 bl    read_u32
+/// copy_loop:      cmp   r5, #0
+///                 beq   next_op
+///                 ldrb  r6, [r4]
+///                 strb  r6, [r3]
+///                 adds  r4, r4, #1
+///                 adds  r3, r3, #1
+///                 subs  r5, r5, #1
+///                 b     copy_loop
+/// fill:           ldrb  r4, [r1]
+///                 adds  r1, r1, #1
+///                 bl    read_u32
+/// fill_loop:      cmp   r5, #0
+///                 beq   next_op
+///                 strb  r4, [r3]
+///                 adds  r3, r3, #1
+///                 subs  r5, r5, #1
+///                 b     fill_loop
+/// literal:        cmp   r4, #3
+///                 bne   malformed
+///                 bl    read_u32
+/// literal_loop:   cmp   r5, #0
+///                 beq   next_op
+///                 ldrb  r6, [r1]
+///                 strb  r6, [r3]
+///                 adds  r1, r1, #1
+///                 adds  r3, r3, #1
+///                 subs  r5, r5, #1
+///                 b     literal_loop
+/// malformed:      movs  r3, #0
+/// done:           movs  r0, r3
+///                 pop   {r4, r5, r6, pc}
+/// read_u32:       ldrb  r5, [r1, #3]      @ Reads a little endian word at r1 into r5.
+///                 lsls  r5, r5, #8
+///                 ldrb  r6, [r1, #2]
+///                 orrs  r5, r6
+///                 lsls  r5, r5, #8
+///                 ldrb  r6, [r1, #1]
+///                 orrs  r5, r6
+///                 lsls  r5, r5, #8
+///                 ldrb  r6, [r1]
+///                 orrs  r5, r6
+///                 adds  r1, r1, #4
+///                 bx    lr
+/// ```
+const THUMB_PATCHER: [u8; 122] = [
+    0x70, 0xb5, 0x8a, 0x18, 0x91, 0x42, 0x2a, 0xd2, 0x0c, 0x78, 0x49, 0x1c, 0x02, 0x2c, 0x0f, 0xd0,
+    0x18, 0xd8, 0x01, 0x2c, 0x22, 0xd1, 0x00, 0xf0, 0x24, 0xf8, 0x44, 0x19, 0x00, 0xf0, 0x21, 0xf8,
+    0x00, 0x2d, 0xef, 0xd0, 0x26, 0x78, 0x1e, 0x70, 0x64, 0x1c, 0x5b, 0x1c, 0x6d, 0x1e, 0xf7, 0xe7,
+    0x0c, 0x78, 0x49, 0x1c, 0x00, 0xf0, 0x15, 0xf8, 0x00, 0x2d, 0xe3, 0xd0, 0x1c, 0x70, 0x5b, 0x1c,
+    0x6d, 0x1e, 0xf9, 0xe7, 0x03, 0x2c, 0x09, 0xd1, 0x00, 0xf0, 0x0b, 0xf8, 0x00, 0x2d, 0xd9, 0xd0,
+    0x0e, 0x78, 0x1e, 0x70, 0x49, 0x1c, 0x5b, 0x1c, 0x6d, 0x1e, 0xf7, 0xe7, 0x00, 0x23, 0x18, 0x00,
+    0x70, 0xbd, 0xcd, 0x78, 0x2d, 0x02, 0x8e, 0x78, 0x35, 0x43, 0x2d, 0x02, 0x4e, 0x78, 0x35, 0x43,
+    0x2d, 0x02, 0x0e, 0x78, 0x35, 0x43, 0x09, 0x1d, 0x70, 0x47,
+];
+
+/// The location of the patcher and the buffer it decodes sectors into, in the RAM of the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Patcher {
+    /// The address of the patcher code.
+    pub(super) address: u64,
+    /// The address of the buffer which a sector is decoded into.
+    pub(super) output: u64,
+    /// The size of the output buffer.
+    pub(super) capacity: u64,
+}
+
+impl Patcher {
+    /// Place the patcher and its output buffer in `free_ram`.
+    ///
+    /// Returns `None` if there is no room for an output buffer after the patcher.
+    pub(super) fn place(free_ram: Range<u64>) -> Option<Self> {
+        let address = (free_ram.start + 3) & !3;
+        let output = (address + THUMB_PATCHER.len() as u64 + 3) & !3;
+        let capacity = free_ram
+            .end
+            .checked_sub(output)
+            .filter(|&capacity| capacity > 0)?;
+
+        Some(Self {
+            address,
+            output,
+            capacity,
+        })
+    }
+
+    /// The code of the patcher, which is loaded to [`Patcher::address`].
+    pub(super) fn code(&self) -> &'static [u8] {
+        &THUMB_PATCHER
+    }
+
+    /// Returns `true` if a sector of `size` bytes fits into the output buffer.
+    pub(super) fn fits(&self, size: u64) -> bool {
+        size <= self.capacity
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_patch, DeltaOp, DeltaPatch, Patcher, THUMB_PATCHER};
+
+    /// Pseudo random bytes, which do not repeat like code does.
+    fn firmware(len: usize) -> Vec<u8> {
+        let mut state = 0x853c_49e6_748f_ea9b_u64;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_sector_is_identity() {
+        let old = firmware(4096);
+        let patch = DeltaPatch::diff(&old, &old);
+
+        assert!(patch.is_identity());
+        assert_eq!(
+            patch.ops(),
+            [DeltaOp::Copy {
+                offset: 0,
+                len: 4096
+            }]
+        );
+    }
+
+    #[test]
+    fn inserted_code_is_copied_around() {
+        let old = firmware(4096);
+        // Insert 10 bytes, which shifts the rest of the code, and erase the end.
+        let mut new = old[..1000].to_vec();
+        new.extend_from_slice(&[0xAA; 10]);
+        new.extend_from_slice(&old[1000..3000]);
+        new.resize(old.len(), 0xFF);
+
+        let patch = DeltaPatch::diff(&old, &new);
+        assert!(!patch.is_identity());
+        assert_eq!(
+            patch.ops(),
+            [
+                DeltaOp::Copy {
+                    offset: 0,
+                    len: 1000
+                },
+                DeltaOp::Literal(vec![0xAA; 10]),
+                DeltaOp::Copy {
+                    offset: 1000,
+                    len: 2000
+                },
+                DeltaOp::Fill {
+                    byte: 0xFF,
+                    len: 1086
+                },
+            ]
+        );
+        assert_eq!(
+            apply_patch(&old, &patch.encode()).as_deref(),
+            Some(&new[..])
+        );
+    }
+
+    #[test]
+    fn chunks_end_at_operation_boundaries() {
+        let old = firmware(2048);
+        let mut new = old.clone();
+        new[100..400].copy_from_slice(&firmware(2300)[2000..]);
+
+        let patch = DeltaPatch::diff(&old, &new);
+        let chunks = patch.encode_chunks(64);
+        assert!(chunks.len() > 1);
+
+        let mut applied = Vec::new();
+        let mut offset = 0;
+        for chunk in &chunks {
+            assert!(chunk.len() <= 64);
+            // Every chunk decodes on its own.
+            let output = apply_patch(&old, chunk).unwrap();
+            offset += output.len();
+            applied.extend(output);
+        }
+        assert_eq!(offset, new.len());
+        assert_eq!(applied, new);
+    }
+
+    #[test]
+    fn malformed_patches_are_rejected() {
+        let old = firmware(16);
+
+        assert_eq!(apply_patch(&old, &[0x01, 8, 0, 0, 0, 9, 0, 0, 0]), None);
+        assert_eq!(apply_patch(&old, &[0x03, 4, 0, 0, 0, 1, 2]), None);
+        assert_eq!(apply_patch(&old, &[0x04]), None);
+    }
+
+    #[test]
+    fn patcher_is_placed_after_the_flash_algorithm() {
+        let patcher = Patcher::place(0x2000_1002..0x2000_4000).unwrap();
+
+        assert_eq!(patcher.address, 0x2000_1004);
+        assert_eq!(patcher.output, 0x2000_1080);
+        assert_eq!(patcher.capacity, 0x2f80);
+        assert!(patcher.fits(0x2000));
+        assert!(!patcher.fits(0x4000));
+
+        // No room for the output after the patcher.
+        let end = 0x2000_1000 + THUMB_PATCHER.len() as u64;
+        assert_eq!(Patcher::place(0x2000_1000..end), None);
+        assert_eq!(Patcher::place(0x2000_1000..0x2000_1000), None);
+    }
+}
@@ -3,8 +3,14 @@ use object::{
     Object, ObjectSection,
 };
 use probe_rs_target::MemoryRange;
+use serde::{Deserialize, Serialize};
 
-use std::{fs::File, path::Path, str::FromStr};
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use super::*;
 use crate::session::Session;
@@ -95,6 +101,22 @@ pub enum FileDownloadError {
     /// This is most likely because of a bad linker script.
     #[error("No loadable ELF sections were found.")]
     NoLoadableSegments,
+    /// The memory map of the target has no region with the given name.
+    #[error("No memory region named '{0}' was found.")]
+    NoSuchRegion(String),
+    /// The partition image does not fit into the memory region it should be programmed into.
+    #[error("The partition image ({image_size} bytes) does not fit into region '{region_name}' ({available} bytes available).")]
+    PartitionImageTooLarge {
+        /// The name of the memory region.
+        region_name: String,
+        /// The size of the image in bytes.
+        image_size: u64,
+        /// The number of bytes available in the region after the requested offset.
+        available: u64,
+    },
+    /// The compressed image file could not be decompressed.
+    #[error("Could not decompress the {0} image")]
+    Decompress(Compression, #[source] std::io::Error),
 }
 
 /// Options for downloading a file onto a target chip.
@@ -110,7 +132,7 @@ pub enum FileDownloadError {
 ///
 /// options.verify = true;
 /// ```
-#[derive(Default)]
+#[derive(Default, Clone)]
 #[non_exhaustive]
 pub struct DownloadOptions {
     /// An optional progress reporter which is used if this argument is set to `Some(...)`.
@@ -122,6 +144,15 @@ pub struct DownloadOptions {
     /// instead of the full sector, the excessively erased bytes wont match the contents before the erase which might not be intuitive
     /// to the user or even worse, result in unexpected behavior if those contents contain important data.
     pub keep_unwritten_bytes: bool,
+    /// How gaps between the data of the image inside a flash sector are filled.
+    pub gap_fill: GapFill,
+    /// Compute the checksums which the target description declares, for example a CRC in an
+    /// application header, and patch them into the image before programming.
+    ///
+    /// Vector table checksums, which the target description declares or which the LPC8xx and
+    /// LPC11xx families need, are always patched in.
+    /// This is done after the pre-programming hooks ran.
+    pub stamp_checksums: bool,
     /// Perform a dry run. This prepares everything for flashing, but does not write anything to flash.
     pub dry_run: bool,
     /// If this flag is set to true, probe-rs will try to use the chips built in method to do a full chip erase if one is available.
@@ -135,6 +166,26 @@ pub struct DownloadOptions {
     pub verify: bool,
     /// Disable double buffering when loading flash.
     pub disable_double_buffering: bool,
+    /// Only update the flash sectors whose contents change, instead of erasing and programming
+    /// all sectors of the image.
+    ///
+    /// On Cortex-M cores, changed sectors are sent as a binary diff against their current
+    /// contents, see [`DeltaPatch`], and decoded by a patcher loaded to RAM next to the flash
+    /// algorithm. Sectors for which the diff is not smaller, or which do not fit into the free
+    /// RAM, are programmed as a whole. This is ignored if the chip is erased or erasing is
+    /// skipped.
+    pub delta: bool,
+    /// Hooks which can patch the image before programming and run actions afterwards.
+    pub hooks: DownloadHooks,
+    /// The signature check of the image before it is programmed, see [`ImageVerification`].
+    pub image_verification: ImageVerification,
+    /// A file in which the progress of the download is recorded, see [`DownloadJournal`].
+    pub journal: Option<PathBuf>,
+    /// A file in which the data programmed to flash is recorded, see [`GoldenImage`].
+    pub golden_image: Option<PathBuf>,
+    /// A directory in which ELF files are cached by their build ID, see
+    /// [`FlashLoader::load_elf_data_cached`].
+    pub image_cache: Option<PathBuf>,
 }
 
 impl DownloadOptions {
@@ -144,6 +195,31 @@ impl DownloadOptions {
     }
 }
 
+/// How gaps between the data of an image inside a flash sector, for example between two ELF
+/// sections, are filled.
+///
+/// Some bootloaders interpret the contents of such gaps, for example as metadata of the
+/// application, and need them to have a specific value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GapFill {
+    /// Gaps are handled like all other bytes which are not written, see
+    /// [`DownloadOptions::keep_unwritten_bytes`].
+    #[default]
+    Unwritten,
+    /// Gaps are programmed with the given byte, for example `0xFF` or `0x00`.
+    Byte(u8),
+    /// Gaps keep their contents from before the download.
+    PreserveExisting,
+}
+
+/// Information about a completed download.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct DownloadReport {
+    /// The flash and RAM usage of the image, if it was an ELF file.
+    pub memory_usage: Option<MemoryUsageReport>,
+}
+
 /// Downloads a file of given `format` at `path` to the flash of the target given in `session`.
 ///
 /// This will ensure that memory bounderies are honored and does unlocking, erasing and programming of the flash for you.
@@ -153,7 +229,7 @@ pub fn download_file<P: AsRef<Path>>(
     session: &mut Session,
     path: P,
     format: Format,
-) -> Result<(), FileDownloadError> {
+) -> Result<DownloadReport, FileDownloadError> {
     download_file_with_options(session, path, format, DownloadOptions::default())
 }
 
@@ -161,13 +237,15 @@ pub fn download_file<P: AsRef<Path>>(
 ///
 /// This will ensure that memory bounderies are honored and does unlocking, erasing and programming of the flash for you.
 ///
+/// Files compressed with gzip or zstd are decompressed while loading them, see [`Compression::detect`].
+///
 /// If you are looking for a simple version without many options, have a look at [download_file].
 pub fn download_file_with_options<P: AsRef<Path>>(
     session: &mut Session,
     path: P,
     format: Format,
     options: DownloadOptions,
-) -> Result<(), FileDownloadError> {
+) -> Result<DownloadReport, FileDownloadError> {
     let mut file = match File::open(path.as_ref()) {
         Ok(file) => file,
         Err(e) => return Err(FileDownloadError::IO(e)),
@@ -175,16 +253,123 @@ pub fn download_file_with_options<P: AsRef<Path>>(
 
     let mut loader = session.target().flash_loader();
 
-    match format {
-        Format::Bin(options) => loader.load_bin_data(&mut file, options),
-        Format::Elf => loader.load_elf_data(&mut file),
-        Format::Hex => loader.load_hex_data(&mut file),
-        Format::Idf(options) => loader.load_idf_data(session, &mut file, options),
-    }?;
+    match Compression::detect(path.as_ref(), &mut file)? {
+        Compression::None => load_image(&mut loader, session, &mut file, format, &options)?,
+        compression => {
+            // The binary and hex loaders need to seek, so the image is decompressed into memory.
+            // They keep the whole image in memory anyway.
+            let image = compression.decompress(file)?;
+            load_image(
+                &mut loader,
+                session,
+                &mut Cursor::new(image),
+                format,
+                &options,
+            )?
+        }
+    }
 
     loader
         .commit(session, options)
-        .map_err(FileDownloadError::Flash)
+        .map_err(FileDownloadError::Flash)?;
+
+    Ok(DownloadReport {
+        memory_usage: loader.memory_usage().cloned(),
+    })
+}
+
+fn load_image<T: Read + Seek>(
+    loader: &mut FlashLoader,
+    session: &mut Session,
+    file: &mut T,
+    format: Format,
+    download_options: &DownloadOptions,
+) -> Result<(), FileDownloadError> {
+    match format {
+        Format::Bin(options) => loader.load_bin_data(file, options),
+        Format::Elf => match &download_options.image_cache {
+            Some(directory) => loader.load_elf_data_cached(file, directory),
+            None => loader.load_elf_data(file),
+        },
+        Format::Hex => loader.load_hex_data(file),
+        Format::Idf(options) => loader.load_idf_data(session, file, options),
+    }
+}
+
+/// The compression of an image file, see [`download_file_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// The file is not compressed.
+    None,
+    /// The file is compressed with [gzip](https://www.rfc-editor.org/rfc/rfc1952).
+    Gzip,
+    /// The file is compressed with [Zstandard](https://www.rfc-editor.org/rfc/rfc8878).
+    Zstd,
+}
+
+impl Compression {
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+    const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+    /// Detect the compression of `file` from its first bytes, or from the extension of `path`
+    /// (`.gz` or `.zst`) if the file is too short to tell.
+    ///
+    /// The position of `file` is restored afterwards.
+    pub fn detect<T: Read + Seek>(path: &Path, file: &mut T) -> Result<Self, std::io::Error> {
+        let start = file.stream_position()?;
+        let mut magic = [0; 4];
+        let mut len = 0;
+        while len < magic.len() {
+            match file.read(&mut magic[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        file.seek(std::io::SeekFrom::Start(start))?;
+
+        let header = &magic[..len];
+        if header.starts_with(&Self::GZIP_MAGIC) {
+            return Ok(Self::Gzip);
+        }
+        if header.starts_with(&Self::ZSTD_MAGIC) {
+            return Ok(Self::Zstd);
+        }
+        if len < magic.len() {
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("gz") => return Ok(Self::Gzip),
+                Some("zst") => return Ok(Self::Zstd),
+                _ => {}
+            }
+        }
+
+        Ok(Self::None)
+    }
+
+    /// Decompress all of `source`.
+    pub fn decompress(self, mut source: impl Read) -> Result<Vec<u8>, FileDownloadError> {
+        let mut image = Vec::new();
+        let result = match self {
+            Self::None => source.read_to_end(&mut image),
+            Self::Gzip => flate2::read::GzDecoder::new(source).read_to_end(&mut image),
+            Self::Zstd => ruzstd::StreamingDecoder::new(source)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+                .and_then(|mut decoder| decoder.read_to_end(&mut image)),
+        };
+
+        result
+            .map(|_| image)
+            .map_err(|e| FileDownloadError::Decompress(self, e))
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::None => "uncompressed",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        })
+    }
 }
 
 /// Flash data which was extraced from an ELF file.
@@ -307,7 +492,7 @@ pub(super) fn extract_from_elf<'data>(
 mod tests {
     use std::str::FromStr;
 
-    use super::{BinOptions, Format};
+    use super::{BinOptions, Compression, Format};
 
     #[test]
     fn parse_format() {
@@ -363,4 +548,59 @@ mod tests {
             Err("Format 'asdasdf' is unknown.".to_string())
         );
     }
+
+    #[test]
+    fn detect_compression() {
+        use std::io::Cursor;
+        use std::path::Path;
+
+        let detect = |path: &str, data: &[u8]| {
+            Compression::detect(Path::new(path), &mut Cursor::new(data)).unwrap()
+        };
+
+        assert_eq!(detect("image.bin", &[0x1f, 0x8b, 8, 0]), Compression::Gzip);
+        assert_eq!(
+            detect("image", &[0x28, 0xb5, 0x2f, 0xfd, 0]),
+            Compression::Zstd
+        );
+        assert_eq!(
+            detect("image.gz", &[0x7f, b'E', b'L', b'F']),
+            Compression::None
+        );
+        // Too short for the magic bytes, so the extension decides.
+        assert_eq!(detect("image.zst", &[]), Compression::Zstd);
+        assert_eq!(detect("image.hex", b":"), Compression::None);
+    }
+
+    #[test]
+    fn decompress_gzip() {
+        use flate2::{write::GzEncoder, Compression as Level};
+        use std::io::Write;
+
+        let image = (0..4096u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+        encoder.write_all(&image).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(
+            Compression::Gzip.decompress(&compressed[..]).unwrap(),
+            image
+        );
+        assert!(Compression::Gzip
+            .decompress(&compressed[..compressed.len() / 2])
+            .is_err());
+    }
+
+    #[test]
+    fn decompress_zstd() {
+        // A frame with a single raw block.
+        let compressed = [
+            0x28, 0xb5, 0x2f, 0xfd, 0x20, 5, 0x29, 0x00, 0x00, 1, 2, 3, 4, 5,
+        ];
+
+        assert_eq!(
+            Compression::Zstd.decompress(&compressed[..]).unwrap(),
+            [1, 2, 3, 4, 5]
+        );
+    }
 }
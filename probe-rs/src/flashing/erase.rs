@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use probe_rs_target::{MemoryRange, MemoryRegion, NvmRegion};
+use probe_rs_target::{MemoryRegion, NvmRegion, SectorInfo};
 
 use crate::flashing::{flasher::Flasher, FlashError, FlashLoader};
 use crate::Session;
@@ -31,7 +31,7 @@ pub fn erase_all(session: &mut Session, progress: Option<FlashProgress>) -> Resu
             let core_name = region
                 .cores
                 .first()
-                .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?;
+                .ok_or_else(|| FlashError::NoNvmCoreAccess(Box::new(region.clone())))?;
 
             let entry = algos
                 .entry((algo.name.clone(), core_name.clone()))
@@ -60,17 +60,10 @@ pub fn erase_all(session: &mut Session, progress: Option<FlashProgress>) -> Resu
 
             // loop over all sectors erasing them individually instead.
 
-            let sectors = flasher
-                .flash_algorithm()
-                .iter_sectors()
-                .filter(|info| {
-                    let range = info.base_address..info.base_address + info.size;
-                    regions.iter().any(|r| r.range.contains_range(&range))
-                })
-                .collect::<Vec<_>>();
+            let sectors = erase_addresses(&mut flasher, &regions)?;
 
             flasher.run_erase(|active| {
-                for info in sectors {
+                for (info, erase_address) in sectors {
                     tracing::debug!(
                         "    sector: {:08x}-{:08x} ({} bytes)",
                         info.base_address,
@@ -78,7 +71,7 @@ pub fn erase_all(session: &mut Session, progress: Option<FlashProgress>) -> Resu
                         info.size
                     );
 
-                    active.erase_sector(info.base_address)?;
+                    active.erase_sector(erase_address)?;
                 }
                 Ok(())
             })?;
@@ -117,7 +110,7 @@ pub fn erase_sectors(
             let core_name = region
                 .cores
                 .first()
-                .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?;
+                .ok_or_else(|| FlashError::NoNvmCoreAccess(Box::new(region.clone())))?;
 
             let entry = algos
                 .entry((algo.name.clone(), core_name.clone()))
@@ -138,19 +131,14 @@ pub fn erase_sectors(
         let core_index = session.target().core_index_by_name(&core_name).unwrap();
         let mut flasher = Flasher::new(session, core_index, &algo, progress.clone())?;
 
-        let sectors = flasher
-            .flash_algorithm()
-            .iter_sectors()
+        let sectors = erase_addresses(&mut flasher, &regions)?
+            .into_iter()
             .skip(start_sector)
             .take(sectors)
-            .filter(|info| {
-                let range = info.base_address..info.base_address + info.size;
-                regions.iter().any(|r| r.range.contains_range(&range))
-            })
             .collect::<Vec<_>>();
 
         flasher.run_erase(|active| {
-            for info in sectors {
+            for (info, erase_address) in sectors {
                 tracing::debug!(
                     "    sector: {:08x}-{:08x} ({} bytes)",
                     info.base_address,
@@ -158,7 +146,7 @@ pub fn erase_sectors(
                     info.size
                 );
 
-                active.erase_sector(info.base_address)?;
+                active.erase_sector(erase_address)?;
             }
             Ok(())
         })?;
@@ -166,3 +154,28 @@ pub fn erase_sectors(
 
     Ok(())
 }
+
+/// The sectors of `regions`, with the address at which to erase them.
+///
+/// The erase address differs from the sector address for dual-bank regions with swapped banks.
+fn erase_addresses(
+    flasher: &mut Flasher,
+    regions: &[NvmRegion],
+) -> Result<Vec<(SectorInfo, u64)>, FlashError> {
+    let mut sectors = Vec::new();
+
+    for region in regions {
+        let swapped = flasher.banks_swapped(region)?;
+
+        for info in flasher.flash_algorithm().region_sectors(region) {
+            let erase_address = if swapped {
+                region.other_bank_address(info.base_address)
+            } else {
+                info.base_address
+            };
+            sectors.push((info, erase_address));
+        }
+    }
+
+    Ok(sectors)
+}
@@ -17,6 +17,23 @@ pub enum FlashError {
         /// The source of this target description (was it a built in target or one loaded externally and from what file path?).
         description_source: TargetDescriptionSource,
     },
+    /// The requested memory range is in a flash region which is read-only, for example OTP memory.
+    #[error(
+        "The memory range {start:#010x}..{end:#010x} is in the read-only flash region {} ({:#010x}..{:#010x}).",
+        region_name.as_deref().unwrap_or("unnamed"),
+        region_range.start,
+        region_range.end
+    )]
+    ReadOnlyNvm {
+        /// The start of the requested memory range.
+        start: u64,
+        /// The end of the requested memory range.
+        end: u64,
+        /// The name of the read-only region.
+        region_name: Option<String>,
+        /// The address range of the read-only region.
+        region_range: Range<u64>,
+    },
     /// Erasing the full chip flash failed.
     #[error("Failed to erase the whole chip.")]
     ChipEraseFailed {
@@ -32,6 +49,23 @@ pub enum FlashError {
         #[source]
         source: Box<dyn std::error::Error + 'static + Send + Sync>,
     },
+    /// Patching the given flash sector failed.
+    #[error("Failed to patch flash sector at address {sector_address:#010x}.")]
+    PatchFailed {
+        /// The address of the sector that should have been patched.
+        sector_address: u64,
+        /// The source error of this error.
+        #[source]
+        source: Box<dyn std::error::Error + 'static + Send + Sync>,
+    },
+    /// The patcher did not decode the whole sector.
+    #[error("The patcher decoded {decoded} bytes instead of the {expected} bytes of the sector.")]
+    PatchIncomplete {
+        /// The number of decoded bytes.
+        decoded: u64,
+        /// The size of the sector.
+        expected: u64,
+    },
     /// Writing the given page failed.
     #[error("The page write of the page at address {page_address:#010x} failed.")]
     PageWrite {
@@ -70,7 +104,7 @@ pub enum FlashError {
         /// The address which was not contained in `region`.
         address: u32,
         /// The region which did not contain `address`.
-        region: NvmRegion,
+        region: Box<NvmRegion>,
     },
     /// An error occurred during the interaction with the core.
     #[error("Something during the interaction with the core went wrong")]
@@ -109,13 +143,13 @@ pub enum FlashError {
     #[error("Trying to write flash, but found more than one suitable flash loader algorithim marked as default for {region:?}.")]
     MultipleDefaultFlashLoaderAlgorithms {
         /// The region which matched more than one flash algorithm.
-        region: NvmRegion,
+        region: Box<NvmRegion>,
     },
     /// More than one matching flash algorithm was found for the given memory range and none of them is marked as default.
     #[error("Trying to write flash, but found more than one suitable flash algorithims but none marked as default for {region:?}.")]
     MultipleFlashLoaderAlgorithmsNoDefault {
         /// The region which matched more than one flash algorithm.
-        region: NvmRegion,
+        region: Box<NvmRegion>,
     },
     /// Flash content verification failed.
     #[error("Flash content verification failed.")]
@@ -150,11 +184,50 @@ pub enum FlashError {
     },
     /// No core can access this NVM region.
     #[error("No core can access the NVM region {0:?}.")]
-    NoNvmCoreAccess(NvmRegion),
+    NoNvmCoreAccess(Box<NvmRegion>),
     /// No core can access this RAM region.
     #[error("No core can access the ram region {0:?}.")]
     NoRamCoreAccess(RamRegion),
     /// The register value supplied for this flash algorithm is out of the supported range.
     #[error("The register value {0:08X?} is out of the supported range.")]
     RegisterValueNotSupported(u64),
+    /// A pre-programming hook failed.
+    #[error("A pre-programming hook failed.")]
+    PreProgrammingHook(#[source] Box<dyn std::error::Error + 'static + Send + Sync>),
+    /// A post-programming hook failed.
+    #[error("A post-programming hook failed.")]
+    PostProgrammingHook(#[source] Box<dyn std::error::Error + 'static + Send + Sync>),
+    /// The image has no valid signature, but the policy requires one.
+    #[error("The image is not signed, but only signed images may be programmed.")]
+    UnsignedImage,
+    /// The signature of the image is invalid, or could not be checked.
+    #[error("The signature of the image is invalid.")]
+    InvalidImageSignature(#[source] Box<dyn std::error::Error + 'static + Send + Sync>),
+    /// The download journal could not be read or written.
+    #[error("Failed to access the download journal.")]
+    Journal(#[source] std::io::Error),
+    /// The golden image could not be read or written.
+    #[error("Failed to access the golden image.")]
+    GoldenImage(#[source] std::io::Error),
+    /// The flash contents do not match the golden image.
+    #[error("The flash contents differ from the golden image at {address:#010x}.")]
+    GoldenImageMismatch {
+        /// The first address at which the flash contents differ.
+        address: u64,
+    },
+    /// Programming through the bootloader of the chip failed.
+    #[error("Programming through the bootloader failed.")]
+    Bootloader(#[from] super::BootloaderError),
+    /// The range of an image checksum is not completely covered by the image.
+    #[error("The image checksum over {start:#010x}..{end:#010x} cannot be computed, because the image does not cover the whole range.")]
+    IncompleteChecksumRange {
+        /// The start of the range.
+        start: u64,
+        /// The end of the range.
+        end: u64,
+    },
+    /// The image could not be read from the stream given to
+    /// [`FlashLoader::commit_stream`](crate::flashing::FlashLoader::commit_stream).
+    #[error("Failed to read the image from the stream.")]
+    Stream(#[source] std::io::Error),
 }
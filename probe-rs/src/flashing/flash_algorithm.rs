@@ -1,4 +1,6 @@
-use probe_rs_target::{FlashProperties, PageInfo, RamRegion, RawFlashAlgorithm, SectorInfo};
+use probe_rs_target::{
+    FlashProperties, MemoryRange, NvmRegion, PageInfo, RamRegion, RawFlashAlgorithm, SectorInfo,
+};
 
 use super::FlashError;
 use crate::core::Architecture;
@@ -97,6 +99,32 @@ impl FlashAlgorithm {
         })
     }
 
+    /// The sectors of `region`, from the sector map of the region if it has one, and from the
+    /// flash properties of the algorithm otherwise.
+    pub fn region_sectors(&self, region: &NvmRegion) -> Vec<SectorInfo> {
+        if region.sectors.is_empty() {
+            self.iter_sectors()
+                .filter(|info| {
+                    let range = info.base_address..info.base_address + info.size;
+                    region.range.contains_range(&range)
+                })
+                .collect()
+        } else {
+            region.iter_sectors().collect()
+        }
+    }
+
+    /// The sector of `region` which contains `address`, see [`FlashAlgorithm::region_sectors`].
+    pub fn region_sector_info(&self, region: &NvmRegion, address: u64) -> Option<SectorInfo> {
+        if region.sectors.is_empty() {
+            self.sector_info(address)
+        } else {
+            region
+                .iter_sectors()
+                .find(|info| (info.base_address..info.base_address + info.size).contains(&address))
+        }
+    }
+
     /// Iterate over all the sectors of the flash.
     pub fn iter_sectors(&self) -> impl Iterator<Item = SectorInfo> + '_ {
         let props = &self.flash_properties;
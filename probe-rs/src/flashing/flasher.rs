@@ -1,12 +1,16 @@
 use probe_rs_target::{MemoryRegion, RawFlashAlgorithm};
 use tracing::Level;
 
+use super::cache::{LayoutCache, LayoutKey};
+use super::delta::Patcher;
 use super::{
-    FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashLayout, FlashPage, FlashProgress,
+    DeltaPatch, FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashLayout, FlashPage,
+    FlashProgress, FlashSector, GapFill,
 };
 use crate::config::NvmRegion;
 use crate::memory::MemoryInterface;
 use crate::{core::CoreRegisters, session::Session, Core, InstructionSet};
+use std::ops::Range;
 use std::time::Instant;
 use std::{fmt::Debug, time::Duration};
 
@@ -53,7 +57,11 @@ pub(super) struct Flasher<'session> {
     session: &'session mut Session,
     core_index: usize,
     flash_algorithm: FlashAlgorithm,
+    /// The part of the RAM of the flash algorithm which it does not use.
+    free_ram: Range<u64>,
     progress: FlashProgress,
+    /// The cached flash layouts of the image, if the image is cached.
+    layouts: Option<LayoutCache>,
 }
 
 impl<'session> Flasher<'session> {
@@ -94,11 +102,17 @@ impl<'session> Flasher<'session> {
 
         let flash_algorithm = FlashAlgorithm::assemble_from_raw(raw_flash_algorithm, ram, target)?;
 
+        let algorithm_end = flash_algorithm.page_buffers.last().unwrap()
+            + flash_algorithm.flash_properties.page_size as u64;
+        let free_ram = algorithm_end..ram.range.end;
+
         let mut this = Self {
             session,
             core_index,
             flash_algorithm,
+            free_ram,
             progress: progress.unwrap_or(FlashProgress::new(|_| {})),
+            layouts: None,
         };
 
         this.load()?;
@@ -114,6 +128,16 @@ impl<'session> Flasher<'session> {
         self.flash_algorithm.page_buffers.len() > 1
     }
 
+    /// Use the flash layouts in `layouts` instead of computing them, and add the computed ones.
+    pub(super) fn use_layouts(&mut self, layouts: LayoutCache) {
+        self.layouts = Some(layouts);
+    }
+
+    /// Returns the flash layouts passed to [`Flasher::use_layouts`], with the computed ones.
+    pub(super) fn take_layouts(&mut self) -> Option<LayoutCache> {
+        self.layouts.take()
+    }
+
     fn load(&mut self) -> Result<(), FlashError> {
         tracing::debug!("Initializing the flash algorithm.");
         let algo = &mut self.flash_algorithm;
@@ -200,9 +224,14 @@ impl<'session> Flasher<'session> {
         Ok(flasher)
     }
 
+    /// Erase the whole flash.
+    ///
+    /// The EraseChip routine of the flash algorithm is used if it has one, otherwise the
+    /// chip erase debug sequence of the target, for example an erase through a vendor AP.
     pub(super) fn run_erase_all(&mut self) -> Result<(), FlashError> {
         self.progress.started_erasing();
-        let result = if self.session.has_sequence_erase_all() {
+        let use_sequence = self.flash_algorithm().pc_erase_all.is_none();
+        let result = if use_sequence && self.session.has_sequence_erase_all() {
             fn run(flasher: &mut Flasher) -> Result<(), FlashError> {
                 flasher
                     .session
@@ -266,29 +295,275 @@ impl<'session> Flasher<'session> {
         self.session.has_sequence_erase_all() || self.flash_algorithm().pc_erase_all.is_some()
     }
 
-    /// Program the contents of given `FlashBuilder` to the flash.
+    /// Program the contents of given `FlashBuilder` to the flash, and return the programmed pages.
     ///
     /// If `restore_unwritten_bytes` is `true`, all bytes of a sector,
     /// that are not to be written during flashing will be read from the flash first
-    /// and written again once the sector is erased.
+    /// and written again once the sector is erased. Gaps between the data in a sector
+    /// are filled according to `gap_fill` instead.
     pub(super) fn program(
         &mut self,
         region: &NvmRegion,
         flash_builder: &FlashBuilder,
         restore_unwritten_bytes: bool,
+        gap_fill: GapFill,
         enable_double_buffering: bool,
         skip_erasing: bool,
-    ) -> Result<(), FlashError> {
+    ) -> Result<FlashLayout, FlashError> {
         tracing::debug!("Starting program procedure.");
-        // Convert the list of flash operations into flash sectors and pages.
-        let mut flash_layout = flash_builder.build_sectors_and_pages(
-            region,
-            &self.flash_algorithm,
-            restore_unwritten_bytes,
-        )?;
+        tracing::debug!("Double Buffering enabled: {:?}", enable_double_buffering);
+
+        let flash_layout =
+            self.prepare_layout(region, flash_builder, restore_unwritten_bytes, gap_fill)?;
+
+        // Skip erase if necessary
+        if !skip_erasing {
+            // Erase all necessary sectors
+            self.sector_erase(region, &flash_layout)?;
+        }
+
+        // Flash all necessary pages.
+        if self.double_buffering_supported() && enable_double_buffering {
+            self.program_double_buffer(&flash_layout)?;
+        } else {
+            self.program_simple(&flash_layout)?;
+        };
+
+        Ok(flash_layout)
+    }
+
+    /// Program the contents of given `FlashBuilder` to the flash like [`Flasher::program`], but
+    /// only update the sectors whose contents change, and return the programmed pages.
+    ///
+    /// The current contents of the sectors are read first. Changed sectors are transferred as a
+    /// [`DeltaPatch`] against their current contents where that is smaller than the data, and
+    /// decoded into RAM by a patcher on the target, from where they are programmed. All other
+    /// changed sectors are erased and programmed as a whole.
+    pub(super) fn program_delta(
+        &mut self,
+        region: &NvmRegion,
+        flash_builder: &FlashBuilder,
+        restore_unwritten_bytes: bool,
+        gap_fill: GapFill,
+        enable_double_buffering: bool,
+    ) -> Result<FlashLayout, FlashError> {
+        tracing::debug!("Starting delta program procedure.");
+
+        let flash_layout =
+            self.prepare_layout(region, flash_builder, restore_unwritten_bytes, gap_fill)?;
+
+        let erased_byte = self.flash_algorithm.flash_properties.erased_byte_value;
+        let chunk_size = self.flash_algorithm.flash_properties.page_size as usize;
+        let patcher = self.patcher()?;
+
+        let mut rewritten = Vec::new();
+        let mut patches = Vec::new();
+        let mut unchanged = 0;
+
+        for sector in flash_layout.sectors() {
+            let range = sector.address()..sector.address() + sector.size();
+
+            // Pages which are not part of the layout are erased when the sector is.
+            let mut new = vec![erased_byte; sector.size() as usize];
+            for page in flash_layout.pages() {
+                if range.contains(&page.address()) {
+                    let offset = (page.address() - sector.address()) as usize;
+                    new[offset..offset + page.data().len()].copy_from_slice(page.data());
+                }
+            }
+
+            let mut old = vec![0; new.len()];
+            self.read(sector.address(), &mut old)?;
+
+            if old == new {
+                unchanged += 1;
+                continue;
+            }
+
+            if patcher.is_some_and(|patcher| patcher.fits(sector.size())) {
+                let patch = DeltaPatch::diff(&old, &new);
+                if patch.encoded_len() < new.len() {
+                    patches.push((sector.clone(), patch.encode_chunks(chunk_size)));
+                    continue;
+                }
+            }
+
+            rewritten.push(sector.address());
+        }
+
+        tracing::info!(
+            "Delta update of {} sectors: {} unchanged, {} patched, {} rewritten",
+            flash_layout.sectors().len(),
+            unchanged,
+            patches.len(),
+            rewritten.len()
+        );
+
+        if let Some(patcher) = patcher.filter(|_| !patches.is_empty()) {
+            self.patch_sectors(region, &flash_layout, &patcher, &patches)?;
+        }
+
+        if !rewritten.is_empty() {
+            let rewritten_layout = flash_layout.select_sectors(&rewritten);
+            self.sector_erase(region, &rewritten_layout)?;
+
+            if self.double_buffering_supported() && enable_double_buffering {
+                self.program_double_buffer(&rewritten_layout)?;
+            } else {
+                self.program_simple(&rewritten_layout)?;
+            };
+        }
+
+        Ok(flash_layout)
+    }
+
+    /// Returns where the patcher for delta updates is placed, or `None` if it cannot run on
+    /// the core or there is no RAM left for it.
+    fn patcher(&mut self) -> Result<Option<Patcher>, FlashError> {
+        let mut core = self
+            .session
+            .core(self.core_index)
+            .map_err(FlashError::Core)?;
+
+        if core.instruction_set().map_err(FlashError::Core)? != InstructionSet::Thumb2 {
+            tracing::debug!("The delta patcher only runs on Thumb cores");
+            return Ok(None);
+        }
+
+        let patcher = Patcher::place(self.free_ram.clone());
+        if patcher.is_none() {
+            tracing::debug!("No RAM left for the delta patcher after the flash algorithm");
+        }
+
+        Ok(patcher)
+    }
+
+    /// Decode the encoded patch chunks of the given sectors with the patcher, and erase and
+    /// program each sector from the decoded contents.
+    fn patch_sectors(
+        &mut self,
+        region: &NvmRegion,
+        flash_layout: &FlashLayout,
+        patcher: &Patcher,
+        patches: &[(FlashSector, Vec<Vec<u8>>)],
+    ) -> Result<(), FlashError> {
+        let swapped = self.banks_swapped(region)?;
+
+        self.session
+            .core(self.core_index)
+            .and_then(|mut core| core.write_8(patcher.address, patcher.code()))
+            .map_err(FlashError::Core)?;
+
+        self.progress.started_programming();
+
+        let mut t = std::time::Instant::now();
+        let mut patch_sector = |flasher: &mut Self, sector: &FlashSector, chunks: &[Vec<u8>]| {
+            // The flash controller erases by physical address, the layout uses the mapped one.
+            let erase_address = if swapped {
+                region.other_bank_address(sector.address())
+            } else {
+                sector.address()
+            };
+
+            flasher.run_erase(|active| {
+                active
+                    .apply_patch(patcher, sector, chunks)
+                    .map_err(|error| FlashError::PatchFailed {
+                        sector_address: sector.address(),
+                        source: Box::new(error),
+                    })?;
+
+                active
+                    .erase_sector(erase_address)
+                    .map_err(|e| FlashError::EraseFailed {
+                        sector_address: sector.address(),
+                        source: Box::new(e),
+                    })
+            })?;
+            flasher.progress.sector_erased(sector.size(), t.elapsed());
+            t = std::time::Instant::now();
+
+            let range = sector.address()..sector.address() + sector.size();
+            flasher.run_program(|active| {
+                for page in flash_layout.pages() {
+                    if !range.contains(&page.address()) {
+                        continue;
+                    }
+
+                    let buffer = patcher.output + (page.address() - sector.address());
+                    active
+                        .program_page_from(page.address(), page.size(), buffer)
+                        .map_err(|error| FlashError::PageWrite {
+                            page_address: page.address(),
+                            source: Box::new(error),
+                        })?;
+                    active.progress.page_programmed(page.size(), t.elapsed());
+
+                    t = std::time::Instant::now();
+                }
+                Ok(())
+            })
+        };
+
+        let result = patches
+            .iter()
+            .try_for_each(|(sector, chunks)| patch_sector(self, sector, chunks));
+
+        if result.is_ok() {
+            self.progress.finished_programming();
+        } else {
+            self.progress.failed_programming();
+        }
+
+        result
+    }
+
+    /// Lay out the contents of given `FlashBuilder` in sectors and pages, and read the bytes
+    /// which are restored or preserved from the flash.
+    fn prepare_layout(
+        &mut self,
+        region: &NvmRegion,
+        flash_builder: &FlashBuilder,
+        restore_unwritten_bytes: bool,
+        gap_fill: GapFill,
+    ) -> Result<FlashLayout, FlashError> {
+        let key = self.layouts.as_ref().map(|_| {
+            LayoutKey::new(
+                region,
+                &self.flash_algorithm,
+                flash_builder,
+                restore_unwritten_bytes,
+                gap_fill,
+            )
+        });
+        let cached = key
+            .as_ref()
+            .zip(self.layouts.as_ref())
+            .and_then(|(key, layouts)| layouts.get(key).cloned());
+
+        let mut flash_layout = match cached {
+            Some(flash_layout) => {
+                tracing::debug!("Using the cached flash layout");
+                flash_layout
+            }
+            None => {
+                // Convert the list of flash operations into flash sectors and pages.
+                let flash_layout = flash_builder.build_sectors_and_pages(
+                    region,
+                    &self.flash_algorithm,
+                    restore_unwritten_bytes,
+                    gap_fill,
+                )?;
+
+                if let (Some(key), Some(layouts)) = (key, self.layouts.as_mut()) {
+                    layouts.insert(key, flash_layout.clone());
+                }
+
+                flash_layout
+            }
+        };
         self.progress.initialized(flash_layout.clone());
 
-        tracing::debug!("Double Buffering enabled: {:?}", enable_double_buffering);
         tracing::debug!(
             "Restoring unwritten bytes enabled: {:?}",
             restore_unwritten_bytes
@@ -297,17 +572,36 @@ impl<'session> Flasher<'session> {
         // Read all fill areas from the flash.
         self.progress.started_filling();
 
-        if restore_unwritten_bytes {
+        if restore_unwritten_bytes || gap_fill != GapFill::Unwritten {
             let fills = flash_layout.fills().to_vec();
             for fill in fills {
+                let sector = self
+                    .flash_algorithm
+                    .region_sector_info(region, fill.address())
+                    .unwrap();
+                let sector_range = sector.base_address..sector.base_address + sector.size;
+                let fill_range = fill.address()..fill.address() + fill.size();
+
+                let restore = match gap_fill {
+                    GapFill::Unwritten => restore_unwritten_bytes,
+                    _ if flash_builder.is_gap(&fill_range, &sector_range) => {
+                        gap_fill == GapFill::PreserveExisting
+                    }
+                    _ => restore_unwritten_bytes,
+                };
+
+                if !restore {
+                    continue;
+                }
+
                 let t = std::time::Instant::now();
                 let page = &mut flash_layout.pages_mut()[fill.page_index()];
                 let result = self.fill_page(page, &fill);
 
                 // If we encounter an error, catch it, gracefully report the failure and return the error.
-                if result.is_err() {
+                if let Err(error) = result {
                     self.progress.failed_filling();
-                    return result;
+                    return Err(error);
                 } else {
                     self.progress.page_filled(fill.size(), t.elapsed());
                 }
@@ -317,20 +611,7 @@ impl<'session> Flasher<'session> {
         // We successfully finished filling.
         self.progress.finished_filling();
 
-        // Skip erase if necessary
-        if !skip_erasing {
-            // Erase all necessary sectors
-            self.sector_erase(&flash_layout)?;
-        }
-
-        // Flash all necessary pages.
-        if self.double_buffering_supported() && enable_double_buffering {
-            self.program_double_buffer(&flash_layout)?;
-        } else {
-            self.program_simple(&flash_layout)?;
-        };
-
-        Ok(())
+        Ok(flash_layout)
     }
 
     /// Fills all the bytes of `current_page`.
@@ -353,6 +634,11 @@ impl<'session> Flasher<'session> {
         })
     }
 
+    /// Read the flash at `address`, for example to verify programmed data.
+    pub(super) fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), FlashError> {
+        self.run_verify(|active| active.core.read(address, data).map_err(FlashError::Core))
+    }
+
     /// Programs the pages given in `flash_layout` into the flash.
     fn program_simple(&mut self, flash_layout: &FlashLayout) -> Result<(), FlashError> {
         self.progress.started_programming();
@@ -382,15 +668,55 @@ impl<'session> Flasher<'session> {
         result
     }
 
+    /// Returns `true` if the banks of a dual-bank `region` are currently swapped, see
+    /// [`BankSwap`](probe_rs_target::BankSwap).
+    pub(super) fn banks_swapped(&mut self, region: &NvmRegion) -> Result<bool, FlashError> {
+        let Some(bank_swap) = &region.bank_swap else {
+            return Ok(false);
+        };
+
+        let mut core = self
+            .session
+            .core(self.core_index)
+            .map_err(FlashError::Core)?;
+        let value = core
+            .read_word_32(bank_swap.register)
+            .map_err(FlashError::Core)?;
+        let swapped = bank_swap.is_swapped(value);
+
+        if swapped {
+            tracing::info!(
+                "The banks of {:#010x}..{:#010x} are swapped, erasing the other bank",
+                region.range.start,
+                region.range.end
+            );
+        }
+
+        Ok(swapped)
+    }
+
     /// Perform an erase of all sectors given in `flash_layout`.
-    fn sector_erase(&mut self, flash_layout: &FlashLayout) -> Result<(), FlashError> {
+    fn sector_erase(
+        &mut self,
+        region: &NvmRegion,
+        flash_layout: &FlashLayout,
+    ) -> Result<(), FlashError> {
+        let swapped = self.banks_swapped(region)?;
+
         self.progress.started_erasing();
 
         let mut t = std::time::Instant::now();
         let result = self.run_erase(|active| {
             for sector in flash_layout.sectors() {
+                // The flash controller erases by physical address, the layout uses the mapped one.
+                let erase_address = if swapped {
+                    region.other_bank_address(sector.address())
+                } else {
+                    sector.address()
+                };
+
                 active
-                    .erase_sector(sector.address())
+                    .erase_sector(erase_address)
                     .map_err(|e| FlashError::EraseFailed {
                         sector_address: sector.address(),
                         source: Box::new(e),
@@ -707,6 +1033,8 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
 
         let mut timeout_ocurred = true;
         while start.elapsed() < timeout {
+            crate::cancellation::check().map_err(crate::Error::from)?;
+
             match self.core.status()? {
                 crate::CoreStatus::Halted(_) => {
                     timeout_ocurred = false;
@@ -837,30 +1165,91 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
             Ok(())
         }
     }
+
+    /// Decode the encoded patch `chunks` of `sector` into the output buffer of `patcher`.
+    ///
+    /// The patcher has to be loaded to the RAM already.
+    pub(super) fn apply_patch(
+        &mut self,
+        patcher: &Patcher,
+        sector: &FlashSector,
+        chunks: &[Vec<u8>],
+    ) -> Result<(), FlashError> {
+        tracing::debug!(
+            "Patching sector at address {:#08x} with {} bytes",
+            sector.address(),
+            chunks.iter().map(Vec::len).sum::<usize>()
+        );
+
+        let mut output = into_reg(patcher.output)?;
+
+        for chunk in chunks {
+            // Transfer the chunk to RAM.
+            self.core
+                .write_8(self.flash_algorithm.begin_data, chunk)
+                .map_err(FlashError::Core)?;
+
+            output = self.call_function_and_wait(
+                &Registers {
+                    pc: into_reg(patcher.address)?,
+                    r0: Some(into_reg(sector.address())?),
+                    r1: Some(into_reg(self.flash_algorithm.begin_data)?),
+                    r2: Some(chunk.len() as u32),
+                    r3: Some(output),
+                },
+                true,
+                Duration::from_secs(2),
+            )?;
+
+            if output == 0 {
+                break;
+            }
+        }
+
+        let decoded = u64::from(output).saturating_sub(patcher.output);
+        if decoded != sector.size() {
+            return Err(FlashError::PatchIncomplete {
+                decoded,
+                expected: sector.size(),
+            });
+        }
+
+        Ok(())
+    }
 }
 
 impl<'p> ActiveFlasher<'p, Program> {
     pub(super) fn program_page(&mut self, address: u64, bytes: &[u8]) -> Result<(), FlashError> {
+        // Transfer the bytes to RAM.
+        self.core
+            .write_8(self.flash_algorithm.begin_data, bytes)
+            .map_err(FlashError::Core)?;
+
+        self.program_page_from(address, bytes.len() as u32, self.flash_algorithm.begin_data)
+    }
+
+    /// Program the page at `address` with `size` bytes which are already in RAM at `buffer`.
+    pub(super) fn program_page_from(
+        &mut self,
+        address: u64,
+        size: u32,
+        buffer: u64,
+    ) -> Result<(), FlashError> {
         let t1 = std::time::Instant::now();
 
         tracing::info!(
             "Flashing page at address {:#08x} with size: {}",
             address,
-            bytes.len()
+            size
         );
 
-        // Transfer the bytes to RAM.
-        self.core
-            .write_8(self.flash_algorithm.begin_data, bytes)
-            .map_err(FlashError::Core)?;
-
         let result = self
             .call_function_and_wait(
                 &Registers {
                     pc: into_reg(self.flash_algorithm.pc_program_page)?,
                     r0: Some(into_reg(address)?),
-                    r1: Some(bytes.len() as u32),
-                    r2: Some(into_reg(self.flash_algorithm.begin_data)?),
+                    r1: Some(size),
+                    r2: Some(into_reg(buffer)?),
                     r3: None,
                 },
                 false,
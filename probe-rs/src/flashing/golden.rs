@@ -0,0 +1,194 @@
+use ihex::Record;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::FlashError;
+use crate::{MemoryInterface, Session};
+
+/// The data which was actually programmed to flash by a download.
+///
+/// If [`DownloadOptions::golden_image`](super::DownloadOptions::golden_image) is set, every
+/// flash page is recorded as it was programmed, after unwritten bytes were restored and gaps
+/// were filled, so the image contains the exact flash contents of the programmed pages.
+/// A device can later be checked against the golden image with [`GoldenImage::verify`],
+/// which compares every recorded byte.
+///
+/// The golden image is stored as Intel HEX, so it can also be checked or programmed by other
+/// tools.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GoldenImage {
+    /// Contiguous blocks of data, by start address.
+    blocks: BTreeMap<u64, Vec<u8>>,
+}
+
+impl GoldenImage {
+    /// The number of data bytes in a HEX record.
+    const RECORD_SIZE: usize = 16;
+
+    /// Read the golden image at `path`.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, FlashError> {
+        let contents = fs::read_to_string(path.as_ref()).map_err(FlashError::GoldenImage)?;
+
+        let mut image = Self::default();
+        let mut base_address = 0;
+
+        for record in ihex::Reader::new(&contents) {
+            let record = record.map_err(|e| {
+                FlashError::GoldenImage(io::Error::new(io::ErrorKind::InvalidData, e))
+            })?;
+
+            match record {
+                Record::Data { offset, value } => image.add(base_address + offset as u64, &value),
+                Record::ExtendedLinearAddress(address) => base_address = (address as u64) << 16,
+                Record::ExtendedSegmentAddress(address) => base_address = (address as u64) * 16,
+                Record::EndOfFile
+                | Record::StartSegmentAddress { .. }
+                | Record::StartLinearAddress(_) => (),
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// Write the golden image to `path`.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), FlashError> {
+        let contents = ihex::create_object_file_representation(&self.records())
+            .map_err(|e| FlashError::GoldenImage(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+
+        fs::write(path.as_ref(), contents).map_err(FlashError::GoldenImage)
+    }
+
+    /// The contiguous blocks of data in the image, with their start address.
+    pub fn blocks(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.blocks
+            .iter()
+            .map(|(&address, data)| (address, data.as_slice()))
+    }
+
+    /// Check that the memory of the target in `session` contains the golden image.
+    ///
+    /// Returns [`FlashError::GoldenImageMismatch`] with the first address which differs.
+    pub fn verify(&self, session: &mut Session) -> Result<(), FlashError> {
+        for (&address, data) in &self.blocks {
+            let core_index = session
+                .target()
+                .get_memory_region_by_address(address)
+                .and_then(|region| region.cores().first())
+                .and_then(|core_name| session.target().core_index_by_name(core_name))
+                .ok_or_else(|| FlashError::NoSuitableNvm {
+                    start: address,
+                    end: address + data.len() as u64,
+                    description_source: session.target().source().clone(),
+                })?;
+            let mut core = session.core(core_index).map_err(FlashError::Core)?;
+
+            let mut contents = vec![0; data.len()];
+            core.read(address, &mut contents)
+                .map_err(FlashError::Core)?;
+
+            if let Some(offset) = contents.iter().zip(data).position(|(a, b)| a != b) {
+                return Err(FlashError::GoldenImageMismatch {
+                    address: address + offset as u64,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add `data` at `address`, merging it with adjacent blocks.
+    pub(super) fn add(&mut self, address: u64, data: &[u8]) {
+        if let Some((&start, block)) = self.blocks.range_mut(..=address).next_back() {
+            if start + block.len() as u64 == address {
+                block.extend_from_slice(data);
+                self.merge_following(start);
+                return;
+            }
+        }
+
+        self.blocks.insert(address, data.to_vec());
+        self.merge_following(address);
+    }
+
+    /// Append the block following the block at `start` to it, if they are adjacent.
+    fn merge_following(&mut self, start: u64) {
+        let end = start + self.blocks[&start].len() as u64;
+
+        if let Some(following) = self.blocks.remove(&end) {
+            self.blocks.get_mut(&start).unwrap().extend(following);
+        }
+    }
+
+    fn records(&self) -> Vec<Record> {
+        let mut records = Vec::new();
+        let mut upper_address = None;
+
+        for (&address, data) in &self.blocks {
+            let mut address = address;
+            let mut data = data.as_slice();
+
+            while !data.is_empty() {
+                let upper = (address >> 16) as u16;
+                if upper_address != Some(upper) {
+                    records.push(Record::ExtendedLinearAddress(upper));
+                    upper_address = Some(upper);
+                }
+
+                // Records must not cross a 64 KiB boundary of the linear address.
+                let to_boundary = 0x1_0000 - (address & 0xFFFF) as usize;
+                let len = data.len().min(Self::RECORD_SIZE).min(to_boundary);
+
+                records.push(Record::Data {
+                    offset: address as u16,
+                    value: data[..len].to_vec(),
+                });
+
+                address += len as u64;
+                data = &data[len..];
+            }
+        }
+
+        records.push(Record::EndOfFile);
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GoldenImage;
+
+    #[test]
+    fn adjacent_data_is_merged() {
+        let mut image = GoldenImage::default();
+        image.add(0x0800_0000, &[1, 2]);
+        image.add(0x0800_0004, &[5, 6]);
+        image.add(0x0800_0002, &[3, 4]);
+        image.add(0x0800_1000, &[7]);
+
+        let blocks = image.blocks().collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            vec![
+                (0x0800_0000, &[1, 2, 3, 4, 5, 6][..]),
+                (0x0800_1000, &[7][..])
+            ]
+        );
+    }
+
+    #[test]
+    fn write_and_read() {
+        let path = std::env::temp_dir().join(format!("probe-rs-golden-{}.hex", std::process::id()));
+
+        let mut image = GoldenImage::default();
+        // Crosses a 64 KiB boundary.
+        image.add(0x0800_FFF8, &(0..=255).collect::<Vec<u8>>());
+        image.add(0x2000_0000, &[0xAA; 3]);
+        image.write(&path).unwrap();
+
+        assert_eq!(GoldenImage::read(&path).unwrap(), image);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use probe_rs_target::{MemoryRegion, TargetDescriptionSource};
+use std::sync::Arc;
+
+use super::{FlashBuilder, FlashError, FlashLoader};
+use crate::session::Session;
+
+/// The error type returned by download hooks.
+pub type HookError = Box<dyn std::error::Error + 'static + Send + Sync>;
+
+type PreProgrammingHook = Arc<dyn Fn(&mut StagedImage<'_>) -> Result<(), HookError>>;
+type PostProgrammingHook = Arc<dyn Fn(&mut Session) -> Result<(), HookError>>;
+
+/// User registered hooks which are run by [`FlashLoader::commit`].
+///
+/// Pre-programming hooks can patch the staged image before anything is written to the target,
+/// for example to inject a serial number, stamp a CRC or sign the image.
+/// Post-programming hooks run after programming and verification succeeded,
+/// for example to read back a unique ID or write a provisioning record.
+///
+/// Hooks are run in the order in which they were registered.
+///
+/// # Example
+///
+/// ```
+/// use probe_rs::flashing::{DownloadHooks, DownloadOptions};
+///
+/// let mut options = DownloadOptions::default();
+///
+/// options.hooks.pre_programming(|image| {
+///     image.write(0x0800_0400, &0x1234_5678u32.to_le_bytes())?;
+///     Ok(())
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct DownloadHooks {
+    pre_programming: Vec<PreProgrammingHook>,
+    post_programming: Vec<PostProgrammingHook>,
+}
+
+impl DownloadHooks {
+    /// Create an empty set of hooks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a hook which can patch the image before it is programmed.
+    pub fn pre_programming(
+        &mut self,
+        hook: impl Fn(&mut StagedImage<'_>) -> Result<(), HookError> + 'static,
+    ) -> &mut Self {
+        self.pre_programming.push(Arc::new(hook));
+        self
+    }
+
+    /// Register a hook which is run after the image was programmed.
+    pub fn post_programming(
+        &mut self,
+        hook: impl Fn(&mut Session) -> Result<(), HookError> + 'static,
+    ) -> &mut Self {
+        self.post_programming.push(Arc::new(hook));
+        self
+    }
+
+    pub(super) fn has_pre_programming(&self) -> bool {
+        !self.pre_programming.is_empty()
+    }
+
+    pub(super) fn run_pre_programming(
+        &self,
+        image: &mut StagedImage<'_>,
+    ) -> Result<(), FlashError> {
+        for hook in &self.pre_programming {
+            hook(image).map_err(FlashError::PreProgrammingHook)?;
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn run_post_programming(&self, session: &mut Session) -> Result<(), FlashError> {
+        for hook in &self.post_programming {
+            hook(session).map_err(FlashError::PostProgrammingHook)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for DownloadHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadHooks")
+            .field("pre_programming", &self.pre_programming.len())
+            .field("post_programming", &self.post_programming.len())
+            .finish()
+    }
+}
+
+/// The image which is about to be programmed, as seen by a pre-programming hook.
+pub struct StagedImage<'a> {
+    pub(super) builder: &'a mut FlashBuilder,
+    pub(super) memory_map: &'a [MemoryRegion],
+    pub(super) source: &'a TargetDescriptionSource,
+}
+
+impl StagedImage<'_> {
+    /// Return the staged data chunks as pairs of address and bytes.
+    pub fn data(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.builder
+            .data
+            .iter()
+            .map(|(address, data)| (*address, data.as_slice()))
+    }
+
+    /// Read staged data starting at `address` into `data`.
+    ///
+    /// Returns `false` if the requested range is not completely covered by staged data.
+    pub fn read(&self, address: u64, data: &mut [u8]) -> bool {
+        let range = address..address + data.len() as u64;
+        let mut covered = 0;
+
+        for (chunk_address, chunk) in self.builder.data_in_range(&range) {
+            let offset = (chunk_address - address) as usize;
+            data[offset..offset + chunk.len()].copy_from_slice(chunk);
+            covered += chunk.len();
+        }
+
+        covered == data.len()
+    }
+
+    /// Write `data` to the staged image at `address`, overwriting any data already staged there.
+    ///
+    /// The data has to be covered by the memory map of the target.
+    pub fn write(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        FlashLoader::check_range_in_memory_map(
+            self.memory_map,
+            self.source,
+            address..address + data.len() as u64,
+        )?;
+        self.builder.patch_data(address, data);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use super::builder::FlashBuilder;
+use super::FlashError;
+
+/// The status of a download recorded in a [`DownloadJournal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalStatus {
+    /// The download was started, but did not finish.
+    InProgress,
+    /// The image was programmed and, if requested, verified.
+    Complete,
+}
+
+/// A host-side record of the progress of a flash download.
+///
+/// If [`DownloadOptions::journal`](super::DownloadOptions::journal) is set, the journal file is
+/// written when programming starts, after every programmed flash region, and when the download
+/// is complete. A journal which is still [`JournalStatus::InProgress`] at the start of the next
+/// download means that the previous one was interrupted, for example because the probe was
+/// unplugged, and the device may be half-programmed.
+///
+/// If the interrupted download was for the same chip and image, regions which were completely
+/// programmed are read back, and are only programmed again if they do not match the image.
+///
+/// The journal is stored as JSON, so it can also be checked by other tools:
+///
+/// ```text
+/// {"chip":"nRF52840_xxAA","image":"9c1e5fa2b3d4c6e7","status":"in_progress","programmed_regions":[{"start":0,"end":1048576}]}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadJournal {
+    /// The name of the chip the image is downloaded to.
+    pub chip: String,
+    /// A hash of the addresses and contents of the image.
+    pub image: String,
+    /// The status of the download.
+    pub status: JournalStatus,
+    /// The flash regions which were completely programmed.
+    pub programmed_regions: Vec<Range<u64>>,
+}
+
+impl DownloadJournal {
+    /// Read the journal at `path`, or return `None` if there is no journal.
+    pub fn read(path: impl AsRef<Path>) -> Result<Option<Self>, FlashError> {
+        let contents = match fs::read(path.as_ref()) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(FlashError::Journal(e)),
+        };
+
+        serde_json::from_slice(&contents)
+            .map(Some)
+            .map_err(|e| FlashError::Journal(e.into()))
+    }
+
+    /// Returns `true` if the recorded download was started, but did not complete.
+    pub fn is_interrupted(&self) -> bool {
+        self.status == JournalStatus::InProgress
+    }
+
+    pub(super) fn new(chip: &str, builder: &FlashBuilder) -> Self {
+        Self {
+            chip: chip.to_owned(),
+            image: format!("{:016x}", image_hash(builder)),
+            status: JournalStatus::InProgress,
+            programmed_regions: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if `previous` is an interrupted download of the same image, which can be
+    /// resumed.
+    pub(super) fn can_resume(&self, previous: &DownloadJournal) -> bool {
+        previous.is_interrupted() && previous.chip == self.chip && previous.image == self.image
+    }
+
+    /// Write the journal to `path`.
+    ///
+    /// The journal is written to a temporary file first, so an interruption while writing
+    /// leaves the previous journal in place.
+    pub(super) fn write(&self, path: &Path) -> Result<(), FlashError> {
+        let contents = serde_json::to_vec(self).map_err(|e| FlashError::Journal(e.into()))?;
+
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+
+        fs::write(&temporary, contents)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(FlashError::Journal)
+    }
+}
+
+/// A FNV-1a hash of the addresses and contents of the image, which is stable across builds
+/// of probe-rs.
+pub(super) fn image_hash(builder: &FlashBuilder) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    for (address, data) in &builder.data {
+        for byte in address.to_le_bytes().iter().chain(data) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadJournal, JournalStatus};
+    use crate::flashing::builder::FlashBuilder;
+
+    fn builder(data: &[u8]) -> FlashBuilder {
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0x0800_0000, data).unwrap();
+        builder
+    }
+
+    #[test]
+    fn resume_only_interrupted_download_of_same_image() {
+        let journal = DownloadJournal::new("STM32F429ZITx", &builder(&[1, 2, 3, 4]));
+
+        let mut previous = journal.clone();
+        previous.programmed_regions.push(0x0800_0000..0x0810_0000);
+        assert!(journal.can_resume(&previous));
+
+        let other_image = DownloadJournal::new("STM32F429ZITx", &builder(&[1, 2, 3, 5]));
+        assert!(!other_image.can_resume(&previous));
+
+        previous.status = JournalStatus::Complete;
+        assert!(!journal.can_resume(&previous));
+    }
+
+    #[test]
+    fn write_and_read() {
+        let path = std::env::temp_dir().join(format!("probe-rs-journal-{}", std::process::id()));
+
+        assert_eq!(DownloadJournal::read(&path).unwrap(), None);
+
+        let mut journal = DownloadJournal::new("nRF52840_xxAA", &builder(&[0xAA; 16]));
+        journal.programmed_regions.push(0..0x10_0000);
+        journal.write(&path).unwrap();
+
+        assert_eq!(DownloadJournal::read(&path).unwrap(), Some(journal));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -5,13 +5,19 @@ use probe_rs_target::{
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Instant;
 
 use super::builder::FlashBuilder;
+use super::cache::{cache_path, CachedImage, LayoutCache};
+use super::checksum::{builtin_checksums, stamp_checksums};
 use super::{
-    extract_from_elf, BinOptions, DownloadOptions, FileDownloadError, FlashError, Flasher,
-    IdfOptions,
+    extract_from_elf, BinOptions, BootloaderTransport, DownloadJournal, DownloadOptions,
+    FileDownloadError, FlashError, FlashProgress, Flasher, GoldenImage, IdfOptions, JournalStatus,
+    MemoryUsageReport, SignaturePolicy, StagedImage,
 };
+use crate::events::SessionEvent;
 use crate::memory::MemoryInterface;
 use crate::session::Session;
 use crate::Target;
@@ -29,6 +35,12 @@ pub struct FlashLoader {
     /// Source of the flash description,
     /// used for diagnostics.
     source: TargetDescriptionSource,
+
+    /// The memory usage of the loaded ELF file.
+    memory_usage: Option<MemoryUsageReport>,
+
+    /// The cache file of the loaded ELF file, and its contents.
+    cache: Option<(PathBuf, CachedImage)>,
 }
 
 impl FlashLoader {
@@ -38,22 +50,47 @@ impl FlashLoader {
             memory_map,
             builder: FlashBuilder::new(),
             source,
+            memory_usage: None,
+            cache: None,
         }
     }
 
+    /// The flash and RAM usage of the image, if it was loaded from an ELF file.
+    pub fn memory_usage(&self) -> Option<&MemoryUsageReport> {
+        self.memory_usage.as_ref()
+    }
+
     /// Check the given address range is completely covered by the memory map,
     /// possibly by multiple memory regions.
     fn check_data_in_memory_map(&mut self, range: Range<u64>) -> Result<(), FlashError> {
+        Self::check_range_in_memory_map(&self.memory_map, &self.source, range)
+    }
+
+    /// Check the given address range is completely covered by `memory_map`,
+    /// possibly by multiple memory regions.
+    pub(super) fn check_range_in_memory_map(
+        memory_map: &[MemoryRegion],
+        source: &TargetDescriptionSource,
+        range: Range<u64>,
+    ) -> Result<(), FlashError> {
         let mut address = range.start;
         while address < range.end {
-            match Self::get_region_for_address(&self.memory_map, address) {
+            match Self::get_region_for_address(memory_map, address) {
+                Some(MemoryRegion::Nvm(region)) if region.access.read_only => {
+                    return Err(FlashError::ReadOnlyNvm {
+                        start: range.start,
+                        end: range.end,
+                        region_name: region.name.clone(),
+                        region_range: region.range.clone(),
+                    })
+                }
                 Some(MemoryRegion::Nvm(region)) => address = region.range.end,
                 Some(MemoryRegion::Ram(region)) => address = region.range.end,
                 _ => {
                     return Err(FlashError::NoSuitableNvm {
                         start: range.start,
                         end: range.end,
-                        description_source: self.source.clone(),
+                        description_source: source.clone(),
                     })
                 }
             }
@@ -192,9 +229,65 @@ impl FlashLoader {
         let mut elf_buffer = Vec::new();
         file.read_to_end(&mut elf_buffer)?;
 
+        self.load_elf_buffer(&elf_buffer)
+    }
+
+    /// Prepares the data sections of an ELF file like [`FlashLoader::load_elf_data`], but keeps
+    /// them in a cache in `cache_directory`, keyed by the build ID of the ELF file.
+    ///
+    /// If the same ELF file is loaded again, the data and memory usage are taken from the cache,
+    /// and the flash layouts computed by the previous [`FlashLoader::commit`] are reused, so
+    /// neither the ELF file is parsed nor the layouts are computed again. ELF files without a
+    /// build ID, for example because the linker was not passed `--build-id`, are not cached.
+    pub fn load_elf_data_cached<T: Read>(
+        &mut self,
+        file: &mut T,
+        cache_directory: impl AsRef<Path>,
+    ) -> Result<(), FileDownloadError> {
+        let mut elf_buffer = Vec::new();
+        file.read_to_end(&mut elf_buffer)?;
+
+        let Some(path) = cache_path(cache_directory.as_ref(), &elf_buffer) else {
+            tracing::debug!("The ELF file has no build ID, it is not cached.");
+            return self.load_elf_buffer(&elf_buffer);
+        };
+
+        let image = match CachedImage::read(&path, &self.memory_map) {
+            Some(image) => {
+                tracing::info!("Using the cached image {}", path.display());
+                image
+            }
+            None => {
+                // The image is loaded on its own, so data added before is not cached with it.
+                let mut elf_loader = FlashLoader::new(self.memory_map.clone(), self.source.clone());
+                elf_loader.load_elf_buffer(&elf_buffer)?;
+
+                let image = CachedImage {
+                    memory_map: elf_loader.memory_map,
+                    data: elf_loader.builder.data,
+                    memory_usage: elf_loader
+                        .memory_usage
+                        .expect("The memory usage is computed when an ELF file is loaded"),
+                    layouts: LayoutCache::default(),
+                };
+                image.write(&path);
+                image
+            }
+        };
+
+        for (&address, data) in &image.data {
+            self.add_data(address, data)?;
+        }
+        self.memory_usage = Some(image.memory_usage.clone());
+        self.cache = Some((path, image));
+
+        Ok(())
+    }
+
+    fn load_elf_buffer(&mut self, elf_buffer: &[u8]) -> Result<(), FileDownloadError> {
         let mut extracted_data = Vec::new();
 
-        let num_sections = extract_from_elf(&mut extracted_data, &elf_buffer)?;
+        let num_sections = extract_from_elf(&mut extracted_data, elf_buffer)?;
 
         if num_sections == 0 {
             tracing::warn!("No loadable segments were found in the ELF file.");
@@ -225,6 +318,8 @@ impl FlashLoader {
             self.add_data(data.address.into(), data.data)?;
         }
 
+        self.memory_usage = Some(MemoryUsageReport::from_elf(elf_buffer, &self.memory_map)?);
+
         Ok(())
     }
 
@@ -237,11 +332,75 @@ impl FlashLoader {
         &self,
         session: &mut Session,
         options: DownloadOptions,
+    ) -> Result<(), FlashError> {
+        let result = session
+            .run_pre_flash_hooks()
+            .map_err(FlashError::from)
+            .and_then(|()| self.commit_image(session, options));
+
+        if let Err(error) = &result {
+            crate::events::emit(|| SessionEvent::flash_error(error));
+        }
+
+        result
+    }
+
+    /// Like [`FlashLoader::commit`], but without running the pre-flash hooks of the session.
+    ///
+    /// This is used to program an image in parts, for which the hooks only run once.
+    pub(super) fn commit_part(
+        &self,
+        session: &mut Session,
+        options: DownloadOptions,
+    ) -> Result<(), FlashError> {
+        let result = self.commit_image(session, options);
+
+        if let Err(error) = &result {
+            crate::events::emit(|| SessionEvent::flash_error(error));
+        }
+
+        result
+    }
+
+    fn commit_image(
+        &self,
+        session: &mut Session,
+        options: DownloadOptions,
     ) -> Result<(), FlashError> {
         tracing::debug!("committing FlashLoader!");
 
+        let mut checksums = builtin_checksums(session.target());
+        if options.stamp_checksums {
+            for checksum in &session.target().image_checksums {
+                if !checksums.contains(checksum) {
+                    checksums.push(checksum.clone());
+                }
+            }
+        }
+
+        // Pre-programming hooks and checksums work on a copy, so the staged data stays untouched.
+        let patched_builder;
+        let builder = if options.hooks.has_pre_programming()
+            || !checksums.is_empty()
+            || options.image_verification.is_enabled()
+        {
+            let mut builder = self.builder.clone();
+            let mut image = StagedImage {
+                builder: &mut builder,
+                memory_map: &self.memory_map,
+                source: &self.source,
+            };
+            options.hooks.run_pre_programming(&mut image)?;
+            stamp_checksums(&mut image, &checksums)?;
+            options.image_verification.check(&image)?;
+            patched_builder = builder;
+            &patched_builder
+        } else {
+            &self.builder
+        };
+
         tracing::debug!("Contents of builder:");
-        for (&address, data) in &self.builder.data {
+        for (&address, data) in &builder.data {
             tracing::debug!(
                 "    data: {:08x}-{:08x} ({} bytes)",
                 address,
@@ -263,6 +422,30 @@ impl FlashLoader {
             );
         }
 
+        // Regions which were completely programmed by an interrupted download of the same image.
+        let mut journal = None;
+        let mut resumable_regions = Vec::new();
+
+        if let Some(path) = options.journal.as_deref().filter(|_| !options.dry_run) {
+            let new_journal = DownloadJournal::new(&session.target().name, builder);
+
+            if let Some(previous) = DownloadJournal::read(path)? {
+                if previous.is_interrupted() {
+                    tracing::warn!(
+                        "The previous download to {} was interrupted, the flash may be partially programmed.",
+                        previous.chip
+                    );
+                }
+
+                if new_journal.can_resume(&previous) && !options.do_chip_erase {
+                    resumable_regions = previous.programmed_regions;
+                }
+            }
+
+            new_journal.write(path)?;
+            journal = Some((path, new_journal));
+        }
+
         // Iterate over all memory regions, and program their data.
 
         if self.memory_map != session.target().memory_map {
@@ -292,11 +475,28 @@ impl FlashLoader {
 
                 // If we have no data in this region, ignore it.
                 // This avoids uselessly initializing and deinitializing its flash algorithm.
-                if !self.builder.has_data_in_range(&region.range) {
+                if !builder.has_data_in_range(&region.range) {
                     tracing::debug!("     -- empty, ignoring!");
                     continue;
                 }
 
+                // The golden image records the programmed pages, so no region is skipped.
+                if options.golden_image.is_none()
+                    && resumable_regions.contains(&region.range)
+                    && Self::region_matches(session, builder, region)?
+                {
+                    tracing::info!(
+                        "Region {:08x}-{:08x} was already programmed by the interrupted download, skipping it",
+                        region.range.start,
+                        region.range.end
+                    );
+
+                    if let Some((_, journal)) = &mut journal {
+                        journal.programmed_regions.push(region.range.clone());
+                    }
+                    continue;
+                }
+
                 let algo = Self::get_flash_algorithm_for_region(region, session.target())?;
 
                 let entry = algos
@@ -305,7 +505,7 @@ impl FlashLoader {
                         region
                             .cores
                             .first()
-                            .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?
+                            .ok_or_else(|| FlashError::NoNvmCoreAccess(Box::new(region.clone())))?
                             .clone(),
                     ))
                     .or_default();
@@ -327,6 +527,14 @@ impl FlashLoader {
             return Ok(());
         }
 
+        let mut golden_image = options
+            .golden_image
+            .as_ref()
+            .map(|_| GoldenImage::default());
+
+        let mut layouts = self.cache.as_ref().map(|(_, image)| image.layouts.clone());
+        let cached_layouts = layouts.as_ref().map_or(0, LayoutCache::len);
+
         // Iterate all flash algorithms we need to use.
         for ((algo_name, core_name), regions) in algos {
             tracing::debug!("Flashing ranges for algo: {}", algo_name);
@@ -343,6 +551,10 @@ impl FlashLoader {
                 .unwrap();
             let mut flasher = Flasher::new(session, core, &algo, options.progress.clone())?;
 
+            if let Some(layouts) = layouts.take() {
+                flasher.use_layouts(layouts);
+            }
+
             let mut do_chip_erase = options.do_chip_erase;
 
             // If the flash algo doesn't support erase all, disable chip erase.
@@ -364,6 +576,8 @@ impl FlashLoader {
             }
 
             for region in regions {
+                crate::cancellation::check().map_err(crate::Error::from)?;
+
                 tracing::debug!(
                     "    programming region: {:08x}-{:08x} ({} bytes)",
                     region.range.start,
@@ -372,13 +586,48 @@ impl FlashLoader {
                 );
 
                 // Program the data.
-                flasher.program(
-                    &region,
-                    &self.builder,
-                    options.keep_unwritten_bytes,
-                    do_use_double_buffering,
-                    options.skip_erase || do_chip_erase,
-                )?;
+                let flash_layout = if options.delta && !options.skip_erase && !do_chip_erase {
+                    flasher.program_delta(
+                        &region,
+                        builder,
+                        options.keep_unwritten_bytes,
+                        options.gap_fill,
+                        do_use_double_buffering,
+                    )?
+                } else {
+                    flasher.program(
+                        &region,
+                        builder,
+                        options.keep_unwritten_bytes,
+                        options.gap_fill,
+                        do_use_double_buffering,
+                        options.skip_erase || do_chip_erase,
+                    )?
+                };
+
+                if let Some(golden_image) = &mut golden_image {
+                    for page in flash_layout.pages() {
+                        golden_image.add(page.address(), page.data());
+                    }
+                }
+
+                if let Some((path, journal)) = &mut journal {
+                    journal.programmed_regions.push(region.range.clone());
+                    journal.write(path)?;
+                }
+            }
+
+            layouts = flasher.take_layouts();
+        }
+
+        // Cache the flash layouts which were computed, to reuse them for the next download.
+        if let (Some((path, image)), Some(layouts)) = (&self.cache, layouts) {
+            if layouts.len() > cached_layouts {
+                CachedImage {
+                    layouts,
+                    ..image.clone()
+                }
+                .write(path);
             }
         }
 
@@ -407,7 +656,7 @@ impl FlashLoader {
                 let mut core = session.core(region_core_index).map_err(FlashError::Core)?;
 
                 let mut some = false;
-                for (address, data) in self.builder.data_in_range(&region.range) {
+                for (address, data) in builder.data_in_range(&region.range) {
                     some = true;
                     tracing::debug!(
                         "     -- writing: {:08x}-{:08x} ({} bytes)",
@@ -427,41 +676,104 @@ impl FlashLoader {
 
         if options.verify {
             tracing::debug!("Verifying!");
-            for (&address, data) in &self.builder.data {
-                tracing::debug!(
-                    "    data: {:08x}-{:08x} ({} bytes)",
-                    address,
-                    address + data.len() as u64,
-                    data.len()
-                );
+            let progress = options
+                .progress
+                .clone()
+                .unwrap_or(FlashProgress::new(|_| {}));
+            let size = builder.data.values().map(|data| data.len() as u64).sum();
+            progress.started_verifying(size);
+
+            let result = Self::verify(session, builder, &progress);
+            if result.is_ok() {
+                progress.finished_verifying();
+            } else {
+                progress.failed_verifying();
+            }
+            result?;
+        }
 
-                let associated_region = session
-                    .target()
-                    .get_memory_region_by_address(address)
-                    .unwrap();
-                let core_name = match associated_region {
-                    MemoryRegion::Ram(r) => &r.cores,
-                    MemoryRegion::Generic(r) => &r.cores,
-                    MemoryRegion::Nvm(r) => &r.cores,
-                }
-                .first()
-                .unwrap();
-                let core_index = session.target().core_index_by_name(core_name).unwrap();
-                let mut core = session.core(core_index).map_err(FlashError::Core)?;
+        if let (Some(path), Some(golden_image)) = (&options.golden_image, &golden_image) {
+            golden_image.write(path)?;
+        }
 
-                let mut written_data = vec![0; data.len()];
-                core.read(address, &mut written_data)
-                    .map_err(FlashError::Core)?;
+        if let Some((path, mut journal)) = journal {
+            journal.status = JournalStatus::Complete;
+            journal.write(path)?;
+        }
 
-                if data != &written_data {
-                    return Err(FlashError::Verify);
-                }
+        options.hooks.run_post_programming(session)?;
+
+        Ok(())
+    }
+
+    /// Read back all data in `builder`, and check if it matches what was written.
+    fn verify(
+        session: &mut Session,
+        builder: &FlashBuilder,
+        progress: &FlashProgress,
+    ) -> Result<(), FlashError> {
+        for (&address, data) in &builder.data {
+            tracing::debug!(
+                "    data: {:08x}-{:08x} ({} bytes)",
+                address,
+                address + data.len() as u64,
+                data.len()
+            );
+
+            let associated_region = session
+                .target()
+                .get_memory_region_by_address(address)
+                .unwrap();
+            let core_name = match associated_region {
+                MemoryRegion::Ram(r) => &r.cores,
+                MemoryRegion::Generic(r) => &r.cores,
+                MemoryRegion::Nvm(r) => &r.cores,
+            }
+            .first()
+            .unwrap();
+            let core_index = session.target().core_index_by_name(core_name).unwrap();
+            let mut core = session.core(core_index).map_err(FlashError::Core)?;
+
+            let t = Instant::now();
+            let mut written_data = vec![0; data.len()];
+            core.read(address, &mut written_data)
+                .map_err(FlashError::Core)?;
+
+            if data != &written_data {
+                return Err(FlashError::Verify);
             }
+            progress.data_verified(data.len() as u64, t.elapsed());
         }
 
         Ok(())
     }
 
+    /// Read back the contents of `region`, and check if they match the image in `builder`.
+    fn region_matches(
+        session: &mut Session,
+        builder: &FlashBuilder,
+        region: &NvmRegion,
+    ) -> Result<bool, FlashError> {
+        let core_name = region
+            .cores
+            .first()
+            .ok_or_else(|| FlashError::NoNvmCoreAccess(Box::new(region.clone())))?;
+        let core_index = session.target().core_index_by_name(core_name).unwrap();
+        let mut core = session.core(core_index).map_err(FlashError::Core)?;
+
+        for (address, data) in builder.data_in_range(&region.range) {
+            let mut written_data = vec![0; data.len()];
+            core.read(address, &mut written_data)
+                .map_err(FlashError::Core)?;
+
+            if data != written_data {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
     /// Try to find a flash algorithm for the given NvmRegion.
     /// Errors when:
     /// - there's no algo for the region.
@@ -496,17 +808,225 @@ impl FlashLoader {
 
                 match defaults.len() {
                     0 => Err(FlashError::MultipleFlashLoaderAlgorithmsNoDefault {
-                        region: region.clone(),
+                        region: Box::new(region.clone()),
                     }),
                     1 => Ok(defaults[0]),
                     _ => Err(FlashError::MultipleDefaultFlashLoaderAlgorithms {
-                        region: region.clone(),
+                        region: Box::new(region.clone()),
                     }),
                 }
             }
         }
     }
 
+    /// Writes all the stored data chunks through the bootloader of the chip, instead of a
+    /// debug probe, for targets without working debug access.
+    ///
+    /// The flash containing data is erased first. With `verify`, the written data is read
+    /// back and compared.
+    pub fn commit_bootloader(
+        &self,
+        transport: &mut dyn BootloaderTransport,
+        verify: bool,
+    ) -> Result<(), FlashError> {
+        let nvm_ranges = self
+            .data()
+            .filter(|(address, _)| {
+                matches!(
+                    Self::get_region_for_address(&self.memory_map, *address),
+                    Some(MemoryRegion::Nvm(_))
+                )
+            })
+            .map(|(address, data)| address..address + data.len() as u64)
+            .collect::<Vec<_>>();
+        transport.erase(&nvm_ranges)?;
+
+        let chunk_size = transport.max_transfer_size();
+        for (address, data) in self.data() {
+            for (i, chunk) in data.chunks(chunk_size).enumerate() {
+                transport.write(address + (i * chunk_size) as u64, chunk)?;
+            }
+        }
+
+        if verify {
+            for (address, data) in self.data() {
+                for (i, chunk) in data.chunks(chunk_size).enumerate() {
+                    let mut read_back = vec![0; chunk.len()];
+                    transport.read(address + (i * chunk_size) as u64, &mut read_back)?;
+                    if read_back != chunk {
+                        return Err(FlashError::Verify);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Programs an image read from `stream` to the flash at `address`, without holding the
+    /// whole image in memory, and returns the number of bytes programmed.
+    ///
+    /// The image is programmed in chunks of whole flash sectors, of at least `chunk_size`
+    /// bytes, as soon as a chunk was read, so only one chunk is kept in memory. This is meant
+    /// for very large images, for example of external flash, which are downloaded or
+    /// decompressed while they are programmed. Data added to the loader before is not
+    /// programmed.
+    ///
+    /// The image has to fit into the NVM region at `address`. If the stream is longer, the
+    /// part which fits is programmed before [`FlashError::NoSuitableNvm`] is returned.
+    ///
+    /// The pre-programming hooks, checksums, signature check, journal and golden image of
+    /// `options` need the whole image and are not supported. If the signature policy requires
+    /// signed images, [`FlashError::UnsignedImage`] is returned. Verification compares each chunk after programming it.
+    pub fn commit_stream(
+        &self,
+        session: &mut Session,
+        address: u64,
+        stream: &mut impl Read,
+        chunk_size: usize,
+        options: DownloadOptions,
+    ) -> Result<u64, FlashError> {
+        let result = self.commit_stream_image(session, address, stream, chunk_size, options);
+
+        if let Err(error) = &result {
+            crate::events::emit(|| SessionEvent::flash_error(error));
+        }
+
+        result
+    }
+
+    fn commit_stream_image(
+        &self,
+        session: &mut Session,
+        address: u64,
+        stream: &mut impl Read,
+        chunk_size: usize,
+        options: DownloadOptions,
+    ) -> Result<u64, FlashError> {
+        // The signature covers the whole image, which is never available here.
+        if options.image_verification.policy() == SignaturePolicy::RequireSigned {
+            return Err(FlashError::UnsignedImage);
+        }
+
+        if options.hooks.has_pre_programming()
+            || options.stamp_checksums
+            || options.image_verification.is_enabled()
+            || options.journal.is_some()
+            || options.golden_image.is_some()
+        {
+            tracing::warn!(
+                "Pre-programming hooks, checksums, signature checks, journals and golden images are not supported when programming from a stream, they are ignored."
+            );
+        }
+
+        let region = match Self::get_region_for_address(&self.memory_map, address) {
+            Some(MemoryRegion::Nvm(region)) => region.clone(),
+            _ => {
+                return Err(FlashError::NoSuitableNvm {
+                    start: address,
+                    end: address + 1,
+                    description_source: self.source.clone(),
+                })
+            }
+        };
+        Self::check_range_in_memory_map(&self.memory_map, &self.source, address..address + 1)?;
+
+        let too_long = |end: u64| FlashError::NoSuitableNvm {
+            start: address,
+            end,
+            description_source: self.source.clone(),
+        };
+
+        if options.dry_run {
+            tracing::info!("Skipping programming, dry run!");
+            let len = std::io::copy(stream, &mut std::io::sink()).map_err(FlashError::Stream)?;
+            if address + len > region.range.end {
+                return Err(too_long(address + len));
+            }
+            return Ok(len);
+        }
+
+        session.run_pre_flash_hooks()?;
+
+        let algo = Self::get_flash_algorithm_for_region(&region, session.target())?.clone();
+        let core_index = region
+            .cores
+            .first()
+            .and_then(|core_name| session.target().core_index_by_name(core_name))
+            .ok_or_else(|| FlashError::NoNvmCoreAccess(Box::new(region.clone())))?;
+
+        let mut flasher = Flasher::new(session, core_index, &algo, options.progress.clone())?;
+
+        let do_chip_erase = options.do_chip_erase && flasher.is_chip_erase_supported();
+        if options.do_chip_erase && !do_chip_erase {
+            tracing::warn!("Chip erase was the selected method to erase the sectors but this chip does not support chip erases (yet).");
+            tracing::warn!("A manual sector erase will be performed.");
+        }
+        if do_chip_erase {
+            flasher.run_erase_all()?;
+        }
+
+        let use_double_buffering =
+            flasher.double_buffering_supported() && !options.disable_double_buffering;
+
+        let mut chunk_start = address;
+        loop {
+            crate::cancellation::check().map_err(crate::Error::from)?;
+
+            // Chunks end at a sector boundary, so no sector is erased twice.
+            let chunk_end = flasher
+                .flash_algorithm()
+                .sector_info(chunk_start + chunk_size.max(1) as u64 - 1)
+                .map_or(region.range.end, |sector| sector.base_address + sector.size)
+                .min(region.range.end);
+
+            let mut data = vec![0; (chunk_end - chunk_start) as usize];
+            let len = read_chunk(stream, &mut data).map_err(FlashError::Stream)?;
+            data.truncate(len);
+
+            if data.is_empty() {
+                break;
+            }
+
+            let mut builder = FlashBuilder::new();
+            builder.add_data(chunk_start, &data)?;
+            flasher.program(
+                &region,
+                &builder,
+                options.keep_unwritten_bytes,
+                options.gap_fill,
+                use_double_buffering,
+                options.skip_erase || do_chip_erase,
+            )?;
+
+            if options.verify {
+                let mut written_data = vec![0; data.len()];
+                flasher.read(chunk_start, &mut written_data)?;
+                if data != written_data {
+                    return Err(FlashError::Verify);
+                }
+            }
+
+            chunk_start += len as u64;
+
+            if chunk_start < chunk_end {
+                // The stream ended.
+                break;
+            }
+            if chunk_start == region.range.end {
+                let mut next = [0];
+                if read_chunk(stream, &mut next).map_err(FlashError::Stream)? > 0 {
+                    return Err(too_long(region.range.end + 1));
+                }
+                break;
+            }
+        }
+
+        options.hooks.run_post_programming(session)?;
+
+        Ok(chunk_start - address)
+    }
+
     /// Return data chunks stored in the `FlashLoader` as pairs of address and bytes.
     pub fn data(&self) -> impl Iterator<Item = (u64, &[u8])> {
         self.builder
@@ -515,3 +1035,86 @@ impl FlashLoader {
             .map(|(address, data)| (*address, data.as_slice()))
     }
 }
+
+/// Read from `stream` until `data` is full or the stream ends, and return the number of bytes
+/// read.
+fn read_chunk(stream: &mut impl Read, data: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < data.len() {
+        match stream.read(&mut data[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use probe_rs_target::{MemoryAccess, MemoryRegion, NvmRegion, TargetDescriptionSource};
+
+    use super::{read_chunk, FlashLoader};
+    use crate::flashing::FlashError;
+
+    fn nvm_region(range: std::ops::Range<u64>, read_only: bool) -> MemoryRegion {
+        MemoryRegion::Nvm(NvmRegion {
+            name: Some("otp".into()),
+            range,
+            is_boot_memory: false,
+            cores: vec!["main".into()],
+            access: MemoryAccess {
+                read_only,
+                ..Default::default()
+            },
+            sectors: vec![],
+            bank_swap: None,
+        })
+    }
+
+    #[test]
+    fn chunks_are_read_completely() {
+        /// Returns at most 3 bytes per read, like a slow network stream.
+        struct Trickle<'a>(&'a [u8]);
+
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(3).min(self.0.len());
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let data = (0..20).collect::<Vec<u8>>();
+        let mut stream = Trickle(&data);
+
+        let mut chunk = [0; 8];
+        assert_eq!(read_chunk(&mut stream, &mut chunk).unwrap(), 8);
+        assert_eq!(chunk, data[..8]);
+        assert_eq!(read_chunk(&mut stream, &mut chunk).unwrap(), 8);
+        assert_eq!(read_chunk(&mut stream, &mut chunk).unwrap(), 4);
+        assert_eq!(chunk[..4], data[16..]);
+        assert_eq!(read_chunk(&mut stream, &mut chunk).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_only_nvm_is_rejected() {
+        let memory_map = [
+            nvm_region(0..0x1000, false),
+            nvm_region(0x1000..0x1100, true),
+        ];
+        let source = TargetDescriptionSource::BuiltIn;
+
+        assert!(FlashLoader::check_range_in_memory_map(&memory_map, &source, 0..0x100).is_ok());
+        assert!(matches!(
+            FlashLoader::check_range_in_memory_map(&memory_map, &source, 0xF00..0x1010),
+            Err(FlashError::ReadOnlyNvm {
+                start: 0xF00,
+                end: 0x1010,
+                ..
+            })
+        ));
+    }
+}
@@ -43,23 +43,41 @@
 //!
 //!
 
+mod bootloader;
 mod builder;
+mod cache;
+mod checksum;
+mod delta;
 mod download;
 mod erase;
 mod error;
 mod flash_algorithm;
 mod flasher;
+mod golden;
+mod hooks;
+mod journal;
 mod loader;
+mod partition;
 mod progress;
+mod signing;
+mod usage;
 mod visualizer;
 
 use builder::*;
 use flasher::*;
 
+pub use bootloader::*;
+pub use delta::*;
 pub use download::*;
 pub use erase::*;
 pub use error::*;
 pub use flash_algorithm::*;
+pub use golden::*;
+pub use hooks::*;
+pub use journal::*;
 pub use loader::*;
+pub use partition::*;
 pub use progress::*;
+pub use signing::*;
+pub use usage::*;
 pub use visualizer::*;
@@ -0,0 +1,331 @@
+use probe_rs_target::{MemoryRegion, NvmRegion};
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use super::{
+    DownloadHooks, DownloadOptions, FileDownloadError, FlashError, FlashLoader, FlashProgress,
+    Flasher, ImageVerification, SignaturePolicy,
+};
+use crate::session::Session;
+
+/// Options for programming an opaque partition image, like a littlefs or spiffs blob,
+/// into a named region of the memory map.
+///
+/// The image is programmed in pages of `page_size` bytes, each of which is committed separately.
+/// After every page, the number of bytes programmed so far is reported, which can be passed back
+/// in as `resume_from` to continue an interrupted download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionImageOptions {
+    /// The name of the memory region the image is programmed into.
+    pub region_name: String,
+    /// The offset of the image from the start of the region.
+    pub offset: u64,
+    /// The number of bytes committed at once.
+    pub page_size: u64,
+    /// The number of bytes at the start of the image which were already programmed
+    /// by a previous download and will be skipped.
+    pub resume_from: u64,
+}
+
+impl PartitionImageOptions {
+    /// The default number of bytes committed at once.
+    pub const DEFAULT_PAGE_SIZE: u64 = 64 * 1024;
+
+    /// Options to program an image to the start of the region called `region_name`.
+    pub fn new(region_name: impl Into<String>) -> Self {
+        Self {
+            region_name: region_name.into(),
+            offset: 0,
+            page_size: Self::DEFAULT_PAGE_SIZE,
+            resume_from: 0,
+        }
+    }
+}
+
+/// Programs the opaque image read from `image` into the memory region named in `options`.
+///
+/// `keep_unwritten_bytes` is always enabled, so pages which share an erase sector do not destroy
+/// each other. If `do_chip_erase` is set, the chip is erased once before the first page, unless
+/// the download is resumed. The pre-flash hooks of the session and the post-programming hooks run
+/// once for the whole image, and each page is verified after it was programmed.
+///
+/// The pre-programming hooks, checksums, signature check, journal and golden image of
+/// `download_options` need the whole image and are not supported. If the signature policy
+/// requires signed images, [`FlashError::UnsignedImage`] is returned.
+///
+/// `on_page_programmed` is called with the number of image bytes programmed so far after each page.
+/// On success, the total size of the image is returned.
+pub fn download_partition_image<T: Read + Seek>(
+    session: &mut Session,
+    image: &mut T,
+    options: &PartitionImageOptions,
+    download_options: &DownloadOptions,
+    mut on_page_programmed: impl FnMut(u64),
+) -> Result<u64, FileDownloadError> {
+    // The signature covers the whole image, which is never staged at once.
+    if download_options.image_verification.policy() == SignaturePolicy::RequireSigned {
+        return Err(FlashError::UnsignedImage.into());
+    }
+
+    if download_options.hooks.has_pre_programming()
+        || download_options.stamp_checksums
+        || download_options.image_verification.is_enabled()
+        || download_options.journal.is_some()
+        || download_options.golden_image.is_some()
+    {
+        tracing::warn!(
+            "Pre-programming hooks, checksums, signature checks, journals and golden images are not supported when programming a partition image, they are ignored."
+        );
+    }
+
+    let region = session
+        .target()
+        .memory_map
+        .iter()
+        .find(|region| {
+            let name = match region {
+                MemoryRegion::Ram(r) => &r.name,
+                MemoryRegion::Generic(r) => &r.name,
+                MemoryRegion::Nvm(r) => &r.name,
+            };
+
+            name.as_deref() == Some(options.region_name.as_str())
+        })
+        .cloned()
+        .ok_or_else(|| FileDownloadError::NoSuchRegion(options.region_name.clone()))?;
+
+    let region_range = match &region {
+        MemoryRegion::Ram(r) => r.range.clone(),
+        MemoryRegion::Generic(r) => r.range.clone(),
+        MemoryRegion::Nvm(r) => r.range.clone(),
+    };
+
+    let image_size = image.seek(SeekFrom::End(0))?;
+
+    let available = (region_range.end - region_range.start).saturating_sub(options.offset);
+    if image_size > available {
+        return Err(FileDownloadError::PartitionImageTooLarge {
+            region_name: options.region_name.clone(),
+            image_size,
+            available,
+        });
+    }
+
+    let base_address = region_range.start + options.offset;
+
+    let plan = PartitionPlan::new(image_size, options, download_options);
+
+    session.run_pre_flash_hooks().map_err(FlashError::from)?;
+
+    if plan.erase_chip && !download_options.dry_run {
+        if let MemoryRegion::Nvm(region) = &region {
+            erase_chip(session, region, download_options.progress.clone())?;
+        }
+    }
+
+    for page in plan.pages {
+        tracing::debug!(
+            "Programming partition image bytes {:#x}..{:#x} of {:#x}",
+            page.start,
+            page.end,
+            image_size
+        );
+
+        let mut buffer = vec![0; (page.end - page.start) as usize];
+        image.seek(SeekFrom::Start(page.start))?;
+        image.read_exact(&mut buffer)?;
+
+        let mut loader = session.target().flash_loader();
+        loader.add_data(base_address + page.start, &buffer)?;
+        loader.commit_part(session, plan.page_options.clone())?;
+
+        on_page_programmed(page.end);
+    }
+
+    if !download_options.dry_run {
+        download_options
+            .hooks
+            .run_post_programming(session)
+            .map_err(FileDownloadError::Flash)?;
+    }
+
+    Ok(image_size)
+}
+
+/// How a partition image is split into commits of a [`FlashLoader`](super::FlashLoader).
+struct PartitionPlan {
+    /// Whether the chip is erased once, before the first page is programmed.
+    erase_chip: bool,
+    /// The options every page is committed with.
+    page_options: DownloadOptions,
+    /// The ranges of the image which are committed one after another.
+    pages: Vec<Range<u64>>,
+}
+
+impl PartitionPlan {
+    fn new(
+        image_size: u64,
+        options: &PartitionImageOptions,
+        download_options: &DownloadOptions,
+    ) -> Self {
+        let resuming = options.resume_from > 0;
+        if download_options.do_chip_erase && resuming {
+            tracing::warn!(
+                "The chip is not erased when a partition image download is resumed, the pages which were already programmed are kept."
+            );
+        }
+
+        // A chip erase in every commit would erase the pages programmed before.
+        let page_options = DownloadOptions {
+            keep_unwritten_bytes: true,
+            do_chip_erase: false,
+            stamp_checksums: false,
+            hooks: DownloadHooks::default(),
+            image_verification: ImageVerification::default(),
+            journal: None,
+            golden_image: None,
+            ..download_options.clone()
+        };
+
+        Self {
+            erase_chip: download_options.do_chip_erase && !resuming,
+            page_options,
+            pages: partition_pages(image_size, options.page_size, options.resume_from).collect(),
+        }
+    }
+}
+
+/// Erases the flash behind `region` with a chip erase, if its flash algorithm supports one.
+fn erase_chip(
+    session: &mut Session,
+    region: &NvmRegion,
+    progress: Option<FlashProgress>,
+) -> Result<(), FlashError> {
+    let algo = FlashLoader::get_flash_algorithm_for_region(region, session.target())?.clone();
+    let core_index = region
+        .cores
+        .first()
+        .and_then(|core_name| session.target().core_index_by_name(core_name))
+        .ok_or_else(|| FlashError::NoNvmCoreAccess(Box::new(region.clone())))?;
+
+    let mut flasher = Flasher::new(session, core_index, &algo, progress)?;
+
+    if !flasher.is_chip_erase_supported() {
+        tracing::warn!("Chip erase was the selected method to erase the sectors but this chip does not support chip erases (yet).");
+        tracing::warn!("A manual sector erase will be performed.");
+        return Ok(());
+    }
+
+    flasher.run_erase_all()
+}
+
+/// Splits an image of `image_size` bytes into the ranges which still have to be programmed.
+///
+/// Pages are aligned to `page_size` relative to the start of the image, so resuming at an
+/// arbitrary offset only shortens the first page.
+fn partition_pages(
+    image_size: u64,
+    page_size: u64,
+    resume_from: u64,
+) -> impl Iterator<Item = Range<u64>> {
+    let page_size = page_size.max(1);
+    let mut start = resume_from.min(image_size);
+
+    std::iter::from_fn(move || {
+        if start >= image_size {
+            return None;
+        }
+
+        let end = ((start / page_size + 1) * page_size).min(image_size);
+        let page = start..end;
+        start = end;
+
+        Some(page)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{partition_pages, PartitionImageOptions, PartitionPlan};
+    use crate::flashing::DownloadOptions;
+
+    /// Programs `image` into `flash` like the commits of `plan` do, where a commit with
+    /// `do_chip_erase` erases the whole flash first.
+    fn program(plan: &PartitionPlan, image: &[u8], flash: &mut [u8]) {
+        if plan.erase_chip {
+            flash.fill(0xff);
+        }
+
+        for page in &plan.pages {
+            if plan.page_options.do_chip_erase {
+                flash.fill(0xff);
+            }
+
+            let page = page.start as usize..page.end as usize;
+            flash[page.clone()].copy_from_slice(&image[page]);
+        }
+    }
+
+    #[test]
+    fn pages_cover_image() {
+        let pages: Vec<_> = partition_pages(10, 4, 0).collect();
+
+        assert_eq!(pages, vec![0..4, 4..8, 8..10]);
+    }
+
+    #[test]
+    fn resume_shortens_first_page() {
+        let pages: Vec<_> = partition_pages(10, 4, 5).collect();
+
+        assert_eq!(pages, vec![5..8, 8..10]);
+    }
+
+    #[test]
+    fn resume_past_end_is_empty() {
+        assert_eq!(partition_pages(10, 4, 10).count(), 0);
+        assert_eq!(partition_pages(10, 4, 20).count(), 0);
+    }
+
+    #[test]
+    fn chip_erase_keeps_earlier_pages() {
+        let image: Vec<u8> = (0..10).collect();
+        let mut options = PartitionImageOptions::new("storage");
+        options.page_size = 4;
+        let download_options = DownloadOptions {
+            do_chip_erase: true,
+            ..Default::default()
+        };
+
+        let plan = PartitionPlan::new(image.len() as u64, &options, &download_options);
+        assert!(plan.erase_chip);
+        assert_eq!(plan.pages.len(), 3);
+
+        let mut flash = [0x55; 12];
+        program(&plan, &image, &mut flash);
+
+        assert_eq!(&flash[..10], &image[..]);
+        assert_eq!(&flash[10..], &[0xff, 0xff]);
+    }
+
+    #[test]
+    fn resume_does_not_erase_chip() {
+        let image = [0xaa; 10];
+        let mut options = PartitionImageOptions::new("storage");
+        options.page_size = 4;
+        options.resume_from = 4;
+        let download_options = DownloadOptions {
+            do_chip_erase: true,
+            ..Default::default()
+        };
+
+        let plan = PartitionPlan::new(image.len() as u64, &options, &download_options);
+        assert!(!plan.erase_chip);
+
+        // The first page was programmed by the interrupted download.
+        let mut flash = [0x55; 10];
+        flash[..4].copy_from_slice(&image[..4]);
+        program(&plan, &image, &mut flash);
+
+        assert_eq!(flash, image);
+    }
+}
@@ -1,6 +1,22 @@
 use super::FlashLayout;
+use crate::events::{FlashPhase, PhaseStatus, SessionEvent};
 use std::{sync::Arc, time::Duration};
 
+/// Receives the [`ProgressEvent`]s of the flashing procedure, see [`FlashProgress::from_handler`].
+///
+/// This is implemented for all closures taking a [`ProgressEvent`]. Types which keep state
+/// across events, like the progress bars of a GUI, can implement it directly.
+pub trait ProgressHandler {
+    /// Called for every event of the flashing procedure.
+    fn on_event(&self, event: ProgressEvent);
+}
+
+impl<F: Fn(ProgressEvent)> ProgressHandler for F {
+    fn on_event(&self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
 /// A structure to manage the flashing procedure progress reporting.
 ///
 /// This struct stores a handler closure which will be called everytime an event happens during the flashing process.
@@ -16,12 +32,35 @@ use std::{sync::Arc, time::Duration};
 /// ```
 #[derive(Clone)]
 pub struct FlashProgress {
-    handler: Arc<dyn Fn(ProgressEvent)>,
+    handler: Arc<dyn ProgressHandler>,
 }
 
 impl FlashProgress {
     /// Create a new `FlashProgress` structure with a given `handler` to be called on events.
     pub fn new(handler: impl Fn(ProgressEvent) + 'static) -> Self {
+        Self::from_handler(handler)
+    }
+
+    /// Create a new `FlashProgress` structure which passes the events to `handler`.
+    ///
+    /// ```
+    /// use probe_rs::flashing::{FlashProgress, ProgressEvent, ProgressHandler};
+    /// use std::cell::Cell;
+    ///
+    /// #[derive(Default)]
+    /// struct ProgrammedBytes(Cell<u64>);
+    ///
+    /// impl ProgressHandler for ProgrammedBytes {
+    ///     fn on_event(&self, event: ProgressEvent) {
+    ///         if let ProgressEvent::PageProgrammed { size, .. } = event {
+    ///             self.0.set(self.0.get() + u64::from(size));
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let progress = FlashProgress::from_handler(ProgrammedBytes::default());
+    /// ```
+    pub fn from_handler(handler: impl ProgressHandler + 'static) -> Self {
         Self {
             handler: Arc::new(handler),
         }
@@ -29,7 +68,11 @@ impl FlashProgress {
 
     /// Emit a flashing progress event.
     fn emit(&self, event: ProgressEvent) {
-        (self.handler)(event);
+        if let Some((phase, status)) = event.phase() {
+            crate::events::emit(|| SessionEvent::Flash { phase, status });
+        }
+
+        self.handler.on_event(event);
     }
 
     /// Signalize that the flashing algorithm was set up and is initialized.
@@ -97,6 +140,26 @@ impl FlashProgress {
         self.emit(ProgressEvent::FinishedFilling);
     }
 
+    /// Signalize that the verification of `size` bytes started.
+    pub(super) fn started_verifying(&self, size: u64) {
+        self.emit(ProgressEvent::StartedVerifying { size });
+    }
+
+    /// Signalize that the verification procedure has made progress.
+    pub(super) fn data_verified(&self, size: u64, time: Duration) {
+        self.emit(ProgressEvent::DataVerified { size, time });
+    }
+
+    /// Signalize that the verification procedure failed.
+    pub(super) fn failed_verifying(&self) {
+        self.emit(ProgressEvent::FailedVerifying);
+    }
+
+    /// Signalize that the verification procedure completed successfully.
+    pub(super) fn finished_verifying(&self) {
+        self.emit(ProgressEvent::FinishedVerifying);
+    }
+
     #[cfg(feature = "rtt")]
     pub(super) fn message(&self, message: String) {
         self.emit(ProgressEvent::DiagnosticMessage { message });
@@ -118,6 +181,8 @@ impl FlashProgress {
 /// * `StartedProgramming`
 /// * `PageProgrammed` for every page
 /// * `FinishedProgramming`
+/// * `StartedVerifying`, `DataVerified` for every chunk of data and `FinishedVerifying`, if
+///   [`DownloadOptions::verify`](super::DownloadOptions::verify) is set
 ///
 /// If an erorr occurs in any stage, one of the `Failed*` event will be returned,
 /// and no further events will be returned.
@@ -170,9 +235,53 @@ pub enum ProgressEvent {
     FailedProgramming,
     /// Programming of the flash has finished successfully.
     FinishedProgramming,
+    /// Verification of the written data has started.
+    StartedVerifying {
+        /// The number of bytes which will be verified.
+        size: u64,
+    },
+    /// A chunk of data has been read back and matches the written data.
+    DataVerified {
+        /// The size of the chunk in bytes.
+        size: u64,
+        /// The time it took to read back this chunk.
+        time: Duration,
+    },
+    /// The written data does not match, or could not be read back.
+    FailedVerifying,
+    /// Verification has finished successfully.
+    FinishedVerifying,
     /// a message was received from the algo.
     DiagnosticMessage {
         /// The message that was emitted.
         message: String,
     },
 }
+
+impl ProgressEvent {
+    /// The phase of the flashing procedure this event starts or ends, if any.
+    fn phase(&self) -> Option<(FlashPhase, PhaseStatus)> {
+        let phase = match self {
+            ProgressEvent::Initialized { .. } => (FlashPhase::Initialize, PhaseStatus::Finished),
+            ProgressEvent::StartedFilling => (FlashPhase::Fill, PhaseStatus::Started),
+            ProgressEvent::FailedFilling => (FlashPhase::Fill, PhaseStatus::Failed),
+            ProgressEvent::FinishedFilling => (FlashPhase::Fill, PhaseStatus::Finished),
+            ProgressEvent::StartedErasing => (FlashPhase::Erase, PhaseStatus::Started),
+            ProgressEvent::FailedErasing => (FlashPhase::Erase, PhaseStatus::Failed),
+            ProgressEvent::FinishedErasing => (FlashPhase::Erase, PhaseStatus::Finished),
+            ProgressEvent::StartedProgramming => (FlashPhase::Program, PhaseStatus::Started),
+            ProgressEvent::FailedProgramming => (FlashPhase::Program, PhaseStatus::Failed),
+            ProgressEvent::FinishedProgramming => (FlashPhase::Program, PhaseStatus::Finished),
+            ProgressEvent::StartedVerifying { .. } => (FlashPhase::Verify, PhaseStatus::Started),
+            ProgressEvent::FailedVerifying => (FlashPhase::Verify, PhaseStatus::Failed),
+            ProgressEvent::FinishedVerifying => (FlashPhase::Verify, PhaseStatus::Finished),
+            ProgressEvent::PageFilled { .. }
+            | ProgressEvent::SectorErased { .. }
+            | ProgressEvent::PageProgrammed { .. }
+            | ProgressEvent::DataVerified { .. }
+            | ProgressEvent::DiagnosticMessage { .. } => return None,
+        };
+
+        Some(phase)
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use super::{FlashError, HookError, StagedImage};
+
+/// The result of a signature check by an [`ImageVerifier`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// The image carries a signature, and it is valid.
+    Valid,
+    /// The image carries no signature.
+    Unsigned,
+}
+
+/// Checks the signature of an image before it is programmed.
+///
+/// The verifier owns the key the signature is checked against, and knows where the image
+/// format keeps its signature. A signature which is present, but does not match the image or
+/// the key, is reported as an error.
+///
+/// Closures taking a [`StagedImage`] implement this trait as well.
+pub trait ImageVerifier {
+    /// Check the signature of `image`.
+    fn verify(&self, image: &StagedImage<'_>) -> Result<SignatureStatus, HookError>;
+}
+
+impl<F> ImageVerifier for F
+where
+    F: Fn(&StagedImage<'_>) -> Result<SignatureStatus, HookError>,
+{
+    fn verify(&self, image: &StagedImage<'_>) -> Result<SignatureStatus, HookError> {
+        self(image)
+    }
+}
+
+/// Whether unsigned images are programmed, see [`ImageVerification`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Unsigned images are programmed. Images with an invalid signature are still refused.
+    #[default]
+    AllowUnsigned,
+    /// Only images with a valid signature are programmed.
+    RequireSigned,
+}
+
+/// The signature check which [`FlashLoader::commit`](super::FlashLoader::commit) runs before
+/// anything is written to the target.
+///
+/// The image is checked after the pre-programming hooks ran and the checksums were stamped,
+/// so the signature has to cover the image as it is programmed.
+///
+/// # Example
+///
+/// ```
+/// use probe_rs::flashing::{
+///     DownloadOptions, HookError, ImageVerification, SignatureStatus, StagedImage,
+/// };
+///
+/// let mut options = DownloadOptions::default();
+///
+/// // A real verifier checks a signature, for example an Ed25519 signature in the image header.
+/// let verifier = |image: &StagedImage<'_>| -> Result<SignatureStatus, HookError> {
+///     let mut magic = [0; 4];
+///     if image.read(0x0800_0000, &mut magic) && magic == *b"SIGN" {
+///         Ok(SignatureStatus::Valid)
+///     } else {
+///         Ok(SignatureStatus::Unsigned)
+///     }
+/// };
+///
+/// options.image_verification = ImageVerification::new(verifier).require_signed();
+/// ```
+#[derive(Clone, Default)]
+pub struct ImageVerification {
+    verifier: Option<Arc<dyn ImageVerifier>>,
+    policy: SignaturePolicy,
+}
+
+impl ImageVerification {
+    /// Check images with `verifier`, and program unsigned images.
+    pub fn new(verifier: impl ImageVerifier + 'static) -> Self {
+        Self {
+            verifier: Some(Arc::new(verifier)),
+            policy: SignaturePolicy::AllowUnsigned,
+        }
+    }
+
+    /// Refuse images without a valid signature.
+    ///
+    /// Without a verifier, all images are refused.
+    pub fn require_signed(mut self) -> Self {
+        self.policy = SignaturePolicy::RequireSigned;
+        self
+    }
+
+    /// The policy for unsigned images.
+    pub fn policy(&self) -> SignaturePolicy {
+        self.policy
+    }
+
+    /// Returns `true` if images are checked, or refused because no verifier is set.
+    pub(super) fn is_enabled(&self) -> bool {
+        self.verifier.is_some() || self.policy == SignaturePolicy::RequireSigned
+    }
+
+    /// Check `image` and apply the policy.
+    pub(super) fn check(&self, image: &StagedImage<'_>) -> Result<(), FlashError> {
+        let status = match &self.verifier {
+            Some(verifier) => verifier
+                .verify(image)
+                .map_err(FlashError::InvalidImageSignature)?,
+            None => SignatureStatus::Unsigned,
+        };
+
+        match (status, self.policy) {
+            (SignatureStatus::Valid, _) => {
+                tracing::info!("The signature of the image is valid");
+                Ok(())
+            }
+            (SignatureStatus::Unsigned, SignaturePolicy::AllowUnsigned) => {
+                tracing::warn!("The image is not signed");
+                Ok(())
+            }
+            (SignatureStatus::Unsigned, SignaturePolicy::RequireSigned) => {
+                Err(FlashError::UnsignedImage)
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ImageVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageVerification")
+            .field("verifier", &self.verifier.is_some())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImageVerification, SignatureStatus};
+    use crate::flashing::{FlashBuilder, FlashError, HookError, StagedImage};
+    use probe_rs_target::TargetDescriptionSource;
+
+    fn check(verification: &ImageVerification, data: &[u8]) -> Result<(), FlashError> {
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0, data).unwrap();
+
+        verification.check(&StagedImage {
+            builder: &mut builder,
+            memory_map: &[],
+            source: &TargetDescriptionSource::BuiltIn,
+        })
+    }
+
+    #[test]
+    fn policy_decides_about_unsigned_images() {
+        let verifier = |image: &StagedImage<'_>| -> Result<SignatureStatus, HookError> {
+            let mut header = [0; 2];
+            image.read(0, &mut header);
+            match header {
+                [0x5A, 0x5A] => Ok(SignatureStatus::Valid),
+                [0x5A, _] => Err("signature mismatch".into()),
+                _ => Ok(SignatureStatus::Unsigned),
+            }
+        };
+
+        let allow = ImageVerification::new(verifier);
+        let require = allow.clone().require_signed();
+
+        assert!(check(&allow, &[0x00, 0x00]).is_ok());
+        assert!(check(&require, &[0x5A, 0x5A]).is_ok());
+        assert!(matches!(
+            check(&require, &[0x00, 0x00]),
+            Err(FlashError::UnsignedImage)
+        ));
+        assert!(matches!(
+            check(&allow, &[0x5A, 0x00]),
+            Err(FlashError::InvalidImageSignature(_))
+        ));
+
+        // Without a verifier, nothing counts as signed.
+        let require_only = ImageVerification::default().require_signed();
+        assert!(require_only.is_enabled());
+        assert!(!ImageVerification::default().is_enabled());
+        assert!(matches!(
+            check(&require_only, &[0x5A, 0x5A]),
+            Err(FlashError::UnsignedImage)
+        ));
+    }
+}
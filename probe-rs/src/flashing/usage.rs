@@ -0,0 +1,332 @@
+use super::FileDownloadError;
+
+use object::elf::{FileHeader32, PT_LOAD, SHF_ALLOC, SHT_NOBITS};
+use object::read::elf::{FileHeader, ProgramHeader, SectionHeader};
+use object::{Endianness, Object, ObjectSymbol};
+use probe_rs_target::MemoryRegion;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+
+/// Symbols whose address is the size of the stack reserved by the linker script.
+const STACK_SIZE_SYMBOLS: &[&str] = &["_Min_Stack_Size", "__stack_size__", "_stack_size"];
+/// Symbols whose address is the size of the heap reserved by the linker script.
+const HEAP_SIZE_SYMBOLS: &[&str] = &["_Min_Heap_Size", "__heap_size__", "_heap_size"];
+
+/// The kind of memory a [`RegionUsage`] refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryKind {
+    /// Non-volatile memory, which holds the image.
+    Flash,
+    /// RAM, which holds the data used at runtime.
+    Ram,
+}
+
+/// The memory used by an image in a single memory region of the target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegionUsage {
+    /// The name of the memory region.
+    pub name: Option<String>,
+    /// The kind of the memory region.
+    pub kind: MemoryKind,
+    /// The address range of the memory region.
+    pub range: Range<u64>,
+    /// The number of bytes used, including the stack and heap reservations.
+    pub used: u64,
+    /// The number of bytes reserved for the stack.
+    pub stack: u64,
+    /// The number of bytes reserved for the heap.
+    pub heap: u64,
+    /// The end of the highest section in this region.
+    end: u64,
+    /// Whether a section for the stack or heap is placed in this region.
+    has_reservation_section: bool,
+}
+
+impl RegionUsage {
+    /// The size of the memory region in bytes.
+    pub fn size(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    /// The number of bytes which are not used, or 0 if the region is overfull.
+    pub fn free(&self) -> u64 {
+        self.size().saturating_sub(self.used)
+    }
+
+    /// Returns `false` if the used memory exceeds the region.
+    pub fn fits(&self) -> bool {
+        self.used <= self.size() && self.end <= self.range.end
+    }
+
+    fn add(&mut self, address: u64, size: u64) {
+        self.used += size;
+        self.end = self.end.max(address + size);
+    }
+}
+
+/// The flash and RAM usage of an ELF image, per memory region of the target.
+///
+/// Flash usage is the size of all sections with contents, at their load address. RAM usage is
+/// the size of all sections placed in RAM, including zero initialized sections.
+///
+/// Stack and heap sizes are read from the symbols `_Min_Stack_Size`, `__stack_size__` or
+/// `_stack_size`, and `_Min_Heap_Size`, `__heap_size__` or `_heap_size`, as defined by
+/// common linker scripts. They are added to the RAM region with the most data, unless
+/// that region already contains a section for them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsageReport {
+    /// The usage of every flash and RAM region which is used by the image.
+    pub regions: Vec<RegionUsage>,
+    /// The number of bytes which are not placed in any known memory region.
+    pub unmapped: u64,
+}
+
+impl MemoryUsageReport {
+    /// Compute the memory usage of the ELF file `elf_data` on a target with `memory_map`.
+    pub fn from_elf(
+        elf_data: &[u8],
+        memory_map: &[MemoryRegion],
+    ) -> Result<Self, FileDownloadError> {
+        let elf_header = FileHeader32::<Endianness>::parse(elf_data)?;
+        let endian = elf_header.endian()?;
+
+        let mut regions: Vec<_> = memory_map
+            .iter()
+            .filter_map(|region| {
+                let (name, kind, range) = match region {
+                    MemoryRegion::Nvm(region) => (&region.name, MemoryKind::Flash, &region.range),
+                    MemoryRegion::Ram(region) => (&region.name, MemoryKind::Ram, &region.range),
+                    MemoryRegion::Generic(_) => return None,
+                };
+
+                Some(RegionUsage {
+                    name: name.clone(),
+                    kind,
+                    range: range.clone(),
+                    used: 0,
+                    stack: 0,
+                    heap: 0,
+                    end: range.start,
+                    has_reservation_section: false,
+                })
+            })
+            .collect();
+
+        let mut unmapped = 0;
+
+        let segments = elf_header.program_headers(endian, elf_data)?;
+        let sections = elf_header.sections(endian, elf_data)?;
+
+        for section in sections.iter() {
+            let size = section.sh_size(endian) as u64;
+            if section.sh_flags(endian) & SHF_ALLOC == 0 || size == 0 {
+                continue;
+            }
+
+            let name = sections.section_name(endian, section).unwrap_or_default();
+            let name = String::from_utf8_lossy(name);
+            let is_reservation = name.contains("stack") || name.contains("heap");
+
+            let address = section.sh_addr(endian) as u64;
+            let mut addresses = vec![address];
+
+            if section.sh_type(endian) != SHT_NOBITS {
+                // Sections with contents, which are copied to RAM at startup, are also stored
+                // in flash at their load address.
+                let offset = section.sh_offset(endian) as u64;
+                let load_address = segments
+                    .iter()
+                    .filter(|segment| segment.p_type(endian) == PT_LOAD)
+                    .find_map(|segment| {
+                        let (start, size) = segment.file_range(endian);
+                        (start..start + size)
+                            .contains(&offset)
+                            .then(|| segment.p_paddr(endian) as u64 + offset - start)
+                    });
+
+                if let Some(load_address) = load_address.filter(|&load| load != address) {
+                    addresses.push(load_address);
+                }
+            }
+
+            for address in addresses {
+                match regions
+                    .iter_mut()
+                    .find(|region| region.range.contains(&address))
+                {
+                    Some(region) => {
+                        region.add(address, size);
+                        region.has_reservation_section |= is_reservation;
+                    }
+                    None => unmapped += size,
+                }
+            }
+        }
+
+        let binary = object::read::elf::ElfFile::<FileHeader32<Endianness>>::parse(elf_data)?;
+        let symbol_value = |names: &[&str]| {
+            binary
+                .symbols()
+                .find(|symbol| symbol.name().is_ok_and(|name| names.contains(&name)))
+                .map(|symbol| symbol.address())
+                .unwrap_or(0)
+        };
+
+        let stack = symbol_value(STACK_SIZE_SYMBOLS);
+        let heap = symbol_value(HEAP_SIZE_SYMBOLS);
+
+        if stack > 0 || heap > 0 {
+            let ram = regions
+                .iter_mut()
+                .filter(|region| region.kind == MemoryKind::Ram)
+                .reduce(|most_used, region| {
+                    if region.used > most_used.used {
+                        region
+                    } else {
+                        most_used
+                    }
+                });
+
+            if let Some(ram) = ram {
+                ram.stack = stack;
+                ram.heap = heap;
+
+                if !ram.has_reservation_section {
+                    ram.used += stack + heap;
+                }
+            }
+        }
+
+        regions.retain(|region| region.used > 0);
+
+        Ok(Self { regions, unmapped })
+    }
+
+    /// Returns `true` if the image fits into the memory regions of the target.
+    pub fn fits(&self) -> bool {
+        self.unmapped == 0 && self.regions.iter().all(RegionUsage::fits)
+    }
+
+    /// The total number of bytes used in memory of `kind`.
+    pub fn total(&self, kind: MemoryKind) -> u64 {
+        self.regions
+            .iter()
+            .filter(|region| region.kind == kind)
+            .map(|region| region.used)
+            .sum()
+    }
+}
+
+impl fmt::Display for MemoryUsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for region in &self.regions {
+            let kind = match region.kind {
+                MemoryKind::Flash => "Flash",
+                MemoryKind::Ram => "RAM",
+            };
+
+            write!(
+                f,
+                "{:>5} {:<12} {:>8} / {:>8} bytes ({:5.1}%)",
+                kind,
+                region.name.as_deref().unwrap_or("<unnamed>"),
+                region.used,
+                region.size(),
+                100.0 * region.used as f64 / region.size() as f64,
+            )?;
+
+            if region.stack > 0 || region.heap > 0 {
+                write!(
+                    f,
+                    ", stack {} bytes, heap {} bytes",
+                    region.stack, region.heap
+                )?;
+            }
+
+            if !region.fits() {
+                write!(f, ", exceeds the region")?;
+            }
+
+            writeln!(f)?;
+        }
+
+        if self.unmapped > 0 {
+            writeln!(
+                f,
+                "{} bytes are outside of all memory regions",
+                self.unmapped
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryKind, MemoryUsageReport};
+    use probe_rs_target::{MemoryRegion, NvmRegion, RamRegion};
+    use std::ops::Range;
+
+    fn memory_map(flash: Range<u64>, ram: Range<u64>) -> Vec<MemoryRegion> {
+        vec![
+            MemoryRegion::Nvm(NvmRegion {
+                name: Some("FLASH".to_owned()),
+                range: flash,
+                is_boot_memory: true,
+                cores: vec!["main".to_owned()],
+                access: Default::default(),
+                sectors: vec![],
+                bank_swap: None,
+            }),
+            MemoryRegion::Ram(RamRegion {
+                name: Some("RAM".to_owned()),
+                range: ram,
+                is_boot_memory: false,
+                cores: vec!["main".to_owned()],
+                access: Default::default(),
+            }),
+        ]
+    }
+
+    fn test_elf() -> Vec<u8> {
+        std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/probe-rs-debugger-test"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn usage_per_region() {
+        let report = MemoryUsageReport::from_elf(
+            &test_elf(),
+            &memory_map(0x0800_0000..0x0810_0000, 0x2000_0000..0x2002_0000),
+        )
+        .unwrap();
+
+        // .vector_table, .text, .rodata and the initial values of .data
+        assert_eq!(
+            report.total(MemoryKind::Flash),
+            0x400 + 0x582c + 0x17d0 + 0x48
+        );
+        // .data and .bss
+        assert_eq!(report.total(MemoryKind::Ram), 0x48 + 0x85c);
+        assert_eq!(report.unmapped, 0);
+        assert!(report.fits());
+    }
+
+    #[test]
+    fn image_exceeding_flash_does_not_fit() {
+        let report = MemoryUsageReport::from_elf(
+            &test_elf(),
+            &memory_map(0x0800_0000..0x0800_4000, 0x2000_0000..0x2002_0000),
+        )
+        .unwrap();
+
+        assert!(!report.fits());
+        // .text ends beyond the region, .rodata and the initial values of .data are outside of it.
+        assert_eq!(report.unmapped, 0x17d0 + 0x48);
+    }
+}
@@ -1,4 +1,4 @@
-use gdbstub::arch::{Arch, RegId, Registers, SingleStepGdbBehavior};
+use gdbstub::arch::{Arch, RegId, Registers};
 
 // Placeholder type for runtime architecture trait data
 pub(crate) enum RuntimeArch {}
@@ -10,16 +10,14 @@ impl Arch for RuntimeArch {
     type Registers = RuntimeRegisters;
     type BreakpointKind = usize;
     type RegId = RuntimeRegId;
-
-    fn single_step_gdb_behavior() -> SingleStepGdbBehavior {
-        SingleStepGdbBehavior::Required
-    }
 }
 
 #[derive(Clone, Default, Debug, PartialEq)]
 pub(crate) struct RuntimeRegisters {
     pub pc: u64,
     pub regs: Vec<u8>,
+    /// Set if the values are not known, for tasks which have no registers of their own
+    pub unavailable: bool,
 }
 
 impl Registers for RuntimeRegisters {
@@ -31,12 +29,13 @@ impl Registers for RuntimeRegisters {
 
     fn gdb_serialize(&self, mut write_byte: impl FnMut(Option<u8>)) {
         for b in &self.regs {
-            write_byte(Some(*b))
+            write_byte((!self.unavailable).then_some(*b))
         }
     }
 
     fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
         self.regs = bytes.to_vec();
+        self.unavailable = false;
 
         Ok(())
     }
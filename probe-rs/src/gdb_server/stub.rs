@@ -1,8 +1,10 @@
+use crate::rtos::RtosAwareness;
 use crate::{CoreType, Error, Session};
 use anyhow::Result;
 
 use std::net::{SocketAddr, ToSocketAddrs};
-use std::sync::Mutex;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use itertools::Itertools;
@@ -19,6 +21,15 @@ pub struct GdbInstanceConfiguration {
     pub cores: Vec<usize>,
     /// The list of [SocketAddr] addresses to bind to
     pub socket_addrs: Vec<SocketAddr>,
+    /// The RTOS awareness used to report the RTOS threads as GDB threads.
+    ///
+    /// This is only supported for instances with a single core, see [crate::rtos::detect_rtos].
+    pub rtos: Option<Arc<dyn RtosAwareness>>,
+    /// The directory in which the target can open files through semihosting.
+    ///
+    /// Paths of the target are resolved relative to it, and cannot leave it. If this is `None`,
+    /// the target can only use the console.
+    pub semihosting_root: Option<PathBuf>,
 }
 
 impl GdbInstanceConfiguration {
@@ -65,6 +76,8 @@ impl GdbInstanceConfiguration {
                 core_type: *core_type,
                 cores: cores.to_vec(),
                 socket_addrs: adjust_addrs(&addrs, i),
+                rtos: None,
+                semihosting_root: None,
             })
             .collect();
 
@@ -89,7 +102,13 @@ pub fn run<'a>(
     // Turn our group list into GDB targets
     let mut targets = instances
         .map(|instance| {
-            target::RuntimeTarget::new(session, instance.cores.to_vec(), &instance.socket_addrs[..])
+            target::RuntimeTarget::new(
+                session,
+                instance.cores.to_vec(),
+                &instance.socket_addrs[..],
+                instance.rtos.clone(),
+                instance.semihosting_root.clone(),
+            )
         })
         .collect::<Result<Vec<target::RuntimeTarget>, Error>>()?;
 
@@ -0,0 +1,303 @@
+use super::desc::GdbRegisterSource;
+use super::{GdbErrorExt, RuntimeTarget};
+use crate::gdb_server::arch::{RuntimeRegId, RuntimeRegisters};
+use crate::rtos::RtosThread;
+use crate::{Core, Error, MemoryInterface, RegisterId};
+use gdbstub::common::Tid;
+use gdbstub::target::ext::base::multithread::MultiThreadBase;
+use gdbstub::target::ext::base::multithread::MultiThreadResumeOps;
+use gdbstub::target::ext::base::single_register_access::SingleRegisterAccess;
+use gdbstub::target::ext::base::single_register_access::SingleRegisterAccessOps;
+use gdbstub::target::ext::thread_extra_info::ThreadExtraInfoOps;
+use gdbstub::target::TargetError;
+use std::collections::BTreeMap;
+
+impl MultiThreadBase for RuntimeTarget<'_> {
+    fn read_registers(
+        &mut self,
+        regs: &mut RuntimeRegisters,
+        tid: Tid,
+    ) -> gdbstub::target::TargetResult<(), Self> {
+        let mut session = self.session.lock().unwrap();
+        let mut core = session.core(self.core_id(tid)).into_target_result()?;
+
+        let thread = self.suspended_rtos_thread(tid);
+
+        let pc = GdbRegisterSource::SingleRegister(core.program_counter().id());
+        let registers = self
+            .target_desc
+            .get_registers_for_main_group()
+            .collect::<Vec<_>>();
+
+        if thread.is_some_and(|thread| thread.registers.is_empty()) {
+            // The task shares the stack of the core and has no registers of its own.
+            let size = registers.iter().map(|reg| reg.size_in_bytes()).sum();
+
+            regs.pc = 0;
+            regs.regs = vec![0; size];
+            regs.unavailable = true;
+
+            return Ok(());
+        }
+        let sources = std::iter::once(pc)
+            .chain(registers.iter().map(|reg| reg.source()))
+            .collect::<Vec<_>>();
+
+        let values =
+            read_registers_from_sources(&mut core, thread, &sources).into_target_result()?;
+
+        regs.pc = values[0] as u64;
+
+        let mut reg_buffer = Vec::<u8>::new();
+
+        for (reg, mut value) in registers.iter().zip(values.into_iter().skip(1)) {
+            for _ in 0..reg.size_in_bytes() {
+                let byte = value as u8;
+                reg_buffer.push(byte);
+                value >>= 8;
+            }
+        }
+
+        regs.regs = reg_buffer;
+        regs.unavailable = false;
+
+        Ok(())
+    }
+
+    fn write_registers(
+        &mut self,
+        regs: &RuntimeRegisters,
+        tid: Tid,
+    ) -> gdbstub::target::TargetResult<(), Self> {
+        if self.suspended_rtos_thread(tid).is_some() {
+            // The saved context of a thread which is not running cannot be modified.
+            return Err(TargetError::NonFatal);
+        }
+
+        let mut session = self.session.lock().unwrap();
+        let mut core = session.core(self.core_id(tid)).into_target_result()?;
+
+        core.write_core_reg(core.program_counter(), regs.pc)
+            .into_target_result()?;
+
+        let mut current_regval_offset = 0;
+
+        for reg in self.target_desc.get_registers_for_main_group() {
+            let bytesize = reg.size_in_bytes();
+
+            let current_regval_end = current_regval_offset + bytesize;
+
+            if current_regval_end > regs.regs.len() {
+                // Supplied write general registers command argument length not valid, tell GDB
+                tracing::error!(
+                    "Unable to write register {:#?}, because supplied register value length was too short",
+                    reg.source()
+                );
+                return Err(TargetError::Errno(22));
+            }
+
+            let str_value = &regs.regs[current_regval_offset..current_regval_end];
+
+            let mut value = 0;
+            for (exp, ch) in str_value.iter().enumerate() {
+                value += (*ch as u128) << (8 * exp);
+            }
+
+            write_register_from_source(&mut core, reg.source(), value).into_target_result()?;
+
+            current_regval_offset = current_regval_end;
+
+            if current_regval_offset == regs.regs.len() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_addrs(
+        &mut self,
+        start_addr: u64,
+        data: &mut [u8],
+        tid: Tid,
+    ) -> gdbstub::target::TargetResult<usize, Self> {
+        let mut session = self.session.lock().unwrap();
+        let mut core = session.core(self.core_id(tid)).into_target_result()?;
+
+        core.read(start_addr, data).into_target_result_non_fatal()?;
+
+        Ok(data.len())
+    }
+
+    fn write_addrs(
+        &mut self,
+        start_addr: u64,
+        data: &[u8],
+        tid: Tid,
+    ) -> gdbstub::target::TargetResult<(), Self> {
+        let mut session = self.session.lock().unwrap();
+        let mut core = session.core(self.core_id(tid)).into_target_result()?;
+
+        core.write_8(start_addr, data)
+            .into_target_result_non_fatal()
+    }
+
+    fn list_active_threads(
+        &mut self,
+        thread_is_active: &mut dyn FnMut(Tid),
+    ) -> Result<(), Self::Error> {
+        for tid in self.thread_ids() {
+            thread_is_active(tid);
+        }
+
+        Ok(())
+    }
+
+    fn support_resume(&mut self) -> Option<MultiThreadResumeOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_single_register_access(&mut self) -> Option<SingleRegisterAccessOps<'_, Tid, Self>> {
+        Some(self)
+    }
+
+    fn support_thread_extra_info(&mut self) -> Option<ThreadExtraInfoOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl SingleRegisterAccess<Tid> for RuntimeTarget<'_> {
+    fn read_register(
+        &mut self,
+        tid: Tid,
+        reg_id: RuntimeRegId,
+        buf: &mut [u8],
+    ) -> gdbstub::target::TargetResult<usize, Self> {
+        let mut session = self.session.lock().unwrap();
+        let mut core = session.core(self.core_id(tid)).into_target_result()?;
+
+        let thread = self.suspended_rtos_thread(tid);
+        if thread.is_some_and(|thread| thread.registers.is_empty()) {
+            // The register of a task without registers of its own is unavailable.
+            return Ok(0);
+        }
+
+        let reg = self.target_desc.get_register(reg_id.into());
+        let bytesize = reg.size_in_bytes();
+
+        let mut value: u128 =
+            read_register_from_source(&mut core, thread, reg.source()).into_target_result()?;
+
+        for buf_entry in buf.iter_mut().take(bytesize) {
+            let byte = value as u8;
+            *buf_entry = byte;
+            value >>= 8;
+        }
+
+        Ok(bytesize)
+    }
+
+    fn write_register(
+        &mut self,
+        tid: Tid,
+        reg_id: RuntimeRegId,
+        val: &[u8],
+    ) -> gdbstub::target::TargetResult<(), Self> {
+        if self.suspended_rtos_thread(tid).is_some() {
+            return Err(TargetError::NonFatal);
+        }
+
+        let mut session = self.session.lock().unwrap();
+        let mut core = session.core(self.core_id(tid)).into_target_result()?;
+
+        let reg = self.target_desc.get_register(reg_id.into());
+        let bytesize = reg.size_in_bytes();
+
+        let mut value = 0;
+
+        for (exp, ch) in val.iter().enumerate().take(bytesize) {
+            value += (*ch as u128) << (8 * exp);
+        }
+
+        write_register_from_source(&mut core, reg.source(), value).into_target_result()?;
+
+        Ok(())
+    }
+}
+
+/// Read a register of the core, or from the saved context of an RTOS `thread` which is not running.
+///
+/// Registers which are not part of the saved context, like the MSP, are shared by all threads
+/// and read from the core.
+fn read_register_from_source(
+    core: &mut Core,
+    thread: Option<&RtosThread>,
+    source: GdbRegisterSource,
+) -> Result<u128, Error> {
+    Ok(read_registers_from_sources(core, thread, &[source])?[0])
+}
+
+/// Read several registers like [`read_register_from_source`], fetching all registers which are
+/// read from the core at once.
+fn read_registers_from_sources(
+    core: &mut Core,
+    thread: Option<&RtosThread>,
+    sources: &[GdbRegisterSource],
+) -> Result<Vec<u128>, Error> {
+    let saved = |id: RegisterId| thread.and_then(|thread| thread.register(id));
+
+    let ids = sources
+        .iter()
+        .flat_map(|source| match *source {
+            GdbRegisterSource::SingleRegister(id) => vec![id],
+            GdbRegisterSource::TwoWordRegister { low, high, .. } => vec![low, high],
+        })
+        .filter(|&id| saved(id).is_none())
+        .collect::<Vec<_>>();
+
+    let core_values = ids
+        .iter()
+        .copied()
+        .zip(core.read_core_regs(&ids)?)
+        .collect::<BTreeMap<_, _>>();
+
+    let value = |id: RegisterId| -> Result<u128, Error> {
+        match saved(id) {
+            Some(value) => Ok(value as u128),
+            None => core_values[&id].try_into(),
+        }
+    };
+
+    sources
+        .iter()
+        .map(|source| match *source {
+            GdbRegisterSource::SingleRegister(id) => value(id),
+            GdbRegisterSource::TwoWordRegister {
+                low,
+                high,
+                word_size,
+            } => Ok(value(low)? | value(high)? << word_size),
+        })
+        .collect()
+}
+
+fn write_register_from_source(
+    core: &mut Core,
+    source: GdbRegisterSource,
+    value: u128,
+) -> Result<(), Error> {
+    match source {
+        GdbRegisterSource::SingleRegister(id) => core.write_core_reg(id, value),
+        GdbRegisterSource::TwoWordRegister {
+            low,
+            high,
+            word_size,
+        } => {
+            let low_word = value & ((1 << word_size) - 1);
+            let high_word = value >> word_size;
+
+            core.write_core_reg(low, low_word)?;
+            core.write_core_reg(high, high_word)
+        }
+    }
+}
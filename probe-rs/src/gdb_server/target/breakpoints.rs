@@ -0,0 +1,142 @@
+use super::{GdbErrorExt, RuntimeTarget};
+use crate::{Error, Watchpoint, WatchpointKind};
+
+use gdbstub::target::ext::breakpoints::{
+    Breakpoints, HwBreakpoint, HwBreakpointOps, HwWatchpoint, HwWatchpointOps, SwBreakpointOps,
+    WatchKind,
+};
+
+impl Breakpoints for RuntimeTarget<'_> {
+    fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
+        None
+    }
+
+    fn support_hw_breakpoint(&mut self) -> Option<HwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_hw_watchpoint(&mut self) -> Option<HwWatchpointOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl HwBreakpoint for RuntimeTarget<'_> {
+    fn add_hw_breakpoint(
+        &mut self,
+        addr: u64,
+        _kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        let mut session = self.session.lock().unwrap();
+
+        for core_id in &self.cores {
+            let mut core = session.core(*core_id).into_target_result()?;
+
+            core.set_hw_breakpoint(addr).into_target_result()?;
+        }
+
+        Ok(true)
+    }
+
+    fn remove_hw_breakpoint(
+        &mut self,
+        addr: u64,
+        _kind: <Self::Arch as gdbstub::arch::Arch>::BreakpointKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        let mut session = self.session.lock().unwrap();
+
+        for core_id in &self.cores {
+            let mut core = session.core(*core_id).into_target_result()?;
+
+            core.clear_hw_breakpoint(addr).into_target_result()?;
+        }
+
+        Ok(true)
+    }
+}
+
+impl HwWatchpoint for RuntimeTarget<'_> {
+    fn add_hw_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        let mut session = self.session.lock().unwrap();
+        let watchpoint = watchpoint(addr, len, kind);
+        let mut set_on = Vec::with_capacity(self.cores.len());
+
+        let result = self.cores.iter().try_for_each(|&core_id| {
+            session.core(core_id)?.set_hw_watchpoint(watchpoint)?;
+            set_on.push(core_id);
+            Ok(())
+        });
+
+        let Err(error) = result else {
+            return Ok(true);
+        };
+
+        // All cores have to watch the range, so undo what was already set.
+        for core_id in set_on {
+            if let Err(e) = session
+                .core(core_id)
+                .and_then(|mut core| core.clear_hw_watchpoint(watchpoint))
+            {
+                tracing::warn!(
+                    "Failed to remove watchpoint {:?} from core {}: {}",
+                    watchpoint,
+                    core_id,
+                    e
+                );
+            }
+        }
+
+        match error {
+            // Let GDB fall back to a software watchpoint.
+            Error::NoWatchpointAvailable { .. } => {
+                tracing::info!("{}", error);
+                Ok(false)
+            }
+            error => Err(error).into_target_result(),
+        }
+    }
+
+    fn remove_hw_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchKind,
+    ) -> gdbstub::target::TargetResult<bool, Self> {
+        let mut session = self.session.lock().unwrap();
+        let watchpoint = watchpoint(addr, len, kind);
+
+        for core_id in &self.cores {
+            let mut core = session.core(*core_id).into_target_result()?;
+
+            core.clear_hw_watchpoint(watchpoint).into_target_result()?;
+        }
+
+        Ok(true)
+    }
+}
+
+fn watchpoint(address: u64, length: u64, kind: WatchKind) -> Watchpoint {
+    let kind = match kind {
+        WatchKind::Read => WatchpointKind::Read,
+        WatchKind::Write => WatchpointKind::Write,
+        WatchKind::ReadWrite => WatchpointKind::ReadWrite,
+    };
+
+    Watchpoint {
+        address,
+        length,
+        kind,
+    }
+}
+
+pub(super) fn watch_kind(kind: WatchpointKind) -> WatchKind {
+    match kind {
+        WatchpointKind::Read => WatchKind::Read,
+        WatchpointKind::Write => WatchKind::Write,
+        WatchpointKind::ReadWrite => WatchKind::ReadWrite,
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{CoreType, InstructionSet};
+
+use super::{flash_blocks, TargetDescription};
+use crate::config::SectorDescription;
+
+#[test]
+fn test_target_description_microbit() {
+    let target_desc = TargetDescription::new(CoreType::Armv6m, InstructionSet::Thumb2);
+    let description = target_desc.get_target_xml();
+
+    insta::assert_snapshot!(description);
+}
+
+#[test]
+fn test_target_with_features() {
+    let mut target_desc = TargetDescription::new(CoreType::Armv6m, InstructionSet::Thumb2);
+    target_desc.add_gdb_feature("org.probe-rs.feature1");
+    target_desc.add_register_from_details("r0", 32, 0.into());
+    target_desc.add_register_from_details("x1", 64, 1.into());
+    target_desc.add_register_from_details("t2", 64, 2.into());
+
+    target_desc.update_register_name("t2", "at2");
+    target_desc.update_register_type("at2", "special_reg");
+
+    target_desc.add_gdb_feature("org.probe-rs.feature2");
+    target_desc.add_register_from_details("v4", 128, 4.into());
+
+    let description = target_desc.get_target_xml();
+
+    insta::assert_snapshot!(description);
+}
+
+#[test]
+fn test_flash_blocks_uniform() {
+    let sectors = [SectorDescription {
+        size: 0x400,
+        address: 0x0,
+    }];
+
+    assert_eq!(
+        flash_blocks(&(0x0..0x4_0000), 0x0, &sectors),
+        vec![(0x0..0x4_0000, 0x400)]
+    );
+}
+
+#[test]
+fn test_flash_blocks_mixed_sector_sizes() {
+    let sectors = [
+        SectorDescription {
+            size: 0x4000,
+            address: 0x0,
+        },
+        SectorDescription {
+            size: 0x1_0000,
+            address: 0x1_0000,
+        },
+        SectorDescription {
+            size: 0x2_0000,
+            address: 0x2_0000,
+        },
+    ];
+
+    assert_eq!(
+        flash_blocks(&(0x800_8000..0x808_0000), 0x800_0000, &sectors),
+        vec![
+            (0x800_8000..0x801_0000, 0x4000),
+            (0x801_0000..0x802_0000, 0x1_0000),
+            (0x802_0000..0x808_0000, 0x2_0000),
+        ]
+    );
+}
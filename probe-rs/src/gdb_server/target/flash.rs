@@ -0,0 +1,62 @@
+use super::{GdbErrorExt, RuntimeTarget};
+use crate::flashing::{DownloadOptions, FlashLoader};
+
+use gdbstub::target::ext::flash::Flash;
+use gdbstub::target::{TargetError, TargetResult};
+use std::time::Duration;
+
+impl Flash for RuntimeTarget<'_> {
+    fn flash_erase(&mut self, start_addr: u64, length: u64) -> TargetResult<(), Self> {
+        // GDB only erases the blocks it writes afterwards. The flash loader erases
+        // these sectors itself when the data is programmed in `flash_done`.
+        tracing::debug!(
+            "Erasing {:#x} bytes of flash at {:#010x}",
+            length,
+            start_addr
+        );
+
+        self.flash_loader();
+
+        Ok(())
+    }
+
+    fn flash_write(&mut self, start_addr: u64, data: &[u8]) -> TargetResult<(), Self> {
+        self.flash_loader().add_data(start_addr, data).map_err(|e| {
+            tracing::error!("Failed to add flash data at {:#010x}: {}", start_addr, e);
+            TargetError::NonFatal
+        })
+    }
+
+    fn flash_done(&mut self) -> TargetResult<(), Self> {
+        let Some(loader) = self.flash_loader.take() else {
+            return Ok(());
+        };
+
+        let mut session = self.session.lock().unwrap();
+
+        if let Err(e) = loader.commit(&mut session, DownloadOptions::default()) {
+            tracing::error!("Failed to program flash: {}", e);
+            return Err(TargetError::NonFatal);
+        }
+
+        // The flash algorithm ran on the core, so reset it into a known state.
+        session
+            .core(self.cores[0])
+            .and_then(|mut core| core.reset_and_halt(Duration::from_secs(1)))
+            .into_target_result()?;
+
+        drop(session);
+
+        self.refresh_rtos_threads().into_target_result()
+    }
+}
+
+impl RuntimeTarget<'_> {
+    /// The flash loader collecting the data of the current `load`, created by its first packet.
+    fn flash_loader(&mut self) -> &mut FlashLoader {
+        let session = self.session;
+
+        self.flash_loader
+            .get_or_insert_with(|| session.lock().unwrap().target().flash_loader())
+    }
+}
@@ -0,0 +1,394 @@
+mod base;
+mod breakpoints;
+mod desc;
+mod flash;
+mod monitor;
+mod resume;
+mod rtos;
+mod semihosting;
+mod thread;
+mod traits;
+mod utils;
+
+use super::arch::RuntimeArch;
+use crate::flashing::FlashLoader;
+use crate::rtos::RtosAwareness;
+use crate::{BreakpointCause, CoreStatus, Error, HaltReason, Session};
+use gdbstub::stub::state_machine::GdbStubStateMachine;
+use semihosting::SemihostingOutcome;
+
+use std::collections::VecDeque;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use gdbstub::common::Signal;
+use gdbstub::conn::ConnectionExt;
+use gdbstub::stub::{GdbStub, MultiThreadStopReason};
+use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::breakpoints::BreakpointsOps;
+use gdbstub::target::ext::flash::FlashOps;
+use gdbstub::target::ext::memory_map::MemoryMapOps;
+use gdbstub::target::ext::monitor_cmd::MonitorCmdOps;
+use gdbstub::target::ext::target_description_xml_override::TargetDescriptionXmlOverrideOps;
+use gdbstub::target::Target;
+
+pub(crate) use traits::{GdbErrorExt, ProbeRsErrorExt};
+
+use desc::TargetDescription;
+
+/// Actions for resuming a core
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum ResumeAction {
+    /// Don't change the state
+    Unchanged,
+    /// Resume core
+    Resume,
+    /// Single step core
+    Step,
+}
+
+/// The top level gdbstub target for a probe-rs debug session
+pub(crate) struct RuntimeTarget<'a> {
+    /// The probe-rs session object
+    session: &'a Mutex<Session>,
+    /// A list of core IDs for this stub
+    cores: Vec<usize>,
+
+    /// TCP listener accepting incoming connections
+    listener: TcpListener,
+    /// The current GDB stub state machine
+    gdb: Option<GdbStubStateMachine<'a, RuntimeTarget<'a>, TcpStream>>,
+    /// Resume action to be used upon a continue request
+    resume_action: (usize, ResumeAction),
+
+    /// Description of target's architecture and registers
+    target_desc: TargetDescription,
+
+    /// RTT control block attached to through the `rtt attach` monitor command
+    #[cfg(feature = "rtt")]
+    rtt: Option<crate::rtt::Rtt>,
+
+    /// Host side state for semihosting requests
+    semihosting: semihosting::SemihostingHost,
+    /// Whether GDB acknowledges the packets it receives
+    ack_mode: semihosting::AckMode,
+    /// Bytes from GDB which were received while sending console output, not passed to gdbstub yet
+    pending_input: VecDeque<u8>,
+
+    /// RTOS threads, reported to GDB instead of the core
+    rtos: Option<rtos::RtosState>,
+
+    /// Flash data received from GDB through `vFlashWrite`, programmed on `vFlashDone`
+    flash_loader: Option<FlashLoader>,
+}
+
+impl<'a> RuntimeTarget<'a> {
+    /// Create a new RuntimeTarget and get ready to start processing GDB input
+    pub fn new(
+        session: &'a Mutex<Session>,
+        cores: Vec<usize>,
+        addrs: &[SocketAddr],
+        rtos: Option<Arc<dyn RtosAwareness>>,
+        semihosting_root: Option<PathBuf>,
+    ) -> Result<Self, Error> {
+        let listener = TcpListener::bind(addrs).into_error()?;
+        listener.set_nonblocking(true).into_error()?;
+
+        let rtos = match rtos {
+            // Threads of an RTOS are only supported when debugging a single core.
+            Some(rtos) if cores.len() > 1 => {
+                tracing::warn!(
+                    "{} awareness is not supported with multiple cores",
+                    rtos.name()
+                );
+                None
+            }
+            rtos => rtos.map(rtos::RtosState::new),
+        };
+
+        Ok(Self {
+            session,
+            cores,
+            listener,
+            gdb: None,
+            resume_action: (0, ResumeAction::Unchanged),
+            target_desc: TargetDescription::default(),
+            #[cfg(feature = "rtt")]
+            rtt: None,
+            semihosting: semihosting::SemihostingHost::new(semihosting_root),
+            ack_mode: semihosting::AckMode::default(),
+            pending_input: VecDeque::new(),
+            rtos,
+            flash_loader: None,
+        })
+    }
+
+    /// Process any pending work for this target
+    ///
+    /// Returns: Duration to wait before processing this target again
+    pub fn process(&mut self) -> Result<Duration, Error> {
+        // State 1 - unconnected
+        if self.gdb.is_none() {
+            // See if we have a connection
+            match self.listener.accept() {
+                Ok((s, addr)) => {
+                    tracing::info!("New connection from {:#?}", addr);
+
+                    for i in 0..self.cores.len() {
+                        let core_id = self.cores[i];
+                        // When we first attach to the core, GDB expects us to halt the core, so we do this here when a new client connects.
+                        // If the core is already halted, nothing happens if we issue a halt command again, so we always do this no matter of core state.
+                        self.session
+                            .lock()
+                            .unwrap()
+                            .core(core_id)?
+                            .halt(Duration::from_millis(100))?;
+
+                        self.load_target_desc()?;
+                    }
+
+                    self.refresh_rtos_threads()?;
+
+                    self.ack_mode = semihosting::AckMode::default();
+                    self.pending_input.clear();
+                    self.flash_loader = None;
+
+                    // Start the GDB Stub state machine
+                    let stub = GdbStub::<RuntimeTarget, _>::new(s);
+                    match stub.run_state_machine(self) {
+                        Ok(gdbstub) => {
+                            self.gdb = Some(gdbstub);
+                        }
+                        Err(e) => {
+                            // Any errors at this state are either IO errors or fatal config errors
+                            return Err(anyhow::Error::from(e).into());
+                        }
+                    };
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    // No connection yet
+                    return Ok(Duration::from_millis(10));
+                }
+                Err(e) => {
+                    // Fatal error
+                    return Err(anyhow::Error::from(e).into());
+                }
+            };
+        }
+
+        // Stage 2 - connected
+        if self.gdb.is_some() {
+            let mut wait_time = Duration::ZERO;
+            let gdb = self.gdb.take().unwrap();
+
+            self.gdb = match gdb {
+                GdbStubStateMachine::Idle(mut state) => {
+                    // Read data if available
+                    let next_byte = self.next_byte(state.borrow_conn())?;
+
+                    if let Some(b) = next_byte {
+                        Some(state.incoming_data(self, b).into_error()?)
+                    } else {
+                        wait_time = Duration::from_millis(10);
+                        Some(state.into())
+                    }
+                }
+                GdbStubStateMachine::Running(mut state) => {
+                    // Read data if available
+                    let next_byte = self.next_byte(state.borrow_conn())?;
+
+                    if let Some(b) = next_byte {
+                        Some(state.incoming_data(self, b).into_error()?)
+                    } else {
+                        // Check for break
+                        let mut stop_reason: Option<MultiThreadStopReason<u64>> = None;
+                        {
+                            let mut session = self.session.lock().unwrap();
+
+                            for i in &self.cores {
+                                let mut core = session.core(*i)?;
+                                let status = core.status()?;
+
+                                if let CoreStatus::Halted(reason) = status {
+                                    if let HaltReason::Breakpoint(_) = reason {
+                                        match self.semihosting.handle(&mut core)? {
+                                            Some(SemihostingOutcome::Resume) => {
+                                                core.run()?;
+                                                continue;
+                                            }
+                                            Some(SemihostingOutcome::Pending) => continue,
+                                            Some(SemihostingOutcome::Exited(code)) => {
+                                                stop_reason =
+                                                    Some(MultiThreadStopReason::Exited(code));
+                                                break;
+                                            }
+                                            None => {}
+                                        }
+                                    }
+
+                                    if let Some(rtos) = self.rtos.as_mut() {
+                                        rtos.refresh(&mut core);
+                                    }
+                                    let tid = self.current_tid(*i);
+
+                                    stop_reason = Some(match reason {
+                                        HaltReason::Breakpoint(BreakpointCause::Hardware)
+                                        | HaltReason::Breakpoint(BreakpointCause::Unknown) => {
+                                            // Some architectures do not allow us to distinguish between hardware and software breakpoints, so we just treat `Unknown` as hardware breakpoints.
+                                            MultiThreadStopReason::HwBreak(tid)
+                                        }
+                                        HaltReason::Step => MultiThreadStopReason::DoneStep,
+                                        HaltReason::Watchpoint(Some(watchpoint)) => {
+                                            MultiThreadStopReason::Watch {
+                                                tid,
+                                                kind: breakpoints::watch_kind(watchpoint.kind),
+                                                addr: watchpoint.address,
+                                            }
+                                        }
+                                        HaltReason::Watchpoint(None) => {
+                                            MultiThreadStopReason::SignalWithThread {
+                                                tid,
+                                                signal: Signal::SIGTRAP,
+                                            }
+                                        }
+                                        _ => MultiThreadStopReason::SignalWithThread {
+                                            tid,
+                                            signal: Signal::SIGINT,
+                                        },
+                                    });
+                                    break;
+                                }
+                            }
+
+                            // halt all remaining cores that are still running
+                            // GDB expects all or nothing stops
+                            if stop_reason.is_some() {
+                                for i in &self.cores {
+                                    let mut core = session.core(*i)?;
+                                    if !core.core_halted()? {
+                                        core.halt(Duration::from_millis(100))?;
+                                    }
+                                }
+                            }
+                        }
+
+                        let console_output = self.semihosting.take_console_output();
+                        if !console_output.is_empty() {
+                            semihosting::send_console_output(
+                                state.borrow_conn(),
+                                &console_output,
+                                &self.ack_mode,
+                                &mut self.pending_input,
+                            )
+                            .into_error()?;
+                        }
+
+                        if let Some(reason) = stop_reason {
+                            Some(state.report_stop(self, reason).into_error()?)
+                        } else {
+                            wait_time = Duration::from_millis(10);
+                            Some(state.into())
+                        }
+                    }
+                }
+                GdbStubStateMachine::CtrlCInterrupt(state) => {
+                    // Break core, handle interrupt
+                    {
+                        let mut session = self.session.lock().unwrap();
+                        for i in &self.cores {
+                            let mut core = session.core(*i)?;
+
+                            core.halt(Duration::from_millis(100))?;
+                        }
+                    }
+
+                    self.refresh_rtos_threads()?;
+
+                    Some(
+                        state
+                            .interrupt_handled(
+                                self,
+                                Some(MultiThreadStopReason::Signal(Signal::SIGINT)),
+                            )
+                            .into_error()?,
+                    )
+                }
+                GdbStubStateMachine::Disconnected(state) => {
+                    tracing::info!("GDB client disconnected: {:?}", state.get_reason());
+
+                    None
+                }
+            };
+
+            return Ok(wait_time);
+        }
+
+        Ok(Duration::ZERO)
+    }
+
+    /// The next byte from GDB for gdbstub, if one is available.
+    fn next_byte(&mut self, conn: &mut TcpStream) -> Result<Option<u8>, Error> {
+        let next_byte = match self.pending_input.pop_front() {
+            Some(byte) => Some(byte),
+            None => read_if_available(conn)?,
+        };
+
+        if let Some(byte) = next_byte {
+            self.ack_mode.received(byte);
+        }
+
+        Ok(next_byte)
+    }
+}
+
+impl Target for RuntimeTarget<'_> {
+    type Arch = RuntimeArch;
+    type Error = Error;
+
+    fn base_ops(&mut self) -> BaseOps<'_, Self::Arch, Self::Error> {
+        BaseOps::MultiThread(self)
+    }
+
+    fn support_target_description_xml_override(
+        &mut self,
+    ) -> Option<TargetDescriptionXmlOverrideOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_memory_map(&mut self) -> Option<MemoryMapOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_monitor_cmd(&mut self) -> Option<MonitorCmdOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn support_flash_operations(&mut self) -> Option<FlashOps<'_, Self>> {
+        Some(self)
+    }
+
+    fn guard_rail_implicit_sw_breakpoints(&self) -> bool {
+        true
+    }
+}
+
+/// Read a byte from a stream if available, otherwise return None
+fn read_if_available(conn: &mut TcpStream) -> Result<Option<u8>, Error> {
+    match conn.peek() {
+        Ok(p) => {
+            // Unwrap is safe because peek already showed
+            // there's data in the buffer
+            match p {
+                Some(_) => conn.read().map(Some).into_error(),
+                None => Ok(None),
+            }
+        }
+        Err(e) => Err(anyhow::Error::from(e).into()),
+    }
+}
@@ -0,0 +1,258 @@
+use std::time::Duration;
+
+use super::RuntimeTarget;
+use crate::architecture::arm::component::TraceSink;
+use crate::architecture::arm::SwoConfig;
+
+use gdbstub::target::ext::monitor_cmd::outputln;
+use gdbstub::target::ext::monitor_cmd::{ConsoleOutput, MonitorCmd};
+
+const HELP_TEXT: &str = r#"Supported Commands:
+
+    info - print session information
+    info cores - print the status of all cores of this GDB instance
+    reset - reset target
+    reset run - reset target and let it run
+    reset halt - reset target and halt afterwards
+    erase - mass erase all non-volatile memory
+    rtt attach - search the target RAM for an RTT control block
+    rtt channels - list the RTT channels
+    rtt read <channel> - read pending data from an RTT up channel
+    rtt write <channel> <text> - write text to an RTT down channel
+    rtt detach - forget the RTT control block
+    swo enable <tpiu clock> <baud> - enable SWO tracing
+    swo read - read and hexdump pending SWO data
+    swo disable - disable SWO tracing
+"#;
+
+impl MonitorCmd for RuntimeTarget<'_> {
+    fn handle_monitor_cmd(
+        &mut self,
+        cmd: &[u8],
+        mut out: ConsoleOutput<'_>,
+    ) -> Result<(), Self::Error> {
+        let cmd = String::from_utf8_lossy(cmd);
+        let args: Vec<&str> = cmd.split_whitespace().collect();
+
+        match args.as_slice() {
+            ["info"] => {
+                outputln!(
+                    out,
+                    "Target info:\n\n{:#?}",
+                    self.session.lock().unwrap().target()
+                );
+            }
+            ["info", "cores"] => {
+                let mut session = self.session.lock().unwrap();
+                for core_id in &self.cores {
+                    let status = session.core(*core_id)?.status()?;
+                    outputln!(out, "Core {}: {:?}", core_id, status);
+                }
+            }
+            ["reset"] | ["reset", "run"] => {
+                outputln!(out, "Resetting target");
+                match self.session.lock().unwrap().core(self.cores[0])?.reset() {
+                    Ok(_) => {
+                        outputln!(out, "Done")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while resetting target:\n\t{}", e)
+                    }
+                }
+            }
+            ["reset", "halt"] => {
+                let timeout: Duration = Duration::new(1, 0);
+                outputln!(out, "Resetting and halting target");
+                match self
+                    .session
+                    .lock()
+                    .unwrap()
+                    .core(self.cores[0])?
+                    .reset_and_halt(timeout)
+                {
+                    Ok(_) => {
+                        outputln!(out, "Target halted")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while halting target:\n\t{}", e)
+                    }
+                }
+            }
+            ["erase"] => {
+                outputln!(out, "Erasing all non-volatile memory");
+                match crate::flashing::erase_all(&mut self.session.lock().unwrap(), None) {
+                    Ok(_) => {
+                        outputln!(out, "Done")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while erasing target:\n\t{}", e)
+                    }
+                }
+            }
+            ["rtt", rtt_args @ ..] => self.handle_rtt_cmd(rtt_args, &mut out)?,
+            ["swo", "enable", tpiu_clk, baud] => {
+                let (Ok(tpiu_clk), Ok(baud)) = (tpiu_clk.parse::<u32>(), baud.parse::<u32>())
+                else {
+                    outputln!(out, "Invalid TPIU clock or baud rate");
+                    return Ok(());
+                };
+
+                let config = SwoConfig::new(tpiu_clk).set_baud(baud);
+                match self
+                    .session
+                    .lock()
+                    .unwrap()
+                    .setup_tracing(self.cores[0], TraceSink::Swo(config))
+                {
+                    Ok(_) => {
+                        outputln!(out, "SWO enabled")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while enabling SWO:\n\t{}", e)
+                    }
+                }
+            }
+            ["swo", "read"] => match self.session.lock().unwrap().read_trace_data() {
+                Ok(data) => {
+                    for (i, line) in data.chunks(16).enumerate() {
+                        let bytes: Vec<String> = line.iter().map(|b| format!("{b:02x}")).collect();
+
+                        outputln!(out, "{:08x}: {}", i * 16, bytes.join(" "));
+                    }
+                    outputln!(out, "{} bytes", data.len());
+                }
+                Err(e) => {
+                    outputln!(out, "Error while reading SWO data:\n\t{}", e)
+                }
+            },
+            ["swo", "disable"] => {
+                let mut session = self.session.lock().unwrap();
+                match session.disable_swv(self.cores[0]) {
+                    Ok(_) => {
+                        outputln!(out, "SWO disabled")
+                    }
+                    Err(e) => {
+                        outputln!(out, "Error while disabling SWO:\n\t{}", e)
+                    }
+                }
+            }
+            _ => {
+                outputln!(out, "{}", HELP_TEXT);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RuntimeTarget<'_> {
+    #[cfg(feature = "rtt")]
+    fn handle_rtt_cmd(
+        &mut self,
+        args: &[&str],
+        out: &mut ConsoleOutput<'_>,
+    ) -> Result<(), crate::Error> {
+        use crate::rtt::Rtt;
+
+        let mut session = self.session.lock().unwrap();
+        let memory_map = session.target().memory_map.clone();
+        let mut core = session.core(self.cores[0])?;
+
+        match args {
+            ["attach"] => match Rtt::attach(&mut core, &memory_map) {
+                Ok(rtt) => {
+                    outputln!(out, "RTT control block found at {:#010x}", rtt.ptr());
+                    self.rtt = Some(rtt);
+                }
+                Err(e) => {
+                    outputln!(out, "Error while attaching to RTT:\n\t{}", e)
+                }
+            },
+            ["detach"] => {
+                self.rtt = None;
+                outputln!(out, "Done");
+            }
+            _ => {
+                let Some(rtt) = self.rtt.as_mut() else {
+                    outputln!(out, "RTT is not attached, use 'rtt attach' first");
+                    return Ok(());
+                };
+
+                match args {
+                    ["channels"] => {
+                        for channel in rtt.up_channels().iter() {
+                            outputln!(
+                                out,
+                                "up {}: {} ({} bytes)",
+                                channel.number(),
+                                channel.name().unwrap_or("unnamed"),
+                                channel.buffer_size()
+                            );
+                        }
+                        for channel in rtt.down_channels().iter() {
+                            outputln!(
+                                out,
+                                "down {}: {} ({} bytes)",
+                                channel.number(),
+                                channel.name().unwrap_or("unnamed"),
+                                channel.buffer_size()
+                            );
+                        }
+                    }
+                    ["read", number] => {
+                        let Some(channel) =
+                            number.parse().ok().and_then(|n| rtt.up_channels().get(n))
+                        else {
+                            outputln!(out, "No such up channel: {}", number);
+                            return Ok(());
+                        };
+
+                        let mut buf = vec![0; channel.buffer_size()];
+                        match channel.read(&mut core, &mut buf) {
+                            Ok(count) => {
+                                outputln!(out, "{}", String::from_utf8_lossy(&buf[..count]))
+                            }
+                            Err(e) => {
+                                outputln!(out, "Error while reading RTT:\n\t{}", e)
+                            }
+                        }
+                    }
+                    ["write", number, text @ ..] => {
+                        let Some(channel) =
+                            number.parse().ok().and_then(|n| rtt.down_channels().get(n))
+                        else {
+                            outputln!(out, "No such down channel: {}", number);
+                            return Ok(());
+                        };
+
+                        let text = text.join(" ");
+                        match channel.write(&mut core, text.as_bytes()) {
+                            Ok(count) => {
+                                outputln!(out, "Wrote {} bytes", count)
+                            }
+                            Err(e) => {
+                                outputln!(out, "Error while writing RTT:\n\t{}", e)
+                            }
+                        }
+                    }
+                    _ => {
+                        outputln!(out, "{}", HELP_TEXT);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "rtt"))]
+    fn handle_rtt_cmd(
+        &mut self,
+        _args: &[&str],
+        out: &mut ConsoleOutput<'_>,
+    ) -> Result<(), crate::Error> {
+        outputln!(out, "RTT support is not enabled in this build");
+
+        Ok(())
+    }
+}
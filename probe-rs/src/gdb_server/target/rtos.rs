@@ -0,0 +1,134 @@
+use super::RuntimeTarget;
+use crate::rtos::{RtosAwareness, RtosThread, RtosThreadState};
+use crate::{Core, Error};
+
+use gdbstub::common::Tid;
+use std::sync::Arc;
+
+/// The RTOS threads of a GDB instance which debugs a single core.
+///
+/// While threads are available, they are reported to GDB instead of the core, with the thread
+/// ID being the index into the thread list plus one.
+pub(crate) struct RtosState {
+    awareness: Arc<dyn RtosAwareness>,
+    threads: Vec<RtosThread>,
+}
+
+impl RtosState {
+    pub(crate) fn new(awareness: Arc<dyn RtosAwareness>) -> Self {
+        Self {
+            awareness,
+            threads: Vec::new(),
+        }
+    }
+
+    /// Re-read the threads from the halted `core`.
+    ///
+    /// If the threads cannot be read, for example because the scheduler was not started yet,
+    /// the core itself is reported to GDB again.
+    ///
+    /// Tasks which share the stack of the core are listed next to a thread for the code running
+    /// on the core, as none of them is known to be running.
+    pub(crate) fn refresh(&mut self, core: &mut Core) {
+        self.threads = match self.awareness.threads(core) {
+            Ok(mut threads) if self.awareness.shared_stack() && !threads.is_empty() => {
+                threads.insert(
+                    0,
+                    RtosThread {
+                        id: 0,
+                        name: "Current context".to_owned(),
+                        state: RtosThreadState::Running,
+                        priority: None,
+                        registers: Vec::new(),
+                    },
+                );
+
+                threads
+            }
+            Ok(threads)
+                if threads
+                    .iter()
+                    .any(|thread| thread.state == RtosThreadState::Running) =>
+            {
+                threads
+            }
+            Ok(_) => {
+                tracing::debug!("No running {} thread found", self.awareness.name());
+                Vec::new()
+            }
+            Err(e) => {
+                tracing::warn!("Failed to read {} threads: {}", self.awareness.name(), e);
+                Vec::new()
+            }
+        };
+    }
+
+    pub(crate) fn threads(&self) -> &[RtosThread] {
+        &self.threads
+    }
+}
+
+impl RuntimeTarget<'_> {
+    /// Re-read the RTOS threads, after the core was halted.
+    pub(crate) fn refresh_rtos_threads(&mut self) -> Result<(), Error> {
+        if let Some(rtos) = self.rtos.as_mut() {
+            let mut session = self.session.lock().unwrap();
+            let mut core = session.core(self.cores[0])?;
+
+            rtos.refresh(&mut core);
+        }
+
+        Ok(())
+    }
+
+    fn rtos_threads(&self) -> Option<&[RtosThread]> {
+        self.rtos
+            .as_ref()
+            .map(|rtos| rtos.threads())
+            .filter(|threads| !threads.is_empty())
+    }
+
+    /// Returns the RTOS thread with ID `tid`, if RTOS threads are reported to GDB.
+    pub(crate) fn rtos_thread(&self, tid: Tid) -> Option<&RtosThread> {
+        self.rtos_threads()?.get(tid.get() - 1)
+    }
+
+    /// Returns the saved context of thread `tid`, if it is an RTOS thread which is not running.
+    pub(crate) fn suspended_rtos_thread(&self, tid: Tid) -> Option<&RtosThread> {
+        self.rtos_thread(tid)
+            .filter(|thread| thread.state != RtosThreadState::Running)
+    }
+
+    /// Returns the ID of the core which runs thread `tid`.
+    pub(crate) fn core_id(&self, tid: Tid) -> usize {
+        match self.rtos_threads() {
+            Some(_) => self.cores[0],
+            None => tid.get() - 1,
+        }
+    }
+
+    /// Returns the IDs of all threads reported to GDB.
+    pub(crate) fn thread_ids(&self) -> Vec<Tid> {
+        let count = match self.rtos_threads() {
+            Some(threads) => threads.len(),
+            None => return self.cores.iter().filter_map(|i| Tid::new(i + 1)).collect(),
+        };
+
+        (1..=count).filter_map(Tid::new).collect()
+    }
+
+    /// Returns the ID of the thread which is executing on core `core_id`.
+    pub(crate) fn current_tid(&self, core_id: usize) -> Tid {
+        let position = self.rtos_threads().and_then(|threads| {
+            threads
+                .iter()
+                .position(|thread| thread.state == RtosThreadState::Running)
+        });
+
+        // Unwrap is always safe because we'll never pass 0 to new
+        match position {
+            Some(position) => Tid::new(position + 1).unwrap(),
+            None => Tid::new(core_id + 1).unwrap(),
+        }
+    }
+}
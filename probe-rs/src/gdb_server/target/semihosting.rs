@@ -0,0 +1,543 @@
+use crate::semihosting::{
+    complete_semihosting_read, complete_semihosting_syscall, decode_semihosting_syscall,
+    SemihostingCommand, ADP_STOPPED_APPLICATION_EXIT, MAX_TRANSFER_LENGTH,
+};
+use crate::{Core, Error};
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+/// Handles returned to the target for the console opened through the special path `:tt`.
+const STDIN_HANDLE: u32 = 1;
+const STDOUT_HANDLE: u32 = 2;
+const STDERR_HANDLE: u32 = 3;
+
+/// The value returned to the target if a request failed.
+const RESULT_ERROR: u32 = u32::MAX;
+
+/// The console output is sent to GDB in `O` packets with at most this many bytes each.
+const MAX_CONSOLE_PACKET: usize = 256;
+
+/// How often an `O` packet is sent again after GDB rejected it.
+const MAX_CONSOLE_RETRIES: usize = 3;
+
+/// How long to wait for GDB to acknowledge an `O` packet.
+const CONSOLE_ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What should happen with a core after a semihosting request was handled.
+pub(crate) enum SemihostingOutcome {
+    /// The request was handled, the core can continue running.
+    Resume,
+    /// The request waits for input on stdin. The core stays halted on it, and the request is
+    /// handled again the next time the core is checked.
+    Pending,
+    /// The target application exited with the given code.
+    Exited(u8),
+}
+
+/// Host side state for semihosting requests of the target.
+///
+/// Console output is collected for the GDB console, see [`SemihostingHost::take_console_output`],
+/// and console input is read from stdin of the GDB server. Other paths are only opened if a
+/// directory for file I/O was configured, and only below it.
+pub(crate) struct SemihostingHost {
+    /// The directory below which the target can open files, file I/O is disabled if `None`.
+    root: Option<PathBuf>,
+    files: HashMap<u32, File>,
+    next_handle: u32,
+    console_output: Vec<u8>,
+    /// Chunks read from stdin by a background thread, started on the first read of the target.
+    stdin: Option<Receiver<Vec<u8>>>,
+    stdin_buffer: Vec<u8>,
+    stdin_closed: bool,
+}
+
+impl SemihostingHost {
+    pub(crate) fn new(root: Option<PathBuf>) -> Self {
+        Self {
+            root,
+            files: HashMap::new(),
+            next_handle: STDERR_HANDLE + 1,
+            console_output: Vec::new(),
+            stdin: None,
+            stdin_buffer: Vec::new(),
+            stdin_closed: false,
+        }
+    }
+
+    /// Handle a semihosting request, if `core` is halted on one.
+    ///
+    /// Returns `None` if the core did not halt because of semihosting.
+    pub(crate) fn handle(&mut self, core: &mut Core) -> Result<Option<SemihostingOutcome>, Error> {
+        let Some(command) = decode_semihosting_syscall(core)? else {
+            return Ok(None);
+        };
+
+        tracing::debug!("Semihosting request: {:?}", command);
+
+        let result = match command {
+            SemihostingCommand::Open { path, mode } => self.open(&path, mode),
+            SemihostingCommand::Close { handle } => match self.files.remove(&handle) {
+                Some(_) => 0,
+                None if handle <= STDERR_HANDLE => 0,
+                None => RESULT_ERROR,
+            },
+            SemihostingCommand::WriteC(c) => {
+                self.console_output.push(c);
+                0
+            }
+            SemihostingCommand::Write0(string) => {
+                self.console_output.extend_from_slice(&string);
+                0
+            }
+            SemihostingCommand::Write {
+                handle,
+                data,
+                length,
+            } => {
+                let written = match handle {
+                    STDOUT_HANDLE | STDERR_HANDLE => {
+                        self.console_output.extend_from_slice(&data);
+                        true
+                    }
+                    _ => self
+                        .files
+                        .get_mut(&handle)
+                        .is_some_and(|file| file.write_all(&data).is_ok()),
+                };
+
+                // SYS_WRITE returns the number of bytes which were *not* written.
+                if written {
+                    length - data.len() as u32
+                } else {
+                    length
+                }
+            }
+            SemihostingCommand::Read {
+                handle,
+                buffer_address,
+                length,
+            } => {
+                let mut buffer = vec![0; length.min(MAX_TRANSFER_LENGTH) as usize];
+                let read = match handle {
+                    STDIN_HANDLE => match self.read_stdin(&mut buffer) {
+                        Some(read) => Some(read),
+                        None => return Ok(Some(SemihostingOutcome::Pending)),
+                    },
+                    _ => self
+                        .files
+                        .get_mut(&handle)
+                        .and_then(|file| file.read(&mut buffer).ok()),
+                };
+
+                match read {
+                    Some(read) => {
+                        complete_semihosting_read(core, buffer_address, length, &buffer[..read])?;
+                        return Ok(Some(SemihostingOutcome::Resume));
+                    }
+                    None => RESULT_ERROR,
+                }
+            }
+            SemihostingCommand::Exit { reason } => {
+                let code = if reason == ADP_STOPPED_APPLICATION_EXIT {
+                    0
+                } else {
+                    1
+                };
+
+                return Ok(Some(SemihostingOutcome::Exited(code)));
+            }
+            SemihostingCommand::Unknown { operation, .. } => {
+                tracing::warn!("Unsupported semihosting operation {:#x}", operation);
+                RESULT_ERROR
+            }
+        };
+
+        complete_semihosting_syscall(core, result)?;
+
+        Ok(Some(SemihostingOutcome::Resume))
+    }
+
+    /// Take the console output of the target, which was not sent to GDB yet.
+    pub(crate) fn take_console_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.console_output)
+    }
+
+    fn open(&mut self, path: &str, mode: u32) -> u32 {
+        if path == ":tt" {
+            return match mode {
+                0..=3 => STDIN_HANDLE,
+                4..=7 => STDOUT_HANDLE,
+                _ => STDERR_HANDLE,
+            };
+        }
+
+        let Some(host_path) = self.resolve(path) else {
+            tracing::warn!(
+                "Semihosting: refusing to open '{}', file I/O is only allowed below the configured directory",
+                path
+            );
+            return RESULT_ERROR;
+        };
+
+        let mut options = OpenOptions::new();
+        match mode {
+            0 | 1 => options.read(true),
+            2 | 3 => options.read(true).write(true),
+            4 | 5 => options.write(true).create(true).truncate(true),
+            6 | 7 => options.read(true).write(true).create(true).truncate(true),
+            8 | 9 => options.append(true).create(true),
+            10 | 11 => options.read(true).append(true).create(true),
+            _ => return RESULT_ERROR,
+        };
+
+        match options.open(&host_path) {
+            Ok(file) => {
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                self.files.insert(handle, file);
+                handle
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Semihosting: failed to open '{}': {}",
+                    host_path.display(),
+                    e
+                );
+                RESULT_ERROR
+            }
+        }
+    }
+
+    /// The host path for `path` of the target, if file I/O is enabled and `path` is a relative
+    /// path which stays below the directory for file I/O.
+    ///
+    /// Symbolic links are resolved, so a link below the directory cannot point outside of it.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let root = self.root.as_ref()?.canonicalize().ok()?;
+        let path = Path::new(path);
+
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return None;
+        }
+
+        let host_path = root.join(path);
+        let resolved = match host_path.canonicalize() {
+            Ok(resolved) => resolved,
+            // A dangling link would be followed when the file is created.
+            Err(_) if host_path.symlink_metadata().is_ok() => return None,
+            // A new file is created in an existing directory.
+            Err(_) => host_path
+                .parent()?
+                .canonicalize()
+                .ok()?
+                .join(host_path.file_name()?),
+        };
+
+        resolved.starts_with(&root).then_some(resolved)
+    }
+
+    /// Read the available input from stdin into `buffer`, without waiting for more.
+    ///
+    /// Returns `None` if no input is available yet, and `Some(0)` once stdin was closed.
+    fn read_stdin(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        let stdin = self.stdin.get_or_insert_with(spawn_stdin_reader);
+
+        while self.stdin_buffer.is_empty() && !self.stdin_closed {
+            match stdin.try_recv() {
+                Ok(data) => self.stdin_buffer = data,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => self.stdin_closed = true,
+            }
+        }
+
+        let read = self.stdin_buffer.len().min(buffer.len());
+        buffer[..read].copy_from_slice(&self.stdin_buffer[..read]);
+        self.stdin_buffer.drain(..read);
+
+        Some(read)
+    }
+}
+
+/// Read stdin in a background thread, as reading it blocks until input is available.
+///
+/// The thread ends when stdin is closed. Otherwise it keeps waiting for input until the GDB
+/// server exits.
+fn spawn_stdin_reader() -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut buffer = [0; 256];
+        loop {
+            match std::io::stdin().read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => {
+                    if sender.send(buffer[..read].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Follows the packets GDB sends, to know whether it still acknowledges the packets it receives.
+///
+/// GDB stops doing so once gdbstub accepted its `QStartNoAckMode` packet.
+#[derive(Default)]
+pub(crate) struct AckMode {
+    packet: Vec<u8>,
+    in_packet: bool,
+    disabled: bool,
+}
+
+impl AckMode {
+    const NO_ACK_PACKET: &'static [u8] = b"QStartNoAckMode";
+
+    /// Follow a byte received from GDB.
+    pub(crate) fn received(&mut self, byte: u8) {
+        if !self.in_packet {
+            if byte == b'$' {
+                self.in_packet = true;
+                self.packet.clear();
+            }
+        } else if byte == b'#' {
+            self.in_packet = false;
+            self.disabled |= self.packet == Self::NO_ACK_PACKET;
+        } else if self.packet.len() <= Self::NO_ACK_PACKET.len() {
+            self.packet.push(byte);
+        }
+    }
+
+    /// Returns `true` if GDB acknowledges packets.
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.disabled
+    }
+}
+
+/// Send console output of the target to GDB over `conn`, see [`write_console_output`].
+pub(crate) fn send_console_output(
+    conn: &mut TcpStream,
+    data: &[u8],
+    ack_mode: &AckMode,
+    pending: &mut VecDeque<u8>,
+) -> std::io::Result<()> {
+    // gdbstub leaves the connection non-blocking after checking for input.
+    conn.set_nonblocking(false)?;
+    conn.set_read_timeout(Some(CONSOLE_ACK_TIMEOUT))?;
+
+    let result = write_console_output(conn, data, ack_mode.is_enabled(), pending);
+
+    conn.set_read_timeout(None)?;
+
+    result
+}
+
+/// Send console output of the target to GDB, which shows it in its console.
+///
+/// This uses `O` packets, which GDB accepts while the target is running. They are sent next to
+/// gdbstub, so if GDB `acknowledges` packets, its acknowledgement of each packet is consumed
+/// here, and the packet is sent again if GDB rejected it. Other bytes received in the meantime,
+/// like an interrupt, are added to `pending`, to be passed on to gdbstub.
+fn write_console_output(
+    conn: &mut (impl Read + Write),
+    data: &[u8],
+    acknowledges: bool,
+    pending: &mut VecDeque<u8>,
+) -> std::io::Result<()> {
+    for chunk in data.chunks(MAX_CONSOLE_PACKET) {
+        let packet = console_packet(chunk);
+
+        let mut retries = 0;
+        loop {
+            conn.write_all(&packet)?;
+            conn.flush()?;
+
+            if !acknowledges || wait_for_ack(conn, pending)? {
+                break;
+            }
+
+            retries += 1;
+            if retries > MAX_CONSOLE_RETRIES {
+                return Err(std::io::Error::new(
+                    ErrorKind::Other,
+                    "GDB rejected the console output",
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Wait for GDB to acknowledge a packet, and return `false` if GDB rejected it.
+///
+/// If GDB does not answer in time, the packet is assumed to be received.
+fn wait_for_ack(conn: &mut impl Read, pending: &mut VecDeque<u8>) -> std::io::Result<bool> {
+    let mut byte = [0];
+
+    loop {
+        match conn.read(&mut byte) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(_) => match byte[0] {
+                b'+' => return Ok(true),
+                b'-' => return Ok(false),
+                other => pending.push_back(other),
+            },
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                tracing::warn!("GDB did not acknowledge the semihosting console output");
+                return Ok(true);
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Encode `data` as an `O` packet, which contains the data in hex.
+fn console_packet(data: &[u8]) -> Vec<u8> {
+    let mut payload = String::from("O");
+    for byte in data {
+        payload.push_str(&format!("{byte:02x}"));
+    }
+
+    let checksum = payload.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+
+    format!("${payload}#{checksum:02x}").into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A connection to GDB, which answers with `input`.
+    struct FakeGdb {
+        input: std::io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl FakeGdb {
+        fn new(input: &[u8]) -> Self {
+            Self {
+                input: std::io::Cursor::new(input.to_vec()),
+                output: Vec::new(),
+            }
+        }
+
+        fn packets(&self) -> usize {
+            self.output.iter().filter(|&&b| b == b'$').count()
+        }
+    }
+
+    impl Read for FakeGdb {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.input.read(buf)? {
+                0 => Err(ErrorKind::TimedOut.into()),
+                read => Ok(read),
+            }
+        }
+    }
+
+    impl Write for FakeGdb {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn console_output_is_hex_encoded() {
+        assert_eq!(console_packet(b"Hi\n"), b"$O48690a#bb");
+
+        let mut gdb = FakeGdb::new(b"");
+        let mut pending = VecDeque::new();
+        write_console_output(
+            &mut gdb,
+            &[b'a'; MAX_CONSOLE_PACKET + 1],
+            false,
+            &mut pending,
+        )
+        .unwrap();
+        assert_eq!(gdb.packets(), 2);
+    }
+
+    #[test]
+    fn console_output_consumes_acks() {
+        // GDB rejects the first packet, and interrupts the target before acknowledging it.
+        let mut gdb = FakeGdb::new(b"-\x03+");
+        let mut pending = VecDeque::new();
+        write_console_output(&mut gdb, b"Hi\n", true, &mut pending).unwrap();
+
+        assert_eq!(gdb.packets(), 2);
+        assert_eq!(pending, [0x03]);
+        assert_eq!(gdb.input.position(), 3);
+    }
+
+    #[test]
+    fn ack_mode_follows_no_ack_packet() {
+        let mut ack_mode = AckMode::default();
+        for &byte in b"+$QStartNoAckModeX#00+$qSupported#37" {
+            ack_mode.received(byte);
+        }
+        assert!(ack_mode.is_enabled());
+
+        for &byte in b"+$QStartNoAckMode#b0" {
+            ack_mode.received(byte);
+        }
+        assert!(!ack_mode.is_enabled());
+    }
+
+    #[test]
+    fn files_are_only_opened_below_the_root() {
+        let host = SemihostingHost::new(None);
+        assert_eq!(host.resolve("log.txt"), None);
+
+        let directory =
+            std::env::temp_dir().join(format!("probe-rs-semihosting-{}", std::process::id()));
+        let root = directory.join("root");
+        std::fs::create_dir_all(root.join("out")).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let host = SemihostingHost::new(Some(root.clone()));
+        let resolved = host.resolve("./out/log.txt");
+        let rejected = [
+            host.resolve("/etc/passwd"),
+            host.resolve("../secret"),
+            host.resolve("out/../../secret"),
+            host.resolve("missing/log.txt"),
+        ];
+
+        #[cfg(unix)]
+        let links = {
+            std::os::unix::fs::symlink(&directory, root.join("escape")).unwrap();
+            std::os::unix::fs::symlink(directory.join("secret"), root.join("dangling")).unwrap();
+            std::os::unix::fs::symlink(root.join("out"), root.join("inside")).unwrap();
+
+            [
+                host.resolve("escape/secret"),
+                host.resolve("dangling"),
+                host.resolve("inside/log.txt"),
+            ]
+        };
+
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(resolved, Some(root.join("out/log.txt")));
+        assert_eq!(rejected, [None, None, None, None]);
+        #[cfg(unix)]
+        assert_eq!(links, [None, None, Some(root.join("out/log.txt"))]);
+    }
+}
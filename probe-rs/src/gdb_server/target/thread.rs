@@ -0,0 +1,23 @@
+use super::RuntimeTarget;
+use crate::gdb_server::target::utils::copy_to_buf;
+
+use gdbstub::target::ext::thread_extra_info::ThreadExtraInfo;
+
+impl ThreadExtraInfo for RuntimeTarget<'_> {
+    fn thread_extra_info(
+        &self,
+        tid: gdbstub::common::Tid,
+        buf: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        if let Some(thread) = self.rtos_thread(tid) {
+            let info = format!("{} ({:?})", thread.name, thread.state);
+
+            return Ok(copy_to_buf(info.as_bytes(), buf));
+        }
+
+        let session = self.session.lock().unwrap();
+        let name = &session.target().cores[tid.get() - 1].name;
+
+        Ok(copy_to_buf(name.as_bytes(), buf))
+    }
+}
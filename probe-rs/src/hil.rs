@@ -0,0 +1,350 @@
+//! Helpers for hardware-in-the-loop tests
+//!
+//! Tests running against real hardware usually need the same primitives: waiting until the
+//! target reaches some state, checking registers, and collecting enough information to debug
+//! a failure after the fact. This module provides them on top of [`Core`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe_rs::hil::{capture_on_failure, expect_halt_at, wait_for_memory_value};
+//! use probe_rs::{Permissions, Session};
+//! use std::time::Duration;
+//!
+//! let mut session = Session::auto_attach("nrf52840_xxAA", Permissions::default())?;
+//! let mut core = session.core(0)?;
+//!
+//! let result = capture_on_failure(&mut core, &[0x2000_0000..0x2000_0400], None, |core| {
+//!     core.run()?;
+//!     // Wait until the firmware reports that it is initialized.
+//!     wait_for_memory_value(core, 0x2000_0000, 0x1, 0x1, Duration::from_secs(1))?;
+//!     expect_halt_at(core, 0x0800_1234, Duration::from_secs(5))
+//! });
+//!
+//! if let Err(failure) = result {
+//!     eprintln!("{}", failure.error);
+//!     if let Some(capture) = failure.capture {
+//!         eprintln!("{}", capture.dump);
+//!     }
+//! }
+//! # Ok::<(), probe_rs::Error>(())
+//! ```
+
+use crate::rtt::UpChannel;
+use crate::{Core, CoreStatus, Error, HaltReason, MemoryInterface, RegisterId, RegisterValue};
+
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// How long to wait between reads of the target state.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The maximum time to wait for the core to halt before a core dump is captured.
+const CAPTURE_HALT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A failed assertion about the state of the target.
+#[derive(thiserror::Error, Debug)]
+pub enum AssertionError {
+    /// The memory did not reach the expected value in time.
+    #[error("Timed out after {timeout:?} waiting for {address:#010x} to become {expected:#010x} (mask {mask:#010x}), the last value was {actual:#010x}")]
+    MemoryTimeout {
+        /// The address which was read.
+        address: u64,
+        /// The expected value, after applying `mask`.
+        expected: u32,
+        /// The mask applied to the memory value before comparing.
+        mask: u32,
+        /// The last value which was read, without the mask applied.
+        actual: u32,
+        /// The time which was waited.
+        timeout: Duration,
+    },
+    /// A register did not have the expected value.
+    #[error("Register {register} is {actual:#x}, expected {expected:#x}")]
+    RegisterMismatch {
+        /// The name of the register.
+        register: String,
+        /// The expected value.
+        expected: u64,
+        /// The actual value.
+        actual: u64,
+    },
+    /// The core did not halt in time.
+    #[error("The core did not halt within {0:?}")]
+    NotHalted(Duration),
+    /// The core halted at a different address than expected.
+    #[error("The core halted at {actual:#010x} instead of {expected:#010x} ({reason:?})")]
+    UnexpectedHaltAddress {
+        /// The expected address.
+        expected: u64,
+        /// The address the core halted at.
+        actual: u64,
+        /// The reason for the halt.
+        reason: HaltReason,
+    },
+    /// Accessing the target failed.
+    #[error("Failed to access the target")]
+    Probe(#[from] Error),
+}
+
+/// Wait until the 32-bit word at `address`, masked with `mask`, equals `expected`.
+///
+/// The memory is read while the core is running, so the target has to support memory access
+/// in that state.
+pub fn wait_for_memory_value(
+    core: &mut Core,
+    address: u64,
+    expected: u32,
+    mask: u32,
+    timeout: Duration,
+) -> Result<(), AssertionError> {
+    let start = Instant::now();
+
+    loop {
+        let actual = core.read_word_32(address)?;
+        if actual & mask == expected & mask {
+            return Ok(());
+        }
+
+        if start.elapsed() > timeout {
+            return Err(AssertionError::MemoryTimeout {
+                address,
+                expected,
+                mask,
+                actual,
+                timeout,
+            });
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Check that `register` of the halted core has the value `expected`.
+pub fn assert_register_eq(
+    core: &mut Core,
+    register: impl Into<RegisterId>,
+    expected: u64,
+) -> Result<(), AssertionError> {
+    let register = register.into();
+    let actual: u64 = core.read_core_reg(register)?;
+
+    if actual == expected {
+        return Ok(());
+    }
+
+    let name = core
+        .registers()
+        .all_registers()
+        .find(|r| r.id() == register)
+        .map(|r| r.name().to_owned())
+        .unwrap_or_else(|| format!("{register:?}"));
+
+    Err(AssertionError::RegisterMismatch {
+        register: name,
+        expected,
+        actual,
+    })
+}
+
+/// Wait until the core halts, and check that it halted at `address`.
+///
+/// The Thumb bit of `address` is ignored, so the address of a function symbol can be used.
+/// Returns the reason for the halt.
+pub fn expect_halt_at(
+    core: &mut Core,
+    address: u64,
+    timeout: Duration,
+) -> Result<HaltReason, AssertionError> {
+    let reason = wait_for_halt(core, timeout)?;
+
+    let expected = address & !1;
+    let actual: u64 = core.read_core_reg(core.program_counter())?;
+
+    if actual != expected {
+        return Err(AssertionError::UnexpectedHaltAddress {
+            expected,
+            actual,
+            reason,
+        });
+    }
+
+    Ok(reason)
+}
+
+fn wait_for_halt(core: &mut Core, timeout: Duration) -> Result<HaltReason, AssertionError> {
+    let start = Instant::now();
+
+    loop {
+        if let CoreStatus::Halted(reason) = core.status()? {
+            return Ok(reason);
+        }
+
+        if start.elapsed() > timeout {
+            return Err(AssertionError::NotHalted(timeout));
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// The registers and selected memory ranges of a halted core.
+#[derive(Debug, Clone, Default)]
+pub struct CoreDump {
+    /// The name and value of every register which could be read.
+    pub registers: Vec<(&'static str, RegisterValue)>,
+    /// The start address and contents of every captured memory range.
+    pub memory: Vec<(u64, Vec<u8>)>,
+}
+
+impl CoreDump {
+    /// Halt the core, if it is running, and read all registers and the memory in `ranges`.
+    ///
+    /// Registers which cannot be read, for example FPU registers of a core with disabled FPU,
+    /// are skipped.
+    pub fn capture(core: &mut Core, ranges: &[Range<u64>]) -> Result<Self, Error> {
+        if !core.core_halted()? {
+            core.halt(CAPTURE_HALT_TIMEOUT)?;
+        }
+
+        let registers: Vec<_> = core
+            .registers()
+            .all_registers()
+            .map(|register| (register.name(), register.id()))
+            .collect();
+
+        let registers = registers
+            .into_iter()
+            .filter_map(|(name, id)| match core.read_core_reg::<RegisterValue>(id) {
+                Ok(value) => Some((name, value)),
+                Err(e) => {
+                    tracing::debug!("Skipping register {} in core dump: {}", name, e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut memory = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let mut data = vec![0; (range.end - range.start) as usize];
+            core.read(range.start, &mut data)?;
+            memory.push((range.start, data));
+        }
+
+        Ok(Self { registers, memory })
+    }
+}
+
+impl fmt::Display for CoreDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.registers {
+            writeln!(f, "{name:>8}: {value}")?;
+        }
+
+        for (start, data) in &self.memory {
+            writeln!(f)?;
+            for (i, line) in data.chunks(16).enumerate() {
+                write!(f, "{:#010x}:", start + 16 * i as u64)?;
+                for byte in line {
+                    write!(f, " {byte:02x}")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The state of the target which was collected after a failure.
+#[derive(Debug, Clone)]
+pub struct FailureCapture {
+    /// The registers and memory of the core.
+    pub dump: CoreDump,
+    /// The RTT output which was not read yet when the failure occurred.
+    pub rtt_log: Vec<u8>,
+}
+
+/// A failed test, together with the state of the target.
+#[derive(Debug)]
+pub struct TestFailure<E> {
+    /// The error returned by the test.
+    pub error: E,
+    /// The collected target state, or `None` if it could not be collected.
+    pub capture: Option<FailureCapture>,
+}
+
+/// Run `test`, and collect the state of the target if it fails.
+///
+/// On failure, a [`CoreDump`] with the memory in `ranges` is captured, and the pending output
+/// of `rtt` is read. The core is left halted in that case.
+pub fn capture_on_failure<T, E>(
+    core: &mut Core,
+    ranges: &[Range<u64>],
+    rtt: Option<&UpChannel>,
+    test: impl FnOnce(&mut Core) -> Result<T, E>,
+) -> Result<T, TestFailure<E>> {
+    let error = match test(core) {
+        Ok(value) => return Ok(value),
+        Err(error) => error,
+    };
+
+    let capture = match capture(core, ranges, rtt) {
+        Ok(capture) => Some(capture),
+        Err(e) => {
+            tracing::warn!("Failed to capture the target state after a failure: {}", e);
+            None
+        }
+    };
+
+    Err(TestFailure { error, capture })
+}
+
+fn capture(
+    core: &mut Core,
+    ranges: &[Range<u64>],
+    rtt: Option<&UpChannel>,
+) -> Result<FailureCapture, Error> {
+    let mut rtt_log = Vec::new();
+
+    // Read RTT first, the core dump halts the core.
+    if let Some(channel) = rtt {
+        let mut buffer = [0u8; 1024];
+        loop {
+            match channel.read(core, &mut buffer) {
+                Ok(0) => break,
+                Ok(count) => rtt_log.extend_from_slice(&buffer[..count]),
+                Err(e) => {
+                    tracing::warn!("Failed to read the RTT log after a failure: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(FailureCapture {
+        dump: CoreDump::capture(core, ranges)?,
+        rtt_log,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoreDump;
+
+    #[test]
+    fn core_dump_display() {
+        let dump = CoreDump {
+            registers: vec![("R0", 0x1234u32.into()), ("PC", 0x0800_0100u32.into())],
+            memory: vec![(0x2000_0000, (0u8..20).collect())],
+        };
+
+        assert_eq!(
+            dump.to_string(),
+            "      R0: 0x00001234\n      PC: 0x08000100\n\n\
+             0x20000000: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n\
+             0x20000010: 10 11 12 13\n"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use crate::flashing::HookError;
+use crate::{Core, Error, Probe, Session};
+
+type ProbeHook = Arc<dyn Fn(&mut Probe) -> Result<(), HookError> + Send + Sync>;
+type SessionHook = Arc<dyn Fn(&mut Session) -> Result<(), HookError> + Send + Sync>;
+type CoreHook = Arc<dyn Fn(&mut Core<'_>) -> Result<(), HookError> + Send + Sync>;
+
+/// User registered hooks which are run at points of the lifecycle of a [`Session`].
+///
+/// They allow embedding tools to perform board specific setup, for example to enable an
+/// external power supply or switch a multiplexer, without changing the debug sequence
+/// of the target.
+///
+/// - Pre-attach hooks run before the probe connects to the target.
+/// - Post-attach hooks run after the session was opened.
+/// - Pre-flash hooks run at the start of [`FlashLoader::commit`](crate::flashing::FlashLoader::commit).
+/// - Post-reset hooks run after [`Core::reset`] and [`Core::reset_and_halt`].
+/// - On-halt hooks run after [`Core::halt`] or [`Core::wait_for_core_halted`] found the core
+///   halted. They should not halt or resume the core themselves.
+///
+/// The hooks are set on the [`Probe`] with [`Probe::set_session_hooks`] before attaching, and
+/// can be extended through [`Session::hooks_mut`] later. Hooks are run in the order in which
+/// they were registered, and a failing hook aborts the operation.
+///
+/// # Example
+///
+/// ```no_run
+/// use probe_rs::{Permissions, Probe, SessionHooks};
+///
+/// let mut probe = Probe::open(&Probe::list_all()[0])?;
+///
+/// let mut hooks = SessionHooks::new();
+/// hooks.pre_attach(|probe| {
+///     // Power up the target through the probe, then give it time to boot.
+///     probe.target_reset_deassert()?;
+///     std::thread::sleep(std::time::Duration::from_millis(10));
+///     Ok(())
+/// });
+/// probe.set_session_hooks(hooks);
+///
+/// let session = probe.attach("nrf52840_xxAA", Permissions::default())?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct SessionHooks {
+    pre_attach: Vec<ProbeHook>,
+    post_attach: Vec<SessionHook>,
+    pre_flash: Vec<SessionHook>,
+    post_reset: Vec<CoreHook>,
+    on_halt: Vec<CoreHook>,
+}
+
+impl SessionHooks {
+    /// Create an empty set of hooks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a hook which is run before the probe connects to the target.
+    pub fn pre_attach(
+        &mut self,
+        hook: impl Fn(&mut Probe) -> Result<(), HookError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.pre_attach.push(Arc::new(hook));
+        self
+    }
+
+    /// Register a hook which is run after the session was opened.
+    pub fn post_attach(
+        &mut self,
+        hook: impl Fn(&mut Session) -> Result<(), HookError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.post_attach.push(Arc::new(hook));
+        self
+    }
+
+    /// Register a hook which is run before an image is flashed.
+    pub fn pre_flash(
+        &mut self,
+        hook: impl Fn(&mut Session) -> Result<(), HookError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.pre_flash.push(Arc::new(hook));
+        self
+    }
+
+    /// Register a hook which is run after a core was reset.
+    pub fn post_reset(
+        &mut self,
+        hook: impl Fn(&mut Core<'_>) -> Result<(), HookError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.post_reset.push(Arc::new(hook));
+        self
+    }
+
+    /// Register a hook which is run after a core halted.
+    pub fn on_halt(
+        &mut self,
+        hook: impl Fn(&mut Core<'_>) -> Result<(), HookError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.on_halt.push(Arc::new(hook));
+        self
+    }
+
+    pub(crate) fn run_pre_attach(&self, probe: &mut Probe) -> Result<(), Error> {
+        for hook in &self.pre_attach {
+            hook(probe).map_err(|e| Error::Hook("pre-attach", e))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn run_post_reset(&self, core: &mut Core<'_>) -> Result<(), Error> {
+        for hook in &self.post_reset {
+            hook(core).map_err(|e| Error::Hook("post-reset", e))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn run_on_halt(&self, core: &mut Core<'_>) -> Result<(), Error> {
+        for hook in &self.on_halt {
+            hook(core).map_err(|e| Error::Hook("on-halt", e))?;
+        }
+
+        Ok(())
+    }
+}
+
+// The hooks of a session are cloned before they are run, as they can modify the hooks
+// of the session.
+impl Session {
+    pub(crate) fn run_post_attach_hooks(&mut self) -> Result<(), Error> {
+        for hook in self.hooks().post_attach.clone() {
+            hook(self).map_err(|e| Error::Hook("post-attach", e))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn run_pre_flash_hooks(&mut self) -> Result<(), Error> {
+        for hook in self.hooks().pre_flash.clone() {
+            hook(self).map_err(|e| Error::Hook("pre-flash", e))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SessionHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionHooks")
+            .field("pre_attach", &self.pre_attach.len())
+            .field("post_attach", &self.post_attach.len())
+            .field("pre_flash", &self.pre_flash.len())
+            .field("post_reset", &self.post_reset.len())
+            .field("on_halt", &self.on_halt.len())
+            .finish()
+    }
+}
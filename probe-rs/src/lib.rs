@@ -69,39 +69,70 @@ extern crate serde;
 
 /// All the interface bits for the different architectures.
 pub mod architecture;
+pub mod cancellation;
 pub mod config;
 
 #[warn(missing_docs)]
 mod core;
 pub mod debug;
 mod error;
+pub mod events;
 #[warn(missing_docs)]
 pub mod flashing;
 #[cfg(feature = "gdb-server")]
 pub mod gdb_server;
 #[warn(missing_docs)]
+#[cfg(feature = "rtt")]
+pub mod hil;
+#[warn(missing_docs)]
+mod hooks;
+#[warn(missing_docs)]
 mod memory;
 #[warn(missing_docs)]
+pub mod memory_stream;
+#[warn(missing_docs)]
+pub mod memory_watch;
+#[warn(missing_docs)]
 mod probe;
 #[warn(missing_docs)]
+pub mod ram_stub;
+#[warn(missing_docs)]
+pub mod register_snapshot;
+#[warn(missing_docs)]
+pub mod rtos;
+#[warn(missing_docs)]
 #[cfg(feature = "rtt")]
 pub mod rtt;
 #[warn(missing_docs)]
+#[cfg(feature = "rtt")]
+pub mod runner;
+#[warn(missing_docs)]
+pub mod semihosting;
+#[warn(missing_docs)]
 mod session;
+#[warn(missing_docs)]
+#[cfg(feature = "rtt")]
+pub mod test_runner;
 
 pub use crate::config::{CoreType, InstructionSet, Target};
 pub use crate::core::{
-    Architecture, BreakpointCause, Core, CoreInformation, CoreInterface, CoreRegister,
-    CoreRegisters, CoreState, CoreStatus, HaltReason, MemoryMappedRegister, RegisterId,
-    RegisterRole, RegisterValue, SpecificCoreState,
+    Architecture, Breakpoint, BreakpointBatch, BreakpointCause, BreakpointKind, BreakpointManager,
+    BreakpointUnit, Core, CoreInformation, CoreInterface, CoreRegister, CoreRegisters, CoreState,
+    CoreStatus, HaltEscalation, HaltOutcome, HaltPath, HaltReason, MemoryMappedRegister,
+    RegisterId, RegisterRole, RegisterValue, SpecificCoreState, UnsatisfiedBreakpoint, Watchpoint,
+    WatchpointKind,
 };
 pub use crate::error::Error;
+pub use crate::hooks::SessionHooks;
 pub use crate::memory::MemoryInterface;
 pub use crate::probe::{
     AttachMethod, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType,
-    Probe, ProbeCreationError, WireProtocol,
+    FirmwareInfo, FirmwareStatus, Probe, ProbeCreationError, ProbeSetting, ResetTiming, SwdTiming,
+    UsbSettings, WireProtocol,
+};
+pub use crate::session::{
+    AttachDiagnostics, AttachStep, PartialAttach, Permissions, RawDapAccessGuard, Session,
 };
-pub use crate::session::{Permissions, Session};
 
 // TODO: Hide behind feature
 pub use crate::probe::fake_probe::FakeProbe;
@@ -233,6 +233,74 @@ pub trait MemoryInterface {
         Ok(())
     }
 
+    /// Read the 32 bit word at `address`, pass it to `f`, and write the result back.
+    ///
+    /// The word is read and written with single 32 bit accesses, so this can be used for
+    /// peripheral registers. Returns the written value.
+    ///
+    /// The address has to be word aligned, otherwise [`Error::MemoryNotAligned`] is returned
+    /// without accessing the target.
+    fn modify_word_32(&mut self, address: u64, f: impl FnOnce(u32) -> u32) -> Result<u32, Error>
+    where
+        Self: Sized,
+    {
+        if address % 4 != 0 {
+            return Err(Error::MemoryNotAligned {
+                address,
+                alignment: 4,
+            });
+        }
+
+        let value = f(self.read_word_32(address)?);
+        self.write_word_32(address, value)?;
+
+        Ok(value)
+    }
+
+    /// Set the bits of the 32 bit word at `address` which are set in `mask` to the bits of
+    /// `value`, and keep the other bits, see [`MemoryInterface::modify_word_32`].
+    fn update_bits_32(&mut self, address: u64, mask: u32, value: u32) -> Result<(), Error> {
+        if address % 4 != 0 {
+            return Err(Error::MemoryNotAligned {
+                address,
+                alignment: 4,
+            });
+        }
+
+        let word = self.read_word_32(address)?;
+        self.write_word_32(address, (word & !mask) | (value & mask))
+    }
+
+    /// Set the bits in `mask` of the 32 bit word at `address`, see
+    /// [`MemoryInterface::update_bits_32`].
+    fn set_bits_32(&mut self, address: u64, mask: u32) -> Result<(), Error> {
+        self.update_bits_32(address, mask, mask)
+    }
+
+    /// Clear the bits in `mask` of the 32 bit word at `address`, see
+    /// [`MemoryInterface::update_bits_32`].
+    fn clear_bits_32(&mut self, address: u64, mask: u32) -> Result<(), Error> {
+        self.update_bits_32(address, mask, 0)
+    }
+
+    /// Set the bits of the byte at `address` which are set in `mask` to the bits of `value`,
+    /// and keep the other bits.
+    ///
+    /// The byte is read and written with single 8 bit accesses. If the platform does not
+    /// support them, [`Error::MemoryNotAligned`] is returned instead of accessing the
+    /// surrounding word.
+    fn update_bits_8(&mut self, address: u64, mask: u8, value: u8) -> Result<(), Error> {
+        if !self.supports_8bit_transfers()? {
+            return Err(Error::MemoryNotAligned {
+                address,
+                alignment: 4,
+            });
+        }
+
+        let byte = self.read_word_8(address)?;
+        self.write_word_8(address, (byte & !mask) | (value & mask))
+    }
+
     /// Returns whether the current platform supports native 8bit transfers.
     fn supports_8bit_transfers(&self) -> Result<bool, Error>;
 
@@ -0,0 +1,262 @@
+//! Streaming access to target memory.
+//!
+//! [`MemoryReader`] and [`MemoryWriter`] adapt a [`MemoryInterface`] to [`std::io::Read`] and
+//! [`std::io::Write`], so large memories, such as external RAM or flash, can be copied from and
+//! to files with [`std::io::copy`] without holding their whole content in host memory.
+//!
+//! The memory is accessed in chunks, which start at addresses aligned to the chunk size, so
+//! all but the first and the last access of a stream are word aligned. Unaligned bytes are
+//! written with byte accesses, or by rewriting the containing word if the target only supports
+//! word accesses. Each chunk is split into transfers of the block size of the probe by its
+//! driver, so the chunk size only bounds the host memory used, and the time a single call
+//! blocks.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe_rs::memory_stream::MemoryReader;
+//! use probe_rs::{Permissions, Session};
+//!
+//! let mut session = Session::auto_attach("nrf52840_xxAA", Permissions::default())?;
+//! let mut core = session.core(0)?;
+//!
+//! let mut reader = MemoryReader::new(&mut core, 0x2000_0000, 0x4_0000);
+//! let mut file = std::fs::File::create("ram.bin")?;
+//! std::io::copy(&mut reader, &mut file)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io;
+
+use crate::{Error, MemoryInterface};
+
+/// The default size of the chunks in which memory is accessed.
+pub const DEFAULT_CHUNK_SIZE: usize = 16 * 1024;
+
+/// The length of the chunk at `address`, which ends at the next multiple of `chunk_size`, or
+/// at `end`.
+fn chunk_len(address: u64, end: u64, chunk_size: usize) -> usize {
+    let chunk_end = (address / chunk_size as u64 + 1) * chunk_size as u64;
+
+    (chunk_end.min(end) - address) as usize
+}
+
+/// Reads `len` bytes of target memory starting at an address, see the [module
+/// documentation](self).
+pub struct MemoryReader<M: MemoryInterface> {
+    memory: M,
+    address: u64,
+    end: u64,
+    chunk_size: usize,
+}
+
+impl<M: MemoryInterface> MemoryReader<M> {
+    /// Create a reader of the `len` bytes of `memory` at `address`.
+    pub fn new(memory: M, address: u64, len: u64) -> Self {
+        Self {
+            memory,
+            address,
+            end: address.saturating_add(len),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Read the memory in chunks of `chunk_size` bytes, instead of [`DEFAULT_CHUNK_SIZE`].
+    ///
+    /// The chunk size has to be a power of two.
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(
+            chunk_size.is_power_of_two(),
+            "The chunk size has to be a power of two"
+        );
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// The address of the next byte which is read.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Return the memory interface, dropping the reader.
+    pub fn into_inner(self) -> M {
+        self.memory
+    }
+}
+
+impl<M: MemoryInterface> io::Read for MemoryReader<M> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = chunk_len(self.address, self.end, self.chunk_size).min(buf.len());
+        if len == 0 {
+            return Ok(0);
+        }
+
+        self.memory
+            .read(self.address, &mut buf[..len])
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.address += len as u64;
+
+        Ok(len)
+    }
+}
+
+/// Writes at most `len` bytes to target memory starting at an address, see the [module
+/// documentation](self).
+///
+/// Data is buffered until a chunk is complete, so [`MemoryWriter::finish`] or
+/// [`io::Write::flush`] has to be called after the last write. Errors of writes on drop are
+/// ignored.
+pub struct MemoryWriter<M: MemoryInterface> {
+    memory: M,
+    address: u64,
+    end: u64,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl<M: MemoryInterface> MemoryWriter<M> {
+    /// Create a writer of at most `len` bytes to `memory` at `address`.
+    ///
+    /// Writes past the end fail with [`io::ErrorKind::WriteZero`].
+    pub fn new(memory: M, address: u64, len: u64) -> Self {
+        Self {
+            memory,
+            address,
+            end: address.saturating_add(len),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Write the memory in chunks of `chunk_size` bytes, instead of [`DEFAULT_CHUNK_SIZE`].
+    ///
+    /// The chunk size has to be a power of two.
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(
+            chunk_size.is_power_of_two(),
+            "The chunk size has to be a power of two"
+        );
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// The address at which the next byte is written.
+    pub fn address(&self) -> u64 {
+        self.address + self.buffer.len() as u64
+    }
+
+    /// Write the buffered data.
+    ///
+    /// Unlike dropping the writer, this reports errors of the last write.
+    pub fn finish(mut self) -> io::Result<()> {
+        io::Write::flush(&mut self)
+    }
+
+    fn write_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        write_unaligned(&mut self.memory, self.address, &self.buffer)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.address += self.buffer.len() as u64;
+        self.buffer.clear();
+
+        Ok(())
+    }
+}
+
+/// Write `data` to `address`, with word writes for the aligned part.
+///
+/// The unaligned bytes at the start and the end are written with byte writes, or, if the target
+/// does not support them, by reading and writing the word which contains them.
+fn write_unaligned<M: MemoryInterface>(
+    memory: &mut M,
+    address: u64,
+    data: &[u8],
+) -> Result<(), Error> {
+    let head = (((4 - address % 4) % 4) as usize).min(data.len());
+    let (head, rest) = data.split_at(head);
+    let (body, tail) = rest.split_at(rest.len() / 4 * 4);
+
+    write_partial_word(memory, address, head)?;
+
+    let words = body
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect::<Vec<_>>();
+    memory.write_32(address + head.len() as u64, &words)?;
+
+    write_partial_word(memory, address + (head.len() + body.len()) as u64, tail)
+}
+
+/// Write less than a word of `data`, which does not cross a word boundary, to `address`.
+fn write_partial_word<M: MemoryInterface>(
+    memory: &mut M,
+    address: u64,
+    data: &[u8],
+) -> Result<(), Error> {
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    if memory.supports_8bit_transfers()? {
+        return memory.write_8(address, data);
+    }
+
+    let word_address = address & !3;
+    let offset = (address % 4) as usize;
+
+    let mut word = memory.read_word_32(word_address)?.to_le_bytes();
+    word[offset..offset + data.len()].copy_from_slice(data);
+
+    memory.write_word_32(word_address, u32::from_le_bytes(word))
+}
+
+impl<M: MemoryInterface> io::Write for MemoryWriter<M> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let address = self.address();
+        let len = chunk_len(address, self.end, self.chunk_size).min(buf.len());
+        if len == 0 {
+            return Ok(0);
+        }
+
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        let address = self.address();
+        if address % self.chunk_size as u64 == 0 || address == self.end {
+            // The chunk is complete.
+            self.write_buffer()?;
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buffer()?;
+        self.memory
+            .flush()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+impl<M: MemoryInterface> Drop for MemoryWriter<M> {
+    fn drop(&mut self) {
+        let _ = io::Write::flush(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::chunk_len;
+
+    #[test]
+    fn chunks_are_aligned() {
+        assert_eq!(chunk_len(0x1000, 0x3000, 0x1000), 0x1000);
+        assert_eq!(chunk_len(0x1004, 0x3000, 0x1000), 0xFFC);
+        assert_eq!(chunk_len(0x2F00, 0x2F10, 0x1000), 0x10);
+        assert_eq!(chunk_len(0x3000, 0x3000, 0x1000), 0);
+    }
+}
@@ -0,0 +1,206 @@
+//! Detection of memory changes made by the running target.
+//!
+//! Memory corruption by a peripheral or a DMA transfer is usually found with a watchpoint, but
+//! there are only a few comparators, and they are often all in use. A [`MemoryWatch`] instead
+//! reads the configured memory regions periodically while the target is running, and reports
+//! the address ranges which changed since the previous read, with the time the change was
+//! detected.
+//!
+//! Only changes which are still visible at the next read are detected, so values which are
+//! overwritten again within the polling interval are missed. Reading memory while the core is
+//! running requires that the target supports memory access in the background, as ARM targets do.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe_rs::memory_watch::MemoryWatch;
+//! use probe_rs::{Permissions, Session};
+//! use std::time::Duration;
+//!
+//! let mut session = Session::auto_attach("nrf52840_xxAA", Permissions::default())?;
+//! let mut core = session.core(0)?;
+//!
+//! let mut watch = MemoryWatch::new([(0x2000_0000..0x2000_0100)]);
+//! watch.run(&mut core, Duration::from_millis(10), |change| {
+//!     println!("{:?} changed at {:?}", change.range, change.timestamp);
+//!     true
+//! })?;
+//! # Ok::<(), probe_rs::Error>(())
+//! ```
+
+use crate::{Error, MemoryInterface};
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// A range of memory which changed between two reads of a [`MemoryWatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryChange {
+    /// The changed addresses.
+    pub range: Range<u64>,
+    /// The time the change was detected, relative to the first read of the watch.
+    pub timestamp: Duration,
+    /// The contents of the range at the previous read.
+    pub old: Vec<u8>,
+    /// The contents of the range at the current read.
+    pub new: Vec<u8>,
+}
+
+/// A watched memory region and its contents at the last read.
+#[derive(Debug)]
+struct WatchedRegion {
+    address: u64,
+    snapshot: Vec<u8>,
+}
+
+/// Periodic comparison of memory regions, see the [module documentation](self).
+#[derive(Debug)]
+pub struct MemoryWatch {
+    regions: Vec<WatchedRegion>,
+    /// The time of the first read, or `None` before it.
+    start: Option<Instant>,
+}
+
+impl MemoryWatch {
+    /// Create a watch of the memory `regions`.
+    pub fn new(regions: impl IntoIterator<Item = Range<u64>>) -> Self {
+        Self {
+            regions: regions
+                .into_iter()
+                .map(|range| WatchedRegion {
+                    address: range.start,
+                    snapshot: vec![0; range.end.saturating_sub(range.start) as usize],
+                })
+                .collect(),
+            start: None,
+        }
+    }
+
+    /// Read all regions and compare them with the previous read.
+    ///
+    /// The first call only records the current contents, and reports no changes.
+    pub fn poll(&mut self, memory: &mut impl MemoryInterface) -> Result<Vec<MemoryChange>, Error> {
+        let first = self.start.is_none();
+        let start = *self.start.get_or_insert_with(Instant::now);
+
+        let mut changes = Vec::new();
+
+        for region in &mut self.regions {
+            let mut current = vec![0; region.snapshot.len()];
+            memory.read(region.address, &mut current)?;
+            let timestamp = start.elapsed();
+
+            if !first {
+                changes.extend(diff(region.address, &region.snapshot, &current, timestamp));
+            }
+
+            region.snapshot = current;
+        }
+
+        Ok(changes)
+    }
+
+    /// Poll the regions every `interval` and call `on_change` for every change, until it
+    /// returns `false`.
+    ///
+    /// `on_change` is only called when the memory changes, so if it never does, this function
+    /// only returns when the operation is cancelled or reading the memory fails.
+    ///
+    /// This is a cancellation point, see [`cancellation`](crate::cancellation).
+    pub fn run(
+        &mut self,
+        memory: &mut impl MemoryInterface,
+        interval: Duration,
+        mut on_change: impl FnMut(&MemoryChange) -> bool,
+    ) -> Result<(), Error> {
+        loop {
+            crate::cancellation::check()?;
+
+            let poll_start = Instant::now();
+
+            for change in self.poll(memory)? {
+                if !on_change(&change) {
+                    return Ok(());
+                }
+            }
+
+            if let Some(remaining) = interval.checked_sub(poll_start.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+    }
+}
+
+/// The contiguous ranges which differ between `old` and `new`, the contents of the memory
+/// at `address`.
+fn diff(address: u64, old: &[u8], new: &[u8], timestamp: Duration) -> Vec<MemoryChange> {
+    let mut changes = Vec::new();
+    let mut start = None;
+
+    for i in 0..=old.len() {
+        let changed = i < old.len() && old[i] != new[i];
+
+        match (start, changed) {
+            (None, true) => start = Some(i),
+            (Some(first), false) => {
+                changes.push(MemoryChange {
+                    range: address + first as u64..address + i as u64,
+                    timestamp,
+                    old: old[first..i].to_vec(),
+                    new: new[first..i].to_vec(),
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, MemoryChange, MemoryWatch};
+    use crate::rtos::test_memory::TestMemory;
+    use std::time::Duration;
+
+    #[test]
+    fn contiguous_changes_are_merged() {
+        let changes = diff(
+            0x100,
+            &[0, 1, 2, 3, 4, 5],
+            &[9, 1, 7, 7, 4, 8],
+            Duration::ZERO,
+        );
+
+        let ranges: Vec<_> = changes.iter().map(|change| change.range.clone()).collect();
+        assert_eq!(ranges, [0x100..0x101, 0x102..0x104, 0x105..0x106]);
+        assert_eq!(changes[1].old, [2, 3]);
+        assert_eq!(changes[1].new, [7, 7]);
+    }
+
+    #[test]
+    fn poll_reports_changes_since_previous_read() {
+        let mut memory = TestMemory::default();
+        memory.set_word(0x2000_0000, 0x1234_5678);
+
+        let region = 0x2000_0000..0x2000_0010;
+        let mut watch = MemoryWatch::new([region]);
+        assert_eq!(watch.poll(&mut memory).unwrap(), []);
+        assert_eq!(watch.poll(&mut memory).unwrap(), []);
+
+        memory.set_bytes(0x2000_0009, &[0xAA, 0xBB]);
+        let changes = watch.poll(&mut memory).unwrap();
+
+        assert_eq!(
+            changes,
+            [MemoryChange {
+                range: 0x2000_0009..0x2000_000B,
+                timestamp: changes[0].timestamp,
+                old: vec![0, 0],
+                new: vec![0xAA, 0xBB],
+            }]
+        );
+        assert_eq!(watch.poll(&mut memory).unwrap(), []);
+    }
+}
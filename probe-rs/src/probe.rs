@@ -1,16 +1,20 @@
 pub(crate) mod cmsisdap;
 pub(crate) mod espusbjtag;
 pub(crate) mod fake_probe;
+mod firmware;
 #[cfg(feature = "ftdi")]
 pub(crate) mod ftdi;
 pub(crate) mod jlink;
+#[cfg(feature = "serial-port")]
+mod serial_port;
+mod settings;
 pub(crate) mod stlink;
 
 use self::espusbjtag::list_espjtag_devices;
 use crate::architecture::arm::ArmError;
 use crate::architecture::riscv::communication_interface::RiscvError;
 use crate::error::Error;
-use crate::Session;
+use crate::session::AttachProcess;
 use crate::{
     architecture::arm::communication_interface::UninitializedArmProbe,
     config::{RegistryError, TargetSelector},
@@ -19,14 +23,21 @@ use crate::{
     architecture::{
         arm::{
             communication_interface::DapProbe,
-            sequences::{ArmDebugSequence, DefaultArmSequence},
+            sequences::{
+                swj_dp_switch, ArmDebugSequence, BootMode, BootModePins, DefaultArmSequence,
+            },
             PortType, SwoAccess,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
     Permissions,
 };
+use crate::{PartialAttach, Session, SessionHooks};
 use jlink::list_jlink_devices;
+
+pub use firmware::{FirmwareInfo, FirmwareStatus};
+pub use settings::ProbeSetting;
+use std::time::{Duration, Instant};
 use std::{convert::TryFrom, fmt};
 
 /// Used to log warnings when the measured target voltage is
@@ -209,6 +220,17 @@ pub enum ProbeCreationError {
 pub struct Probe {
     inner: Box<dyn DebugProbe>,
     attached: bool,
+    /// A protocol was selected with [`Probe::select_protocol`] or [`Probe::switch_protocol`].
+    protocol_selected: bool,
+    /// Boxed, so a `Probe` returned in an error stays small.
+    session_settings: Box<SessionSettings>,
+}
+
+/// Settings of the sessions opened with a [`Probe`], which are applied when attaching.
+#[derive(Debug, Default)]
+struct SessionSettings {
+    boot_mode: Option<(BootMode, Option<BootModePins>)>,
+    hooks: SessionHooks,
 }
 
 impl Probe {
@@ -217,6 +239,8 @@ impl Probe {
         Self {
             inner: Box::new(probe),
             attached: false,
+            protocol_selected: false,
+            session_settings: Box::default(),
         }
     }
 
@@ -224,6 +248,8 @@ impl Probe {
         Self {
             inner: probe,
             attached: true,
+            protocol_selected: false,
+            session_settings: Box::default(),
         }
     }
 
@@ -232,6 +258,8 @@ impl Probe {
         Probe {
             inner: probe,
             attached: false,
+            protocol_selected: false,
+            session_settings: Box::default(),
         }
     }
 
@@ -311,6 +339,49 @@ impl Probe {
         Session::new(self, target.into(), AttachMethod::Normal, permissions)
     }
 
+    /// Attach to the chip without resetting, halting or erasing it.
+    ///
+    /// This can be used to extract RAM resident data, for example RTT buffers or logs written
+    /// before a crash, from a running or faulted target. No erase permissions are given to the
+    /// debug sequence of the target, and boot mode pins are not driven.
+    ///
+    /// The state of each core is read as soon as the debug port is connected, before the unlock
+    /// sequence of the target runs, and checked again after a re-attach requested by the unlock
+    /// sequence and at the end of the attach. [`Error::StateNotPreserved`] is returned if a core
+    /// was reset or halted during the attach. Only targets with Cortex-M cores can be verified, attaching to other targets
+    /// returns [`Error::NotImplemented`].
+    pub fn attach_preserving(
+        mut self,
+        target: impl Into<TargetSelector>,
+    ) -> Result<Session, Error> {
+        self.attached = true;
+
+        Session::new(
+            self,
+            target.into(),
+            AttachMethod::Preserve,
+            Permissions::new(),
+        )
+    }
+
+    /// Attach to the chip, and report how far the attach got if it fails.
+    ///
+    /// This is the same as [`Probe::attach`], [`Probe::attach_under_reset`] or
+    /// [`Probe::attach_preserving`], depending on `attach_method`, but a failure returns a
+    /// [`PartialAttach`] with the step which failed, the steps which succeeded, and
+    /// diagnostics such as the target voltage and the sticky error flags of the debug port.
+    /// The attach can be retried from the failed step with [`PartialAttach::retry`].
+    pub fn attach_with_diagnostics(
+        mut self,
+        target: impl Into<TargetSelector>,
+        attach_method: AttachMethod,
+        permissions: Permissions,
+    ) -> Result<Session, Box<PartialAttach>> {
+        self.attached = true;
+
+        AttachProcess::attach(self, target.into(), attach_method, permissions)
+    }
+
     /// Attach to a target without knowing what target you have at hand.
     /// This can be used for automatic device discovery or performing operations on an unspecified target.
     pub fn attach_to_unspecified(&mut self) -> Result<(), Error> {
@@ -363,14 +434,52 @@ impl Probe {
     }
 
     /// Selects the transport protocol to be used by the debug probe.
+    ///
+    /// Without a selected protocol, the protocol of ARM targets is detected when attaching.
     pub fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
         if !self.attached {
-            self.inner.select_protocol(protocol)
+            self.inner.select_protocol(protocol)?;
+            self.protocol_selected = true;
+            Ok(())
         } else {
             Err(DebugProbeError::Attached)
         }
     }
 
+    /// Switch the transport protocol of the probe to `protocol`, also while it is attached.
+    ///
+    /// Unlike [`Probe::select_protocol`], this does not require detaching or re-opening the probe.
+    /// An attached probe is re-initialized with the new protocol, and the SWJ-DP of the target
+    /// is switched with the JTAG-to-SWD or SWD-to-JTAG sequence. This allows to access a target
+    /// over JTAG first, for example to configure a JTAG router with [`DebugProbe::jtag_sequence`],
+    /// and then attach to it over SWD.
+    ///
+    /// Probes which do not support SWJ sequences, like the ST-Link, select the mode of the
+    /// SWJ-DP when they are re-initialized.
+    pub fn switch_protocol(&mut self, protocol: WireProtocol) -> Result<(), Error> {
+        if !self.attached {
+            return Ok(self.select_protocol(protocol)?);
+        }
+
+        self.protocol_selected = true;
+        if self.inner.active_protocol() == Some(protocol) {
+            return Ok(());
+        }
+
+        tracing::debug!("Switching the probe to {}", protocol);
+
+        self.detach()?;
+        self.inner.select_protocol(protocol)?;
+        self.inner.attach()?;
+        self.attached = true;
+
+        if let Some(dap_probe) = self.try_as_dap_probe() {
+            swj_dp_switch(dap_probe, protocol)?;
+        }
+
+        Ok(())
+    }
+
     /// Get the currently selected protocol
     ///
     /// Depending on the probe, this might not be available.
@@ -386,8 +495,24 @@ impl Probe {
     }
 
     /// Resets the target device.
+    ///
+    /// If an assert duration is configured with [`Probe::set_reset_timing`], reset is
+    /// asserted for this duration instead of the pulse width of the probe.
     pub fn target_reset(&mut self) -> Result<(), DebugProbeError> {
-        self.inner.target_reset()
+        let timing = self.inner.reset_timing();
+
+        if timing.assert_duration.is_zero() {
+            self.inner.target_reset()?;
+        } else {
+            let asserted = Instant::now();
+            self.inner.target_reset_assert()?;
+            timing.wait_asserted(asserted);
+            self.inner.target_reset_deassert()?;
+        }
+
+        timing.wait_released();
+
+        Ok(())
     }
 
     /// Asserts the reset of the target.
@@ -426,6 +551,86 @@ impl Probe {
         self.inner.speed_khz()
     }
 
+    /// Configure the SWD timing, see [`SwdTiming`].
+    pub fn set_swd_timing(&mut self, timing: SwdTiming) -> Result<(), DebugProbeError> {
+        if self.attached {
+            return Err(DebugProbeError::Attached);
+        }
+
+        if !(1..=4).contains(&timing.turnaround_cycles) {
+            return Err(DebugProbeError::Other(anyhow::anyhow!(
+                "The SWD turnaround period must be between 1 and 4 cycles, not {}",
+                timing.turnaround_cycles
+            )));
+        }
+
+        self.inner.set_swd_timing(timing)
+    }
+
+    /// Get the SWD timing used by the probe.
+    pub fn swd_timing(&self) -> SwdTiming {
+        self.inner.swd_timing()
+    }
+
+    /// Configure the timing of the hardware reset line, see [`ResetTiming`].
+    pub fn set_reset_timing(&mut self, timing: ResetTiming) -> Result<(), DebugProbeError> {
+        self.inner.set_reset_timing(timing)
+    }
+
+    /// Get the timing of the hardware reset line.
+    pub fn reset_timing(&self) -> ResetTiming {
+        self.inner.reset_timing()
+    }
+
+    /// Configure the USB transfers of the probe, see [`UsbSettings`].
+    pub fn set_usb_settings(&mut self, settings: UsbSettings) -> Result<(), DebugProbeError> {
+        self.inner.set_usb_settings(settings)
+    }
+
+    /// Get the settings of the USB transfers of the probe.
+    pub fn usb_settings(&self) -> UsbSettings {
+        self.inner.usb_settings()
+    }
+
+    /// Select the boot mode of the target for sessions opened with this probe.
+    ///
+    /// The boot mode pins are driven when attaching, after the probe connected to the target,
+    /// so the target boots in `mode` when attaching under reset, and after every following
+    /// hardware or system reset. If `pins` is `None`, the pins declared by the target
+    /// description are used, or else the ones declared by the debug sequence of the target.
+    ///
+    /// This is only supported by probes which can drive pins other than nRESET with `swj_pins`,
+    /// such as CMSIS-DAP probes.
+    pub fn set_boot_mode(&mut self, mode: BootMode, pins: Option<BootModePins>) {
+        self.session_settings.boot_mode = Some((mode, pins));
+    }
+
+    /// Get the boot mode selected with [`Probe::set_boot_mode`].
+    pub fn boot_mode(&self) -> Option<(BootMode, Option<BootModePins>)> {
+        self.session_settings.boot_mode
+    }
+
+    /// Set the hooks of sessions opened with this probe.
+    ///
+    /// The pre-attach hooks are run when attaching, the other hooks are passed on to the
+    /// [`Session`], see [`SessionHooks`].
+    pub fn set_session_hooks(&mut self, hooks: SessionHooks) {
+        self.session_settings.hooks = hooks;
+    }
+
+    pub(crate) fn take_session_hooks(&mut self) -> SessionHooks {
+        std::mem::take(&mut self.session_settings.hooks)
+    }
+
+    /// Shift a raw sequence over JTAG, see [`DebugProbe::jtag_sequence`].
+    pub fn jtag_sequence(
+        &mut self,
+        tms_bits: &[bool],
+        tdi_bits: &[bool],
+    ) -> Result<Vec<bool>, DebugProbeError> {
+        self.inner.jtag_sequence(tms_bits, tdi_bits)
+    }
+
     /// Check if the probe has an interface to
     /// debug ARM chips.
     pub fn has_arm_interface(&self) -> bool {
@@ -436,15 +641,23 @@ impl Probe {
     /// can be used to communicate with chips using the ARM architecture.
     ///
     /// If an error occurs while trying to connect, the probe is returned.
+    ///
+    /// If no protocol was selected with [`Probe::select_protocol`], the interface detects the
+    /// protocol the target uses, see [`UninitializedArmProbe::set_protocol_detection`].
     pub fn try_into_arm_interface<'probe>(
         self,
     ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Self, DebugProbeError)> {
         if !self.attached {
             Err((self, DebugProbeError::NotAttached))
         } else {
-            self.inner
+            let protocol_detection = !self.protocol_selected;
+            let mut interface = self
+                .inner
                 .try_get_arm_interface()
-                .map_err(|(probe, err)| (Probe::from_attached_probe(probe), err))
+                .map_err(|(probe, err)| (Probe::from_attached_probe(probe), err))?;
+            interface.set_protocol_detection(protocol_detection);
+
+            Ok(interface)
         }
     }
 
@@ -498,6 +711,36 @@ impl Probe {
     pub fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
         self.inner.get_target_voltage()
     }
+
+    /// Read the firmware version of the probe, and check if it should be updated.
+    ///
+    /// Returns `Ok(None)` if the probe does not report its firmware version.
+    pub fn firmware_info(&mut self) -> Result<Option<FirmwareInfo>, DebugProbeError> {
+        self.inner.firmware_info()
+    }
+
+    /// Write a new firmware `image` to the probe.
+    ///
+    /// This does not work on all probes, see [`DebugProbe::update_firmware`]. The probe cannot be
+    /// used while it is attached to a target.
+    pub fn update_firmware(&mut self, image: &[u8]) -> Result<(), DebugProbeError> {
+        if self.attached {
+            return Err(DebugProbeError::Attached);
+        }
+
+        self.inner.update_firmware(image)
+    }
+
+    /// Switch a setting of the probe on or off.
+    ///
+    /// This does not work on all probes, see [`DebugProbe::write_setting`].
+    pub fn write_setting(
+        &mut self,
+        setting: ProbeSetting,
+        enabled: bool,
+    ) -> Result<(), DebugProbeError> {
+        self.inner.write_setting(setting, enabled)
+    }
 }
 
 /// An abstraction over general debug probe functionality.
@@ -627,6 +870,203 @@ pub trait DebugProbe: Send + fmt::Debug {
     fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
         Ok(None)
     }
+
+    /// Read the firmware version of the probe, and check if it should be updated.
+    ///
+    /// Returns `Ok(None)` if the probe doesn't report its firmware version.
+    fn firmware_info(&mut self) -> Result<Option<FirmwareInfo>, DebugProbeError> {
+        Ok(None)
+    }
+
+    /// Write a new firmware `image` to the probe.
+    ///
+    /// This is not available on all probes.
+    fn update_firmware(&mut self, _image: &[u8]) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "firmware updates",
+        ))
+    }
+
+    /// Switch a setting of the probe on or off.
+    ///
+    /// Probes which do not have the setting return
+    /// [`DebugProbeError::CommandNotSupportedByProbe`].
+    fn write_setting(
+        &mut self,
+        _setting: ProbeSetting,
+        _enabled: bool,
+    ) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "probe settings",
+        ))
+    }
+
+    /// Shift a raw sequence over JTAG, and return the bits sampled on TDO.
+    ///
+    /// In every clock cycle, one bit of `tms_bits` is output on TMS and one bit of `tdi_bits`
+    /// on TDI, so both have to be of the same length. Unlike the register accesses of
+    /// [`JTAGAccess`], this does not track the TAP state: a sequence has to leave the TAP in
+    /// Run-Test/Idle if other JTAG operations follow.
+    ///
+    /// This can be used to access TAPs which are not part of the debug interface, for example
+    /// vendor specific unlock TAPs, or to configure JTAG routers in front of the DAP.
+    ///
+    /// This is not available on all probes.
+    fn jtag_sequence(
+        &mut self,
+        _tms_bits: &[bool],
+        _tdi_bits: &[bool],
+    ) -> Result<Vec<bool>, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "JTAG sequences",
+        ))
+    }
+
+    /// Get the SWD timing used by the probe.
+    fn swd_timing(&self) -> SwdTiming {
+        SwdTiming::default()
+    }
+
+    /// Set the SWD timing, which is applied when attaching to the target.
+    ///
+    /// Probes which cannot change the timing only accept the default timing.
+    fn set_swd_timing(&mut self, timing: SwdTiming) -> Result<(), DebugProbeError> {
+        if timing == SwdTiming::default() {
+            Ok(())
+        } else {
+            Err(DebugProbeError::CommandNotSupportedByProbe(
+                "SWD timing configuration",
+            ))
+        }
+    }
+
+    /// Get the timing of the hardware reset line.
+    fn reset_timing(&self) -> ResetTiming {
+        ResetTiming::default()
+    }
+
+    /// Set the timing of the hardware reset line.
+    ///
+    /// Probes which cannot store the timing only accept the default timing.
+    fn set_reset_timing(&mut self, timing: ResetTiming) -> Result<(), DebugProbeError> {
+        if timing == ResetTiming::default() {
+            Ok(())
+        } else {
+            Err(DebugProbeError::CommandNotSupportedByProbe(
+                "reset timing configuration",
+            ))
+        }
+    }
+
+    /// Get the settings of the USB transfers.
+    fn usb_settings(&self) -> UsbSettings {
+        UsbSettings::default()
+    }
+
+    /// Set the settings of the USB transfers.
+    ///
+    /// Probes which cannot change their USB transfers only accept the default settings.
+    fn set_usb_settings(&mut self, settings: UsbSettings) -> Result<(), DebugProbeError> {
+        if settings == UsbSettings::default() {
+            Ok(())
+        } else {
+            Err(DebugProbeError::CommandNotSupportedByProbe(
+                "USB transfer configuration",
+            ))
+        }
+    }
+}
+
+/// Check that a sequence for [`DebugProbe::jtag_sequence`] has a TDI bit for every TMS bit.
+pub(crate) fn check_jtag_sequence(
+    tms_bits: &[bool],
+    tdi_bits: &[bool],
+) -> Result<(), DebugProbeError> {
+    if tms_bits.len() == tdi_bits.len() {
+        Ok(())
+    } else {
+        Err(DebugProbeError::Other(anyhow::anyhow!(
+            "A JTAG sequence needs the same number of TMS and TDI bits, got {} TMS and {} TDI bits",
+            tms_bits.len(),
+            tdi_bits.len()
+        )))
+    }
+}
+
+/// Timing parameters of the SWD protocol.
+///
+/// Targets with slow level shifters or long cables can require additional idle cycles
+/// after transfers, or a longer turnaround period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwdTiming {
+    /// Idle cycles inserted after each transfer, in addition to the ones the probe inserts anyway.
+    pub idle_cycles: u8,
+    /// Length of the turnaround period in clock cycles, from 1 to 4.
+    pub turnaround_cycles: u8,
+}
+
+impl Default for SwdTiming {
+    fn default() -> Self {
+        Self {
+            idle_cycles: 0,
+            turnaround_cycles: 1,
+        }
+    }
+}
+
+/// Timing of the hardware reset line.
+///
+/// Boards with a large capacitor on the reset line can need a longer reset pulse,
+/// or more time after the line was released, than the probe uses by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResetTiming {
+    /// The minimum time reset is asserted for.
+    ///
+    /// If this is zero, the probe uses its own pulse width for [`Probe::target_reset`].
+    pub assert_duration: Duration,
+    /// The time to wait after releasing reset, before accessing the target.
+    pub release_delay: Duration,
+}
+
+impl ResetTiming {
+    /// Wait until reset, which was asserted at `asserted`, was held for the assert duration.
+    pub(crate) fn wait_asserted(&self, asserted: Instant) {
+        if let Some(remaining) = self.assert_duration.checked_sub(asserted.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    /// Wait for the release delay.
+    pub(crate) fn wait_released(&self) {
+        std::thread::sleep(self.release_delay);
+    }
+}
+
+/// Settings of the USB transfers between the host and the probe.
+///
+/// Slow USB hubs or isolators can need a longer timeout than the probe uses by default, for
+/// example while a large flash is erased, or smaller transfers. The defaults of the probe are
+/// used for settings which are `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UsbSettings {
+    /// The timeout of a single USB transfer.
+    pub timeout: Option<Duration>,
+    /// The maximum number of data bytes transferred by a single probe command.
+    ///
+    /// This can only reduce the size of the transfers below the maximum supported by the probe.
+    pub max_chunk_size: Option<usize>,
+}
+
+impl UsbSettings {
+    /// The number of bytes transferred by a single command of a probe, which supports at most
+    /// `probe_maximum` bytes, rounded down to a multiple of `granularity`.
+    pub(crate) fn chunk_size(&self, probe_maximum: usize, granularity: usize) -> usize {
+        let size = self
+            .max_chunk_size
+            .map_or(probe_maximum, |max| max.min(probe_maximum));
+
+        (size / granularity * granularity).max(granularity)
+    }
 }
 
 /// Denotes the type of a given [`DebugProbe`].
@@ -913,4 +1353,9 @@ pub enum AttachMethod {
     ///
     /// This is required on targets that can remap SWD pins or disable the SWD interface in sleep.
     UnderReset,
+    /// Attach without resetting, halting or erasing the target.
+    ///
+    /// This preserves RAM resident data such as RTT buffers or crash logs, see
+    /// [`Probe::attach_preserving`].
+    Preserve,
 }
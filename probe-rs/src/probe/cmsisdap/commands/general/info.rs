@@ -40,6 +40,8 @@ info_command!(0x07, TargetBoardVendorCommand, Option<String>);
 
 info_command!(0x08, TargetBoardNameCommand, Option<String>);
 
+info_command!(0x09, ProductFirmwareVersionCommand, Option<String>);
+
 info_command!(0xF0, CapabilitiesCommand, Capabilities);
 
 #[derive(Copy, Clone, Debug)]
@@ -131,7 +133,7 @@ pub struct Capabilities {
     pub(crate) swo_uart_implemented: bool,
     pub(crate) swo_manchester_implemented: bool,
     pub(crate) _atomic_commands_implemented: bool,
-    pub(crate) _test_domain_timer_implemented: bool,
+    pub(crate) test_domain_timer_implemented: bool,
     pub(crate) swo_streaming_trace_implemented: bool,
     pub(crate) _uart_communication_port_implemented: bool,
     pub(crate) uart_com_port_implemented: bool,
@@ -148,7 +150,7 @@ impl ParseFromResponse for Capabilities {
                 swo_uart_implemented: buffer[1] & 0x04 > 0,
                 swo_manchester_implemented: buffer[1] & 0x08 > 0,
                 _atomic_commands_implemented: buffer[1] & 0x10 > 0,
-                _test_domain_timer_implemented: buffer[1] & 0x20 > 0,
+                test_domain_timer_implemented: buffer[1] & 0x20 > 0,
                 swo_streaming_trace_implemented: buffer[1] & 0x40 > 0,
                 _uart_communication_port_implemented: buffer[1] & 0x80 > 0,
                 uart_com_port_implemented: false,
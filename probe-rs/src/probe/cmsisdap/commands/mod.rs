@@ -1,4 +1,5 @@
 pub mod general;
+pub mod msd;
 pub mod swd;
 pub mod swj;
 pub mod swo;
@@ -30,6 +31,10 @@ pub enum CmsisDapError {
     SwoReadError(#[source] rusb::Error),
     #[error("Could not determine a suitable packet size for this probe")]
     NoPacketSize,
+    #[error("DAPLink rejected the image with error code {0}")]
+    MsdError(u8),
+    #[error("The probe does not report itself as DAPLink, which is required for firmware updates")]
+    NotDapLink,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -79,6 +84,8 @@ pub enum CmsisDapDevice {
     V1 {
         handle: hidapi::HidDevice,
         report_size: usize,
+        /// The read timeout, the default is used if this is `None`.
+        timeout: Option<Duration>,
     },
 
     /// CMSIS-DAP v2 over WinUSB/Bulk.
@@ -90,6 +97,8 @@ pub enum CmsisDapDevice {
         in_ep: u8,
         max_packet_size: usize,
         swo_ep: Option<(u8, usize)>,
+        /// The transfer timeout, the default is used if this is `None`.
+        timeout: Option<Duration>,
     },
 }
 
@@ -97,13 +106,26 @@ impl CmsisDapDevice {
     /// Read from the probe into `buf`, returning the number of bytes read on success.
     fn read(&self, buf: &mut [u8]) -> Result<usize, SendError> {
         match self {
-            CmsisDapDevice::V1 { handle, .. } => match handle.read_timeout(buf, 1000)? {
-                // Timeout is not indicated by error, but by returning 0 read bytes
-                0 => Err(SendError::Timeout),
-                n => Ok(n),
-            },
-            CmsisDapDevice::V2 { handle, in_ep, .. } => {
-                let timeout = Duration::from_millis(100);
+            CmsisDapDevice::V1 {
+                handle, timeout, ..
+            } => {
+                let timeout_ms = timeout.map_or(1000, |timeout| {
+                    timeout.as_millis().min(i32::MAX as u128) as i32
+                });
+
+                match handle.read_timeout(buf, timeout_ms)? {
+                    // Timeout is not indicated by error, but by returning 0 read bytes
+                    0 => Err(SendError::Timeout),
+                    n => Ok(n),
+                }
+            }
+            CmsisDapDevice::V2 {
+                handle,
+                in_ep,
+                timeout,
+                ..
+            } => {
+                let timeout = timeout.unwrap_or(Duration::from_millis(100));
                 Ok(handle.read_bulk(*in_ep, buf, timeout)?)
             }
         }
@@ -113,8 +135,13 @@ impl CmsisDapDevice {
     fn write(&self, buf: &[u8]) -> Result<usize, SendError> {
         match self {
             CmsisDapDevice::V1 { handle, .. } => Ok(handle.write(buf)?),
-            CmsisDapDevice::V2 { handle, out_ep, .. } => {
-                let timeout = Duration::from_millis(100);
+            CmsisDapDevice::V2 {
+                handle,
+                out_ep,
+                timeout,
+                ..
+            } => {
+                let timeout = timeout.unwrap_or(Duration::from_millis(100));
                 // Skip first byte as it's set to 0 for HID transfers
                 Ok(handle.write_bulk(*out_ep, &buf[1..], timeout)?)
             }
@@ -180,6 +207,17 @@ impl CmsisDapDevice {
         }
     }
 
+    /// Set the timeout of transfers, `None` selects the default timeout.
+    ///
+    /// Writes to V1 devices block until the report was sent, so only reads time out.
+    pub(super) fn set_timeout(&mut self, new_timeout: Option<Duration>) {
+        match self {
+            CmsisDapDevice::V1 { timeout, .. } | CmsisDapDevice::V2 { timeout, .. } => {
+                *timeout = new_timeout;
+            }
+        }
+    }
+
     /// Attempt to determine the correct packet size for this device.
     ///
     /// Tries to request the CMSIS-DAP maximum packet size, allowing several
@@ -305,6 +343,9 @@ pub enum CommandId {
     UartControl = 0x22,
     UartStatus = 0x23,
     UartTransfer = 0x21,
+    MsdOpen = 0x8A,
+    MsdClose = 0x8B,
+    MsdWrite = 0x8C,
 }
 
 pub(crate) trait Request {
@@ -0,0 +1,109 @@
+//! DAPLink vendor commands, which write an image to the drag-and-drop programming stream of the
+//! mass storage device.
+
+use super::{CommandId, Request, SendError};
+
+/// The format of an image written to the stream.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamType {
+    Bin = 0,
+    Hex = 1,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct OpenRequest(pub StreamType);
+
+impl Request for OpenRequest {
+    const COMMAND_ID: CommandId = CommandId::MsdOpen;
+
+    type Response = MsdResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        buffer[0] = self.0 as u8;
+        Ok(1)
+    }
+
+    fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        Ok(MsdResponse(buffer[0]))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct CloseRequest;
+
+impl Request for CloseRequest {
+    const COMMAND_ID: CommandId = CommandId::MsdClose;
+
+    type Response = MsdResponse;
+
+    fn to_bytes(&self, _buffer: &mut [u8]) -> Result<usize, SendError> {
+        Ok(0)
+    }
+
+    fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        Ok(MsdResponse(buffer[0]))
+    }
+}
+
+/// Write a chunk of the image, which has to fit into a single packet after the command ID and
+/// the length byte.
+#[derive(Copy, Clone, Debug)]
+pub struct WriteRequest<'a>(pub &'a [u8]);
+
+impl Request for WriteRequest<'_> {
+    const COMMAND_ID: CommandId = CommandId::MsdWrite;
+
+    type Response = MsdResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        // The buffer is sized for the largest packet, so the chunk fits if the caller split the
+        // image according to the packet size.
+        buffer[0] = self.0.len() as u8;
+        buffer[1..1 + self.0.len()].copy_from_slice(self.0);
+        Ok(1 + self.0.len())
+    }
+
+    fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        Ok(MsdResponse(buffer[0]))
+    }
+}
+
+/// The DAPLink error code of a stream operation, zero on success.
+#[derive(Copy, Clone, Debug)]
+pub struct MsdResponse(pub(crate) u8);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn msd_requests_are_encoded() {
+        assert_eq!(OpenRequest::COMMAND_ID as u8, 0x8A);
+        assert_eq!(CloseRequest::COMMAND_ID as u8, 0x8B);
+        assert_eq!(WriteRequest::COMMAND_ID as u8, 0x8C);
+
+        let mut buffer = [0u8; 8];
+        assert_eq!(
+            OpenRequest(StreamType::Hex).to_bytes(&mut buffer).unwrap(),
+            1
+        );
+        assert_eq!(buffer[0], 1);
+
+        assert_eq!(CloseRequest.to_bytes(&mut buffer).unwrap(), 0);
+
+        let mut buffer = [0u8; 8];
+        assert_eq!(
+            WriteRequest(&[0xAA, 0xBB, 0xCC])
+                .to_bytes(&mut buffer)
+                .unwrap(),
+            4
+        );
+        assert_eq!(buffer[..4], [3, 0xAA, 0xBB, 0xCC]);
+
+        assert_eq!(
+            OpenRequest(StreamType::Bin).parse_response(&[0]).unwrap().0,
+            0
+        );
+    }
+}
@@ -1,7 +1,10 @@
 use super::super::{CommandId, Request, SendError, Status};
 
 #[derive(Debug)]
-pub struct ConfigureRequest;
+pub struct ConfigureRequest {
+    /// Length of the turnaround period in clock cycles, from 1 to 4.
+    pub turnaround_cycles: u8,
+}
 
 impl Request for ConfigureRequest {
     const COMMAND_ID: CommandId = CommandId::SwdConfigure;
@@ -9,8 +12,8 @@ impl Request for ConfigureRequest {
     type Response = ConfigureResponse;
 
     fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
-        // TODO: Allow configuration
-        buffer[0] = 0;
+        // Bit 0..1: turnaround period minus one. Bit 2: data phase on WAIT and FAULT, not used.
+        buffer[0] = self.turnaround_cycles.saturating_sub(1) & 0b11;
         Ok(1)
     }
 
@@ -132,7 +132,7 @@ impl Request for StatusRequest {
                 .map_err(|_| SendError::NotEnoughData)?,
         );
         Ok(StatusResponse {
-            _status: status,
+            status,
             _count: count,
         })
     }
@@ -142,7 +142,7 @@ impl Request for StatusRequest {
 pub struct TraceStatus {
     pub(crate) _active: bool,
     pub(crate) error: bool,
-    pub(crate) _overrun: bool,
+    pub(crate) overrun: bool,
 }
 
 impl From<u8> for TraceStatus {
@@ -150,14 +150,14 @@ impl From<u8> for TraceStatus {
         Self {
             _active: value & (1 << 0) != 0,
             error: value & (1 << 6) != 0,
-            _overrun: value & (1 << 7) != 0,
+            overrun: value & (1 << 7) != 0,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct StatusResponse {
-    pub(crate) _status: TraceStatus,
+    pub(crate) status: TraceStatus,
     pub(crate) _count: u32,
 }
 
@@ -206,7 +206,7 @@ impl Request for ExtendedStatusRequest {
             _status: status,
             _count: count,
             _index: index,
-            _timestamp: timestamp,
+            timestamp,
         })
     }
 }
@@ -216,7 +216,7 @@ pub struct ExtendedStatusResponse {
     pub(crate) _status: TraceStatus,
     pub(crate) _count: u32,
     pub(crate) _index: u32,
-    pub(crate) _timestamp: u32,
+    pub(crate) timestamp: u32,
 }
 
 #[derive(Debug)]
@@ -8,14 +8,18 @@ use crate::{
         dp::{Abort, Ctrl},
         swo::poll_interval_from_buf_size,
         ArmCommunicationInterface, ArmError, DapError, DpAddress, Pins, PortType, RawDapAccess,
-        Register, SwoAccess, SwoConfig, SwoMode,
+        RawDapOperation, Register, SwoAccess, SwoChunk, SwoConfig, SwoMode, SwoTimestamp,
     },
     probe::{
         cmsisdap::commands::{
-            general::info::{CapabilitiesCommand, PacketCountCommand, SWOTraceBufferSizeCommand},
+            general::info::{
+                CapabilitiesCommand, FirmwareVersionCommand, PacketCountCommand,
+                ProductFirmwareVersionCommand, ProductIdCommand, SWOTraceBufferSizeCommand,
+                TestDomainTimeCommand, VendorCommand,
+            },
             CmsisDapError,
         },
-        BatchCommand,
+        BatchCommand, FirmwareInfo, FirmwareStatus, ResetTiming, SwdTiming, UsbSettings,
     },
     CoreStatus, DebugProbe, DebugProbeError, DebugProbeSelector, WireProtocol,
 };
@@ -28,7 +32,7 @@ use commands::{
         info::Capabilities,
         reset::{ResetRequest, ResetResponse},
     },
-    swd,
+    msd, swd,
     swj::{
         clock::{SWJClockRequest, SWJClockResponse},
         pins::{SWJPinsRequest, SWJPinsRequestBuilder, SWJPinsResponse},
@@ -43,7 +47,10 @@ use commands::{
     CmsisDapDevice, Status,
 };
 
-use std::{result::Result, time::Duration};
+use std::{
+    result::Result,
+    time::{Duration, Instant},
+};
 
 pub struct CmsisDap {
     pub device: CmsisDapDevice,
@@ -57,11 +64,19 @@ pub struct CmsisDap {
     swo_buffer_size: Option<usize>,
     swo_active: bool,
     swo_streaming: bool,
+    /// The test domain timer of the probe, if SWO data is timestamped with it.
+    swo_clock: Option<SwoClock>,
     connected: bool,
 
     /// Speed in kHz
     speed_khz: u32,
 
+    swd_timing: SwdTiming,
+
+    reset_timing: ResetTiming,
+
+    usb_settings: UsbSettings,
+
     batch: Vec<BatchCommand>,
 }
 
@@ -75,7 +90,11 @@ impl std::fmt::Debug for CmsisDap {
             .field("swo_buffer_size", &self.swo_buffer_size)
             .field("swo_active", &self.swo_active)
             .field("swo_streaming", &self.swo_streaming)
+            .field("swo_clock", &self.swo_clock)
             .field("speed_khz", &self.speed_khz)
+            .field("swd_timing", &self.swd_timing)
+            .field("reset_timing", &self.reset_timing)
+            .field("usb_settings", &self.usb_settings)
             .finish()
     }
 }
@@ -112,8 +131,12 @@ impl CmsisDap {
             swo_buffer_size,
             swo_active: false,
             swo_streaming: false,
+            swo_clock: None,
             connected: false,
             speed_khz: 1_000,
+            swd_timing: SwdTiming::default(),
+            reset_timing: ResetTiming::default(),
+            usb_settings: UsbSettings::default(),
             batch: Vec::new(),
         })
     }
@@ -337,6 +360,57 @@ impl CmsisDap {
         }
     }
 
+    /// Send `operations` in as few DAP_Transfer commands as possible, see
+    /// [`RawDapAccess::raw_batch`].
+    fn transfer_batch(&mut self, operations: &[RawDapOperation]) -> Result<Vec<u32>, ArmError> {
+        // Queued writes have to be executed before the batch.
+        self.process_batch()?;
+
+        let mut values = Vec::with_capacity(operations.len());
+
+        for chunk in batch_chunks(operations, self.packet_size as usize) {
+            let transfers: Vec<InnerTransferRequest> = chunk
+                .iter()
+                .map(|operation| match *operation {
+                    RawDapOperation::Read { port, addr } => {
+                        InnerTransferRequest::new(port, RW::R, addr, None)
+                    }
+                    RawDapOperation::Write { port, addr, value } => {
+                        InnerTransferRequest::new(port, RW::W, addr, Some(value))
+                    }
+                })
+                .collect();
+
+            tracing::debug!("Sending batch of {} transfers", transfers.len());
+
+            let response = commands::send_command::<TransferRequest>(
+                &mut self.device,
+                TransferRequest::new(&transfers),
+            )
+            .map_err(DebugProbeError::from)?;
+
+            if response.last_transfer_response.protocol_error {
+                return Err(DapError::SwdProtocol.into());
+            }
+
+            match response.last_transfer_response.ack {
+                Ack::Ok => {}
+                Ack::Wait => return Err(DapError::WaitResponse.into()),
+                Ack::Fault => return Err(DapError::FaultResponse.into()),
+                Ack::NoAck => return Err(DapError::NoAcknowledge.into()),
+            }
+
+            values.extend(
+                response
+                    .transfers
+                    .iter()
+                    .filter_map(|transfer| transfer.data),
+            );
+        }
+
+        Ok(values)
+    }
+
     /// Set SWO port to use requested transport.
     ///
     /// Check the probe capabilities to determine which transports are available.
@@ -397,7 +471,6 @@ impl CmsisDap {
     }
 
     /// Fetch current SWO trace status.
-    #[allow(dead_code)]
     fn get_swo_status(&mut self) -> Result<swo::StatusResponse, DebugProbeError> {
         Ok(commands::send_command(
             &mut self.device,
@@ -409,8 +482,7 @@ impl CmsisDap {
     ///
     /// request.request_status: request trace status
     /// request.request_count: request remaining bytes in trace buffer
-    /// request.request_index: request sequence number and timestamp of next trace sequence
-    #[allow(dead_code)]
+    /// request.request_index_timestamp: request sequence number and timestamp of next trace sequence
     fn get_swo_extended_status(
         &mut self,
         request: swo::ExtendedStatusRequest,
@@ -418,8 +490,39 @@ impl CmsisDap {
         Ok(commands::send_command(&mut self.device, request)?)
     }
 
+    /// Start timestamping SWO data with the test domain timer, if the probe has one.
+    ///
+    /// Returns `None` if the probe has no test domain timer, or does not report its frequency.
+    fn start_swo_clock(&mut self) -> Result<Option<SwoClock>, DebugProbeError> {
+        if !self.capabilities.test_domain_timer_implemented {
+            return Ok(None);
+        }
+
+        let frequency = match commands::send_command(&mut self.device, TestDomainTimeCommand {}) {
+            Ok(frequency) if frequency > 0 => frequency,
+            Ok(_) | Err(_) => {
+                tracing::debug!("Probe does not report its test domain timer frequency, SWO data is timestamped on the host");
+                return Ok(None);
+            }
+        };
+
+        let status = self.get_swo_extended_status(swo::ExtendedStatusRequest {
+            request_status: true,
+            request_count: true,
+            request_index_timestamp: true,
+        })?;
+        tracing::debug!(
+            "Timestamping SWO data with the {} Hz test domain timer",
+            frequency
+        );
+
+        Ok(Some(SwoClock::new(frequency, status.timestamp)))
+    }
+
     /// Fetch latest SWO trace data by sending a DAP_SWO_Data request.
-    fn get_swo_data(&mut self) -> Result<Vec<u8>, DebugProbeError> {
+    ///
+    /// Returns the data, and whether the trace buffer of the probe overflowed.
+    fn get_swo_data(&mut self) -> Result<(Vec<u8>, bool), DebugProbeError> {
         match self.swo_buffer_size {
             Some(swo_buffer_size) => {
                 // We'll request the smaller of the probe's SWO buffer and
@@ -432,10 +535,10 @@ impl CmsisDap {
                 if response.status.error {
                     Err(CmsisDapError::SwoTraceStreamError.into())
                 } else {
-                    Ok(response.data)
+                    Ok((response.data, response.status.overrun))
                 }
             }
-            None => Ok(Vec::new()),
+            None => Ok((Vec::new(), false)),
         }
     }
 
@@ -468,6 +571,17 @@ impl CmsisDap {
 
         Ok(())
     }
+
+    /// Returns `true` if the vendor or product name reported by the probe says DAPLink.
+    fn is_daplink(&mut self) -> Result<bool, CmsisDapError> {
+        let vendor = commands::send_command(&mut self.device, VendorCommand {})?;
+        let product = commands::send_command(&mut self.device, ProductIdCommand {})?;
+
+        Ok([vendor, product]
+            .iter()
+            .flatten()
+            .any(|name| name.contains("DAPLink")))
+    }
 }
 
 impl DebugProbe for CmsisDap {
@@ -503,6 +617,41 @@ impl DebugProbe for CmsisDap {
         Ok(speed_khz)
     }
 
+    fn swd_timing(&self) -> SwdTiming {
+        self.swd_timing
+    }
+
+    /// The timing is applied by the `DAP_TransferConfigure` and `DAP_SWD_Configure`
+    /// commands when attaching.
+    fn set_swd_timing(&mut self, timing: SwdTiming) -> Result<(), DebugProbeError> {
+        self.swd_timing = timing;
+
+        Ok(())
+    }
+
+    fn reset_timing(&self) -> ResetTiming {
+        self.reset_timing
+    }
+
+    fn set_reset_timing(&mut self, timing: ResetTiming) -> Result<(), DebugProbeError> {
+        self.reset_timing = timing;
+
+        Ok(())
+    }
+
+    fn usb_settings(&self) -> UsbSettings {
+        self.usb_settings
+    }
+
+    /// The chunk size limits the data of `DAP_TransferBlock` commands, which are always limited
+    /// by the packet size of the probe.
+    fn set_usb_settings(&mut self, settings: UsbSettings) -> Result<(), DebugProbeError> {
+        self.device.set_timeout(settings.timeout);
+        self.usb_settings = settings;
+
+        Ok(())
+    }
+
     /// Enters debug mode.
     #[tracing::instrument(skip(self))]
     fn attach(&mut self) -> Result<(), DebugProbeError> {
@@ -515,12 +664,14 @@ impl DebugProbe for CmsisDap {
         self.set_speed(self.speed_khz)?;
 
         self.transfer_configure(ConfigureRequest {
-            idle_cycles: 0,
+            idle_cycles: self.swd_timing.idle_cycles,
             wait_retry: 0xffff,
             match_retry: 0,
         })?;
 
-        self.configure_swd(swd::configure::ConfigureRequest {})?;
+        self.configure_swd(swd::configure::ConfigureRequest {
+            turnaround_cycles: self.swd_timing.turnaround_cycles,
+        })?;
 
         // Tell the probe we are connected so it can turn on an LED.
         let _: Result<HostStatusResponse, _> =
@@ -625,6 +776,141 @@ impl DebugProbe for CmsisDap {
     fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
         Some(self)
     }
+
+    fn firmware_info(&mut self) -> Result<Option<FirmwareInfo>, DebugProbeError> {
+        // The product firmware version was added in CMSIS-DAP 2.1, older probes only report
+        // the version of the protocol.
+        let version =
+            match commands::send_command(&mut self.device, ProductFirmwareVersionCommand {})? {
+                Some(version) => Some(version),
+                None => commands::send_command(&mut self.device, FirmwareVersionCommand {})?
+                    .map(|version| format!("CMSIS-DAP {}", version.trim_end_matches('\0'))),
+            };
+
+        Ok(version.map(|version| FirmwareInfo {
+            version: version.trim_end_matches('\0').to_owned(),
+            status: FirmwareStatus::Unknown,
+            update_hint: Some(
+                "Update DAPLink by copying the firmware image to the MAINTENANCE drive, other CMSIS-DAP probes with the tools of their vendor.",
+            ),
+        }))
+    }
+
+    /// Write `image` to the drag-and-drop programming stream of DAPLink.
+    ///
+    /// DAPLink programs the interface firmware with the image while it runs its bootloader,
+    /// which shows the MAINTENANCE drive, and the target otherwise. Images starting with `:`
+    /// are written as Intel HEX, others as binary.
+    fn update_firmware(&mut self, image: &[u8]) -> Result<(), DebugProbeError> {
+        // The MSD commands are vendor commands of DAPLink, other probes can use the same IDs for
+        // something else.
+        if !self.is_daplink()? {
+            return Err(CmsisDapError::NotDapLink.into());
+        }
+
+        let stream_type = if image.first() == Some(&b':') {
+            msd::StreamType::Hex
+        } else {
+            msd::StreamType::Bin
+        };
+
+        check_msd_response(commands::send_command(
+            &mut self.device,
+            msd::OpenRequest(stream_type),
+        )?)?;
+
+        // Each chunk is sent after the command ID and the length byte.
+        let chunk_size = (self.packet_size as usize - 2).min(u8::MAX as usize);
+        for chunk in image.chunks(chunk_size) {
+            let result = commands::send_command(&mut self.device, msd::WriteRequest(chunk))
+                .and_then(check_msd_response);
+
+            if let Err(e) = result {
+                // The stream has to be closed before it can be opened again.
+                let _ = commands::send_command(&mut self.device, msd::CloseRequest);
+                return Err(e.into());
+            }
+        }
+
+        check_msd_response(commands::send_command(&mut self.device, msd::CloseRequest)?)?;
+
+        Ok(())
+    }
+}
+
+/// Split `operations` into chunks which fit into one DAP_Transfer command and its response,
+/// for a probe with the given packet size.
+fn batch_chunks(operations: &[RawDapOperation], packet_size: usize) -> Vec<&[RawDapOperation]> {
+    // The command, the DAP index and the transfer count, or the command, the transfer count and
+    // the response in the response packet.
+    let available = packet_size.saturating_sub(3);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut request_size = 0;
+    let mut response_size = 0;
+
+    for (i, operation) in operations.iter().enumerate() {
+        let (request, response) = match operation {
+            RawDapOperation::Read { .. } => (1, 4),
+            RawDapOperation::Write { .. } => (1 + 4, 0),
+        };
+
+        let full = request_size + request > available
+            || response_size + response > available
+            || i - start == u8::MAX as usize;
+        if full && i > start {
+            chunks.push(&operations[start..i]);
+            start = i;
+            request_size = 0;
+            response_size = 0;
+        }
+
+        request_size += request;
+        response_size += response;
+    }
+
+    if start < operations.len() {
+        chunks.push(&operations[start..]);
+    }
+
+    chunks
+}
+
+/// The time since SWO was enabled, from the 32 bit test domain timer of the probe.
+#[derive(Debug, Clone, Copy)]
+struct SwoClock {
+    frequency: u32,
+    last: u32,
+    ticks: u64,
+}
+
+impl SwoClock {
+    fn new(frequency: u32, start: u32) -> Self {
+        Self {
+            frequency,
+            last: start,
+            ticks: 0,
+        }
+    }
+
+    /// The time since the start at the given timer value, which must be read at least once
+    /// per wrap of the timer.
+    fn elapsed(&mut self, timestamp: u32) -> Duration {
+        self.ticks += u64::from(timestamp.wrapping_sub(self.last));
+        self.last = timestamp;
+
+        let frequency = u64::from(self.frequency);
+        Duration::from_secs(self.ticks / frequency)
+            + Duration::from_nanos(self.ticks % frequency * 1_000_000_000 / frequency)
+    }
+}
+
+fn check_msd_response(response: msd::MsdResponse) -> Result<(), CmsisDapError> {
+    match response.0 {
+        0 => Ok(()),
+        code => Err(CmsisDapError::MsdError(code)),
+    }
 }
 
 impl RawDapAccess for CmsisDap {
@@ -702,6 +988,10 @@ impl RawDapAccess for CmsisDap {
             .map(|_| ())
     }
 
+    fn raw_batch(&mut self, operations: &[RawDapOperation]) -> Result<Vec<u32>, ArmError> {
+        self.transfer_batch(operations)
+    }
+
     fn raw_write_block(
         &mut self,
         port: PortType,
@@ -722,7 +1012,10 @@ impl RawDapAccess for CmsisDap {
 
         let max_packet_size_words = (self.packet_size - 6) / 4;
 
-        let data_chunk_len = max_packet_size_words as usize;
+        let data_chunk_len = self
+            .usb_settings
+            .chunk_size(max_packet_size_words as usize * 4, 4)
+            / 4;
 
         for (i, chunk) in values.chunks(data_chunk_len).enumerate() {
             let request =
@@ -761,7 +1054,10 @@ impl RawDapAccess for CmsisDap {
 
         let max_packet_size_words = (self.packet_size - 6) / 4;
 
-        let data_chunk_len = max_packet_size_words as usize;
+        let data_chunk_len = self
+            .usb_settings
+            .chunk_size(max_packet_size_words as usize * 4, 4)
+            / 4;
 
         for (i, chunk) in values.chunks_mut(data_chunk_len).enumerate() {
             let request =
@@ -873,6 +1169,7 @@ impl SwoAccess for CmsisDap {
         }
 
         self.start_swo_capture()?;
+        self.swo_clock = self.start_swo_clock()?;
 
         self.swo_active = true;
         Ok(())
@@ -882,26 +1179,76 @@ impl SwoAccess for CmsisDap {
         tracing::debug!("Stopping SWO capture");
         self.stop_swo_capture()?;
         self.swo_active = false;
+        self.swo_clock = None;
         Ok(())
     }
 
     fn read_swo_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>, ArmError> {
-        if self.swo_active {
-            if self.swo_streaming {
-                let buffer = self
-                    .device
-                    .read_swo_stream(timeout)
-                    .map_err(DebugProbeError::from)?;
-                tracing::trace!("SWO streaming buffer: {:?}", buffer);
-                Ok(buffer)
-            } else {
-                let data = self.get_swo_data()?;
-                tracing::trace!("SWO polled data: {:?}", data);
-                Ok(data)
+        Ok(self
+            .read_swo_chunks(timeout)?
+            .into_iter()
+            .flat_map(|chunk| chunk.data)
+            .collect())
+    }
+
+    /// Overruns of the trace buffer of the probe are part of the response in polled mode, and
+    /// are requested with the trace status after each read in streaming mode.
+    ///
+    /// If the probe has a test domain timer, chunks are timestamped with the time at which the
+    /// probe captured their first byte, see [`SwoTimestamp::Probe`].
+    fn read_swo_chunks(&mut self, timeout: Duration) -> Result<Vec<SwoChunk>, ArmError> {
+        if !self.swo_active {
+            return Ok(Vec::new());
+        }
+
+        // The extended status reports the timestamp of the next trace data, so it is requested
+        // before the data is read.
+        let probe_time = match self.swo_clock {
+            Some(mut clock) => {
+                let status = self.get_swo_extended_status(swo::ExtendedStatusRequest {
+                    request_status: true,
+                    request_count: true,
+                    request_index_timestamp: true,
+                })?;
+                let time = clock.elapsed(status.timestamp);
+                self.swo_clock = Some(clock);
+                Some(time)
             }
+            None => None,
+        };
+
+        let (data, overflow) = if self.swo_streaming {
+            let buffer = self
+                .device
+                .read_swo_stream(timeout)
+                .map_err(DebugProbeError::from)?;
+            tracing::trace!("SWO streaming buffer: {:?}", buffer);
+            let status = self.get_swo_status()?.status;
+            (buffer, status.overrun)
         } else {
-            Ok(Vec::new())
+            let (data, overrun) = self.get_swo_data()?;
+            tracing::trace!("SWO polled data: {:?}", data);
+            (data, overrun)
+        };
+
+        if overflow {
+            tracing::warn!("The SWO trace buffer of the probe overflowed, data was lost");
+        }
+
+        if data.is_empty() && !overflow {
+            return Ok(Vec::new());
         }
+
+        let timestamp = match probe_time {
+            Some(time) => SwoTimestamp::Probe(time),
+            None => SwoTimestamp::Host(Instant::now()),
+        };
+
+        Ok(vec![SwoChunk {
+            data,
+            timestamp,
+            overflow,
+        }])
     }
 
     fn swo_poll_interval_hint(&mut self, config: &SwoConfig) -> Option<std::time::Duration> {
@@ -940,3 +1287,51 @@ impl Drop for CmsisDap {
         let _ = self.detach();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{batch_chunks, SwoClock};
+    use crate::architecture::arm::{PortType, RawDapOperation};
+    use std::time::Duration;
+
+    #[test]
+    fn batches_are_split_into_packets() {
+        let read = RawDapOperation::Read {
+            port: PortType::AccessPort,
+            addr: 0xC,
+        };
+        let write = RawDapOperation::Write {
+            port: PortType::DebugPort,
+            addr: 0x8,
+            value: 0,
+        };
+
+        // 61 bytes are available in a 64 byte packet, for 15 reads or 12 writes.
+        let operations = vec![read; 20];
+        let chunks = batch_chunks(&operations, 64);
+        assert_eq!(chunks.iter().map(|c| c.len()).collect::<Vec<_>>(), [15, 5]);
+
+        // Reads and writes share the space in the request.
+        let operations = [vec![write; 12], vec![read; 4]].concat();
+        let chunks = batch_chunks(&operations, 64);
+        assert_eq!(chunks.iter().map(|c| c.len()).collect::<Vec<_>>(), [13, 3]);
+
+        // A DAP_Transfer command has at most 255 transfers.
+        let operations = vec![read; 300];
+        let chunks = batch_chunks(&operations, 1024 * 4);
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>(),
+            [255, 45]
+        );
+
+        assert!(batch_chunks(&[], 64).is_empty());
+    }
+
+    #[test]
+    fn swo_clock_counts_across_timer_wraps() {
+        let mut clock = SwoClock::new(1_000_000, u32::MAX - 499_999);
+
+        assert_eq!(clock.elapsed(u32::MAX), Duration::from_micros(499_999));
+        assert_eq!(clock.elapsed(1_500_000), Duration::from_secs(2));
+    }
+}
@@ -216,6 +216,7 @@ pub fn open_v2_device(device: Device<rusb::Context>) -> Option<CmsisDapDevice> {
                         in_ep: eps[1].address(),
                         swo_ep,
                         max_packet_size: eps[1].max_packet_size() as usize,
+                        timeout: None,
                     });
                 }
                 Err(_) => continue,
@@ -372,6 +373,7 @@ pub fn open_device_from_selector(
             // common size for CMSIS-DAPv1 HID devices. We'll request the
             // actual size to use from the probe later.
             report_size: 64,
+            timeout: None,
         }),
         _ => {
             // Return NotFound if this VID:PID was not a valid CMSIS-DAP probe,
@@ -450,6 +450,20 @@ impl DebugProbe for EspUsbJtag {
         }
     }
 
+    fn jtag_sequence(
+        &mut self,
+        tms_bits: &[bool],
+        tdi_bits: &[bool],
+    ) -> Result<Vec<bool>, DebugProbeError> {
+        crate::probe::check_jtag_sequence(tms_bits, tdi_bits)?;
+
+        let response =
+            self.protocol
+                .jtag_io(tms_bits.iter().copied(), tdi_bits.iter().copied(), true)?;
+
+        Ok(response.collect())
+    }
+
     fn active_protocol(&self) -> Option<WireProtocol> {
         Some(WireProtocol::Jtag)
     }
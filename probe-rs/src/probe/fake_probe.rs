@@ -4,7 +4,7 @@ use crate::{
     architecture::arm::{
         ap::{memory_ap::mock::MockMemoryAp, AccessPort, MemoryAp},
         communication_interface::{
-            ArmDebugState, Initialized, SwdSequence, Uninitialized, UninitializedArmProbe,
+            ArmDebugState, DapProbe, Initialized, SwdSequence, Uninitialized, UninitializedArmProbe,
         },
         dp::DebugPortError,
         memory::adi_v5_memory_interface::{ADIMemoryInterface, ArmProbe},
@@ -151,6 +151,8 @@ impl DebugProbe for FakeProbe {
     }
 }
 
+impl DapProbe for FakeProbe {}
+
 impl RawDapAccess for FakeProbe {
     fn select_dp(&mut self, _dp: DpAddress) -> Result<(), ArmError> {
         Err(DebugPortError::Unsupported(
@@ -174,7 +176,8 @@ impl RawDapAccess for FakeProbe {
     }
 
     fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
-        todo!()
+        // There is no wire, the sequences have no effect.
+        Ok(())
     }
 
     fn swj_pins(
@@ -208,6 +211,8 @@ impl FakeArmInterface<Uninitialized> {
     pub(crate) fn new(probe: Box<FakeProbe>) -> Self {
         let state = Uninitialized {
             use_overrun_detect: false,
+            ap_scan: Default::default(),
+            protocol_detection: false,
         };
         let memory_ap = MockMemoryAp::with_pattern();
 
@@ -343,8 +348,12 @@ impl SwoAccess for FakeArmInterface<Initialized> {
 }
 
 impl DapAccess for FakeArmInterface<Initialized> {
-    fn read_raw_dp_register(&mut self, _dp: DpAddress, _address: u8) -> Result<u32, ArmError> {
-        todo!()
+    fn read_raw_dp_register(&mut self, _dp: DpAddress, address: u8) -> Result<u32, ArmError> {
+        match address {
+            // DPIDR of an SW-DP, which is read when attaching.
+            0x0 => Ok(0x2ba0_1477),
+            _ => todo!(),
+        }
     }
 
     fn write_raw_dp_register(
@@ -390,8 +399,19 @@ impl DapAccess for FakeArmInterface<Initialized> {
 
 #[cfg(test)]
 mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
     use super::FakeProbe;
-    use crate::Permissions;
+    use crate::architecture::arm::{
+        communication_interface::{ArmCommunicationInterface, UninitializedArmProbe},
+        dp::DebugPortError,
+        sequences::DefaultArmSequence,
+        PortType,
+    };
+    use crate::{Permissions, WireProtocol};
 
     #[test]
     fn create_session_with_fake_probe() {
@@ -403,4 +423,33 @@ mod test {
             .attach("nrf51822_xxAC", Permissions::default())
             .unwrap();
     }
+
+    #[test]
+    fn protocol_is_detected_when_debug_port_does_not_respond() {
+        let dpidr_reads = Arc::new(AtomicUsize::new(0));
+
+        let mut fake_probe = FakeProbe::new();
+        let reads = dpidr_reads.clone();
+        fake_probe.set_dap_register_read_handler(Box::new(move |port, address| {
+            assert_eq!((port, address), (PortType::DebugPort, 0));
+
+            // The debug port does not respond over SWD, neither after the JTAG-to-SWD sequence
+            // nor after the dormant wake-up, and responds to the first check over JTAG.
+            match reads.fetch_add(1, Ordering::SeqCst) {
+                0..=3 => Err(DebugPortError::Timeout.into()),
+                _ => Ok(0x0BB1_1477),
+            }
+        }));
+
+        let mut interface = Box::new(ArmCommunicationInterface::new(Box::new(fake_probe), false));
+        interface.set_protocol_detection(true);
+
+        let interface = interface
+            .initialize(DefaultArmSequence::create())
+            .map_err(|(_, error)| error)
+            .unwrap();
+
+        assert_eq!(dpidr_reads.load(Ordering::SeqCst), 5);
+        assert_eq!(interface.close().protocol(), Some(WireProtocol::Jtag));
+    }
 }
@@ -0,0 +1,51 @@
+//! Firmware versions of debug probes.
+//!
+//! Many problems which only occur with some probes are caused by outdated probe firmware.
+//! [`Probe::firmware_info`](crate::Probe::firmware_info) reports the firmware version of a probe,
+//! and whether probe-rs needs or recommends a newer version. Probes which can be updated by
+//! probe-rs accept a new firmware image with [`Probe::update_firmware`](crate::Probe::update_firmware).
+
+/// The firmware version of a debug probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    /// The firmware version, as reported by the probe.
+    pub version: String,
+    /// Whether the firmware should be updated.
+    pub status: FirmwareStatus,
+    /// How the firmware of the probe can be updated, if probe-rs cannot update it.
+    pub update_hint: Option<&'static str>,
+}
+
+/// Whether the firmware of a probe should be updated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FirmwareStatus {
+    /// The firmware is supported by probe-rs without restrictions.
+    UpToDate,
+    /// The firmware works, but a newer version is needed for some features.
+    UpdateRecommended(String),
+    /// The firmware is too old to be used by probe-rs.
+    UpdateRequired(String),
+    /// It is not known which firmware versions work with probe-rs.
+    Unknown,
+}
+
+impl FirmwareStatus {
+    /// Returns true if the firmware should be updated.
+    pub fn needs_update(&self) -> bool {
+        matches!(
+            self,
+            FirmwareStatus::UpdateRecommended(_) | FirmwareStatus::UpdateRequired(_)
+        )
+    }
+}
+
+impl std::fmt::Display for FirmwareStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirmwareStatus::UpToDate => write!(f, "up to date"),
+            FirmwareStatus::UpdateRecommended(reason) => write!(f, "update recommended: {reason}"),
+            FirmwareStatus::UpdateRequired(reason) => write!(f, "update required: {reason}"),
+            FirmwareStatus::Unknown => write!(f, "unknown"),
+        }
+    }
+}
@@ -205,6 +205,25 @@ impl JtagAdapter {
         Ok(reply)
     }
 
+    /// Clock out `tms` and `tdi`, and return the bits sampled on TDO.
+    ///
+    /// Every bit is clocked with a separate TMS command, as TMS and TDI change in every cycle.
+    pub fn shift_raw(&mut self, tms: &[bool], tdi: &[bool]) -> io::Result<Vec<bool>> {
+        if tms.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut command = Vec::with_capacity(3 * tms.len());
+        for (&tms, &tdi) in tms.iter().zip(tdi) {
+            command.extend_from_slice(&[0x6b, 0x00, (tdi as u8) << 7 | tms as u8]);
+        }
+        self.device.write_all(&command)?;
+
+        let reply = self.read_response(tms.len())?;
+
+        Ok(reply.into_iter().map(|byte| byte & 0x80 != 0).collect())
+    }
+
     /// Reset and go to RUN-TEST/IDLE
     pub fn reset(&mut self) -> io::Result<()> {
         self.shift_tms(&[0xff, 0xff, 0xff, 0xff, 0x7f], 40)
@@ -538,6 +557,18 @@ impl DebugProbe for FtdiProbe {
         Some(WireProtocol::Jtag)
     }
 
+    fn jtag_sequence(
+        &mut self,
+        tms_bits: &[bool],
+        tdi_bits: &[bool],
+    ) -> Result<Vec<bool>, DebugProbeError> {
+        crate::probe::check_jtag_sequence(tms_bits, tdi_bits)?;
+
+        self.adapter
+            .shift_raw(tms_bits, tdi_bits)
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))
+    }
+
     fn try_get_riscv_interface(
         self: Box<Self>,
     ) -> Result<RiscvCommunicationInterface, (Box<dyn DebugProbe>, RiscvError)> {
@@ -64,6 +64,19 @@ impl Default for SwdSettings {
     }
 }
 
+impl SwdSettings {
+    /// Insert `extra` idle cycles in addition to the default ones, for slow targets.
+    pub fn with_extra_idle_cycles(extra: u8) -> Self {
+        let default = Self::default();
+
+        Self {
+            num_idle_cycles_between_writes: default.num_idle_cycles_between_writes + extra as usize,
+            idle_cycles_after_transfer: default.idle_cycles_after_transfer + extra as usize,
+            ..default
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct ProbeStatistics {
     /// Number of protocol transfers performed.
@@ -1063,6 +1076,15 @@ impl<Probe: DebugProbe + RawProtocolIo + JTAGAccess + 'static> RawDapAccess for
         address: u8,
         values: &mut [u32],
     ) -> Result<(), ArmError> {
+        let max_words = self.usb_settings().chunk_size(values.len() * 4, 4) / 4;
+        if values.len() > max_words {
+            for chunk in values.chunks_mut(max_words) {
+                self.raw_read_block(port, address, chunk)?;
+            }
+
+            return Ok(());
+        }
+
         let mut succesful_transfers = 0;
 
         let mut idle_cycles = std::cmp::max(1, self.swd_settings().num_idle_cycles_between_writes);
@@ -1246,6 +1268,15 @@ impl<Probe: DebugProbe + RawProtocolIo + JTAGAccess + 'static> RawDapAccess for
         address: u8,
         values: &[u32],
     ) -> Result<(), ArmError> {
+        let max_words = self.usb_settings().chunk_size(values.len() * 4, 4) / 4;
+        if values.len() > max_words {
+            for chunk in values.chunks(max_words) {
+                self.raw_write_block(port, address, chunk)?;
+            }
+
+            return Ok(());
+        }
+
         let mut succesful_transfers = 0;
 
         let mut idle_cycles = std::cmp::max(1, self.swd_settings().num_idle_cycles_between_writes);
@@ -12,17 +12,18 @@ use crate::{
     architecture::{
         arm::{
             communication_interface::DapProbe, communication_interface::UninitializedArmProbe,
-            swo::SwoConfig, ArmCommunicationInterface, SwoAccess,
+            swo::SwoConfig, ArmCommunicationInterface, SwoAccess, SwoChunk, SwoTimestamp,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
     probe::{
-        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeType, JTAGAccess, WireProtocol,
+        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeType, FirmwareInfo, FirmwareStatus,
+        JTAGAccess, ProbeSetting, ResetTiming, SwdTiming, UsbSettings, WireProtocol,
     },
     DebugProbeSelector,
 };
 
-use self::arm::{ProbeStatistics, SwdSettings};
+use self::arm::{ProbeStatistics, RawProtocolIo, SwdSettings};
 
 mod arm;
 
@@ -52,6 +53,9 @@ pub(crate) struct JLink {
 
     probe_statistics: ProbeStatistics,
     swd_settings: SwdSettings,
+    swd_timing: SwdTiming,
+    reset_timing: ResetTiming,
+    usb_settings: UsbSettings,
 }
 
 impl JLink {
@@ -398,6 +402,9 @@ impl DebugProbe for JLink {
             current_ir_reg: 1,
             speed_khz: 0,
             swd_settings: SwdSettings::default(),
+            swd_timing: SwdTiming::default(),
+            reset_timing: ResetTiming::default(),
+            usb_settings: UsbSettings::default(),
             probe_statistics: ProbeStatistics::default(),
         }))
     }
@@ -420,6 +427,22 @@ impl DebugProbe for JLink {
         self.protocol
     }
 
+    fn jtag_sequence(
+        &mut self,
+        tms_bits: &[bool],
+        tdi_bits: &[bool],
+    ) -> Result<Vec<bool>, DebugProbeError> {
+        crate::probe::check_jtag_sequence(tms_bits, tdi_bits)?;
+
+        if self.protocol != Some(WireProtocol::Jtag) {
+            return Err(DebugProbeError::CommandNotSupportedByProbe(
+                "JTAG sequences without JTAG selected as protocol",
+            ));
+        }
+
+        self.jtag_io(tms_bits.iter().copied(), tdi_bits.iter().copied())
+    }
+
     fn get_name(&self) -> &'static str {
         "J-Link"
     }
@@ -453,6 +476,52 @@ impl DebugProbe for JLink {
         Ok(speed_khz)
     }
 
+    fn swd_timing(&self) -> SwdTiming {
+        self.swd_timing
+    }
+
+    fn set_swd_timing(&mut self, timing: SwdTiming) -> Result<(), DebugProbeError> {
+        // The SWD protocol is bit-banged with a fixed position of the turnaround period.
+        if timing.turnaround_cycles != 1 {
+            return Err(DebugProbeError::CommandNotSupportedByProbe(
+                "SWD turnaround configuration",
+            ));
+        }
+
+        self.swd_settings = SwdSettings::with_extra_idle_cycles(timing.idle_cycles);
+        self.swd_timing = timing;
+
+        Ok(())
+    }
+
+    fn reset_timing(&self) -> ResetTiming {
+        self.reset_timing
+    }
+
+    fn set_reset_timing(&mut self, timing: ResetTiming) -> Result<(), DebugProbeError> {
+        self.reset_timing = timing;
+
+        Ok(())
+    }
+
+    fn usb_settings(&self) -> UsbSettings {
+        self.usb_settings
+    }
+
+    /// The USB timeout is fixed by the J-Link driver, the chunk size limits the number of
+    /// transfers of a block access sent in a single USB transfer.
+    fn set_usb_settings(&mut self, settings: UsbSettings) -> Result<(), DebugProbeError> {
+        if settings.timeout.is_some() {
+            return Err(DebugProbeError::CommandNotSupportedByProbe(
+                "USB timeout configuration",
+            ));
+        }
+
+        self.usb_settings = settings;
+
+        Ok(())
+    }
+
     fn attach(&mut self) -> Result<(), super::DebugProbeError> {
         tracing::debug!("Attaching to J-Link");
 
@@ -618,6 +687,37 @@ impl DebugProbe for JLink {
         // Convert the integer millivolts value from self.handle to volts as an f32.
         Ok(Some((self.handle.read_target_voltage()? as f32) / 1000f32))
     }
+
+    fn firmware_info(&mut self) -> Result<Option<FirmwareInfo>, DebugProbeError> {
+        Ok(Some(FirmwareInfo {
+            version: self.handle.read_firmware_version()?,
+            status: FirmwareStatus::Unknown,
+            update_hint: Some(
+                "Update the firmware with the J-Link Configurator of the SEGGER J-Link software.",
+            ),
+        }))
+    }
+
+    fn write_setting(
+        &mut self,
+        setting: ProbeSetting,
+        enabled: bool,
+    ) -> Result<(), DebugProbeError> {
+        match setting {
+            ProbeSetting::TargetPower => {
+                if !self.handle.capabilities().contains(Capability::SetKsPower) {
+                    return Err(DebugProbeError::CommandNotSupportedByProbe(
+                        "switching the target power",
+                    ));
+                }
+
+                // This is not stored in the probe configuration, the default is restored when the
+                // probe is power cycled.
+                self.handle.set_kickstart_power(enabled)?;
+                Ok(())
+            }
+        }
+    }
 }
 
 impl JTAGAccess for JLink {
@@ -703,6 +803,17 @@ impl SwoAccess for JLink {
     }
 
     fn read_swo_timeout(&mut self, timeout: std::time::Duration) -> Result<Vec<u8>, ArmError> {
+        Ok(self
+            .read_swo_chunks(timeout)?
+            .into_iter()
+            .flat_map(|chunk| chunk.data)
+            .collect())
+    }
+
+    /// The J-Link does not report when SWO data was received, so each chunk is
+    /// timestamped with the host time at which the probe returned it. Overruns of the
+    /// buffer of the probe are reported with each read.
+    fn read_swo_chunks(&mut self, timeout: std::time::Duration) -> Result<Vec<SwoChunk>, ArmError> {
         let end = std::time::Instant::now() + timeout;
         let mut buf = vec![0; SWO_BUFFER_SIZE.into()];
 
@@ -710,21 +821,27 @@ impl SwoAccess for JLink {
             .swo_poll_interval_hint(&self.swo_config.unwrap())
             .unwrap();
 
-        let mut bytes = vec![];
+        let mut chunks = vec![];
         loop {
             let data = self
                 .handle
                 .swo_read(&mut buf)
                 .map_err(|e| ArmError::from(DebugProbeError::ProbeSpecific(Box::new(e))))?;
-            bytes.extend(data.as_ref());
             let now = std::time::Instant::now();
+            if !data.as_ref().is_empty() || data.did_overrun() {
+                chunks.push(SwoChunk {
+                    data: data.as_ref().to_vec(),
+                    timestamp: SwoTimestamp::Host(now),
+                    overflow: data.did_overrun(),
+                });
+            }
             if now + poll_interval < end {
                 std::thread::sleep(poll_interval);
             } else {
                 break;
             }
         }
-        Ok(bytes)
+        Ok(chunks)
     }
 }
 
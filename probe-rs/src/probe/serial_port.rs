@@ -0,0 +1,130 @@
+//! The serial ports of debug probes.
+//!
+//! Most CMSIS-DAP probes, ST-Links and J-Links are composite USB devices with a CDC-ACM serial
+//! port, which is usually connected to a UART of the target, for firmware logs. The serial port
+//! has the USB vendor ID, product ID and serial number of the probe, which
+//! [`DebugProbeInfo::serial_ports`] matches, so the port of a probe can be opened alongside its
+//! debug session.
+
+use serialport::{SerialPort, SerialPortInfo, SerialPortType};
+
+use super::DebugProbeInfo;
+
+impl DebugProbeInfo {
+    /// Returns the names of the serial ports of the probe, for example `/dev/ttyACM0` or `COM3`.
+    ///
+    /// If the probe has no serial number, the ports of all probes with its vendor and product ID
+    /// are returned.
+    pub fn serial_ports(&self) -> Result<Vec<String>, serialport::Error> {
+        Ok(self.matching_ports(serialport::available_ports()?))
+    }
+
+    /// Open the first serial port of the probe, see [`DebugProbeInfo::serial_ports`].
+    pub fn open_serial_port(
+        &self,
+        baud_rate: u32,
+    ) -> Result<Box<dyn SerialPort>, serialport::Error> {
+        let Some(port) = self.serial_ports()?.into_iter().next() else {
+            return Err(serialport::Error::new(
+                serialport::ErrorKind::NoDevice,
+                format!("The probe {self:?} has no serial port"),
+            ));
+        };
+
+        serialport::new(port, baud_rate).open()
+    }
+
+    fn matching_ports(&self, ports: Vec<SerialPortInfo>) -> Vec<String> {
+        let names: Vec<_> = ports
+            .into_iter()
+            .filter(|port| match &port.port_type {
+                SerialPortType::UsbPort(usb) => {
+                    usb.vid == self.vendor_id
+                        && usb.pid == self.product_id
+                        && match (&self.serial_number, &usb.serial_number) {
+                            (Some(probe), Some(port)) => probe.eq_ignore_ascii_case(port),
+                            (Some(_), None) => false,
+                            (None, _) => true,
+                        }
+                }
+                _ => false,
+            })
+            .map(|port| port.port_name)
+            .collect();
+
+        // macOS lists every port twice, as a callout and a dial-in device.
+        let mut unique: Vec<_> = names
+            .iter()
+            .filter(|name| {
+                let callout = name.replacen("/dev/tty.", "/dev/cu.", 1);
+                !name.starts_with("/dev/tty.") || !names.contains(&callout)
+            })
+            .cloned()
+            .collect();
+        unique.sort();
+
+        unique
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialport::{SerialPortInfo, SerialPortType, UsbPortInfo};
+
+    use crate::{DebugProbeInfo, DebugProbeType};
+
+    fn usb_port(name: &str, vid: u16, pid: u16, serial_number: Option<&str>) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: name.to_owned(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid,
+                pid,
+                serial_number: serial_number.map(str::to_owned),
+                manufacturer: None,
+                product: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn serial_ports_match_the_probe() {
+        let probe = DebugProbeInfo::new(
+            "CMSIS-DAP",
+            0x2e8a,
+            0x000c,
+            Some("E6614103E7".to_owned()),
+            DebugProbeType::CmsisDap,
+            None,
+        );
+
+        let ports = vec![
+            usb_port("/dev/ttyACM1", 0x2e8a, 0x000c, Some("E6614864D3")),
+            usb_port("/dev/ttyACM0", 0x2e8a, 0x000c, Some("e6614103e7")),
+            usb_port("/dev/ttyUSB0", 0x0403, 0x6010, None),
+            SerialPortInfo {
+                port_name: "/dev/ttyS0".to_owned(),
+                port_type: SerialPortType::Unknown,
+            },
+        ];
+
+        assert_eq!(probe.matching_ports(ports.clone()), ["/dev/ttyACM0"]);
+
+        let without_serial = DebugProbeInfo {
+            serial_number: None,
+            ..probe
+        };
+        assert_eq!(
+            without_serial.matching_ports(ports),
+            ["/dev/ttyACM0", "/dev/ttyACM1"]
+        );
+
+        let macos = vec![
+            usb_port("/dev/tty.usbmodem1102", 0x2e8a, 0x000c, Some("E6614103E7")),
+            usb_port("/dev/cu.usbmodem1102", 0x2e8a, 0x000c, Some("E6614103E7")),
+        ];
+        assert_eq!(
+            without_serial.matching_ports(macos),
+            ["/dev/cu.usbmodem1102"]
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Settings of debug probes, which the probe keeps across connections.
+//!
+//! Lab setups often need the same probe configuration every time, for example a probe which
+//! powers the target board. [`Probe::write_setting`](crate::Probe::write_setting) changes these
+//! settings on the probes which support it, so they do not have to be configured with the tools
+//! of the vendor.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A setting of a debug probe which can be switched on or off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ProbeSetting {
+    /// The probe supplies power to the target through its power pin.
+    ///
+    /// On J-Link probes this switches the 5V supply on pin 19. probe-rs can only switch it until
+    /// the probe is power cycled, the `power on perm` command of the J-Link Commander stores it in
+    /// the probe configuration.
+    TargetPower,
+}
+
+impl ProbeSetting {
+    /// All settings, in the order they are listed in.
+    pub const ALL: [ProbeSetting; 1] = [ProbeSetting::TargetPower];
+
+    /// The name of the setting, as accepted by [`ProbeSetting::from_str`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProbeSetting::TargetPower => "target-power",
+        }
+    }
+}
+
+impl fmt::Display for ProbeSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ProbeSetting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ProbeSetting::ALL
+            .into_iter()
+            .find(|setting| setting.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names = ProbeSetting::ALL.map(|setting| setting.name()).join(", ");
+                format!("unknown probe setting '{s}', expected one of {names}")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProbeSetting;
+
+    #[test]
+    fn setting_names_round_trip() {
+        for setting in ProbeSetting::ALL {
+            assert_eq!(setting.name().parse::<ProbeSetting>(), Ok(setting));
+        }
+
+        assert_eq!("Target-Power".parse(), Ok(ProbeSetting::TargetPower));
+        assert!("msd".parse::<ProbeSetting>().is_err());
+    }
+}
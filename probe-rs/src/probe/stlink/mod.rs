@@ -3,12 +3,18 @@ pub mod tools;
 mod usb_interface;
 
 use self::usb_interface::{StLinkUsb, StLinkUsbDevice};
-use super::{DebugProbe, DebugProbeError, ProbeCreationError, WireProtocol};
+use super::{
+    DebugProbe, DebugProbeError, FirmwareInfo, FirmwareStatus, ProbeCreationError, ResetTiming,
+    UsbSettings, WireProtocol,
+};
 use crate::architecture::arm::memory::adi_v5_memory_interface::ArmProbe;
 use crate::architecture::arm::{valid_32bit_arm_address, ArmError};
 use crate::{
     architecture::arm::{
-        ap::{valid_access_ports, AccessPort, ApAccess, ApClass, MemoryAp, IDR},
+        ap::{
+            valid_access_ports, AccessPort, ApAccess, ApClass, ApScanOptions, GenericAp, MemoryAp,
+            IDR,
+        },
         communication_interface::{
             ArmProbeInterface, Initialized, SwdSequence, UninitializedArmProbe,
         },
@@ -47,6 +53,8 @@ pub(crate) struct StLink<D: StLinkUsb> {
     swd_speed_khz: u32,
     jtag_speed_khz: u32,
     swo_enabled: bool,
+    reset_timing: ResetTiming,
+    usb_settings: UsbSettings,
 
     /// List of opened APs
     opened_aps: Vec<u8>,
@@ -66,6 +74,8 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             swd_speed_khz: 1_800,
             jtag_speed_khz: 1_120,
             swo_enabled: false,
+            reset_timing: ResetTiming::default(),
+            usb_settings: UsbSettings::default(),
 
             opened_aps: vec![],
         };
@@ -171,7 +181,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             &[commands::JTAG_COMMAND, commands::JTAG_ENTER2, param, 0],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         tracing::debug!("Successfully initialized SWD.");
@@ -212,7 +222,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             ],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         Ok(())
@@ -228,7 +238,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             ],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         Ok(())
@@ -244,7 +254,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             ],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         Ok(())
@@ -282,13 +292,41 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
         self: Box<Self>,
     ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
     {
-        Ok(Box::new(UninitializedStLink { probe: self }))
+        Ok(Box::new(UninitializedStLink {
+            probe: self,
+            ap_scan: ApScanOptions::default(),
+        }))
+    }
+
+    fn reset_timing(&self) -> ResetTiming {
+        self.reset_timing
+    }
+
+    fn set_reset_timing(&mut self, timing: ResetTiming) -> Result<(), DebugProbeError> {
+        self.reset_timing = timing;
+
+        Ok(())
+    }
+
+    fn usb_settings(&self) -> UsbSettings {
+        self.usb_settings
+    }
+
+    fn set_usb_settings(&mut self, settings: UsbSettings) -> Result<(), DebugProbeError> {
+        self.usb_settings = settings;
+
+        Ok(())
     }
 
     fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
         let mut buf = [0; 8];
         self.device
-            .write(&[commands::GET_TARGET_VOLTAGE], &[], &mut buf, TIMEOUT)
+            .write(
+                &[commands::GET_TARGET_VOLTAGE],
+                &[],
+                &mut buf,
+                self.timeout(),
+            )
             .and_then(|_| {
                 // The next two unwraps are safe!
                 let a0 = buf[0..4].pread_with::<u32>(0, LE).unwrap();
@@ -302,6 +340,16 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             })
             .map_err(|e| e.into())
     }
+
+    fn firmware_info(&mut self) -> Result<Option<FirmwareInfo>, DebugProbeError> {
+        Ok(Some(FirmwareInfo {
+            version: format!("V{}J{}", self.hw_version, self.jtag_version),
+            status: Self::firmware_status(self.hw_version, self.jtag_version),
+            update_hint: Some(
+                "Update the firmware with the ST-LINK upgrade utility (STSW-LINK007) or STM32CubeProgrammer.",
+            ),
+        }))
+    }
 }
 
 impl<D: StLinkUsb> Drop for StLink<D> {
@@ -371,12 +419,45 @@ impl<D: StLinkUsb> StLink<D> {
     /// FW versions where this is supported.
     const MIN_JTAG_VERSION_DP_BANK_SEL: u8 = 32;
 
+    /// The timeout of USB transfers.
+    fn timeout(&self) -> Duration {
+        self.usb_settings.timeout.unwrap_or(TIMEOUT)
+    }
+
+    /// The maximum number of bytes of a 32 bit transfer, `probe_maximum` by default.
+    fn max_transfer_len(&self, probe_maximum: usize) -> usize {
+        self.usb_settings.chunk_size(probe_maximum, 4)
+    }
+
+    /// Check the firmware version `jtag_version` of an ST-Link with hardware version
+    /// `hw_version` against the versions required by probe-rs.
+    fn firmware_status(hw_version: u8, jtag_version: u8) -> FirmwareStatus {
+        if hw_version < 3 && jtag_version < Self::MIN_JTAG_VERSION {
+            FirmwareStatus::UpdateRequired(format!(
+                "probe-rs requires at least firmware V{hw_version}J{}",
+                Self::MIN_JTAG_VERSION
+            ))
+        } else if hw_version == 3 && jtag_version < Self::MIN_JTAG_VERSION_V3 {
+            FirmwareStatus::UpdateRequired(format!(
+                "probe-rs requires at least firmware V3J{}",
+                Self::MIN_JTAG_VERSION_V3
+            ))
+        } else if hw_version < 3 && jtag_version < Self::MIN_JTAG_VERSION_DP_BANK_SEL {
+            FirmwareStatus::UpdateRecommended(format!(
+                "firmware V{hw_version}J{} or newer is needed to access multiple APs and banked DP registers",
+                Self::MIN_JTAG_VERSION_DP_BANK_SEL
+            ))
+        } else {
+            FirmwareStatus::UpToDate
+        }
+    }
+
     /// Get the current mode of the ST-Link
     fn get_current_mode(&mut self) -> Result<Mode, StlinkError> {
         tracing::trace!("Getting current mode of device...");
         let mut buf = [0; 2];
         self.device
-            .write(&[commands::GET_CURRENT_MODE], &[], &mut buf, TIMEOUT)?;
+            .write(&[commands::GET_CURRENT_MODE], &[], &mut buf, self.timeout())?;
 
         use Mode::*;
 
@@ -411,19 +492,19 @@ impl<D: StLinkUsb> StLink<D> {
                 &[commands::JTAG_COMMAND, commands::JTAG_EXIT],
                 &[],
                 &mut [],
-                TIMEOUT,
+                self.timeout(),
             ),
             Mode::Dfu => self.device.write(
                 &[commands::DFU_COMMAND, commands::DFU_EXIT],
                 &[],
                 &mut [],
-                TIMEOUT,
+                self.timeout(),
             ),
             Mode::Swim => self.device.write(
                 &[commands::SWIM_COMMAND, commands::SWIM_EXIT],
                 &[],
                 &mut [],
-                TIMEOUT,
+                self.timeout(),
             ),
             _ => Ok(()),
         }
@@ -446,7 +527,7 @@ impl<D: StLinkUsb> StLink<D> {
         //   Byte 4-5: STLINK_PID
         let mut buf = [0; 6];
         self.device
-            .write(&[commands::GET_VERSION], &[], &mut buf, TIMEOUT)
+            .write(&[commands::GET_VERSION], &[], &mut buf, self.timeout())
             .map(|_| {
                 let version: u16 = buf[0..2].pread_with(0, BE).unwrap();
                 self.hw_version = (version >> HW_VERSION_SHIFT) as u8 & HW_VERSION_MASK;
@@ -466,7 +547,7 @@ impl<D: StLinkUsb> StLink<D> {
             //  10-11: STLINK_PID
             let mut buf = [0; 12];
             self.device
-                .write(&[commands::GET_VERSION_EXT], &[], &mut buf, TIMEOUT)
+                .write(&[commands::GET_VERSION_EXT], &[], &mut buf, self.timeout())
                 .map(|_| {
                     let version: u8 = buf[2..3].pread_with(0, LE).unwrap();
                     self.jtag_version = version;
@@ -531,7 +612,7 @@ impl<D: StLinkUsb> StLink<D> {
             ],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         Ok(())
@@ -551,7 +632,7 @@ impl<D: StLinkUsb> StLink<D> {
             ],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         Ok(())
@@ -578,7 +659,7 @@ impl<D: StLinkUsb> StLink<D> {
         command.extend_from_slice(&frequency_khz.to_le_bytes());
 
         let mut buf = [0; 8];
-        self.send_jtag_command(&command, &[], &mut buf, TIMEOUT)?;
+        self.send_jtag_command(&command, &[], &mut buf, self.timeout())?;
 
         Ok(())
     }
@@ -604,7 +685,7 @@ impl<D: StLinkUsb> StLink<D> {
             &[commands::JTAG_COMMAND, commands::GET_COM_FREQ, cmd_proto],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         let mut values = buf
@@ -662,7 +743,7 @@ impl<D: StLinkUsb> StLink<D> {
                 &[commands::JTAG_COMMAND, commands::JTAG_INIT_AP, apsel],
                 &[],
                 &mut buf,
-                TIMEOUT,
+                self.timeout(),
             )
         })?;
 
@@ -686,7 +767,7 @@ impl<D: StLinkUsb> StLink<D> {
                 &[commands::JTAG_COMMAND, commands::JTAG_CLOSE_AP_DBG, apsel],
                 &[],
                 &mut buf,
-                TIMEOUT,
+                self.timeout(),
             )
         })?;
 
@@ -718,7 +799,7 @@ impl<D: StLinkUsb> StLink<D> {
         command.extend_from_slice(&bufsize);
         command.extend_from_slice(&baud);
 
-        self.send_jtag_command(&command, &[], &mut buf, TIMEOUT)?;
+        self.send_jtag_command(&command, &[], &mut buf, self.timeout())?;
 
         self.swo_enabled = true;
 
@@ -732,7 +813,7 @@ impl<D: StLinkUsb> StLink<D> {
             &[commands::JTAG_COMMAND, commands::SWO_STOP_TRACE_RECEPTION],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         self.swo_enabled = false;
@@ -750,7 +831,7 @@ impl<D: StLinkUsb> StLink<D> {
             ],
             &[],
             &mut buf,
-            TIMEOUT,
+            self.timeout(),
         )?;
         Ok(buf.pread::<u16>(0).unwrap() as usize)
     }
@@ -773,7 +854,7 @@ impl<D: StLinkUsb> StLink<D> {
             &[commands::JTAG_COMMAND, commands::JTAG_GETLASTRWSTATUS2],
             &[],
             &mut receive_buffer,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         Ok(())
@@ -801,7 +882,7 @@ impl<D: StLinkUsb> StLink<D> {
             0, // Maximum address for DAP registers is 0xFC
         ];
         let mut buf = [0; 8];
-        retry_on_wait(|| self.send_jtag_command(cmd, &[], &mut buf, TIMEOUT))?;
+        retry_on_wait(|| self.send_jtag_command(cmd, &[], &mut buf, self.timeout()))?;
         // Unwrap is ok!
         Ok(buf[4..8].pread_with(0, LE).unwrap())
     }
@@ -834,7 +915,7 @@ impl<D: StLinkUsb> StLink<D> {
         ];
         let mut buf = [0; 2];
 
-        retry_on_wait(|| self.send_jtag_command(cmd, &[], &mut buf, TIMEOUT))?;
+        retry_on_wait(|| self.send_jtag_command(cmd, &[], &mut buf, self.timeout()))?;
 
         Ok(())
     }
@@ -882,7 +963,7 @@ impl<D: StLinkUsb> StLink<D> {
                 ],
                 &[],
                 data,
-                TIMEOUT,
+                self.timeout(),
             )?;
 
             self.get_last_rw_status()
@@ -945,7 +1026,7 @@ impl<D: StLinkUsb> StLink<D> {
                 ],
                 &[],
                 &mut receive_buffer,
-                TIMEOUT,
+                self.timeout(),
             )?;
 
             if length == 1 {
@@ -1001,7 +1082,7 @@ impl<D: StLinkUsb> StLink<D> {
                 ],
                 data,
                 &mut [],
-                TIMEOUT,
+                self.timeout(),
             )?;
 
             self.get_last_rw_status()
@@ -1050,7 +1131,7 @@ impl<D: StLinkUsb> StLink<D> {
                 ],
                 data,
                 &mut [],
-                TIMEOUT,
+                self.timeout(),
             )?;
 
             self.get_last_rw_status()
@@ -1075,7 +1156,7 @@ impl<D: StLinkUsb> StLink<D> {
             ],
             &[],
             &mut buff,
-            TIMEOUT,
+            self.timeout(),
         )?;
 
         Ok(buff.pread(4).unwrap())
@@ -1092,7 +1173,7 @@ impl<D: StLinkUsb> StLink<D> {
         cmd.pwrite_with(address, 2, LE).unwrap();
         cmd.pwrite_with(value, 6, LE).unwrap();
 
-        self.send_jtag_command(&cmd, &[], &mut buff, TIMEOUT)?;
+        self.send_jtag_command(&cmd, &[], &mut buff, self.timeout())?;
 
         Ok(())
     }
@@ -1167,15 +1248,20 @@ impl From<StlinkError> for ProbeCreationError {
 #[derive(Debug)]
 struct UninitializedStLink {
     probe: Box<StLink<StLinkUsbDevice>>,
+    ap_scan: ApScanOptions,
 }
 
 impl UninitializedArmProbe for UninitializedStLink {
+    fn set_ap_scan_options(&mut self, options: ApScanOptions) {
+        self.ap_scan = options;
+    }
+
     #[tracing::instrument(skip(self, _sequence))]
     fn initialize(
         self: Box<Self>,
         _sequence: Arc<dyn ArmDebugSequence>,
     ) -> Result<Box<dyn ArmProbeInterface>, (Box<dyn UninitializedArmProbe>, ProbeRsError)> {
-        let interface = StlinkArmDebug::new(self.probe)
+        let interface = StlinkArmDebug::new(self.probe, self.ap_scan)
             .map_err(|(s, e)| (s as Box<_>, ProbeRsError::from(e)))?;
 
         Ok(Box::new(interface))
@@ -1206,29 +1292,35 @@ impl SwdSequence for UninitializedStLink {
 struct StlinkArmDebug {
     probe: Box<StLink<StLinkUsbDevice>>,
 
-    /// Information about the APs of the target.
-    /// APs are identified by a number, starting from zero.
+    /// Information about the APs of the target, in the order of their numbers.
     pub ap_information: Vec<ApInformation>,
+
+    /// How the access ports are found.
+    ap_scan: ApScanOptions,
 }
 
 impl StlinkArmDebug {
     fn new(
         probe: Box<StLink<StLinkUsbDevice>>,
+        ap_scan: ApScanOptions,
     ) -> Result<Self, (Box<UninitializedStLink>, ArmError)> {
         // Determine the number and type of available APs.
 
         let mut interface = Self {
             probe,
             ap_information: Vec::new(),
+            ap_scan,
         };
 
-        for ap in valid_access_ports(&mut interface, DpAddress::Default) {
+        let ap_scan = interface.ap_scan.clone();
+        for ap in valid_access_ports(&mut interface, DpAddress::Default, &ap_scan) {
             let ap_state = match ApInformation::read_from_target(&mut interface, ap) {
                 Ok(state) => state,
                 Err(e) => {
                     return Err((
                         Box::new(UninitializedStLink {
                             probe: interface.probe,
+                            ap_scan,
                         }),
                         e,
                     ))
@@ -1323,7 +1415,11 @@ impl ArmProbeInterface for StlinkArmDebug {
             return Err(DebugProbeError::from(StlinkError::MultidropNotSupported).into());
         }
 
-        match self.ap_information.get(addr.ap as usize) {
+        match self
+            .ap_information
+            .iter()
+            .find(|information| information.address() == addr)
+        {
             Some(res) => Ok(res),
             None => Err(ArmError::ApDoesNotExist(addr)),
         }
@@ -1337,7 +1433,8 @@ impl ArmProbeInterface for StlinkArmDebug {
             return Err(DebugProbeError::from(StlinkError::MultidropNotSupported).into());
         }
 
-        for access_port in valid_access_ports(self, dp) {
+        let ap_scan = self.ap_scan.clone();
+        for access_port in valid_access_ports(self, dp, &ap_scan) {
             let idr: IDR = self.read_ap_register(access_port)?;
             tracing::debug!("{:#x?}", idr);
 
@@ -1350,7 +1447,9 @@ impl ArmProbeInterface for StlinkArmDebug {
 
                 let component = Component::try_parse(&mut *memory, baseaddr)?;
 
-                if let Component::Class1RomTable(component_id, _) = component {
+                if let Component::Class1RomTable(component_id, _)
+                | Component::Class9RomTable(component_id, _) = component
+                {
                     if let Some(jep106) = component_id.peripheral_id().jep106() {
                         return Ok(Some(ArmChipInfo {
                             manufacturer: jep106,
@@ -1372,6 +1471,18 @@ impl ArmProbeInterface for StlinkArmDebug {
         Ok(self.ap_information.len())
     }
 
+    fn access_ports(&mut self, dp: DpAddress) -> Result<Vec<GenericAp>, ArmError> {
+        if dp != DpAddress::Default {
+            return Err(DebugProbeError::from(StlinkError::MultidropNotSupported).into());
+        }
+
+        Ok(self
+            .ap_information
+            .iter()
+            .map(|information| GenericAp::new(information.address()))
+            .collect())
+    }
+
     fn close(self: Box<Self>) -> Probe {
         Probe::from_attached_probe(self.probe)
     }
@@ -1455,11 +1566,12 @@ impl ArmProbe for StLinkMemoryInterface<'_> {
         let address = valid_32bit_arm_address(address)?;
 
         // Read needs to be chunked into chunks with appropiate max length (see STLINK_MAX_READ_LEN).
-        for (index, chunk) in data.chunks_mut(STLINK_MAX_READ_LEN / 4).enumerate() {
+        let chunk_len = self.probe.probe.max_transfer_len(STLINK_MAX_READ_LEN);
+        for (index, chunk) in data.chunks_mut(chunk_len / 4).enumerate() {
             let mut buff = vec![0u8; 4 * chunk.len()];
 
             self.probe.probe.read_mem_32bit(
-                address + (index * STLINK_MAX_READ_LEN) as u32,
+                address + (index * chunk_len) as u32,
                 &mut buff,
                 self.current_ap.ap_address().ap,
             )?;
@@ -1485,6 +1597,7 @@ impl ArmProbe for StLinkMemoryInterface<'_> {
             // See code of `read_mem_8bit` for more info.
             128
         };
+        let chunk_size = self.probe.probe.usb_settings.chunk_size(chunk_size, 1);
 
         for (index, chunk) in data.chunks_mut(chunk_size).enumerate() {
             chunk.copy_from_slice(&self.probe.probe.read_mem_8bit(
@@ -1510,9 +1623,10 @@ impl ArmProbe for StLinkMemoryInterface<'_> {
                 .expect("Failed to write into tx_buffer");
         }
 
-        for (index, chunk) in tx_buffer.chunks(STLINK_MAX_WRITE_LEN).enumerate() {
+        let chunk_len = self.probe.probe.max_transfer_len(STLINK_MAX_WRITE_LEN);
+        for (index, chunk) in tx_buffer.chunks(chunk_len).enumerate() {
             self.probe.probe.write_mem_32bit(
-                address + (index * STLINK_MAX_WRITE_LEN) as u32,
+                address + (index * chunk_len) as u32,
                 chunk,
                 self.current_ap.ap_address().ap,
             )?;
@@ -1534,9 +1648,10 @@ impl ArmProbe for StLinkMemoryInterface<'_> {
                 .expect("Failed to write into tx_buffer");
         }
 
-        for (index, chunk) in tx_buffer.chunks(STLINK_MAX_WRITE_LEN).enumerate() {
+        let chunk_len = self.probe.probe.max_transfer_len(STLINK_MAX_WRITE_LEN);
+        for (index, chunk) in tx_buffer.chunks(chunk_len).enumerate() {
             self.probe.probe.write_mem_32bit(
-                address + (index * STLINK_MAX_WRITE_LEN) as u32,
+                address + (index * chunk_len) as u32,
                 chunk,
                 self.current_ap.ap_address().ap,
             )?;
@@ -1556,6 +1671,7 @@ impl ArmProbe for StLinkMemoryInterface<'_> {
         } else {
             512
         };
+        let chunk_size = self.probe.probe.usb_settings.chunk_size(chunk_size, 4);
 
         // If we write less than 64 bytes, just write it directly
         if data.len() < chunk_size {
@@ -1599,12 +1715,13 @@ impl ArmProbe for StLinkMemoryInterface<'_> {
                 current_address,
             );
 
+            let chunk_len = self.probe.probe.max_transfer_len(STLINK_MAX_WRITE_LEN);
             for (index, chunk) in data[bytes_beginning..(bytes_beginning + aligned_len)]
-                .chunks(STLINK_MAX_WRITE_LEN)
+                .chunks(chunk_len)
                 .enumerate()
             {
                 self.probe.probe.write_mem_32bit(
-                    current_address + (index * STLINK_MAX_WRITE_LEN) as u32,
+                    current_address + (index * chunk_len) as u32,
                     chunk,
                     self.current_ap.ap_address().ap,
                 )?;
@@ -1714,6 +1831,8 @@ mod test {
                 swd_speed_khz: 0,
                 jtag_speed_khz: 0,
                 swo_enabled: false,
+                reset_timing: ResetTiming::default(),
+                usb_settings: UsbSettings::default(),
                 opened_aps: vec![],
             }
         }
@@ -1859,4 +1978,24 @@ mod test {
             Status::SwdApWait
         )));
     }
+
+    #[test]
+    fn firmware_status() {
+        type Probe = StLink<MockUsb>;
+
+        assert!(matches!(
+            Probe::firmware_status(2, 25),
+            FirmwareStatus::UpdateRequired(_)
+        ));
+        assert!(matches!(
+            Probe::firmware_status(2, 28),
+            FirmwareStatus::UpdateRecommended(_)
+        ));
+        assert_eq!(Probe::firmware_status(2, 37), FirmwareStatus::UpToDate);
+        assert!(matches!(
+            Probe::firmware_status(3, 2),
+            FirmwareStatus::UpdateRequired(_)
+        ));
+        assert_eq!(Probe::firmware_status(3, 7), FirmwareStatus::UpToDate);
+    }
 }
@@ -0,0 +1,418 @@
+//! Memory operations executed by the target.
+//!
+//! Writing large amounts of memory word by word through the access port is slow with probes
+//! which have a low transfer rate, or a high latency per transfer. A [`RamStub`] is a small
+//! routine loaded into RAM of the target, which fills, copies and compares memory with the
+//! core itself, so only the arguments of an operation have to be transferred.
+//!
+//! [`RamStub::write`] uses the stub transparently: runs of repeated words in the written data,
+//! such as zero-initialized buffers, are filled on the target, and all other data is written
+//! through the probe.
+//!
+//! The stub is only available for Cortex-M cores. While an operation runs, the core executes
+//! the stub with interrupts masked, and its registers are restored afterwards, so the state of
+//! the halted program is not changed, apart from the memory used by the stub.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe_rs::ram_stub::RamStub;
+//! use probe_rs::{Permissions, Session};
+//!
+//! let mut session = Session::auto_attach("nrf52840_xxAA", Permissions::default())?;
+//! let mut core = session.core(0)?;
+//! core.halt(std::time::Duration::from_millis(100))?;
+//!
+//! // The last 64 bytes of RAM are not used by the application.
+//! let stub = RamStub::load(&mut core, 0x2003_FFC0)?;
+//! stub.fill(&mut core, 0x2000_0000, 0x3_0000, 0)?;
+//! # Ok::<(), probe_rs::Error>(())
+//! ```
+
+use std::time::Duration;
+
+use crate::{Core, CoreType, Error, MemoryInterface, RegisterId};
+
+/// The machine code of the stub, in Thumb instructions which are supported by all Cortex-M
+/// cores.
+///
+/// The routines take their arguments in `r0` to `r2` and return to the breakpoint at the start
+/// of the stub.
+const STUB: &[u16] = &[
+    // 0x00: Return address.
+    0xBE00, // bkpt #0
+    0xBF00, // nop
+    // 0x04: fill(r0 = address, r1 = value, r2 = words)
+    0x2A00, // cmp r2, #0
+    0xD002, // beq 0x0E
+    0xC002, // stmia r0!, {r1}
+    0x3A01, // subs r2, #1
+    0xD1FC, // bne 0x08
+    0x4770, // 0x0E: bx lr
+    // 0x10: copy(r0 = destination, r1 = source, r2 = words)
+    0x2A00, // cmp r2, #0
+    0xD003, // beq 0x1C
+    0xC908, // ldmia r1!, {r3}
+    0xC008, // stmia r0!, {r3}
+    0x3A01, // subs r2, #1
+    0xD1FB, // bne 0x14
+    0x4770, // 0x1C: bx lr
+    0xBF00, // nop
+    // 0x20: compare(r0 = first, r1 = second, r2 = words), returns the index of the first
+    // differing word, or the number of words.
+    0x2300, // movs r3, #0
+    0x4293, // 0x22: cmp r3, r2
+    0xD005, // beq 0x32
+    0xC810, // ldmia r0!, {r4}
+    0xC920, // ldmia r1!, {r5}
+    0x42AC, // cmp r4, r5
+    0xD101, // bne 0x32
+    0x3301, // adds r3, #1
+    0xE7F7, // b 0x22
+    0x0018, // 0x32: movs r0, r3
+    0x4770, // bx lr
+    0xBF00, // nop
+];
+
+const FILL: u64 = 0x04;
+const COPY: u64 = 0x10;
+const COMPARE: u64 = 0x20;
+
+/// The time an operation may take, in addition to the time for the processed words.
+const BASE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The time an operation may take per MiB of processed memory.
+const TIMEOUT_PER_MIB: Duration = Duration::from_secs(1);
+
+/// The minimum number of repeated words which [`RamStub::write`] fills on the target, shorter
+/// runs are written through the probe.
+pub const MIN_FILL_WORDS: usize = 64;
+
+/// The bit of the xPSR which selects the Thumb state.
+const XPSR_THUMB: u32 = 1 << 24;
+
+/// The bit of the `EXTRA` register which holds PRIMASK.
+const PRIMASK: u32 = 1 << 0;
+
+/// A routine in RAM of the target which performs memory operations, see the [module
+/// documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RamStub {
+    address: u64,
+}
+
+impl RamStub {
+    /// The number of bytes of RAM used by the stub.
+    pub const SIZE: usize = STUB.len() * 2;
+
+    /// Load the stub into the [`RamStub::SIZE`] bytes of RAM at `address`, which has to be
+    /// aligned to 4 bytes and must not be used by the program of the target.
+    pub fn load(core: &mut Core, address: u64) -> Result<Self, Error> {
+        if !matches!(
+            core.core_type(),
+            CoreType::Armv6m | CoreType::Armv7m | CoreType::Armv7em | CoreType::Armv8m
+        ) {
+            return Err(Error::NotImplemented("RAM stubs for this architecture"));
+        }
+
+        check_alignment(address)?;
+
+        let code = STUB
+            .chunks(2)
+            .map(|halfwords| halfwords[0] as u32 | (halfwords[1] as u32) << 16)
+            .collect::<Vec<_>>();
+        core.write_32(address, &code)?;
+        core.flush()?;
+
+        Ok(Self { address })
+    }
+
+    /// The address of the RAM used by the stub.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Fill `len` bytes at `address` with the repeated word `value`.
+    ///
+    /// The address and the length have to be aligned to 4 bytes.
+    pub fn fill(&self, core: &mut Core, address: u64, len: u64, value: u32) -> Result<(), Error> {
+        check_alignment(address)?;
+        check_alignment(len)?;
+
+        self.call(core, FILL, [address, value.into(), len / 4], len)?;
+
+        Ok(())
+    }
+
+    /// Copy `len` bytes from `source` to `destination`, which must not overlap.
+    ///
+    /// The addresses and the length have to be aligned to 4 bytes.
+    pub fn copy(
+        &self,
+        core: &mut Core,
+        destination: u64,
+        source: u64,
+        len: u64,
+    ) -> Result<(), Error> {
+        check_alignment(destination)?;
+        check_alignment(source)?;
+        check_alignment(len)?;
+
+        self.call(core, COPY, [destination, source, len / 4], len)?;
+
+        Ok(())
+    }
+
+    /// Compare `len` bytes at `first` and `second`, and return the offset of the first word
+    /// which differs, or `None` if the memory is equal.
+    ///
+    /// The addresses and the length have to be aligned to 4 bytes.
+    pub fn compare(
+        &self,
+        core: &mut Core,
+        first: u64,
+        second: u64,
+        len: u64,
+    ) -> Result<Option<u64>, Error> {
+        check_alignment(first)?;
+        check_alignment(second)?;
+        check_alignment(len)?;
+
+        let words = len / 4;
+        let index = self.call(core, COMPARE, [first, second, words], 2 * len)?;
+
+        Ok((index < words).then_some(index * 4))
+    }
+
+    /// Write `data` to `address`, filling runs of at least [`MIN_FILL_WORDS`] repeated words
+    /// with the stub.
+    ///
+    /// The address and the length of the data have to be aligned to 4 bytes.
+    pub fn write(&self, core: &mut Core, address: u64, data: &[u8]) -> Result<(), Error> {
+        check_alignment(address)?;
+        check_alignment(data.len() as u64)?;
+
+        let words = data
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect::<Vec<_>>();
+
+        for segment in segments(&words, MIN_FILL_WORDS) {
+            let segment_address = address + segment.start as u64 * 4;
+
+            match segment.fill {
+                Some(value) => {
+                    core.flush()?;
+                    self.fill(core, segment_address, segment.len as u64 * 4, value)?;
+                }
+                None => core.write_32(
+                    segment_address,
+                    &words[segment.start..segment.start + segment.len],
+                )?,
+            }
+        }
+
+        core.flush()
+    }
+
+    /// Run the routine at `offset` with `arguments`, processing `len` bytes, and return its
+    /// result.
+    fn call(
+        &self,
+        core: &mut Core,
+        offset: u64,
+        arguments: [u64; 3],
+        len: u64,
+    ) -> Result<u64, Error> {
+        let registers = core.registers();
+        let extra = registers
+            .other_by_name("EXTRA")
+            .ok_or_else(|| Error::Register("The core has no PRIMASK register".to_owned()))?;
+        let psr = registers
+            .psr()
+            .ok_or_else(|| Error::Register("The core has no xPSR register".to_owned()))?;
+
+        let saved_ids = (0..6)
+            .map(|index| registers.core_register(index).id)
+            .chain([
+                core.program_counter().id,
+                core.return_address().id,
+                psr.id,
+                extra.id,
+            ])
+            .collect::<Vec<RegisterId>>();
+        let saved = core.read_core_regs(&saved_ids)?;
+
+        let result = self.run(core, offset, arguments, len, extra.id, psr.id);
+
+        if result.is_err() && !core.core_halted()? {
+            core.halt(BASE_TIMEOUT)?;
+        }
+
+        let restore = saved_ids.into_iter().zip(saved).collect::<Vec<_>>();
+        core.write_core_regs(&restore)?;
+
+        result
+    }
+
+    fn run(
+        &self,
+        core: &mut Core,
+        offset: u64,
+        arguments: [u64; 3],
+        len: u64,
+        extra: RegisterId,
+        psr: RegisterId,
+    ) -> Result<u64, Error> {
+        let registers = core.registers();
+
+        for (index, argument) in arguments.into_iter().enumerate() {
+            core.write_core_reg(registers.argument_register(index).id, argument as u32)?;
+        }
+
+        // Return to the breakpoint in Thumb state.
+        core.write_core_reg(core.return_address().id, self.address as u32 | 1)?;
+        core.write_core_reg(core.program_counter().id, (self.address + offset) as u32)?;
+        core.write_core_reg(psr, XPSR_THUMB)?;
+
+        let extra_value: u32 = core.read_core_reg(extra)?;
+        core.write_core_reg(extra, extra_value | PRIMASK)?;
+
+        core.run()?;
+        core.wait_for_core_halted(timeout(len))?;
+
+        let pc: u64 = core.read_core_reg(core.program_counter().id)?;
+        if pc != self.address {
+            return Err(Error::Other(anyhow::anyhow!(
+                "The RAM stub halted at {:#010x} instead of its breakpoint at {:#010x}",
+                pc,
+                self.address
+            )));
+        }
+
+        let result: u32 = core.read_core_reg(registers.result_register(0).id)?;
+
+        Ok(result.into())
+    }
+}
+
+fn check_alignment(value: u64) -> Result<(), Error> {
+    if value % 4 != 0 {
+        return Err(Error::MemoryNotAligned {
+            address: value,
+            alignment: 4,
+        });
+    }
+
+    Ok(())
+}
+
+/// The maximum duration of an operation processing `len` bytes.
+fn timeout(len: u64) -> Duration {
+    BASE_TIMEOUT + TIMEOUT_PER_MIB.mul_f64(len as f64 / (1024.0 * 1024.0))
+}
+
+/// A part of the data written by [`RamStub::write`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    /// The index of the first word.
+    start: usize,
+    /// The number of words.
+    len: usize,
+    /// The repeated word, if the segment is filled on the target.
+    fill: Option<u32>,
+}
+
+/// Split `words` into runs of at least `min_fill` repeated words, and the data between them.
+fn segments(words: &[u32], min_fill: usize) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut written = 0;
+    let mut start = 0;
+
+    while start < words.len() {
+        let run = words[start..]
+            .iter()
+            .take_while(|&&word| word == words[start])
+            .count();
+
+        if run >= min_fill {
+            if written < start {
+                segments.push(Segment {
+                    start: written,
+                    len: start - written,
+                    fill: None,
+                });
+            }
+            segments.push(Segment {
+                start,
+                len: run,
+                fill: Some(words[start]),
+            });
+            written = start + run;
+        }
+
+        start += run;
+    }
+
+    if written < words.len() {
+        segments.push(Segment {
+            start: written,
+            len: words.len() - written,
+            fill: None,
+        });
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{segments, Segment};
+
+    #[test]
+    fn repeated_words_are_filled() {
+        let mut words = vec![1, 2, 3];
+        words.extend([0; 8]);
+        words.extend([4, 4]);
+        words.extend([0xFF; 4]);
+
+        assert_eq!(
+            segments(&words, 4),
+            vec![
+                Segment {
+                    start: 0,
+                    len: 3,
+                    fill: None,
+                },
+                Segment {
+                    start: 3,
+                    len: 8,
+                    fill: Some(0),
+                },
+                Segment {
+                    start: 11,
+                    len: 2,
+                    fill: None,
+                },
+                Segment {
+                    start: 13,
+                    len: 4,
+                    fill: Some(0xFF),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn short_runs_are_written() {
+        assert_eq!(
+            segments(&[7, 7, 7, 1], 4),
+            vec![Segment {
+                start: 0,
+                len: 4,
+                fill: None,
+            }]
+        );
+        assert_eq!(segments(&[], 4), vec![]);
+    }
+}
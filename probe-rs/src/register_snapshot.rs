@@ -0,0 +1,240 @@
+//! Comparison of peripheral registers before and after an action.
+//!
+//! When debug access stops working after the firmware ran for a while, the cause is usually a
+//! reconfiguration of the clocks, the power domains or the pin muxing, which disables the debug
+//! logic or the pins of the debug port. A [`RegisterSnapshot`] records the values of a set of
+//! registers, for example those of the clock and power controllers from the SVD file of the
+//! chip, so the snapshots taken before and after the suspicious code ran can be compared with
+//! [`RegisterSnapshot::diff`].
+//!
+//! Registers which cannot be read are recorded as such instead of failing the snapshot, as
+//! the registers of a peripheral without clock often cause a bus fault, and the snapshot after
+//! the action might be taken after debug access was lost.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe_rs::register_snapshot::{snapshot_around, WatchedRegister};
+//! use probe_rs::{Permissions, Session};
+//!
+//! let mut session = Session::auto_attach("nrf52840_xxAA", Permissions::default())?;
+//! let mut core = session.core(0)?;
+//!
+//! let registers = [WatchedRegister::new("CLOCK.HFCLKSTAT", 0x4000_040C)];
+//! let ((), changes) = snapshot_around(&mut core, &registers, |core| {
+//!     core.run()?;
+//!     std::thread::sleep(std::time::Duration::from_millis(100));
+//!     core.halt(std::time::Duration::from_millis(100))?;
+//!     Ok(())
+//! })?;
+//!
+//! for change in changes {
+//!     println!("{}: {:x?} -> {:x?}", change.name, change.before, change.after);
+//! }
+//! # Ok::<(), probe_rs::Error>(())
+//! ```
+
+use crate::{Error, MemoryInterface};
+
+/// A 32-bit register which is recorded in a [`RegisterSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchedRegister {
+    /// The name of the register, for example `RCC.CR`.
+    pub name: String,
+    /// The address of the register.
+    pub address: u64,
+}
+
+impl WatchedRegister {
+    /// Create a register with a `name` at `address`.
+    pub fn new(name: impl Into<String>, address: u64) -> Self {
+        Self {
+            name: name.into(),
+            address,
+        }
+    }
+}
+
+/// A register and its value in a [`RegisterSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRegister {
+    /// The recorded register.
+    pub register: WatchedRegister,
+    /// The value of the register, or `None` if it could not be read.
+    pub value: Option<u32>,
+}
+
+/// A register which differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterChange {
+    /// The name of the register.
+    pub name: String,
+    /// The address of the register.
+    pub address: u64,
+    /// The value in the first snapshot, or `None` if it could not be read.
+    pub before: Option<u32>,
+    /// The value in the second snapshot, or `None` if it could not be read.
+    pub after: Option<u32>,
+}
+
+impl RegisterChange {
+    /// The bits which differ, if the register could be read in both snapshots.
+    pub fn changed_bits(&self) -> Option<u32> {
+        Some(self.before? ^ self.after?)
+    }
+}
+
+/// The values of a set of registers at one point in time, see the [module
+/// documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterSnapshot {
+    /// The registers, in the order in which they were read.
+    pub registers: Vec<RecordedRegister>,
+}
+
+impl RegisterSnapshot {
+    /// Read the `registers` with 32-bit accesses.
+    ///
+    /// Failed reads are recorded as `None`. Only the error of flushing the memory interface,
+    /// which means that the probe cannot be used anymore, is returned.
+    pub fn capture<'a>(
+        memory: &mut impl MemoryInterface,
+        registers: impl IntoIterator<Item = &'a WatchedRegister>,
+    ) -> Result<Self, Error> {
+        let registers = registers
+            .into_iter()
+            .map(|register| RecordedRegister {
+                register: register.clone(),
+                value: memory.read_word_32(register.address).ok(),
+            })
+            .collect();
+
+        memory.flush()?;
+
+        Ok(Self { registers })
+    }
+
+    /// The registers whose value, or readability, differs in the `later` snapshot.
+    ///
+    /// Registers are matched by address, in the order of this snapshot. Registers which are
+    /// only in one of the snapshots are ignored.
+    pub fn diff(&self, later: &RegisterSnapshot) -> Vec<RegisterChange> {
+        self.registers
+            .iter()
+            .filter_map(|before| {
+                let after = later
+                    .registers
+                    .iter()
+                    .find(|after| after.register.address == before.register.address)?;
+
+                (before.value != after.value).then(|| RegisterChange {
+                    name: before.register.name.clone(),
+                    address: before.register.address,
+                    before: before.value,
+                    after: after.value,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Snapshot the `registers`, run `action`, and return its result with the registers which
+/// changed.
+pub fn snapshot_around<M: MemoryInterface, T>(
+    memory: &mut M,
+    registers: &[WatchedRegister],
+    action: impl FnOnce(&mut M) -> Result<T, Error>,
+) -> Result<(T, Vec<RegisterChange>), Error> {
+    let before = RegisterSnapshot::capture(memory, registers)?;
+    let result = action(memory)?;
+    let after = RegisterSnapshot::capture(memory, registers)?;
+
+    Ok((result, before.diff(&after)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        snapshot_around, RecordedRegister, RegisterChange, RegisterSnapshot, WatchedRegister,
+    };
+    use crate::rtos::test_memory::TestMemory;
+    use crate::MemoryInterface;
+
+    fn recorded(name: &str, address: u64, value: Option<u32>) -> RecordedRegister {
+        RecordedRegister {
+            register: WatchedRegister::new(name, address),
+            value,
+        }
+    }
+
+    #[test]
+    fn changed_and_unreadable_registers_are_reported() {
+        let before = RegisterSnapshot {
+            registers: vec![
+                recorded("CR", 0x100, Some(0x83)),
+                recorded("CFGR", 0x104, Some(0)),
+                recorded("PLLCFGR", 0x108, Some(0x1000)),
+                recorded("APB1ENR", 0x10C, None),
+            ],
+        };
+        let after = RegisterSnapshot {
+            registers: vec![
+                recorded("CFGR", 0x104, Some(0)),
+                recorded("CR", 0x100, Some(0x0300_0083)),
+                recorded("PLLCFGR", 0x108, None),
+                recorded("APB1ENR", 0x10C, None),
+            ],
+        };
+
+        let changes = before.diff(&after);
+
+        assert_eq!(
+            changes,
+            [
+                RegisterChange {
+                    name: "CR".to_owned(),
+                    address: 0x100,
+                    before: Some(0x83),
+                    after: Some(0x0300_0083),
+                },
+                RegisterChange {
+                    name: "PLLCFGR".to_owned(),
+                    address: 0x108,
+                    before: Some(0x1000),
+                    after: None,
+                },
+            ]
+        );
+        assert_eq!(changes[0].changed_bits(), Some(0x0300_0000));
+        assert_eq!(changes[1].changed_bits(), None);
+    }
+
+    #[test]
+    fn snapshot_around_action() {
+        let mut memory = TestMemory::default();
+        memory.set_word(0x4000_0000, 1);
+        memory.set_word(0x4000_0004, 2);
+
+        let registers = [
+            WatchedRegister::new("A", 0x4000_0000),
+            WatchedRegister::new("B", 0x4000_0004),
+        ];
+
+        let (result, changes) = snapshot_around(&mut memory, &registers, |memory| {
+            memory.write_word_32(0x4000_0004, 3)?;
+            Ok(42)
+        })
+        .unwrap();
+
+        assert_eq!(result, 42);
+        assert_eq!(
+            changes,
+            [RegisterChange {
+                name: "B".to_owned(),
+                address: 0x4000_0004,
+                before: Some(2),
+                after: Some(3),
+            }]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Static variables and the layout of their types, read from the DWARF debug information.
+//!
+//! The structures of Rust schedulers are generic and not `repr(C)`, so their layout can only be
+//! taken from the debug information of the application.
+
+use crate::debug::debug_info::DwarfReader;
+use gimli::{AttributeValue, DebuggingInformationEntry, Operation, Reader, Unit, UnitOffset};
+use object::{Object, ObjectSection};
+use std::borrow::Cow;
+use std::rc::Rc;
+
+/// A type described by a debugging information entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TypeRef {
+    unit: usize,
+    offset: UnitOffset,
+}
+
+/// A static variable of the application.
+#[derive(Debug, Clone)]
+pub(crate) struct StaticVariable {
+    /// The name of the variable, including the namespaces it is declared in, like
+    /// `app::task::POOL`.
+    pub(crate) path: String,
+    /// The address of the variable.
+    pub(crate) address: u64,
+    /// The type of the variable.
+    pub(crate) ty: TypeRef,
+}
+
+impl StaticVariable {
+    /// Returns the name of the variable, without its namespaces.
+    pub(crate) fn name(&self) -> &str {
+        self.path.rsplit("::").next().unwrap_or(&self.path)
+    }
+
+    /// Returns the innermost namespace the variable is declared in, if any.
+    pub(crate) fn parent(&self) -> Option<&str> {
+        self.path.rsplit("::").nth(1)
+    }
+}
+
+/// The static variables of an application and their types.
+pub(crate) struct DebugTypes {
+    dwarf: gimli::Dwarf<DwarfReader>,
+    units: Vec<Unit<DwarfReader>>,
+    variables: Vec<StaticVariable>,
+}
+
+impl std::fmt::Debug for DebugTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebugTypes")
+            .field("variables", &self.variables.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl DebugTypes {
+    /// Read the static variables from the DWARF sections of `file`.
+    pub(crate) fn from_object(file: &object::File) -> Result<Self, gimli::Error> {
+        let load_section = |id: gimli::SectionId| -> Result<DwarfReader, gimli::Error> {
+            let data = file
+                .section_by_name(id.name())
+                .and_then(|section| section.uncompressed_data().ok())
+                .unwrap_or(Cow::Borrowed(&[][..]));
+
+            Ok(gimli::read::EndianRcSlice::new(
+                Rc::from(&*data),
+                gimli::LittleEndian,
+            ))
+        };
+
+        let dwarf = gimli::Dwarf::load(load_section)?;
+
+        let mut units = Vec::new();
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next()? {
+            units.push(dwarf.unit(header)?);
+        }
+
+        let mut types = Self {
+            dwarf,
+            units,
+            variables: Vec::new(),
+        };
+
+        for unit in 0..types.units.len() {
+            let variables = types.unit_variables(unit)?;
+            types.variables.extend(variables);
+        }
+
+        Ok(types)
+    }
+
+    /// Returns all static variables with a fixed address.
+    pub(crate) fn variables(&self) -> &[StaticVariable] {
+        &self.variables
+    }
+
+    fn unit_variables(&self, index: usize) -> Result<Vec<StaticVariable>, gimli::Error> {
+        let unit = &self.units[index];
+        let mut variables = Vec::new();
+
+        // The namespaces enclosing the current entry, with their depth in the tree.
+        let mut namespaces: Vec<(isize, String)> = Vec::new();
+        let mut depth = 0;
+
+        let mut entries = unit.entries();
+        while let Some((delta, entry)) = entries.next_dfs()? {
+            depth += delta;
+
+            while namespaces.last().is_some_and(|(d, _)| *d >= depth) {
+                namespaces.pop();
+            }
+
+            match entry.tag() {
+                gimli::DW_TAG_namespace => {
+                    let name = self.name(unit, entry).unwrap_or_default();
+                    namespaces.push((depth, name));
+                }
+                gimli::DW_TAG_variable => {
+                    let (Some(name), Some(address), Some(ty)) = (
+                        self.name(unit, entry),
+                        static_address(unit, entry),
+                        type_attribute(entry).and_then(|value| self.resolve(index, value)),
+                    ) else {
+                        continue;
+                    };
+
+                    let path = namespaces
+                        .iter()
+                        .map(|(_, namespace)| namespace.as_str())
+                        .chain(std::iter::once(name.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("::");
+
+                    variables.push(StaticVariable { path, address, ty });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(variables)
+    }
+
+    /// Returns the name of type `ty`.
+    pub(crate) fn type_name(&self, ty: TypeRef) -> Option<String> {
+        let unit = &self.units[ty.unit];
+        let entry = unit.entry(self.strip_typedefs(ty)?.offset).ok()?;
+
+        self.name(unit, &entry)
+    }
+
+    /// Returns the size of type `ty` in bytes.
+    pub(crate) fn size(&self, ty: TypeRef) -> Option<u64> {
+        let ty = self.strip_typedefs(ty)?;
+        let entry = self.units[ty.unit].entry(ty.offset).ok()?;
+
+        entry
+            .attr_value(gimli::DW_AT_byte_size)
+            .ok()??
+            .udata_value()
+    }
+
+    /// Returns the offset and the type of member `name` of the structure `ty`.
+    pub(crate) fn member(&self, ty: TypeRef, name: &str) -> Option<(u64, TypeRef)> {
+        let ty = self.strip_typedefs(ty)?;
+        let unit = &self.units[ty.unit];
+
+        let mut tree = unit.entries_tree(Some(ty.offset)).ok()?;
+        let mut children = tree.root().ok()?.children();
+
+        while let Some(child) = children.next().ok()? {
+            let entry = child.entry();
+
+            if entry.tag() != gimli::DW_TAG_member
+                || self.name(unit, entry).as_deref() != Some(name)
+            {
+                continue;
+            }
+
+            let offset = match entry.attr_value(gimli::DW_AT_data_member_location).ok()? {
+                Some(value) => value.udata_value()?,
+                None => 0,
+            };
+            let member_type = self.resolve(ty.unit, type_attribute(entry)?)?;
+
+            return Some((offset, member_type));
+        }
+
+        None
+    }
+
+    /// Returns the number of elements and the element type of the array `ty`.
+    pub(crate) fn array(&self, ty: TypeRef) -> Option<(u64, TypeRef)> {
+        let ty = self.strip_typedefs(ty)?;
+        let unit = &self.units[ty.unit];
+
+        let entry = unit.entry(ty.offset).ok()?;
+        if entry.tag() != gimli::DW_TAG_array_type {
+            return None;
+        }
+        let element = self.resolve(ty.unit, type_attribute(&entry)?)?;
+
+        let mut tree = unit.entries_tree(Some(ty.offset)).ok()?;
+        let mut children = tree.root().ok()?.children();
+
+        while let Some(child) = children.next().ok()? {
+            let entry = child.entry();
+
+            if entry.tag() != gimli::DW_TAG_subrange_type {
+                continue;
+            }
+
+            if let Some(count) = entry.attr_value(gimli::DW_AT_count).ok()? {
+                return Some((count.udata_value()?, element));
+            }
+
+            let upper_bound = entry.attr_value(gimli::DW_AT_upper_bound).ok()??;
+            return Some((upper_bound.udata_value()? + 1, element));
+        }
+
+        None
+    }
+
+    /// Follows typedefs and type qualifiers to the type they refer to.
+    fn strip_typedefs(&self, mut ty: TypeRef) -> Option<TypeRef> {
+        // Limits the number of steps, in case of a cycle in corrupted debug information.
+        for _ in 0..16 {
+            let entry = self.units[ty.unit].entry(ty.offset).ok()?;
+
+            match entry.tag() {
+                gimli::DW_TAG_typedef | gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type => {
+                    ty = self.resolve(ty.unit, type_attribute(&entry)?)?;
+                }
+                _ => return Some(ty),
+            }
+        }
+
+        None
+    }
+
+    /// Resolves a reference to a type, found in unit `unit`.
+    fn resolve(&self, unit: usize, value: AttributeValue<DwarfReader>) -> Option<TypeRef> {
+        match value {
+            AttributeValue::UnitRef(offset) => Some(TypeRef { unit, offset }),
+            AttributeValue::DebugInfoRef(offset) => {
+                self.units.iter().enumerate().find_map(|(unit, candidate)| {
+                    let offset = offset.to_unit_offset(&candidate.header)?;
+                    Some(TypeRef { unit, offset })
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn name(
+        &self,
+        unit: &Unit<DwarfReader>,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Option<String> {
+        let value = entry.attr_value(gimli::DW_AT_name).ok()??;
+        let name = self.dwarf.attr_string(unit, value).ok()?;
+
+        name.to_string_lossy().ok().map(|name| name.into_owned())
+    }
+}
+
+fn type_attribute(
+    entry: &DebuggingInformationEntry<DwarfReader>,
+) -> Option<AttributeValue<DwarfReader>> {
+    entry.attr_value(gimli::DW_AT_type).ok()?
+}
+
+/// Returns the address of a variable whose location is a fixed address.
+fn static_address(
+    unit: &Unit<DwarfReader>,
+    entry: &DebuggingInformationEntry<DwarfReader>,
+) -> Option<u64> {
+    let AttributeValue::Exprloc(expression) = entry.attr_value(gimli::DW_AT_location).ok()?? else {
+        return None;
+    };
+
+    match expression.operations(unit.encoding()).next().ok()? {
+        Some(Operation::Address { address }) => Some(address),
+        _ => None,
+    }
+}
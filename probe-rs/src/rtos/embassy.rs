@@ -0,0 +1,222 @@
+use super::debug_types::{DebugTypes, StaticVariable};
+use super::{RtosAwareness, RtosError, RtosSymbols, RtosThread, RtosThreadState, MAX_THREADS};
+use crate::{Core, MemoryInterface};
+
+/// Bits of the state word in a `TaskHeader`.
+const STATE_SPAWNED: u32 = 1 << 0;
+const STATE_RUN_QUEUED: u32 = 1 << 1;
+
+/// Where the state of a task is stored, relative to its `TaskStorage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StateLayout {
+    /// A word with the `STATE_*` bits.
+    Word(u64),
+    /// One byte per flag, as used by the `state_atomics_arm` implementation.
+    Flags { spawned: u64, run_queued: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EmbassyTask {
+    name: String,
+    /// The address of the `TaskStorage` of the task.
+    address: u64,
+    state: StateLayout,
+}
+
+/// Task awareness for the embassy executor.
+///
+/// The tasks are found through the `POOL` statics declared by `#[embassy_executor::task]`, and
+/// their layout is taken from the debug information, so the ELF file has to include it.
+///
+/// The tasks run on the stack of the executor and have no saved registers. Only spawned tasks
+/// are listed, and as embassy has no task priorities, they are listed without one.
+#[derive(Debug, Clone)]
+pub struct Embassy {
+    tasks: Vec<EmbassyTask>,
+}
+
+impl Embassy {
+    /// Use the task pools in `symbols`, if there are any.
+    pub fn from_symbols(symbols: &RtosSymbols) -> Option<Self> {
+        let types = symbols.debug_types()?;
+
+        let tasks = types
+            .variables()
+            .iter()
+            .filter(|variable| variable.name() == "POOL")
+            .filter_map(|pool| pool_tasks(types, pool))
+            .flatten()
+            .collect::<Vec<_>>();
+
+        (!tasks.is_empty()).then_some(Self { tasks })
+    }
+
+    fn read_threads(&self, memory: &mut dyn MemoryInterface) -> Result<Vec<RtosThread>, RtosError> {
+        let mut threads = Vec::new();
+
+        for task in &self.tasks {
+            let (spawned, run_queued) = match task.state {
+                StateLayout::Word(offset) => {
+                    let state = memory.read_word_32(task.address + offset)?;
+                    (state & STATE_SPAWNED != 0, state & STATE_RUN_QUEUED != 0)
+                }
+                StateLayout::Flags {
+                    spawned,
+                    run_queued,
+                } => (
+                    memory.read_word_8(task.address + spawned)? != 0,
+                    memory.read_word_8(task.address + run_queued)? != 0,
+                ),
+            };
+
+            if !spawned {
+                continue;
+            }
+
+            threads.push(RtosThread {
+                id: task.address,
+                name: task.name.clone(),
+                state: if run_queued {
+                    RtosThreadState::Ready
+                } else {
+                    RtosThreadState::Blocked
+                },
+                priority: None,
+                registers: Vec::new(),
+            });
+        }
+
+        Ok(threads)
+    }
+}
+
+impl RtosAwareness for Embassy {
+    fn name(&self) -> &'static str {
+        "embassy"
+    }
+
+    fn threads(&self, core: &mut Core) -> Result<Vec<RtosThread>, RtosError> {
+        self.read_threads(core)
+    }
+
+    fn shared_stack(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the tasks of the `TaskPool` in the static `pool`.
+fn pool_tasks(types: &DebugTypes, pool: &StaticVariable) -> Option<Vec<EmbassyTask>> {
+    if !types.type_name(pool.ty)?.starts_with("TaskPool<") {
+        return None;
+    }
+
+    let name = task_name(pool.parent()?);
+
+    let (pool_offset, storages) = types.member(pool.ty, "pool")?;
+    let (count, storage) = types.array(storages)?;
+    let storage_size = types.size(storage)?;
+
+    let (header_offset, header) = types.member(storage, "raw")?;
+    let (state_offset, state) = types.member(header, "state")?;
+    let state_offset = header_offset + state_offset;
+
+    let state = match (
+        types.member(state, "spawned"),
+        types.member(state, "run_queued"),
+    ) {
+        (Some((spawned, _)), Some((run_queued, _))) => StateLayout::Flags {
+            spawned: state_offset + spawned,
+            run_queued: state_offset + run_queued,
+        },
+        // Either an atomic word, or a `State` which wraps one.
+        _ => StateLayout::Word(
+            state_offset + types.member(state, "state").map_or(0, |(offset, _)| offset),
+        ),
+    };
+
+    let tasks = (0..count.min(MAX_THREADS as u64))
+        .map(|index| EmbassyTask {
+            name: if count > 1 {
+                format!("{name}[{index}]")
+            } else {
+                name.to_owned()
+            },
+            address: pool.address + pool_offset + index * storage_size,
+            state,
+        })
+        .collect();
+
+    Some(tasks)
+}
+
+/// Returns the name of the task whose pool is declared in `function`.
+///
+/// Depending on the version, `#[task]` declares the pool in the function named after the task,
+/// or in its inner `__<name>_task` function.
+fn task_name(function: &str) -> &str {
+    function
+        .strip_prefix("__")
+        .and_then(|function| function.strip_suffix("_task"))
+        .unwrap_or(function)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_memory::TestMemory;
+    use super::super::RtosThreadState;
+    use super::{task_name, Embassy, EmbassyTask, StateLayout, STATE_RUN_QUEUED, STATE_SPAWNED};
+
+    fn task(name: &str, address: u64, state: StateLayout) -> EmbassyTask {
+        EmbassyTask {
+            name: name.to_owned(),
+            address,
+            state,
+        }
+    }
+
+    #[test]
+    fn task_names_of_both_macro_versions() {
+        assert_eq!(task_name("blink"), "blink");
+        assert_eq!(task_name("__blink_task"), "blink");
+    }
+
+    #[test]
+    fn only_spawned_tasks_are_listed() {
+        let embassy = Embassy {
+            tasks: vec![
+                task("blink", 0x2000_0000, StateLayout::Word(0)),
+                task("idle", 0x2000_0100, StateLayout::Word(0)),
+                task("uart[0]", 0x2000_0200, StateLayout::Word(4)),
+                task(
+                    "button",
+                    0x2000_0300,
+                    StateLayout::Flags {
+                        spawned: 0,
+                        run_queued: 1,
+                    },
+                ),
+            ],
+        };
+
+        let mut memory = TestMemory::default();
+        memory.set_word(0x2000_0000, STATE_SPAWNED);
+        memory.set_word(0x2000_0204, STATE_SPAWNED | STATE_RUN_QUEUED);
+        memory.set_bytes(0x2000_0300, &[1, 0]);
+
+        let threads = embassy.read_threads(&mut memory).unwrap();
+
+        let summary: Vec<_> = threads
+            .iter()
+            .map(|thread| (thread.name.as_str(), thread.state, thread.priority))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("blink", RtosThreadState::Blocked, None),
+                ("uart[0]", RtosThreadState::Ready, None),
+                ("button", RtosThreadState::Blocked, None),
+            ]
+        );
+        assert!(threads.iter().all(|thread| thread.registers.is_empty()));
+    }
+}
@@ -0,0 +1,292 @@
+use super::{
+    is_supported_cortex_m, read_callee_saved, read_exception_frame, read_string, RtosAwareness,
+    RtosError, RtosSymbol, RtosSymbols, RtosThread, RtosThreadState, MAX_THREADS,
+};
+use crate::{Core, MemoryInterface};
+
+/// Size of a `List_t` on a 32-bit target.
+const LIST_SIZE: u64 = 20;
+/// Offset of `xListEnd` in a `List_t`.
+const LIST_END_OFFSET: u64 = 8;
+/// Offset of `pxNext` in a `ListItem_t` or `MiniListItem_t`.
+const LIST_ITEM_NEXT_OFFSET: u64 = 4;
+/// Offset of `pvOwner` in a `ListItem_t`.
+const LIST_ITEM_OWNER_OFFSET: u64 = 12;
+
+/// Offset of `uxPriority` in a `TCB_t`.
+const TCB_PRIORITY_OFFSET: u64 = 44;
+/// Offset of `pcTaskName` in a `TCB_t`.
+const TCB_NAME_OFFSET: u64 = 52;
+/// The default value of `configMAX_TASK_NAME_LEN`.
+const MAX_TASK_NAME_LEN: usize = 16;
+
+/// RTOS awareness for FreeRTOS on Cortex-M cores.
+///
+/// This expects the task control block layout of a 32-bit build without
+/// `configUSE_LIST_DATA_INTEGRITY_CHECK_BYTES` and without MPU support, and the context
+/// switch code of the `ARM_CM0`, `ARM_CM3`, `ARM_CM4F` and `ARM_CM7` ports.
+#[derive(Debug, Clone)]
+pub struct FreeRtos {
+    current_tcb: u64,
+    ready_lists: RtosSymbol,
+    lists: Vec<(u64, RtosThreadState)>,
+}
+
+impl FreeRtos {
+    /// Use the FreeRTOS kernel symbols, if `symbols` contains them.
+    pub fn from_symbols(symbols: &RtosSymbols) -> Option<Self> {
+        let current_tcb = symbols.require("pxCurrentTCB").ok()?.address;
+        let ready_lists = symbols.require("pxReadyTasksLists").ok()?;
+
+        let lists = [
+            ("xDelayedTaskList1", RtosThreadState::Blocked),
+            ("xDelayedTaskList2", RtosThreadState::Blocked),
+            ("xPendingReadyList", RtosThreadState::Ready),
+            ("xSuspendedTaskList", RtosThreadState::Suspended),
+            ("xTasksWaitingTermination", RtosThreadState::Terminated),
+        ]
+        .into_iter()
+        .filter_map(|(name, state)| Some((symbols.get(name)?.address, state)))
+        .collect();
+
+        Some(Self {
+            current_tcb,
+            ready_lists,
+            lists,
+        })
+    }
+
+    fn read_threads(
+        &self,
+        memory: &mut dyn MemoryInterface,
+        fpu: bool,
+    ) -> Result<Vec<RtosThread>, RtosError> {
+        let current_tcb = memory.read_word_32(self.current_tcb)? as u64;
+
+        // configMAX_PRIORITIES is not available at runtime, but it determines the size of the
+        // array of ready lists.
+        let ready_lists = (0..self.ready_lists.size / LIST_SIZE).map(|priority| {
+            (
+                self.ready_lists.address + priority * LIST_SIZE,
+                RtosThreadState::Ready,
+            )
+        });
+
+        let mut threads: Vec<RtosThread> = Vec::new();
+
+        for (list, state) in ready_lists.chain(self.lists.iter().copied()) {
+            for tcb in list_owners(memory, list)? {
+                if threads.iter().any(|thread| thread.id == tcb) {
+                    continue;
+                }
+
+                let thread = if tcb == current_tcb {
+                    self.read_thread(memory, tcb, RtosThreadState::Running, None)?
+                } else {
+                    self.read_thread(memory, tcb, state, Some(fpu))?
+                };
+
+                threads.push(thread);
+            }
+        }
+
+        Ok(threads)
+    }
+
+    /// Read the task control block at `tcb`.
+    ///
+    /// If `fpu` is `None`, the task is running and its registers are not read from the stack.
+    fn read_thread(
+        &self,
+        memory: &mut dyn MemoryInterface,
+        tcb: u64,
+        state: RtosThreadState,
+        fpu: Option<bool>,
+    ) -> Result<RtosThread, RtosError> {
+        let name = read_string(memory, tcb + TCB_NAME_OFFSET, MAX_TASK_NAME_LEN)?;
+        let priority = memory.read_word_32(tcb + TCB_PRIORITY_OFFSET)?;
+
+        let mut registers = Vec::new();
+
+        if let Some(fpu) = fpu {
+            let top_of_stack = memory.read_word_32(tcb)? as u64;
+
+            read_callee_saved(memory, top_of_stack, &mut registers)?;
+
+            let frame = if fpu {
+                // The ARM_CM4F and ARM_CM7 ports additionally save the EXC_RETURN value,
+                // and s16 to s31 if the task used the FPU.
+                let exc_return = memory.read_word_32(top_of_stack + 32)?;
+                let extended = exc_return & (1 << 4) == 0;
+
+                (top_of_stack + 36 + if extended { 64 } else { 0 }, extended)
+            } else {
+                (top_of_stack + 32, false)
+            };
+
+            read_exception_frame(memory, frame.0, frame.1, &mut registers)?;
+        }
+
+        Ok(RtosThread {
+            id: tcb,
+            name,
+            state,
+            priority: Some(priority as i64),
+            registers,
+        })
+    }
+}
+
+impl RtosAwareness for FreeRtos {
+    fn name(&self) -> &'static str {
+        "FreeRTOS"
+    }
+
+    fn threads(&self, core: &mut Core) -> Result<Vec<RtosThread>, RtosError> {
+        let core_type = core.core_type();
+        if !is_supported_cortex_m(core_type) {
+            return Err(RtosError::UnsupportedCore {
+                rtos: self.name(),
+                core_type,
+            });
+        }
+
+        let fpu = core.fpu_support()?;
+
+        self.read_threads(core, fpu)
+    }
+}
+
+/// Returns the owners of all items in the `List_t` at `list`, which are task control blocks.
+fn list_owners(memory: &mut dyn MemoryInterface, list: u64) -> Result<Vec<u64>, RtosError> {
+    let list_end = list + LIST_END_OFFSET;
+
+    let mut owners = Vec::new();
+    let mut item = memory.read_word_32(list_end + LIST_ITEM_NEXT_OFFSET)? as u64;
+
+    while item != list_end {
+        if item == 0 || owners.len() >= MAX_THREADS {
+            return Err(RtosError::InvalidData(format!(
+                "The task list at {list:#010x} is corrupted"
+            )));
+        }
+
+        owners.push(memory.read_word_32(item + LIST_ITEM_OWNER_OFFSET)? as u64);
+        item = memory.read_word_32(item + LIST_ITEM_NEXT_OFFSET)? as u64;
+    }
+
+    Ok(owners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_memory::TestMemory;
+    use super::super::{cortex_m, RtosSymbol, RtosSymbols, RtosThreadState};
+    use super::{FreeRtos, LIST_END_OFFSET, LIST_SIZE, TCB_NAME_OFFSET, TCB_PRIORITY_OFFSET};
+
+    const CURRENT_TCB: u64 = 0x2000_0000;
+    const READY_LISTS: u64 = 0x2000_0100;
+    const DELAYED_LIST: u64 = 0x2000_0200;
+
+    /// Link `items` into the list at `list`, each item owned by the TCB following it.
+    fn add_list(memory: &mut TestMemory, list: u64, items: &[u64]) {
+        let list_end = list + LIST_END_OFFSET;
+
+        memory.set_word(list, items.len() as u32);
+        let mut previous = list_end;
+        for &item in items {
+            memory.set_word(previous + 4, item as u32);
+            memory.set_word(item + 12, (item + 0x100) as u32);
+            previous = item;
+        }
+        memory.set_word(previous + 4, list_end as u32);
+    }
+
+    fn add_tcb(memory: &mut TestMemory, tcb: u64, name: &str, priority: u32, stack: u64) {
+        memory.set_word(tcb, stack as u32);
+        memory.set_word(tcb + TCB_PRIORITY_OFFSET, priority);
+        memory.set_bytes(tcb + TCB_NAME_OFFSET, name.as_bytes());
+    }
+
+    fn freertos() -> FreeRtos {
+        let mut symbols = RtosSymbols::default();
+        symbols.insert(
+            "pxCurrentTCB",
+            RtosSymbol {
+                address: CURRENT_TCB,
+                size: 4,
+            },
+        );
+        symbols.insert(
+            "pxReadyTasksLists",
+            RtosSymbol {
+                address: READY_LISTS,
+                size: 2 * LIST_SIZE,
+            },
+        );
+        symbols.insert(
+            "xDelayedTaskList1",
+            RtosSymbol {
+                address: DELAYED_LIST,
+                size: LIST_SIZE,
+            },
+        );
+
+        FreeRtos::from_symbols(&symbols).unwrap()
+    }
+
+    #[test]
+    fn missing_symbols_are_not_detected() {
+        assert!(FreeRtos::from_symbols(&RtosSymbols::default()).is_none());
+    }
+
+    #[test]
+    fn read_tasks_from_lists() {
+        let mut memory = TestMemory::default();
+
+        // The idle task at priority 0, the running task at priority 1 and a delayed task.
+        add_list(&mut memory, READY_LISTS, &[0x2000_1000]);
+        add_list(&mut memory, READY_LISTS + LIST_SIZE, &[0x2000_2000]);
+        add_list(&mut memory, DELAYED_LIST, &[0x2000_3000]);
+
+        add_tcb(&mut memory, 0x2000_1100, "IDLE", 0, 0x2000_8000);
+        add_tcb(&mut memory, 0x2000_2100, "main", 1, 0x2000_9000);
+        add_tcb(&mut memory, 0x2000_3100, "blinky", 1, 0x2000_a000);
+        memory.set_word(CURRENT_TCB, 0x2000_2100);
+
+        // The stacked pc of the delayed task.
+        memory.set_word(0x2000_a000 + 32 + 24, 0x0800_1234);
+
+        let threads = freertos().read_threads(&mut memory, false).unwrap();
+
+        let summary: Vec<_> = threads
+            .iter()
+            .map(|thread| (thread.name.as_str(), thread.state, thread.priority))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("IDLE", RtosThreadState::Ready, Some(0)),
+                ("main", RtosThreadState::Running, Some(1)),
+                ("blinky", RtosThreadState::Blocked, Some(1)),
+            ]
+        );
+
+        assert!(threads[1].registers.is_empty());
+        assert_eq!(threads[2].register(cortex_m::PC), Some(0x0800_1234));
+        assert_eq!(
+            threads[2].register(cortex_m::SP),
+            Some(0x2000_a000 + 32 + 32)
+        );
+    }
+
+    #[test]
+    fn corrupted_list_is_an_error() {
+        let mut memory = TestMemory::default();
+
+        // The first item points to address zero instead of back to the list end.
+        memory.set_word(READY_LISTS + LIST_END_OFFSET + 4, 0x2000_1000);
+
+        assert!(freertos().read_threads(&mut memory, false).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+//! RTOS awareness
+//!
+//! Reads the thread structures of a real-time operating system from a halted core, so that every
+//! thread can be inspected with its own registers and stack, instead of only the one which is
+//! currently running.
+//!
+//! Kernel data structures are located with the symbols of the application's ELF file, see
+//! [`RtosSymbols`]. Support for a specific RTOS is implemented with the [`RtosAwareness`] trait,
+//! and [`detect_rtos`] selects a matching implementation based on the available symbols.
+//!
+//! Schedulers which run their tasks on a shared stack, like RTIC and embassy, have no saved
+//! context of a task to unwind. Their tasks are listed with their state, but without registers,
+//! see [`RtosAwareness::shared_stack`]. The layout of their structures is taken from the debug
+//! information of the ELF file.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use probe_rs::{Probe, Permissions};
+//! use probe_rs::rtos::{detect_rtos, RtosSymbols};
+//!
+//! let probe = Probe::list_all()[0].open()?;
+//! let mut session = probe.attach("somechip", Permissions::default())?;
+//! let mut core = session.core(0)?;
+//!
+//! let symbols = RtosSymbols::from_elf(&std::fs::read("firmware.elf")?)?;
+//!
+//! if let Some(rtos) = detect_rtos(&symbols) {
+//!     for thread in rtos.threads(&mut core)? {
+//!         println!("{}: {:?}", thread.name, thread.state);
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+mod debug_types;
+mod embassy;
+mod freertos;
+mod rtic;
+mod zephyr;
+
+pub use embassy::Embassy;
+pub use freertos::FreeRtos;
+pub use rtic::Rtic;
+pub use zephyr::Zephyr;
+
+use debug_types::DebugTypes;
+
+use crate::{Core, CoreType, Error, MemoryInterface, RegisterId};
+use object::{Object, ObjectSymbol};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Upper bound for the number of threads and list entries which are read from the target,
+/// to avoid looping forever on corrupted kernel structures.
+const MAX_THREADS: usize = 1024;
+
+/// An error which occurred while reading RTOS structures.
+#[derive(Debug, thiserror::Error)]
+pub enum RtosError {
+    /// The ELF file could not be parsed.
+    #[error("Failed to parse the ELF file")]
+    Elf(#[from] object::read::Error),
+    /// Reading the target failed.
+    #[error("Failed to read the RTOS structures from the target")]
+    Target(#[from] Error),
+    /// The RTOS needs a symbol which is not present in the ELF file.
+    #[error("The symbol '{0}' required for RTOS awareness is missing")]
+    MissingSymbol(&'static str),
+    /// The core type is not supported by the RTOS awareness.
+    #[error("RTOS awareness for {rtos} is not supported on {core_type:?} cores")]
+    UnsupportedCore {
+        /// The name of the RTOS.
+        rtos: &'static str,
+        /// The type of the core.
+        core_type: CoreType,
+    },
+    /// The kernel structures on the target do not have the expected layout.
+    #[error("Invalid RTOS data: {0}")]
+    InvalidData(String),
+}
+
+/// The location of a symbol in the target memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtosSymbol {
+    /// The address of the symbol.
+    pub address: u64,
+    /// The size of the symbol in bytes.
+    pub size: u64,
+}
+
+/// Symbols of the application which are used to locate the kernel data structures.
+#[derive(Debug, Clone, Default)]
+pub struct RtosSymbols {
+    symbols: HashMap<String, RtosSymbol>,
+    debug_types: Option<Rc<DebugTypes>>,
+}
+
+impl RtosSymbols {
+    /// Collect all data symbols from an ELF file, and the static variables described by its
+    /// debug information.
+    pub fn from_elf(data: &[u8]) -> Result<Self, RtosError> {
+        let file = object::File::parse(data)?;
+
+        let symbols = file
+            .symbols()
+            .filter(|symbol| symbol.is_definition())
+            .filter_map(|symbol| {
+                let name = symbol.name().ok()?;
+
+                Some((
+                    name.to_owned(),
+                    RtosSymbol {
+                        address: symbol.address(),
+                        size: symbol.size(),
+                    },
+                ))
+            })
+            .collect();
+
+        let debug_types = match DebugTypes::from_object(&file) {
+            Ok(types) => Some(Rc::new(types)),
+            Err(e) => {
+                tracing::debug!("Failed to read the debug information: {}", e);
+                None
+            }
+        };
+
+        Ok(Self {
+            symbols,
+            debug_types,
+        })
+    }
+
+    /// Add a symbol, replacing any symbol with the same name.
+    pub fn insert(&mut self, name: impl Into<String>, symbol: RtosSymbol) {
+        self.symbols.insert(name.into(), symbol);
+    }
+
+    /// Look up a symbol by its name.
+    pub fn get(&self, name: &str) -> Option<RtosSymbol> {
+        self.symbols.get(name).copied()
+    }
+
+    fn require(&self, name: &'static str) -> Result<RtosSymbol, RtosError> {
+        self.get(name).ok_or(RtosError::MissingSymbol(name))
+    }
+
+    fn debug_types(&self) -> Option<&DebugTypes> {
+        self.debug_types.as_deref()
+    }
+}
+
+/// The scheduling state of an RTOS thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtosThreadState {
+    /// The thread is currently executing on the core.
+    Running,
+    /// The thread is ready to run.
+    Ready,
+    /// The thread waits for an event or a timeout.
+    Blocked,
+    /// The thread was suspended and is not scheduled.
+    Suspended,
+    /// The thread was terminated, but not yet cleaned up.
+    Terminated,
+}
+
+/// A thread of the RTOS running on the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtosThread {
+    /// A unique ID of the thread, usually the address of its control block.
+    pub id: u64,
+    /// The name of the thread.
+    pub name: String,
+    /// The scheduling state of the thread.
+    pub state: RtosThreadState,
+    /// The priority of the thread, if the RTOS has priorities.
+    pub priority: Option<i64>,
+    /// The registers of the thread, as saved by the last context switch.
+    ///
+    /// This is empty for the running thread, its registers are the ones of the core. It is also
+    /// empty for tasks which share the stack of the core, see [`RtosAwareness::shared_stack`].
+    pub registers: Vec<(RegisterId, u64)>,
+}
+
+impl RtosThread {
+    /// Returns the saved value of register `id`, if the thread is not running.
+    pub fn register(&self, id: RegisterId) -> Option<u64> {
+        self.registers
+            .iter()
+            .find(|(register, _)| *register == id)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Support for reading the threads of a specific RTOS.
+pub trait RtosAwareness: std::fmt::Debug + Send + Sync {
+    /// The name of the RTOS.
+    fn name(&self) -> &'static str;
+
+    /// Read all threads from the halted `core`.
+    fn threads(&self, core: &mut Core) -> Result<Vec<RtosThread>, RtosError>;
+
+    /// Returns `true` if the tasks run on the stack of the core, instead of a stack of their own.
+    ///
+    /// The tasks have no saved registers then, and none of them is reported as running, because
+    /// the code running on the core cannot be attributed to a task.
+    fn shared_stack(&self) -> bool {
+        false
+    }
+}
+
+/// Select the RTOS awareness matching the kernel symbols present in `symbols`.
+///
+/// Returns `None` if no supported RTOS was found.
+pub fn detect_rtos(symbols: &RtosSymbols) -> Option<Arc<dyn RtosAwareness>> {
+    if let Some(rtos) = FreeRtos::from_symbols(symbols) {
+        return Some(Arc::new(rtos));
+    }
+
+    if let Some(rtos) = Zephyr::from_symbols(symbols) {
+        return Some(Arc::new(rtos));
+    }
+
+    if let Some(rtos) = Rtic::from_symbols(symbols) {
+        return Some(Arc::new(rtos));
+    }
+
+    if let Some(rtos) = Embassy::from_symbols(symbols) {
+        return Some(Arc::new(rtos));
+    }
+
+    None
+}
+
+/// Cortex-M register IDs, as used by [`crate::architecture::arm::core::registers::cortex_m`].
+mod cortex_m {
+    use crate::RegisterId;
+
+    pub(super) const R0: RegisterId = RegisterId(0);
+    pub(super) const R4: RegisterId = RegisterId(4);
+    pub(super) const R12: RegisterId = RegisterId(12);
+    pub(super) const SP: RegisterId = RegisterId(13);
+    pub(super) const LR: RegisterId = RegisterId(14);
+    pub(super) const PC: RegisterId = RegisterId(15);
+    pub(super) const XPSR: RegisterId = RegisterId(16);
+}
+
+/// Returns `true` if the RTOS awareness can unwind thread contexts of `core_type`.
+fn is_supported_cortex_m(core_type: CoreType) -> bool {
+    matches!(
+        core_type,
+        CoreType::Armv6m | CoreType::Armv7m | CoreType::Armv7em
+    )
+}
+
+/// Read the callee saved registers `r4` to `r11` stored at `address`.
+fn read_callee_saved(
+    memory: &mut dyn MemoryInterface,
+    address: u64,
+    registers: &mut Vec<(RegisterId, u64)>,
+) -> Result<(), Error> {
+    let mut values = [0u32; 8];
+    memory.read_32(address, &mut values)?;
+
+    registers.extend(
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (RegisterId(cortex_m::R4.0 + i as u16), *value as u64)),
+    );
+
+    Ok(())
+}
+
+/// Read the exception frame stacked by the hardware at `address`.
+///
+/// `extended` is set if the frame includes the floating point registers. The stack pointer of
+/// the thread is the one before the exception was taken.
+fn read_exception_frame(
+    memory: &mut dyn MemoryInterface,
+    address: u64,
+    extended: bool,
+    registers: &mut Vec<(RegisterId, u64)>,
+) -> Result<(), Error> {
+    let mut frame = [0u32; 8];
+    memory.read_32(address, &mut frame)?;
+
+    let [r0, r1, r2, r3, r12, lr, pc, xpsr] = frame.map(u64::from);
+
+    registers.extend([
+        (cortex_m::R0, r0),
+        (RegisterId(1), r1),
+        (RegisterId(2), r2),
+        (RegisterId(3), r3),
+        (cortex_m::R12, r12),
+        (cortex_m::LR, lr),
+        (cortex_m::PC, pc),
+        (cortex_m::XPSR, xpsr),
+    ]);
+
+    let frame_size = if extended { 0x68 } else { 0x20 };
+    // Bit 9 of the stacked xPSR is set if the frame was aligned to 8 bytes.
+    let alignment = if xpsr & (1 << 9) != 0 { 4 } else { 0 };
+
+    registers.push((cortex_m::SP, address + frame_size + alignment));
+
+    Ok(())
+}
+
+/// Read a null terminated string of at most `max_length` bytes.
+fn read_string(
+    memory: &mut dyn MemoryInterface,
+    address: u64,
+    max_length: usize,
+) -> Result<String, Error> {
+    let mut data = vec![0; max_length];
+    memory.read_8(address, &mut data)?;
+
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+
+    Ok(String::from_utf8_lossy(&data[..end]).into_owned())
+}
+
+#[cfg(test)]
+pub(crate) mod test_memory {
+    use crate::{Error, MemoryInterface};
+    use std::collections::BTreeMap;
+
+    /// Sparse target memory for tests, unwritten bytes read as zero.
+    #[derive(Default)]
+    pub(crate) struct TestMemory {
+        bytes: BTreeMap<u64, u8>,
+    }
+
+    impl TestMemory {
+        pub(crate) fn set_word(&mut self, address: u64, value: u32) {
+            self.set_bytes(address, &value.to_le_bytes());
+        }
+
+        pub(crate) fn set_bytes(&mut self, address: u64, data: &[u8]) {
+            for (i, byte) in data.iter().enumerate() {
+                self.bytes.insert(address + i as u64, *byte);
+            }
+        }
+    }
+
+    impl MemoryInterface for TestMemory {
+        fn supports_native_64bit_access(&mut self) -> bool {
+            false
+        }
+
+        fn read_word_64(&mut self, address: u64) -> Result<u64, Error> {
+            let mut data = [0; 8];
+            self.read_8(address, &mut data)?;
+            Ok(u64::from_le_bytes(data))
+        }
+
+        fn read_word_32(&mut self, address: u64) -> Result<u32, Error> {
+            let mut data = [0; 4];
+            self.read_8(address, &mut data)?;
+            Ok(u32::from_le_bytes(data))
+        }
+
+        fn read_word_8(&mut self, address: u64) -> Result<u8, Error> {
+            Ok(self.bytes.get(&address).copied().unwrap_or(0))
+        }
+
+        fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), Error> {
+            for (i, word) in data.iter_mut().enumerate() {
+                *word = self.read_word_64(address + 8 * i as u64)?;
+            }
+            Ok(())
+        }
+
+        fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), Error> {
+            for (i, word) in data.iter_mut().enumerate() {
+                *word = self.read_word_32(address + 4 * i as u64)?;
+            }
+            Ok(())
+        }
+
+        fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+            for (i, byte) in data.iter_mut().enumerate() {
+                *byte = self.read_word_8(address + i as u64)?;
+            }
+            Ok(())
+        }
+
+        fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), Error> {
+            self.set_bytes(address, &data.to_le_bytes());
+            Ok(())
+        }
+
+        fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), Error> {
+            self.set_word(address, data);
+            Ok(())
+        }
+
+        fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), Error> {
+            self.set_bytes(address, &[data]);
+            Ok(())
+        }
+
+        fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), Error> {
+            for (i, word) in data.iter().enumerate() {
+                self.write_word_64(address + 8 * i as u64, *word)?;
+            }
+            Ok(())
+        }
+
+        fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
+            for (i, word) in data.iter().enumerate() {
+                self.set_word(address + 4 * i as u64, *word);
+            }
+            Ok(())
+        }
+
+        fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+            self.set_bytes(address, data);
+            Ok(())
+        }
+
+        fn supports_8bit_transfers(&self) -> Result<bool, Error> {
+            Ok(true)
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+}
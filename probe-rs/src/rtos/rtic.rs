@@ -0,0 +1,137 @@
+use super::{RtosAwareness, RtosError, RtosSymbols, RtosThread, RtosThreadState};
+use crate::{Core, MemoryInterface};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RticTask {
+    name: String,
+    /// The address of the `AsyncTaskExecutor` of the task.
+    address: u64,
+    /// The offset of the flag which is set while the task is spawned.
+    running: u64,
+    /// The offset of the flag which is set when the task was woken up.
+    pending: u64,
+}
+
+/// Task awareness for the software tasks of RTIC 2.
+///
+/// The tasks are found through the `__rtic_internal_<task>_EXEC` statics of their executors, and
+/// their layout is taken from the debug information, so the ELF file has to include it.
+///
+/// The tasks run on the stack of the interrupt dispatching them and have no saved registers.
+/// Only spawned tasks are listed. Their priority is a constant of the dispatcher, which cannot
+/// be read from the target, so they are listed without one. Hardware tasks are interrupt
+/// handlers without state in memory and are not listed.
+#[derive(Debug, Clone)]
+pub struct Rtic {
+    tasks: Vec<RticTask>,
+}
+
+impl Rtic {
+    /// Use the task executors in `symbols`, if there are any.
+    pub fn from_symbols(symbols: &RtosSymbols) -> Option<Self> {
+        let types = symbols.debug_types()?;
+
+        let tasks = types
+            .variables()
+            .iter()
+            .filter_map(|executor| {
+                let name = executor
+                    .name()
+                    .strip_prefix("__rtic_internal_")?
+                    .strip_suffix("_EXEC")?;
+
+                Some(RticTask {
+                    name: name.to_owned(),
+                    address: executor.address,
+                    running: types.member(executor.ty, "running")?.0,
+                    pending: types.member(executor.ty, "pending")?.0,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        (!tasks.is_empty()).then_some(Self { tasks })
+    }
+
+    fn read_threads(&self, memory: &mut dyn MemoryInterface) -> Result<Vec<RtosThread>, RtosError> {
+        let mut threads = Vec::new();
+
+        for task in &self.tasks {
+            if memory.read_word_8(task.address + task.running)? == 0 {
+                continue;
+            }
+
+            let state = if memory.read_word_8(task.address + task.pending)? != 0 {
+                RtosThreadState::Ready
+            } else {
+                RtosThreadState::Blocked
+            };
+
+            threads.push(RtosThread {
+                id: task.address,
+                name: task.name.clone(),
+                state,
+                priority: None,
+                registers: Vec::new(),
+            });
+        }
+
+        Ok(threads)
+    }
+}
+
+impl RtosAwareness for Rtic {
+    fn name(&self) -> &'static str {
+        "RTIC"
+    }
+
+    fn threads(&self, core: &mut Core) -> Result<Vec<RtosThread>, RtosError> {
+        self.read_threads(core)
+    }
+
+    fn shared_stack(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_memory::TestMemory;
+    use super::super::RtosThreadState;
+    use super::{Rtic, RticTask};
+
+    #[test]
+    fn only_spawned_tasks_are_listed() {
+        let task = |name: &str, address| RticTask {
+            name: name.to_owned(),
+            address,
+            running: 0x10,
+            pending: 0x11,
+        };
+
+        let rtic = Rtic {
+            tasks: vec![
+                task("blink", 0x2000_0000),
+                task("idle_work", 0x2000_0100),
+                task("uart", 0x2000_0200),
+            ],
+        };
+
+        let mut memory = TestMemory::default();
+        memory.set_bytes(0x2000_0010, &[1, 0]);
+        memory.set_bytes(0x2000_0210, &[1, 1]);
+
+        let threads = rtic.read_threads(&mut memory).unwrap();
+
+        let summary: Vec<_> = threads
+            .iter()
+            .map(|thread| (thread.name.as_str(), thread.state, thread.priority))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("blink", RtosThreadState::Blocked, None),
+                ("uart", RtosThreadState::Ready, None),
+            ]
+        );
+    }
+}
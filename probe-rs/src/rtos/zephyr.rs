@@ -0,0 +1,266 @@
+use super::{
+    is_supported_cortex_m, read_callee_saved, read_exception_frame, read_string, RtosAwareness,
+    RtosError, RtosSymbols, RtosThread, RtosThreadState, MAX_THREADS,
+};
+use crate::{Core, MemoryInterface};
+
+/// Indices into `_kernel_thread_info_offsets`, see `subsys/debug/thread_info.c` in Zephyr.
+const OFFSET_K_CURR_THREAD: usize = 1;
+const OFFSET_K_THREADS: usize = 2;
+const OFFSET_T_NEXT_THREAD: usize = 4;
+const OFFSET_T_STATE: usize = 5;
+const OFFSET_T_PRIO: usize = 7;
+const OFFSET_T_STACK_PTR: usize = 8;
+const OFFSET_T_NAME: usize = 9;
+const OFFSET_T_ARM_EXC_RETURN: usize = 13;
+
+/// Offsets which are not available in a build are set to `THREAD_INFO_UNIMPLEMENTED`.
+const THREAD_INFO_UNIMPLEMENTED: u32 = u32::MAX;
+
+/// Thread state bits of `k_thread.base.thread_state`.
+const THREAD_PENDING: u8 = 1 << 1;
+const THREAD_PRESTART: u8 = 1 << 2;
+const THREAD_DEAD: u8 = 1 << 3;
+const THREAD_SUSPENDED: u8 = 1 << 4;
+
+/// Names are read up to this length, the actual `CONFIG_THREAD_MAX_NAME_LEN` is not known.
+const MAX_THREAD_NAME_LEN: usize = 32;
+
+/// RTOS awareness for Zephyr on Cortex-M cores.
+///
+/// The application has to be built with `CONFIG_DEBUG_THREAD_INFO`, which exports the layout
+/// of the kernel structures, and `CONFIG_THREAD_MONITOR`, which links all threads into a list.
+#[derive(Debug, Clone)]
+pub struct Zephyr {
+    kernel: u64,
+    offsets: u64,
+    num_offsets: u64,
+}
+
+impl Zephyr {
+    /// Use the Zephyr kernel symbols, if `symbols` contains them.
+    pub fn from_symbols(symbols: &RtosSymbols) -> Option<Self> {
+        Some(Self {
+            kernel: symbols.require("_kernel").ok()?.address,
+            offsets: symbols.require("_kernel_thread_info_offsets").ok()?.address,
+            num_offsets: symbols
+                .require("_kernel_thread_info_num_offsets")
+                .ok()?
+                .address,
+        })
+    }
+
+    fn read_threads(&self, memory: &mut dyn MemoryInterface) -> Result<Vec<RtosThread>, RtosError> {
+        let num_offsets = memory.read_word_32(self.num_offsets)? as usize;
+        let mut offsets = vec![0u32; num_offsets.min(64)];
+        memory.read_32(self.offsets, &mut offsets)?;
+
+        let offset = |index: usize| {
+            offsets
+                .get(index)
+                .copied()
+                .filter(|&offset| offset != THREAD_INFO_UNIMPLEMENTED)
+                .map(u64::from)
+        };
+
+        let required = |index: usize, name: &str| {
+            offset(index).ok_or_else(|| {
+                RtosError::InvalidData(format!(
+                    "The offset of {name} is not available, is CONFIG_THREAD_MONITOR enabled?"
+                ))
+            })
+        };
+
+        let current_thread_offset = required(OFFSET_K_CURR_THREAD, "_kernel.cpus[0].current")?;
+        let threads_offset = required(OFFSET_K_THREADS, "_kernel.threads")?;
+        let next_thread_offset = required(OFFSET_T_NEXT_THREAD, "k_thread.next_thread")?;
+        let state_offset = required(OFFSET_T_STATE, "k_thread.base.thread_state")?;
+        let stack_ptr_offset = required(OFFSET_T_STACK_PTR, "k_thread.callee_saved.psp")?;
+        let priority_offset = offset(OFFSET_T_PRIO);
+        let name_offset = offset(OFFSET_T_NAME);
+        let exc_return_offset = offset(OFFSET_T_ARM_EXC_RETURN);
+
+        let current_thread = memory.read_word_32(self.kernel + current_thread_offset)? as u64;
+
+        let mut threads = Vec::new();
+        let mut thread = memory.read_word_32(self.kernel + threads_offset)? as u64;
+
+        while thread != 0 {
+            if threads.len() >= MAX_THREADS {
+                return Err(RtosError::InvalidData(
+                    "The thread list is corrupted".to_owned(),
+                ));
+            }
+
+            let name = match name_offset {
+                Some(name_offset) => {
+                    read_string(memory, thread + name_offset, MAX_THREAD_NAME_LEN)?
+                }
+                None => String::new(),
+            };
+            let name = if name.is_empty() {
+                format!("Thread {thread:#010x}")
+            } else {
+                name
+            };
+
+            let priority = match priority_offset {
+                Some(priority_offset) => {
+                    Some(memory.read_word_8(thread + priority_offset)? as i8 as i64)
+                }
+                None => None,
+            };
+
+            let state = if thread == current_thread {
+                RtosThreadState::Running
+            } else {
+                thread_state(memory.read_word_8(thread + state_offset)?)
+            };
+
+            let mut registers = Vec::new();
+
+            if state != RtosThreadState::Running {
+                // `callee_saved` holds r4 to r11, directly followed by the stack pointer.
+                read_callee_saved(memory, thread + stack_ptr_offset - 32, &mut registers)?;
+
+                let psp = memory.read_word_32(thread + stack_ptr_offset)? as u64;
+                let extended = match exc_return_offset {
+                    Some(exc_return_offset) => {
+                        memory.read_word_8(thread + exc_return_offset)? & (1 << 4) == 0
+                    }
+                    None => false,
+                };
+
+                read_exception_frame(memory, psp, extended, &mut registers)?;
+            }
+
+            threads.push(RtosThread {
+                id: thread,
+                name,
+                state,
+                priority,
+                registers,
+            });
+
+            thread = memory.read_word_32(thread + next_thread_offset)? as u64;
+        }
+
+        Ok(threads)
+    }
+}
+
+impl RtosAwareness for Zephyr {
+    fn name(&self) -> &'static str {
+        "Zephyr"
+    }
+
+    fn threads(&self, core: &mut Core) -> Result<Vec<RtosThread>, RtosError> {
+        let core_type = core.core_type();
+        if !is_supported_cortex_m(core_type) {
+            return Err(RtosError::UnsupportedCore {
+                rtos: self.name(),
+                core_type,
+            });
+        }
+
+        self.read_threads(core)
+    }
+}
+
+fn thread_state(state: u8) -> RtosThreadState {
+    if state & THREAD_DEAD != 0 {
+        RtosThreadState::Terminated
+    } else if state & (THREAD_SUSPENDED | THREAD_PRESTART) != 0 {
+        RtosThreadState::Suspended
+    } else if state & THREAD_PENDING != 0 {
+        RtosThreadState::Blocked
+    } else {
+        RtosThreadState::Ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_memory::TestMemory;
+    use super::super::{cortex_m, RtosSymbol, RtosSymbols, RtosThreadState};
+    use super::{Zephyr, THREAD_INFO_UNIMPLEMENTED, THREAD_PENDING};
+
+    const KERNEL: u64 = 0x2000_0000;
+    const OFFSETS: u64 = 0x2000_0100;
+    const NUM_OFFSETS: u64 = 0x2000_0200;
+
+    fn zephyr() -> Zephyr {
+        let mut symbols = RtosSymbols::default();
+        for (name, address) in [
+            ("_kernel", KERNEL),
+            ("_kernel_thread_info_offsets", OFFSETS),
+            ("_kernel_thread_info_num_offsets", NUM_OFFSETS),
+        ] {
+            symbols.insert(name, RtosSymbol { address, size: 4 });
+        }
+
+        Zephyr::from_symbols(&symbols).unwrap()
+    }
+
+    #[test]
+    fn read_thread_list() {
+        let mut memory = TestMemory::default();
+
+        // version, current, threads, entry, next_thread, state, user_options, prio, stack_ptr, name
+        let offsets = [0, 0x10, 0x20, 0x0, 0x60, 0x0d, 0x0c, 0x0e, 0x58, 0x70];
+        memory.set_word(NUM_OFFSETS, offsets.len() as u32);
+        for (i, offset) in offsets.iter().enumerate() {
+            memory.set_word(OFFSETS + 4 * i as u64, *offset);
+        }
+
+        let main = 0x2000_1000;
+        let worker = 0x2000_2000;
+
+        memory.set_word(KERNEL + 0x10, main as u32);
+        memory.set_word(KERNEL + 0x20, main as u32);
+
+        memory.set_word(main + 0x60, worker as u32);
+        memory.set_bytes(main + 0x70, b"main\0");
+        memory.set_bytes(main + 0x0e, &[0]);
+
+        memory.set_bytes(worker + 0x70, b"worker\0");
+        memory.set_bytes(worker + 0x0d, &[THREAD_PENDING]);
+        memory.set_bytes(worker + 0x0e, &[(-2i8) as u8]);
+        // r11 and psp, followed by the pc in the exception frame.
+        memory.set_word(worker + 0x58 - 4, 0x1111_1111);
+        memory.set_word(worker + 0x58, 0x2000_8000);
+        memory.set_word(0x2000_8000 + 24, 0x0800_4321);
+
+        let threads = zephyr().read_threads(&mut memory).unwrap();
+
+        let summary: Vec<_> = threads
+            .iter()
+            .map(|thread| (thread.name.as_str(), thread.state, thread.priority))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("main", RtosThreadState::Running, Some(0)),
+                ("worker", RtosThreadState::Blocked, Some(-2)),
+            ]
+        );
+
+        assert!(threads[0].registers.is_empty());
+        assert_eq!(
+            threads[1].register(crate::RegisterId(11)),
+            Some(0x1111_1111)
+        );
+        assert_eq!(threads[1].register(cortex_m::PC), Some(0x0800_4321));
+    }
+
+    #[test]
+    fn missing_thread_monitor_is_an_error() {
+        let mut memory = TestMemory::default();
+
+        memory.set_word(NUM_OFFSETS, 3);
+        memory.set_word(OFFSETS + 4, 0x10);
+        memory.set_word(OFFSETS + 8, THREAD_INFO_UNIMPLEMENTED);
+
+        assert!(zephyr().read_threads(&mut memory).is_err());
+    }
+}
@@ -40,6 +40,15 @@
 //!
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
+//!
+//! [`UpChannel::reader`] and [`DownChannel::writer`] wrap channels in [`std::io::Read`] and
+//! [`std::io::Write`] adapters, which can also wait for the target, see [`IoMode`].
+//!
+//! Targets which cannot use RTT can provide a smaller memory mailbox instead, see
+//! [`Rtt::attach_mailbox`].
+//!
+//! Log messages of the firmware can be forwarded to the [`tracing`] subscriber of the host as
+//! [`TargetLogRecord`]s.
 
 mod channel;
 pub use channel::*;
@@ -47,6 +56,15 @@ pub use channel::*;
 pub mod channels;
 pub use channels::Channels;
 
+mod io;
+pub use io::{ChannelReader, ChannelWriter, IoMode};
+
+mod mailbox;
+pub use mailbox::MAILBOX_SYMBOL;
+
+mod target_log;
+pub use target_log::{TargetLogRecord, TextLogDecoder, TARGET_LOG_TARGET};
+
 use crate::{config::MemoryRegion, Core, MemoryInterface};
 use scroll::{Pread, LE};
 use std::borrow::Cow;
@@ -289,7 +307,26 @@ impl Rtt {
         Ok(instances.remove(0))
     }
 
-    /// Returns the memory address of the control block in target memory.
+    /// Attaches to the memory mailbox at `ptr`, instead of an RTT control block.
+    ///
+    /// The channels of the mailbox are used like RTT channels, its up and down channel are
+    /// channel 0. The address of the mailbox is the value of the [`MAILBOX_SYMBOL`] symbol of
+    /// the firmware.
+    pub fn attach_mailbox(
+        core: &mut Core,
+        memory_map: &[MemoryRegion],
+        ptr: u32,
+    ) -> Result<Rtt, Error> {
+        let mailbox = mailbox::Mailbox::read(core, memory_map, ptr)?;
+
+        Ok(Rtt {
+            ptr,
+            up_channels: Channels(mailbox.up_channel.into_iter().map(|c| (0, c)).collect()),
+            down_channels: Channels(mailbox.down_channel.into_iter().map(|c| (0, c)).collect()),
+        })
+    }
+
+    /// Returns the memory address of the control block, or the mailbox, in target memory.
     pub fn ptr(&self) -> u32 {
         self.ptr
     }
@@ -336,6 +373,10 @@ pub enum Error {
     )]
     ControlBlockNotFound,
 
+    /// No memory mailbox was found at the address.
+    #[error("No memory mailbox was found at {0:#010x}. Make sure the mailbox is initialized by the target.")]
+    MailboxNotFound(u32),
+
     /// Multiple control blocks found in target memory. The data contains the control block addresses (up to 5).
     #[error("Multiple control blocks found in target memory.")]
     MultipleControlBlocksFound(Vec<u32>),
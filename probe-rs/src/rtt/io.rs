@@ -0,0 +1,163 @@
+//! [`std::io`] adapters for RTT channels.
+//!
+//! [`UpChannel::read`] and [`DownChannel::write`] never wait for the target. The adapters in
+//! this module implement [`Read`] and [`Write`] on top of them, and can wait for data or buffer
+//! space, so RTT channels can be used like a console with `read_line` or `write_all`.
+
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::rtt::{DownChannel, Error, UpChannel};
+use crate::Core;
+
+/// How often the channel pointers are read while waiting for the target.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How the host waits for a channel, see [`ChannelReader`] and [`ChannelWriter`].
+///
+/// This is independent of the [`ChannelMode`](crate::rtt::ChannelMode), which decides what the
+/// target does with a full up channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoMode {
+    /// Return immediately. Reads from an empty channel and writes to a full channel fail with
+    /// [`io::ErrorKind::WouldBlock`].
+    NonBlocking,
+    /// Wait until data or buffer space is available. After the timeout, if any, the access fails
+    /// with [`io::ErrorKind::TimedOut`].
+    Blocking(Option<Duration>),
+}
+
+/// Reads from an [`UpChannel`] with [`Read`].
+pub struct ChannelReader<'c, 'probe> {
+    channel: &'c UpChannel,
+    core: &'c mut Core<'probe>,
+    mode: IoMode,
+}
+
+impl<'c, 'probe> ChannelReader<'c, 'probe> {
+    /// Read from `channel` using `core`, waiting for data as specified by `mode`.
+    pub fn new(channel: &'c UpChannel, core: &'c mut Core<'probe>, mode: IoMode) -> Self {
+        Self {
+            channel,
+            core,
+            mode,
+        }
+    }
+}
+
+impl Read for ChannelReader<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        poll(self.mode, || self.channel.read(self.core, buf))
+    }
+}
+
+/// Writes to a [`DownChannel`] with [`Write`].
+pub struct ChannelWriter<'c, 'probe> {
+    channel: &'c DownChannel,
+    core: &'c mut Core<'probe>,
+    mode: IoMode,
+}
+
+impl<'c, 'probe> ChannelWriter<'c, 'probe> {
+    /// Write to `channel` using `core`, waiting for buffer space as specified by `mode`.
+    pub fn new(channel: &'c DownChannel, core: &'c mut Core<'probe>, mode: IoMode) -> Self {
+        Self {
+            channel,
+            core,
+            mode,
+        }
+    }
+}
+
+impl Write for ChannelWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        poll(self.mode, || self.channel.write(self.core, buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Every write updates the write pointer of the channel, there is nothing to flush.
+        Ok(())
+    }
+}
+
+impl UpChannel {
+    /// Returns a [`Read`] adapter for the channel, see [`IoMode`].
+    pub fn reader<'c, 'probe>(
+        &'c self,
+        core: &'c mut Core<'probe>,
+        mode: IoMode,
+    ) -> ChannelReader<'c, 'probe> {
+        ChannelReader::new(self, core, mode)
+    }
+}
+
+impl DownChannel {
+    /// Returns a [`Write`] adapter for the channel, see [`IoMode`].
+    pub fn writer<'c, 'probe>(
+        &'c self,
+        core: &'c mut Core<'probe>,
+        mode: IoMode,
+    ) -> ChannelWriter<'c, 'probe> {
+        ChannelWriter::new(self, core, mode)
+    }
+}
+
+/// Call `access` until it transfers at least one byte, as allowed by `mode`.
+fn poll(mode: IoMode, mut access: impl FnMut() -> Result<usize, Error>) -> io::Result<usize> {
+    let started = Instant::now();
+
+    loop {
+        let count = access().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        if count > 0 {
+            return Ok(count);
+        }
+
+        match mode {
+            IoMode::NonBlocking => return Err(io::ErrorKind::WouldBlock.into()),
+            IoMode::Blocking(Some(timeout)) if started.elapsed() >= timeout => {
+                return Err(io::ErrorKind::TimedOut.into())
+            }
+            IoMode::Blocking(_) => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{poll, IoMode};
+    use crate::rtt::Error;
+    use std::io;
+    use std::time::Duration;
+
+    #[test]
+    fn poll_waits_as_specified_by_the_mode() {
+        let error = poll(IoMode::NonBlocking, || Ok(0)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+
+        let mut calls = 0;
+        let count = poll(IoMode::Blocking(None), || {
+            calls += 1;
+            Ok(if calls == 3 { 5 } else { 0 })
+        })
+        .unwrap();
+        assert_eq!((count, calls), (5, 3));
+
+        let error = poll(IoMode::Blocking(Some(Duration::from_millis(5))), || Ok(0)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+
+        let error = poll(IoMode::Blocking(None), || {
+            Err(Error::ControlBlockCorrupted("test".to_string()))
+        })
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+    }
+}
@@ -0,0 +1,74 @@
+//! Memory mailbox consoles.
+//!
+//! A memory mailbox is a smaller alternative to the SEGGER RTT control block, for targets which
+//! do not have the RAM for it, or whose tooling cannot use its layout. It has at most one up and
+//! one down channel, and is found by the address of its symbol instead of scanning the RAM for an
+//! ID. The channels use the same ring buffers as RTT, so they are read and written with
+//! [`UpChannel`] and [`DownChannel`].
+
+use super::{Channel, DownChannel, Error, UpChannel};
+use crate::{config::MemoryRegion, Core, MemoryInterface};
+
+/// The name of the symbol of the mailbox in the firmware.
+pub const MAILBOX_SYMBOL: &str = "_PROBE_RS_MAILBOX";
+
+// The mailbox must follow this data layout in target memory:
+//
+// struct Mailbox {
+//     char id[8]; // "PRSMBOX\0", used to validate the mailbox.
+//     RttChannel up; // Target to host channel, the buffer is NULL if not used.
+//     RttChannel down; // Host to target channel, the buffer is NULL if not used.
+// }
+
+/// A memory mailbox, see the [module documentation](self) for its layout.
+pub(crate) struct Mailbox {
+    pub(crate) up_channel: Option<UpChannel>,
+    pub(crate) down_channel: Option<DownChannel>,
+}
+
+impl Mailbox {
+    const ID: [u8; 8] = *b"PRSMBOX\0";
+
+    // Size of the mailbox in target memory in bytes
+    const SIZE: usize = Self::O_DOWN + Channel::SIZE;
+
+    // Offsets of fields in target memory in bytes
+    const O_UP: usize = 8;
+    const O_DOWN: usize = Self::O_UP + Channel::SIZE;
+
+    /// Read the mailbox at `ptr`.
+    pub(crate) fn read(
+        core: &mut Core,
+        memory_map: &[MemoryRegion],
+        ptr: u32,
+    ) -> Result<Mailbox, Error> {
+        let mut mem = [0u8; Self::SIZE];
+        core.read(ptr.into(), &mut mem)?;
+
+        if mem[..Self::ID.len()] != Self::ID {
+            return Err(Error::MailboxNotFound(ptr));
+        }
+
+        let up_channel = Channel::from(
+            core,
+            0,
+            memory_map,
+            ptr + Self::O_UP as u32,
+            &mem[Self::O_UP..],
+        )?
+        .map(UpChannel);
+        let down_channel = Channel::from(
+            core,
+            0,
+            memory_map,
+            ptr + Self::O_DOWN as u32,
+            &mem[Self::O_DOWN..],
+        )?
+        .map(DownChannel);
+
+        Ok(Mailbox {
+            up_channel,
+            down_channel,
+        })
+    }
+}
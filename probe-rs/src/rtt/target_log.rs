@@ -0,0 +1,202 @@
+use tracing::Level;
+
+/// The target of the [`tracing`] events emitted by [`TargetLogRecord::emit`], which can be used
+/// to filter firmware logs, for example with `RUST_LOG=firmware=debug`.
+pub const TARGET_LOG_TARGET: &str = "firmware";
+
+/// A log message of the firmware, decoded from an RTT channel.
+///
+/// Records are created from text channels by [`TextLogDecoder`], or by the application from
+/// the frames of a `defmt` decoder, and can be forwarded to the [`tracing`] subscriber of the
+/// host with [`TargetLogRecord::emit`], so firmware and host logs end up in the same place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetLogRecord {
+    /// The name of the RTT channel the message was received on.
+    pub channel: String,
+    /// The level of the message, if the firmware logged one.
+    pub level: Option<Level>,
+    /// The message, without the level.
+    pub message: String,
+    /// The timestamp of the firmware, if it logged one.
+    pub timestamp: Option<String>,
+    /// The module which logged the message.
+    pub module: Option<String>,
+    /// The source file which logged the message.
+    pub file: Option<String>,
+    /// The line in the source file.
+    pub line: Option<u64>,
+}
+
+impl TargetLogRecord {
+    /// Create a record of a `message` without level or location.
+    pub fn new(channel: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            channel: channel.into(),
+            level: None,
+            message: message.into(),
+            timestamp: None,
+            module: None,
+            file: None,
+            line: None,
+        }
+    }
+
+    /// Create a record of a line of text, whose level is taken from a prefix such as `[WARN]`,
+    /// `<warn>`, `WARN:` or `WARN `.
+    ///
+    /// Lines without a known prefix are kept unchanged, without level.
+    pub fn from_text_line(channel: impl Into<String>, line: &str) -> Self {
+        let (level, message) = match split_level(line) {
+            Some((level, message)) => (Some(level), message),
+            None => (None, line),
+        };
+
+        Self {
+            level,
+            ..Self::new(channel, message)
+        }
+    }
+
+    /// Emit the record as a [`tracing`] event with the target [`TARGET_LOG_TARGET`].
+    ///
+    /// Records without level are emitted at [`Level::INFO`]. The channel, timestamp and
+    /// location are recorded as the fields `channel`, `timestamp`, `module`, `file` and `line`.
+    pub fn emit(&self) {
+        macro_rules! emit {
+            ($level:expr) => {
+                tracing::event!(
+                    target: TARGET_LOG_TARGET,
+                    $level,
+                    channel = %self.channel,
+                    timestamp = self.timestamp.as_deref(),
+                    module = self.module.as_deref(),
+                    file = self.file.as_deref(),
+                    line = self.line,
+                    "{}",
+                    self.message
+                )
+            };
+        }
+
+        match self.level.unwrap_or(Level::INFO) {
+            Level::TRACE => emit!(Level::TRACE),
+            Level::DEBUG => emit!(Level::DEBUG),
+            Level::INFO => emit!(Level::INFO),
+            Level::WARN => emit!(Level::WARN),
+            Level::ERROR => emit!(Level::ERROR),
+        }
+    }
+}
+
+/// The level of a level prefix of `line`, and the rest of the line.
+fn split_level(line: &str) -> Option<(Level, &str)> {
+    let bracketed = [('[', ']'), ('<', '>')]
+        .iter()
+        .find_map(|&(open, close)| line.strip_prefix(open)?.split_once(close));
+
+    let (name, rest) = match bracketed {
+        Some((name, rest)) => (name.trim(), rest),
+        None => {
+            // Without brackets, only upper case names are levels, to keep ordinary words.
+            let end = line.find([':', ' '])?;
+            let name = &line[..end];
+            if name.chars().any(|c| !c.is_ascii_uppercase()) {
+                return None;
+            }
+            (name, &line[end + 1..])
+        }
+    };
+
+    let level = match name.to_ascii_uppercase().as_str() {
+        "TRACE" => Level::TRACE,
+        "DEBUG" => Level::DEBUG,
+        "INFO" => Level::INFO,
+        "WARN" | "WARNING" => Level::WARN,
+        "ERROR" | "ERR" => Level::ERROR,
+        _ => return None,
+    };
+
+    Some((level, rest.trim_start()))
+}
+
+/// Splits the data of a text RTT channel into [`TargetLogRecord`]s, one per line.
+///
+/// Data is buffered until the line is complete, as a line can be split over several reads of
+/// the channel.
+#[derive(Debug)]
+pub struct TextLogDecoder {
+    channel: String,
+    pending: Vec<u8>,
+}
+
+impl TextLogDecoder {
+    /// Create a decoder of the data of the RTT channel named `channel`.
+    pub fn new(channel: impl Into<String>) -> Self {
+        Self {
+            channel: channel.into(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Add data read from the channel, and return the records of the lines completed by it.
+    pub fn received(&mut self, data: &[u8]) -> Vec<TargetLogRecord> {
+        self.pending.extend_from_slice(data);
+
+        let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') else {
+            return Vec::new();
+        };
+        let lines = self.pending.drain(..=end).collect::<Vec<_>>();
+
+        String::from_utf8_lossy(&lines)
+            .lines()
+            .map(|line| TargetLogRecord::from_text_line(self.channel.clone(), line))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TargetLogRecord, TextLogDecoder};
+    use tracing::Level;
+
+    #[test]
+    fn level_prefixes() {
+        for (line, level, message) in [
+            ("[INFO] started", Some(Level::INFO), "started"),
+            ("[ warn ] low voltage", Some(Level::WARN), "low voltage"),
+            ("<error> fault", Some(Level::ERROR), "fault"),
+            ("DEBUG: x = 1", Some(Level::DEBUG), "x = 1"),
+            ("TRACE enter", Some(Level::TRACE), "enter"),
+            ("Info about nothing", None, "Info about nothing"),
+            ("[12:00] tick", None, "[12:00] tick"),
+            ("plain", None, "plain"),
+        ] {
+            let record = TargetLogRecord::from_text_line("Terminal", line);
+            assert_eq!((record.level, record.message.as_str()), (level, message));
+        }
+    }
+
+    #[test]
+    fn lines_split_over_reads() {
+        let mut decoder = TextLogDecoder::new("Terminal");
+
+        assert_eq!(decoder.received(b"[INFO] fir"), []);
+
+        let records = decoder.received(b"st\r\nsecond\nthi");
+        assert_eq!(
+            records,
+            [
+                TargetLogRecord {
+                    level: Some(Level::INFO),
+                    ..TargetLogRecord::new("Terminal", "first")
+                },
+                TargetLogRecord::new("Terminal", "second"),
+            ]
+        );
+
+        assert_eq!(
+            decoder.received(b"rd\n"),
+            [TargetLogRecord::new("Terminal", "third")]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Running binaries on the target like `cargo run`
+//!
+//! [`run`] does everything a `cargo run` runner for an embedded target has to do: it flashes an
+//! ELF file, resets the core and runs it, passes the console output over semihosting and the
+//! output of an RTT up channel to the caller while it arrives, and returns when the firmware
+//! exits, with the exit code to pass on. The exit is detected with the same
+//! [`ExitCondition`]s as in the [test runner](crate::test_runner), by default when the firmware
+//! calls the semihosting `SYS_EXIT` operation.
+//!
+//! Firmware which does not exit runs until [`RunOptions::timeout`] has passed, or until the
+//! [`CancellationToken`](crate::cancellation::CancellationToken) [`run`] is called in is
+//! cancelled, for example by a Ctrl-C handler.
+//!
+//! # Example
+//!
+//! A runner, which cargo calls with the path of the ELF file:
+//!
+//! ```no_run
+//! use probe_rs::cancellation::CancellationToken;
+//! use probe_rs::runner::{run, RunOptions, RunOutput};
+//! use probe_rs::{Permissions, Session};
+//! use std::io::Write;
+//!
+//! let elf = std::env::args().nth(1).expect("no ELF file given");
+//! let mut session = Session::auto_attach("nrf52840_xxAA", Permissions::default())?;
+//!
+//! let token = CancellationToken::new();
+//! let outcome = token.run(|| {
+//!     run(&mut session, elf, RunOptions::default(), |output| {
+//!         let (RunOutput::Semihosting(data) | RunOutput::Rtt(data)) = output;
+//!         std::io::stdout().write_all(data).ok();
+//!     })
+//! })?;
+//!
+//! std::process::exit(outcome.exit_code());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::flashing::{download_file_with_options, DownloadOptions, Format};
+use crate::test_runner::{TestRun, TestSymbols};
+use crate::Session;
+
+use std::path::Path;
+use std::time::Duration;
+
+pub use crate::test_runner::{ExitCondition, TestExit, TestRunnerError};
+
+/// Output of the firmware, passed to the caller of [`run`] while it arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutput<'a> {
+    /// Console output over semihosting.
+    Semihosting(&'a [u8]),
+    /// Output of the RTT up channel [`RunOptions::rtt_channel`].
+    Rtt(&'a [u8]),
+}
+
+/// Options for [`run`].
+///
+/// By default, the binary on core 0 runs until it exits through semihosting, and the output of
+/// RTT up channel 0 is passed on.
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct RunOptions {
+    /// The core which runs the binary.
+    pub core_index: usize,
+    /// How long the binary may run, `None` to run it until it exits.
+    pub timeout: Option<Duration>,
+    /// The conditions which end the run, the first one which occurs is reported.
+    pub exit_conditions: Vec<ExitCondition>,
+    /// The RTT up channel whose output is passed on and searched for markers.
+    pub rtt_channel: usize,
+    /// The options for flashing the binary.
+    pub download: DownloadOptions,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            core_index: 0,
+            timeout: None,
+            exit_conditions: vec![ExitCondition::Semihosting],
+            rtt_channel: 0,
+            download: DownloadOptions::default(),
+        }
+    }
+}
+
+/// The result of [`run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutcome {
+    /// How the run ended.
+    pub exit: TestExit,
+    /// How long the binary ran.
+    pub duration: Duration,
+}
+
+impl RunOutcome {
+    /// The exit code for the runner process.
+    ///
+    /// This is the exit code reported by the firmware, or 1 if it did not report one, because
+    /// the run timed out or the core halted for another reason.
+    pub fn exit_code(&self) -> i32 {
+        self.exit.exit_code().unwrap_or(1)
+    }
+}
+
+/// Flash the ELF file at `path`, reset the core and run it until one of the exit conditions of
+/// `options` occurs.
+///
+/// `output` is called with the output of the firmware while it runs.
+pub fn run(
+    session: &mut Session,
+    path: impl AsRef<Path>,
+    options: RunOptions,
+    mut output: impl FnMut(RunOutput<'_>),
+) -> Result<RunOutcome, TestRunnerError> {
+    let path = path.as_ref();
+    let elf = std::fs::read(path)?;
+    let symbols = TestSymbols::from_elf(&elf, &options.exit_conditions)?;
+
+    download_file_with_options(session, path, Format::Elf, options.download.clone())?;
+
+    let memory_map = session.target().memory_map.clone();
+    let mut core = session.core(options.core_index)?;
+
+    core.reset_and_halt(Duration::from_millis(500))?;
+
+    for (address, _) in &symbols.breakpoints {
+        core.set_hw_breakpoint(*address)?;
+    }
+
+    let result = TestRun::new(&options, &symbols, &memory_map, &mut output).run(&mut core);
+
+    for (address, _) in &symbols.breakpoints {
+        if let Err(e) = core.clear_hw_breakpoint(*address) {
+            tracing::warn!("Failed to clear the breakpoint at {:#010x}: {}", address, e);
+        }
+    }
+
+    let (exit, duration) = result?;
+
+    Ok(RunOutcome { exit, duration })
+}
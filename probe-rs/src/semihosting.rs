@@ -0,0 +1,237 @@
+//! Decoding of ARM semihosting requests
+//!
+//! Semihosting allows a program running on the target to use the I/O facilities of the host,
+//! for example to print to a console or to access files. The target signals a request by
+//! executing a `BKPT 0xAB` instruction, with the operation number in `r0` and a pointer to
+//! the parameter block in `r1`.
+//!
+//! The host decodes the request with [`decode_semihosting_syscall`], handles it, and resumes
+//! the target with [`complete_semihosting_syscall`].
+
+use crate::{Core, CoreType, Error, MemoryInterface};
+
+const SYS_OPEN: u32 = 0x01;
+const SYS_CLOSE: u32 = 0x02;
+const SYS_WRITEC: u32 = 0x03;
+const SYS_WRITE0: u32 = 0x04;
+const SYS_WRITE: u32 = 0x05;
+const SYS_READ: u32 = 0x06;
+const SYS_EXIT: u32 = 0x18;
+
+/// The encoding of the `BKPT 0xAB` Thumb instruction.
+const BKPT_SEMIHOSTING: u16 = 0xBEAB;
+
+/// The reason code reported by `SYS_EXIT` when the application exited normally.
+pub const ADP_STOPPED_APPLICATION_EXIT: u32 = 0x20026;
+
+/// Strings read from the target are limited to this length, to avoid reading the entire memory
+/// if the terminating null byte is missing.
+const MAX_STRING_LENGTH: usize = 4096;
+
+/// Paths longer than this are not read from the target, like `PATH_MAX` on Linux.
+const MAX_PATH_LENGTH: u32 = 4096;
+
+/// The number of bytes of a `SYS_WRITE` or `SYS_READ` request which are transferred at once.
+///
+/// Larger requests are completed partially, and the target transfers the rest with another
+/// request. This bounds the memory the host allocates for a request of the target.
+pub const MAX_TRANSFER_LENGTH: u32 = 64 * 1024;
+
+/// A semihosting request made by the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SemihostingCommand {
+    /// SYS_OPEN: Open the file at `path`, with `mode` being an index into the ISO C `fopen` modes
+    /// `r`, `rb`, `r+`, `r+b`, `w`, `wb`, `w+`, `w+b`, `a`, `ab`, `a+` and `a+b`.
+    ///
+    /// The special path `:tt` refers to the console.
+    Open {
+        /// The path of the file.
+        path: String,
+        /// The `fopen` mode.
+        mode: u32,
+    },
+    /// SYS_CLOSE: Close a handle returned by [`SemihostingCommand::Open`].
+    Close {
+        /// The handle to close.
+        handle: u32,
+    },
+    /// SYS_WRITEC: Write a single character to the console.
+    WriteC(u8),
+    /// SYS_WRITE0: Write a null terminated string to the console.
+    Write0(Vec<u8>),
+    /// SYS_WRITE: Write `data` to `handle`.
+    ///
+    /// `data` holds at most the first [`MAX_TRANSFER_LENGTH`] bytes of the request. The rest is
+    /// returned to the target as not written, which then writes it with another request.
+    Write {
+        /// The handle to write to.
+        handle: u32,
+        /// The data to write.
+        data: Vec<u8>,
+        /// The number of bytes the target requested to write.
+        length: u32,
+    },
+    /// SYS_READ: Read up to `length` bytes from `handle` into the target buffer at `buffer_address`.
+    ///
+    /// Use [`complete_semihosting_read`] to return the data. Reading at most
+    /// [`MAX_TRANSFER_LENGTH`] bytes at once is enough, the target reads the rest with another
+    /// request.
+    Read {
+        /// The handle to read from.
+        handle: u32,
+        /// The address of the target buffer.
+        buffer_address: u32,
+        /// The size of the target buffer.
+        length: u32,
+    },
+    /// SYS_EXIT: The application exited.
+    Exit {
+        /// The reason code, [`ADP_STOPPED_APPLICATION_EXIT`] for a normal exit.
+        reason: u32,
+    },
+    /// Any operation which is not decoded by probe-rs, or a `SYS_OPEN` with a path longer than
+    /// probe-rs reads from the target.
+    Unknown {
+        /// The operation number in `r0`.
+        operation: u32,
+        /// The parameter in `r1`.
+        parameter: u32,
+    },
+}
+
+/// Check if `core` is halted on a semihosting request, and decode it.
+///
+/// Returns `None` if the core did not halt on a `BKPT 0xAB` instruction.
+/// Only Cortex-M cores are supported.
+pub fn decode_semihosting_syscall(core: &mut Core) -> Result<Option<SemihostingCommand>, Error> {
+    if !matches!(
+        core.core_type(),
+        CoreType::Armv6m | CoreType::Armv7m | CoreType::Armv7em | CoreType::Armv8m
+    ) {
+        return Ok(None);
+    }
+
+    let pc: u32 = core.read_core_reg(core.program_counter())?;
+
+    let mut instruction = [0u8; 2];
+    core.read_8(pc as u64, &mut instruction)?;
+
+    if u16::from_le_bytes(instruction) != BKPT_SEMIHOSTING {
+        return Ok(None);
+    }
+
+    let operation: u32 = core.read_core_reg(core.registers().argument_register(0))?;
+    let parameter: u32 = core.read_core_reg(core.registers().argument_register(1))?;
+
+    let mut block = [0u32; 3];
+
+    let command = match operation {
+        SYS_OPEN => {
+            core.read_32(parameter as u64, &mut block)?;
+            let [path_address, mode, path_length] = block;
+
+            if path_length > MAX_PATH_LENGTH {
+                tracing::warn!(
+                    "Semihosting: the path to open is {} bytes long, only paths of up to {} bytes are supported",
+                    path_length,
+                    MAX_PATH_LENGTH
+                );
+
+                return Ok(Some(SemihostingCommand::Unknown {
+                    operation,
+                    parameter,
+                }));
+            }
+
+            let mut path = vec![0; path_length as usize];
+            core.read_8(path_address as u64, &mut path)?;
+
+            SemihostingCommand::Open {
+                path: String::from_utf8_lossy(&path).into_owned(),
+                mode,
+            }
+        }
+        SYS_CLOSE => SemihostingCommand::Close {
+            handle: core.read_word_32(parameter as u64)?,
+        },
+        SYS_WRITEC => SemihostingCommand::WriteC(core.read_word_8(parameter as u64)?),
+        SYS_WRITE0 => SemihostingCommand::Write0(read_c_string(core, parameter)?),
+        SYS_WRITE => {
+            core.read_32(parameter as u64, &mut block)?;
+            let [handle, data_address, length] = block;
+
+            let mut data = vec![0; length.min(MAX_TRANSFER_LENGTH) as usize];
+            core.read_8(data_address as u64, &mut data)?;
+
+            SemihostingCommand::Write {
+                handle,
+                data,
+                length,
+            }
+        }
+        SYS_READ => {
+            core.read_32(parameter as u64, &mut block)?;
+            let [handle, buffer_address, length] = block;
+
+            SemihostingCommand::Read {
+                handle,
+                buffer_address,
+                length,
+            }
+        }
+        // On 32-bit targets, the reason code is passed directly in r1.
+        SYS_EXIT => SemihostingCommand::Exit { reason: parameter },
+        _ => SemihostingCommand::Unknown {
+            operation,
+            parameter,
+        },
+    };
+
+    Ok(Some(command))
+}
+
+/// Return `result` to the target in `r0`, and step over the `BKPT 0xAB` instruction.
+///
+/// The core is left halted, it has to be resumed by the caller.
+pub fn complete_semihosting_syscall(core: &mut Core, result: u32) -> Result<(), Error> {
+    core.write_core_reg(core.registers().result_register(0), result)?;
+
+    let pc: u32 = core.read_core_reg(core.program_counter())?;
+    core.write_core_reg(core.program_counter(), pc + 2)?;
+
+    Ok(())
+}
+
+/// Write `data` to the target buffer of a [`SemihostingCommand::Read`] request and complete it.
+///
+/// `length` is the size of the target buffer, `data` must not be longer than that.
+pub fn complete_semihosting_read(
+    core: &mut Core,
+    buffer_address: u32,
+    length: u32,
+    data: &[u8],
+) -> Result<(), Error> {
+    core.write_8(buffer_address as u64, data)?;
+
+    // SYS_READ returns the number of bytes which were *not* read.
+    complete_semihosting_syscall(core, length.saturating_sub(data.len() as u32))
+}
+
+fn read_c_string(core: &mut Core, address: u32) -> Result<Vec<u8>, Error> {
+    let mut string = Vec::new();
+    let mut chunk = [0u8; 32];
+
+    while string.len() < MAX_STRING_LENGTH {
+        core.read_8(address as u64 + string.len() as u64, &mut chunk)?;
+
+        match chunk.iter().position(|&b| b == 0) {
+            Some(end) => {
+                string.extend_from_slice(&chunk[..end]);
+                break;
+            }
+            None => string.extend_from_slice(&chunk),
+        }
+    }
+
+    Ok(string)
+}
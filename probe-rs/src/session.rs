@@ -1,22 +1,42 @@
-use crate::architecture::arm::component::get_arm_components;
-use crate::architecture::arm::sequences::{ArmDebugSequence, DefaultArmSequence};
-use crate::architecture::arm::{ArmError, DpAddress};
+mod attach;
+
+pub(crate) use attach::AttachProcess;
+pub use attach::{AttachDiagnostics, AttachStep, PartialAttach};
+
+use crate::architecture::arm::ap::{AccessPort, ApAccess, ApScanOptions, MemoryAp, CSW};
+use crate::architecture::arm::component::{find_component, get_arm_components, Mtb, Pmu};
+use crate::architecture::arm::dp::{DpAccess, DpHealth, DPIDR, TARGETID};
+use crate::architecture::arm::memory::{ApMemoryMap, PeripheralType};
+use crate::architecture::arm::sequences::{
+    ArmDebugSequence, DefaultArmSequence, LifecycleSequence, LifecycleState,
+};
+use crate::architecture::arm::{
+    ApAddress, ApInformation, ArmError, DapAccess, DapBatch, DpAddress,
+};
 use crate::architecture::riscv::communication_interface::RiscvError;
-use crate::config::{ChipInfo, CoreExt, RegistryError, Target, TargetSelector};
-use crate::core::{Architecture, CombinedCoreState};
+use crate::config::{Chip, ChipInfo, RegistryError, Target, TargetSelector};
+use crate::core::{Architecture, CombinedCoreState, CoreAccessOptions};
+use crate::debug::BranchRecord;
+use crate::events::SessionEvent;
 use crate::{
     architecture::{
         arm::{
-            communication_interface::ArmProbeInterface, component::TraceSink,
-            memory::CoresightComponent, SwoReader,
+            communication_interface::ArmProbeInterface,
+            component::{ItmConfig, TraceLink, TraceSink},
+            memory::CoresightComponent,
+            ConnectionTelemetry, DapStatistics, SwoReader,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
     config::DebugSequence,
 };
-use crate::{AttachMethod, Core, CoreType, Error, FakeProbe, Probe};
-use std::ops::DerefMut;
-use std::{fmt, sync::Arc, time::Duration};
+use crate::{AttachMethod, Core, CoreType, Error, FakeProbe, MemoryInterface, Probe, SessionHooks};
+use std::ops::{DerefMut, Range};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// The `Session` struct represents an active debug session.
 ///
@@ -42,6 +62,9 @@ pub struct Session {
     interface: ArchitectureInterface,
     cores: Vec<CombinedCoreState>,
     configured_trace_sink: Option<TraceSink>,
+    hooks: SessionHooks,
+    /// The memory accessible through each memory AP, empty until [`Session::map_ap_memory`].
+    ap_memory_maps: Vec<ApMemoryMap>,
 }
 
 pub(crate) enum ArchitectureInterface {
@@ -92,197 +115,57 @@ impl Session {
         attach_method: AttachMethod,
         permissions: Permissions,
     ) -> Result<Self, Error> {
-        let (probe, target) = get_target_from_selector(target, attach_method, probe)?;
-
-        let cores = target
-            .cores
-            .iter()
-            .enumerate()
-            .map(|(id, core)| {
-                Core::create_state(
-                    id,
-                    core.core_access_options.clone(),
-                    &target,
-                    core.core_type,
-                )
-            })
-            .collect();
-
-        let mut session = match target.architecture() {
-            Architecture::Arm => {
-                Self::attach_arm(probe, target, attach_method, permissions, cores)?
-            }
-            Architecture::Riscv => {
-                Self::attach_riscv(probe, target, attach_method, permissions, cores)?
-            }
-        };
-
-        session.clear_all_hw_breakpoints()?;
-
-        Ok(session)
+        AttachProcess::attach(probe, target, attach_method, permissions)
+            .map_err(|partial| partial.into_error())
     }
 
-    fn attach_arm(
-        mut probe: Probe,
+    /// Create a session from an interface which is attached to the target.
+    fn from_parts(
         target: Target,
-        attach_method: AttachMethod,
-        permissions: Permissions,
+        interface: ArchitectureInterface,
         cores: Vec<CombinedCoreState>,
-    ) -> Result<Self, Error> {
-        let default_core = target.default_core();
-
-        let default_memory_ap = default_core.memory_ap().ok_or_else(|| {
-            Error::Other(anyhow::anyhow!(
-                "Unable to connect to core {default_core:?}, no memory AP configured"
-            ))
-        })?;
-
-        let sequence_handle = match &target.debug_sequence {
-            DebugSequence::Arm(sequence) => sequence.clone(),
-            DebugSequence::Riscv(_) => {
-                panic!("Mismatch between architecture and sequence type!")
-            }
-        };
-
-        if AttachMethod::UnderReset == attach_method {
-            let span = tracing::debug_span!("Asserting hardware assert");
-            let _enter = span.enter();
-
-            if let Some(dap_probe) = probe.try_as_dap_probe() {
-                sequence_handle.reset_hardware_assert(dap_probe)?;
-            } else {
-                tracing::info!(
-                    "Custom reset sequences are not supported on {}.",
-                    probe.get_name()
-                );
-                tracing::info!("Falling back to standard probe reset.");
-                probe.target_reset_assert()?;
-            }
-        }
-
-        probe.inner_attach()?;
-
-        let interface = probe.try_into_arm_interface().map_err(|(_, err)| err)?;
-
-        let mut interface = interface
-            .initialize(sequence_handle.clone())
-            .map_err(|(_interface, e)| e)?;
-
-        let unlock_span = tracing::debug_span!("debug_device_unlock").entered();
-
-        // Enable debug mode
-        let unlock_res =
-            sequence_handle.debug_device_unlock(&mut *interface, default_memory_ap, &permissions);
-        drop(unlock_span);
-
-        match unlock_res {
-            Ok(()) => (),
-            // In case this happens after unlock. Try to re-attach the probe once.
-            Err(ArmError::ReAttachRequired) => {
-                Self::reattach_arm_interface(&mut interface, &sequence_handle)?;
-            }
-            Err(e) => return Err(Error::Arm(e)),
-        }
-
-        // For each core, setup debugging
-        for core in &cores {
-            core.enable_arm_debug(&mut *interface)?;
-        }
-
-        if attach_method == AttachMethod::UnderReset {
-            {
-                for core in &cores {
-                    core.arm_reset_catch_set(&mut *interface)?;
-                }
-
-                let reset_hardware_deassert =
-                    tracing::debug_span!("reset_hardware_deassert").entered();
-
-                let mut memory_interface = interface.memory_interface(default_memory_ap)?;
-
-                // TODO: A timeout here indicates that the reset pin is probably not properly
-                //       connected.
-                if let Err(e) = sequence_handle.reset_hardware_deassert(&mut *memory_interface) {
-                    if matches!(e, ArmError::Timeout) {
-                        tracing::warn!("Timeout while deasserting hardware reset pin. This indicates that the reset pin is not properly connected. Please check your hardware setup.");
-                    }
-
-                    return Err(e.into());
-                }
-                drop(reset_hardware_deassert);
-            }
-
-            let mut session = Session {
-                target,
-                interface: ArchitectureInterface::Arm(interface),
-                cores,
-                configured_trace_sink: None,
-            };
-
-            {
-                // Wait for the core to be halted. The core should be
-                // halted because we set the `reset_catch` earlier, which
-                // means that the core should stop when coming out of reset.
-
-                for core_id in 0..session.cores.len() {
-                    let mut core = session.core(core_id)?;
-
-                    core.wait_for_core_halted(Duration::from_millis(100))?;
-
-                    core.reset_catch_clear()?;
-                }
-            }
-
-            Ok(session)
-        } else {
-            Ok(Session {
-                target,
-                interface: ArchitectureInterface::Arm(interface),
-                cores,
-                configured_trace_sink: None,
-            })
-        }
-    }
-
-    fn attach_riscv(
-        mut probe: Probe,
-        target: Target,
-        _attach_method: AttachMethod,
-        _permissions: Permissions,
-        cores: Vec<CombinedCoreState>,
-    ) -> Result<Self, Error> {
-        // TODO: Handle attach under reset
-
-        let sequence_handle = match &target.debug_sequence {
-            DebugSequence::Riscv(sequence) => sequence.clone(),
-            DebugSequence::Arm(_) => {
-                panic!("Mismatch between architecture and sequence type!")
-            }
-        };
-
-        probe.inner_attach()?;
-
-        let interface = probe
-            .try_into_riscv_interface()
-            .map_err(|(_probe, err)| err)?;
-
-        let mut session = Session {
+    ) -> Self {
+        Session {
             target,
-            interface: ArchitectureInterface::Riscv(Box::new(interface)),
+            interface,
             cores,
             configured_trace_sink: None,
-        };
-
-        {
-            // Todo: Add multicore support. How to deal with any cores that are not active and won't respond?
-            let mut core = session.core(0)?;
-
-            core.halt(Duration::from_millis(100))?;
+            hooks: SessionHooks::default(),
+            ap_memory_maps: Vec::new(),
         }
+    }
 
-        sequence_handle.on_connect(session.get_riscv_interface()?)?;
+    /// Check that no other debugger is using the Cortex-M cores of the target.
+    ///
+    /// Debuggers set C_DEBUGEN in DHCSR while they are attached, and sessions clear it when they
+    /// end. If it is set before attaching, another debugger is attached, or a session did not end
+    /// cleanly, which cannot be told apart. So this only fails if `deny` is set, and warns
+    /// otherwise. The check runs before the target is unlocked, as unlock sequences can enable
+    /// halting debug, so a target which cannot be accessed yet is not checked.
+    fn check_not_debugged(
+        interface: &mut dyn ArmProbeInterface,
+        cores: &[CombinedCoreState],
+        deny: bool,
+    ) -> Result<(), Error> {
+        for core in cores.iter().filter(|core| core.core_type().is_cortex_m()) {
+            match core.cortex_m_dhcsr(interface) {
+                Ok(dhcsr) if dhcsr.c_debugen() && deny => {
+                    return Err(Error::TargetAlreadyDebugged { core: core.id() })
+                }
+                Ok(dhcsr) if dhcsr.c_debugen() => tracing::warn!(
+                    "Core {} has halting debug enabled, another debugger may be attached to it",
+                    core.id()
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::debug!(
+                    "Core {} could not be checked for another debugger: {}",
+                    core.id(),
+                    e
+                ),
+            }
+        }
 
-        Ok(session)
+        Ok(())
     }
 
     /// Automatically creates a session with the first connected probe found.
@@ -330,7 +213,197 @@ impl Session {
             .cores
             .get_mut(core_index)
             .ok_or(Error::CoreNotFound(core_index))?;
-        self.interface.attach(combined_state)
+        let core = self.interface.attach(combined_state)?;
+
+        Ok(core
+            .with_hooks(&self.hooks)
+            .with_memory_map(&self.target.memory_map, &self.target.cores[core_index].name))
+    }
+
+    /// Read memory through the access port of a core, without halting it.
+    ///
+    /// Unlike reads through [`Session::core`], this does not attach to the core and does not
+    /// access its debug registers such as DHCSR, so the core keeps running undisturbed. This is
+    /// meant for live telemetry, for example to sample variables of a running application.
+    ///
+    /// This is only possible for Cortex-M cores, whose memory AP can access the system bus while
+    /// the core runs. It fails with [`Error::ReadWhileRunningNotSupported`] for other cores, and
+    /// if the access port currently does not allow transfers, for example because the core is
+    /// in a low power state or debug access is locked.
+    ///
+    /// After [`Session::map_ap_memory`], the read goes through another memory AP if the AP of
+    /// the core cannot access the address, see [`Session::memory_ap_for`].
+    pub fn read_while_running(
+        &mut self,
+        core_index: usize,
+        address: u64,
+        data: &mut [u8],
+    ) -> Result<(), Error> {
+        let core = self
+            .cores
+            .get(core_index)
+            .ok_or(Error::CoreNotFound(core_index))?;
+
+        if !core.core_type().is_cortex_m() {
+            return Err(Error::ReadWhileRunningNotSupported(
+                "memory is only accessible through the halted core",
+            ));
+        }
+
+        let memory_ap = self
+            .memory_ap_for(core_index, address..address + data.len() as u64)
+            .unwrap_or_else(|| core.arm_memory_ap());
+        let interface = self.get_arm_interface()?;
+
+        let csw: CSW = interface.read_ap_register(memory_ap)?;
+        if csw.DeviceEn == 0 {
+            return Err(Error::ReadWhileRunningNotSupported(
+                "the access port does not allow transfers",
+            ));
+        }
+
+        interface.memory_interface(memory_ap)?.read(address, data)?;
+
+        Ok(())
+    }
+
+    /// Map the memory which is accessible through each memory AP, see [`ApMemoryMap::probe`].
+    ///
+    /// The regions of the memory map of the target are probed through the memory APs of the
+    /// cores, and through all other memory APs of their debug ports. The maps are kept in the
+    /// session, and used to route accesses to an AP which can reach the address.
+    pub fn map_ap_memory(&mut self) -> Result<&[ApMemoryMap], ArmError> {
+        if self.architecture() != Architecture::Arm {
+            return Err(ArmError::NoArmTarget);
+        }
+
+        let regions = self
+            .target
+            .memory_map
+            .iter()
+            .map(|region| region.address_range().clone())
+            .collect::<Vec<_>>();
+
+        let mut access_ports = self
+            .cores
+            .iter()
+            .map(|core| core.arm_memory_ap())
+            .collect::<Vec<_>>();
+
+        let mut debug_ports = Vec::new();
+        for access_port in &access_ports {
+            let dp = access_port.ap_address().dp;
+            if !debug_ports.contains(&dp) {
+                debug_ports.push(dp);
+            }
+        }
+
+        let interface = self.get_arm_interface()?;
+
+        for dp in debug_ports {
+            for ap in interface.access_ports(dp)? {
+                if let ApInformation::MemoryAp(information) = interface.ap_information(ap)? {
+                    access_ports.push(MemoryAp::new(information.address));
+                }
+            }
+        }
+
+        let mut maps: Vec<ApMemoryMap> = Vec::new();
+        for access_port in access_ports {
+            if maps
+                .iter()
+                .any(|map| map.access_port.ap_address() == access_port.ap_address())
+            {
+                continue;
+            }
+
+            let map = ApMemoryMap::probe(interface, access_port, &regions)?;
+            tracing::debug!(
+                "Memory accessible through {:?}: {:x?}",
+                access_port,
+                map.accessible
+            );
+            maps.push(map);
+        }
+
+        self.ap_memory_maps = maps;
+
+        Ok(&self.ap_memory_maps)
+    }
+
+    /// The maps of the memory accessible through each memory AP, empty before
+    /// [`Session::map_ap_memory`].
+    pub fn ap_memory_maps(&self) -> &[ApMemoryMap] {
+        &self.ap_memory_maps
+    }
+
+    /// The memory AP through which `range` is accessed for the core `core_index`.
+    ///
+    /// This is the AP of the core, if it can access the range or the memory was not mapped
+    /// with [`Session::map_ap_memory`], otherwise the first AP which can access the range.
+    /// Returns `None` for cores which are not ARM cores.
+    pub fn memory_ap_for(&self, core_index: usize, range: Range<u64>) -> Option<MemoryAp> {
+        if self.architecture() != Architecture::Arm {
+            return None;
+        }
+        let core_ap = self.cores.get(core_index)?.arm_memory_ap();
+
+        let reaches = |map: &ApMemoryMap| map.contains_range(&range);
+        let core_map = self
+            .ap_memory_maps
+            .iter()
+            .find(|map| map.access_port.ap_address() == core_ap.ap_address());
+
+        if core_map.map_or(true, reaches) {
+            return Some(core_ap);
+        }
+
+        Some(
+            self.ap_memory_maps
+                .iter()
+                .find(|map| reaches(map))
+                .map_or(core_ap, |map| map.access_port),
+        )
+    }
+
+    /// The performance monitors unit of a Cortex-A core, see [`Pmu`].
+    ///
+    /// The PMU is accessed through the access port of the core, without halting it. Its base
+    /// address has to be set as `pmu_base` of the core in the target description, otherwise this
+    /// fails with [`ArmError::PmuBaseNotSpecified`].
+    pub fn pmu(&mut self, core_index: usize) -> Result<Pmu<'_>, Error> {
+        let core = self
+            .target
+            .cores
+            .get(core_index)
+            .ok_or(Error::CoreNotFound(core_index))?;
+
+        let pmu_base = match &core.core_access_options {
+            CoreAccessOptions::Arm(options) => options.pmu_base,
+            _ => None,
+        }
+        .ok_or(ArmError::PmuBaseNotSpecified)?;
+
+        let core = &self.cores[core_index];
+        let core_type = core.core_type();
+        let memory_ap = core.arm_memory_ap();
+
+        Ok(Pmu::new(
+            self.get_arm_interface()?,
+            memory_ap,
+            pmu_base,
+            core_type,
+        )?)
+    }
+
+    /// The hooks of this session, see [`SessionHooks`].
+    pub fn hooks(&self) -> &SessionHooks {
+        &self.hooks
+    }
+
+    /// Change the hooks of this session, see [`SessionHooks`].
+    pub fn hooks_mut(&mut self) -> &mut SessionHooks {
+        &mut self.hooks
     }
 
     /// Read available trace data from the specified data sink.
@@ -362,6 +435,29 @@ impl Session {
         }
     }
 
+    /// Start recording the branches taken by the core into the first `buffer_size` bytes of the
+    /// SRAM of the micro trace buffer (MTB), see [`Mtb::enable`].
+    ///
+    /// This method is only supported for ARM-based targets with an MTB.
+    pub fn enable_branch_trace(&mut self, buffer_size: u32) -> Result<(), ArmError> {
+        let components = self.get_arm_components(DpAddress::Default)?;
+        let interface = self.get_arm_interface()?;
+
+        Mtb::new(interface, find_component(&components, PeripheralType::Mtb)?).enable(buffer_size)
+    }
+
+    /// Read the branches recorded by the micro trace buffer (MTB), oldest first.
+    ///
+    /// Together with [`DebugInfo::execution_history`](crate::debug::DebugInfo::execution_history),
+    /// this gives the source lines executed before the core halted. The core should be halted,
+    /// so the buffer is not written while it is read.
+    pub fn read_branch_trace(&mut self) -> Result<Vec<BranchRecord>, ArmError> {
+        let components = self.get_arm_components(DpAddress::Default)?;
+        let interface = self.get_arm_interface()?;
+
+        Mtb::new(interface, find_component(&components, PeripheralType::Mtb)?).read_branches()
+    }
+
     /// Returns an implementation of [std::io::Read] that wraps [SwoAccess::read_swo].
     ///
     /// The implementation buffers all available bytes from
@@ -385,6 +481,77 @@ impl Session {
         Ok(interface)
     }
 
+    /// Check the health of the ARM debug port `dp`, see [`DpHealth`].
+    ///
+    /// If a problem is detected, a [`SessionEvent::DpHealth`] is emitted. Sticky errors are
+    /// not cleared, they are cleared by the next operation which fails because of them.
+    /// Use a [`DpHealthMonitor`](crate::architecture::arm::dp::DpHealthMonitor) for
+    /// periodic checks.
+    pub fn check_dp_health(&mut self, dp: DpAddress) -> Result<DpHealth, ArmError> {
+        let health = DpHealth::read(self.get_arm_interface()?, dp)?;
+
+        if !health.is_healthy() {
+            crate::architecture::arm::dp::health::report(dp, health);
+        }
+
+        Ok(health)
+    }
+
+    /// Returns the counters of the DAP transactions of this session, see [`DapStatistics`].
+    ///
+    /// Returns `None` for RISC-V targets, and for probes which access the DAP in their firmware.
+    pub fn statistics(&self) -> Option<DapStatistics> {
+        match &self.interface {
+            ArchitectureInterface::Arm(interface) => interface.statistics(),
+            ArchitectureInterface::Riscv(_) => None,
+        }
+    }
+
+    /// Reset the counters returned by [`Session::statistics`] to zero, for example before
+    /// measuring a single operation.
+    pub fn reset_statistics(&mut self) {
+        if let ArchitectureInterface::Arm(interface) = &mut self.interface {
+            interface.reset_statistics();
+        }
+    }
+
+    /// Returns the connection problems of this session, see [`ConnectionTelemetry`].
+    ///
+    /// Returns `None` for RISC-V targets, and for probes which access the DAP in their firmware.
+    pub fn connection_telemetry(&self) -> Option<ConnectionTelemetry> {
+        match &self.interface {
+            ArchitectureInterface::Arm(interface) => interface.connection_telemetry(),
+            ArchitectureInterface::Riscv(_) => None,
+        }
+    }
+
+    /// Log a summary of the [`Session::connection_telemetry`] every `interval`, or stop logging
+    /// with `None`.
+    pub fn set_telemetry_log_interval(&mut self, interval: Option<Duration>) {
+        if let ArchitectureInterface::Arm(interface) = &mut self.interface {
+            interface.set_telemetry_log_interval(interval);
+        }
+    }
+
+    /// Get temporary raw access to the debug access port of an ARM target.
+    ///
+    /// This is intended for tools which need to access vendor specific registers, for example
+    /// for provisioning or silicon bring-up. The registers can be accessed with the
+    /// [`DapAccess`] methods, or through the typed [`ApAccess`](crate::architecture::arm::ap::ApAccess)
+    /// and [`DpAccess`](crate::architecture::arm::dp::DpAccess) traits.
+    ///
+    /// Raw accesses may change the SELECT register of a debug port behind the back of the
+    /// session. When the returned guard is dropped, the SELECT register of every debug port is
+    /// restored from the cached AP and bank selection, so later accesses of the session work
+    /// as before. The CSW register of a memory AP is not cached across [`Session::core`] calls,
+    /// and is written again on the next memory access.
+    pub fn raw_dap_access(&mut self) -> Result<RawDapAccessGuard<'_>, ArmError> {
+        Ok(RawDapAccessGuard {
+            interface: self.get_arm_interface()?,
+            restored: false,
+        })
+    }
+
     fn get_riscv_interface(&mut self) -> Result<&mut RiscvCommunicationInterface, RiscvError> {
         let interface = match &mut self.interface {
             ArchitectureInterface::Riscv(interface) => interface,
@@ -398,6 +565,7 @@ impl Session {
     fn reattach_arm_interface(
         interface: &mut Box<dyn ArmProbeInterface>,
         debug_sequence: &Arc<dyn ArmDebugSequence>,
+        ap_scan: &ApScanOptions,
     ) -> Result<(), Error> {
         use crate::DebugProbe;
 
@@ -417,7 +585,8 @@ impl Session {
         probe.detach()?;
         probe.attach_to_unspecified()?;
 
-        let new_interface = probe.try_into_arm_interface().map_err(|(_, err)| err)?;
+        let mut new_interface = probe.try_into_arm_interface().map_err(|(_, err)| err)?;
+        new_interface.set_ap_scan_options(ap_scan.clone());
 
         tmp_interface = new_interface
             .initialize(debug_sequence.clone())
@@ -461,11 +630,13 @@ impl Session {
                 unreachable!("This should never happen. Please file a bug if it does.")
             }
         };
+        let ap_scan = self.target.ap_scan.clone();
 
         let Some(erase_sequence) = debug_sequence.debug_erase_sequence() else {
             return Err(Error::Probe(crate::DebugProbeError::NotImplemented(
                 "Debug Erase Sequence",
-        )))};
+            )));
+        };
 
         tracing::info!("Trying Debug Erase Sequence");
         let erase_result = erase_sequence.erase_all(interface.deref_mut());
@@ -474,7 +645,7 @@ impl Session {
             Ok(()) => (),
             // In case this happens after unlock. Try to re-attach the probe once.
             Err(ArmError::ReAttachRequired) => {
-                Self::reattach_arm_interface(interface, &debug_sequence)?;
+                Self::reattach_arm_interface(interface, &debug_sequence, &ap_scan)?;
                 // For re-setup debugging on all cores
                 for core_state in &self.cores {
                     core_state.enable_arm_debug(interface.deref_mut())?;
@@ -486,6 +657,97 @@ impl Session {
         Ok(())
     }
 
+    /// Read the lifecycle state of the device, if it can be read through the debug interface.
+    ///
+    /// # Errors
+    /// NotImplemented if the device has no lifecycle sequence
+    pub fn lifecycle_state(&mut self) -> Result<LifecycleState, Error> {
+        let (interface, sequence) = self.lifecycle_sequence()?;
+
+        Ok(sequence.lifecycle_state(interface)?)
+    }
+
+    /// Advance the device to the lifecycle state `state`.
+    ///
+    /// # Warning
+    /// This restricts debug access to the device, in most cases after the next reset. Going back
+    /// to an earlier state is only possible by vendor specific means, which usually erase the
+    /// device, and may not be possible at all.
+    ///
+    /// # Errors
+    /// MissingPermissions if `permissions` do not allow lifecycle changes, and
+    /// InvalidLifecycleTransition if `state` is not later than the current state.
+    pub fn advance_lifecycle(
+        &mut self,
+        state: LifecycleState,
+        permissions: &Permissions,
+    ) -> Result<(), Error> {
+        permissions
+            .lifecycle_change()
+            .map_err(|MissingPermissions(desc)| Error::MissingPermissions(desc))?;
+
+        let (interface, sequence) = self.lifecycle_sequence()?;
+
+        let current = sequence.lifecycle_state(&mut *interface)?;
+        if state <= current {
+            return Err(Error::InvalidLifecycleTransition {
+                from: current,
+                to: state,
+            });
+        }
+
+        tracing::warn!("Advancing the device from lifecycle state {current} to {state}");
+        sequence.advance_lifecycle(&mut *interface, state)?;
+
+        Ok(())
+    }
+
+    fn lifecycle_sequence(
+        &mut self,
+    ) -> Result<(&mut dyn ArmProbeInterface, Arc<dyn LifecycleSequence>), Error> {
+        let sequence = match &self.target.debug_sequence {
+            DebugSequence::Arm(sequence) => sequence.lifecycle_sequence(),
+            DebugSequence::Riscv(_) => None,
+        };
+
+        match (&mut self.interface, sequence) {
+            (ArchitectureInterface::Arm(interface), Some(sequence)) => {
+                Ok((interface.deref_mut(), sequence))
+            }
+            _ => Err(Error::Probe(crate::DebugProbeError::NotImplemented(
+                "Lifecycle Sequence",
+            ))),
+        }
+    }
+
+    /// Read the unique ID which the vendor programmed into the chip, for example the U_ID of
+    /// STM32 parts or the DEVICEID of nRF parts.
+    ///
+    /// The parts described by [`Target::unique_id`] are read with the first core and
+    /// concatenated. Each part is returned in the byte order of the memory, so IDs which
+    /// consist of several words start with the lowest byte of the first word.
+    ///
+    /// # Errors
+    /// NotImplemented if the target description does not say where the ID is stored
+    pub fn read_unique_id(&mut self) -> Result<Vec<u8>, Error> {
+        let parts = self.target.unique_id.clone();
+        if parts.is_empty() {
+            return Err(Error::NotImplemented(
+                "reading the unique ID of this target",
+            ));
+        }
+
+        let mut core = self.core(0)?;
+        let mut id = Vec::new();
+        for part in parts {
+            let start = id.len();
+            id.resize(start + part.size as usize, 0);
+            core.read(part.address, &mut id[start..])?;
+        }
+
+        Ok(id)
+    }
+
     /// Reads all the available ARM CoresightComponents of the currently attached target.
     ///
     /// This will recursively parse the Romtable of the attached target
@@ -505,10 +767,24 @@ impl Session {
     }
 
     /// Configure the target and probe for serial wire view (SWV) tracing.
+    ///
+    /// ITM timestamps are configured with the defaults of [`ItmConfig`], see
+    /// [`Session::setup_tracing_with_config`] to change them.
     pub fn setup_tracing(
         &mut self,
         core_index: usize,
         destination: TraceSink,
+    ) -> Result<(), Error> {
+        self.setup_tracing_with_config(core_index, destination, &ItmConfig::default())
+    }
+
+    /// Configure the target and probe for serial wire view (SWV) tracing, with the ITM local
+    /// and global timestamps configured by `itm_config`.
+    pub fn setup_tracing_with_config(
+        &mut self,
+        core_index: usize,
+        destination: TraceSink,
+        itm_config: &ItmConfig,
     ) -> Result<(), Error> {
         // Enable tracing on the target
         {
@@ -539,13 +815,29 @@ impl Session {
         }
 
         sequence_handle.trace_start(interface, &components, &destination)?;
-        crate::architecture::arm::component::setup_tracing(interface, &components, &destination)?;
+        crate::architecture::arm::component::setup_tracing(
+            interface,
+            &components,
+            &destination,
+            itm_config,
+        )?;
 
         self.configured_trace_sink.replace(destination);
 
         Ok(())
     }
 
+    /// Configure the trace funnels and replicators in `links`, to route the trace data of a
+    /// source to a sink on targets with multiple trace sources or sinks.
+    ///
+    /// This should be called after [`Session::setup_tracing`], as the debug sequence of the
+    /// target configures the trace links when tracing is set up.
+    pub fn configure_trace_route(&mut self, links: &[TraceLink]) -> Result<(), ArmError> {
+        let components = self.get_arm_components(DpAddress::Default)?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::configure_trace_route(interface, &components, links)
+    }
+
     /// Configure the target to stop emitting SWV trace data.
     #[tracing::instrument(skip(self))]
     pub fn disable_swv(&mut self, core_index: usize) -> Result<(), Error> {
@@ -597,9 +889,16 @@ impl Drop for Session {
     fn drop(&mut self) {
         if let Err(err) = { 0..self.cores.len() }.try_for_each(|i| {
             self.core(i)
-                .and_then(|mut core| core.clear_all_hw_breakpoints())
+                .and_then(|mut core| core.clear_all_breakpoints())
         }) {
-            tracing::warn!("Could not clear all hardware breakpoints: {:?}", err);
+            tracing::warn!("Could not clear all breakpoints: {:?}", err);
+        }
+
+        if let Err(err) = { 0..self.cores.len() }.try_for_each(|i| {
+            self.core(i)
+                .and_then(|mut core| core.clear_all_hw_watchpoints())
+        }) {
+            tracing::warn!("Could not clear all hardware watchpoints: {:?}", err);
         }
 
         // Call any necessary deconfiguration/shutdown hooks.
@@ -611,11 +910,96 @@ impl Drop for Session {
     }
 }
 
+/// Temporary raw access to the debug access port of an ARM target.
+///
+/// Created by [`Session::raw_dap_access`]. The cached debug port state of the session is
+/// restored when the guard is dropped, or when [`RawDapAccessGuard::finish`] is called,
+/// which also reports errors during the restore.
+pub struct RawDapAccessGuard<'session> {
+    interface: &'session mut dyn ArmProbeInterface,
+    restored: bool,
+}
+
+impl RawDapAccessGuard<'_> {
+    /// End the raw access, and restore the cached debug port state of the session.
+    pub fn finish(mut self) -> Result<(), ArmError> {
+        self.restored = true;
+        self.interface.restore_cached_dap_state()
+    }
+}
+
+impl DapAccess for RawDapAccessGuard<'_> {
+    fn read_raw_dp_register(&mut self, dp: DpAddress, addr: u8) -> Result<u32, ArmError> {
+        self.interface.read_raw_dp_register(dp, addr)
+    }
+
+    fn write_raw_dp_register(
+        &mut self,
+        dp: DpAddress,
+        addr: u8,
+        value: u32,
+    ) -> Result<(), ArmError> {
+        self.interface.write_raw_dp_register(dp, addr, value)
+    }
+
+    fn read_raw_ap_register(&mut self, ap: ApAddress, addr: u8) -> Result<u32, ArmError> {
+        self.interface.read_raw_ap_register(ap, addr)
+    }
+
+    fn read_raw_ap_register_repeated(
+        &mut self,
+        ap: ApAddress,
+        addr: u8,
+        values: &mut [u32],
+    ) -> Result<(), ArmError> {
+        self.interface
+            .read_raw_ap_register_repeated(ap, addr, values)
+    }
+
+    fn write_raw_ap_register(
+        &mut self,
+        ap: ApAddress,
+        addr: u8,
+        value: u32,
+    ) -> Result<(), ArmError> {
+        self.interface.write_raw_ap_register(ap, addr, value)
+    }
+
+    fn write_raw_ap_register_repeated(
+        &mut self,
+        ap: ApAddress,
+        addr: u8,
+        values: &[u32],
+    ) -> Result<(), ArmError> {
+        self.interface
+            .write_raw_ap_register_repeated(ap, addr, values)
+    }
+
+    fn execute_batch(&mut self, batch: &DapBatch) -> Result<Vec<u32>, ArmError> {
+        self.interface.execute_batch(batch)
+    }
+}
+
+impl Drop for RawDapAccessGuard<'_> {
+    fn drop(&mut self) {
+        if self.restored {
+            return;
+        }
+
+        if let Err(e) = self.interface.restore_cached_dap_state() {
+            tracing::warn!("Failed to restore the DAP state after raw access: {}", e);
+        }
+    }
+}
+
 /// Determine the [Target] from a [TargetSelector].
 ///
 /// If the selector is [TargetSelector::Unspecified], the target will be looked up in the registry.
 /// If it its [TargetSelector::Auto], probe-rs will try to determine the target automatically, based on
 /// information read from the chip.
+///
+/// The target descriptions of the target directories are loaded into the registry first, see
+/// [`load_target_directories`](crate::config::load_target_directories).
 fn get_target_from_selector(
     target: TargetSelector,
     attach_method: AttachMethod,
@@ -623,15 +1007,20 @@ fn get_target_from_selector(
 ) -> Result<(Probe, Target), Error> {
     let mut probe = probe;
 
+    if !matches!(target, TargetSelector::Specified(_)) {
+        crate::config::load_target_directories()?;
+    }
+
     let target = match target {
         TargetSelector::Unspecified(name) => crate::config::get_target_by_name(name)?,
         TargetSelector::Specified(target) => target,
         TargetSelector::Auto => {
-            let mut found_chip = None;
+            let mut found_target = None;
 
             // At this point we do not know what the target is, so we cannot use the chip specific reset sequence.
             // Thus, we try just using a normal reset for target detection if we want to do so under reset.
             // This can of course fail, but target detection is a best effort, not a guarantee!
+            let reset_asserted = Instant::now();
             if AttachMethod::UnderReset == attach_method {
                 probe.target_reset_assert()?;
             }
@@ -654,7 +1043,24 @@ fn get_target_from_selector(
                                 None
                             });
 
-                        found_chip = found_arm_chip.map(ChipInfo::from);
+                        let target_id = read_target_id(&mut *interface, dp).unwrap_or_else(|e| {
+                            tracing::debug!("Error reading the TARGETID register: {}", e);
+                            None
+                        });
+
+                        if found_arm_chip.is_some() || target_id.is_some() {
+                            let chip_info = ChipInfo::Arm {
+                                rom_table: found_arm_chip,
+                                target_id,
+                            };
+
+                            found_target = Some(crate::config::get_target_by_chip_info(
+                                chip_info,
+                                |chip, address| {
+                                    read_id_register(&mut *interface, dp, chip, address)
+                                },
+                            ));
+                        }
 
                         probe = interface.close();
                     }
@@ -667,7 +1073,7 @@ fn get_target_from_selector(
                 tracing::debug!("No ARM interface was present. Skipping Riscv autodetect.");
             }
 
-            if found_chip.is_none() && probe.has_riscv_interface() {
+            if found_target.is_none() && probe.has_riscv_interface() {
                 match probe.try_into_riscv_interface() {
                     Ok(mut interface) => {
                         let idcode = interface.read_idcode();
@@ -686,10 +1092,23 @@ fn get_target_from_selector(
             }
 
             // Now we can deassert reset in case we asserted it before. This is always okay.
-            probe.target_reset_deassert()?;
+            if AttachMethod::UnderReset == attach_method {
+                let reset_timing = probe.reset_timing();
+                reset_timing.wait_asserted(reset_asserted);
+                probe.target_reset_deassert()?;
+                reset_timing.wait_released();
+            } else {
+                probe.target_reset_deassert()?;
+            }
+
+            if let Some(target) = found_target {
+                let target = target?;
 
-            if let Some(chip) = found_chip {
-                crate::config::get_target_by_chip_info(chip)?
+                crate::events::emit(|| SessionEvent::ChipDetected {
+                    chip: target.name.clone(),
+                });
+
+                target
             } else {
                 return Err(Error::ChipNotFound(RegistryError::ChipAutodetectFailed));
             }
@@ -699,6 +1118,46 @@ fn get_target_from_selector(
     Ok((probe, target))
 }
 
+/// Read the TARGETID register of the debug port `dp`, if it has one.
+fn read_target_id(
+    interface: &mut dyn ArmProbeInterface,
+    dp: DpAddress,
+) -> Result<Option<TARGETID>, ArmError> {
+    let dpidr: DPIDR = interface.read_dp_register(dp)?;
+
+    if dpidr.version() < 2 {
+        return Ok(None);
+    }
+
+    interface.read_dp_register(dp).map(Some)
+}
+
+/// Read the ID register at `address` for automatic detection, with the access port of the first
+/// core of `chip`.
+fn read_id_register(
+    interface: &mut dyn ArmProbeInterface,
+    dp: DpAddress,
+    chip: &Chip,
+    address: u64,
+) -> Option<u32> {
+    let CoreAccessOptions::Arm(options) = &chip.cores.first()?.core_access_options else {
+        return None;
+    };
+
+    let access_port = MemoryAp::new(ApAddress { dp, ap: options.ap });
+    let result = interface
+        .memory_interface(access_port)
+        .and_then(|mut memory| memory.read_word_32(address));
+
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            tracing::debug!("Error reading the ID register at {:#010x}: {}", address, e);
+            None
+        }
+    }
+}
+
 /// The `Permissions` struct represents what a [Session] is allowed to do with a target.
 /// Some operations can be irreversable, so need to be explicitly allowed by the user.
 ///
@@ -714,6 +1173,11 @@ fn get_target_from_selector(
 pub struct Permissions {
     /// When set to true, all memory of the chip may be erased or reset to factory default
     erase_all: bool,
+    /// When set to true, the session must not attach to a target which seems to be used by
+    /// another debugger
+    deny_debugger_takeover: bool,
+    /// When set to true, the lifecycle state of the device may be advanced
+    lifecycle_change: bool,
 }
 
 impl Permissions {
@@ -735,6 +1199,44 @@ impl Permissions {
         }
     }
 
+    /// Do not attach to a target which seems to be used by another debugger.
+    ///
+    /// Attaching fails with [`Error::TargetAlreadyDebugged`] then. Otherwise, only a warning is
+    /// logged, because a previous session which did not end cleanly leaves the target in the
+    /// same state as a debugger which is still attached.
+    #[must_use]
+    pub fn deny_debugger_takeover(self) -> Self {
+        Self {
+            deny_debugger_takeover: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn debugger_takeover_denied(&self) -> bool {
+        self.deny_debugger_takeover
+    }
+
+    /// Allow the session to advance the lifecycle state of the device with
+    /// [`Session::advance_lifecycle`].
+    ///
+    /// # Warning
+    /// This may irreversibly restrict debug access to the device.
+    #[must_use]
+    pub fn allow_lifecycle_change(self) -> Self {
+        Self {
+            lifecycle_change: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn lifecycle_change(&self) -> Result<(), MissingPermissions> {
+        if self.lifecycle_change {
+            Ok(())
+        } else {
+            Err(MissingPermissions("lifecycle_change".into()))
+        }
+    }
+
     pub(crate) fn erase_all(&self) -> Result<(), MissingPermissions> {
         if self.erase_all {
             Ok(())
@@ -0,0 +1,864 @@
+//! Attaching to a target step by step, with diagnostics of how far a failed attach got.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::{get_target_from_selector, ArchitectureInterface, Permissions, Session};
+use crate::architecture::arm::ap::{AccessPort, MemoryAp};
+use crate::architecture::arm::communication_interface::ArmProbeInterface;
+use crate::architecture::arm::core::armv7m::Dhcsr;
+use crate::architecture::arm::dp::{DpHealth, DPIDR};
+use crate::architecture::arm::sequences::ArmDebugSequence;
+use crate::architecture::arm::{ArmError, Register};
+use crate::config::{CoreExt, DebugSequence, Target, TargetSelector};
+use crate::core::{Architecture, CombinedCoreState};
+use crate::events::SessionEvent;
+use crate::{AttachMethod, Core, DebugProbeError, Error, Probe, ResetTiming, SessionHooks};
+
+/// A step of attaching to a target, see [`PartialAttach`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AttachStep {
+    /// Running the pre-attach hooks, and detecting the target if it was not specified.
+    DetectTarget,
+    /// Asserting the reset when attaching under reset.
+    AssertReset,
+    /// Connecting the probe to the debug port, selecting the boot mode, powering the debug port
+    /// up and reading its DPIDR.
+    ConnectDebugPort,
+    /// Finding the access ports of the debug port.
+    ScanAccessPorts,
+    /// Checking that no other debugger is attached to the cores.
+    CheckDebugger,
+    /// Reading the debug state of the cores before the unlock sequence runs, when attaching
+    /// without changing it.
+    ReadCoreState,
+    /// Running the unlock sequence of the target.
+    UnlockDevice,
+    /// Enabling debugging of a core.
+    EnableDebug {
+        /// The index of the core.
+        core: usize,
+    },
+    /// Releasing the reset, when attaching under reset.
+    DeassertReset,
+    /// Waiting for a core to halt after the reset was released.
+    HaltCore {
+        /// The index of the core.
+        core: usize,
+    },
+    /// Checking that the cores were neither reset nor halted while attaching.
+    CheckCoreState,
+    /// Connecting to a RISC-V target and halting its core.
+    AttachRiscv,
+    /// Clearing the hardware breakpoints and running the post-attach hooks.
+    Finish,
+}
+
+impl fmt::Display for AttachStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttachStep::DetectTarget => write!(f, "detecting the target"),
+            AttachStep::AssertReset => write!(f, "asserting reset"),
+            AttachStep::ConnectDebugPort => write!(f, "connecting to the debug port"),
+            AttachStep::ScanAccessPorts => write!(f, "scanning the access ports"),
+            AttachStep::CheckDebugger => write!(f, "checking for another debugger"),
+            AttachStep::ReadCoreState => write!(f, "reading the core state"),
+            AttachStep::UnlockDevice => write!(f, "unlocking the device"),
+            AttachStep::EnableDebug { core } => write!(f, "enabling debugging of core {core}"),
+            AttachStep::DeassertReset => write!(f, "releasing reset"),
+            AttachStep::HaltCore { core } => write!(f, "halting core {core} after reset"),
+            AttachStep::CheckCoreState => write!(f, "checking that the core state was preserved"),
+            AttachStep::AttachRiscv => write!(f, "attaching to the RISC-V core"),
+            AttachStep::Finish => write!(f, "running the post-attach hooks"),
+        }
+    }
+}
+
+/// What was measured about the connection when an attach failed, see [`PartialAttach`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AttachDiagnostics {
+    /// The target voltage, if the probe can measure it.
+    pub target_voltage: Option<f32>,
+    /// The DPIDR of the debug port, if it was read.
+    pub dpidr: Option<u32>,
+    /// The state of the debug port after the failure, including its sticky error flags, if
+    /// it could be read.
+    pub dp_health: Option<DpHealth>,
+}
+
+/// An attach which failed partway, returned by [`Probe::attach_with_diagnostics`].
+///
+/// It reports which step failed, and what was measured about the connection at that point.
+/// If the probe is still connected, the attach can be retried from the failed step with
+/// [`PartialAttach::retry`], for example after fixing the wiring or the target power. Steps
+/// which succeeded are not repeated.
+///
+/// Attaching with a [`CancellationToken`](crate::cancellation::CancellationToken) which is
+/// cancelled fails with [`Error::Cancelled`], and can be retried later as well.
+pub struct PartialAttach {
+    failed_step: AttachStep,
+    completed_steps: Vec<AttachStep>,
+    diagnostics: AttachDiagnostics,
+    error: Error,
+    /// The state to continue from, `None` if the probe was lost.
+    process: Option<AttachProcess>,
+}
+
+impl PartialAttach {
+    /// The step which failed.
+    pub fn failed_step(&self) -> AttachStep {
+        self.failed_step
+    }
+
+    /// The steps which succeeded, in order.
+    pub fn completed_steps(&self) -> &[AttachStep] {
+        &self.completed_steps
+    }
+
+    /// The measurements of the connection after the failure.
+    pub fn diagnostics(&self) -> &AttachDiagnostics {
+        &self.diagnostics
+    }
+
+    /// The error of the failed step.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Returns `true` if [`PartialAttach::retry`] can continue the attach.
+    ///
+    /// This is not possible if the probe was lost, which happens when target detection fails.
+    pub fn can_retry(&self) -> bool {
+        self.process.is_some()
+    }
+
+    /// Retry the attach from the failed step.
+    ///
+    /// If the attach cannot be retried, see [`PartialAttach::can_retry`], it fails again
+    /// with the same error.
+    pub fn retry(mut self: Box<Self>) -> Result<Session, Box<PartialAttach>> {
+        match self.process.take() {
+            Some(process) => process.run(),
+            None => Err(self),
+        }
+    }
+
+    /// Discard the state of the attach, and return the error of the failed step.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Debug for PartialAttach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartialAttach")
+            .field("failed_step", &self.failed_step)
+            .field("completed_steps", &self.completed_steps)
+            .field("diagnostics", &self.diagnostics)
+            .field("error", &self.error)
+            .field("can_retry", &self.can_retry())
+            .finish()
+    }
+}
+
+impl fmt::Display for PartialAttach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Attaching failed while {}", self.failed_step)
+    }
+}
+
+impl std::error::Error for PartialAttach {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// What the attach holds on to between steps.
+enum Connection {
+    /// The probe was lost.
+    None,
+    Probe {
+        probe: Probe,
+        /// The probe was attached to the target with [`Probe::inner_attach`].
+        attached: bool,
+    },
+    Arm(Box<dyn ArmProbeInterface>),
+    Session(Box<Session>),
+}
+
+/// The state of an attach between its steps, see [`PartialAttach`].
+pub(crate) struct AttachProcess {
+    target: Target,
+    attach_method: AttachMethod,
+    permissions: Permissions,
+    /// The hooks of the probe, until they are moved into the session.
+    hooks: Option<SessionHooks>,
+    /// The cores, until they are moved into the session.
+    cores: Vec<CombinedCoreState>,
+    connection: Connection,
+    probe_name: String,
+    reset_timing: ResetTiming,
+    reset_asserted: Instant,
+    /// The debug state of the cores before attaching, when attaching without changing it.
+    preserved_state: Vec<Dhcsr>,
+    diagnostics: AttachDiagnostics,
+    steps: Vec<AttachStep>,
+    /// The index of the next step to run in `steps`.
+    next_step: usize,
+}
+
+impl AttachProcess {
+    /// Attach `probe` to `target`.
+    pub(crate) fn attach(
+        mut probe: Probe,
+        target: TargetSelector,
+        attach_method: AttachMethod,
+        permissions: Permissions,
+    ) -> Result<Session, Box<PartialAttach>> {
+        let probe_name = probe.get_name();
+        let reset_timing = probe.reset_timing();
+        let diagnostics = AttachDiagnostics {
+            target_voltage: probe.get_target_voltage().ok().flatten(),
+            ..Default::default()
+        };
+
+        let hooks = probe.take_session_hooks();
+
+        let detected = hooks
+            .run_pre_attach(&mut probe)
+            .and_then(|_| get_target_from_selector(target, attach_method, probe))
+            .and_then(|(probe, target)| {
+                crate::cancellation::check()?;
+
+                if attach_method == AttachMethod::Preserve
+                    && !target.cores.iter().all(|core| core.core_type.is_cortex_m())
+                {
+                    return Err(Error::NotImplemented(
+                        "attaching without reset to cores other than Cortex-M",
+                    ));
+                }
+
+                Ok((probe, target))
+            });
+
+        let (probe, target) = match detected {
+            Ok(detected) => detected,
+            Err(error) => {
+                crate::events::emit(|| SessionEvent::error("attach", &error));
+
+                // The probe is consumed by the target detection.
+                return Err(Box::new(PartialAttach {
+                    failed_step: AttachStep::DetectTarget,
+                    completed_steps: Vec::new(),
+                    diagnostics,
+                    error,
+                    process: None,
+                }));
+            }
+        };
+
+        let cores = target
+            .cores
+            .iter()
+            .enumerate()
+            .map(|(id, core)| {
+                Core::create_state(
+                    id,
+                    core.core_access_options.clone(),
+                    &target,
+                    core.core_type,
+                )
+            })
+            .collect();
+
+        let steps = Self::plan(&target, attach_method);
+
+        Self {
+            target,
+            attach_method,
+            permissions,
+            hooks: Some(hooks),
+            cores,
+            connection: Connection::Probe {
+                probe,
+                attached: false,
+            },
+            probe_name,
+            reset_timing,
+            reset_asserted: Instant::now(),
+            preserved_state: Vec::new(),
+            diagnostics,
+            steps,
+            next_step: 1,
+        }
+        .run()
+    }
+
+    /// The steps of attaching to `target`, including the target detection.
+    fn plan(target: &Target, attach_method: AttachMethod) -> Vec<AttachStep> {
+        let mut steps = vec![AttachStep::DetectTarget];
+        let cores = 0..target.cores.len();
+
+        match target.architecture() {
+            Architecture::Arm => {
+                steps.extend([
+                    AttachStep::AssertReset,
+                    AttachStep::ConnectDebugPort,
+                    AttachStep::ScanAccessPorts,
+                ]);
+                if attach_method == AttachMethod::Normal {
+                    steps.push(AttachStep::CheckDebugger);
+                }
+                // The state is read before anything else accesses the cores, so every change by
+                // the following steps, including a re-attach after unlocking, is detected.
+                if attach_method == AttachMethod::Preserve {
+                    steps.push(AttachStep::ReadCoreState);
+                }
+                steps.push(AttachStep::UnlockDevice);
+                steps.extend(cores.clone().map(|core| AttachStep::EnableDebug { core }));
+
+                match attach_method {
+                    AttachMethod::UnderReset => {
+                        steps.push(AttachStep::DeassertReset);
+                        steps.extend(cores.map(|core| AttachStep::HaltCore { core }));
+                    }
+                    AttachMethod::Preserve => steps.push(AttachStep::CheckCoreState),
+                    AttachMethod::Normal => {}
+                }
+            }
+            Architecture::Riscv => steps.push(AttachStep::AttachRiscv),
+        }
+
+        steps.push(AttachStep::Finish);
+        steps
+    }
+
+    /// Run the remaining steps.
+    fn run(mut self) -> Result<Session, Box<PartialAttach>> {
+        while let Some(&step) = self.steps.get(self.next_step) {
+            let result = crate::cancellation::check()
+                .map_err(Error::from)
+                .and_then(|_| self.run_step(step));
+
+            if let Err(error) = result {
+                tracing::debug!("Attaching failed while {}: {}", step, error);
+                crate::events::emit(|| SessionEvent::error("attach", &error));
+
+                self.diagnose();
+
+                return Err(Box::new(PartialAttach {
+                    failed_step: step,
+                    completed_steps: self.steps[..self.next_step].to_vec(),
+                    diagnostics: self.diagnostics.clone(),
+                    error,
+                    process: (!matches!(self.connection, Connection::None)).then_some(self),
+                }));
+            }
+
+            tracing::debug!("Attach step done: {}", step);
+            self.next_step += 1;
+        }
+
+        let Connection::Session(session) = self.connection else {
+            unreachable!(
+                "The last attach step creates the session. This is a bug, please report it."
+            );
+        };
+
+        crate::events::emit(|| SessionEvent::Attached {
+            chip: session.target.name.clone(),
+            probe: self.probe_name.clone(),
+            under_reset: self.attach_method == AttachMethod::UnderReset,
+        });
+
+        Ok(*session)
+    }
+
+    fn run_step(&mut self, step: AttachStep) -> Result<(), Error> {
+        match step {
+            AttachStep::DetectTarget => Ok(()),
+            AttachStep::AssertReset => self.assert_reset(),
+            AttachStep::ConnectDebugPort => self.connect_debug_port(),
+            AttachStep::ScanAccessPorts => {
+                let dp = self.default_memory_ap()?.ap_address().dp;
+                let access_ports = self.arm_interface()?.access_ports(dp)?;
+                tracing::debug!("Found {} access ports", access_ports.len());
+                Ok(())
+            }
+            AttachStep::CheckDebugger => {
+                let interface = arm_interface(&mut self.connection)?;
+                Session::check_not_debugged(
+                    interface,
+                    &self.cores,
+                    self.permissions.debugger_takeover_denied(),
+                )
+            }
+            AttachStep::ReadCoreState => {
+                let interface = arm_interface(&mut self.connection)?;
+
+                // Reading DHCSR clears its sticky reset bit, so resets after this point can be detected.
+                self.preserved_state = self
+                    .cores
+                    .iter()
+                    .map(|core| core.cortex_m_dhcsr(interface))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(())
+            }
+            AttachStep::UnlockDevice => self.unlock_device(),
+            AttachStep::EnableDebug { core } => {
+                let interface = arm_interface(&mut self.connection)?;
+                self.cores[core].enable_arm_debug(interface)
+            }
+            AttachStep::DeassertReset => self.deassert_reset(),
+            AttachStep::HaltCore { core } => {
+                // The core should be halted because the reset catch was set before the reset
+                // was released, which means that the core should stop when coming out of reset.
+                let mut core = self.session()?.core(core)?;
+                core.wait_for_core_halted(Duration::from_millis(100))?;
+                core.reset_catch_clear()
+            }
+            AttachStep::CheckCoreState => self.check_core_state(),
+            AttachStep::AttachRiscv => self.attach_riscv(),
+            AttachStep::Finish => {
+                let hooks = self.hooks.take();
+                let session = self.session()?;
+
+                session.clear_all_hw_breakpoints()?;
+
+                if let Some(hooks) = hooks {
+                    session.hooks = hooks;
+                }
+                session.run_post_attach_hooks()
+            }
+        }
+    }
+
+    fn assert_reset(&mut self) -> Result<(), Error> {
+        let sequence_handle = self.arm_sequence();
+        let attach_method = self.attach_method;
+        let probe = self.probe()?;
+
+        if attach_method == AttachMethod::UnderReset {
+            let span = tracing::debug_span!("Asserting hardware assert");
+            let _enter = span.enter();
+
+            if let Some(dap_probe) = probe.try_as_dap_probe() {
+                sequence_handle.reset_hardware_assert(dap_probe)?;
+            } else {
+                tracing::info!(
+                    "Custom reset sequences are not supported on {}.",
+                    probe.get_name()
+                );
+                tracing::info!("Falling back to standard probe reset.");
+                probe.target_reset_assert()?;
+            }
+
+            self.reset_asserted = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    /// Drive the boot mode pins, if a boot mode was selected with [`Probe::set_boot_mode`].
+    fn select_boot_mode(&self, probe: &mut Probe) -> Result<(), Error> {
+        let Some((mode, pins)) = probe.boot_mode() else {
+            return Ok(());
+        };
+
+        if self.attach_method == AttachMethod::Preserve {
+            return Err(Error::Other(anyhow::anyhow!(
+                "A boot mode cannot be selected when attaching without reset"
+            )));
+        }
+
+        let pins = pins
+            .or(self.target.boot_mode_pins)
+            .or_else(|| self.arm_sequence().boot_mode_pins())
+            .ok_or_else(|| {
+                Error::Other(anyhow::anyhow!(
+                    "A boot mode was selected, but no boot mode pins are known for {}",
+                    self.target.name
+                ))
+            })?;
+
+        let dap_probe =
+            probe
+                .try_as_dap_probe()
+                .ok_or(DebugProbeError::CommandNotSupportedByProbe(
+                    "boot mode pins",
+                ))?;
+
+        tracing::debug!("Selecting boot mode {:?}", mode);
+        pins.apply(dap_probe, mode)?;
+
+        Ok(())
+    }
+
+    fn connect_debug_port(&mut self) -> Result<(), Error> {
+        let dp = self.default_memory_ap()?.ap_address().dp;
+
+        // The interface already exists if reading the DPIDR failed before.
+        if let Connection::Probe { .. } = self.connection {
+            let Connection::Probe {
+                mut probe,
+                attached,
+            } = std::mem::replace(&mut self.connection, Connection::None)
+            else {
+                unreachable!();
+            };
+
+            if !attached {
+                if let Err(error) = probe.inner_attach() {
+                    self.connection = Connection::Probe {
+                        probe,
+                        attached: false,
+                    };
+                    return Err(error.into());
+                }
+
+                // Connecting configures the pins of the probe, so the boot mode pins are only
+                // driven afterwards.
+                if let Err(error) = self.select_boot_mode(&mut probe) {
+                    self.connection = Connection::Probe {
+                        probe,
+                        attached: true,
+                    };
+                    return Err(error);
+                }
+            }
+
+            let mut interface = match probe.try_into_arm_interface() {
+                Ok(interface) => interface,
+                Err((probe, error)) => {
+                    self.connection = Connection::Probe {
+                        probe,
+                        attached: true,
+                    };
+                    return Err(error.into());
+                }
+            };
+            interface.set_ap_scan_options(self.target.ap_scan.clone());
+
+            match interface.initialize(self.arm_sequence()) {
+                Ok(interface) => self.connection = Connection::Arm(interface),
+                Err((interface, error)) => {
+                    self.connection = Connection::Probe {
+                        probe: interface.close(),
+                        attached: true,
+                    };
+                    return Err(error);
+                }
+            }
+        }
+
+        // This powers up the debug port.
+        let dpidr = self
+            .arm_interface()?
+            .read_raw_dp_register(dp, DPIDR::ADDRESS)?;
+        tracing::debug!("DPIDR: {:#010x}", dpidr);
+        self.diagnostics.dpidr = Some(dpidr);
+
+        Ok(())
+    }
+
+    fn unlock_device(&mut self) -> Result<(), Error> {
+        let sequence_handle = self.arm_sequence();
+        let default_memory_ap = self.default_memory_ap()?;
+        let interface = match &mut self.connection {
+            Connection::Arm(interface) => interface,
+            _ => return Err(ArmError::NoArmTarget.into()),
+        };
+
+        let unlock_span = tracing::debug_span!("debug_device_unlock").entered();
+
+        // Enable debug mode
+        let unlock_res = sequence_handle.debug_device_unlock(
+            &mut **interface,
+            default_memory_ap,
+            &self.permissions,
+        );
+        drop(unlock_span);
+
+        match unlock_res {
+            Ok(()) => Ok(()),
+            // In case this happens after unlock. Try to re-attach the probe once.
+            Err(ArmError::ReAttachRequired) => {
+                Session::reattach_arm_interface(interface, &sequence_handle, &self.target.ap_scan)?;
+
+                // The re-attach may reset the target, check right away.
+                if self.attach_method == AttachMethod::Preserve {
+                    self.check_core_state()?;
+                }
+
+                Ok(())
+            }
+            Err(e) => Err(Error::Arm(e)),
+        }
+    }
+
+    /// Check that no core was reset or halted since [`AttachStep::ReadCoreState`].
+    ///
+    /// Reading DHCSR clears its sticky reset bit, so the state is checked whenever it is read.
+    fn check_core_state(&mut self) -> Result<(), Error> {
+        let interface = arm_interface(&mut self.connection)?;
+
+        for (core, before) in self.cores.iter().zip(&self.preserved_state) {
+            let after = core.cortex_m_dhcsr(interface)?;
+
+            let operation = if after.s_reset_st() {
+                "reset"
+            } else if after.s_halt() && !before.s_halt() {
+                "halted"
+            } else {
+                continue;
+            };
+
+            return Err(Error::StateNotPreserved {
+                core: core.id(),
+                operation,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn deassert_reset(&mut self) -> Result<(), Error> {
+        let sequence_handle = self.arm_sequence();
+        let default_memory_ap = self.default_memory_ap()?;
+        let interface = arm_interface(&mut self.connection)?;
+
+        for core in &self.cores {
+            core.arm_reset_catch_set(interface)?;
+        }
+
+        let reset_hardware_deassert = tracing::debug_span!("reset_hardware_deassert").entered();
+
+        let mut memory_interface = interface.memory_interface(default_memory_ap)?;
+
+        self.reset_timing.wait_asserted(self.reset_asserted);
+
+        // TODO: A timeout here indicates that the reset pin is probably not properly
+        //       connected.
+        if let Err(e) = sequence_handle.reset_hardware_deassert(&mut *memory_interface) {
+            if matches!(e, ArmError::Timeout) {
+                tracing::warn!("Timeout while deasserting hardware reset pin. This indicates that the reset pin is not properly connected. Please check your hardware setup.");
+            }
+
+            return Err(e.into());
+        }
+        self.reset_timing.wait_released();
+        drop(reset_hardware_deassert);
+
+        Ok(())
+    }
+
+    fn attach_riscv(&mut self) -> Result<(), Error> {
+        // TODO: Handle attach under reset
+
+        let sequence_handle = match &self.target.debug_sequence {
+            DebugSequence::Riscv(sequence) => sequence.clone(),
+            DebugSequence::Arm(_) => {
+                panic!("Mismatch between architecture and sequence type!")
+            }
+        };
+
+        if let Connection::Probe { .. } = self.connection {
+            let Connection::Probe {
+                mut probe,
+                attached,
+            } = std::mem::replace(&mut self.connection, Connection::None)
+            else {
+                unreachable!();
+            };
+
+            if !attached {
+                if let Err(error) = probe.inner_attach() {
+                    self.connection = Connection::Probe {
+                        probe,
+                        attached: false,
+                    };
+                    return Err(error.into());
+                }
+            }
+
+            let interface = match probe.try_into_riscv_interface() {
+                Ok(interface) => interface,
+                Err((probe, error)) => {
+                    self.connection = Connection::Probe {
+                        probe,
+                        attached: true,
+                    };
+                    return Err(error.into());
+                }
+            };
+
+            self.connection = Connection::Session(Box::new(Session::from_parts(
+                self.target.clone(),
+                ArchitectureInterface::Riscv(Box::new(interface)),
+                std::mem::take(&mut self.cores),
+            )));
+        }
+
+        let session = self.session()?;
+        {
+            // Todo: Add multicore support. How to deal with any cores that are not active and won't respond?
+            let mut core = session.core(0)?;
+
+            core.halt(Duration::from_millis(100))?;
+        }
+
+        sequence_handle.on_connect(session.get_riscv_interface()?)?;
+
+        Ok(())
+    }
+
+    /// Measure what can still be measured about the connection after a failed step.
+    fn diagnose(&mut self) {
+        let dp = self.default_memory_ap().map(|ap| ap.ap_address().dp).ok();
+
+        match &mut self.connection {
+            Connection::Probe { probe, .. } => {
+                if let Ok(Some(voltage)) = probe.get_target_voltage() {
+                    self.diagnostics.target_voltage = Some(voltage);
+                }
+            }
+            // The debug port is only accessed once it is powered up, accessing it before would
+            // repeat the failed power up.
+            Connection::Arm(interface) if self.diagnostics.dpidr.is_some() => {
+                if let Some(dp) = dp {
+                    self.diagnostics.dp_health = DpHealth::read(&mut **interface, dp).ok();
+                }
+            }
+            Connection::Session(session) if self.diagnostics.dpidr.is_some() => {
+                if let (Some(dp), Ok(interface)) = (dp, session.get_arm_interface()) {
+                    self.diagnostics.dp_health = DpHealth::read(interface, dp).ok();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn arm_sequence(&self) -> std::sync::Arc<dyn ArmDebugSequence> {
+        match &self.target.debug_sequence {
+            DebugSequence::Arm(sequence) => sequence.clone(),
+            DebugSequence::Riscv(_) => {
+                panic!("Mismatch between architecture and sequence type!")
+            }
+        }
+    }
+
+    fn default_memory_ap(&self) -> Result<MemoryAp, Error> {
+        let default_core = self.target.default_core();
+
+        default_core.memory_ap().ok_or_else(|| {
+            Error::Other(anyhow::anyhow!(
+                "Unable to connect to core {default_core:?}, no memory AP configured"
+            ))
+        })
+    }
+
+    fn probe(&mut self) -> Result<&mut Probe, Error> {
+        match &mut self.connection {
+            Connection::Probe { probe, .. } => Ok(probe),
+            _ => Err(Error::Other(anyhow::anyhow!(
+                "The probe is already connected to the target"
+            ))),
+        }
+    }
+
+    fn arm_interface(&mut self) -> Result<&mut dyn ArmProbeInterface, Error> {
+        arm_interface(&mut self.connection)
+    }
+
+    /// The session, which is created from the interface on first use.
+    fn session(&mut self) -> Result<&mut Session, Error> {
+        self.connection = match std::mem::replace(&mut self.connection, Connection::None) {
+            Connection::Arm(interface) => Connection::Session(Box::new(Session::from_parts(
+                self.target.clone(),
+                ArchitectureInterface::Arm(interface),
+                std::mem::take(&mut self.cores),
+            ))),
+            connection => connection,
+        };
+
+        match &mut self.connection {
+            Connection::Session(session) => Ok(session),
+            _ => Err(Error::Other(anyhow::anyhow!(
+                "The probe is not connected to the target"
+            ))),
+        }
+    }
+}
+
+fn arm_interface(connection: &mut Connection) -> Result<&mut dyn ArmProbeInterface, Error> {
+    match connection {
+        Connection::Arm(interface) => Ok(&mut **interface),
+        Connection::Session(session) => Ok(session.get_arm_interface()?),
+        _ => Err(ArmError::NoArmTarget.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttachProcess, AttachStep};
+    use crate::AttachMethod;
+
+    #[test]
+    fn attach_under_reset_plan() {
+        let target = crate::config::get_target_by_name("nRF52840_xxAA").unwrap();
+
+        let steps = AttachProcess::plan(&target, AttachMethod::UnderReset);
+
+        assert_eq!(
+            steps,
+            [
+                AttachStep::DetectTarget,
+                AttachStep::AssertReset,
+                AttachStep::ConnectDebugPort,
+                AttachStep::ScanAccessPorts,
+                AttachStep::UnlockDevice,
+                AttachStep::EnableDebug { core: 0 },
+                AttachStep::DeassertReset,
+                AttachStep::HaltCore { core: 0 },
+                AttachStep::Finish,
+            ]
+        );
+    }
+
+    #[test]
+    fn preserving_attach_reads_the_core_state_first() {
+        let target = crate::config::get_target_by_name("nRF52840_xxAA").unwrap();
+
+        let steps = AttachProcess::plan(&target, AttachMethod::Preserve);
+
+        assert_eq!(
+            steps,
+            [
+                AttachStep::DetectTarget,
+                AttachStep::AssertReset,
+                AttachStep::ConnectDebugPort,
+                AttachStep::ScanAccessPorts,
+                AttachStep::ReadCoreState,
+                AttachStep::UnlockDevice,
+                AttachStep::EnableDebug { core: 0 },
+                AttachStep::CheckCoreState,
+                AttachStep::Finish,
+            ]
+        );
+    }
+
+    #[test]
+    fn takeover_check_only_for_normal_attach() {
+        let target = crate::config::get_target_by_name("nRF52840_xxAA").unwrap();
+
+        let normal = AttachProcess::plan(&target, AttachMethod::Normal);
+        let under_reset = AttachProcess::plan(&target, AttachMethod::UnderReset);
+        let preserving = AttachProcess::plan(&target, AttachMethod::Preserve);
+
+        assert!(normal.contains(&AttachStep::CheckDebugger));
+        assert!(!under_reset.contains(&AttachStep::CheckDebugger));
+        assert!(!preserving.contains(&AttachStep::CheckDebugger));
+    }
+}
@@ -0,0 +1,562 @@
+//! Running test binaries on the target
+//!
+//! [`run_test`] flashes an ELF file, runs it and waits until the test signals that it
+//! finished. The end of a test is detected with one or more [`ExitCondition`]s:
+//!
+//! - The target calls the semihosting `SYS_EXIT` operation.
+//! - The target reaches a function, for example a `test_finished` function which the test
+//!   framework calls at the end. A hardware breakpoint is set on the symbol.
+//! - The RTT output contains a marker, for example `TESTS PASSED`.
+//!
+//! Console output over semihosting and the output of an RTT up channel are captured while the
+//! test is running. [`run`](crate::runner::run) runs a binary the same way, and streams the
+//! output instead.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe_rs::test_runner::{run_test, ExitCondition, TestRunnerOptions};
+//! use probe_rs::{Permissions, Session};
+//!
+//! let mut session = Session::auto_attach("nrf52840_xxAA", Permissions::default())?;
+//!
+//! let mut options = TestRunnerOptions::default();
+//! options.exit_conditions.push(ExitCondition::RttMarker {
+//!     marker: "TESTS FAILED".to_owned(),
+//!     exit_code: 1,
+//! });
+//!
+//! let outcome = run_test(&mut session, "target/thumbv7em-none-eabihf/debug/tests", options)?;
+//! print!("{}", String::from_utf8_lossy(&outcome.output));
+//! std::process::exit(outcome.exit.exit_code().unwrap_or(1));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::flashing::{DownloadOptions, FileDownloadError};
+use crate::rtt::{Rtt, ScanRegion, UpChannel};
+use crate::runner::{RunOptions, RunOutput};
+use crate::semihosting::{
+    complete_semihosting_syscall, decode_semihosting_syscall, SemihostingCommand,
+    ADP_STOPPED_APPLICATION_EXIT,
+};
+use crate::{Core, CoreStatus, HaltReason, Session};
+
+use object::{Object, ObjectSymbol};
+use probe_rs_target::MemoryRegion;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The name of the RTT control block symbol, used to find the control block without scanning.
+const RTT_CONTROL_BLOCK_SYMBOL: &str = "_SEGGER_RTT";
+
+/// How long to wait between checks of the target state.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The value returned to the target for semihosting requests which are not supported.
+const SEMIHOSTING_ERROR: u32 = u32::MAX;
+
+/// An error which prevented a test from being run.
+///
+/// A failing test is not an error, but reported in the [`TestOutcome`].
+#[derive(thiserror::Error, Debug)]
+pub enum TestRunnerError {
+    /// The test binary could not be read.
+    #[error("Failed to read the test binary")]
+    Io(#[from] std::io::Error),
+    /// The test binary is not a valid ELF file.
+    #[error("Failed to parse the test binary")]
+    Elf(#[from] object::read::Error),
+    /// The symbol of an [`ExitCondition::Breakpoint`] is not defined in the test binary.
+    #[error("The symbol '{0}' was not found in the test binary")]
+    MissingSymbol(String),
+    /// Flashing the test binary failed.
+    #[error("Failed to flash the test binary")]
+    Download(#[from] FileDownloadError),
+    /// Controlling the core failed.
+    #[error("Failed to control the core")]
+    Core(#[from] crate::Error),
+    /// Reading the RTT output failed.
+    #[error("Failed to read the RTT output")]
+    Rtt(#[from] crate::rtt::Error),
+}
+
+/// A way for the test binary to signal that it finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExitCondition {
+    /// The target calls the semihosting `SYS_EXIT` operation.
+    ///
+    /// The exit code is 0 if the reason is `ADP_Stopped_ApplicationExit`, and 1 otherwise.
+    Semihosting,
+    /// The target reaches the function or label `symbol`.
+    Breakpoint {
+        /// The name of the symbol in the test binary.
+        symbol: String,
+        /// The exit code reported for this condition.
+        exit_code: i32,
+    },
+    /// The captured RTT output contains `marker`.
+    RttMarker {
+        /// The text to look for.
+        marker: String,
+        /// The exit code reported for this condition.
+        exit_code: i32,
+    },
+}
+
+/// Options for [`run_test`].
+///
+/// By default, the test on core 0 has 60 seconds to exit through semihosting, and the output of
+/// RTT up channel 0 is captured.
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct TestRunnerOptions {
+    /// The index of the core which runs the test.
+    pub core_index: usize,
+    /// The time after which the test is considered to hang.
+    pub timeout: Duration,
+    /// The conditions which end the test, the first one which occurs is reported.
+    pub exit_conditions: Vec<ExitCondition>,
+    /// The RTT up channel whose output is captured.
+    pub rtt_channel: usize,
+    /// Options for flashing the test binary.
+    pub download: DownloadOptions,
+}
+
+impl Default for TestRunnerOptions {
+    fn default() -> Self {
+        Self {
+            core_index: 0,
+            timeout: Duration::from_secs(60),
+            exit_conditions: vec![ExitCondition::Semihosting],
+            rtt_channel: 0,
+            download: DownloadOptions::default(),
+        }
+    }
+}
+
+impl From<TestRunnerOptions> for RunOptions {
+    fn from(options: TestRunnerOptions) -> Self {
+        RunOptions {
+            core_index: options.core_index,
+            timeout: Some(options.timeout),
+            exit_conditions: options.exit_conditions,
+            rtt_channel: options.rtt_channel,
+            download: options.download,
+        }
+    }
+}
+
+/// How a test run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestExit {
+    /// The target called the semihosting `SYS_EXIT` operation.
+    Semihosting {
+        /// The exit code derived from the reason of the exit.
+        code: i32,
+        /// The reason code passed to `SYS_EXIT`.
+        reason: u32,
+    },
+    /// The target reached the symbol of an [`ExitCondition::Breakpoint`].
+    Breakpoint {
+        /// The name of the symbol.
+        symbol: String,
+        /// The exit code of the condition.
+        code: i32,
+    },
+    /// The RTT output contained the marker of an [`ExitCondition::RttMarker`].
+    RttMarker {
+        /// The marker which was found.
+        marker: String,
+        /// The exit code of the condition.
+        code: i32,
+    },
+    /// The core halted for another reason, for example because of a fault.
+    Halted {
+        /// The reason for the halt.
+        reason: HaltReason,
+        /// The program counter of the halted core.
+        pc: u64,
+    },
+    /// None of the exit conditions occurred before the timeout.
+    Timeout,
+}
+
+impl TestExit {
+    /// The exit code of the test, if it exited through one of the [`ExitCondition`]s.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            TestExit::Semihosting { code, .. }
+            | TestExit::Breakpoint { code, .. }
+            | TestExit::RttMarker { code, .. } => Some(*code),
+            TestExit::Halted { .. } | TestExit::Timeout => None,
+        }
+    }
+}
+
+/// The result of a test run.
+#[derive(Debug, Clone)]
+pub struct TestOutcome {
+    /// How the test ended.
+    pub exit: TestExit,
+    /// The semihosting console output and the RTT output, in the order it was received.
+    pub output: Vec<u8>,
+    /// The time from starting the core until the test ended.
+    pub duration: Duration,
+}
+
+impl TestOutcome {
+    /// Returns `true` if the test exited with exit code 0.
+    pub fn passed(&self) -> bool {
+        self.exit.exit_code() == Some(0)
+    }
+}
+
+/// Flash the ELF file at `path`, run it and wait until the test exits.
+///
+/// The core is reset before the test is started, and is left halted if the test ended with a
+/// halt, and running otherwise.
+pub fn run_test(
+    session: &mut Session,
+    path: impl AsRef<Path>,
+    options: TestRunnerOptions,
+) -> Result<TestOutcome, TestRunnerError> {
+    let mut output = Vec::new();
+
+    let outcome = crate::runner::run(session, path, options.into(), |received| {
+        let (RunOutput::Semihosting(data) | RunOutput::Rtt(data)) = received;
+        output.extend_from_slice(data);
+    })?;
+
+    Ok(TestOutcome {
+        exit: outcome.exit,
+        output,
+        duration: outcome.duration,
+    })
+}
+
+/// The addresses of the symbols used by a test run.
+pub(crate) struct TestSymbols {
+    /// The breakpoint addresses and the index of their exit condition.
+    pub(crate) breakpoints: Vec<(u64, usize)>,
+    rtt_control_block: Option<u64>,
+}
+
+impl TestSymbols {
+    pub(crate) fn from_elf(
+        elf: &[u8],
+        exit_conditions: &[ExitCondition],
+    ) -> Result<Self, TestRunnerError> {
+        let file = object::File::parse(elf)?;
+
+        let address_of = |name: &str| {
+            file.symbols()
+                .find(|symbol| symbol.is_definition() && symbol.name() == Ok(name))
+                .map(|symbol| symbol.address())
+        };
+
+        let mut breakpoints = Vec::new();
+        for (index, condition) in exit_conditions.iter().enumerate() {
+            if let ExitCondition::Breakpoint { symbol, .. } = condition {
+                let address = address_of(symbol)
+                    .ok_or_else(|| TestRunnerError::MissingSymbol(symbol.clone()))?;
+
+                // Clear the Thumb bit of function symbols.
+                breakpoints.push((address & !1, index));
+            }
+        }
+
+        Ok(Self {
+            breakpoints,
+            rtt_control_block: address_of(RTT_CONTROL_BLOCK_SYMBOL),
+        })
+    }
+}
+
+/// The state of a running test.
+pub(crate) struct TestRun<'a> {
+    options: &'a RunOptions,
+    symbols: &'a TestSymbols,
+    memory_map: &'a [MemoryRegion],
+    rtt_channel: Option<UpChannel>,
+    /// Receives the output as it arrives.
+    output: &'a mut dyn FnMut(RunOutput<'_>),
+    /// The end of the RTT output, where markers are searched.
+    rtt_output: Vec<u8>,
+}
+
+impl<'a> TestRun<'a> {
+    pub(crate) fn new(
+        options: &'a RunOptions,
+        symbols: &'a TestSymbols,
+        memory_map: &'a [MemoryRegion],
+        output: &'a mut dyn FnMut(RunOutput<'_>),
+    ) -> Self {
+        Self {
+            options,
+            symbols,
+            memory_map,
+            rtt_channel: None,
+            output,
+            rtt_output: Vec::new(),
+        }
+    }
+
+    /// Run the core until one of the exit conditions occurs, and return the exit and the time
+    /// the core ran.
+    pub(crate) fn run(mut self, core: &mut Core) -> Result<(TestExit, Duration), TestRunnerError> {
+        let start = Instant::now();
+        core.run()?;
+
+        let exit = loop {
+            if let Some(exit) = self.poll(core)? {
+                break exit;
+            }
+
+            if self
+                .options
+                .timeout
+                .is_some_and(|timeout| start.elapsed() > timeout)
+            {
+                break TestExit::Timeout;
+            }
+
+            crate::cancellation::check().map_err(crate::Error::from)?;
+            std::thread::sleep(POLL_INTERVAL);
+        };
+
+        // Collect any output which was written just before the test ended.
+        if !matches!(exit, TestExit::RttMarker { .. }) {
+            self.read_rtt(core)?;
+        }
+
+        Ok((exit, start.elapsed()))
+    }
+
+    /// Check the state of the core and the RTT output once.
+    fn poll(&mut self, core: &mut Core) -> Result<Option<TestExit>, TestRunnerError> {
+        if let CoreStatus::Halted(reason) = core.status()? {
+            return self.halted(core, reason);
+        }
+
+        self.read_rtt(core)?;
+
+        let exit = find_marker(&self.rtt_output, &self.options.exit_conditions);
+
+        // Only the end of the output can contain the start of a marker which is not complete
+        // yet, the rest does not have to be searched again.
+        let keep = longest_marker(&self.options.exit_conditions).saturating_sub(1);
+        let searched = self.rtt_output.len().saturating_sub(keep);
+        self.rtt_output.drain(..searched);
+
+        Ok(exit)
+    }
+
+    fn halted(
+        &mut self,
+        core: &mut Core,
+        reason: HaltReason,
+    ) -> Result<Option<TestExit>, TestRunnerError> {
+        let pc: u64 = core.read_core_reg(core.program_counter())?;
+
+        if let Some((_, index)) = self
+            .symbols
+            .breakpoints
+            .iter()
+            .find(|(address, _)| *address == pc)
+        {
+            if let ExitCondition::Breakpoint { symbol, exit_code } =
+                &self.options.exit_conditions[*index]
+            {
+                return Ok(Some(TestExit::Breakpoint {
+                    symbol: symbol.clone(),
+                    code: *exit_code,
+                }));
+            }
+        }
+
+        if let Some(command) = decode_semihosting_syscall(core)? {
+            return self.semihosting(core, command);
+        }
+
+        Ok(Some(TestExit::Halted { reason, pc }))
+    }
+
+    /// Handle a semihosting request, the core is resumed unless the test exited.
+    fn semihosting(
+        &mut self,
+        core: &mut Core,
+        command: SemihostingCommand,
+    ) -> Result<Option<TestExit>, TestRunnerError> {
+        let result = match command {
+            SemihostingCommand::Exit { reason }
+                if self
+                    .options
+                    .exit_conditions
+                    .contains(&ExitCondition::Semihosting) =>
+            {
+                return Ok(Some(TestExit::Semihosting {
+                    code: semihosting_exit_code(reason),
+                    reason,
+                }));
+            }
+            SemihostingCommand::WriteC(c) => {
+                (self.output)(RunOutput::Semihosting(&[c]));
+                0
+            }
+            SemihostingCommand::Write0(string) => {
+                (self.output)(RunOutput::Semihosting(&string));
+                0
+            }
+            // Only the console is available, which is opened as `:tt`.
+            SemihostingCommand::Open { path, .. } if path == ":tt" => 1,
+            SemihostingCommand::Write {
+                handle: 1,
+                data,
+                length,
+            } => {
+                (self.output)(RunOutput::Semihosting(&data));
+                // The part of a long write which was not decoded is written by the next request.
+                length - data.len() as u32
+            }
+            command => {
+                tracing::debug!("Unsupported semihosting request in test: {:?}", command);
+                SEMIHOSTING_ERROR
+            }
+        };
+
+        complete_semihosting_syscall(core, result)?;
+        core.run()?;
+
+        Ok(None)
+    }
+
+    /// Read the available RTT output, attaching to the control block once it is initialized.
+    fn read_rtt(&mut self, core: &mut Core) -> Result<(), TestRunnerError> {
+        if self.rtt_channel.is_none() {
+            self.rtt_channel = self.attach_rtt(core);
+        }
+
+        let Some(channel) = &self.rtt_channel else {
+            return Ok(());
+        };
+
+        let mut buffer = [0u8; 1024];
+        loop {
+            let count = channel.read(core, &mut buffer)?;
+            if count == 0 {
+                break;
+            }
+
+            (self.output)(RunOutput::Rtt(&buffer[..count]));
+            self.rtt_output.extend_from_slice(&buffer[..count]);
+        }
+
+        Ok(())
+    }
+
+    fn attach_rtt(&self, core: &mut Core) -> Option<UpChannel> {
+        let scan_region = match self.symbols.rtt_control_block {
+            Some(address) => ScanRegion::Exact(address as u32),
+            // Scanning the whole RAM is slow, only do it if the RTT output is needed.
+            None if self
+                .options
+                .exit_conditions
+                .iter()
+                .any(|condition| matches!(condition, ExitCondition::RttMarker { .. })) =>
+            {
+                ScanRegion::Ram
+            }
+            None => return None,
+        };
+
+        match Rtt::attach_region(core, self.memory_map, &scan_region) {
+            Ok(mut rtt) => rtt.up_channels().take(self.options.rtt_channel),
+            Err(e) => {
+                // The control block is initialized by the target, try again later.
+                tracing::debug!("RTT is not available yet: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Returns the exit of the first [`ExitCondition::RttMarker`] whose marker is in `output`.
+fn find_marker(output: &[u8], exit_conditions: &[ExitCondition]) -> Option<TestExit> {
+    exit_conditions
+        .iter()
+        .find_map(|condition| match condition {
+            ExitCondition::RttMarker { marker, exit_code }
+                if !marker.is_empty()
+                    && output
+                        .windows(marker.len())
+                        .any(|window| window == marker.as_bytes()) =>
+            {
+                Some(TestExit::RttMarker {
+                    marker: marker.clone(),
+                    code: *exit_code,
+                })
+            }
+            _ => None,
+        })
+}
+
+/// The length of the longest marker of the [`ExitCondition::RttMarker`]s.
+fn longest_marker(exit_conditions: &[ExitCondition]) -> usize {
+    exit_conditions
+        .iter()
+        .filter_map(|condition| match condition {
+            ExitCondition::RttMarker { marker, .. } => Some(marker.len()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn semihosting_exit_code(reason: u32) -> i32 {
+    if reason == ADP_STOPPED_APPLICATION_EXIT {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_marker, longest_marker, semihosting_exit_code, ExitCondition, TestExit};
+    use crate::semihosting::ADP_STOPPED_APPLICATION_EXIT;
+
+    #[test]
+    fn first_matching_marker_is_reported() {
+        let conditions = [
+            ExitCondition::Semihosting,
+            ExitCondition::RttMarker {
+                marker: "TESTS FAILED".to_owned(),
+                exit_code: 1,
+            },
+            ExitCondition::RttMarker {
+                marker: "TESTS PASSED".to_owned(),
+                exit_code: 0,
+            },
+        ];
+
+        assert_eq!(find_marker(b"running 3 tests\n", &conditions), None);
+        assert_eq!(
+            find_marker(b"running 3 tests\nTESTS PASSED\n", &conditions),
+            Some(TestExit::RttMarker {
+                marker: "TESTS PASSED".to_owned(),
+                code: 0
+            })
+        );
+
+        // A marker can start in the last 11 bytes of the output searched before.
+        assert_eq!(longest_marker(&conditions), 12);
+        assert_eq!(longest_marker(&[ExitCondition::Semihosting]), 0);
+    }
+
+    #[test]
+    fn semihosting_exit_reason() {
+        assert_eq!(semihosting_exit_code(ADP_STOPPED_APPLICATION_EXIT), 0);
+        // ADP_Stopped_RunTimeErrorUnknown
+        assert_eq!(semihosting_exit_code(0x20023), 1);
+    }
+}
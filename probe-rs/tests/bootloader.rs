@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use probe_rs::config::get_target_by_name;
+use probe_rs::flashing::{BootloaderError, BootloaderTransport, FlashError};
+
+/// A bootloader which programs a sparse memory.
+#[derive(Default)]
+struct MemoryBootloader {
+    memory: BTreeMap<u64, u8>,
+    erased: Vec<Range<u64>>,
+    /// Bits which are stuck at zero, to simulate a broken flash.
+    stuck_bits: u8,
+}
+
+impl BootloaderTransport for MemoryBootloader {
+    fn max_transfer_size(&self) -> usize {
+        256
+    }
+
+    fn erase(&mut self, ranges: &[Range<u64>]) -> Result<(), BootloaderError> {
+        self.erased.extend_from_slice(ranges);
+        Ok(())
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), BootloaderError> {
+        assert!(data.len() <= 256);
+        for (i, byte) in data.iter().enumerate() {
+            self.memory
+                .insert(address + i as u64, byte & !self.stuck_bits);
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), BootloaderError> {
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self
+                .memory
+                .get(&(address + i as u64))
+                .copied()
+                .unwrap_or(0xFF);
+        }
+        Ok(())
+    }
+
+    fn go(&mut self, _address: u64) -> Result<(), BootloaderError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn commit_through_bootloader() {
+    let target = get_target_by_name("stm32wb55ccux").unwrap();
+    let mut loader = target.flash_loader();
+
+    let data = (0..1000u32).map(|i| i as u8).collect::<Vec<_>>();
+    loader.add_data(0x0800_0000, &data).unwrap();
+    loader.add_data(0x2000_0000, &[1, 2, 3, 4]).unwrap();
+
+    let mut bootloader = MemoryBootloader::default();
+    loader.commit_bootloader(&mut bootloader, true).unwrap();
+
+    // Only the flash is erased.
+    assert_eq!(bootloader.erased.len(), 1);
+    assert_eq!(bootloader.erased[0], 0x0800_0000..0x0800_0000 + 1000);
+
+    let mut written = vec![0; data.len()];
+    bootloader.read(0x0800_0000, &mut written).unwrap();
+    assert_eq!(written, data);
+}
+
+#[test]
+fn bootloader_verify_failure() {
+    let target = get_target_by_name("stm32wb55ccux").unwrap();
+    let mut loader = target.flash_loader();
+    loader.add_data(0x0800_0000, &[0xFF; 16]).unwrap();
+
+    let mut bootloader = MemoryBootloader {
+        stuck_bits: 0x01,
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        loader.commit_bootloader(&mut bootloader, true),
+        Err(FlashError::Verify)
+    ));
+}
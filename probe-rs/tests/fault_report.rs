@@ -0,0 +1,18 @@
+use probe_rs::debug::FaultReport;
+use probe_rs::{FakeProbe, Permissions, Probe};
+
+#[test]
+fn report_is_serializable() {
+    let probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+    let mut session = probe
+        .attach("stm32wb55ccux", Permissions::default())
+        .expect("Failed to attach with 'fake' probe.");
+    let mut core = session.core(0).unwrap();
+
+    let report = FaultReport::collect(&mut core, None, 16);
+    assert_eq!(report.core_type, core.core_type());
+    assert!(report.backtrace.is_empty());
+
+    let json = serde_json::to_string(&report).unwrap();
+    assert_eq!(serde_json::from_str::<FaultReport>(&json).unwrap(), report);
+}
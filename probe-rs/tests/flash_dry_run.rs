@@ -1,4 +1,7 @@
-use probe_rs::{flashing::DownloadOptions, FakeProbe, Permissions, Probe};
+use probe_rs::{
+    flashing::{DownloadOptions, FlashError},
+    FakeProbe, Permissions, Probe,
+};
 
 /// A chip where the flash algorithm's range is greater than the NVM range.
 #[test]
@@ -47,3 +50,47 @@ fn flash_dry_run_mimxrt1010() {
         .commit(&mut session, flash_options)
         .expect("Failed to flash in dry run mode.");
 }
+
+#[test]
+fn flash_stream_dry_run() {
+    let probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+
+    let mut session = probe
+        .attach("stm32wb55ccux", Permissions::default())
+        .expect("Failed to attach with 'fake' probe.");
+
+    let flasher = session.target().flash_loader();
+
+    let mut flash_options = DownloadOptions::new();
+    flash_options.dry_run = true;
+
+    let image = vec![0xAA; 10_000];
+    let programmed = flasher
+        .commit_stream(
+            &mut session,
+            0x8000000,
+            &mut image.as_slice(),
+            4096,
+            flash_options.clone(),
+        )
+        .expect("Failed to flash the stream in dry run mode.");
+    assert_eq!(programmed, 10_000);
+
+    // The stream is longer than the flash left after the address.
+    let flash_end = session
+        .target()
+        .memory_map
+        .iter()
+        .find(|region| region.address_range().contains(&0x8000000))
+        .unwrap()
+        .address_range()
+        .end;
+    let result = flasher.commit_stream(
+        &mut session,
+        flash_end - 16,
+        &mut [0u8; 32].as_slice(),
+        4096,
+        flash_options,
+    );
+    assert!(matches!(result, Err(FlashError::NoSuitableNvm { .. })));
+}
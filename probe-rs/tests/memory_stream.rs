@@ -0,0 +1,38 @@
+use std::io::{Read, Write};
+
+use probe_rs::memory_stream::{MemoryReader, MemoryWriter};
+use probe_rs::{FakeProbe, MemoryInterface, Permissions, Probe};
+
+#[test]
+fn stream_memory_in_chunks() {
+    let probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+    let mut session = probe
+        .attach("stm32wb55ccux", Permissions::default())
+        .expect("Failed to attach with 'fake' probe.");
+    let mut core = session.core(0).unwrap();
+
+    let data = (0..5000u32).map(|i| i as u8).collect::<Vec<_>>();
+
+    let mut writer = MemoryWriter::new(&mut core, 0x102, 6000).with_chunk_size(1024);
+    writer.write_all(&data).unwrap();
+    assert_eq!(writer.address(), 0x102 + 5000);
+    writer.finish().unwrap();
+
+    let mut written = vec![0; data.len()];
+    core.read(0x102, &mut written).unwrap();
+    assert_eq!(written, data);
+
+    let mut read = Vec::new();
+    MemoryReader::new(&mut core, 0x102, 5000)
+        .with_chunk_size(1024)
+        .read_to_end(&mut read)
+        .unwrap();
+    assert_eq!(read, data);
+
+    // Writes past the end of the stream fail.
+    let mut writer = MemoryWriter::new(&mut core, 0x100, 4);
+    assert_eq!(
+        writer.write_all(&[0; 8]).unwrap_err().kind(),
+        std::io::ErrorKind::WriteZero
+    );
+}
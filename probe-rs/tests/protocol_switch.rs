@@ -0,0 +1,25 @@
+use probe_rs::{FakeProbe, Probe, WireProtocol};
+
+#[test]
+fn switch_protocol_while_attached() {
+    let mut probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+    probe
+        .select_protocol(WireProtocol::Jtag)
+        .expect("Failed to select JTAG");
+    probe
+        .attach_to_unspecified()
+        .expect("Failed to attach with 'fake' probe.");
+
+    // Selecting a protocol requires detaching first, switching does not.
+    assert!(probe.select_protocol(WireProtocol::Swd).is_err());
+
+    probe
+        .switch_protocol(WireProtocol::Swd)
+        .expect("Failed to switch to SWD");
+    assert_eq!(probe.protocol(), Some(WireProtocol::Swd));
+
+    probe
+        .switch_protocol(WireProtocol::Jtag)
+        .expect("Failed to switch to JTAG");
+    assert_eq!(probe.protocol(), Some(WireProtocol::Jtag));
+}
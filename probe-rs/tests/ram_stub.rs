@@ -0,0 +1,31 @@
+use probe_rs::ram_stub::RamStub;
+use probe_rs::{Error, FakeProbe, MemoryInterface, Permissions, Probe};
+
+#[test]
+fn load_ram_stub() {
+    let probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+    let mut session = probe
+        .attach("stm32wb55ccux", Permissions::default())
+        .expect("Failed to attach with 'fake' probe.");
+    let mut core = session.core(0).unwrap();
+
+    let stub = RamStub::load(&mut core, 0x1000).unwrap();
+    assert_eq!(stub.address(), 0x1000);
+
+    let mut code = vec![0; RamStub::SIZE];
+    core.read(0x1000, &mut code).unwrap();
+    // The stub starts with the breakpoint its routines return to.
+    assert_eq!(&code[..2], &[0x00, 0xBE]);
+
+    assert!(matches!(
+        RamStub::load(&mut core, 0x1002),
+        Err(Error::MemoryNotAligned {
+            address: 0x1002,
+            ..
+        })
+    ));
+    assert!(matches!(
+        stub.fill(&mut core, 0x2000, 6, 0),
+        Err(Error::MemoryNotAligned { address: 6, .. })
+    ));
+}
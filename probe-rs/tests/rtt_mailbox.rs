@@ -0,0 +1,49 @@
+use probe_rs::rtt::{Error, Rtt};
+use probe_rs::{FakeProbe, MemoryInterface, Permissions, Probe};
+
+const MAILBOX: u64 = 0x1000;
+const UP_BUFFER: u32 = 0x2000;
+const DOWN_BUFFER: u32 = 0x3000;
+
+/// An RTT channel in target memory: name, buffer, size, write, read and flags.
+fn channel(buffer: u32, size: u32, write: u32, read: u32) -> [u32; 6] {
+    [0, buffer, size, write, read, 0]
+}
+
+#[test]
+fn read_and_write_mailbox_channels() {
+    let probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+    let mut session = probe
+        .attach("stm32wb55ccux", Permissions::default())
+        .expect("Failed to attach with 'fake' probe.");
+    let memory_map = session.target().memory_map.clone();
+    let mut core = session.core(0).unwrap();
+
+    assert!(matches!(
+        Rtt::attach_mailbox(&mut core, &memory_map, MAILBOX as u32),
+        Err(Error::MailboxNotFound(0x1000))
+    ));
+
+    core.write_8(MAILBOX, b"PRSMBOX\0").unwrap();
+    core.write_32(MAILBOX + 8, &channel(UP_BUFFER, 16, 5, 0))
+        .unwrap();
+    core.write_32(MAILBOX + 32, &channel(DOWN_BUFFER, 16, 0, 0))
+        .unwrap();
+    core.write_8(UP_BUFFER.into(), b"hello").unwrap();
+
+    let mut rtt = Rtt::attach_mailbox(&mut core, &memory_map, MAILBOX as u32).unwrap();
+    assert_eq!(rtt.ptr(), MAILBOX as u32);
+
+    let up = rtt.up_channels().take(0).unwrap();
+    let mut buf = [0u8; 16];
+    assert_eq!(up.read(&mut core, &mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"hello");
+    // The read pointer of the up channel is updated.
+    assert_eq!(core.read_word_32(MAILBOX + 8 + 16).unwrap(), 5);
+
+    let down = rtt.down_channels().take(0).unwrap();
+    assert_eq!(down.write(&mut core, b"ok").unwrap(), 2);
+    let mut written = [0u8; 2];
+    core.read(DOWN_BUFFER.into(), &mut written).unwrap();
+    assert_eq!(&written, b"ok");
+}
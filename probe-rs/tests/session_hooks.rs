@@ -0,0 +1,76 @@
+use probe_rs::{flashing::DownloadOptions, FakeProbe, Permissions, Probe, SessionHooks};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[test]
+fn hooks_run_at_attach_and_flash() {
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let mut hooks = SessionHooks::new();
+    let pre_attach = calls.clone();
+    hooks.pre_attach(move |_probe| {
+        // Runs first.
+        assert_eq!(pre_attach.fetch_add(1, Ordering::SeqCst), 0);
+        Ok(())
+    });
+    let post_attach = calls.clone();
+    hooks.post_attach(move |session| {
+        assert_eq!(session.target().name, "STM32WB55CCUx");
+        assert_eq!(post_attach.fetch_add(1, Ordering::SeqCst), 1);
+        Ok(())
+    });
+    let pre_flash = calls.clone();
+    hooks.pre_flash(move |_session| {
+        assert_eq!(pre_flash.fetch_add(1, Ordering::SeqCst), 2);
+        Ok(())
+    });
+
+    let mut probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+    probe.set_session_hooks(hooks);
+
+    let mut session = probe
+        .attach("stm32wb55ccux", Permissions::default())
+        .expect("Failed to attach with 'fake' probe.");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let mut flasher = session.target().flash_loader();
+    flasher
+        .add_data(0x8000000, &[0x1, 0x2, 0x3, 0x4])
+        .expect("Failed to add flash");
+
+    let mut flash_options = DownloadOptions::new();
+    flash_options.dry_run = true;
+
+    flasher
+        .commit(&mut session, flash_options)
+        .expect("Failed to flash in dry run mode.");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn failing_hook_aborts_flashing() {
+    let probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+
+    let mut session = probe
+        .attach("stm32wb55ccux", Permissions::default())
+        .expect("Failed to attach with 'fake' probe.");
+    session
+        .hooks_mut()
+        .pre_flash(|_session| Err("external power is off".into()));
+
+    let mut flasher = session.target().flash_loader();
+    flasher
+        .add_data(0x8000000, &[0x1, 0x2, 0x3, 0x4])
+        .expect("Failed to add flash");
+
+    let mut flash_options = DownloadOptions::new();
+    flash_options.dry_run = true;
+
+    let error = flasher
+        .commit(&mut session, flash_options)
+        .expect_err("Flashing should be aborted by the hook.");
+    assert!(matches!(
+        error,
+        probe_rs::flashing::FlashError::Core(probe_rs::Error::Hook("pre-flash", _))
+    ));
+}
@@ -10,7 +10,7 @@ use std::{
     path::Path,
 };
 
-use crate::parser::extract_flash_algo;
+use probe_rs::config::pack::extract_flash_algo;
 
 /// Prepare a target config based on an ELF file containing a flash algorithm.
 pub fn cmd_elf(
@@ -84,6 +84,7 @@ pub fn cmd_elf(
                         psel: 0,
                         debug_base: None,
                         cti_base: None,
+                        pmu_base: None,
                     }),
                 }],
                 part: None,
@@ -93,18 +94,27 @@ pub fn cmd_elf(
                         is_boot_memory: false,
                         range: 0..0x2000,
                         cores: vec!["main".to_owned()],
+                        access: Default::default(),
+                        sectors: vec![],
+                        bank_swap: None,
                         name: None,
                     }),
                     MemoryRegion::Ram(RamRegion {
                         is_boot_memory: true,
                         range: 0x1_0000..0x2_0000,
                         cores: vec!["main".to_owned()],
+                        access: Default::default(),
                         name: None,
                     }),
                 ],
                 flash_algorithms: vec![algorithm_name],
+                access_ports: None,
+                boot_mode_pins: None,
             }],
             flash_algorithms: vec![algorithm],
+            image_checksums: vec![],
+            unique_id: vec![],
+            chip_detection: vec![],
             source: BuiltIn,
         };
 
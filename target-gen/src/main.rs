@@ -1,16 +1,13 @@
-pub mod algorithm_binary;
 pub mod commands;
 pub mod fetch;
-pub mod flash_device;
 pub mod generate;
-pub mod parser;
 
 use anyhow::{ensure, Context, Result};
 use clap::Parser;
-use probe_rs::config::ChipFamily;
+use probe_rs::config::{pack, ChipFamily};
 use std::{
     env::current_dir,
-    fs::create_dir,
+    fs::{create_dir, File},
     path::{Path, PathBuf},
 };
 use tracing_subscriber::EnvFilter;
@@ -168,11 +165,13 @@ fn cmd_pack(input: &Path, out_dir: &Path) -> Result<()> {
     let mut families = Vec::<ChipFamily>::new();
 
     if input.is_file() {
-        generate::visit_file(input, &mut families)
+        File::open(input)
+            .map_err(pack::PackError::from)
+            .and_then(|file| pack::add_families_from_archive(file, &mut families, |_| true))
             .context(format!("Failed to process file {}.", input.display()))?;
     } else {
         // Look for the .pdsc file in the given dir and it's child directories.
-        generate::visit_dirs(input, &mut families)
+        pack::add_families_from_directory(input, &mut families)
             .context("Failed to generate target configuration.")?;
 
         // Check that we found at least a single .pdsc file